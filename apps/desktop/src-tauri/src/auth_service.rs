@@ -4,6 +4,7 @@ use std::sync::Mutex;
 use base64::Engine;
 use openconv_crypto::{identity, master_key, prekeys, storage::CryptoStore};
 use openconv_shared::api::auth::*;
+use openconv_shared::error::{ErrorCode, ErrorMessage};
 use openconv_shared::ids::DeviceId;
 use reqwest::Client;
 use rusqlite::Connection;
@@ -15,12 +16,20 @@ use rusqlite::Connection;
 #[derive(Debug, serde::Serialize, specta::Type)]
 pub struct AppError {
     pub message: String,
+    /// The server's error code, when the error came from an API response.
+    /// Branch on this rather than `message`.
+    pub code: Option<ErrorCode>,
+    /// Key and parameters for showing `message` translated, when the server
+    /// sent them.
+    pub localized: Option<ErrorMessage>,
 }
 
 impl AppError {
     pub fn new(message: impl Into<String>) -> Self {
         Self {
             message: message.into(),
+            code: None,
+            localized: None,
         }
     }
 }
@@ -80,6 +89,9 @@ pub struct AuthResult {
     pub user_id: String,
     pub public_key: String,
     pub device_id: String,
+    /// Word code for this device, shown instead of the raw `device_id`
+    /// when linking or verifying devices.
+    pub device_code: String,
 }
 
 // ---------------------------------------------------------------------------
@@ -102,7 +114,7 @@ fn store_tokens(access_token: &str, refresh_token: &str) -> Result<(), AppError>
     Ok(())
 }
 
-fn get_access_token() -> Result<String, AppError> {
+pub(crate) fn get_access_token() -> Result<String, AppError> {
     Ok(keyring::Entry::new(KEYRING_SERVICE, "access_token")?.get_password()?)
 }
 
@@ -156,14 +168,21 @@ pub fn get_or_create_device_id(conn: &Connection) -> Result<(DeviceId, String),
 // ---------------------------------------------------------------------------
 
 /// Parse a user-safe error message from a non-success HTTP response.
-async fn error_from_response(resp: reqwest::Response, context: &str) -> AppError {
+pub(crate) async fn error_from_response(resp: reqwest::Response, context: &str) -> AppError {
     #[derive(serde::Deserialize)]
     struct ServerError {
         error: String,
+        // Kept loose so a code this build does not know still yields the message
+        code: Option<serde_json::Value>,
+        localized: Option<ErrorMessage>,
     }
     let status = resp.status();
     if let Ok(body) = resp.json::<ServerError>().await {
-        AppError::new(body.error)
+        AppError {
+            message: body.error,
+            code: body.code.and_then(|code| serde_json::from_value(code).ok()),
+            localized: body.localized,
+        }
     } else {
         AppError::new(format!("{context} (HTTP {status})"))
     }
@@ -187,6 +206,8 @@ impl AuthService {
             .map_err(|e| AppError::new(format!("failed to derive DB key: {e}")))?;
         master_key::apply_encryption_key(&conn, &db_key)
             .map_err(|e| AppError::new(format!("failed to apply encryption: {e}")))?;
+        crate::db::configure_durability(&conn)
+            .map_err(|e| AppError::new(format!("failed to configure crypto DB: {e}")))?;
 
         CryptoStore::new(&conn)
             .run_migrations()
@@ -315,6 +336,7 @@ impl AuthService {
             user_id: data.user_id.to_string(),
             public_key,
             device_id: data.device_id.to_string(),
+            device_code: data.device_id.pairing_code(),
         })
     }
 
@@ -381,6 +403,7 @@ impl AuthService {
             user_id: data.user_id.to_string(),
             public_key,
             device_id: data.device_id.to_string(),
+            device_code: data.device_id.pairing_code(),
         })
     }
 
@@ -501,6 +524,7 @@ impl AuthService {
             user_id: data.user_id.to_string(),
             public_key: new_public_key,
             device_id: data.device_id.to_string(),
+            device_code: data.device_id.pairing_code(),
         })
     }
 
@@ -608,6 +632,7 @@ mod tests {
             user_id: "u1".into(),
             public_key: "pk1".into(),
             device_id: "d1".into(),
+            device_code: "acid-acorn-actor-agent".into(),
         };
         let json = serde_json::to_string(&result).unwrap();
         let back: AuthResult = serde_json::from_str(&json).unwrap();
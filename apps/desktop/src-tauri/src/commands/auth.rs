@@ -1,6 +1,8 @@
 use tauri::State;
 
 use crate::auth_service::{get_or_create_device_id, AppError, AuthResult, AuthState};
+use crate::gateway_service::GatewayState;
+use crate::media_service::MediaState;
 use crate::DbState;
 
 #[tauri::command]
@@ -61,7 +63,13 @@ pub async fn auth_refresh(state: State<'_, AuthState>) -> Result<(), AppError> {
 
 #[tauri::command]
 #[specta::specta]
-pub async fn auth_logout(state: State<'_, AuthState>) -> Result<(), AppError> {
+pub async fn auth_logout(
+    state: State<'_, AuthState>,
+    media: State<'_, MediaState>,
+    gateway: State<'_, GatewayState>,
+) -> Result<(), AppError> {
+    gateway.gateway_service.disconnect()?;
+    media.media_service.clear_cache()?;
     state.auth_service.logout().await
 }
 
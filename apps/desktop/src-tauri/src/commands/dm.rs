@@ -0,0 +1,22 @@
+use tauri::State;
+
+use crate::auth_service::AppError;
+use crate::dm_service::{DmChannel, DmState};
+
+/// List the user's DM channels for the sidebar, with "Notes" pinned first.
+/// Pass `archived: true` for the "Archived" view.
+#[tauri::command]
+#[specta::specta]
+pub async fn dm_list_channels(
+    archived: Option<bool>,
+    state: State<'_, DmState>,
+) -> Result<Vec<DmChannel>, AppError> {
+    state.dm_service.list(archived.unwrap_or(false)).await
+}
+
+/// Open the user's personal notes channel ("message yourself").
+#[tauri::command]
+#[specta::specta]
+pub async fn dm_notes_channel(state: State<'_, DmState>) -> Result<DmChannel, AppError> {
+    state.dm_service.notes_channel().await
+}
@@ -0,0 +1,47 @@
+use openconv_shared::ids::ChannelId;
+use tauri::{AppHandle, State, WebviewWindow};
+
+use crate::auth_service::AppError;
+use crate::gateway_service::{GatewayState, GatewayStatus};
+
+fn parse_channel_id(channel_id: &str) -> Result<ChannelId, AppError> {
+    channel_id
+        .parse()
+        .map_err(|_| AppError::new("invalid channel id"))
+}
+
+/// Receive gateway events for a channel in the calling window. All windows
+/// share one gateway connection; the channel is subscribed upstream only
+/// once regardless of how many windows show it.
+#[tauri::command]
+#[specta::specta]
+pub fn gateway_subscribe(
+    channel_id: String,
+    app: AppHandle,
+    window: WebviewWindow,
+    state: State<'_, GatewayState>,
+) -> Result<(), AppError> {
+    let channel_id = parse_channel_id(&channel_id)?;
+    state
+        .gateway_service
+        .subscribe(&app, window.label(), channel_id)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn gateway_unsubscribe(
+    channel_id: String,
+    window: WebviewWindow,
+    state: State<'_, GatewayState>,
+) -> Result<(), AppError> {
+    let channel_id = parse_channel_id(&channel_id)?;
+    state
+        .gateway_service
+        .unsubscribe(window.label(), channel_id)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn gateway_status(state: State<'_, GatewayState>) -> Result<GatewayStatus, AppError> {
+    state.gateway_service.status()
+}
@@ -0,0 +1,61 @@
+use std::path::PathBuf;
+
+use openconv_shared::ids::FileId;
+use tauri::State;
+
+use crate::auth_service::AppError;
+use crate::media_service::{MediaCacheStats, MediaImage, MediaState};
+
+fn parse_file_id(file_id: &str) -> Result<FileId, AppError> {
+    file_id
+        .parse()
+        .map_err(|_| AppError::new("invalid file id"))
+}
+
+/// Open an image attachment in the viewer. The original is fetched and
+/// decrypted on first view and served from the in-memory cache afterwards.
+#[tauri::command]
+#[specta::specta]
+pub async fn media_view_image(
+    file_id: String,
+    file_key: String,
+    state: State<'_, MediaState>,
+) -> Result<MediaImage, AppError> {
+    let file_id = parse_file_id(&file_id)?;
+    state.media_service.view_image(file_id, &file_key).await
+}
+
+/// Export an image attachment. Metadata (EXIF location, camera details,
+/// comments) is stripped unless `keep_metadata` is true.
+#[tauri::command]
+#[specta::specta]
+pub async fn save_attachment_as(
+    file_id: String,
+    file_key: String,
+    path: String,
+    keep_metadata: Option<bool>,
+    state: State<'_, MediaState>,
+) -> Result<(), AppError> {
+    let file_id = parse_file_id(&file_id)?;
+    state
+        .media_service
+        .save_as(
+            file_id,
+            &file_key,
+            &PathBuf::from(path),
+            keep_metadata.unwrap_or(false),
+        )
+        .await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn media_cache_stats(state: State<'_, MediaState>) -> Result<MediaCacheStats, AppError> {
+    state.media_service.cache_stats()
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn media_clear_cache(state: State<'_, MediaState>) -> Result<(), AppError> {
+    state.media_service.clear_cache()
+}
@@ -1,2 +1,6 @@
 pub mod auth;
+pub mod dm;
+pub mod gateway;
 pub mod health;
+pub mod media;
+pub mod settings;
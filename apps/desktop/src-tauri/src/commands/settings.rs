@@ -0,0 +1,33 @@
+use tauri::State;
+
+use crate::auth_service::AppError;
+use crate::settings_service::{EmojiPreferences, EmojiSkinTone, SettingsState};
+
+/// Load the synced emoji picker preferences.
+#[tauri::command]
+#[specta::specta]
+pub async fn settings_emoji_preferences(
+    state: State<'_, SettingsState>,
+) -> Result<EmojiPreferences, AppError> {
+    state.settings_service.emoji_preferences().await
+}
+
+/// Record emoji sent or reacted with, updating the frequently-used ranking.
+#[tauri::command]
+#[specta::specta]
+pub async fn settings_record_emoji_use(
+    state: State<'_, SettingsState>,
+    shortcodes: Vec<String>,
+) -> Result<EmojiPreferences, AppError> {
+    state.settings_service.record_emoji_use(shortcodes).await
+}
+
+/// Change the default emoji skin tone on all of the user's devices.
+#[tauri::command]
+#[specta::specta]
+pub async fn settings_set_skin_tone(
+    state: State<'_, SettingsState>,
+    skin_tone: EmojiSkinTone,
+) -> Result<EmojiPreferences, AppError> {
+    state.settings_service.set_skin_tone(skin_tone).await
+}
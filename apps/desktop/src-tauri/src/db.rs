@@ -1,9 +1,32 @@
-use rusqlite::{Connection, Result};
+use std::path::{Path, PathBuf};
+
+use rusqlite::{Connection, ErrorCode, Result};
+
+/// Enable write-ahead logging and fsync every commit.
+///
+/// With `synchronous=FULL` a transaction that returned from COMMIT survives
+/// a process kill or power loss; an interrupted transaction is rolled back
+/// when the WAL is replayed on the next open. Frames with a bad checksum
+/// (a torn write at the WAL tail) are ignored by that replay.
+///
+/// Returns the journal mode actually in effect, which stays `memory` for
+/// in-memory databases.
+pub(crate) fn configure_durability(conn: &Connection) -> Result<String> {
+    let mode: String = conn.query_row("PRAGMA journal_mode=WAL", [], |row| row.get(0))?;
+    conn.execute_batch("PRAGMA synchronous=FULL;")?;
+    Ok(mode)
+}
 
 fn configure_connection(conn: &Connection) -> Result<()> {
+    let mode = configure_durability(conn)?;
+    if mode != "wal" && mode != "memory" {
+        tracing::warn!(
+            mode,
+            "SQLite WAL mode unavailable; writes are not crash-safe"
+        );
+    }
     conn.execute_batch(
-        "PRAGMA journal_mode=WAL;
-         PRAGMA foreign_keys=ON;
+        "PRAGMA foreign_keys=ON;
          PRAGMA busy_timeout=5000;",
     )
 }
@@ -109,13 +132,104 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
-pub fn init_db(path: &std::path::Path) -> Result<Connection> {
+/// Fail with `SQLITE_CORRUPT` unless `PRAGMA quick_check` passes.
+fn check_integrity(conn: &Connection) -> Result<()> {
+    let result: String = conn.query_row("PRAGMA quick_check", [], |row| row.get(0))?;
+    if result == "ok" {
+        Ok(())
+    } else {
+        Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CORRUPT),
+            Some(result),
+        ))
+    }
+}
+
+fn is_corruption(e: &rusqlite::Error) -> bool {
+    matches!(
+        e.sqlite_error_code(),
+        Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase)
+    )
+}
+
+/// Drop sync checkpoints that point at messages missing from the cache, so
+/// those channels re-sync instead of skipping past the lost writes.
+fn repair_sync_state(conn: &Connection) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM sync_state WHERE last_message_id IS NOT NULL \
+         AND last_message_id NOT IN (SELECT id FROM cached_messages)",
+        [],
+    )
+}
+
+fn open_checked(path: &Path) -> Result<Connection> {
     let conn = Connection::open(path)?;
     configure_connection(&conn)?;
+    check_integrity(&conn)?;
     run_migrations(&conn)?;
+    let reset = repair_sync_state(&conn)?;
+    if reset > 0 {
+        tracing::warn!(
+            channels = reset,
+            "reset sync state pointing at missing messages"
+        );
+    }
     Ok(conn)
 }
 
+/// Move a corrupt database and its WAL/SHM files aside as
+/// `<name>.corrupt-<unix time>*`. Returns the new database path.
+fn quarantine(path: &Path) -> std::io::Result<PathBuf> {
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let mut quarantined = None;
+    for suffix in ["", "-wal", "-shm"] {
+        let mut from = path.as_os_str().to_owned();
+        from.push(suffix);
+        let from = PathBuf::from(from);
+        if !from.exists() {
+            continue;
+        }
+        let mut to = path.as_os_str().to_owned();
+        to.push(format!(".corrupt-{stamp}{suffix}"));
+        let to = PathBuf::from(to);
+        std::fs::rename(&from, &to)?;
+        if suffix.is_empty() {
+            quarantined = Some(to);
+        }
+    }
+    Ok(quarantined.unwrap_or_else(|| path.to_path_buf()))
+}
+
+/// Open the local cache database, recovering from crashes.
+///
+/// Interrupted transactions are rolled back by SQLite's WAL replay. If the
+/// file is still damaged (failed integrity check), it is moved aside and a
+/// fresh database is created: everything in it is a cache of server state
+/// and is re-synced.
+pub fn init_db(path: &Path) -> Result<Connection> {
+    match open_checked(path) {
+        Ok(conn) => Ok(conn),
+        Err(e) if is_corruption(&e) => {
+            let moved_to = quarantine(path).map_err(|io| {
+                rusqlite::Error::SqliteFailure(
+                    rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CANTOPEN),
+                    Some(format!("failed to move corrupt database aside: {io}")),
+                )
+            })?;
+            tracing::error!(
+                error = %e,
+                moved_to = %moved_to.display(),
+                "local database corrupt; rebuilding"
+            );
+            open_checked(path)
+        }
+        Err(e) => Err(e),
+    }
+}
+
 #[cfg(test)]
 pub fn init_db_in_memory() -> Result<Connection> {
     let conn = Connection::open_in_memory()?;
@@ -153,6 +267,143 @@ mod tests {
         assert_eq!(fk, 1, "foreign_keys should be enabled");
     }
 
+    // --- Crash recovery -------------------------------------------------
+
+    /// Set in a re-executed copy of the test binary that writes to the
+    /// database at this path and then dies without cleanup.
+    const CRASH_CHILD_ENV: &str = "OPENCONV_DB_CRASH_CHILD";
+
+    fn insert_message(conn: &Connection, id: &str) {
+        conn.execute(
+            "INSERT INTO cached_messages (id, channel_id, sender_id, content, created_at)
+             VALUES (?1, 'c1', 'u1', 'hello', '2024-01-01T00:00:00')",
+            [id],
+        )
+        .expect("should insert message");
+    }
+
+    fn message_ids(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT id FROM cached_messages ORDER BY id")
+            .expect("should prepare");
+        stmt.query_map([], |row| row.get(0))
+            .expect("should query")
+            .collect::<Result<Vec<String>>>()
+            .expect("should collect")
+    }
+
+    /// Run `test_name` again in a child process with `CRASH_CHILD_ENV` set,
+    /// simulating a process kill at the point the child aborts.
+    fn run_killed_child(test_name: &str, path: &Path) {
+        let status = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([test_name, "--exact", "--nocapture", "--test-threads=1"])
+            .env(CRASH_CHILD_ENV, path)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .expect("should spawn child test process");
+        assert!(!status.success(), "child process should have been killed");
+    }
+
+    #[test]
+    fn init_db_enables_wal_and_full_sync() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db(&dir.path().join("openconv.db")).expect("should open db");
+        let mode: String = conn
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+        let sync: i64 = conn
+            .query_row("PRAGMA synchronous", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(sync, 2, "synchronous should be FULL");
+    }
+
+    #[test]
+    fn kill_keeps_committed_writes_and_drops_open_transaction() {
+        if let Ok(path) = std::env::var(CRASH_CHILD_ENV) {
+            let conn = init_db(Path::new(&path)).unwrap();
+            insert_message(&conn, "m1");
+            let tx = conn.unchecked_transaction().unwrap();
+            insert_message(&tx, "m2");
+            std::process::abort();
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("openconv.db");
+        run_killed_child(
+            "db::tests::kill_keeps_committed_writes_and_drops_open_transaction",
+            &path,
+        );
+
+        let conn = init_db(&path).expect("should reopen after kill");
+        assert_eq!(message_ids(&conn), ["m1"]);
+        check_integrity(&conn).expect("database should be intact");
+    }
+
+    #[test]
+    fn torn_wal_tail_is_ignored_on_recovery() {
+        if let Ok(path) = std::env::var(CRASH_CHILD_ENV) {
+            let conn = init_db(Path::new(&path)).unwrap();
+            insert_message(&conn, "m1");
+            insert_message(&conn, "m2");
+            std::process::abort();
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("openconv.db");
+        run_killed_child("db::tests::torn_wal_tail_is_ignored_on_recovery", &path);
+
+        // Simulate a half-written frame at the end of the WAL
+        let wal = dir.path().join("openconv.db-wal");
+        assert!(wal.exists(), "killed process should leave its WAL behind");
+        let mut bytes = std::fs::read(&wal).unwrap();
+        bytes.extend([0xA5u8; 1500]);
+        std::fs::write(&wal, bytes).unwrap();
+
+        let conn = init_db(&path).expect("should reopen with torn WAL");
+        assert_eq!(message_ids(&conn), ["m1", "m2"]);
+    }
+
+    #[test]
+    fn corrupt_database_is_moved_aside_and_rebuilt() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("openconv.db");
+        std::fs::write(&path, vec![0x5A; 8192]).unwrap();
+
+        let conn = init_db(&path).expect("should rebuild corrupt db");
+        insert_message(&conn, "m1");
+        assert_eq!(message_ids(&conn), ["m1"]);
+
+        let quarantined = std::fs::read_dir(dir.path())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .any(|e| e.file_name().to_string_lossy().contains(".corrupt-"));
+        assert!(quarantined, "corrupt file should be kept for inspection");
+    }
+
+    #[test]
+    fn sync_state_pointing_at_lost_messages_is_reset() {
+        let conn = migrated_conn();
+        insert_message(&conn, "m1");
+        conn.execute_batch(
+            "INSERT INTO sync_state (channel_id, last_message_id) VALUES ('c1', 'm1');
+             INSERT INTO sync_state (channel_id, last_message_id) VALUES ('c2', 'm9');
+             INSERT INTO sync_state (channel_id, last_message_id) VALUES ('c3', NULL);",
+        )
+        .unwrap();
+
+        assert_eq!(repair_sync_state(&conn).unwrap(), 1);
+        let remaining: Vec<String> = conn
+            .prepare("SELECT channel_id FROM sync_state ORDER BY channel_id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(remaining, ["c1", "c3"]);
+    }
+
     fn migrated_conn() -> Connection {
         let conn = init_db_in_memory().expect("should create in-memory db");
         run_migrations(&conn).expect("should run migrations");
@@ -0,0 +1,147 @@
+use openconv_shared::api::dm_channel::{DmChannelResponse, NOTES_CHANNEL_NAME};
+use reqwest::Client;
+
+use crate::auth_service::{error_from_response, get_access_token, AppError};
+
+/// A DM channel as shown in the sidebar.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, specta::Type)]
+pub struct DmChannel {
+    pub id: String,
+    /// Display name: the group name, "Notes" for the notes channel, or
+    /// `None` for a 1:1 DM (the client shows the other member's name).
+    pub name: Option<String>,
+    pub is_group: bool,
+    pub is_notes: bool,
+    /// Hidden from the main list; shown under "Archived".
+    pub archived: bool,
+    pub members: Vec<String>,
+}
+
+impl From<DmChannelResponse> for DmChannel {
+    fn from(resp: DmChannelResponse) -> Self {
+        let name = if resp.is_notes {
+            Some(NOTES_CHANNEL_NAME.to_string())
+        } else {
+            resp.name
+        };
+        Self {
+            id: resp.id.to_string(),
+            name,
+            is_group: resp.is_group,
+            is_notes: resp.is_notes,
+            archived: resp.archived,
+            members: resp.members.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+/// Notes first, then the server's order (most recent first).
+fn sidebar_order(channels: Vec<DmChannelResponse>) -> Vec<DmChannel> {
+    let (notes, others): (Vec<_>, Vec<_>) = channels.into_iter().partition(|c| c.is_notes);
+    notes
+        .into_iter()
+        .chain(others)
+        .map(DmChannel::from)
+        .collect()
+}
+
+/// Managed state for DM channel commands.
+pub struct DmState {
+    pub dm_service: DmService,
+}
+
+pub struct DmService {
+    api_base_url: String,
+    http_client: Client,
+}
+
+impl DmService {
+    pub fn new(api_base_url: String) -> Result<Self, AppError> {
+        let http_client = Client::builder()
+            .timeout(std::time::Duration::from_secs(15))
+            .connect_timeout(std::time::Duration::from_secs(5))
+            .build()
+            .map_err(|e| AppError::new(format!("failed to create HTTP client: {e}")))?;
+        Ok(Self {
+            api_base_url,
+            http_client,
+        })
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        context: &str,
+    ) -> Result<T, AppError> {
+        let token = get_access_token()?;
+        let resp = self
+            .http_client
+            .get(format!("{}{path}", self.api_base_url))
+            .bearer_auth(&token)
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            return Err(error_from_response(resp, context).await);
+        }
+        Ok(resp.json::<T>().await?)
+    }
+
+    /// The user's DM channels, with their notes channel pinned first.
+    /// With `archived`, only the archived channels are listed.
+    pub async fn list(&self, archived: bool) -> Result<Vec<DmChannel>, AppError> {
+        let path = if archived {
+            "/api/dm-channels?archived=true"
+        } else {
+            "/api/dm-channels"
+        };
+        let channels: Vec<DmChannelResponse> = self.get(path, "failed to load DM channels").await?;
+        Ok(sidebar_order(channels))
+    }
+
+    /// The user's personal notes channel. The server creates it on first use.
+    pub async fn notes_channel(&self) -> Result<DmChannel, AppError> {
+        let channel: DmChannelResponse = self
+            .get("/api/dm-channels/notes", "failed to load notes channel")
+            .await?;
+        Ok(channel.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openconv_shared::ids::{DmChannelId, UserId};
+
+    fn channel(is_notes: bool, name: Option<&str>) -> DmChannelResponse {
+        DmChannelResponse {
+            id: DmChannelId::new(),
+            name: name.map(String::from),
+            creator_id: Some(UserId::new()),
+            is_group: false,
+            is_notes,
+            archived: false,
+            members: vec![UserId::new()],
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn notes_channel_is_named_notes() {
+        let dm = DmChannel::from(channel(true, None));
+        assert_eq!(dm.name.as_deref(), Some("Notes"));
+        assert!(dm.is_notes);
+
+        let dm = DmChannel::from(channel(false, None));
+        assert_eq!(dm.name, None);
+    }
+
+    #[test]
+    fn notes_channel_is_listed_first() {
+        let first = channel(false, None);
+        let notes = channel(true, None);
+        let notes_id = notes.id.to_string();
+        let listed = sidebar_order(vec![first, notes, channel(false, Some("Team"))]);
+        assert_eq!(listed[0].id, notes_id);
+        assert_eq!(listed[2].name.as_deref(), Some("Team"));
+    }
+}
@@ -0,0 +1,638 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use openconv_shared::api::ws::{ClientMessage, SequenceTracker, ServerMessage};
+use openconv_shared::ids::ChannelId;
+use reqwest::Client;
+use tauri::{AppHandle, Emitter, EventTarget};
+use tokio::sync::{mpsc, watch};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::auth_service::{error_from_response, get_access_token, AppError};
+
+/// Event name under which gateway messages are delivered to windows.
+pub const GATEWAY_EVENT: &str = "gateway-event";
+
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+// ---------------------------------------------------------------------------
+// Subscription router
+// ---------------------------------------------------------------------------
+
+/// Which windows a gateway message should be delivered to.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Delivery {
+    /// Channel-scoped message: only windows subscribed to the channel.
+    Windows(Vec<String>),
+    /// Account-wide message (ready, presence, members, errors): every window.
+    All,
+}
+
+/// Reference-counted channel subscriptions per window.
+///
+/// The gateway only knows about one subscription per channel; this tracks
+/// which windows want it so the upstream `Subscribe` is sent when the first
+/// window subscribes and `Unsubscribe` when the last one goes away.
+#[derive(Default)]
+pub struct SubscriptionRouter {
+    /// Window label -> channel -> number of subscriptions from that window.
+    windows: HashMap<String, HashMap<ChannelId, u32>>,
+    /// Channel -> total subscriptions across all windows.
+    channels: HashMap<ChannelId, u32>,
+}
+
+impl SubscriptionRouter {
+    /// Returns true if this is the first subscription to the channel, i.e. the
+    /// gateway must be told to subscribe.
+    pub fn subscribe(&mut self, window: &str, channel_id: ChannelId) -> bool {
+        *self
+            .windows
+            .entry(window.to_string())
+            .or_default()
+            .entry(channel_id)
+            .or_default() += 1;
+        let total = self.channels.entry(channel_id).or_default();
+        *total += 1;
+        *total == 1
+    }
+
+    /// Returns true if this dropped the last subscription to the channel.
+    /// Unsubscribing a channel the window never subscribed to is a no-op.
+    pub fn unsubscribe(&mut self, window: &str, channel_id: ChannelId) -> bool {
+        let Some(window_channels) = self.windows.get_mut(window) else {
+            return false;
+        };
+        let Some(count) = window_channels.get_mut(&channel_id) else {
+            return false;
+        };
+        *count -= 1;
+        if *count == 0 {
+            window_channels.remove(&channel_id);
+            if window_channels.is_empty() {
+                self.windows.remove(window);
+            }
+        }
+        self.release(channel_id, 1)
+    }
+
+    /// Drop every subscription held by a closed window. Returns the channels
+    /// that no longer have any subscriber.
+    pub fn remove_window(&mut self, window: &str) -> Vec<ChannelId> {
+        let Some(window_channels) = self.windows.remove(window) else {
+            return Vec::new();
+        };
+        window_channels
+            .into_iter()
+            .filter(|&(channel_id, count)| self.release(channel_id, count))
+            .map(|(channel_id, _)| channel_id)
+            .collect()
+    }
+
+    /// All channels with at least one subscriber, for resubscribing after a
+    /// reconnect.
+    pub fn channels(&self) -> Vec<ChannelId> {
+        self.channels.keys().copied().collect()
+    }
+
+    pub fn delivery(&self, message: &ServerMessage) -> Delivery {
+        let channel_id = match message {
+            ServerMessage::MessageCreated { channel_id, .. }
+            | ServerMessage::MessageUpdated { channel_id, .. }
+            | ServerMessage::MessageDeleted { channel_id, .. }
+            | ServerMessage::TypingStarted { channel_id, .. }
+            | ServerMessage::ReplayComplete { channel_id } => channel_id,
+            _ => return Delivery::All,
+        };
+        let windows: BTreeSet<&String> = self
+            .windows
+            .iter()
+            .filter(|(_, channels)| channels.contains_key(channel_id))
+            .map(|(label, _)| label)
+            .collect();
+        Delivery::Windows(windows.into_iter().cloned().collect())
+    }
+
+    fn release(&mut self, channel_id: ChannelId, count: u32) -> bool {
+        let Some(total) = self.channels.get_mut(&channel_id) else {
+            return false;
+        };
+        *total = total.saturating_sub(count);
+        if *total == 0 {
+            self.channels.remove(&channel_id);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Journal sequencing
+// ---------------------------------------------------------------------------
+
+/// What to do with a message after checking its journal sequence number.
+#[derive(Debug)]
+enum Sequencing {
+    Dispatch(ServerMessage),
+    Drop,
+    /// Replay finished with events still missing: reconnect to resume again.
+    Resume,
+}
+
+/// Unwrap journaled events, dropping duplicates. The tracker persists
+/// across reconnects so the next session resumes where this one stopped.
+fn sequence(tracker: &mut Option<SequenceTracker>, message: ServerMessage) -> Sequencing {
+    match message {
+        ServerMessage::Ready { seq, .. } => {
+            // A lower number means the server's counter restarted
+            if tracker.as_ref().is_none_or(|t| seq < t.resume_from()) {
+                *tracker = Some(SequenceTracker::starting_at(seq));
+            }
+            Sequencing::Dispatch(message)
+        }
+        ServerMessage::Sequenced { seq, event } => {
+            let new = tracker.get_or_insert_with(Default::default).observe(seq);
+            match event {
+                Some(event) if new => Sequencing::Dispatch(*event),
+                _ => Sequencing::Drop,
+            }
+        }
+        ServerMessage::ResumeComplete { seq, truncated } => {
+            let tracker = tracker.get_or_insert_with(Default::default);
+            if truncated {
+                tracker.skip_to(seq);
+            }
+            // Gaps past `seq` can still be filled by live events
+            if tracker.gap().is_some_and(|gap| *gap.start() <= seq) {
+                Sequencing::Resume
+            } else if truncated {
+                // Windows refetch what the journal no longer has
+                Sequencing::Dispatch(message)
+            } else {
+                Sequencing::Drop
+            }
+        }
+        message => Sequencing::Dispatch(message),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// GatewayService
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, serde::Serialize, specta::Type)]
+pub struct GatewayStatus {
+    pub connected: bool,
+    pub subscribed_channels: u32,
+}
+
+/// Managed state for the gateway connection.
+pub struct GatewayState {
+    pub gateway_service: GatewayService,
+}
+
+/// Handle to the running connection task.
+struct Connection {
+    outbound: mpsc::UnboundedSender<ClientMessage>,
+    shutdown: watch::Sender<bool>,
+}
+
+/// Owns the single gateway WebSocket shared by all windows.
+///
+/// The first window to subscribe starts the connection; every other window
+/// reuses it. Incoming messages are routed to the windows that asked for
+/// them, and the connection reconnects with backoff until logout.
+pub struct GatewayService {
+    api_base_url: String,
+    http_client: Client,
+    router: Arc<Mutex<SubscriptionRouter>>,
+    connection: Mutex<Option<Connection>>,
+    connected: Arc<watch::Sender<bool>>,
+}
+
+impl GatewayService {
+    pub fn new(api_base_url: String) -> Result<Self, AppError> {
+        let http_client = Client::builder()
+            .connect_timeout(Duration::from_secs(5))
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(|e| AppError::new(format!("failed to create HTTP client: {e}")))?;
+        Ok(Self {
+            api_base_url,
+            http_client,
+            router: Arc::new(Mutex::new(SubscriptionRouter::default())),
+            connection: Mutex::new(None),
+            connected: Arc::new(watch::channel(false).0),
+        })
+    }
+
+    fn lock_router(&self) -> Result<std::sync::MutexGuard<'_, SubscriptionRouter>, AppError> {
+        lock(&self.router)
+    }
+
+    fn lock_connection(&self) -> Result<std::sync::MutexGuard<'_, Option<Connection>>, AppError> {
+        self.connection
+            .lock()
+            .map_err(|e| AppError::new(format!("gateway connection lock poisoned: {e}")))
+    }
+
+    /// Subscribe `window` to a channel, starting the shared connection if
+    /// this is the first subscription.
+    pub fn subscribe(
+        &self,
+        app: &AppHandle,
+        window: &str,
+        channel_id: ChannelId,
+    ) -> Result<(), AppError> {
+        let first = self.lock_router()?.subscribe(window, channel_id);
+        self.ensure_connected(app)?;
+        if first {
+            self.send(ClientMessage::Subscribe { channel_id })?;
+        }
+        Ok(())
+    }
+
+    pub fn unsubscribe(&self, window: &str, channel_id: ChannelId) -> Result<(), AppError> {
+        if self.lock_router()?.unsubscribe(window, channel_id) {
+            self.send(ClientMessage::Unsubscribe { channel_id })?;
+        }
+        Ok(())
+    }
+
+    /// Release all subscriptions of a window that was closed.
+    pub fn remove_window(&self, window: &str) -> Result<(), AppError> {
+        let released = self.lock_router()?.remove_window(window);
+        for channel_id in released {
+            self.send(ClientMessage::Unsubscribe { channel_id })?;
+        }
+        Ok(())
+    }
+
+    pub fn status(&self) -> Result<GatewayStatus, AppError> {
+        Ok(GatewayStatus {
+            connected: *self.connected.borrow(),
+            subscribed_channels: self.lock_router()?.channels().len() as u32,
+        })
+    }
+
+    /// Close the connection and forget all subscriptions, e.g. on logout.
+    pub fn disconnect(&self) -> Result<(), AppError> {
+        if let Some(connection) = self.lock_connection()?.take() {
+            let _ = connection.shutdown.send(true);
+        }
+        *self.lock_router()? = SubscriptionRouter::default();
+        Ok(())
+    }
+
+    /// Queue a message for the gateway. Dropped silently when no connection
+    /// exists; subscriptions are replayed from the router on connect.
+    fn send(&self, message: ClientMessage) -> Result<(), AppError> {
+        if let Some(connection) = self.lock_connection()?.as_ref() {
+            let _ = connection.outbound.send(message);
+        }
+        Ok(())
+    }
+
+    fn ensure_connected(&self, app: &AppHandle) -> Result<(), AppError> {
+        let mut connection = self.lock_connection()?;
+        if connection.is_some() {
+            return Ok(());
+        }
+
+        let (outbound_tx, outbound_rx) = mpsc::unbounded_channel();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let task = ConnectionTask {
+            app: app.clone(),
+            api_base_url: self.api_base_url.clone(),
+            http_client: self.http_client.clone(),
+            router: self.router.clone(),
+            connected: self.connected.clone(),
+        };
+        tauri::async_runtime::spawn(task.run(outbound_rx, shutdown_rx));
+        *connection = Some(Connection {
+            outbound: outbound_tx,
+            shutdown: shutdown_tx,
+        });
+        Ok(())
+    }
+}
+
+struct ConnectionTask {
+    app: AppHandle,
+    api_base_url: String,
+    http_client: Client,
+    router: Arc<Mutex<SubscriptionRouter>>,
+    connected: Arc<watch::Sender<bool>>,
+}
+
+impl ConnectionTask {
+    async fn run(
+        self,
+        mut outbound: mpsc::UnboundedReceiver<ClientMessage>,
+        mut shutdown: watch::Receiver<bool>,
+    ) {
+        let mut delay = RECONNECT_BASE_DELAY;
+        let mut tracker = None;
+        loop {
+            let wait = match self
+                .session(&mut outbound, &mut shutdown, &mut tracker)
+                .await
+            {
+                Ok(hint) => {
+                    delay = RECONNECT_BASE_DELAY;
+                    hint.unwrap_or(delay)
+                }
+                Err(e) => {
+                    tracing::warn!("gateway connection failed: {}", e.message);
+                    delay
+                }
+            };
+            self.connected.send_replace(false);
+            if *shutdown.borrow() {
+                return;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = shutdown.changed() => return,
+            }
+            delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+        }
+    }
+
+    /// Run one connection until it drops or shutdown is requested. Returns
+    /// the server's reconnect hint when it closed the connection to drain.
+    async fn session(
+        &self,
+        outbound: &mut mpsc::UnboundedReceiver<ClientMessage>,
+        shutdown: &mut watch::Receiver<bool>,
+        tracker: &mut Option<SequenceTracker>,
+    ) -> Result<Option<Duration>, AppError> {
+        let ticket = self.fetch_ticket().await?;
+        let mut url = format!("{}/ws?ticket={ticket}", ws_base_url(&self.api_base_url));
+        if let Some(tracker) = tracker {
+            url.push_str(&format!("&resume_from={}", tracker.resume_from()));
+        }
+        let (stream, _) = tokio_tungstenite::connect_async(url)
+            .await
+            .map_err(|e| AppError::new(format!("gateway connect failed: {e}")))?;
+        let (mut sink, mut stream) = stream.split();
+
+        // Anything queued while disconnected is superseded by the router state.
+        while outbound.try_recv().is_ok() {}
+        let channels = lock(&self.router)?.channels();
+        for channel_id in channels {
+            send_message(&mut sink, &ClientMessage::Subscribe { channel_id }).await?;
+        }
+        self.connected.send_replace(true);
+
+        loop {
+            tokio::select! {
+                _ = shutdown.changed() => {
+                    let _ = sink.send(Message::Close(None)).await;
+                    return Ok(None);
+                }
+                Some(message) = outbound.recv() => {
+                    send_message(&mut sink, &message).await?;
+                }
+                frame = stream.next() => {
+                    let text = match frame {
+                        Some(Ok(Message::Text(text))) => text,
+                        Some(Ok(Message::Close(_))) | None => return Ok(None),
+                        Some(Ok(_)) => continue,
+                        Some(Err(e)) => {
+                            return Err(AppError::new(format!("gateway read failed: {e}")));
+                        }
+                    };
+                    match serde_json::from_str::<ServerMessage>(&text) {
+                        Ok(ServerMessage::Reconnect { reconnect_after_ms }) => {
+                            return Ok(Some(Duration::from_millis(reconnect_after_ms)));
+                        }
+                        Ok(message) => match sequence(tracker, message) {
+                            Sequencing::Dispatch(message) => self.dispatch(&message)?,
+                            Sequencing::Drop => {}
+                            Sequencing::Resume => return Ok(None),
+                        },
+                        Err(e) => tracing::warn!("ignoring malformed gateway message: {e}"),
+                    }
+                }
+            }
+        }
+    }
+
+    async fn fetch_ticket(&self) -> Result<String, AppError> {
+        #[derive(serde::Deserialize)]
+        struct TicketResponse {
+            ticket: String,
+        }
+
+        let token = get_access_token()?;
+        let resp = self
+            .http_client
+            .post(format!("{}/api/ws/ticket", self.api_base_url))
+            .bearer_auth(&token)
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            return Err(error_from_response(resp, "gateway ticket request failed").await);
+        }
+        Ok(resp.json::<TicketResponse>().await?.ticket)
+    }
+
+    fn dispatch(&self, message: &ServerMessage) -> Result<(), AppError> {
+        let delivery = lock(&self.router)?.delivery(message);
+        let result = match delivery {
+            Delivery::All => self.app.emit(GATEWAY_EVENT, message),
+            Delivery::Windows(labels) => self.app.emit_filter(GATEWAY_EVENT, message, |target| {
+                matches!(target, EventTarget::WebviewWindow { label } if labels.contains(label))
+            }),
+        };
+        if let Err(e) = result {
+            tracing::warn!("failed to deliver gateway event: {e}");
+        }
+        Ok(())
+    }
+}
+
+fn lock(
+    router: &Mutex<SubscriptionRouter>,
+) -> Result<std::sync::MutexGuard<'_, SubscriptionRouter>, AppError> {
+    router
+        .lock()
+        .map_err(|e| AppError::new(format!("gateway router lock poisoned: {e}")))
+}
+
+async fn send_message<S>(sink: &mut S, message: &ClientMessage) -> Result<(), AppError>
+where
+    S: futures::Sink<Message, Error = tokio_tungstenite::tungstenite::Error> + Unpin,
+{
+    let json = serde_json::to_string(message)?;
+    sink.send(Message::Text(json.into()))
+        .await
+        .map_err(|e| AppError::new(format!("gateway write failed: {e}")))
+}
+
+/// Map the REST base URL to the gateway's WebSocket scheme.
+fn ws_base_url(api_base_url: &str) -> String {
+    let base = api_base_url.trim_end_matches('/');
+    if let Some(rest) = base.strip_prefix("https://") {
+        format!("wss://{rest}")
+    } else if let Some(rest) = base.strip_prefix("http://") {
+        format!("ws://{rest}")
+    } else {
+        base.to_string()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openconv_shared::ids::{MessageId, UserId};
+
+    fn created(channel_id: ChannelId) -> ServerMessage {
+        ServerMessage::MessageCreated {
+            channel_id,
+            message_id: MessageId::new(),
+        }
+    }
+
+    #[test]
+    fn upstream_subscribe_only_for_first_subscriber() {
+        let mut router = SubscriptionRouter::default();
+        let channel = ChannelId::new();
+        assert!(router.subscribe("main", channel));
+        assert!(!router.subscribe("chat-1", channel));
+        assert!(!router.subscribe("main", channel));
+        assert_eq!(router.channels(), vec![channel]);
+    }
+
+    #[test]
+    fn upstream_unsubscribe_only_when_last_reference_drops() {
+        let mut router = SubscriptionRouter::default();
+        let channel = ChannelId::new();
+        router.subscribe("main", channel);
+        router.subscribe("main", channel);
+        router.subscribe("chat-1", channel);
+
+        assert!(!router.unsubscribe("main", channel));
+        assert!(!router.unsubscribe("chat-1", channel));
+        assert!(router.unsubscribe("main", channel));
+        assert!(router.channels().is_empty());
+        // Extra unsubscribes are ignored
+        assert!(!router.unsubscribe("main", channel));
+    }
+
+    #[test]
+    fn closing_window_releases_only_its_exclusive_channels() {
+        let mut router = SubscriptionRouter::default();
+        let (shared, popped) = (ChannelId::new(), ChannelId::new());
+        router.subscribe("main", shared);
+        router.subscribe("chat-1", shared);
+        router.subscribe("chat-1", popped);
+        router.subscribe("chat-1", popped);
+
+        assert_eq!(router.remove_window("chat-1"), vec![popped]);
+        assert_eq!(router.channels(), vec![shared]);
+        assert!(router.remove_window("chat-1").is_empty());
+    }
+
+    #[test]
+    fn channel_events_go_to_subscribed_windows_only() {
+        let mut router = SubscriptionRouter::default();
+        let (a, b) = (ChannelId::new(), ChannelId::new());
+        router.subscribe("main", a);
+        router.subscribe("chat-1", a);
+        router.subscribe("chat-2", b);
+
+        assert_eq!(
+            router.delivery(&created(a)),
+            Delivery::Windows(vec!["chat-1".into(), "main".into()])
+        );
+        assert_eq!(
+            router.delivery(&created(b)),
+            Delivery::Windows(vec!["chat-2".into()])
+        );
+        assert_eq!(
+            router.delivery(&created(ChannelId::new())),
+            Delivery::Windows(vec![])
+        );
+    }
+
+    #[test]
+    fn account_events_go_to_all_windows() {
+        let router = SubscriptionRouter::default();
+        let ready = ServerMessage::Ready {
+            user_id: UserId::new(),
+            guild_ids: vec![],
+            archived_dm_channel_ids: vec![],
+            seq: 0,
+        };
+        assert_eq!(router.delivery(&ready), Delivery::All);
+        assert_eq!(
+            router.delivery(&ServerMessage::Pong { ts: 1 }),
+            Delivery::All
+        );
+    }
+
+    #[test]
+    fn journaled_events_are_unwrapped_once() {
+        let mut tracker = None;
+        let ready = ServerMessage::Ready {
+            user_id: UserId::new(),
+            guild_ids: vec![],
+            archived_dm_channel_ids: vec![],
+            seq: 4,
+        };
+        assert!(matches!(
+            sequence(&mut tracker, ready),
+            Sequencing::Dispatch(ServerMessage::Ready { .. })
+        ));
+        let journaled = |seq| ServerMessage::Sequenced {
+            seq,
+            event: Some(Box::new(created(ChannelId::new()))),
+        };
+        assert!(matches!(
+            sequence(&mut tracker, journaled(5)),
+            Sequencing::Dispatch(ServerMessage::MessageCreated { .. })
+        ));
+        assert!(matches!(
+            sequence(&mut tracker, journaled(5)),
+            Sequencing::Drop
+        ));
+        assert_eq!(tracker.as_ref().unwrap().resume_from(), 5);
+    }
+
+    #[test]
+    fn resume_with_missing_events_reconnects_unless_truncated() {
+        let mut tracker = Some(SequenceTracker::starting_at(2));
+        sequence(
+            &mut tracker,
+            ServerMessage::Sequenced {
+                seq: 6,
+                event: None,
+            },
+        );
+        let complete = |truncated| ServerMessage::ResumeComplete { seq: 5, truncated };
+        assert!(matches!(
+            sequence(&mut tracker, complete(false)),
+            Sequencing::Resume
+        ));
+        assert!(matches!(
+            sequence(&mut tracker, complete(true)),
+            Sequencing::Dispatch(ServerMessage::ResumeComplete { .. })
+        ));
+        assert_eq!(tracker.unwrap().resume_from(), 6);
+    }
+
+    #[test]
+    fn ws_base_url_maps_scheme() {
+        assert_eq!(ws_base_url("http://localhost:3000"), "ws://localhost:3000");
+        assert_eq!(ws_base_url("https://chat.example/"), "wss://chat.example");
+    }
+}
@@ -0,0 +1,416 @@
+//! Container-level image inspection and metadata stripping.
+//!
+//! Works directly on the JPEG, PNG and WebP container formats, so images are
+//! never decoded or re-encoded: stripping metadata leaves the pixel data
+//! byte-for-byte identical. Colour profiles are kept.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, specta::Type)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Gif,
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Png => "image/png",
+            Self::Gif => "image/gif",
+            Self::Webp => "image/webp",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct MalformedImage;
+
+impl std::fmt::Display for MalformedImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("malformed image")
+    }
+}
+
+impl std::error::Error for MalformedImage {}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Identify the image format from its magic bytes.
+pub fn detect_format(bytes: &[u8]) -> Option<ImageFormat> {
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some(ImageFormat::Jpeg)
+    } else if bytes.starts_with(PNG_SIGNATURE) {
+        Some(ImageFormat::Png)
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some(ImageFormat::Gif)
+    } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some(ImageFormat::Webp)
+    } else {
+        None
+    }
+}
+
+/// Read the pixel dimensions from the image header, if present.
+pub fn dimensions(format: ImageFormat, bytes: &[u8]) -> Option<(u32, u32)> {
+    match format {
+        ImageFormat::Jpeg => jpeg_dimensions(bytes),
+        ImageFormat::Png => {
+            // IHDR is always the first chunk: width and height follow its type.
+            let ihdr = bytes.get(8..24)?;
+            (&ihdr[4..8] == b"IHDR").then(|| (be_u32(&ihdr[8..12]), be_u32(&ihdr[12..16])))
+        }
+        ImageFormat::Gif => {
+            let screen = bytes.get(6..10)?;
+            Some((
+                u16::from_le_bytes([screen[0], screen[1]]) as u32,
+                u16::from_le_bytes([screen[2], screen[3]]) as u32,
+            ))
+        }
+        ImageFormat::Webp => webp_dimensions(bytes),
+    }
+}
+
+/// Remove EXIF, XMP and textual metadata. GIFs carry no EXIF and are
+/// returned unchanged.
+pub fn strip_metadata(format: ImageFormat, bytes: &[u8]) -> Result<Vec<u8>, MalformedImage> {
+    match format {
+        ImageFormat::Jpeg => strip_jpeg(bytes),
+        ImageFormat::Png => strip_png(bytes),
+        ImageFormat::Gif => Ok(bytes.to_vec()),
+        ImageFormat::Webp => strip_webp(bytes),
+    }
+}
+
+fn be_u16(b: &[u8]) -> u16 {
+    u16::from_be_bytes([b[0], b[1]])
+}
+
+fn be_u32(b: &[u8]) -> u32 {
+    u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+}
+
+fn le_u32(b: &[u8]) -> u32 {
+    u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+}
+
+// ---------------------------------------------------------------------------
+// JPEG
+// ---------------------------------------------------------------------------
+
+const JPEG_APP1: u8 = 0xE1; // EXIF and XMP
+const JPEG_APP13: u8 = 0xED; // Photoshop / IPTC
+const JPEG_COM: u8 = 0xFE;
+const JPEG_SOS: u8 = 0xDA;
+
+/// A marker segment before the scan data: `(marker, start, end)` where
+/// `start..end` covers the whole segment including the marker bytes.
+fn jpeg_segments(bytes: &[u8]) -> Result<(Vec<(u8, usize, usize)>, usize), MalformedImage> {
+    let mut segments = Vec::new();
+    let mut pos = 2; // after SOI
+    loop {
+        // Markers may be preceded by any number of 0xFF fill bytes.
+        if bytes.get(pos) != Some(&0xFF) {
+            return Err(MalformedImage);
+        }
+        let start = pos;
+        while bytes.get(pos) == Some(&0xFF) {
+            pos += 1;
+        }
+        let marker = *bytes.get(pos).ok_or(MalformedImage)?;
+        pos += 1;
+        if marker == JPEG_SOS {
+            return Ok((segments, start));
+        }
+        let len = bytes.get(pos..pos + 2).map(be_u16).ok_or(MalformedImage)? as usize;
+        if len < 2 || pos + len > bytes.len() {
+            return Err(MalformedImage);
+        }
+        pos += len;
+        segments.push((marker, start, pos));
+    }
+}
+
+fn strip_jpeg(bytes: &[u8]) -> Result<Vec<u8>, MalformedImage> {
+    let (segments, scan_start) = jpeg_segments(bytes)?;
+    let mut out = Vec::with_capacity(bytes.len());
+    out.extend_from_slice(&bytes[..2]);
+    for (marker, start, end) in segments {
+        if !matches!(marker, JPEG_APP1 | JPEG_APP13 | JPEG_COM) {
+            out.extend_from_slice(&bytes[start..end]);
+        }
+    }
+    out.extend_from_slice(&bytes[scan_start..]);
+    Ok(out)
+}
+
+fn jpeg_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let (segments, _) = jpeg_segments(bytes).ok()?;
+    // SOF0..SOF15, excluding DHT (C4), JPG (C8) and DAC (CC).
+    segments
+        .into_iter()
+        .find(|&(m, _, _)| (0xC0..=0xCF).contains(&m) && !matches!(m, 0xC4 | 0xC8 | 0xCC))
+        .and_then(|(m, start, end)| {
+            let seg = &bytes[start..end];
+            let body = &seg[seg.iter().position(|&b| b == m)? + 3..];
+            // precision (1), height (2), width (2)
+            let dims = body.get(1..5)?;
+            Some((be_u16(&dims[2..4]) as u32, be_u16(&dims[0..2]) as u32))
+        })
+}
+
+// ---------------------------------------------------------------------------
+// PNG
+// ---------------------------------------------------------------------------
+
+const PNG_METADATA_CHUNKS: &[&[u8; 4]] = &[b"eXIf", b"tEXt", b"zTXt", b"iTXt", b"tIME"];
+
+fn strip_png(bytes: &[u8]) -> Result<Vec<u8>, MalformedImage> {
+    let mut out = Vec::with_capacity(bytes.len());
+    out.extend_from_slice(PNG_SIGNATURE);
+    let mut pos = PNG_SIGNATURE.len();
+    while pos < bytes.len() {
+        let header = bytes.get(pos..pos + 8).ok_or(MalformedImage)?;
+        let len = be_u32(&header[0..4]) as usize;
+        // length (4) + type (4) + data + CRC (4)
+        let end = pos
+            .checked_add(12)
+            .and_then(|n| n.checked_add(len))
+            .filter(|&end| end <= bytes.len())
+            .ok_or(MalformedImage)?;
+        if !PNG_METADATA_CHUNKS.iter().any(|t| &header[4..8] == *t) {
+            out.extend_from_slice(&bytes[pos..end]);
+        }
+        pos = end;
+        if &header[4..8] == b"IEND" {
+            break;
+        }
+    }
+    Ok(out)
+}
+
+// ---------------------------------------------------------------------------
+// WebP
+// ---------------------------------------------------------------------------
+
+const VP8X_FLAG_EXIF: u8 = 0x08;
+const VP8X_FLAG_XMP: u8 = 0x04;
+
+/// RIFF chunks after the `WEBP` tag: `(fourcc, start, end)` with padding.
+fn webp_chunks(bytes: &[u8]) -> Result<Vec<([u8; 4], usize, usize)>, MalformedImage> {
+    if bytes.len() < 12 {
+        return Err(MalformedImage);
+    }
+    let mut chunks = Vec::new();
+    let mut pos = 12;
+    while pos < bytes.len() {
+        let header = bytes.get(pos..pos + 8).ok_or(MalformedImage)?;
+        let len = le_u32(&header[4..8]) as usize;
+        let end = pos
+            .checked_add(8)
+            .and_then(|n| n.checked_add(len + (len & 1)))
+            .ok_or(MalformedImage)?
+            .min(bytes.len());
+        if pos + 8 + len > bytes.len() {
+            return Err(MalformedImage);
+        }
+        chunks.push(([header[0], header[1], header[2], header[3]], pos, end));
+        pos = end;
+    }
+    Ok(chunks)
+}
+
+fn strip_webp(bytes: &[u8]) -> Result<Vec<u8>, MalformedImage> {
+    let chunks = webp_chunks(bytes)?;
+    let mut out = Vec::with_capacity(bytes.len());
+    out.extend_from_slice(&bytes[..12]);
+    for (fourcc, start, end) in chunks {
+        match &fourcc {
+            b"EXIF" | b"XMP " => {}
+            b"VP8X" => {
+                let flags_at = out.len() + 8;
+                out.extend_from_slice(&bytes[start..end]);
+                if let Some(flags) = out.get_mut(flags_at) {
+                    *flags &= !(VP8X_FLAG_EXIF | VP8X_FLAG_XMP);
+                }
+            }
+            _ => out.extend_from_slice(&bytes[start..end]),
+        }
+    }
+    let riff_size = (out.len() - 8) as u32;
+    out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Ok(out)
+}
+
+fn webp_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let (fourcc, start, end) = *webp_chunks(bytes).ok()?.first()?;
+    let data = &bytes[start + 8..end];
+    match &fourcc {
+        b"VP8X" => {
+            let d = data.get(4..10)?;
+            let w = u32::from_le_bytes([d[0], d[1], d[2], 0]) + 1;
+            let h = u32::from_le_bytes([d[3], d[4], d[5], 0]) + 1;
+            Some((w, h))
+        }
+        b"VP8 " => {
+            let d = data.get(6..10)?;
+            Some((
+                (u16::from_le_bytes([d[0], d[1]]) & 0x3FFF) as u32,
+                (u16::from_le_bytes([d[2], d[3]]) & 0x3FFF) as u32,
+            ))
+        }
+        b"VP8L" => {
+            let bits = le_u32(data.get(1..5)?);
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jpeg_segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+        let mut seg = vec![0xFF, marker];
+        seg.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+        seg.extend_from_slice(payload);
+        seg
+    }
+
+    fn test_jpeg() -> Vec<u8> {
+        let mut jpeg = vec![0xFF, 0xD8];
+        jpeg.extend(jpeg_segment(0xE0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0"));
+        jpeg.extend(jpeg_segment(JPEG_APP1, b"Exif\0\0GPS-LAT-LONG"));
+        jpeg.extend(jpeg_segment(0xE2, b"ICC_PROFILE\0"));
+        jpeg.extend(jpeg_segment(JPEG_COM, b"taken at home"));
+        // SOF0: precision 8, height 480, width 640, 1 component
+        jpeg.extend(jpeg_segment(
+            0xC0,
+            &[8, 0x01, 0xE0, 0x02, 0x80, 1, 1, 0x11, 0],
+        ));
+        jpeg.extend([0xFF, JPEG_SOS, 0x00, 0x08, 1, 1, 0, 0, 0x3F, 0]);
+        jpeg.extend([0x12, 0x34, 0xFF, 0x00, 0x56, 0xFF, 0xD9]);
+        jpeg
+    }
+
+    fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(kind);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&[0, 0, 0, 0]); // CRC is not checked
+        chunk
+    }
+
+    fn test_png() -> Vec<u8> {
+        let mut png = PNG_SIGNATURE.to_vec();
+        let mut ihdr = 320u32.to_be_bytes().to_vec();
+        ihdr.extend(200u32.to_be_bytes());
+        ihdr.extend([8, 6, 0, 0, 0]);
+        png.extend(png_chunk(b"IHDR", &ihdr));
+        png.extend(png_chunk(b"eXIf", b"MM\0*GPS"));
+        png.extend(png_chunk(b"tEXt", b"Author\0someone"));
+        png.extend(png_chunk(b"iCCP", b"profile"));
+        png.extend(png_chunk(b"IDAT", b"pixels"));
+        png.extend(png_chunk(b"IEND", b""));
+        png
+    }
+
+    fn riff_chunk(fourcc: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = fourcc.to_vec();
+        chunk.extend((data.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    fn test_webp() -> Vec<u8> {
+        // VP8X: EXIF + XMP flags, canvas 100x50 (stored minus one)
+        let vp8x = [VP8X_FLAG_EXIF | VP8X_FLAG_XMP, 0, 0, 0, 99, 0, 0, 49, 0, 0];
+        let mut body = b"WEBP".to_vec();
+        body.extend(riff_chunk(b"VP8X", &vp8x));
+        body.extend(riff_chunk(b"VP8L", b"\x2f\0\0\0\0pixels"));
+        body.extend(riff_chunk(b"EXIF", b"MM\0*GPS"));
+        body.extend(riff_chunk(b"XMP ", b"<x:xmpmeta/>"));
+        let mut webp = b"RIFF".to_vec();
+        webp.extend((body.len() as u32).to_le_bytes());
+        webp.extend(body);
+        webp
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    fn detect_format_from_magic_bytes() {
+        assert_eq!(detect_format(&test_jpeg()), Some(ImageFormat::Jpeg));
+        assert_eq!(detect_format(&test_png()), Some(ImageFormat::Png));
+        assert_eq!(detect_format(b"GIF89a\x10\0\x08\0"), Some(ImageFormat::Gif));
+        assert_eq!(detect_format(&test_webp()), Some(ImageFormat::Webp));
+        assert_eq!(detect_format(b"%PDF-1.7"), None);
+    }
+
+    #[test]
+    fn strip_jpeg_removes_exif_and_comments_but_keeps_icc() {
+        let jpeg = test_jpeg();
+        let stripped = strip_metadata(ImageFormat::Jpeg, &jpeg).unwrap();
+        assert!(!contains(&stripped, b"Exif"));
+        assert!(!contains(&stripped, b"taken at home"));
+        assert!(contains(&stripped, b"JFIF"));
+        assert!(contains(&stripped, b"ICC_PROFILE"));
+        assert!(stripped.ends_with(&[0x12, 0x34, 0xFF, 0x00, 0x56, 0xFF, 0xD9]));
+        assert_eq!(dimensions(ImageFormat::Jpeg, &stripped), Some((640, 480)));
+    }
+
+    #[test]
+    fn strip_png_removes_exif_and_text_chunks() {
+        let stripped = strip_metadata(ImageFormat::Png, &test_png()).unwrap();
+        assert!(!contains(&stripped, b"eXIf"));
+        assert!(!contains(&stripped, b"tEXt"));
+        assert!(contains(&stripped, b"iCCP"));
+        assert!(contains(&stripped, b"IDAT"));
+        assert!(stripped.ends_with(&png_chunk(b"IEND", b"")));
+    }
+
+    #[test]
+    fn strip_webp_removes_chunks_and_clears_flags() {
+        let stripped = strip_metadata(ImageFormat::Webp, &test_webp()).unwrap();
+        assert!(!contains(&stripped, b"EXIF"));
+        assert!(!contains(&stripped, b"XMP "));
+        assert_eq!(stripped[20] & (VP8X_FLAG_EXIF | VP8X_FLAG_XMP), 0);
+        assert_eq!(le_u32(&stripped[4..8]) as usize, stripped.len() - 8);
+        assert_eq!(dimensions(ImageFormat::Webp, &stripped), Some((100, 50)));
+    }
+
+    #[test]
+    fn dimensions_from_headers() {
+        assert_eq!(dimensions(ImageFormat::Png, &test_png()), Some((320, 200)));
+        assert_eq!(
+            dimensions(ImageFormat::Gif, b"GIF89a\x10\0\x08\0"),
+            Some((16, 8))
+        );
+    }
+
+    #[test]
+    fn truncated_images_are_rejected() {
+        let jpeg = test_jpeg();
+        assert_eq!(
+            strip_metadata(ImageFormat::Jpeg, &jpeg[..12]),
+            Err(MalformedImage)
+        );
+        let png = test_png();
+        assert_eq!(
+            strip_metadata(ImageFormat::Png, &png[..30]),
+            Err(MalformedImage)
+        );
+        let webp = test_webp();
+        assert_eq!(
+            strip_metadata(ImageFormat::Webp, &webp[..24]),
+            Err(MalformedImage)
+        );
+    }
+}
@@ -1,6 +1,11 @@
 pub(crate) mod auth_service;
 pub(crate) mod commands;
 pub(crate) mod db;
+pub(crate) mod dm_service;
+pub(crate) mod gateway_service;
+pub(crate) mod image_metadata;
+pub(crate) mod media_service;
+pub(crate) mod settings_service;
 
 pub struct DbState {
     pub conn: std::sync::Mutex<rusqlite::Connection>,
@@ -64,6 +69,18 @@ fn specta_builder() -> tauri_specta::Builder<tauri::Wry> {
         commands::auth::auth_recover_complete,
         commands::auth::auth_check_identity,
         commands::auth::auth_get_public_key,
+        commands::media::media_view_image,
+        commands::media::save_attachment_as,
+        commands::media::media_cache_stats,
+        commands::media::media_clear_cache,
+        commands::gateway::gateway_subscribe,
+        commands::gateway::gateway_unsubscribe,
+        commands::gateway::gateway_status,
+        commands::dm::dm_list_channels,
+        commands::dm::dm_notes_channel,
+        commands::settings::settings_emoji_preferences,
+        commands::settings::settings_record_emoji_use,
+        commands::settings::settings_set_skin_tone,
     ])
 }
 
@@ -101,6 +118,31 @@ pub fn run() {
             let crypto_db_path = app_data_dir.join("crypto.db");
             let api_base_url = std::env::var("OPENCONV_API_URL")
                 .unwrap_or_else(|_| "http://localhost:3000".into());
+            let media_svc = media_service::MediaService::new(
+                api_base_url.clone(),
+                media_service::DEFAULT_CACHE_MAX_BYTES,
+            )
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+            app.manage(media_service::MediaState {
+                media_service: media_svc,
+            });
+
+            let gateway_svc = gateway_service::GatewayService::new(api_base_url.clone())
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+            app.manage(gateway_service::GatewayState {
+                gateway_service: gateway_svc,
+            });
+
+            let dm_svc = dm_service::DmService::new(api_base_url.clone())
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+            app.manage(dm_service::DmState { dm_service: dm_svc });
+
+            let settings_svc = settings_service::SettingsService::new(api_base_url.clone())
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+            app.manage(settings_service::SettingsState {
+                settings_service: settings_svc,
+            });
+
             let auth_svc = auth_service::AuthService::new(crypto_db_path, api_base_url)
                 .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
             app.manage(auth_service::AuthState {
@@ -119,6 +161,14 @@ pub fn run() {
 
             Ok(())
         })
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::Destroyed = event {
+                let gateway = window.state::<gateway_service::GatewayState>();
+                if let Err(e) = gateway.gateway_service.remove_window(window.label()) {
+                    tracing::warn!("Failed to release gateway subscriptions: {}", e.message);
+                }
+            }
+        })
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
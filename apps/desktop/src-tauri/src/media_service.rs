@@ -0,0 +1,342 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use base64::Engine;
+use openconv_crypto::file_encryption::{decrypt_file, EncryptedBlob, FileKey};
+use openconv_shared::ids::FileId;
+use reqwest::Client;
+
+use crate::auth_service::{error_from_response, get_access_token, AppError};
+use crate::image_metadata::{self, ImageFormat};
+
+// ---------------------------------------------------------------------------
+// Decrypted image cache
+// ---------------------------------------------------------------------------
+
+/// Default upper bound for decrypted image bytes held in memory.
+pub const DEFAULT_CACHE_MAX_BYTES: usize = 256 * 1024 * 1024;
+
+/// A decrypted image attachment.
+pub struct DecryptedImage {
+    pub format: ImageFormat,
+    pub bytes: Vec<u8>,
+}
+
+/// In-memory LRU of decrypted attachments, bounded by total byte size.
+///
+/// Plaintext never touches disk: entries live only for the lifetime of the
+/// process and are dropped on eviction or logout.
+pub struct MediaCache {
+    max_bytes: usize,
+    used_bytes: usize,
+    entries: HashMap<FileId, Arc<DecryptedImage>>,
+    /// Least recently used first.
+    order: VecDeque<FileId>,
+}
+
+impl MediaCache {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            used_bytes: 0,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn get(&mut self, file_id: FileId) -> Option<Arc<DecryptedImage>> {
+        let image = self.entries.get(&file_id)?.clone();
+        self.touch(file_id);
+        Some(image)
+    }
+
+    /// Insert an image, evicting least recently used entries to stay within
+    /// the byte bound. Images larger than the whole cache are not stored.
+    pub fn insert(&mut self, file_id: FileId, image: Arc<DecryptedImage>) {
+        self.remove(file_id);
+        let size = image.bytes.len();
+        if size > self.max_bytes {
+            return;
+        }
+        while self.used_bytes + size > self.max_bytes {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if let Some(evicted) = self.entries.remove(&oldest) {
+                self.used_bytes -= evicted.bytes.len();
+            }
+        }
+        self.used_bytes += size;
+        self.entries.insert(file_id, image);
+        self.order.push_back(file_id);
+    }
+
+    pub fn remove(&mut self, file_id: FileId) {
+        if let Some(image) = self.entries.remove(&file_id) {
+            self.used_bytes -= image.bytes.len();
+            self.order.retain(|id| *id != file_id);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.used_bytes = 0;
+    }
+
+    pub fn used_bytes(&self) -> usize {
+        self.used_bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn touch(&mut self, file_id: FileId) {
+        if let Some(pos) = self.order.iter().position(|id| *id == file_id) {
+            self.order.remove(pos);
+        }
+        self.order.push_back(file_id);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// MediaService
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, serde::Serialize, specta::Type)]
+pub struct MediaImage {
+    pub file_id: String,
+    pub mime_type: String,
+    /// Pixel size from the image header, used to set the viewer's zoom range.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub size_bytes: u32,
+    /// Base64-encoded original image bytes.
+    pub data: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, specta::Type)]
+pub struct MediaCacheStats {
+    pub entries: u32,
+    pub used_bytes: u32,
+    pub max_bytes: u32,
+}
+
+/// Managed state for the media viewer.
+pub struct MediaState {
+    pub media_service: MediaService,
+}
+
+/// Fetches, decrypts and caches image attachments for the media viewer.
+///
+/// Nothing is prefetched: the full-resolution original is downloaded only
+/// when the viewer opens an image, decrypted once and then served from the
+/// cache for zooming and re-opening.
+pub struct MediaService {
+    api_base_url: String,
+    http_client: Client,
+    cache: Mutex<MediaCache>,
+}
+
+impl MediaService {
+    pub fn new(api_base_url: String, cache_max_bytes: usize) -> Result<Self, AppError> {
+        let http_client = Client::builder()
+            .connect_timeout(std::time::Duration::from_secs(5))
+            .timeout(std::time::Duration::from_secs(120))
+            .build()
+            .map_err(|e| AppError::new(format!("failed to create HTTP client: {e}")))?;
+        Ok(Self {
+            api_base_url,
+            http_client,
+            cache: Mutex::new(MediaCache::new(cache_max_bytes)),
+        })
+    }
+
+    fn lock_cache(&self) -> Result<std::sync::MutexGuard<'_, MediaCache>, AppError> {
+        self.cache
+            .lock()
+            .map_err(|e| AppError::new(format!("media cache lock poisoned: {e}")))
+    }
+
+    /// Return the decrypted image, downloading and decrypting it on a cache miss.
+    /// `file_key` is the base64 AES-256 key shared in the message.
+    pub async fn get_image(
+        &self,
+        file_id: FileId,
+        file_key: &str,
+    ) -> Result<Arc<DecryptedImage>, AppError> {
+        let cached = self.lock_cache()?.get(file_id);
+        if let Some(image) = cached {
+            return Ok(image);
+        }
+
+        let key = parse_file_key(file_key)?;
+        let encrypted = self.download(file_id).await?;
+        let bytes = decrypt_file(&key, &EncryptedBlob { data: encrypted }, None)
+            .map_err(|_| AppError::new("failed to decrypt attachment"))?;
+        let format = image_metadata::detect_format(&bytes)
+            .ok_or_else(|| AppError::new("attachment is not a supported image"))?;
+
+        let image = Arc::new(DecryptedImage { format, bytes });
+        self.lock_cache()?.insert(file_id, image.clone());
+        Ok(image)
+    }
+
+    pub async fn view_image(
+        &self,
+        file_id: FileId,
+        file_key: &str,
+    ) -> Result<MediaImage, AppError> {
+        let image = self.get_image(file_id, file_key).await?;
+        let (width, height) = image_metadata::dimensions(image.format, &image.bytes).unzip();
+        Ok(MediaImage {
+            file_id: file_id.to_string(),
+            mime_type: image.format.mime_type().to_string(),
+            width,
+            height,
+            size_bytes: image.bytes.len() as u32,
+            data: base64::engine::general_purpose::STANDARD.encode(&image.bytes),
+        })
+    }
+
+    /// Write the decrypted image to `path`. EXIF, XMP and text metadata are
+    /// stripped unless `keep_metadata` is set.
+    pub async fn save_as(
+        &self,
+        file_id: FileId,
+        file_key: &str,
+        path: &Path,
+        keep_metadata: bool,
+    ) -> Result<(), AppError> {
+        if !path.is_absolute() {
+            return Err(AppError::new("save path must be absolute"));
+        }
+        let image = self.get_image(file_id, file_key).await?;
+        let bytes = if keep_metadata {
+            image.bytes.clone()
+        } else {
+            image_metadata::strip_metadata(image.format, &image.bytes)
+                .map_err(|e| AppError::new(format!("could not strip image metadata: {e}")))?
+        };
+        std::fs::write(path, bytes)
+            .map_err(|e| AppError::new(format!("failed to save attachment: {e}")))
+    }
+
+    pub fn cache_stats(&self) -> Result<MediaCacheStats, AppError> {
+        let cache = self.lock_cache()?;
+        Ok(MediaCacheStats {
+            entries: cache.len() as u32,
+            used_bytes: cache.used_bytes() as u32,
+            max_bytes: cache.max_bytes as u32,
+        })
+    }
+
+    /// Drop all decrypted images, e.g. on logout.
+    pub fn clear_cache(&self) -> Result<(), AppError> {
+        self.lock_cache()?.clear();
+        Ok(())
+    }
+
+    async fn download(&self, file_id: FileId) -> Result<Vec<u8>, AppError> {
+        let token = get_access_token()?;
+        let resp = self
+            .http_client
+            .get(format!("{}/api/files/{file_id}", self.api_base_url))
+            .bearer_auth(&token)
+            .send()
+            .await?;
+
+        if !resp.status().is_success() {
+            return Err(error_from_response(resp, "attachment download failed").await);
+        }
+        Ok(resp.bytes().await?.to_vec())
+    }
+}
+
+fn parse_file_key(file_key: &str) -> Result<FileKey, AppError> {
+    let bytes = base64::engine::general_purpose::STANDARD.decode(file_key)?;
+    let key: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| AppError::new("invalid file key"))?;
+    Ok(FileKey::from_bytes(key))
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(size: usize) -> Arc<DecryptedImage> {
+        Arc::new(DecryptedImage {
+            format: ImageFormat::Png,
+            bytes: vec![0; size],
+        })
+    }
+
+    #[test]
+    fn cache_evicts_least_recently_used_to_stay_within_bound() {
+        let mut cache = MediaCache::new(100);
+        let (a, b, c) = (FileId::new(), FileId::new(), FileId::new());
+        cache.insert(a, image(40));
+        cache.insert(b, image(40));
+        // Touch `a` so `b` becomes the eviction candidate
+        assert!(cache.get(a).is_some());
+        cache.insert(c, image(40));
+
+        assert!(cache.get(a).is_some());
+        assert!(cache.get(b).is_none());
+        assert!(cache.get(c).is_some());
+        assert_eq!(cache.used_bytes(), 80);
+    }
+
+    #[test]
+    fn cache_skips_images_larger_than_bound() {
+        let mut cache = MediaCache::new(100);
+        let small = FileId::new();
+        cache.insert(small, image(10));
+        cache.insert(FileId::new(), image(101));
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(small).is_some());
+    }
+
+    #[test]
+    fn cache_reinsert_replaces_entry_and_clear_empties() {
+        let mut cache = MediaCache::new(100);
+        let id = FileId::new();
+        cache.insert(id, image(30));
+        cache.insert(id, image(50));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.used_bytes(), 50);
+
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.used_bytes(), 0);
+    }
+
+    #[test]
+    fn parse_file_key_requires_32_bytes() {
+        let b64 = base64::engine::general_purpose::STANDARD;
+        assert!(parse_file_key(&b64.encode([7u8; 32])).is_ok());
+        assert!(parse_file_key(&b64.encode([7u8; 16])).is_err());
+        assert!(parse_file_key("not base64!").is_err());
+    }
+
+    #[tokio::test]
+    async fn save_as_rejects_relative_paths() {
+        let svc = MediaService::new("http://localhost:0".into(), 1024).unwrap();
+        let err = svc
+            .save_as(FileId::new(), "", Path::new("photo.jpg"), false)
+            .await
+            .unwrap_err();
+        assert_eq!(err.message, "save path must be absolute");
+    }
+}
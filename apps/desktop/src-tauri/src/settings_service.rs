@@ -0,0 +1,186 @@
+use openconv_shared::api::settings::{
+    EmojiPreferences as ApiEmojiPreferences, SkinTone, UpdateEmojiPreferencesRequest,
+    UserSettingsResponse,
+};
+use reqwest::Client;
+
+use crate::auth_service::{error_from_response, get_access_token, AppError};
+
+/// Default skin tone for emoji that support modifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum EmojiSkinTone {
+    Default,
+    Light,
+    MediumLight,
+    Medium,
+    MediumDark,
+    Dark,
+}
+
+impl From<SkinTone> for EmojiSkinTone {
+    fn from(tone: SkinTone) -> Self {
+        match tone {
+            SkinTone::Default => Self::Default,
+            SkinTone::Light => Self::Light,
+            SkinTone::MediumLight => Self::MediumLight,
+            SkinTone::Medium => Self::Medium,
+            SkinTone::MediumDark => Self::MediumDark,
+            SkinTone::Dark => Self::Dark,
+        }
+    }
+}
+
+impl From<EmojiSkinTone> for SkinTone {
+    fn from(tone: EmojiSkinTone) -> Self {
+        match tone {
+            EmojiSkinTone::Default => Self::Default,
+            EmojiSkinTone::Light => Self::Light,
+            EmojiSkinTone::MediumLight => Self::MediumLight,
+            EmojiSkinTone::Medium => Self::Medium,
+            EmojiSkinTone::MediumDark => Self::MediumDark,
+            EmojiSkinTone::Dark => Self::Dark,
+        }
+    }
+}
+
+/// The emoji picker's synced preferences.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, specta::Type)]
+pub struct EmojiPreferences {
+    pub skin_tone: EmojiSkinTone,
+    /// Shortcodes ranked most used first, for the picker's "Frequently
+    /// used" row.
+    pub frequent: Vec<String>,
+}
+
+impl From<ApiEmojiPreferences> for EmojiPreferences {
+    fn from(prefs: ApiEmojiPreferences) -> Self {
+        Self {
+            skin_tone: prefs.skin_tone.into(),
+            frequent: prefs.frequent.into_iter().map(|e| e.shortcode).collect(),
+        }
+    }
+}
+
+/// Strip surrounding colons so ":tada:" and "tada" count as the same emoji.
+fn normalize_shortcodes(shortcodes: Vec<String>) -> Vec<String> {
+    shortcodes
+        .into_iter()
+        .map(|s| s.trim().trim_matches(':').to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Managed state for synced settings commands.
+pub struct SettingsState {
+    pub settings_service: SettingsService,
+}
+
+pub struct SettingsService {
+    api_base_url: String,
+    http_client: Client,
+}
+
+impl SettingsService {
+    pub fn new(api_base_url: String) -> Result<Self, AppError> {
+        let http_client = Client::builder()
+            .timeout(std::time::Duration::from_secs(15))
+            .connect_timeout(std::time::Duration::from_secs(5))
+            .build()
+            .map_err(|e| AppError::new(format!("failed to create HTTP client: {e}")))?;
+        Ok(Self {
+            api_base_url,
+            http_client,
+        })
+    }
+
+    /// The user's emoji preferences as stored on the server.
+    pub async fn emoji_preferences(&self) -> Result<EmojiPreferences, AppError> {
+        let token = get_access_token()?;
+        let resp = self
+            .http_client
+            .get(format!("{}/api/users/me/settings", self.api_base_url))
+            .bearer_auth(&token)
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            return Err(error_from_response(resp, "failed to load settings").await);
+        }
+        let settings: UserSettingsResponse = resp.json().await?;
+        Ok(settings.emoji.into())
+    }
+
+    /// Count one use of each shortcode and return the updated ranking.
+    pub async fn record_emoji_use(
+        &self,
+        shortcodes: Vec<String>,
+    ) -> Result<EmojiPreferences, AppError> {
+        self.update_emoji(UpdateEmojiPreferencesRequest {
+            used: normalize_shortcodes(shortcodes),
+            ..Default::default()
+        })
+        .await
+    }
+
+    pub async fn set_skin_tone(&self, tone: EmojiSkinTone) -> Result<EmojiPreferences, AppError> {
+        self.update_emoji(UpdateEmojiPreferencesRequest {
+            skin_tone: Some(tone.into()),
+            ..Default::default()
+        })
+        .await
+    }
+
+    async fn update_emoji(
+        &self,
+        body: UpdateEmojiPreferencesRequest,
+    ) -> Result<EmojiPreferences, AppError> {
+        let token = get_access_token()?;
+        let resp = self
+            .http_client
+            .patch(format!("{}/api/users/me/settings/emoji", self.api_base_url))
+            .bearer_auth(&token)
+            .json(&body)
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            return Err(error_from_response(resp, "failed to save emoji preferences").await);
+        }
+        let settings: UserSettingsResponse = resp.json().await?;
+        Ok(settings.emoji.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openconv_shared::api::settings::FrequentEmoji;
+
+    #[test]
+    fn shortcodes_are_normalized() {
+        let normalized = normalize_shortcodes(vec![":tada:".into(), " wave ".into(), "::".into()]);
+        assert_eq!(normalized, ["tada", "wave"]);
+    }
+
+    #[test]
+    fn preferences_keep_server_ranking() {
+        let now = chrono::Utc::now();
+        let prefs = EmojiPreferences::from(ApiEmojiPreferences {
+            skin_tone: SkinTone::MediumDark,
+            frequent: vec![
+                FrequentEmoji {
+                    shortcode: "tada".into(),
+                    count: 3,
+                    last_used_at: now,
+                },
+                FrequentEmoji {
+                    shortcode: "wave".into(),
+                    count: 1,
+                    last_used_at: now,
+                },
+            ],
+        });
+        assert_eq!(prefs.skin_tone, EmojiSkinTone::MediumDark);
+        assert_eq!(prefs.frequent, ["tada", "wave"]);
+        assert_eq!(SkinTone::from(prefs.skin_tone), SkinTone::MediumDark);
+    }
+}
@@ -43,7 +43,12 @@ describe("LoginPage", () => {
     const user = userEvent.setup();
     vi.mocked(commands.authLogin).mockResolvedValue({
       status: "ok",
-      data: { user_id: "u1", public_key: "pk", device_id: "d1" },
+      data: {
+        user_id: "u1",
+        public_key: "pk",
+        device_id: "d1",
+        device_code: "acid-acorn-actor-agent",
+      },
     });
     renderLoginPage();
     await user.click(screen.getByRole("button", { name: /log in/i }));
@@ -56,7 +61,7 @@ describe("LoginPage", () => {
     const user = userEvent.setup();
     vi.mocked(commands.authLogin).mockResolvedValue({
       status: "error",
-      error: { message: "No identity found" },
+      error: { message: "No identity found", code: null, localized: null },
     });
     renderLoginPage();
     await user.click(screen.getByRole("button", { name: /log in/i }));
@@ -100,6 +100,102 @@ async authGetPublicKey() : Promise<Result<string, AppError>> {
     if(e instanceof Error) throw e;
     else return { status: "error", error: e  as any };
 }
+},
+async mediaViewImage(fileId: string, fileKey: string) : Promise<Result<MediaImage, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("media_view_image", { fileId, fileKey }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async saveAttachmentAs(fileId: string, fileKey: string, path: string, keepMetadata: boolean | null) : Promise<Result<null, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("save_attachment_as", { fileId, fileKey, path, keepMetadata }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async mediaCacheStats() : Promise<Result<MediaCacheStats, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("media_cache_stats") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async mediaClearCache() : Promise<Result<null, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("media_clear_cache") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async gatewaySubscribe(channelId: string) : Promise<Result<null, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("gateway_subscribe", { channelId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async gatewayUnsubscribe(channelId: string) : Promise<Result<null, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("gateway_unsubscribe", { channelId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async gatewayStatus() : Promise<Result<GatewayStatus, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("gateway_status") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async dmListChannels(archived: boolean | null) : Promise<Result<DmChannel[], AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("dm_list_channels", { archived }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async dmNotesChannel() : Promise<Result<DmChannel, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("dm_notes_channel") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async settingsEmojiPreferences() : Promise<Result<EmojiPreferences, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("settings_emoji_preferences") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async settingsRecordEmojiUse(shortcodes: string[]) : Promise<Result<EmojiPreferences, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("settings_record_emoji_use", { shortcodes }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async settingsSetSkinTone(skinTone: EmojiSkinTone) : Promise<Result<EmojiPreferences, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("settings_set_skin_tone", { skinTone }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
 }
 }
 
@@ -113,9 +209,73 @@ async authGetPublicKey() : Promise<Result<string, AppError>> {
 
 /** user-defined types **/
 
-export type AppError = { message: string }
+export type AppError = { message: string; 
+/**
+ * The server's error code, when the error came from an API response.
+ * Branch on this rather than `message`.
+ */
+code: ErrorCode | null; 
+/**
+ * Key and parameters for showing `message` translated, when the server
+ * sent them.
+ */
+localized: ErrorMessage | null }
 export type AppHealth = { version: string; db_status: string }
-export type AuthResult = { user_id: string; public_key: string; device_id: string }
+export type AuthResult = { user_id: string; public_key: string; device_id: string; 
+/**
+ * Word code for this device, shown instead of the raw `device_id`
+ * when linking or verifying devices.
+ */
+device_code: string }
+export type DmChannel = { id: string; 
+/**
+ * Display name: the group name, "Notes" for the notes channel, or
+ * `None` for a 1:1 DM (the client shows the other member's name).
+ */
+name: string | null; is_group: boolean; is_notes: boolean; 
+/**
+ * Hidden from the main list; shown under "Archived".
+ */
+archived: boolean; members: string[] }
+export type EmojiPreferences = { skin_tone: EmojiSkinTone; 
+/**
+ * Shortcodes ranked most used first, for the picker's "Frequently
+ * used" row.
+ */
+frequent: string[] }
+/**
+ * Default skin tone for emoji that support modifiers.
+ */
+export type EmojiSkinTone = "default" | "light" | "medium_light" | "medium" | "medium_dark" | "dark"
+/**
+ * Stable, machine-readable identifier sent as `code` in every error
+ * response. Clients branch on this rather than the message, which may be
+ * reworded.
+ */
+export type ErrorCode = "NOT_FOUND" | "UNAUTHORIZED" | "FORBIDDEN" | "REAUTH_REQUIRED" | "VALIDATION_FAILED" | "INTERNAL_ERROR" | "CRYPTO_ERROR" | "RATE_LIMITED" | "SESSION_COMPROMISED" | "CONFLICT" | "SERVICE_UNAVAILABLE" | "TEMPORARILY_UNAVAILABLE" | "PAYLOAD_TOO_LARGE" | "AUTH_CODE_INVALID" | "AUTH_CODE_EXPIRED" | "ACCOUNT_EXISTS" | "INVITE_EXPIRED" | "INVITE_MAX_USES" | "INVITES_PAUSED" | "GUILD_FULL" | "ALREADY_MEMBER" | "BANNED_FROM_GUILD" | "VERIFICATION_LEVEL_NOT_MET"
+/**
+ * A localizable error message: a stable key naming the message and the
+ * values to fill into it, sent as `localized` alongside the English
+ * `error` text. Clients translate known keys and show `error` for the
+ * rest. Unlike [`ErrorCode`], which says what went wrong, the key says
+ * how to phrase it, e.g. `display_name.too_long` with `{ "max": "64" }`.
+ */
+export type ErrorMessage = { 
+/**
+ * Dotted, subject first: "guild_name.length", "channel_topic.too_long".
+ */
+key: string; params: { [key in string]: string } }
+export type GatewayStatus = { connected: boolean; subscribed_channels: number }
+export type MediaCacheStats = { entries: number; used_bytes: number; max_bytes: number }
+export type MediaImage = { file_id: string; mime_type: string; 
+/**
+ * Pixel size from the image header, used to set the viewer's zoom range.
+ */
+width: number | null; height: number | null; size_bytes: number; 
+/**
+ * Base64-encoded original image bytes.
+ */
+data: string }
 
 /** tauri-specta globals **/
 
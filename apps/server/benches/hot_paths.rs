@@ -0,0 +1,67 @@
+//! Micro-benchmarks for code on every request or gateway message: token
+//! issuing and validation, gateway message (de)serialization and
+//! permission resolution.
+//!
+//! Run with `cargo bench -p openconv-server`. Compare against a saved
+//! baseline with `-- --save-baseline main` on the base branch, then
+//! `-- --baseline main` on yours.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use openconv_shared::api::ws::{ClientMessage, ServerMessage};
+use openconv_shared::ids::{ChannelId, DeviceId, MessageId, UserId};
+use openconv_shared::permissions::{self, Permissions};
+use openconv_test_support::test_jwt;
+
+fn jwt(c: &mut Criterion) {
+    let jwt = test_jwt();
+    let user_id = UserId::new();
+    let device_id = DeviceId::new();
+    let token = jwt.issue_access_token(&user_id, &device_id).unwrap();
+
+    c.bench_function("jwt/issue_access_token", |b| {
+        b.iter(|| jwt.issue_access_token(black_box(&user_id), black_box(&device_id)))
+    });
+    c.bench_function("jwt/validate_access_token", |b| {
+        b.iter(|| jwt.validate_access_token(black_box(&token)).unwrap())
+    });
+}
+
+fn gateway(c: &mut Criterion) {
+    let send = serde_json::to_string(&ClientMessage::SendMessage {
+        channel_id: ChannelId::new(),
+        encrypted_content: vec![0xA5; 2048],
+        nonce: vec![0x5A; 12],
+        payload_kind: Default::default(),
+        idempotency_key: None,
+        metadata: None,
+    })
+    .unwrap();
+    let created = ServerMessage::MessageCreated {
+        channel_id: ChannelId::new(),
+        message_id: MessageId::new(),
+    };
+
+    c.bench_function("gateway/parse_send_message_2k", |b| {
+        b.iter(|| serde_json::from_str::<ClientMessage>(black_box(&send)).unwrap())
+    });
+    c.bench_function("gateway/serialize_message_created", |b| {
+        b.iter(|| serde_json::to_string(black_box(&created)).unwrap())
+    });
+}
+
+fn permission_resolution(c: &mut Criterion) {
+    let roles = [
+        Permissions::SEND_MESSAGES | Permissions::READ_MESSAGES,
+        Permissions::ATTACH_FILES | Permissions::CONNECT,
+        Permissions::MANAGE_MESSAGES,
+        Permissions::KICK_MEMBERS | Permissions::SPEAK,
+    ];
+    c.bench_function("permissions/resolve_4_roles", |b| {
+        b.iter(|| permissions::resolve(black_box(&roles)))
+    });
+}
+
+criterion_group!(benches, jwt, gateway, permission_resolution);
+criterion_main!(benches);
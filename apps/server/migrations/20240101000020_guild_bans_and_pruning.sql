@@ -0,0 +1,46 @@
+ALTER TABLE guilds ADD COLUMN prune_on_ban BOOLEAN NOT NULL DEFAULT false;
+
+-- Grant the new BAN_MEMBERS bit (1 << 11) to existing built-in owner and admin roles.
+UPDATE roles SET permissions = permissions | 2048 WHERE role_type IN ('owner', 'admin');
+
+CREATE TABLE guild_bans (
+    guild_id UUID NOT NULL REFERENCES guilds(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    banned_by UUID NOT NULL REFERENCES users(id),
+    reason TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE TABLE guild_audit_log (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    guild_id UUID NOT NULL REFERENCES guilds(id) ON DELETE CASCADE,
+    actor_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    action TEXT NOT NULL,
+    target_user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    details JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_guild_audit_log_guild_created ON guild_audit_log (guild_id, created_at DESC);
+
+-- Background jobs that tombstone a banned user's messages and delete their
+-- files within a single guild. Progress counters are updated per batch.
+CREATE TABLE member_prune_jobs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    guild_id UUID NOT NULL REFERENCES guilds(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    requested_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    status TEXT NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'running', 'completed', 'failed')),
+    messages_tombstoned BIGINT NOT NULL DEFAULT 0,
+    files_deleted BIGINT NOT NULL DEFAULT 0,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    started_at TIMESTAMPTZ,
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_member_prune_jobs_pending ON member_prune_jobs (created_at)
+    WHERE status = 'pending';
+CREATE INDEX idx_member_prune_jobs_guild ON member_prune_jobs (guild_id, created_at DESC);
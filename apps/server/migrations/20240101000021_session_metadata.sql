@@ -0,0 +1,7 @@
+-- Client metadata recorded when a refresh token is issued, so each session
+-- (refresh token family) can be listed with where it was last used from.
+ALTER TABLE refresh_tokens ADD COLUMN ip_address TEXT;
+ALTER TABLE refresh_tokens ADD COLUMN geo_country TEXT;
+
+ALTER TABLE devices ADD COLUMN last_ip TEXT;
+ALTER TABLE devices ADD COLUMN last_geo_country TEXT;
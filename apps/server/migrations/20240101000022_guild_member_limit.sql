@@ -0,0 +1,2 @@
+-- Optional cap on guild size; NULL means unlimited.
+ALTER TABLE guilds ADD COLUMN max_members INTEGER CHECK (max_members IS NULL OR max_members > 0);
@@ -0,0 +1,4 @@
+ALTER TABLE users
+    ADD COLUMN suspended_at TIMESTAMPTZ,
+    ADD COLUMN suspension_reason TEXT,
+    ADD COLUMN suspended_by UUID REFERENCES users(id) ON DELETE SET NULL;
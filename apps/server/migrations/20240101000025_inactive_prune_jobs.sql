@@ -0,0 +1,27 @@
+-- Background jobs that remove inactive members from a guild. The cutoff is
+-- fixed when the job is requested so the dry-run count and the job agree.
+CREATE TABLE inactive_prune_jobs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    guild_id UUID NOT NULL REFERENCES guilds(id) ON DELETE CASCADE,
+    requested_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    inactive_days INTEGER NOT NULL CHECK (inactive_days > 0),
+    inactive_since TIMESTAMPTZ NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'running', 'completed', 'failed')),
+    members_removed BIGINT NOT NULL DEFAULT 0,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    started_at TIMESTAMPTZ,
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_inactive_prune_jobs_pending ON inactive_prune_jobs (created_at)
+    WHERE status = 'pending';
+CREATE INDEX idx_inactive_prune_jobs_guild ON inactive_prune_jobs (guild_id, created_at DESC);
+
+-- At most one queued or running prune per guild.
+CREATE UNIQUE INDEX idx_inactive_prune_jobs_active ON inactive_prune_jobs (guild_id)
+    WHERE status IN ('pending', 'running');
+
+-- Supports the "no recent messages" check.
+CREATE INDEX idx_messages_sender_created ON messages (sender_id, created_at);
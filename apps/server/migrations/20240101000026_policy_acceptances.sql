@@ -0,0 +1,8 @@
+-- Every terms-of-service / privacy-policy version a user has accepted.
+CREATE TABLE policy_acceptances (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    document TEXT NOT NULL CHECK (document IN ('terms', 'privacy')),
+    version TEXT NOT NULL,
+    accepted_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, document, version)
+);
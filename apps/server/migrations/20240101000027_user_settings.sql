@@ -0,0 +1,8 @@
+-- Per-user settings synced across devices. `render` holds only the fields the
+-- user overrode; the rest follow the instance defaults.
+CREATE TABLE user_settings (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    render JSONB NOT NULL DEFAULT '{}',
+    version BIGINT NOT NULL DEFAULT 1,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,11 @@
+-- Files referenced from messages. A file can be attached to several messages
+-- (e.g. when forwarded); attachments go away with the message or the file.
+CREATE TABLE attachments (
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    file_id UUID NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+    position SMALLINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (message_id, file_id)
+);
+
+CREATE INDEX idx_attachments_file_id ON attachments (file_id);
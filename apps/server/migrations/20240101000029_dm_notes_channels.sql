@@ -0,0 +1,12 @@
+-- Personal notes: a DM channel whose only member is its creator.
+ALTER TABLE dm_channels ADD COLUMN is_notes BOOLEAN NOT NULL DEFAULT false;
+
+CREATE UNIQUE INDEX idx_dm_channels_notes_owner ON dm_channels (creator_id) WHERE is_notes;
+
+WITH created AS (
+    INSERT INTO dm_channels (creator_id, is_notes)
+    SELECT id, true FROM users
+    RETURNING id, creator_id
+)
+INSERT INTO dm_channel_members (dm_channel_id, user_id)
+SELECT id, creator_id FROM created;
@@ -0,0 +1,4 @@
+-- Per-member archive state. Archiving hides a conversation from the member's
+-- default list without leaving it.
+ALTER TABLE dm_channel_members ADD COLUMN archived_at TIMESTAMPTZ;
+ALTER TABLE dm_channel_members ADD COLUMN unarchive_on_message BOOLEAN NOT NULL DEFAULT true;
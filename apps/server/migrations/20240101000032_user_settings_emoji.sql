@@ -0,0 +1,3 @@
+-- Emoji picker preferences (skin tone, frequently used list). Merged on
+-- update rather than replaced, so it lives beside the render overrides.
+ALTER TABLE user_settings ADD COLUMN emoji JSONB NOT NULL DEFAULT '{}';
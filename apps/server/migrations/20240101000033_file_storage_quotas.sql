@@ -0,0 +1,9 @@
+-- Guild a file was uploaded to, for per-guild storage usage. NULL for DM and
+-- standalone uploads, which count only against the uploader.
+ALTER TABLE files ADD COLUMN guild_id UUID;
+
+UPDATE files SET guild_id = split_part(storage_path, '/', 2)::uuid
+    WHERE storage_path LIKE 'guilds/%';
+
+CREATE INDEX idx_files_uploader_id ON files (uploader_id);
+CREATE INDEX idx_files_guild_id ON files (guild_id) WHERE guild_id IS NOT NULL;
@@ -0,0 +1,9 @@
+-- Optional file retention; files uploaded to the guild's channels are deleted
+-- this many days after upload. NULL keeps files forever.
+ALTER TABLE guilds ADD COLUMN file_retention_days INTEGER
+    CHECK (file_retention_days IS NULL OR file_retention_days > 0);
+
+-- Serves the expiry scan as well as per-guild usage sums.
+DROP INDEX idx_files_guild_id;
+CREATE INDEX idx_files_guild_id_created_at ON files (guild_id, created_at)
+    WHERE guild_id IS NOT NULL;
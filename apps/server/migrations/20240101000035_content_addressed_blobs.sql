@@ -0,0 +1,41 @@
+-- Uploaded ciphertext stored once per distinct content under
+-- blobs/{digest}, where digest is the hex SHA-256 of the bytes. Files point
+-- at a blob; identical uploads (e.g. the same encrypted image forwarded
+-- again) share it.
+CREATE TABLE blobs (
+    digest TEXT PRIMARY KEY,
+    size_bytes BIGINT NOT NULL,
+    ref_count INTEGER NOT NULL DEFAULT 0 CHECK (ref_count >= 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_blobs_unreferenced ON blobs (created_at) WHERE ref_count = 0;
+
+-- NULL for files uploaded before deduplication; their bytes still live at
+-- storage_path.
+ALTER TABLE files ADD COLUMN blob_digest TEXT REFERENCES blobs(digest);
+
+CREATE INDEX idx_files_blob_digest ON files (blob_digest) WHERE blob_digest IS NOT NULL;
+
+-- Keep blobs.ref_count equal to the number of files pointing at each blob,
+-- however the file rows are removed (cleanup tasks, prunes, cascades).
+CREATE OR REPLACE FUNCTION files_blob_ref_count()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        IF OLD.blob_digest IS NOT NULL THEN
+            UPDATE blobs SET ref_count = ref_count - 1 WHERE digest = OLD.blob_digest;
+        END IF;
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        IF NEW.blob_digest IS NOT NULL THEN
+            UPDATE blobs SET ref_count = ref_count + 1 WHERE digest = NEW.blob_digest;
+        END IF;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_files_blob_ref_count
+    AFTER INSERT OR DELETE OR UPDATE OF blob_digest ON files
+    FOR EACH ROW EXECUTE FUNCTION files_blob_ref_count();
@@ -0,0 +1,13 @@
+-- Upload scanning. 'pending' files cannot be downloaded until a scanner
+-- marks them 'clean'; 'flagged' files stay blocked. Existing files, and all
+-- files when no scanner is configured, are 'clean'.
+ALTER TABLE files ADD COLUMN scan_status TEXT NOT NULL DEFAULT 'clean'
+    CHECK (scan_status IN ('pending', 'clean', 'flagged'));
+-- The scanner's reason for flagging, e.g. a signature name.
+ALTER TABLE files ADD COLUMN scan_detail TEXT;
+-- Last time a scan was started, so failed scans are retried with backoff.
+ALTER TABLE files ADD COLUMN scan_attempted_at TIMESTAMPTZ;
+ALTER TABLE files ADD COLUMN scanned_at TIMESTAMPTZ;
+
+CREATE INDEX idx_files_scan_pending ON files (scan_attempted_at NULLS FIRST)
+    WHERE scan_status = 'pending';
@@ -0,0 +1,7 @@
+-- Uploaded avatars and guild icons are stored under
+-- avatars/{user_id}/{key}/ and guild-icons/{guild_id}/{key}/ as
+-- original.png plus one PNG per standard size. A new key is minted on every
+-- upload so the served URL changes with it. NULL when nothing was uploaded
+-- (avatar_url / icon_url may still point elsewhere).
+ALTER TABLE users ADD COLUMN avatar_key UUID;
+ALTER TABLE guilds ADD COLUMN icon_key UUID;
@@ -0,0 +1,22 @@
+-- Bridge applications registered by instance admins. A bridge manages
+-- puppet users mirroring accounts on another network; its token is stored
+-- as a SHA-256 hash.
+CREATE TABLE bridges (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL,
+    namespace TEXT NOT NULL UNIQUE,
+    token_hash TEXT NOT NULL UNIQUE,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    token_rotated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Maps a remote account, identified by the bridge, to its puppet user.
+CREATE TABLE bridge_puppets (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    bridge_id UUID NOT NULL REFERENCES bridges(id) ON DELETE CASCADE,
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    remote_id TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (bridge_id, remote_id)
+);
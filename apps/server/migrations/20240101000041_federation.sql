@@ -0,0 +1,41 @@
+-- Experimental federation. Tables exist in every build; only servers built
+-- with the `federation` feature write to them.
+
+-- Local stand-ins for users homed on other instances, so they can hold
+-- guild memberships here. They have no devices and cannot sign in.
+CREATE TABLE federated_users (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    server_name TEXT NOT NULL,
+    remote_user_id UUID NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (server_name, remote_user_id)
+);
+
+-- Guilds hosted elsewhere that local users have joined.
+CREATE TABLE federated_memberships (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    server_name TEXT NOT NULL,
+    guild_id UUID NOT NULL,
+    guild_name TEXT NOT NULL,
+    joined_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, server_name, guild_id)
+);
+
+-- Events waiting to be sent to other instances. Each row is one signed
+-- transaction; its ID doubles as the transaction ID so retries are
+-- deduplicated by the receiver.
+CREATE TABLE federation_outbox (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    destination TEXT NOT NULL,
+    events JSONB NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'delivered', 'failed')),
+    attempts INT NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    delivered_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_federation_outbox_due ON federation_outbox (next_attempt_at)
+    WHERE status = 'pending';
@@ -0,0 +1,3 @@
+-- Grant the new CONNECT (1 << 12) and SPEAK (1 << 13) bits to existing
+-- built-in roles, so current members can use voice channels.
+UPDATE roles SET permissions = permissions | 12288 WHERE role_type IN ('owner', 'admin', 'member');
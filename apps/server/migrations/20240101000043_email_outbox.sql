@@ -0,0 +1,20 @@
+-- Outbound email queue. Rows are sent by the email_delivery task and retried
+-- with backoff; those that keep failing, or outlive the code they carry,
+-- are dead-lettered. The code is cleared once a row is sent or dead.
+CREATE TABLE email_outbox (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    kind TEXT NOT NULL CHECK (kind IN ('verification', 'recovery')),
+    recipient TEXT NOT NULL,
+    code TEXT,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'sent', 'dead')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    sent_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_email_outbox_due ON email_outbox (next_attempt_at) WHERE status = 'pending';
+CREATE INDEX idx_email_outbox_recipient ON email_outbox (recipient, kind) WHERE status = 'pending';
+CREATE INDEX idx_email_outbox_dead ON email_outbox (created_at DESC) WHERE status = 'dead';
@@ -0,0 +1,18 @@
+-- Per-guild automod rules. `rule` holds the serialized `AutomodRule`,
+-- tagged by type; the server enforces the kinds it can see through
+-- encryption and clients enforce the rest.
+CREATE TABLE automod_rules (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    guild_id UUID NOT NULL REFERENCES guilds(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    rule JSONB NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    created_by UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_automod_rules_guild ON automod_rules (guild_id);
+
+-- Attachment rate rules count a sender's recent attachments.
+CREATE INDEX idx_attachments_created_at ON attachments (created_at);
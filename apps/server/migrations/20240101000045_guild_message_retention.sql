@@ -0,0 +1,4 @@
+-- Per-guild message retention: messages older than this many days are
+-- purged by the message_retention job. NULL keeps messages forever.
+ALTER TABLE guilds ADD COLUMN message_retention_days INTEGER
+    CHECK (message_retention_days IS NULL OR message_retention_days > 0);
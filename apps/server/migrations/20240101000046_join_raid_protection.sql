@@ -0,0 +1,16 @@
+-- Join raid protection. Invites cannot be accepted while invites_paused_at
+-- is set, joins beyond max_joins_per_minute are refused, and
+-- verification_level sets how established an account must be to join.
+ALTER TABLE guilds
+    ADD COLUMN verification_level TEXT NOT NULL DEFAULT 'none'
+        CHECK (verification_level IN ('none', 'low', 'medium', 'high')),
+    ADD COLUMN max_joins_per_minute INTEGER
+        CHECK (max_joins_per_minute IS NULL OR max_joins_per_minute > 0),
+    ADD COLUMN invites_paused_at TIMESTAMPTZ;
+
+-- When the account's email address was verified. Registration verifies it
+-- before creating the account; bots, bridge puppets and federated stand-ins
+-- have none.
+ALTER TABLE users ADD COLUMN email_verified_at TIMESTAMPTZ;
+UPDATE users SET email_verified_at = created_at
+    WHERE NOT is_bot AND email NOT LIKE '%.invalid';
@@ -0,0 +1,24 @@
+-- Addresses refused before any other request handling, managed by instance
+-- admins. Entries in the config file are enforced as well but not stored.
+-- cidr is normalized (host bits cleared, prefix always present).
+CREATE TABLE ip_denylist (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    cidr TEXT NOT NULL UNIQUE,
+    reason TEXT,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ
+);
+
+-- Blocked attempts, one row per address and entry per node flush.
+CREATE TABLE ip_denylist_blocks (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    ip TEXT NOT NULL,
+    cidr TEXT NOT NULL,
+    attempts BIGINT NOT NULL,
+    first_seen_at TIMESTAMPTZ NOT NULL,
+    last_seen_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_ip_denylist_blocks_last_seen ON ip_denylist_blocks (last_seen_at DESC);
@@ -0,0 +1,24 @@
+-- Each device signs in with its own identity key. users.public_key stays
+-- the key the account was registered (or last recovered) with; further
+-- devices are linked by an already signed-in device.
+ALTER TABLE devices ADD COLUMN identity_key TEXT;
+
+-- Devices that predate this shared the account key. The most recently
+-- active one keeps it; the others stay unkeyed and may still sign in with
+-- the account key until they are linked again.
+UPDATE devices d SET identity_key = u.public_key
+    FROM users u
+    WHERE d.user_id = u.id
+      AND d.id = (
+          SELECT id FROM devices
+          WHERE user_id = u.id
+          ORDER BY last_active DESC, id DESC
+          LIMIT 1
+      );
+
+-- A key identifies exactly one device.
+CREATE UNIQUE INDEX idx_devices_identity_key ON devices (identity_key)
+    WHERE identity_key IS NOT NULL;
+
+CREATE INDEX idx_pre_key_bundles_device_id ON pre_key_bundles (device_id)
+    WHERE is_used = false;
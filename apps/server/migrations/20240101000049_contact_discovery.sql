@@ -0,0 +1,8 @@
+-- Contact discovery matches truncated salted hashes of email addresses.
+-- The server fills this in from its configured salt at startup and at
+-- registration; it stays NULL while discovery is disabled. Truncated
+-- hashes can collide, so the index is not unique.
+ALTER TABLE users ADD COLUMN discovery_hash BYTEA;
+
+CREATE INDEX idx_users_discovery_hash ON users (discovery_hash)
+    WHERE discovery_hash IS NOT NULL;
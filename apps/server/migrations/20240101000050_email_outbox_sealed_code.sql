@@ -0,0 +1,10 @@
+-- Queued codes are sealed with a server-side key instead of stored in
+-- plaintext, so database access alone does not reveal a pending code.
+-- Rows still holding a plaintext code are dead-lettered first: their codes
+-- expire within minutes and the user can request a new one.
+UPDATE email_outbox
+SET status = 'dead', last_error = COALESCE(last_error, 'dropped when codes were sealed')
+WHERE status = 'pending';
+
+ALTER TABLE email_outbox DROP COLUMN code;
+ALTER TABLE email_outbox ADD COLUMN sealed_code BYTEA;
@@ -0,0 +1,334 @@
+//! Command-line arguments for the `openconv-admin` binary.
+
+use std::path::PathBuf;
+
+use openconv_shared::ids::UserId;
+
+use crate::redis::keys::RateLimitKey;
+use crate::tasks::dev_seed::SeedOptions;
+
+pub const USAGE: &str = "\
+Usage: openconv-admin [--config <path>] <command>
+
+Commands:
+  create-user --email <email> --display-name <name> --public-key <base64>
+                          Create an account for an existing identity key
+  reset-rate-limits (--email <email> | --ip <ip> | --user <user_id> | --all)
+                          Clear rate limit counters
+  purge-user <user_id> --yes
+                          Delete a user's devices, sessions and memberships,
+                          scrub their profile and prune their messages
+  rotate-jwt-keys [--kid <key_id>]
+                          Generate a new JWT signing key and print the
+                          settings that roll it out
+  run-cleanup-now [<job>...]
+                          Run cleanup jobs once, all of them by default
+  stats                   Print instance-wide counts
+  seed [--users <n>] [--guilds <n>] [--channels <n>] [--messages <n>] [--seed <n>]
+                          Insert deterministic development data: users,
+                          guilds, roles, channels, invites and messages
+
+Options:
+  -c, --config <path>  Config file (.toml, .yaml or .yml). Defaults to
+                       $CONFIG_PATH, then ./config.toml if present.
+  -h, --help           Print this help
+
+Environment variables override values from the file.";
+
+/// Whose rate limit counters to clear.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RateLimitTarget {
+    Email(String),
+    Ip(String),
+    User(UserId),
+    All,
+}
+
+impl RateLimitTarget {
+    /// Redis key pattern matching the target's counters.
+    pub fn key_pattern(&self) -> String {
+        match self {
+            Self::Email(email) => RateLimitKey::Email(&email.trim().to_lowercase()).to_string(),
+            Self::Ip(ip) => RateLimitKey::Ip { ip, endpoint: "*" }.to_string(),
+            Self::User(user_id) => RateLimitKey::User {
+                user_id: &user_id.to_string(),
+                endpoint: "*",
+            }
+            .to_string(),
+            Self::All => "rl:*".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminCommand {
+    CreateUser {
+        email: String,
+        display_name: String,
+        public_key: String,
+    },
+    ResetRateLimits(RateLimitTarget),
+    PurgeUser {
+        user_id: UserId,
+    },
+    RotateJwtKeys {
+        kid: Option<String>,
+    },
+    /// Jobs to run by name; empty runs them all.
+    RunCleanupNow {
+        jobs: Vec<String>,
+    },
+    Stats,
+    Seed(SeedOptions),
+    Help,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdminArgs {
+    pub config: Option<PathBuf>,
+    pub command: AdminCommand,
+}
+
+/// Parse arguments, excluding the program name.
+pub fn parse_args<I>(args: I) -> Result<AdminArgs, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+    let mut config = None;
+    let mut options: Vec<(String, String)> = Vec::new();
+    let mut flags: Vec<String> = Vec::new();
+    let mut positional = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                return Ok(AdminArgs {
+                    config,
+                    command: AdminCommand::Help,
+                })
+            }
+            "-c" | "--config" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| format!("{arg} requires a path"))?;
+                config = Some(PathBuf::from(path));
+            }
+            "--yes" | "--all" => flags.push(arg),
+            "--email" | "--display-name" | "--public-key" | "--ip" | "--user" | "--kid"
+            | "--users" | "--guilds" | "--channels" | "--messages" | "--seed" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("{arg} requires a value"))?;
+                options.push((arg, value));
+            }
+            _ => {
+                if let Some(path) = arg.strip_prefix("--config=") {
+                    config = Some(PathBuf::from(path));
+                } else if arg.starts_with('-') {
+                    return Err(format!("unknown option: {arg}"));
+                } else {
+                    positional.push(arg);
+                }
+            }
+        }
+    }
+
+    let option = |name: &str| {
+        options
+            .iter()
+            .rev()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.clone())
+    };
+    let required = |name: &str| option(name).ok_or_else(|| format!("{name} is required"));
+    let flag = |name: &str| flags.iter().any(|f| f == name);
+    let number = |name: &str| {
+        option(name)
+            .map(|v| {
+                v.parse::<u32>()
+                    .map_err(|_| format!("{name} must be a number"))
+            })
+            .transpose()
+    };
+
+    let (name, rest) = positional
+        .split_first()
+        .ok_or_else(|| "a command is required".to_string())?;
+    let no_extra_args = || {
+        if rest.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("unexpected argument: {}", rest[0]))
+        }
+    };
+
+    let command = match name.as_str() {
+        "create-user" => {
+            no_extra_args()?;
+            AdminCommand::CreateUser {
+                email: required("--email")?,
+                display_name: required("--display-name")?,
+                public_key: required("--public-key")?,
+            }
+        }
+        "reset-rate-limits" => {
+            no_extra_args()?;
+            let mut targets = Vec::new();
+            if let Some(email) = option("--email") {
+                targets.push(RateLimitTarget::Email(email));
+            }
+            if let Some(ip) = option("--ip") {
+                targets.push(RateLimitTarget::Ip(ip));
+            }
+            if let Some(user) = option("--user") {
+                let user_id = user
+                    .parse()
+                    .map_err(|_| format!("invalid user ID: {user}"))?;
+                targets.push(RateLimitTarget::User(user_id));
+            }
+            if flag("--all") {
+                targets.push(RateLimitTarget::All);
+            }
+            match targets.len() {
+                1 => AdminCommand::ResetRateLimits(targets.remove(0)),
+                _ => return Err("pass exactly one of --email, --ip, --user or --all".into()),
+            }
+        }
+        "purge-user" => {
+            let [user] = rest else {
+                return Err("purge-user takes one user ID".into());
+            };
+            let user_id = user
+                .parse()
+                .map_err(|_| format!("invalid user ID: {user}"))?;
+            if !flag("--yes") {
+                return Err("purge-user cannot be undone; pass --yes to confirm".into());
+            }
+            AdminCommand::PurgeUser { user_id }
+        }
+        "rotate-jwt-keys" => {
+            no_extra_args()?;
+            AdminCommand::RotateJwtKeys {
+                kid: option("--kid"),
+            }
+        }
+        "run-cleanup-now" => AdminCommand::RunCleanupNow {
+            jobs: rest.to_vec(),
+        },
+        "stats" => {
+            no_extra_args()?;
+            AdminCommand::Stats
+        }
+        "seed" => {
+            no_extra_args()?;
+            let defaults = SeedOptions::default();
+            AdminCommand::Seed(SeedOptions {
+                users: number("--users")?.unwrap_or(defaults.users),
+                guilds: number("--guilds")?.unwrap_or(defaults.guilds),
+                channels_per_guild: number("--channels")?.unwrap_or(defaults.channels_per_guild),
+                messages_per_channel: number("--messages")?
+                    .unwrap_or(defaults.messages_per_channel),
+                seed: number("--seed")?.map_or(defaults.seed, u64::from),
+            })
+        }
+        other => return Err(format!("unknown command: {other}")),
+    };
+    Ok(AdminArgs { config, command })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<AdminCommand, String> {
+        parse_args(args.iter().map(|a| a.to_string())).map(|a| a.command)
+    }
+
+    #[test]
+    fn create_user_requires_all_fields() {
+        assert_eq!(
+            parse(&[
+                "create-user",
+                "--email",
+                "a@b.com",
+                "--display-name",
+                "Alice",
+                "--public-key",
+                "BQ==",
+            ]),
+            Ok(AdminCommand::CreateUser {
+                email: "a@b.com".into(),
+                display_name: "Alice".into(),
+                public_key: "BQ==".into(),
+            })
+        );
+        assert!(parse(&["create-user", "--email", "a@b.com"]).is_err());
+    }
+
+    #[test]
+    fn reset_rate_limits_takes_one_target() {
+        assert_eq!(
+            parse(&["reset-rate-limits", "--ip", "10.0.0.1"]),
+            Ok(AdminCommand::ResetRateLimits(RateLimitTarget::Ip(
+                "10.0.0.1".into()
+            )))
+        );
+        assert!(parse(&["reset-rate-limits"]).is_err());
+        assert!(parse(&["reset-rate-limits", "--all", "--ip", "10.0.0.1"]).is_err());
+        assert_eq!(
+            RateLimitTarget::Email(" A@B.com".into()).key_pattern(),
+            "rl:email:a@b.com"
+        );
+    }
+
+    #[test]
+    fn purge_user_needs_confirmation() {
+        let id = UserId::new().to_string();
+        assert!(parse(&["purge-user", &id]).is_err());
+        assert!(parse(&["purge-user", "not-a-uuid", "--yes"]).is_err());
+        assert!(matches!(
+            parse(&["purge-user", &id, "--yes"]),
+            Ok(AdminCommand::PurgeUser { .. })
+        ));
+    }
+
+    #[test]
+    fn config_and_job_names() {
+        let args = parse_args(
+            ["--config=prod.toml", "run-cleanup-now", "guild_cleanup"]
+                .iter()
+                .map(|a| a.to_string()),
+        )
+        .unwrap();
+        assert_eq!(args.config, Some(PathBuf::from("prod.toml")));
+        assert_eq!(
+            args.command,
+            AdminCommand::RunCleanupNow {
+                jobs: vec!["guild_cleanup".into()]
+            }
+        );
+    }
+
+    #[test]
+    fn seed_fills_in_defaults() {
+        assert_eq!(
+            parse(&["seed", "--users", "100", "--seed", "7"]),
+            Ok(AdminCommand::Seed(SeedOptions {
+                users: 100,
+                seed: 7,
+                ..SeedOptions::default()
+            }))
+        );
+        assert!(parse(&["seed", "--messages", "lots"]).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["migrate"]).is_err());
+        assert!(parse(&["stats", "--verbose"]).is_err());
+        assert!(parse(&["stats", "extra"]).is_err());
+        assert_eq!(parse(&["stats", "--help"]), Ok(AdminCommand::Help));
+    }
+}
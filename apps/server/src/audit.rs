@@ -0,0 +1,42 @@
+//! Guild audit log.
+//!
+//! Moderation actions append a row to `guild_audit_log`. Entries are written
+//! inside the same transaction as the action they describe where possible,
+//! so the log never records something that was rolled back.
+
+use openconv_shared::ids::{GuildId, UserId};
+
+pub const MEMBER_BAN: &str = "member_ban";
+pub const MEMBER_UNBAN: &str = "member_unban";
+pub const MEMBER_PRUNE_QUEUED: &str = "member_prune_queued";
+pub const MEMBER_PRUNE_COMPLETED: &str = "member_prune_completed";
+pub const MEMBER_PRUNE_FAILED: &str = "member_prune_failed";
+pub const INACTIVE_PRUNE_QUEUED: &str = "inactive_prune_queued";
+pub const INACTIVE_PRUNE_COMPLETED: &str = "inactive_prune_completed";
+pub const INACTIVE_PRUNE_FAILED: &str = "inactive_prune_failed";
+
+/// Append an entry to a guild's audit log.
+pub async fn record<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    actor_id: Option<UserId>,
+    action: &str,
+    target_user_id: Option<UserId>,
+    details: serde_json::Value,
+) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query(
+        "INSERT INTO guild_audit_log (guild_id, actor_id, action, target_user_id, details) \
+         VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(guild_id)
+    .bind(actor_id)
+    .bind(action)
+    .bind(target_user_id)
+    .bind(details)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
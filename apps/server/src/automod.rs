@@ -0,0 +1,149 @@
+//! Automod rule enforcement.
+//!
+//! Guild rules are stored in `automod_rules` and managed through
+//! `handlers::automod`. Message content is end-to-end encrypted, so the
+//! server enforces only what it can see: mention and invite rules against
+//! the [`MessageMetadata`] the sender declares, and attachment rate rules
+//! against the attachments table. Keyword rules are left to clients.
+//!
+//! Members with `MANAGE_MESSAGES` are exempt.
+
+use openconv_shared::api::automod::{AutomodRule, MessageMetadata};
+use openconv_shared::ids::{GuildId, MessageId, UserId};
+use openconv_shared::permissions::Permissions;
+
+/// An enabled rule the server enforces.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ActiveRule {
+    pub name: String,
+    pub rule: sqlx::types::Json<AutomodRule>,
+}
+
+/// Whether automod applies to a member with `permissions`.
+pub fn applies_to(permissions: Permissions) -> bool {
+    !permissions.contains(Permissions::MANAGE_MESSAGES)
+}
+
+/// Load the guild's enabled, server-enforced rules.
+pub async fn active_rules(
+    db: &sqlx::PgPool,
+    guild_id: GuildId,
+) -> Result<Vec<ActiveRule>, sqlx::Error> {
+    let rules = sqlx::query_as::<_, ActiveRule>(
+        "SELECT name, rule FROM automod_rules \
+         WHERE guild_id = $1 AND enabled ORDER BY created_at",
+    )
+    .bind(guild_id)
+    .fetch_all(db)
+    .await?;
+    Ok(rules
+        .into_iter()
+        .filter(|r| r.rule.is_server_enforced())
+        .collect())
+}
+
+/// The first rule `metadata` breaks, if any.
+pub fn check_message<'a>(rules: &'a [ActiveRule], metadata: &MessageMetadata) -> Option<&'a str> {
+    rules
+        .iter()
+        .find(|r| match *r.rule {
+            AutomodRule::MentionLimit { max_mentions } => metadata.mention_count > max_mentions,
+            AutomodRule::BlockInviteLinks => metadata.contains_invite,
+            AutomodRule::AttachmentRate { .. } | AutomodRule::Keyword { .. } => false,
+        })
+        .map(|r| r.name.as_str())
+}
+
+/// The first attachment rate rule that attaching `adding` files to
+/// `message_id` would break, if any. Files already on the message are not
+/// counted again.
+pub async fn check_attachments<'a>(
+    db: &sqlx::PgPool,
+    rules: &'a [ActiveRule],
+    user_id: UserId,
+    guild_id: GuildId,
+    message_id: MessageId,
+    adding: usize,
+) -> Result<Option<&'a str>, sqlx::Error> {
+    for rule in rules {
+        let AutomodRule::AttachmentRate {
+            max_attachments,
+            window_seconds,
+        } = *rule.rule
+        else {
+            continue;
+        };
+        let recent: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM attachments a \
+             JOIN messages m ON m.id = a.message_id \
+             JOIN channels c ON c.id = m.channel_id \
+             WHERE m.sender_id = $1 AND c.guild_id = $2 AND a.message_id <> $3 \
+               AND a.created_at > NOW() - make_interval(secs => $4)",
+        )
+        .bind(user_id)
+        .bind(guild_id)
+        .bind(message_id)
+        .bind(window_seconds as f64)
+        .fetch_one(db)
+        .await?;
+        if exceeds_rate(recent, adding, max_attachments) {
+            return Ok(Some(rule.name.as_str()));
+        }
+    }
+    Ok(None)
+}
+
+fn exceeds_rate(recent: i64, adding: usize, max: u32) -> bool {
+    adding > 0 && recent.max(0) as u64 + adding as u64 > max as u64
+}
+
+/// The error shown to a sender whose message a rule blocked.
+pub fn blocked_message(rule_name: &str) -> String {
+    format!("blocked by automod rule \"{rule_name}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, rule: AutomodRule) -> ActiveRule {
+        ActiveRule {
+            name: name.into(),
+            rule: sqlx::types::Json(rule),
+        }
+    }
+
+    #[test]
+    fn message_rules_check_declared_metadata() {
+        let rules = [
+            rule("mentions", AutomodRule::MentionLimit { max_mentions: 3 }),
+            rule("invites", AutomodRule::BlockInviteLinks),
+        ];
+        let mut metadata = MessageMetadata {
+            mention_count: 3,
+            contains_invite: false,
+        };
+        assert_eq!(check_message(&rules, &metadata), None);
+        metadata.mention_count = 4;
+        assert_eq!(check_message(&rules, &metadata), Some("mentions"));
+        metadata.mention_count = 0;
+        metadata.contains_invite = true;
+        assert_eq!(check_message(&rules, &metadata), Some("invites"));
+    }
+
+    #[test]
+    fn attachment_rate_counts_new_files_only() {
+        assert!(!exceeds_rate(3, 2, 5));
+        assert!(exceeds_rate(4, 2, 5));
+        // Clearing a message's attachments is always allowed
+        assert!(!exceeds_rate(9, 0, 5));
+    }
+
+    #[test]
+    fn moderators_are_exempt() {
+        assert!(applies_to(Permissions::SEND_MESSAGES));
+        assert!(!applies_to(
+            Permissions::SEND_MESSAGES | Permissions::MANAGE_MESSAGES
+        ));
+    }
+}
@@ -0,0 +1,350 @@
+//! `openconv-admin`: one-off operational tasks against a server's database,
+//! Redis and object store, using the same configuration as the server.
+
+use std::sync::Arc;
+
+use futures::TryStreamExt;
+use object_store::path::Path as StorePath;
+use object_store::ObjectStore;
+use openconv_shared::ids::UserId;
+
+use openconv_server::admin_cli::{self, AdminCommand, RateLimitTarget, USAGE};
+use openconv_server::config::{LoadedConfig, ServerConfig};
+use openconv_server::crypto_verify;
+use openconv_server::email::outbox;
+use openconv_server::handlers::auth::validate_email;
+use openconv_server::handlers::images::avatar_prefix;
+use openconv_server::jwt;
+use openconv_server::permission_cache;
+use openconv_server::redis::create_redis_pool;
+use openconv_server::repo;
+use openconv_server::repo::users::{NewUser, PurgeOutcome};
+use openconv_server::revocation;
+use openconv_server::storage::create_object_store;
+use openconv_server::tasks::cleanup::RefreshTokenCleanupJob;
+use openconv_server::tasks::dev_seed::{self, SeedOptions};
+use openconv_server::tasks::file_cleanup::{
+    BlobCleanupJob, ExpiredFileCleanupJob, OrphanFileCleanupJob, UploadSessionCleanupJob,
+};
+use openconv_server::tasks::guild_cleanup::GuildCleanupJob;
+use openconv_server::tasks::log_retention::LogRetentionJob;
+use openconv_server::tasks::scheduler::{PeriodicJob, Schedule};
+use openconv_server::validation::validate_display_name;
+
+type AdminResult = Result<(), Box<dyn std::error::Error>>;
+
+#[tokio::main]
+async fn main() {
+    dotenvy::dotenv().ok();
+
+    let args = match admin_cli::parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+    if args.command == AdminCommand::Help {
+        println!("{USAGE}");
+        return;
+    }
+
+    let result = match ServerConfig::load_from(args.config.as_deref()) {
+        Ok(LoadedConfig { config, .. }) => run(config, args.command).await,
+        Err(e) => Err(format!("configuration invalid: {e}").into()),
+    };
+    if let Err(e) = result {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
+
+async fn run(config: ServerConfig, command: AdminCommand) -> AdminResult {
+    match command {
+        // Needs nothing but fresh key material
+        AdminCommand::RotateJwtKeys { kid } => {
+            rotate_jwt_keys(&config, kid);
+            Ok(())
+        }
+        AdminCommand::CreateUser {
+            email,
+            display_name,
+            public_key,
+        } => create_user(&config, &email, &display_name, &public_key).await,
+        AdminCommand::ResetRateLimits(target) => reset_rate_limits(&config, &target).await,
+        AdminCommand::PurgeUser { user_id } => purge_user(&config, user_id).await,
+        AdminCommand::RunCleanupNow { jobs } => run_cleanup_now(&config, &jobs).await,
+        AdminCommand::Stats => stats(&config).await,
+        AdminCommand::Seed(options) => seed(&config, &options).await,
+        AdminCommand::Help => unreachable!("handled before loading config"),
+    }
+}
+
+async fn connect_db(config: &ServerConfig) -> Result<sqlx::PgPool, sqlx::Error> {
+    sqlx::postgres::PgPoolOptions::new()
+        .max_connections(2)
+        .connect(&config.database_url)
+        .await
+}
+
+async fn create_user(
+    config: &ServerConfig,
+    email: &str,
+    display_name: &str,
+    public_key: &str,
+) -> AdminResult {
+    validate_email(email).map_err(|e| e.0)?;
+    let email = email.trim().to_lowercase();
+    let display_name = validate_display_name(display_name).map_err(|e| e.0)?;
+    crypto_verify::parse_public_key(public_key)?;
+
+    let pool = connect_db(config).await?;
+    let user_id = UserId::new();
+    let result = repo::users::insert(
+        &pool,
+        &NewUser {
+            id: user_id,
+            public_key,
+            email: &email,
+            display_name: &display_name,
+        },
+    )
+    .await;
+    match result {
+        Ok(()) => {
+            println!("Created user {user_id} <{email}>");
+            Ok(())
+        }
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            Err("a user with that email or public key already exists".into())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn reset_rate_limits(config: &ServerConfig, target: &RateLimitTarget) -> AdminResult {
+    use fred::interfaces::{ClientLike, KeysInterface};
+    use futures::StreamExt;
+
+    let redis = create_redis_pool(&config.redis).await?;
+    let pattern = redis.key_pattern(target.key_pattern());
+    // SCAN keeps Redis responsive on large keyspaces, unlike KEYS. A cluster
+    // holds each key on one node, so every node is scanned.
+    let client = redis.next();
+    let pages = if client.is_clustered() {
+        client
+            .scan_cluster(pattern.as_str(), Some(1000), None)
+            .boxed()
+    } else {
+        client.scan(pattern.as_str(), Some(1000), None).boxed()
+    };
+    let keys: Vec<fred::types::Key> = pages
+        .map_ok(|mut page| page.take_results().unwrap_or_default())
+        .try_concat()
+        .await?;
+    let mut deleted = 0;
+    for key in keys {
+        deleted += redis.del::<i64, _>(key).await?;
+    }
+    println!("Cleared {deleted} rate limit counter(s) matching {pattern}");
+    Ok(())
+}
+
+async fn purge_user(config: &ServerConfig, user_id: UserId) -> AdminResult {
+    let pool = connect_db(config).await?;
+    let redis = create_redis_pool(&config.redis).await?;
+    let store = create_object_store(&config.file_storage)?;
+
+    let mut tx = pool.begin().await?;
+    let guilds = match repo::users::purge(&mut tx, user_id).await? {
+        PurgeOutcome::NotFound => return Err(format!("no user {user_id}").into()),
+        PurgeOutcome::OwnsGuilds(count) => {
+            return Err(format!(
+                "user {user_id} owns {count} guild(s); transfer or delete them first"
+            )
+            .into())
+        }
+        PurgeOutcome::Purged { guilds } => guilds,
+    };
+    tx.commit().await?;
+
+    // Existing sessions end at their next request
+    revocation::set_user_suspended(&redis, user_id, true).await?;
+    for guild_id in &guilds {
+        permission_cache::invalidate_member(&redis, user_id, *guild_id).await;
+    }
+
+    let prefix = StorePath::from(avatar_prefix(user_id));
+    let objects: Vec<_> = store.list(Some(&prefix)).try_collect().await?;
+    for object in objects {
+        match store.delete(&object.location).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    println!(
+        "Purged user {user_id}; queued message pruning in {} guild(s)",
+        guilds.len()
+    );
+    Ok(())
+}
+
+fn rotate_jwt_keys(config: &ServerConfig, kid: Option<String>) {
+    let kid = kid.unwrap_or_else(|| chrono::Utc::now().format("%Y-%m-%d").to_string());
+    let (private_key_pem, public_key_pem) = jwt::generate_keypair_pem();
+    let current = &config.jwt;
+
+    println!("# New signing key. Set on every node, then restart them:");
+    println!("JWT_KEY_ID={kid}");
+    println!("JWT_PRIVATE_KEY_PEM=\"{}\"", private_key_pem.trim_end());
+    println!("JWT_PUBLIC_KEY_PEM=\"{}\"", public_key_pem.trim_end());
+    if !current.public_key_pem.is_empty() {
+        println!();
+        println!(
+            "# Keep accepting tokens signed with the current key until they expire \
+             (at most {}s), then remove this entry:",
+            current.refresh_token_ttl_seconds
+        );
+        println!("[[jwt.additional_public_keys]]");
+        println!("kid = {:?}", current.key_id);
+        println!(
+            "public_key_pem = \"\"\"\n{}\"\"\"",
+            current.public_key_pem.trim_end()
+        );
+    }
+}
+
+async fn run_cleanup_now(config: &ServerConfig, names: &[String]) -> AdminResult {
+    let pool = connect_db(config).await?;
+    let store: Arc<dyn ObjectStore> = create_object_store(&config.file_storage)?;
+    let schedules = &config.schedules;
+
+    let jobs: Vec<Box<dyn PeriodicJob>> = vec![
+        Box::new(RefreshTokenCleanupJob {
+            pool: pool.clone(),
+            schedule: Schedule::cron(&schedules.refresh_token_cleanup)?,
+        }),
+        Box::new(GuildCleanupJob {
+            pool: pool.clone(),
+            store: store.clone(),
+            schedule: Schedule::cron(&schedules.guild_cleanup)?,
+        }),
+        Box::new(OrphanFileCleanupJob {
+            pool: pool.clone(),
+            store: store.clone(),
+            schedule: Schedule::cron(&schedules.orphan_file_cleanup)?,
+        }),
+        Box::new(UploadSessionCleanupJob {
+            pool: pool.clone(),
+            store: store.clone(),
+            schedule: Schedule::cron(&schedules.upload_session_cleanup)?,
+        }),
+        Box::new(ExpiredFileCleanupJob {
+            pool: pool.clone(),
+            store: store.clone(),
+            schedule: Schedule::cron(&schedules.expired_file_cleanup)?,
+        }),
+        Box::new(BlobCleanupJob {
+            pool: pool.clone(),
+            store: store.clone(),
+            schedule: Schedule::cron(&schedules.blob_cleanup)?,
+        }),
+        Box::new(LogRetentionJob {
+            pool: pool.clone(),
+            store: store.clone(),
+            config: config.log_retention.clone(),
+            schedule: Schedule::cron(&schedules.log_retention)?,
+        }),
+    ];
+
+    if let Some(unknown) = names
+        .iter()
+        .find(|name| !jobs.iter().any(|job| job.name() == name.as_str()))
+    {
+        let known: Vec<_> = jobs.iter().map(|job| job.name()).collect();
+        return Err(format!(
+            "unknown job {unknown}; expected one of: {}",
+            known.join(", ")
+        )
+        .into());
+    }
+
+    let mut failed = false;
+    for job in jobs
+        .iter()
+        .filter(|job| names.is_empty() || names.iter().any(|n| n == job.name()))
+    {
+        match job.run().await {
+            Ok(count) => println!("{}: processed {count}", job.name()),
+            Err(e) => {
+                eprintln!("{}: failed: {e}", job.name());
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        return Err("one or more cleanup jobs failed".into());
+    }
+    Ok(())
+}
+
+async fn stats(config: &ServerConfig) -> AdminResult {
+    let pool = connect_db(config).await?;
+
+    let (users, suspended, guilds, channels, messages, files, file_bytes): (
+        i64,
+        i64,
+        i64,
+        i64,
+        i64,
+        i64,
+        i64,
+    ) = sqlx::query_as(
+        "SELECT \
+            (SELECT COUNT(*) FROM users), \
+            (SELECT COUNT(*) FROM users WHERE suspended_at IS NOT NULL), \
+            (SELECT COUNT(*) FROM guilds WHERE deleted_at IS NULL), \
+            (SELECT COUNT(*) FROM channels), \
+            (SELECT COUNT(*) FROM messages), \
+            (SELECT COUNT(*) FROM files), \
+            (SELECT COALESCE(SUM(size_bytes), 0)::BIGINT FROM files)",
+    )
+    .fetch_one(&pool)
+    .await?;
+    let email = outbox::stats(&pool).await?;
+
+    println!("users:          {users} ({suspended} suspended)");
+    println!("guilds:         {guilds}");
+    println!("channels:       {channels}");
+    println!("messages:       {messages}");
+    println!("files:          {files} ({file_bytes} bytes)");
+    println!(
+        "email queue:    {} pending, {} sent, {} dead",
+        email.pending, email.sent, email.dead
+    );
+    if let Some(oldest) = email.oldest_pending_at {
+        println!("oldest pending: {oldest}");
+    }
+    Ok(())
+}
+
+async fn seed(config: &ServerConfig, options: &SeedOptions) -> AdminResult {
+    let pool = connect_db(config).await?;
+    let mut tx = pool.begin().await?;
+    let report = dev_seed::seed(&mut tx, options).await?;
+    tx.commit().await?;
+
+    println!(
+        "Inserted {} user(s), {} guild(s), {} channel(s), {} invite(s), {} message(s)",
+        report.users, report.guilds, report.channels, report.invites, report.messages
+    );
+    for g in 0..options.guilds {
+        println!(
+            "guild {}: invite code {}",
+            dev_seed::guild_id(options.seed, g),
+            dev_seed::invite_code(options.seed, g)
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,121 @@
+//! Command-line arguments for the server binary.
+
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: openconv-server [--config <path>]
+       openconv-server config check [--config <path>]
+
+Options:
+  -c, --config <path>  Config file (.toml, .yaml or .yml). Defaults to
+                       $CONFIG_PATH, then ./config.toml if present.
+  -h, --help           Print this help
+
+Environment variables override values from the file.";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Run the server.
+    Serve {
+        config: Option<PathBuf>,
+    },
+    /// Load and validate the configuration, then exit.
+    ConfigCheck {
+        config: Option<PathBuf>,
+    },
+    Help,
+}
+
+/// Parse arguments, excluding the program name.
+pub fn parse_args<I>(args: I) -> Result<Command, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+    let mut config = None;
+    let mut positional = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-c" | "--config" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| format!("{arg} requires a path"))?;
+                config = Some(PathBuf::from(path));
+            }
+            _ => {
+                if let Some(path) = arg.strip_prefix("--config=") {
+                    config = Some(PathBuf::from(path));
+                } else if arg.starts_with('-') {
+                    return Err(format!("unknown option: {arg}"));
+                } else {
+                    positional.push(arg);
+                }
+            }
+        }
+    }
+
+    match positional
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] => Ok(Command::Serve { config }),
+        ["config", "check"] => Ok(Command::ConfigCheck { config }),
+        other => Err(format!("unknown command: {}", other.join(" "))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        parse_args(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn no_args_serves_with_default_config() {
+        assert_eq!(parse(&[]), Ok(Command::Serve { config: None }));
+    }
+
+    #[test]
+    fn config_flag_in_both_forms() {
+        let expected = Ok(Command::Serve {
+            config: Some(PathBuf::from("/etc/openconv.yaml")),
+        });
+        assert_eq!(parse(&["--config", "/etc/openconv.yaml"]), expected);
+        assert_eq!(parse(&["--config=/etc/openconv.yaml"]), expected);
+        assert_eq!(parse(&["-c", "/etc/openconv.yaml"]), expected);
+    }
+
+    #[test]
+    fn config_check_accepts_config_before_or_after() {
+        let expected = Ok(Command::ConfigCheck {
+            config: Some(PathBuf::from("prod.toml")),
+        });
+        assert_eq!(
+            parse(&["config", "check", "--config", "prod.toml"]),
+            expected
+        );
+        assert_eq!(
+            parse(&["--config", "prod.toml", "config", "check"]),
+            expected
+        );
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(parse(&["--config"]).is_err());
+        assert!(parse(&["--verbose"]).is_err());
+        assert!(parse(&["config"]).is_err());
+        assert!(parse(&["migrate"]).is_err());
+    }
+
+    #[test]
+    fn help_wins() {
+        assert_eq!(parse(&["config", "check", "--help"]), Ok(Command::Help));
+    }
+}
@@ -1,5 +1,37 @@
 use base64::Engine;
+use libsignal_protocol::kem;
 use openconv_shared::error::OpenConvError;
+use serde::{Deserialize, Serialize};
+
+/// Highest registration ID libsignal generates (IDs are 14-bit, never 0).
+pub const MAX_REGISTRATION_ID: u32 = 16380;
+
+/// Most one-time pre-keys a bundle may carry, matching the pre-key upload
+/// endpoint's batch limit.
+pub const MAX_BUNDLE_ONE_TIME_PRE_KEYS: usize = 100;
+
+/// The pre-key bundle a client uploads when registering or recovering: the
+/// JSON form of the crypto crate's `SerializedPreKeyBundle`, plus any
+/// one-time pre-keys sent alongside it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadedPreKeyBundle {
+    pub identity_key: Vec<u8>,
+    pub signed_pre_key_id: u32,
+    pub signed_pre_key: Vec<u8>,
+    pub signed_pre_key_signature: Vec<u8>,
+    pub registration_id: u32,
+    pub kyber_pre_key_id: u32,
+    pub kyber_pre_key: Vec<u8>,
+    pub kyber_pre_key_signature: Vec<u8>,
+    #[serde(default)]
+    pub one_time_pre_keys: Vec<UploadedPreKey>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadedPreKey {
+    pub key_id: u32,
+    pub public_key: Vec<u8>,
+}
 
 /// Parse a base64-encoded public key string into a libsignal PublicKey.
 /// Expects 33 bytes after base64 decoding (Curve25519 compressed point).
@@ -29,6 +61,62 @@ pub fn verify_challenge_signature(
     public_key.verify_signature(challenge, signature)
 }
 
+/// Check that a pre-key bundle is well formed and belongs to
+/// `identity_key` before it is stored: its identity key must be the
+/// account's, both pre-key signatures must verify against it, and every key
+/// must parse. Peers would otherwise only find a broken bundle when their
+/// session setup fails.
+pub fn validate_pre_key_bundle(
+    bundle: &[u8],
+    identity_key: &libsignal_protocol::PublicKey,
+) -> Result<UploadedPreKeyBundle, OpenConvError> {
+    let parsed: UploadedPreKeyBundle = serde_json::from_slice(bundle)
+        .map_err(|e| OpenConvError::Validation(format!("malformed pre-key bundle: {e}")))?;
+
+    if !(1..=MAX_REGISTRATION_ID).contains(&parsed.registration_id) {
+        return Err(OpenConvError::Validation(format!(
+            "pre-key bundle registration id must be between 1 and {MAX_REGISTRATION_ID}"
+        )));
+    }
+
+    let bundle_identity = libsignal_protocol::PublicKey::deserialize(&parsed.identity_key)
+        .map_err(|_| OpenConvError::Validation("invalid pre-key bundle identity key".into()))?;
+    if bundle_identity != *identity_key {
+        return Err(OpenConvError::Validation(
+            "pre-key bundle identity key does not match the public key".into(),
+        ));
+    }
+
+    libsignal_protocol::PublicKey::deserialize(&parsed.signed_pre_key)
+        .map_err(|_| OpenConvError::Validation("invalid signed pre-key".into()))?;
+    if !identity_key.verify_signature(&parsed.signed_pre_key, &parsed.signed_pre_key_signature) {
+        return Err(OpenConvError::Validation(
+            "signed pre-key signature does not verify".into(),
+        ));
+    }
+
+    kem::PublicKey::deserialize(&parsed.kyber_pre_key)
+        .map_err(|_| OpenConvError::Validation("invalid kyber pre-key".into()))?;
+    if !identity_key.verify_signature(&parsed.kyber_pre_key, &parsed.kyber_pre_key_signature) {
+        return Err(OpenConvError::Validation(
+            "kyber pre-key signature does not verify".into(),
+        ));
+    }
+
+    if parsed.one_time_pre_keys.len() > MAX_BUNDLE_ONE_TIME_PRE_KEYS {
+        return Err(OpenConvError::Validation(format!(
+            "pre-key bundle must not carry more than {MAX_BUNDLE_ONE_TIME_PRE_KEYS} one-time pre-keys"
+        )));
+    }
+    for key in &parsed.one_time_pre_keys {
+        libsignal_protocol::PublicKey::deserialize(&key.public_key).map_err(|_| {
+            OpenConvError::Validation(format!("invalid one-time pre-key {}", key.key_id))
+        })?;
+    }
+
+    Ok(parsed)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -106,4 +194,121 @@ mod tests {
             &signature
         ));
     }
+
+    fn signed_bundle(identity: &IdentityKeyPair) -> UploadedPreKeyBundle {
+        let signed_pre_key = libsignal_protocol::KeyPair::generate(&mut rand::rng())
+            .public_key
+            .serialize()
+            .to_vec();
+        let kyber_pre_key = kem::KeyPair::generate(kem::KeyType::Kyber1024, &mut rand::rng())
+            .public_key
+            .serialize()
+            .to_vec();
+        let sign = |bytes: &[u8]| {
+            identity
+                .private_key()
+                .calculate_signature(bytes, &mut rand::rng())
+                .unwrap()
+                .to_vec()
+        };
+        UploadedPreKeyBundle {
+            identity_key: identity.public_key().serialize().to_vec(),
+            signed_pre_key_id: 1,
+            signed_pre_key_signature: sign(&signed_pre_key),
+            signed_pre_key,
+            registration_id: 42,
+            kyber_pre_key_id: 1,
+            kyber_pre_key_signature: sign(&kyber_pre_key),
+            kyber_pre_key,
+            one_time_pre_keys: vec![],
+        }
+    }
+
+    fn validate(bundle: &UploadedPreKeyBundle, identity: &IdentityKeyPair) -> Result<(), String> {
+        validate_pre_key_bundle(&serde_json::to_vec(bundle).unwrap(), identity.public_key())
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn accepts_well_formed_bundle() {
+        let identity = IdentityKeyPair::generate(&mut rand::rng());
+        let mut bundle = signed_bundle(&identity);
+        bundle.one_time_pre_keys = vec![UploadedPreKey {
+            key_id: 1,
+            public_key: libsignal_protocol::KeyPair::generate(&mut rand::rng())
+                .public_key
+                .serialize()
+                .to_vec(),
+        }];
+        assert_eq!(validate(&bundle, &identity), Ok(()));
+    }
+
+    #[test]
+    fn rejects_malformed_json() {
+        let identity = IdentityKeyPair::generate(&mut rand::rng());
+        assert!(validate_pre_key_bundle(&[1, 2, 3, 4, 5], identity.public_key()).is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_range_registration_id() {
+        let identity = IdentityKeyPair::generate(&mut rand::rng());
+        let mut bundle = signed_bundle(&identity);
+        bundle.registration_id = 0;
+        assert!(validate(&bundle, &identity).is_err());
+        bundle.registration_id = MAX_REGISTRATION_ID + 1;
+        assert!(validate(&bundle, &identity).is_err());
+    }
+
+    #[test]
+    fn rejects_bundle_for_another_identity() {
+        let identity = IdentityKeyPair::generate(&mut rand::rng());
+        let other = IdentityKeyPair::generate(&mut rand::rng());
+        let bundle = signed_bundle(&other);
+        assert!(validate(&bundle, &identity)
+            .unwrap_err()
+            .contains("does not match"));
+    }
+
+    #[test]
+    fn rejects_bad_signatures() {
+        let identity = IdentityKeyPair::generate(&mut rand::rng());
+
+        let mut bundle = signed_bundle(&identity);
+        bundle.signed_pre_key_signature[0] ^= 1;
+        assert!(validate(&bundle, &identity)
+            .unwrap_err()
+            .contains("signed pre-key signature"));
+
+        let mut bundle = signed_bundle(&identity);
+        bundle.kyber_pre_key_signature[0] ^= 1;
+        assert!(validate(&bundle, &identity)
+            .unwrap_err()
+            .contains("kyber pre-key signature"));
+    }
+
+    #[test]
+    fn rejects_too_many_or_invalid_one_time_pre_keys() {
+        let identity = IdentityKeyPair::generate(&mut rand::rng());
+        let mut bundle = signed_bundle(&identity);
+        bundle.one_time_pre_keys = vec![UploadedPreKey {
+            key_id: 7,
+            public_key: vec![0; 32],
+        }];
+        assert!(validate(&bundle, &identity)
+            .unwrap_err()
+            .contains("one-time pre-key 7"));
+
+        let key = libsignal_protocol::KeyPair::generate(&mut rand::rng())
+            .public_key
+            .serialize()
+            .to_vec();
+        bundle.one_time_pre_keys = (0..=MAX_BUNDLE_ONE_TIME_PRE_KEYS as u32)
+            .map(|key_id| UploadedPreKey {
+                key_id,
+                public_key: key.clone(),
+            })
+            .collect();
+        assert!(validate(&bundle, &identity).is_err());
+    }
 }
@@ -0,0 +1,138 @@
+//! Optional read-only replica for reads that tolerate replication lag.
+//!
+//! Handlers go through [`AppState::read`](crate::state::AppState::read),
+//! which uses the replica while it is healthy and falls back to the
+//! primary when it cannot be reached. [`ReplicaHealthJob`] probes the
+//! replica and lets reads return to it once it answers again.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use sqlx::postgres::{PgPool, PgPoolOptions};
+
+use crate::tasks::scheduler::{JobResult, PeriodicJob, Schedule};
+
+/// How long a replica read waits for a connection before falling back.
+const ACQUIRE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A replica pool and whether reads should currently use it.
+pub struct ReadReplica {
+    pool: PgPool,
+    healthy: AtomicBool,
+}
+
+impl ReadReplica {
+    /// Connect lazily, so an unreachable replica does not hold up startup.
+    /// Reads stay on the primary until the first health check succeeds.
+    pub fn connect_lazy(url: &str, max_connections: u32) -> Result<Self, sqlx::Error> {
+        let pool = PgPoolOptions::new()
+            .max_connections(max_connections)
+            .acquire_timeout(ACQUIRE_TIMEOUT)
+            .connect_lazy(url)?;
+        Ok(Self::new(pool, false))
+    }
+
+    pub fn new(pool: PgPool, healthy: bool) -> Self {
+        Self {
+            pool,
+            healthy: AtomicBool::new(healthy),
+        }
+    }
+
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    /// Record the outcome of a probe or read, logging transitions only.
+    pub fn set_healthy(&self, healthy: bool) {
+        if self.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+            if healthy {
+                tracing::info!("read replica reachable, routing reads to it");
+            } else {
+                tracing::warn!("read replica unreachable, routing reads to the primary");
+            }
+        }
+    }
+}
+
+/// Whether `e` means the database could not be reached, as opposed to the
+/// query itself failing. Only these are worth retrying on the primary.
+pub fn is_connection_error(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Io(_)
+        | sqlx::Error::Tls(_)
+        | sqlx::Error::PoolTimedOut
+        | sqlx::Error::PoolClosed
+        | sqlx::Error::WorkerCrashed => true,
+        // Class 08 is connection exceptions; 57P covers shutdowns and
+        // "cannot connect now" while a standby is starting
+        sqlx::Error::Database(db) => db
+            .code()
+            .is_some_and(|code| code.starts_with("08") || code.starts_with("57P")),
+        _ => false,
+    }
+}
+
+/// Probe the replica every 10 seconds. Runs on every node, since each has
+/// its own view of the replica.
+pub struct ReplicaHealthJob {
+    pub replica: std::sync::Arc<ReadReplica>,
+}
+
+#[async_trait::async_trait]
+impl PeriodicJob for ReplicaHealthJob {
+    fn name(&self) -> &'static str {
+        "replica_health"
+    }
+
+    fn schedule(&self) -> Schedule {
+        Schedule::every_secs(10)
+    }
+
+    fn per_node(&self) -> bool {
+        true
+    }
+
+    async fn run(&self) -> JobResult {
+        let probe = sqlx::query("SELECT 1").execute(self.replica.pool()).await;
+        self.replica.set_healthy(probe.is_ok());
+        match probe {
+            Ok(_) => Ok(0),
+            Err(e) => Err(format!("read replica unreachable: {e}").into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connection_errors_fall_back() {
+        assert!(is_connection_error(&sqlx::Error::PoolTimedOut));
+        assert!(is_connection_error(&sqlx::Error::PoolClosed));
+        assert!(is_connection_error(&sqlx::Error::Io(
+            std::io::ErrorKind::ConnectionRefused.into()
+        )));
+    }
+
+    #[test]
+    fn query_errors_do_not_fall_back() {
+        assert!(!is_connection_error(&sqlx::Error::RowNotFound));
+        assert!(!is_connection_error(&sqlx::Error::ColumnNotFound(
+            "id".into()
+        )));
+    }
+
+    #[tokio::test]
+    async fn lazy_replica_starts_unhealthy() {
+        let replica = ReadReplica::connect_lazy("postgresql://localhost/replica", 2).unwrap();
+        assert!(!replica.is_healthy());
+        replica.set_healthy(true);
+        assert!(replica.is_healthy());
+    }
+}
@@ -0,0 +1,58 @@
+//! Contact discovery hashes. A client finds which of its contacts have
+//! accounts here by sending truncated hashes of their email addresses
+//! instead of the addresses themselves:
+//!
+//! `hex(SHA-256(salt || lowercase(trim(email)))[..HASH_BYTES])`
+//!
+//! The salt is per instance and public, so it only stops hashes from being
+//! matched against tables built for other instances. Truncation makes
+//! each hash match many possible addresses, and enumeration is bounded by
+//! the per-user lookup budget rather than by the hash.
+
+use sha2::{Digest, Sha256};
+
+/// Bytes of SHA-256 output kept in each hash.
+pub const HASH_BYTES: usize = 10;
+
+/// The discovery hash of `email` under `salt`, as stored in
+/// `users.discovery_hash`.
+pub fn contact_hash(salt: &str, email: &str) -> Vec<u8> {
+    let digest = Sha256::new()
+        .chain_update(salt.as_bytes())
+        .chain_update(email.trim().to_lowercase().as_bytes())
+        .finalize();
+    digest[..HASH_BYTES].to_vec()
+}
+
+/// Parse a hash as sent by clients: exactly [`HASH_BYTES`] bytes of hex.
+pub fn parse_hash(hash: &str) -> Option<Vec<u8>> {
+    hex::decode(hash)
+        .ok()
+        .filter(|bytes| bytes.len() == HASH_BYTES)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_is_truncated_salted_sha256_of_normalized_email() {
+        let hash = contact_hash("salt", " Alice@Example.org ");
+        assert_eq!(hash.len(), HASH_BYTES);
+        assert_eq!(hash, contact_hash("salt", "alice@example.org"));
+        assert_ne!(hash, contact_hash("pepper", "alice@example.org"));
+
+        let full = Sha256::digest(b"saltalice@example.org");
+        assert_eq!(hash, full[..HASH_BYTES]);
+    }
+
+    #[test]
+    fn parse_hash_requires_exact_length_hex() {
+        let hash = contact_hash("salt", "alice@example.org");
+        assert_eq!(parse_hash(&hex::encode(&hash)), Some(hash.clone()));
+        assert_eq!(parse_hash(&hex::encode_upper(&hash)), Some(hash));
+        assert!(parse_hash("abcd").is_none());
+        assert!(parse_hash(&"zz".repeat(HASH_BYTES)).is_none());
+        assert!(parse_hash(&"00".repeat(HASH_BYTES + 1)).is_none());
+    }
+}
@@ -0,0 +1,133 @@
+//! Outgoing email: verification and recovery codes.
+//!
+//! Messages are queued in [`outbox`], rendered from branded templates (see
+//! [`templates`]) and handed to the provider selected by `email.backend`:
+//! an SMTP relay, the Amazon SES API or the SendGrid API. Without any SMTP
+//! host configured, codes are only logged.
+
+pub mod outbox;
+mod sendgrid;
+mod ses;
+mod smtp;
+pub mod templates;
+
+use std::sync::Arc;
+
+use openconv_shared::error::OpenConvError;
+
+use crate::config::{BrandingConfig, EmailBackend, EmailConfig};
+
+pub use sendgrid::SendGridEmailService;
+pub use ses::SesEmailService;
+pub use smtp::SmtpEmailService;
+pub use templates::EmailTemplates;
+
+#[async_trait::async_trait]
+pub trait EmailService: Send + Sync {
+    async fn send_verification_code(&self, to: &str, code: &str) -> Result<(), OpenConvError>;
+    async fn send_recovery_code(&self, to: &str, code: &str) -> Result<(), OpenConvError>;
+
+    /// Check that mail can be handed off. Services without a remote
+    /// transport are always healthy.
+    async fn health_check(&self) -> Result<(), OpenConvError> {
+        Ok(())
+    }
+}
+
+/// Build the email service selected by the config.
+pub fn create_email_service(
+    config: &EmailConfig,
+    branding: &BrandingConfig,
+) -> Result<Arc<dyn EmailService>, OpenConvError> {
+    let templates = EmailTemplates::new(branding);
+    Ok(match config.backend {
+        _ if !config.delivers_mail() => {
+            tracing::warn!("SMTP not configured, using mock email service");
+            Arc::new(MockEmailService::new())
+        }
+        EmailBackend::Smtp => Arc::new(SmtpEmailService::new(config, templates)?),
+        EmailBackend::Ses => Arc::new(SesEmailService::new(config, templates)?),
+        EmailBackend::Sendgrid => Arc::new(SendGridEmailService::new(config, templates)?),
+    })
+}
+
+/// The configured `From` mailbox.
+fn sender(config: &EmailConfig) -> Result<lettre::message::Mailbox, OpenConvError> {
+    format!("{} <{}>", config.from_name, config.from_address)
+        .parse()
+        .map_err(|e| OpenConvError::Internal(format!("invalid from address: {e}")))
+}
+
+/// HTTP client for the API-based providers.
+fn http_client() -> Result<reqwest::Client, OpenConvError> {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+        .map_err(|e| OpenConvError::Internal(format!("email HTTP client: {e}")))
+}
+
+/// Mock email service that logs codes via tracing. Used for development and testing.
+#[derive(Default)]
+pub struct MockEmailService;
+
+impl MockEmailService {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailService for MockEmailService {
+    async fn send_verification_code(&self, to: &str, code: &str) -> Result<(), OpenConvError> {
+        tracing::info!(to = to, code = code, "mock: verification code");
+        Ok(())
+    }
+
+    async fn send_recovery_code(&self, to: &str, code: &str) -> Result<(), OpenConvError> {
+        tracing::info!(to = to, code = code, "mock: recovery code");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn mock_email_service_implements_trait() {
+        let svc = MockEmailService::new();
+        assert!(svc
+            .send_verification_code("a@b.com", "123456")
+            .await
+            .is_ok());
+        assert!(svc.send_recovery_code("a@b.com", "654321").await.is_ok());
+        assert!(svc.health_check().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn mock_email_send_verification_code_succeeds() {
+        let svc = MockEmailService::new();
+        let result = svc
+            .send_verification_code("test@example.com", "999999")
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn mock_email_send_recovery_code_succeeds() {
+        let svc = MockEmailService::new();
+        let result = svc.send_recovery_code("test@example.com", "111111").await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn unconfigured_smtp_falls_back_to_mock() {
+        let svc =
+            create_email_service(&EmailConfig::default(), &BrandingConfig::default()).unwrap();
+        assert!(svc
+            .send_verification_code("a@b.com", "123456")
+            .await
+            .is_ok());
+    }
+}
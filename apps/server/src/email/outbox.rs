@@ -0,0 +1,252 @@
+//! The outbound email queue.
+//!
+//! Handlers enqueue a message instead of sending it inline; the
+//! `email_delivery` task (see [`crate::tasks::email_delivery`]) hands it to
+//! the provider and retries transient failures, so a provider outage delays
+//! codes instead of losing them.
+//!
+//! Queued codes are sealed with [`OutboxKey`], so read access to the
+//! database is not enough to learn a pending code.
+
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use openconv_shared::api::admin::{EmailDeadLetterResponse, EmailQueueStatsResponse};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+use super::templates::CODE_EXPIRY_MINUTES;
+use crate::config::ServerConfig;
+
+/// Dead letters returned with the queue stats, newest first.
+const DEAD_LETTER_LIMIT: i64 = 20;
+
+/// What a queued message says.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailKind {
+    Verification,
+    Recovery,
+}
+
+impl EmailKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Verification => "verification",
+            Self::Recovery => "recovery",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "verification" => Some(Self::Verification),
+            "recovery" => Some(Self::Recovery),
+            _ => None,
+        }
+    }
+}
+
+const NONCE_LEN: usize = 12;
+
+/// Outbox secret for development setups. The key must not come from a
+/// secret that gets rotated, such as the JWT signing key, or codes queued
+/// before a rotation could no longer be opened.
+const DEV_OUTBOX_SECRET: &str = "openconv-email-outbox-dev";
+
+/// AES-256-GCM key sealing the codes in the outbox. A sealed code only opens
+/// for the kind and recipient it was queued for.
+#[derive(Clone)]
+pub struct OutboxKey(Aes256Gcm);
+
+impl OutboxKey {
+    /// Key from the configured `email.outbox_secret`. Without one, which
+    /// config validation only allows while codes go to the logging mock, a
+    /// fixed development key is used.
+    pub fn from_config(config: &ServerConfig) -> Self {
+        let secret = match config.email.outbox_secret.as_str() {
+            "" => DEV_OUTBOX_SECRET,
+            secret => secret,
+        };
+        let key = Sha256::digest(secret.as_bytes());
+        Self(Aes256Gcm::new_from_slice(&key).expect("SHA-256 output is a valid key"))
+    }
+
+    /// `code` sealed for an email of `kind` to `recipient`: a random nonce
+    /// followed by the ciphertext.
+    pub fn seal(&self, kind: EmailKind, recipient: &str, code: &str) -> Vec<u8> {
+        let nonce: [u8; NONCE_LEN] = rand::rng().random();
+        let ciphertext = self
+            .0
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: code.as_bytes(),
+                    aad: &sealing_aad(kind, recipient),
+                },
+            )
+            .expect("sealing a code cannot fail");
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        sealed
+    }
+
+    /// The code in `sealed`. `None` if it was sealed under another key or
+    /// for another email.
+    pub fn open(&self, kind: EmailKind, recipient: &str, sealed: &[u8]) -> Option<String> {
+        if sealed.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let code = self
+            .0
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: &sealing_aad(kind, recipient),
+                },
+            )
+            .ok()?;
+        String::from_utf8(code).ok()
+    }
+}
+
+fn sealing_aad(kind: EmailKind, recipient: &str) -> Vec<u8> {
+    format!("{}:{recipient}", kind.as_str()).into_bytes()
+}
+
+/// Queue a code email, sealing the code with `key`. Any unsent email of the
+/// same kind to the same address is dropped, since the new code replaces the
+/// old one. The message is dead-lettered if it is still unsent when the code
+/// expires.
+pub async fn enqueue(
+    pool: &sqlx::PgPool,
+    key: &OutboxKey,
+    kind: EmailKind,
+    recipient: &str,
+    code: &str,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "DELETE FROM email_outbox WHERE recipient = $1 AND kind = $2 AND status = 'pending'",
+    )
+    .bind(recipient)
+    .bind(kind.as_str())
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "INSERT INTO email_outbox (kind, recipient, sealed_code, expires_at) \
+         VALUES ($1, $2, $3, NOW() + make_interval(mins => $4))",
+    )
+    .bind(kind.as_str())
+    .bind(recipient)
+    .bind(key.seal(kind, recipient, code))
+    .bind(CODE_EXPIRY_MINUTES as i32)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await
+}
+
+#[derive(sqlx::FromRow)]
+struct StatusCounts {
+    pending: i64,
+    sent: i64,
+    dead: i64,
+    oldest_pending_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(sqlx::FromRow)]
+struct DeadLetterRow {
+    id: uuid::Uuid,
+    kind: String,
+    recipient: String,
+    attempts: i32,
+    last_error: Option<String>,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Queue depth by status, plus the most recent dead letters.
+pub async fn stats(pool: &sqlx::PgPool) -> Result<EmailQueueStatsResponse, sqlx::Error> {
+    let counts: StatusCounts = sqlx::query_as(
+        "SELECT COUNT(*) FILTER (WHERE status = 'pending') AS pending, \
+                COUNT(*) FILTER (WHERE status = 'sent') AS sent, \
+                COUNT(*) FILTER (WHERE status = 'dead') AS dead, \
+                MIN(created_at) FILTER (WHERE status = 'pending') AS oldest_pending_at \
+         FROM email_outbox",
+    )
+    .fetch_one(pool)
+    .await?;
+    let dead_letters: Vec<DeadLetterRow> = sqlx::query_as(
+        "SELECT id, kind, recipient, attempts, last_error, created_at FROM email_outbox \
+         WHERE status = 'dead' ORDER BY created_at DESC LIMIT $1",
+    )
+    .bind(DEAD_LETTER_LIMIT)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(EmailQueueStatsResponse {
+        pending: counts.pending,
+        sent: counts.sent,
+        dead: counts.dead,
+        oldest_pending_at: counts.oldest_pending_at,
+        dead_letters: dead_letters
+            .into_iter()
+            .map(|r| EmailDeadLetterResponse {
+                id: r.id,
+                kind: r.kind,
+                recipient: r.recipient,
+                attempts: r.attempts,
+                last_error: r.last_error,
+                created_at: r.created_at,
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kind_round_trips() {
+        for kind in [EmailKind::Verification, EmailKind::Recovery] {
+            assert_eq!(EmailKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(EmailKind::parse("newsletter"), None);
+    }
+
+    #[test]
+    fn sealed_code_opens_only_for_its_email_and_key() {
+        let mut config = ServerConfig::default();
+        let key = OutboxKey::from_config(&config);
+        let sealed = key.seal(EmailKind::Recovery, "a@example.com", "123456");
+        assert!(!sealed.windows(6).any(|w| w == b"123456"));
+        assert_ne!(
+            sealed,
+            key.seal(EmailKind::Recovery, "a@example.com", "123456")
+        );
+
+        assert_eq!(
+            key.open(EmailKind::Recovery, "a@example.com", &sealed)
+                .as_deref(),
+            Some("123456")
+        );
+        assert_eq!(
+            key.open(EmailKind::Verification, "a@example.com", &sealed),
+            None
+        );
+        assert_eq!(
+            key.open(EmailKind::Recovery, "b@example.com", &sealed),
+            None
+        );
+        assert_eq!(
+            key.open(EmailKind::Recovery, "a@example.com", b"short"),
+            None
+        );
+
+        config.email.outbox_secret = "secret".into();
+        let other = OutboxKey::from_config(&config);
+        assert_eq!(
+            other.open(EmailKind::Recovery, "a@example.com", &sealed),
+            None
+        );
+    }
+}
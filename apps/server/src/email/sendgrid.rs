@@ -0,0 +1,126 @@
+//! SendGrid through its v3 Mail Send API.
+
+use openconv_shared::error::OpenConvError;
+
+use super::templates::{Email, EmailTemplates};
+use super::EmailService;
+use crate::config::EmailConfig;
+
+const API_BASE: &str = "https://api.sendgrid.com/v3";
+
+/// SendGrid email service.
+pub struct SendGridEmailService {
+    client: reqwest::Client,
+    api_key: String,
+    from: lettre::message::Mailbox,
+    templates: EmailTemplates,
+}
+
+impl SendGridEmailService {
+    pub fn new(config: &EmailConfig, templates: EmailTemplates) -> Result<Self, OpenConvError> {
+        Ok(Self {
+            client: super::http_client()?,
+            api_key: config.sendgrid_api_key.clone(),
+            from: super::sender(config)?,
+            templates,
+        })
+    }
+
+    fn mail_body(&self, to: &str, email: Email) -> serde_json::Value {
+        serde_json::json!({
+            "personalizations": [{ "to": [{ "email": to }] }],
+            "from": {
+                "email": self.from.email.to_string(),
+                "name": self.from.name,
+            },
+            "subject": email.subject,
+            "content": [
+                { "type": "text/plain", "value": email.text },
+                { "type": "text/html", "value": email.html },
+            ],
+        })
+    }
+
+    async fn send_email(&self, to: &str, email: Email) -> Result<(), OpenConvError> {
+        let response = self
+            .client
+            .post(format!("{API_BASE}/mail/send"))
+            .bearer_auth(&self.api_key)
+            .json(&self.mail_body(to, email))
+            .send()
+            .await
+            .map_err(|e| {
+                OpenConvError::ServiceUnavailable(format!("SendGrid request failed: {e}"))
+            })?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(OpenConvError::ServiceUnavailable(format!(
+                "SendGrid returned {status}: {body}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailService for SendGridEmailService {
+    /// Listing the key's scopes succeeds for any valid key.
+    async fn health_check(&self) -> Result<(), OpenConvError> {
+        let response = self
+            .client
+            .get(format!("{API_BASE}/scopes"))
+            .bearer_auth(&self.api_key)
+            .send()
+            .await
+            .map_err(|e| {
+                OpenConvError::ServiceUnavailable(format!("SendGrid request failed: {e}"))
+            })?;
+        if !response.status().is_success() {
+            return Err(OpenConvError::ServiceUnavailable(format!(
+                "SendGrid returned {}",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+
+    async fn send_verification_code(&self, to: &str, code: &str) -> Result<(), OpenConvError> {
+        self.send_email(to, self.templates.verification_code(code)?)
+            .await
+    }
+
+    async fn send_recovery_code(&self, to: &str, code: &str) -> Result<(), OpenConvError> {
+        self.send_email(to, self.templates.recovery_code(code)?)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BrandingConfig;
+
+    #[test]
+    fn mail_body_has_text_and_html_parts() {
+        let config = EmailConfig {
+            from_address: "noreply@example.com".to_string(),
+            from_name: "Example Chat".to_string(),
+            sendgrid_api_key: "SG.test".to_string(),
+            ..Default::default()
+        };
+        let templates = EmailTemplates::new(&BrandingConfig::default());
+        let svc = SendGridEmailService::new(&config, templates.clone()).unwrap();
+
+        let body = svc.mail_body("a@b.com", templates.verification_code("123456").unwrap());
+        assert_eq!(body["personalizations"][0]["to"][0]["email"], "a@b.com");
+        assert_eq!(body["from"]["email"], "noreply@example.com");
+        assert_eq!(body["from"]["name"], "Example Chat");
+        assert_eq!(body["content"][0]["type"], "text/plain");
+        assert_eq!(body["content"][1]["type"], "text/html");
+        assert!(body["content"][0]["value"]
+            .as_str()
+            .unwrap()
+            .contains("123456"));
+    }
+}
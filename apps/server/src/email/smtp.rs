@@ -0,0 +1,122 @@
+use openconv_shared::error::OpenConvError;
+
+use super::templates::{Email, EmailTemplates};
+use super::EmailService;
+use crate::config::EmailConfig;
+
+/// SMTP email service using lettre.
+pub struct SmtpEmailService {
+    transport: lettre::AsyncSmtpTransport<lettre::Tokio1Executor>,
+    from: lettre::message::Mailbox,
+    templates: EmailTemplates,
+}
+
+impl SmtpEmailService {
+    pub fn new(config: &EmailConfig, templates: EmailTemplates) -> Result<Self, OpenConvError> {
+        use lettre::transport::smtp::authentication::Credentials;
+        use lettre::AsyncSmtpTransport;
+
+        let creds = Credentials::new(config.smtp_username.clone(), config.smtp_password.clone());
+
+        let transport =
+            AsyncSmtpTransport::<lettre::Tokio1Executor>::starttls_relay(&config.smtp_host)
+                .map_err(|e| OpenConvError::Internal(format!("SMTP relay error: {e}")))?
+                .port(config.smtp_port)
+                .credentials(creds)
+                .build();
+
+        Ok(Self {
+            transport,
+            from: super::sender(config)?,
+            templates,
+        })
+    }
+
+    async fn send_email(&self, to: &str, email: Email) -> Result<(), OpenConvError> {
+        use lettre::message::MultiPart;
+        use lettre::{AsyncTransport, Message};
+
+        let to_mailbox: lettre::message::Mailbox = to
+            .parse()
+            .map_err(|e| OpenConvError::Validation(format!("invalid email address: {e}")))?;
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to_mailbox)
+            .subject(email.subject)
+            .multipart(MultiPart::alternative_plain_html(email.text, email.html))
+            .map_err(|e| OpenConvError::Internal(format!("email build error: {e}")))?;
+
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| OpenConvError::ServiceUnavailable(format!("email send failed: {e}")))?;
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailService for SmtpEmailService {
+    async fn health_check(&self) -> Result<(), OpenConvError> {
+        match self.transport.test_connection().await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(OpenConvError::ServiceUnavailable(
+                "SMTP server rejected NOOP".into(),
+            )),
+            Err(e) => Err(OpenConvError::ServiceUnavailable(format!(
+                "SMTP connection failed: {e}"
+            ))),
+        }
+    }
+
+    async fn send_verification_code(&self, to: &str, code: &str) -> Result<(), OpenConvError> {
+        self.send_email(to, self.templates.verification_code(code)?)
+            .await
+    }
+
+    async fn send_recovery_code(&self, to: &str, code: &str) -> Result<(), OpenConvError> {
+        self.send_email(to, self.templates.recovery_code(code)?)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BrandingConfig;
+
+    fn templates() -> EmailTemplates {
+        EmailTemplates::new(&BrandingConfig::default())
+    }
+
+    #[tokio::test]
+    async fn smtp_email_service_initializes_with_valid_config() {
+        let config = EmailConfig {
+            smtp_host: "smtp.example.com".to_string(),
+            smtp_port: 587,
+            smtp_username: "user".to_string(),
+            smtp_password: "pass".to_string(),
+            from_address: "noreply@example.com".to_string(),
+            from_name: "OpenConv".to_string(),
+            ..Default::default()
+        };
+        let result = SmtpEmailService::new(&config, templates());
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn smtp_email_service_fails_with_invalid_config() {
+        let config = EmailConfig {
+            smtp_host: String::new(),
+            smtp_port: 587,
+            smtp_username: String::new(),
+            smtp_password: String::new(),
+            from_address: "not-an-email".to_string(),
+            from_name: String::new(),
+            ..Default::default()
+        };
+        let result = SmtpEmailService::new(&config, templates());
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,121 @@
+//! Branded message bodies, rendered with askama from `templates/email/`.
+//!
+//! Every message has a plain-text and an HTML part carrying the instance
+//! name from `branding.instance_name` and, in HTML, the logo at
+//! `branding.logo_url`.
+
+use askama::Template;
+use openconv_shared::error::OpenConvError;
+
+use crate::config::BrandingConfig;
+
+/// How long emailed codes stay valid, as stated in the message.
+pub const CODE_EXPIRY_MINUTES: u32 = 10;
+
+/// A rendered message, ready to hand to a provider.
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+/// Fields shared by the text and HTML templates of a code email.
+struct CodeEmail<'a> {
+    instance_name: &'a str,
+    logo_url: Option<&'a str>,
+    /// e.g. "verification", as in "your verification code".
+    purpose: &'a str,
+    code: &'a str,
+    expiry_minutes: u32,
+}
+
+#[derive(Template)]
+#[template(path = "email/code.txt")]
+struct CodeText<'a> {
+    email: &'a CodeEmail<'a>,
+}
+
+#[derive(Template)]
+#[template(path = "email/code.html")]
+struct CodeHtml<'a> {
+    email: &'a CodeEmail<'a>,
+}
+
+/// Renders the messages the server sends, branded for this instance.
+#[derive(Debug, Clone)]
+pub struct EmailTemplates {
+    instance_name: String,
+    logo_url: Option<String>,
+}
+
+impl EmailTemplates {
+    pub fn new(branding: &BrandingConfig) -> Self {
+        Self {
+            instance_name: branding.instance_name.clone(),
+            logo_url: branding.logo_url.clone(),
+        }
+    }
+
+    pub fn verification_code(&self, code: &str) -> Result<Email, OpenConvError> {
+        self.code_email("Verification Code", "verification", code)
+    }
+
+    pub fn recovery_code(&self, code: &str) -> Result<Email, OpenConvError> {
+        self.code_email("Account Recovery Code", "recovery", code)
+    }
+
+    fn code_email(&self, title: &str, purpose: &str, code: &str) -> Result<Email, OpenConvError> {
+        let email = CodeEmail {
+            instance_name: &self.instance_name,
+            logo_url: self.logo_url.as_deref(),
+            purpose,
+            code,
+            expiry_minutes: CODE_EXPIRY_MINUTES,
+        };
+        Ok(Email {
+            subject: format!("{} - {title}", self.instance_name),
+            text: CodeText { email: &email }.render().map_err(render_err)?,
+            html: CodeHtml { email: &email }.render().map_err(render_err)?,
+        })
+    }
+}
+
+fn render_err(e: askama::Error) -> OpenConvError {
+    OpenConvError::Internal(format!("email template error: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_email_is_branded() {
+        let templates = EmailTemplates::new(&BrandingConfig {
+            instance_name: "Example <Chat>".to_string(),
+            logo_url: Some("https://example.org/logo.png".to_string()),
+            ..Default::default()
+        });
+        let email = templates.recovery_code("654321").unwrap();
+
+        assert_eq!(email.subject, "Example <Chat> - Account Recovery Code");
+        assert!(email
+            .text
+            .contains("Your Example <Chat> recovery code is: 654321"));
+        assert!(email.text.contains("expires in 10 minutes"));
+        assert!(email.html.contains("654321"));
+        assert!(email.html.contains("<img src=\"https:"));
+        assert!(email.html.contains("example.org"));
+        // The instance name is escaped in HTML
+        assert!(email.html.contains("Example &lt;Chat&gt;"));
+        assert!(!email.html.contains("<Chat>"));
+    }
+
+    #[test]
+    fn logo_is_optional() {
+        let templates = EmailTemplates::new(&BrandingConfig::default());
+        let email = templates.verification_code("123456").unwrap();
+        assert_eq!(email.subject, "OpenConv - Verification Code");
+        assert!(!email.html.contains("<img"));
+    }
+}
@@ -1,12 +1,35 @@
-use axum::http::StatusCode;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
-use openconv_shared::error::OpenConvError;
+use openconv_shared::error::{ErrorCode, ErrorMessage, OpenConvError, RateLimitInfo};
 
 /// Error response body for OpenAPI documentation.
 #[derive(Debug, serde::Serialize, utoipa::ToSchema)]
 pub struct ErrorResponse {
     pub error: String,
+    pub code: ErrorCode,
+    /// Key and parameters for a translated message, when the server has one.
+    pub localized: Option<ErrorMessage>,
+    /// Same as the `X-Request-Id` response header. Quote it when reporting
+    /// the error; the server's logs are searchable by it.
+    pub request_id: Option<String>,
+}
+
+/// The JSON body of every error response: the message, its code, any
+/// localizable form of the message and, while handling a request, its ID.
+pub fn error_body(
+    code: ErrorCode,
+    message: &str,
+    localized: Option<&ErrorMessage>,
+) -> Json<serde_json::Value> {
+    let mut body = serde_json::json!({ "error": message, "code": code });
+    if let Some(localized) = localized {
+        body["localized"] = serde_json::json!(localized);
+    }
+    if let Some(request_id) = crate::middleware::request_id::current() {
+        body["request_id"] = request_id.into();
+    }
+    Json(body)
 }
 
 /// Newtype wrapper for `OpenConvError` that implements `IntoResponse`.
@@ -18,25 +41,66 @@ pub struct ServerError(pub OpenConvError);
 
 impl IntoResponse for ServerError {
     fn into_response(self) -> Response {
-        let (status, message) = match &self.0 {
-            OpenConvError::NotFound => (StatusCode::NOT_FOUND, self.0.to_string()),
-            OpenConvError::Unauthorized => (StatusCode::UNAUTHORIZED, self.0.to_string()),
-            OpenConvError::Forbidden => (StatusCode::FORBIDDEN, self.0.to_string()),
+        let error = self.0.base();
+        crate::error_reporting::capture_server_error(error);
+        let (status, message) = match error {
+            OpenConvError::NotFound => (StatusCode::NOT_FOUND, error.to_string()),
+            OpenConvError::Unauthorized => (StatusCode::UNAUTHORIZED, error.to_string()),
+            OpenConvError::Forbidden => (StatusCode::FORBIDDEN, error.to_string()),
+            OpenConvError::ReauthRequired => (StatusCode::FORBIDDEN, error.to_string()),
             OpenConvError::Validation(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
             OpenConvError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.clone()),
             OpenConvError::Crypto(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.clone()),
             OpenConvError::Conflict(msg) => (StatusCode::CONFLICT, msg.clone()),
-            OpenConvError::RateLimited => (StatusCode::TOO_MANY_REQUESTS, self.0.to_string()),
-            OpenConvError::SessionCompromised => (StatusCode::UNAUTHORIZED, self.0.to_string()),
-            OpenConvError::ServiceUnavailable(_) => {
-                (StatusCode::SERVICE_UNAVAILABLE, self.0.to_string())
+            OpenConvError::RateLimited(_) => (StatusCode::TOO_MANY_REQUESTS, error.to_string()),
+            OpenConvError::SessionCompromised => (StatusCode::UNAUTHORIZED, error.to_string()),
+            OpenConvError::ServiceUnavailable(_) | OpenConvError::TemporarilyUnavailable { .. } => {
+                (StatusCode::SERVICE_UNAVAILABLE, error.to_string())
             }
             OpenConvError::PayloadTooLarge(msg) => (StatusCode::PAYLOAD_TOO_LARGE, msg.clone()),
+            OpenConvError::Detailed { .. } => unreachable!("base() strips details"),
         };
-        (status, Json(serde_json::json!({ "error": message }))).into_response()
+        let mut response = (
+            status,
+            error_body(self.0.code(), &message, self.0.message()),
+        )
+            .into_response();
+        match error {
+            OpenConvError::RateLimited(info) => {
+                apply_rate_limit_headers(response.headers_mut(), info)
+            }
+            OpenConvError::TemporarilyUnavailable {
+                retry_after_seconds,
+                ..
+            } => {
+                response
+                    .headers_mut()
+                    .insert("Retry-After", HeaderValue::from(*retry_after_seconds));
+            }
+            _ => {}
+        }
+        response
     }
 }
 
+/// Set the `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset`
+/// headers describing the caller's current quota.
+pub fn insert_rate_limit_headers(
+    headers: &mut HeaderMap,
+    limit: u32,
+    remaining: u32,
+    reset_seconds: u64,
+) {
+    headers.insert("RateLimit-Limit", HeaderValue::from(limit));
+    headers.insert("RateLimit-Remaining", HeaderValue::from(remaining));
+    headers.insert("RateLimit-Reset", HeaderValue::from(reset_seconds));
+}
+
+fn apply_rate_limit_headers(headers: &mut HeaderMap, info: &RateLimitInfo) {
+    insert_rate_limit_headers(headers, info.limit, info.remaining, info.reset_seconds);
+    headers.insert("Retry-After", HeaderValue::from(info.retry_after_seconds));
+}
+
 impl From<OpenConvError> for ServerError {
     fn from(e: OpenConvError) -> Self {
         ServerError(e)
@@ -65,6 +129,12 @@ mod tests {
         assert_eq!(response.status(), StatusCode::FORBIDDEN);
     }
 
+    #[test]
+    fn test_reauth_required_maps_to_403() {
+        let response = ServerError(OpenConvError::ReauthRequired).into_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
     #[test]
     fn test_validation_maps_to_400() {
         let response = ServerError(OpenConvError::Validation("bad input".into())).into_response();
@@ -96,12 +166,29 @@ mod tests {
         assert_eq!(response.status(), StatusCode::CONFLICT);
     }
 
+    const RATE_LIMIT_INFO: RateLimitInfo = RateLimitInfo {
+        limit: 5,
+        remaining: 0,
+        reset_seconds: 60,
+        retry_after_seconds: 12,
+    };
+
     #[test]
     fn test_rate_limited_maps_to_429() {
-        let response = ServerError(OpenConvError::RateLimited).into_response();
+        let response = ServerError(OpenConvError::RateLimited(RATE_LIMIT_INFO)).into_response();
         assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
     }
 
+    #[test]
+    fn test_rate_limited_sets_backoff_headers() {
+        let response = ServerError(OpenConvError::RateLimited(RATE_LIMIT_INFO)).into_response();
+        let headers = response.headers();
+        assert_eq!(headers.get("Retry-After").unwrap(), "12");
+        assert_eq!(headers.get("RateLimit-Limit").unwrap(), "5");
+        assert_eq!(headers.get("RateLimit-Remaining").unwrap(), "0");
+        assert_eq!(headers.get("RateLimit-Reset").unwrap(), "60");
+    }
+
     #[test]
     fn test_session_compromised_maps_to_401() {
         let response = ServerError(OpenConvError::SessionCompromised).into_response();
@@ -115,6 +202,17 @@ mod tests {
         assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
     }
 
+    #[test]
+    fn test_temporarily_unavailable_sets_retry_after() {
+        let response = ServerError(OpenConvError::TemporarilyUnavailable {
+            service: "redis".into(),
+            retry_after_seconds: 7,
+        })
+        .into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers().get("Retry-After").unwrap(), "7");
+    }
+
     #[test]
     fn test_payload_too_large_maps_to_413() {
         let response =
@@ -122,11 +220,58 @@ mod tests {
         assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
     }
 
+    #[tokio::test]
+    async fn test_error_bodies_carry_the_code() {
+        let response = ServerError(OpenConvError::NotFound).into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "NOT_FOUND");
+
+        let response = ServerError(
+            OpenConvError::Validation("invite is at max uses".into())
+                .with_code(ErrorCode::InviteMaxUses),
+        )
+        .into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "INVITE_MAX_USES");
+        assert_eq!(json["error"], "invite is at max uses");
+    }
+
+    #[tokio::test]
+    async fn test_error_bodies_carry_the_localized_message() {
+        let response = ServerError(
+            OpenConvError::Validation("Topic must be at most 1024 characters".into())
+                .with_message(ErrorMessage::new("channel_topic.too_long").param("max", 1024)),
+        )
+        .into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "VALIDATION_FAILED");
+        assert_eq!(json["localized"]["key"], "channel_topic.too_long");
+        assert_eq!(json["localized"]["params"]["max"], "1024");
+
+        let response = ServerError(OpenConvError::NotFound).into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(json.get("localized").is_none());
+    }
+
     #[tokio::test]
     async fn test_new_error_variants_produce_json_body() {
         let variants: Vec<OpenConvError> = vec![
             OpenConvError::Conflict("test".into()),
-            OpenConvError::RateLimited,
+            OpenConvError::RateLimited(RATE_LIMIT_INFO),
             OpenConvError::SessionCompromised,
             OpenConvError::ServiceUnavailable("test".into()),
             OpenConvError::PayloadTooLarge("test".into()),
@@ -0,0 +1,233 @@
+//! Reporting of internal errors to Sentry or a generic webhook.
+//!
+//! Reporting is process-wide so that `ServerError` conversion, which has no
+//! access to `AppState`, can report too. Until [`init`] runs every capture
+//! is a no-op, which keeps tests and unconfigured deployments silent.
+
+use std::fmt::Display;
+use std::sync::{Arc, LazyLock, OnceLock};
+
+use openconv_shared::error::OpenConvError;
+use regex::Regex;
+
+use crate::config::{ErrorReportingBackend, ErrorReportingConfig};
+
+static REPORTER: OnceLock<Reporter> = OnceLock::new();
+
+static EMAIL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap());
+static IPV4_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(?:\d{1,3}\.){3}\d{1,3}\b").unwrap());
+static BEARER_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)bearer\s+[A-Za-z0-9._~+/=-]+").unwrap());
+static JWT_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\beyJ[A-Za-z0-9_-]*\.[A-Za-z0-9_-]+\.[A-Za-z0-9_-]*").unwrap());
+
+struct Reporter {
+    sink: Sink,
+    sample_rate: f32,
+    scrub_pii: bool,
+    environment: Option<String>,
+}
+
+enum Sink {
+    Sentry,
+    Webhook {
+        client: reqwest::Client,
+        url: Arc<str>,
+    },
+}
+
+/// Keeps the Sentry client alive; dropping it flushes queued events.
+/// Hold it for the lifetime of `main`.
+pub struct ErrorReportingGuard(#[allow(dead_code)] Option<sentry::ClientInitGuard>);
+
+/// Install the configured reporter. Call once at startup.
+pub fn init(config: &ErrorReportingConfig) -> Result<ErrorReportingGuard, String> {
+    let (sink, guard) = match config.backend {
+        ErrorReportingBackend::None => return Ok(ErrorReportingGuard(None)),
+        ErrorReportingBackend::Sentry => {
+            let dsn = config
+                .dsn
+                .parse()
+                .map_err(|e| format!("invalid Sentry DSN: {e}"))?;
+            let scrub_pii = config.scrub_pii;
+            let guard = sentry::init(sentry::ClientOptions {
+                dsn: Some(dsn),
+                release: sentry::release_name!(),
+                environment: config.environment.clone().map(Into::into),
+                // Sampled in `report`; panics are rare enough to send all
+                sample_rate: 1.0,
+                send_default_pii: false,
+                before_send: Some(Arc::new(move |mut event| {
+                    if scrub_pii {
+                        scrub_event(&mut event);
+                    }
+                    Some(event)
+                })),
+                ..Default::default()
+            });
+            (Sink::Sentry, Some(guard))
+        }
+        ErrorReportingBackend::Webhook => {
+            let client = reqwest::Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .timeout(std::time::Duration::from_secs(10))
+                .build()
+                .map_err(|e| format!("error webhook client: {e}"))?;
+            let sink = Sink::Webhook {
+                client,
+                url: config.webhook_url.as_str().into(),
+            };
+            (sink, None)
+        }
+    };
+
+    REPORTER
+        .set(Reporter {
+            sink,
+            sample_rate: config.sample_rate,
+            scrub_pii: config.scrub_pii,
+            environment: config.environment.clone(),
+        })
+        .map_err(|_| "error reporting already initialized".to_string())?;
+    Ok(ErrorReportingGuard(guard))
+}
+
+/// Report an error being turned into a 5xx response. Client errors are
+/// expected traffic and are not reported.
+pub fn capture_server_error(error: &OpenConvError) {
+    if matches!(error, OpenConvError::Internal(_) | OpenConvError::Crypto(_)) {
+        report("http", &error.to_string());
+    }
+}
+
+/// Report a failed run of a background task.
+pub fn capture_task_failure(task: &str, error: &dyn Display) {
+    report(task, &format!("{task} failed: {error}"));
+}
+
+fn report(source: &str, message: &str) {
+    let Some(reporter) = REPORTER.get() else {
+        return;
+    };
+    if reporter.sample_rate < 1.0 && rand::random::<f32>() >= reporter.sample_rate {
+        return;
+    }
+    let message = if reporter.scrub_pii {
+        scrub(message)
+    } else {
+        message.to_string()
+    };
+
+    match &reporter.sink {
+        Sink::Sentry => {
+            sentry::with_scope(
+                |scope| scope.set_tag("source", source),
+                || sentry::capture_message(&message, sentry::Level::Error),
+            );
+        }
+        Sink::Webhook { client, url } => {
+            // Reports are fire-and-forget; without a runtime there is
+            // nothing to send them on
+            let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+                return;
+            };
+            let body = serde_json::json!({
+                "level": "error",
+                "source": source,
+                "message": message,
+                "environment": reporter.environment,
+                "release": env!("CARGO_PKG_VERSION"),
+                "timestamp": chrono::Utc::now(),
+            });
+            let client = client.clone();
+            let url = url.clone();
+            runtime.spawn(async move {
+                let result = client
+                    .post(&*url)
+                    .json(&body)
+                    .send()
+                    .await
+                    .and_then(|r| r.error_for_status());
+                if let Err(e) = result {
+                    // Logged only; reporting this would loop
+                    tracing::warn!(error = %e, "failed to deliver error report");
+                }
+            });
+        }
+    }
+}
+
+/// Redact personal data and credentials from free-form text.
+pub fn scrub(text: &str) -> String {
+    let text = BEARER_RE.replace_all(text, "Bearer [redacted]");
+    let text = JWT_RE.replace_all(&text, "[token]");
+    let text = EMAIL_RE.replace_all(&text, "[email]");
+    IPV4_RE.replace_all(&text, "[ip]").into_owned()
+}
+
+/// Scrub events that reach Sentry without passing through [`report`],
+/// such as panics.
+fn scrub_event(event: &mut sentry::protocol::Event<'static>) {
+    event.user = None;
+    event.request = None;
+    if let Some(message) = &mut event.message {
+        *message = scrub(message);
+    }
+    for exception in &mut event.exception.values {
+        if let Some(value) = &mut exception.value {
+            *value = scrub(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrub_redacts_emails_ips_and_tokens() {
+        let scrubbed = scrub(
+            "login for alice@example.com from 203.0.113.9 failed \
+             (Authorization: Bearer abc.def-123)",
+        );
+        assert_eq!(
+            scrubbed,
+            "login for [email] from [ip] failed (Authorization: Bearer [redacted])"
+        );
+    }
+
+    #[test]
+    fn scrub_redacts_bare_jwts() {
+        let scrubbed = scrub("token eyJhbGciOiJFZERTQSJ9.eyJzdWIiOiIxIn0.c2ln rejected");
+        assert_eq!(scrubbed, "token [token] rejected");
+    }
+
+    #[test]
+    fn scrub_leaves_ordinary_text_alone() {
+        let text = "database error: relation \"files\" does not exist";
+        assert_eq!(scrub(text), text);
+    }
+
+    #[test]
+    fn scrub_event_drops_user_and_request() {
+        let mut event = sentry::protocol::Event {
+            message: Some("panic for bob@example.com".into()),
+            user: Some(sentry::User {
+                email: Some("bob@example.com".into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        scrub_event(&mut event);
+        assert!(event.user.is_none());
+        assert_eq!(event.message.as_deref(), Some("panic for [email]"));
+    }
+
+    #[test]
+    fn capture_without_init_is_a_no_op() {
+        capture_server_error(&OpenConvError::Internal("boom".into()));
+        capture_task_failure("test_task", &"boom");
+    }
+}
@@ -0,0 +1,105 @@
+//! Conditional GET for list endpoints.
+//!
+//! The ETag is a hash of the serialized body rather than a timestamp, since
+//! role and role-assignment rows carry no `updated_at`. The query still
+//! runs, but a client polling an unchanged list gets an empty 304 instead
+//! of the full body. Tags are weak: equal JSON, not byte-identical
+//! encodings, is what they promise.
+
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Weak ETag for a JSON body.
+fn etag_for(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    format!("W/\"{}\"", hex::encode(&digest[..16]))
+}
+
+/// Whether `If-None-Match` matches `etag` under the weak comparison.
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let ours = opaque(etag);
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|tag| tag.trim() == "*" || opaque(tag) == ours)
+}
+
+/// Respond with `body` as JSON and its ETag, or with 304 Not Modified when
+/// the client already holds this version.
+pub fn json_with_etag<T: Serialize>(headers: &HeaderMap, body: &T) -> Response {
+    let bytes = match serde_json::to_vec(body) {
+        Ok(bytes) => bytes,
+        // Let Json report the failure the usual way
+        Err(_) => return Json(body).into_response(),
+    };
+    let etag = etag_for(&bytes);
+    let etag_header = HeaderValue::from_str(&etag).expect("hex ETag is a valid header value");
+
+    if if_none_match(headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag_header)]).into_response();
+    }
+    (
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            ),
+            (header::ETAG, etag_header),
+        ],
+        bytes,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_if_none_match(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn returns_body_and_etag_without_condition() {
+        let resp = json_with_etag(&HeaderMap::new(), &vec![1, 2, 3]);
+        assert_eq!(resp.status(), StatusCode::OK);
+        let etag = resp.headers()[header::ETAG].to_str().unwrap();
+        assert!(etag.starts_with("W/\""), "{etag}");
+    }
+
+    #[test]
+    fn matching_etag_is_not_modified() {
+        let first = json_with_etag(&HeaderMap::new(), &vec![1, 2, 3]);
+        let etag = first.headers()[header::ETAG].to_str().unwrap().to_string();
+
+        let resp = json_with_etag(&with_if_none_match(&etag), &vec![1, 2, 3]);
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(resp.headers()[header::ETAG], etag.as_str());
+
+        // Strong form and lists of tags match too
+        let strong = etag.trim_start_matches("W/");
+        let resp = json_with_etag(&with_if_none_match(strong), &vec![1, 2, 3]);
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        let list = format!("\"other\", {etag}");
+        let resp = json_with_etag(&with_if_none_match(&list), &vec![1, 2, 3]);
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[test]
+    fn changed_body_gets_new_etag() {
+        let first = json_with_etag(&HeaderMap::new(), &vec![1, 2, 3]);
+        let etag = first.headers()[header::ETAG].to_str().unwrap().to_string();
+
+        let resp = json_with_etag(&with_if_none_match(&etag), &vec![1, 2, 4]);
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_ne!(resp.headers()[header::ETAG], etag.as_str());
+    }
+}
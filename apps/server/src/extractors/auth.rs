@@ -2,12 +2,17 @@ use axum::extract::FromRequestParts;
 use axum::http::request::Parts;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
-use axum::Json;
+use openconv_shared::error::{ErrorCode, OpenConvError};
 use openconv_shared::ids::{DeviceId, UserId};
 
+use crate::config::ServerConfig;
+use crate::error::ServerError;
 use crate::state::AppState;
 
-/// Authenticated user information extracted from a valid access JWT.
+/// Authenticated user information extracted from a valid access JWT, from
+/// a bot token (`Authorization: Bot <token>`), or from a bridge token acting
+/// as one of its puppets (`Authorization: Bridge <token>` with
+/// `X-Bridge-Puppet: <user_id>`).
 ///
 /// Use this as a handler parameter to require authentication:
 /// ```ignore
@@ -17,6 +22,54 @@ use crate::state::AppState;
 pub struct AuthUser {
     pub user_id: UserId,
     pub device_id: DeviceId,
+    /// Epoch seconds of the last step-up re-authentication, if the token has one.
+    pub reauth_at: Option<usize>,
+    /// Whether the caller is a bot account.
+    pub bot: bool,
+}
+
+impl AuthUser {
+    /// Require that the token was re-authenticated within `window_secs`.
+    /// Sensitive endpoints call this; clients answer the 403 by calling
+    /// `POST /api/auth/reauth` and retrying with the elevated token.
+    pub fn require_recent_auth(&self, window_secs: u64) -> Result<(), ServerError> {
+        if is_recent(self.reauth_at, window_secs, now_epoch()) {
+            Ok(())
+        } else {
+            Err(ServerError(OpenConvError::ReauthRequired))
+        }
+    }
+
+    /// Reject bot accounts, for account-level actions such as creating or
+    /// joining guilds.
+    pub fn require_human(&self) -> Result<(), ServerError> {
+        if self.bot {
+            Err(ServerError(OpenConvError::Forbidden))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Require that the user is an instance administrator
+    /// (listed in `admin_user_ids`).
+    pub fn require_instance_admin(&self, config: &ServerConfig) -> Result<(), ServerError> {
+        if config.admin_user_ids.contains(&self.user_id) {
+            Ok(())
+        } else {
+            Err(ServerError(OpenConvError::Forbidden))
+        }
+    }
+}
+
+fn now_epoch() -> usize {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("system time before UNIX epoch")
+        .as_secs() as usize
+}
+
+fn is_recent(reauth_at: Option<usize>, window_secs: u64, now: usize) -> bool {
+    reauth_at.is_some_and(|at| now.saturating_sub(at) <= window_secs as usize)
 }
 
 #[derive(Debug)]
@@ -26,7 +79,7 @@ impl IntoResponse for AuthRejection {
     fn into_response(self) -> Response {
         (
             StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({ "error": "unauthorized" })),
+            crate::error::error_body(ErrorCode::Unauthorized, "unauthorized", None),
         )
             .into_response()
     }
@@ -48,6 +101,27 @@ impl FromRequestParts<AppState> for AuthUser {
                 AuthRejection
             })?;
 
+        if let Some(token) = header.strip_prefix(crate::extractors::bot::SCHEME_PREFIX) {
+            let bot = crate::extractors::bot::authenticate(state, token).await?;
+            return Ok(AuthUser {
+                user_id: bot.user_id,
+                device_id: bot.device_id,
+                reauth_at: None,
+                bot: true,
+            });
+        }
+
+        if let Some(token) = header.strip_prefix(crate::extractors::bridge::SCHEME_PREFIX) {
+            let (user_id, device_id) =
+                crate::extractors::bridge::authenticate_puppet(state, token, parts).await?;
+            return Ok(AuthUser {
+                user_id,
+                device_id,
+                reauth_at: None,
+                bot: false,
+            });
+        }
+
         let token = header.strip_prefix("Bearer ").ok_or_else(|| {
             tracing::debug!("auth: Authorization header missing Bearer prefix");
             AuthRejection
@@ -61,7 +135,17 @@ impl FromRequestParts<AppState> for AuthUser {
         let user_id: UserId = claims.sub.parse().map_err(|_| AuthRejection)?;
         let device_id: DeviceId = claims.device_id.parse().map_err(|_| AuthRejection)?;
 
-        Ok(AuthUser { user_id, device_id })
+        if crate::revocation::is_access_token_rejected(&state.redis, &claims.jti, user_id).await {
+            tracing::debug!("auth: access token has been revoked or the user is suspended");
+            return Err(AuthRejection);
+        }
+
+        Ok(AuthUser {
+            user_id,
+            device_id,
+            reauth_at: claims.reauth_at,
+            bot: false,
+        })
     }
 }
 
@@ -83,6 +167,7 @@ mod tests {
             public_key_pem: TEST_PUBLIC_KEY_PEM.to_string(),
             access_token_ttl_seconds: 300,
             refresh_token_ttl_seconds: 604800,
+            ..Default::default()
         }
     }
 
@@ -96,20 +181,30 @@ mod tests {
             .connect_lazy("postgres://localhost/openconv_test")
             .unwrap();
         let redis_config = fred::types::config::Config::from_url("redis://localhost:6379").unwrap();
-        let redis = fred::clients::Pool::new(redis_config, None, None, None, 1).unwrap();
+        let redis = crate::redis::RedisPool::new(
+            fred::clients::Pool::new(redis_config, None, None, None, 1).unwrap(),
+            "",
+        );
         let config = Arc::new(ServerConfig {
             database_url: "postgres://localhost/openconv_test".to_string(),
             ..ServerConfig::default()
         });
         let email: Arc<dyn crate::email::EmailService> = Arc::new(MockEmailService::new());
+        let outbox_key = crate::email::outbox::OutboxKey::from_config(&config);
         AppState {
             db,
+            db_replica: None,
+            live: Arc::new(crate::live_config::LiveConfig::new(&config)),
             config,
             redis,
             jwt,
             email,
+            outbox_key,
             object_store: std::sync::Arc::new(object_store::memory::InMemory::new()),
+            scanner: std::sync::Arc::new(crate::scan::NoopScanner),
+            jobs: Default::default(),
             ws: std::sync::Arc::new(crate::ws::state::WsState::new()),
+            ip_denylist: std::sync::Arc::new(crate::ip_denylist::IpDenylist::new(Vec::new())),
         }
     }
 
@@ -133,6 +228,80 @@ mod tests {
         assert_eq!(auth.device_id, did);
     }
 
+    #[tokio::test]
+    async fn auth_user_extractor_exposes_reauth_at_from_elevated_token() {
+        let state = test_app_state();
+        let (token, _) = state
+            .jwt
+            .issue_elevated_access_token(&UserId::new(), &DeviceId::new())
+            .unwrap();
+
+        let request = axum::http::Request::builder()
+            .header("Authorization", format!("Bearer {token}"))
+            .body(())
+            .unwrap();
+        let (mut parts, _) = request.into_parts();
+
+        let auth = AuthUser::from_request_parts(&mut parts, &state)
+            .await
+            .unwrap();
+        assert!(auth.reauth_at.is_some());
+        assert!(auth.require_recent_auth(300).is_ok());
+    }
+
+    #[test]
+    fn is_recent_requires_reauth_within_window() {
+        assert!(!is_recent(None, 300, 1_000));
+        assert!(is_recent(Some(900), 300, 1_000));
+        assert!(is_recent(Some(700), 300, 1_000));
+        assert!(!is_recent(Some(699), 300, 1_000));
+    }
+
+    #[test]
+    fn require_recent_auth_rejects_plain_token() {
+        let auth = AuthUser {
+            user_id: UserId::new(),
+            device_id: DeviceId::new(),
+            reauth_at: None,
+            bot: false,
+        };
+        let err = auth.require_recent_auth(300).unwrap_err();
+        assert!(matches!(err.0, OpenConvError::ReauthRequired));
+    }
+
+    #[test]
+    fn require_human_rejects_bots() {
+        let mut auth = AuthUser {
+            user_id: UserId::new(),
+            device_id: DeviceId::new(),
+            reauth_at: None,
+            bot: false,
+        };
+        assert!(auth.require_human().is_ok());
+        auth.bot = true;
+        assert!(matches!(
+            auth.require_human().unwrap_err().0,
+            OpenConvError::Forbidden
+        ));
+    }
+
+    #[test]
+    fn require_instance_admin_checks_config_list() {
+        let auth = AuthUser {
+            user_id: UserId::new(),
+            device_id: DeviceId::new(),
+            reauth_at: None,
+            bot: false,
+        };
+        let mut config = ServerConfig::default();
+        assert!(matches!(
+            auth.require_instance_admin(&config).unwrap_err().0,
+            OpenConvError::Forbidden
+        ));
+        config.admin_user_ids.push(auth.user_id);
+        assert!(auth.require_instance_admin(&config).is_ok());
+    }
+
     #[tokio::test]
     async fn auth_user_extractor_returns_401_when_header_missing() {
         let state = test_app_state();
@@ -160,6 +329,7 @@ mod tests {
             exp: 1000, // epoch + 1000s, clearly expired
             iat: 900,
             jti: uuid::Uuid::new_v4().to_string(),
+            reauth_at: None,
         };
         let encoding_key = EncodingKey::from_ed_pem(TEST_PRIVATE_KEY_PEM.as_bytes()).unwrap();
         let token =
@@ -0,0 +1,69 @@
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use openconv_shared::ids::{DeviceId, GuildId, UserId};
+
+use crate::extractors::auth::AuthRejection;
+use crate::repo;
+use crate::secret_token;
+use crate::state::AppState;
+
+/// Authorization scheme for bot tokens: `Authorization: Bot <token>`.
+pub const SCHEME_PREFIX: &str = "Bot ";
+
+/// A bot authenticated by its long-lived token.
+///
+/// Only accepts `Bot` tokens. Endpoints open to both humans and bots take
+/// [`AuthUser`](crate::extractors::auth::AuthUser), which also accepts them.
+#[derive(Debug, Clone)]
+pub struct BotUser {
+    pub user_id: UserId,
+    pub device_id: DeviceId,
+    /// The guild that owns the bot.
+    pub guild_id: GuildId,
+}
+
+/// Validate a bot token, returning the bot it belongs to.
+pub(crate) async fn authenticate(state: &AppState, token: &str) -> Result<BotUser, AuthRejection> {
+    let identity = repo::bots::authenticate(&state.db, &secret_token::hash(token))
+        .await
+        .map_err(|e| {
+            tracing::error!(error = %e, "auth: bot token lookup failed");
+            AuthRejection
+        })?
+        .ok_or_else(|| {
+            tracing::debug!("auth: unknown bot token");
+            AuthRejection
+        })?;
+
+    if crate::revocation::is_user_suspended(&state.redis, identity.user_id).await {
+        tracing::debug!("auth: bot is suspended");
+        return Err(AuthRejection);
+    }
+
+    Ok(BotUser {
+        user_id: identity.user_id,
+        device_id: identity.device_id,
+        guild_id: identity.guild_id,
+    })
+}
+
+impl FromRequestParts<AppState> for BotUser {
+    type Rejection = AuthRejection;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(axum::http::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|h| h.strip_prefix(SCHEME_PREFIX))
+            .ok_or_else(|| {
+                tracing::debug!("auth: Authorization header missing Bot prefix");
+                AuthRejection
+            })?;
+
+        authenticate(state, token).await
+    }
+}
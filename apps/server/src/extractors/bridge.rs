@@ -0,0 +1,96 @@
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use openconv_shared::ids::{DeviceId, UserId};
+
+use crate::extractors::auth::AuthRejection;
+use crate::repo;
+use crate::secret_token;
+use crate::state::AppState;
+
+/// Authorization scheme for bridge tokens: `Authorization: Bridge <token>`.
+pub const SCHEME_PREFIX: &str = "Bridge ";
+
+/// Header naming the puppet a bridge request acts as. With it, endpoints
+/// taking [`AuthUser`](crate::extractors::auth::AuthUser) see the puppet as
+/// the caller.
+pub const PUPPET_HEADER: &str = "x-bridge-puppet";
+
+/// A bridge application authenticated by its token, acting as itself.
+#[derive(Debug, Clone)]
+pub struct BridgeApp {
+    pub bridge_id: uuid::Uuid,
+}
+
+/// Validate a bridge token, returning the bridge it belongs to.
+async fn authenticate(state: &AppState, token: &str) -> Result<uuid::Uuid, AuthRejection> {
+    repo::bridges::authenticate(&state.db, &secret_token::hash(token))
+        .await
+        .map_err(|e| {
+            tracing::error!(error = %e, "auth: bridge token lookup failed");
+            AuthRejection
+        })?
+        .ok_or_else(|| {
+            tracing::debug!("auth: unknown bridge token");
+            AuthRejection
+        })
+}
+
+/// Validate a bridge token and the puppet named in [`PUPPET_HEADER`],
+/// returning the puppet's user and device.
+pub(crate) async fn authenticate_puppet(
+    state: &AppState,
+    token: &str,
+    parts: &Parts,
+) -> Result<(UserId, DeviceId), AuthRejection> {
+    let user_id: UserId = parts
+        .headers
+        .get(PUPPET_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| {
+            tracing::debug!("auth: bridge request without a valid puppet header");
+            AuthRejection
+        })?;
+    let bridge_id = authenticate(state, token).await?;
+
+    let device_id = repo::bridges::puppet_device(&state.db, bridge_id, user_id)
+        .await
+        .map_err(|e| {
+            tracing::error!(error = %e, "auth: puppet lookup failed");
+            AuthRejection
+        })?
+        .ok_or_else(|| {
+            tracing::debug!("auth: puppet does not belong to bridge");
+            AuthRejection
+        })?;
+
+    if crate::revocation::is_user_suspended(&state.redis, user_id).await {
+        tracing::debug!("auth: puppet is suspended");
+        return Err(AuthRejection);
+    }
+
+    Ok((user_id, device_id))
+}
+
+impl FromRequestParts<AppState> for BridgeApp {
+    type Rejection = AuthRejection;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(axum::http::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|h| h.strip_prefix(SCHEME_PREFIX))
+            .ok_or_else(|| {
+                tracing::debug!("auth: Authorization header missing Bridge prefix");
+                AuthRejection
+            })?;
+
+        Ok(BridgeApp {
+            bridge_id: authenticate(state, token).await?,
+        })
+    }
+}
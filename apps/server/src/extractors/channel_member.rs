@@ -6,7 +6,7 @@ use std::collections::HashMap;
 
 use crate::error::ServerError;
 use crate::extractors::auth::AuthUser;
-use crate::extractors::guild_member::{resolve_guild_membership, GuildMemberRejection};
+use crate::extractors::guild_member::{cached_guild_membership, GuildMemberRejection};
 use crate::state::AppState;
 
 /// Extracted channel membership info with resolved permissions.
@@ -62,7 +62,7 @@ impl FromRequestParts<AppState> for ChannelMember {
             .map_err(|e| GuildMemberRejection::Internal(e.to_string()))?
             .ok_or(GuildMemberRejection::NotFound)?;
 
-        let permissions = resolve_guild_membership(&state.db, auth.user_id, guild_id).await?;
+        let permissions = cached_guild_membership(state, auth.user_id, guild_id).await?;
 
         let member = ChannelMember {
             user_id: auth.user_id,
@@ -0,0 +1,104 @@
+use std::convert::Infallible;
+
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::request::Parts;
+
+use crate::state::AppState;
+
+/// Network metadata about the calling client, recorded on sessions.
+///
+/// Never rejects: fields are `None` when the information is unavailable.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    /// ISO 3166-1 alpha-2 country code from the configured edge proxy header.
+    pub country: Option<String>,
+}
+
+impl FromRequestParts<AppState> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let country = state
+            .config
+            .geo_country_header
+            .as_deref()
+            .and_then(|name| parts.headers.get(name))
+            .and_then(|v| v.to_str().ok())
+            .and_then(normalize_country);
+
+        Ok(ClientInfo {
+            ip: client_ip(parts),
+            country,
+        })
+    }
+}
+
+/// Same precedence as the rate limiter: socket address, then proxy headers.
+fn client_ip(parts: &Parts) -> Option<String> {
+    if let Some(ConnectInfo(addr)) = parts.extensions.get::<ConnectInfo<std::net::SocketAddr>>() {
+        return Some(addr.ip().to_string());
+    }
+    let header = |name: &str| {
+        parts
+            .headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.split(',').next().unwrap_or("").trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+    header("x-forwarded-for").or_else(|| header("x-real-ip"))
+}
+
+/// Accept only two ASCII letters; proxies use "XX"/"T1" for unknown/Tor.
+fn normalize_country(raw: &str) -> Option<String> {
+    let code = raw.trim();
+    if code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+        let upper = code.to_ascii_uppercase();
+        (upper != "XX").then_some(upper)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+
+    fn parts_with_headers(headers: &[(&str, &str)]) -> Parts {
+        let mut builder = Request::builder();
+        for (k, v) in headers {
+            builder = builder.header(*k, *v);
+        }
+        builder.body(()).unwrap().into_parts().0
+    }
+
+    #[test]
+    fn client_ip_uses_first_forwarded_address() {
+        let parts = parts_with_headers(&[("x-forwarded-for", "203.0.113.7, 10.0.0.1")]);
+        assert_eq!(client_ip(&parts).as_deref(), Some("203.0.113.7"));
+    }
+
+    #[test]
+    fn client_ip_falls_back_to_real_ip() {
+        let parts = parts_with_headers(&[("x-real-ip", "198.51.100.2")]);
+        assert_eq!(client_ip(&parts).as_deref(), Some("198.51.100.2"));
+    }
+
+    #[test]
+    fn client_ip_none_without_headers() {
+        assert!(client_ip(&parts_with_headers(&[])).is_none());
+    }
+
+    #[test]
+    fn normalize_country_accepts_two_letter_codes() {
+        assert_eq!(normalize_country("de").as_deref(), Some("DE"));
+        assert!(normalize_country("XX").is_none());
+        assert!(normalize_country("USA").is_none());
+        assert!(normalize_country("1A").is_none());
+    }
+}
@@ -2,14 +2,14 @@ use axum::extract::{FromRequestParts, Path};
 use axum::http::request::Parts;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
-use axum::Json;
-use openconv_shared::error::OpenConvError;
+use openconv_shared::error::{ErrorCode, OpenConvError};
 use openconv_shared::ids::{DeviceId, GuildId, UserId};
 use openconv_shared::permissions::{self, Permissions};
 use std::collections::HashMap;
 
 use crate::error::ServerError;
 use crate::extractors::auth::AuthUser;
+use crate::permission_cache;
 use crate::state::AppState;
 
 /// Extracted guild membership info with resolved permissions.
@@ -43,23 +43,32 @@ pub enum GuildMemberRejection {
 
 impl IntoResponse for GuildMemberRejection {
     fn into_response(self) -> Response {
-        let (status, message) = match &self {
-            Self::Unauthenticated => (StatusCode::UNAUTHORIZED, "unauthorized"),
-            Self::Forbidden => (StatusCode::FORBIDDEN, "forbidden"),
-            Self::NotFound => (StatusCode::NOT_FOUND, "not found"),
+        let (status, code, message) = match &self {
+            Self::Unauthenticated => (
+                StatusCode::UNAUTHORIZED,
+                ErrorCode::Unauthorized,
+                "unauthorized",
+            ),
+            Self::Forbidden => (StatusCode::FORBIDDEN, ErrorCode::Forbidden, "forbidden"),
+            Self::NotFound => (StatusCode::NOT_FOUND, ErrorCode::NotFound, "not found"),
             Self::Internal(e) => {
                 tracing::error!(error = %e, "guild member extractor error");
-                (StatusCode::INTERNAL_SERVER_ERROR, "internal error")
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::InternalError,
+                    "internal error",
+                )
             }
         };
-        (status, Json(serde_json::json!({ "error": message }))).into_response()
+        (status, crate::error::error_body(code, message, None)).into_response()
     }
 }
 
 /// Resolve guild membership and permissions for a user.
 ///
 /// Runs a single JOIN query across `guild_members`, `guild_member_roles`, and `roles`
-/// to fetch all role permissions, then ORs them together via `resolve()`.
+/// to fetch all role permissions, then ORs them together via `resolve()`. For bot
+/// accounts the result is capped by the permissions granted to the bot.
 ///
 /// Returns `Forbidden` if the user is not a guild member (or has no roles).
 pub(crate) async fn resolve_guild_membership(
@@ -67,11 +76,12 @@ pub(crate) async fn resolve_guild_membership(
     user_id: UserId,
     guild_id: GuildId,
 ) -> Result<Permissions, GuildMemberRejection> {
-    let role_perms: Vec<i64> = sqlx::query_scalar(
-        "SELECT r.permissions \
+    let rows: Vec<(i64, Option<i64>)> = sqlx::query_as(
+        "SELECT r.permissions, b.permissions \
          FROM guild_members gm \
          JOIN guild_member_roles gmr ON gmr.user_id = gm.user_id AND gmr.guild_id = gm.guild_id \
          JOIN roles r ON r.id = gmr.role_id \
+         LEFT JOIN bots b ON b.user_id = gm.user_id AND b.guild_id = gm.guild_id \
          WHERE gm.user_id = $1 AND gm.guild_id = $2",
     )
     .bind(user_id)
@@ -80,16 +90,36 @@ pub(crate) async fn resolve_guild_membership(
     .await
     .map_err(|e| GuildMemberRejection::Internal(e.to_string()))?;
 
-    if role_perms.is_empty() {
+    let Some(&(_, bot_cap)) = rows.first() else {
         return Err(GuildMemberRejection::Forbidden);
-    }
+    };
 
-    let perms: Vec<Permissions> = role_perms
+    let perms: Vec<Permissions> = rows
         .into_iter()
-        .map(|bits| Permissions::from_bits_truncate(bits as u64))
+        .map(|(bits, _)| Permissions::from_bits_truncate(bits as u64))
         .collect();
 
-    Ok(permissions::resolve(&perms))
+    let resolved = permissions::resolve(&perms);
+    Ok(match bot_cap {
+        Some(cap) => {
+            resolved & permissions::resolve(&[Permissions::from_bits_truncate(cap as u64)])
+        }
+        None => resolved,
+    })
+}
+
+/// [`resolve_guild_membership`] behind the Redis permission cache.
+pub(crate) async fn cached_guild_membership(
+    state: &AppState,
+    user_id: UserId,
+    guild_id: GuildId,
+) -> Result<Permissions, GuildMemberRejection> {
+    if let Some(perms) = permission_cache::get(&state.redis, user_id, guild_id).await {
+        return Ok(perms);
+    }
+    let perms = resolve_guild_membership(&state.db, user_id, guild_id).await?;
+    permission_cache::put(&state.redis, user_id, guild_id, perms).await;
+    Ok(perms)
 }
 
 impl FromRequestParts<AppState> for GuildMember {
@@ -113,7 +143,7 @@ impl FromRequestParts<AppState> for GuildMember {
             .parse()
             .map_err(|_| GuildMemberRejection::NotFound)?;
 
-        let permissions = resolve_guild_membership(&state.db, auth.user_id, guild_id).await?;
+        let permissions = cached_guild_membership(state, auth.user_id, guild_id).await?;
 
         let member = GuildMember {
             user_id: auth.user_id,
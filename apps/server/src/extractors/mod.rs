@@ -1,3 +1,7 @@
 pub mod auth;
+pub mod bot;
+pub mod bridge;
 pub mod channel_member;
+pub mod client_info;
 pub mod guild_member;
+pub mod oauth;
@@ -0,0 +1,94 @@
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use openconv_shared::api::oauth::OAuthScope;
+use openconv_shared::error::OpenConvError;
+use openconv_shared::ids::UserId;
+
+use crate::error::ServerError;
+use crate::extractors::auth::AuthRejection;
+use crate::oauth::scopes_from_db;
+use crate::repo;
+use crate::secret_token;
+use crate::state::AppState;
+
+/// A third-party app acting for a user with an OAuth access token.
+///
+/// OAuth tokens are opaque and are only accepted by the `/api/oauth/@me`
+/// endpoints; [`AuthUser`](crate::extractors::auth::AuthUser) rejects them.
+#[derive(Debug, Clone)]
+pub struct OAuthUser {
+    pub user_id: UserId,
+    pub client_id: uuid::Uuid,
+    pub scopes: Vec<OAuthScope>,
+}
+
+impl OAuthUser {
+    /// Require that the token was granted `scope`.
+    pub fn require(&self, scope: OAuthScope) -> Result<(), ServerError> {
+        if self.scopes.contains(&scope) {
+            Ok(())
+        } else {
+            Err(ServerError(OpenConvError::Forbidden))
+        }
+    }
+}
+
+impl FromRequestParts<AppState> for OAuthUser {
+    type Rejection = AuthRejection;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(axum::http::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .ok_or_else(|| {
+                tracing::debug!("oauth: missing Bearer token");
+                AuthRejection
+            })?;
+
+        let grant = repo::oauth::find_access_token(&state.db, &secret_token::hash(token))
+            .await
+            .map_err(|e| {
+                tracing::error!(error = %e, "oauth: token lookup failed");
+                AuthRejection
+            })?
+            .ok_or_else(|| {
+                tracing::debug!("oauth: unknown or expired access token");
+                AuthRejection
+            })?;
+
+        if crate::revocation::is_user_suspended(&state.redis, grant.user_id).await {
+            tracing::debug!("oauth: user is suspended");
+            return Err(AuthRejection);
+        }
+
+        Ok(OAuthUser {
+            user_id: grant.user_id,
+            client_id: grant.client_id,
+            scopes: scopes_from_db(&grant.scopes),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn require_checks_granted_scopes() {
+        let user = OAuthUser {
+            user_id: UserId::new(),
+            client_id: uuid::Uuid::new_v4(),
+            scopes: vec![OAuthScope::Identify],
+        };
+        assert!(user.require(OAuthScope::Identify).is_ok());
+        assert!(matches!(
+            user.require(OAuthScope::GuildsRead).unwrap_err().0,
+            OpenConvError::Forbidden
+        ));
+    }
+}
@@ -0,0 +1,158 @@
+//! Applying transactions received from other instances.
+//!
+//! Every event is checked against the sender: a server may only speak for
+//! its own users, and only a guild's host may report who is in it. Events
+//! that fail these checks, or that this instance cannot act on (an expired
+//! invite, a ban), are skipped without failing the rest of the transaction.
+
+use fred::interfaces::KeysInterface;
+use fred::types::{Expiration, SetOptions};
+use openconv_shared::api::gateway::{MemberEvent, MemberEventKind};
+use openconv_shared::error::OpenConvError;
+use openconv_shared::federation::{FederationEvent, ServerName, Transaction};
+
+use super::signing::TRANSACTION_TTL_SECS;
+use crate::error::ServerError;
+use crate::member_events;
+use crate::redis::RedisPool;
+use crate::repo;
+use crate::state::AppState;
+
+fn db_err(e: sqlx::Error) -> ServerError {
+    tracing::error!(error = %e, "database error");
+    ServerError(OpenConvError::Internal("database error".into()))
+}
+
+/// Mark a transaction as seen. Returns false if it already was, so a
+/// replayed or retried transaction is applied once. Fails open when Redis
+/// is unavailable: every event is safe to apply twice.
+async fn claim(redis: &RedisPool, origin: &ServerName, jti: uuid::Uuid) -> bool {
+    match redis
+        .set::<Option<String>, _, _>(
+            redis.key(format_args!("fed:txn:{origin}:{jti}")),
+            "1",
+            Some(Expiration::EX(2 * TRANSACTION_TTL_SECS as i64)),
+            Some(SetOptions::NX),
+            false,
+        )
+        .await
+    {
+        Ok(reply) => reply.is_some(),
+        Err(e) => {
+            tracing::warn!(error = %e, "federation replay check failed, applying anyway");
+            true
+        }
+    }
+}
+
+/// Apply a verified transaction from `origin`. Returns the number of
+/// events applied.
+pub async fn apply(
+    state: &AppState,
+    origin: &ServerName,
+    txn: Transaction,
+) -> Result<usize, ServerError> {
+    if !claim(&state.redis, origin, txn.jti).await {
+        tracing::debug!(%origin, jti = %txn.jti, "dropping repeated federation transaction");
+        return Ok(0);
+    }
+
+    let mut accepted = 0;
+    for event in txn.events {
+        match apply_event(state, origin, event).await {
+            Ok(true) => accepted += 1,
+            Ok(false) => {}
+            Err(e) => {
+                tracing::info!(%origin, error = %e.0, "skipped federation event");
+            }
+        }
+    }
+    Ok(accepted)
+}
+
+async fn apply_event(
+    state: &AppState,
+    origin: &ServerName,
+    event: FederationEvent,
+) -> Result<bool, ServerError> {
+    let local = super::local_server_name(&state.config.federation);
+    match event {
+        FederationEvent::JoinRequest {
+            user,
+            display_name,
+            invite_code,
+        } => {
+            if &user.server != origin {
+                return Ok(false);
+            }
+            let display_name = crate::validation::validate_display_name(&display_name)?;
+            let mut tx = state.db.begin().await.map_err(db_err)?;
+            let user_id = repo::federation::map_remote_user(&mut tx, &user, &display_name)
+                .await
+                .map_err(db_err)?;
+            tx.commit().await.map_err(db_err)?;
+
+            let guild_id =
+                crate::handlers::invites::join_with_invite(state, user_id, &invite_code).await?;
+            tracing::info!(%user, %guild_id, "remote user joined guild");
+            Ok(true)
+        }
+        FederationEvent::LeaveRequest { user, guild_id } => {
+            if &user.server != origin {
+                return Ok(false);
+            }
+            let Some(user_id) = repo::federation::find_remote_user(&state.db, &user)
+                .await
+                .map_err(db_err)?
+            else {
+                return Ok(false);
+            };
+            if !repo::guilds::is_member(&state.db, guild_id, user_id)
+                .await
+                .map_err(db_err)?
+            {
+                return Ok(false);
+            }
+            repo::guilds::remove_member(&state.db, guild_id, user_id)
+                .await
+                .map_err(db_err)?;
+            member_events::publish(
+                state,
+                MemberEvent::new(guild_id, user_id, MemberEventKind::Leave),
+            )
+            .await;
+            Ok(true)
+        }
+        FederationEvent::MemberJoined {
+            guild_id,
+            guild_name,
+            user,
+        } => {
+            if user.server != local
+                || !repo::federation::is_local_user(&state.db, user.user_id)
+                    .await
+                    .map_err(db_err)?
+            {
+                return Ok(false);
+            }
+            repo::federation::upsert_membership(
+                &state.db,
+                user.user_id,
+                origin,
+                guild_id,
+                &guild_name,
+            )
+            .await
+            .map_err(db_err)?;
+            Ok(true)
+        }
+        FederationEvent::MemberLeft { guild_id, user } => {
+            if user.server != local {
+                return Ok(false);
+            }
+            repo::federation::delete_membership(&state.db, user.user_id, origin, guild_id)
+                .await
+                .map_err(db_err)
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Server-to-server federation (experimental, behind the `federation`
+//! feature).
+//!
+//! A guild lives on one instance, its host. Users of other instances join
+//! it through their own server, which asks the host to admit them; the host
+//! then holds a local stand-in account for each of them (see
+//! [`repo::federation`](crate::repo::federation)) so memberships, roles and
+//! bans work unchanged.
+//!
+//! Instances talk over HTTPS under
+//! [`FEDERATION_PATH_PREFIX`](openconv_shared::federation::FEDERATION_PATH_PREFIX):
+//! - `GET /keys` publishes the instance's Ed25519 signing key.
+//! - `POST /transactions` accepts a batch of events as a compact EdDSA JWS,
+//!   verified against the sender's published key.
+//!
+//! Outgoing events are queued in `federation_outbox` and delivered by
+//! [`outbox::FederationDeliveryJob`] with retries. Only servers listed in
+//! `federation.trusted_servers` are talked to in either direction.
+//!
+//! This first cut federates guild membership: remote joins with an invite,
+//! leaves, and the host forwarding member events to every instance with
+//! members in the guild. Messages are not federated yet.
+
+pub mod inbound;
+pub mod outbox;
+pub mod peers;
+pub mod signing;
+
+use openconv_shared::federation::ServerName;
+
+use crate::config::FederationConfig;
+
+/// This instance's server name. Only call once the config has been
+/// validated with federation enabled.
+pub fn local_server_name(config: &FederationConfig) -> ServerName {
+    config
+        .server_name
+        .parse()
+        .expect("federation.server_name is validated at startup")
+}
+
+/// Whether `server` may federate with this instance.
+pub fn is_trusted(config: &FederationConfig, server: &ServerName) -> bool {
+    config
+        .trusted_servers
+        .iter()
+        .any(|trusted| trusted == server.as_str())
+}
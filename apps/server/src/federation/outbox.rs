@@ -0,0 +1,215 @@
+//! Outgoing federation events.
+//!
+//! Events are queued in `federation_outbox`, one row per transaction, and
+//! delivered by [`FederationDeliveryJob`] the same way guild webhooks are:
+//! due rows are claimed with `FOR UPDATE SKIP LOCKED`, leased, and retried
+//! with the same backoff. The row ID is the transaction's `jti`, so a
+//! retry of a transaction the peer already applied is dropped there.
+
+use openconv_shared::api::gateway::{MemberEvent, MemberEventKind};
+use openconv_shared::federation::{FederationEvent, RemoteUserId, ServerName};
+use sqlx::PgPool;
+
+use super::signing::FederationKeys;
+use crate::repo::federation as repo;
+use crate::state::AppState;
+use crate::tasks::scheduler::{JobResult, PeriodicJob, Schedule};
+use crate::tasks::webhook_delivery::retry_backoff;
+
+/// Transactions claimed per run.
+const DELIVERY_BATCH_SIZE: i64 = 50;
+
+/// Attempts before a transaction is marked failed.
+pub const MAX_DELIVERY_ATTEMPTS: i32 = 8;
+
+/// How long a claimed transaction stays invisible to other workers.
+const CLAIM_LEASE_SECS: f64 = 300.0;
+
+/// Queue `events` for a peer, skipping peers that are not trusted.
+pub async fn enqueue(
+    state: &AppState,
+    destination: &ServerName,
+    events: &[FederationEvent],
+) -> Result<(), sqlx::Error> {
+    if !super::is_trusted(&state.config.federation, destination) {
+        tracing::debug!(%destination, "not federating with untrusted server");
+        return Ok(());
+    }
+    repo::enqueue(&state.db, destination, events).await?;
+    Ok(())
+}
+
+/// Tell every instance with members in a guild hosted here, and the
+/// subject's own instance, that someone joined or left it. Role changes are
+/// not federated.
+pub async fn forward_member_event(state: &AppState, event: &MemberEvent) {
+    if let Err(e) = try_forward_member_event(state, event).await {
+        tracing::error!(error = %e, guild_id = %event.guild_id, "failed to queue federation events");
+    }
+}
+
+async fn try_forward_member_event(
+    state: &AppState,
+    event: &MemberEvent,
+) -> Result<(), sqlx::Error> {
+    let local = super::local_server_name(&state.config.federation);
+    let remote = repo::remote_identity(&state.db, event.user_id).await?;
+    let user = remote.clone().unwrap_or(RemoteUserId {
+        user_id: event.user_id,
+        server: local,
+    });
+
+    let federated = match event.kind {
+        MemberEventKind::Join => {
+            let guild_name: Option<String> =
+                sqlx::query_scalar("SELECT name FROM guilds WHERE id = $1")
+                    .bind(event.guild_id)
+                    .fetch_optional(&state.db)
+                    .await?;
+            let Some(guild_name) = guild_name else {
+                return Ok(());
+            };
+            FederationEvent::MemberJoined {
+                guild_id: event.guild_id,
+                guild_name,
+                user,
+            }
+        }
+        MemberEventKind::Leave | MemberEventKind::Kick | MemberEventKind::Ban => {
+            FederationEvent::MemberLeft {
+                guild_id: event.guild_id,
+                user,
+            }
+        }
+        MemberEventKind::RoleAdded | MemberEventKind::RoleRemoved => return Ok(()),
+    };
+
+    let mut destinations: Vec<ServerName> =
+        repo::remote_servers_in_guild(&state.db, event.guild_id)
+            .await?
+            .into_iter()
+            .filter_map(|s| s.parse().ok())
+            .collect();
+    // Someone who just left is no longer counted above.
+    if let Some(remote) = remote {
+        if !destinations.contains(&remote.server) {
+            destinations.push(remote.server);
+        }
+    }
+    for destination in &destinations {
+        enqueue(state, destination, std::slice::from_ref(&federated)).await?;
+    }
+    Ok(())
+}
+
+/// Deliver every due transaction. Returns the number delivered.
+pub async fn deliver_pending(
+    pool: &PgPool,
+    keys: &FederationKeys,
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let due = sqlx::query_as::<_, DueTransaction>(
+        "UPDATE federation_outbox \
+         SET attempts = attempts + 1, \
+             next_attempt_at = NOW() + make_interval(secs => $2) \
+         WHERE id IN ( \
+             SELECT id FROM federation_outbox \
+             WHERE status = 'pending' AND next_attempt_at <= NOW() \
+             ORDER BY next_attempt_at \
+             LIMIT $1 \
+             FOR UPDATE SKIP LOCKED \
+         ) \
+         RETURNING id, destination, events, attempts",
+    )
+    .bind(DELIVERY_BATCH_SIZE)
+    .bind(CLAIM_LEASE_SECS)
+    .fetch_all(pool)
+    .await?;
+
+    let mut delivered = 0u64;
+    for txn in due {
+        match send(keys, &txn).await {
+            Ok(()) => {
+                sqlx::query(
+                    "UPDATE federation_outbox \
+                     SET status = 'delivered', delivered_at = NOW(), last_error = NULL \
+                     WHERE id = $1",
+                )
+                .bind(txn.id)
+                .execute(pool)
+                .await?;
+                delivered += 1;
+            }
+            Err(error) => {
+                tracing::warn!(
+                    transaction_id = %txn.id,
+                    destination = %txn.destination,
+                    attempts = txn.attempts,
+                    %error,
+                    "federation delivery failed"
+                );
+                if txn.attempts >= MAX_DELIVERY_ATTEMPTS {
+                    sqlx::query(
+                        "UPDATE federation_outbox SET status = 'failed', last_error = $2 \
+                         WHERE id = $1",
+                    )
+                    .bind(txn.id)
+                    .bind(&error)
+                    .execute(pool)
+                    .await?;
+                } else {
+                    sqlx::query(
+                        "UPDATE federation_outbox \
+                         SET next_attempt_at = NOW() + make_interval(secs => $2), last_error = $3 \
+                         WHERE id = $1",
+                    )
+                    .bind(txn.id)
+                    .bind(retry_backoff(txn.attempts).as_secs_f64())
+                    .bind(&error)
+                    .execute(pool)
+                    .await?;
+                }
+            }
+        }
+    }
+
+    Ok(delivered)
+}
+
+#[derive(sqlx::FromRow)]
+struct DueTransaction {
+    id: uuid::Uuid,
+    destination: String,
+    events: serde_json::Value,
+    attempts: i32,
+}
+
+async fn send(keys: &FederationKeys, txn: &DueTransaction) -> Result<(), String> {
+    let destination: ServerName = txn.destination.parse().map_err(|e| format!("{e}"))?;
+    let events: Vec<FederationEvent> =
+        serde_json::from_value(txn.events.clone()).map_err(|e| e.to_string())?;
+    let token = keys
+        .sign(&destination, txn.id, events)
+        .map_err(|e| e.to_string())?;
+    super::peers::send_transaction(&destination, token).await
+}
+
+/// [`deliver_pending`] every 5 seconds.
+pub struct FederationDeliveryJob {
+    pub pool: PgPool,
+    pub keys: FederationKeys,
+}
+
+#[async_trait::async_trait]
+impl PeriodicJob for FederationDeliveryJob {
+    fn name(&self) -> &'static str {
+        "federation_delivery"
+    }
+
+    fn schedule(&self) -> Schedule {
+        Schedule::every_secs(5)
+    }
+
+    async fn run(&self) -> JobResult {
+        deliver_pending(&self.pool, &self.keys).await
+    }
+}
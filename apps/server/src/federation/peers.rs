@@ -0,0 +1,108 @@
+//! Talking to other instances: key discovery and sending transactions.
+//!
+//! Peers' keys are cached in Redis for [`KEY_CACHE_TTL`]. A peer that
+//! rotates its key is picked up once the entry expires; a transaction
+//! signed with an unknown key ID refetches right away.
+
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use fred::interfaces::KeysInterface;
+use fred::types::Expiration;
+use openconv_shared::federation::{
+    ServerKey, ServerKeysResponse, ServerName, FEDERATION_PATH_PREFIX,
+};
+
+use crate::redis::RedisPool;
+
+/// How long a peer's published keys are trusted without refetching.
+pub const KEY_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Per-request timeout for peer requests.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .expect("federation HTTP client")
+});
+
+/// Base URL of a peer's federation API.
+pub fn base_url(server: &ServerName) -> String {
+    format!("https://{server}{FEDERATION_PATH_PREFIX}")
+}
+
+fn cache_key(server: &ServerName) -> String {
+    format!("fed:keys:{server}")
+}
+
+/// A peer's signing keys, from cache unless `refresh` is set.
+pub async fn keys_for(
+    redis: &RedisPool,
+    server: &ServerName,
+    refresh: bool,
+) -> Result<Vec<ServerKey>, String> {
+    if !refresh {
+        match redis
+            .get::<Option<String>, _>(redis.key(cache_key(server)))
+            .await
+        {
+            Ok(Some(cached)) => {
+                if let Ok(keys) = serde_json::from_str(&cached) {
+                    return Ok(keys);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!(error = %e, %server, "federation key cache read failed"),
+        }
+    }
+
+    let response = CLIENT
+        .get(format!("{}/keys", base_url(server)))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("key fetch responded with {}", response.status()));
+    }
+    let published: ServerKeysResponse = response.json().await.map_err(|e| e.to_string())?;
+    if &published.server_name != server {
+        return Err(format!(
+            "server at {server} published keys for {}",
+            published.server_name
+        ));
+    }
+
+    let encoded = serde_json::to_string(&published.keys).expect("ServerKey serializes");
+    if let Err(e) = redis
+        .set::<(), _, _>(
+            redis.key(cache_key(server)),
+            encoded,
+            Some(Expiration::EX(KEY_CACHE_TTL.as_secs() as i64)),
+            None,
+            false,
+        )
+        .await
+    {
+        tracing::warn!(error = %e, %server, "federation key cache write failed");
+    }
+    Ok(published.keys)
+}
+
+/// POST a signed transaction to a peer.
+pub async fn send_transaction(destination: &ServerName, token: String) -> Result<(), String> {
+    let response = CLIENT
+        .post(format!("{}/transactions", base_url(destination)))
+        .header(reqwest::header::CONTENT_TYPE, "application/jwt")
+        .body(token)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("peer responded with {}", response.status()))
+    }
+}
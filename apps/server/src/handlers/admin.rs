@@ -0,0 +1,457 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use openconv_shared::api::admin::{
+    ConfigReloadResponse, CreateIpDenylistEntryRequest, EmailQueueStatsResponse, IpBlockResponse,
+    IpDenylistEntryResponse, IpDenylistResponse, JobStatusResponse, SuspendUserRequest,
+    UserSuspensionResponse,
+};
+use openconv_shared::error::OpenConvError;
+use openconv_shared::ids::{DeviceId, UserId};
+
+use crate::error::ServerError;
+use crate::extractors::auth::AuthUser;
+use crate::ip_denylist::IpNet;
+use crate::state::AppState;
+
+fn db_err(e: sqlx::Error) -> ServerError {
+    tracing::error!(error = %e, "database error");
+    ServerError(OpenConvError::Internal("database error".into()))
+}
+
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    e.as_database_error()
+        .is_some_and(|db_err| db_err.is_unique_violation())
+}
+
+const MAX_SUSPENSION_REASON_LEN: usize = 512;
+const MAX_DENYLIST_REASON_LEN: usize = 512;
+/// Blocked attempt rows returned by GET /api/admin/ip-denylist/blocks.
+const RECENT_IP_BLOCKS: i64 = 100;
+
+#[utoipa::path(get, path = "/api/admin/users/{user_id}/suspension", tag = "Admin", security(("bearer_auth" = [])), params(("user_id" = openconv_shared::ids::UserId, Path, description = "User ID")), responses((status = 200, body = openconv_shared::api::admin::UserSuspensionResponse), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// Get a user's suspension state. Instance admins only.
+pub async fn get_suspension(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(user_id): Path<UserId>,
+) -> Result<Json<UserSuspensionResponse>, ServerError> {
+    auth.require_instance_admin(&state.config)?;
+
+    let row = sqlx::query_as::<_, SuspensionRow>(
+        "SELECT id, suspended_at, suspension_reason, suspended_by FROM users WHERE id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(db_err)?
+    .ok_or(ServerError(OpenConvError::NotFound))?;
+
+    Ok(Json(row.into_response()))
+}
+
+#[utoipa::path(put, path = "/api/admin/users/{user_id}/suspension", tag = "Admin", security(("bearer_auth" = [])), params(("user_id" = openconv_shared::ids::UserId, Path, description = "User to suspend")), request_body = openconv_shared::api::admin::SuspendUserRequest, responses((status = 200, body = openconv_shared::api::admin::UserSuspensionResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse), (status = 409, body = crate::error::ErrorResponse)))]
+/// Suspend a user account. Instance admins only.
+///
+/// The user's refresh tokens are deleted, outstanding access tokens are
+/// revoked and open gateway connections are closed. Login fails with the
+/// same generic error as a bad signature until the user is unsuspended.
+pub async fn suspend_user(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(user_id): Path<UserId>,
+    Json(body): Json<SuspendUserRequest>,
+) -> Result<Json<UserSuspensionResponse>, ServerError> {
+    auth.require_instance_admin(&state.config)?;
+
+    if auth.user_id == user_id {
+        return Err(ServerError(OpenConvError::Validation(
+            "Cannot suspend yourself".into(),
+        )));
+    }
+
+    let reason = body
+        .reason
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty());
+    if reason
+        .as_ref()
+        .is_some_and(|r| r.len() > MAX_SUSPENSION_REASON_LEN)
+    {
+        return Err(ServerError(OpenConvError::Validation(format!(
+            "Suspension reason must be at most {MAX_SUSPENSION_REASON_LEN} characters"
+        ))));
+    }
+
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+
+    let row = sqlx::query_as::<_, SuspensionRow>(
+        "UPDATE users SET suspended_at = NOW(), suspension_reason = $2, suspended_by = $3 \
+         WHERE id = $1 AND suspended_at IS NULL \
+         RETURNING id, suspended_at, suspension_reason, suspended_by",
+    )
+    .bind(user_id)
+    .bind(reason.as_deref())
+    .bind(auth.user_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_err)?;
+
+    let Some(row) = row else {
+        let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM users WHERE id = $1)")
+            .bind(user_id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(db_err)?;
+        return Err(if exists {
+            ServerError(OpenConvError::Conflict("user is already suspended".into()))
+        } else {
+            ServerError(OpenConvError::NotFound)
+        });
+    };
+
+    sqlx::query("DELETE FROM refresh_tokens WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?;
+
+    let device_ids: Vec<DeviceId> = sqlx::query_scalar("SELECT id FROM devices WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(db_err)?;
+
+    tx.commit().await.map_err(db_err)?;
+
+    crate::revocation::set_user_suspended(&state.redis, user_id, true).await?;
+    crate::revocation::revoke_user_access_tokens(&state, user_id, &device_ids).await?;
+    let closed = crate::ws::connection::disconnect_user(&state, user_id).await;
+
+    tracing::info!(
+        user_id = %user_id,
+        admin_id = %auth.user_id,
+        closed_connections = closed,
+        "user suspended"
+    );
+
+    Ok(Json(row.into_response()))
+}
+
+#[utoipa::path(delete, path = "/api/admin/users/{user_id}/suspension", tag = "Admin", security(("bearer_auth" = [])), params(("user_id" = openconv_shared::ids::UserId, Path, description = "User to unsuspend")), responses((status = 204), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// Lift a suspension. The user can log in again. Instance admins only.
+pub async fn unsuspend_user(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(user_id): Path<UserId>,
+) -> Result<StatusCode, ServerError> {
+    auth.require_instance_admin(&state.config)?;
+
+    let updated = sqlx::query(
+        "UPDATE users SET suspended_at = NULL, suspension_reason = NULL, suspended_by = NULL \
+         WHERE id = $1 AND suspended_at IS NOT NULL",
+    )
+    .bind(user_id)
+    .execute(&state.db)
+    .await
+    .map_err(db_err)?
+    .rows_affected();
+
+    if updated == 0 {
+        return Err(ServerError(OpenConvError::NotFound));
+    }
+
+    crate::revocation::set_user_suspended(&state.redis, user_id, false).await?;
+
+    tracing::info!(user_id = %user_id, admin_id = %auth.user_id, "user unsuspended");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(get, path = "/api/admin/jobs", tag = "Admin", security(("bearer_auth" = [])), responses((status = 200, body = Vec<openconv_shared::api::admin::JobStatusResponse>), (status = 403, body = crate::error::ErrorResponse)))]
+/// Run statistics for the background jobs on the node answering the
+/// request. Instance admins only.
+pub async fn list_jobs(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<Vec<JobStatusResponse>>, ServerError> {
+    auth.require_instance_admin(&state.config)?;
+    Ok(Json(state.jobs.snapshot()))
+}
+
+#[utoipa::path(get, path = "/api/admin/email-queue", tag = "Admin", security(("bearer_auth" = [])), responses((status = 200, body = openconv_shared::api::admin::EmailQueueStatsResponse), (status = 403, body = crate::error::ErrorResponse)))]
+/// Depth of the outbound email queue and its most recent dead letters.
+/// Instance admins only.
+pub async fn email_queue_stats(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<EmailQueueStatsResponse>, ServerError> {
+    auth.require_instance_admin(&state.config)?;
+    let stats = crate::email::outbox::stats(&state.db)
+        .await
+        .map_err(db_err)?;
+    Ok(Json(stats))
+}
+
+#[utoipa::path(post, path = "/api/admin/config/reload", tag = "Admin", security(("bearer_auth" = [])), responses((status = 200, body = openconv_shared::api::admin::ConfigReloadResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse)))]
+/// Reload rate limits, log level and feature flags from the config file
+/// on the node answering the request. Instance admins only.
+///
+/// Other nodes pick up file changes on their own within seconds. A file
+/// that fails to load is rejected with 400 and nothing changes.
+pub async fn reload_config(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<ConfigReloadResponse>, ServerError> {
+    auth.require_instance_admin(&state.config)?;
+
+    let changed = state.live.reload().map_err(|e| {
+        ServerError(OpenConvError::Validation(format!(
+            "Config reload failed: {e}"
+        )))
+    })?;
+    tracing::info!(admin = %auth.user_id, ?changed, "runtime config reloaded");
+
+    Ok(Json(ConfigReloadResponse {
+        source: state.live.source().map(|p| p.display().to_string()),
+        changed: changed.into_iter().map(String::from).collect(),
+    }))
+}
+
+#[utoipa::path(get, path = "/api/admin/ip-denylist", tag = "Admin", security(("bearer_auth" = [])), responses((status = 200, body = openconv_shared::api::admin::IpDenylistResponse), (status = 403, body = crate::error::ErrorResponse)))]
+/// List the IP denylist: ranges from the config file and those managed
+/// through this API. Instance admins only.
+pub async fn list_ip_denylist(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<IpDenylistResponse>, ServerError> {
+    auth.require_instance_admin(&state.config)?;
+
+    let rows = sqlx::query_as::<_, IpDenylistRow>(
+        "SELECT id, cidr, reason, created_by, created_at, expires_at \
+         FROM ip_denylist ORDER BY created_at DESC",
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(db_err)?;
+
+    Ok(Json(IpDenylistResponse {
+        configured: state
+            .config
+            .ip_denylist
+            .ranges()
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+        entries: rows.into_iter().map(IpDenylistRow::into_response).collect(),
+    }))
+}
+
+#[utoipa::path(post, path = "/api/admin/ip-denylist", tag = "Admin", security(("bearer_auth" = [])), request_body = openconv_shared::api::admin::CreateIpDenylistEntryRequest, responses((status = 201, body = openconv_shared::api::admin::IpDenylistEntryResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse), (status = 409, body = crate::error::ErrorResponse)))]
+/// Deny an address or CIDR range. Instance admins only.
+///
+/// Takes effect on the answering node at once and on other nodes at their
+/// next denylist refresh. Requests from the range are refused with 403
+/// before authentication, so an admin can lock themselves out.
+pub async fn add_ip_denylist_entry(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(body): Json<CreateIpDenylistEntryRequest>,
+) -> Result<(StatusCode, Json<IpDenylistEntryResponse>), ServerError> {
+    auth.require_instance_admin(&state.config)?;
+
+    let net: IpNet = body
+        .cidr
+        .parse()
+        .map_err(|e: String| ServerError(OpenConvError::Validation(e)))?;
+    let reason = body
+        .reason
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty());
+    if reason
+        .as_ref()
+        .is_some_and(|r| r.len() > MAX_DENYLIST_REASON_LEN)
+    {
+        return Err(ServerError(OpenConvError::Validation(format!(
+            "Denylist reason must be at most {MAX_DENYLIST_REASON_LEN} characters"
+        ))));
+    }
+    if body.expires_at.is_some_and(|at| at <= chrono::Utc::now()) {
+        return Err(ServerError(OpenConvError::Validation(
+            "expires_at must be in the future".into(),
+        )));
+    }
+
+    let row = sqlx::query_as::<_, IpDenylistRow>(
+        "INSERT INTO ip_denylist (cidr, reason, created_by, expires_at) VALUES ($1, $2, $3, $4) \
+         RETURNING id, cidr, reason, created_by, created_at, expires_at",
+    )
+    .bind(net.to_string())
+    .bind(reason.as_deref())
+    .bind(auth.user_id)
+    .bind(body.expires_at)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| {
+        if is_unique_violation(&e) {
+            ServerError(OpenConvError::Conflict(format!(
+                "{net} is already on the denylist"
+            )))
+        } else {
+            db_err(e)
+        }
+    })?;
+
+    state.ip_denylist.reload(&state.db).await.map_err(db_err)?;
+    tracing::info!(
+        admin = %auth.user_id,
+        entry = %net,
+        expires_at = ?row.expires_at,
+        "IP denylist entry added"
+    );
+
+    Ok((StatusCode::CREATED, Json(row.into_response())))
+}
+
+#[utoipa::path(delete, path = "/api/admin/ip-denylist/{entry_id}", tag = "Admin", security(("bearer_auth" = [])), params(("entry_id" = uuid::Uuid, Path, description = "Denylist entry ID")), responses((status = 204), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// Remove a denylist entry. Instance admins only.
+pub async fn remove_ip_denylist_entry(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(entry_id): Path<uuid::Uuid>,
+) -> Result<StatusCode, ServerError> {
+    auth.require_instance_admin(&state.config)?;
+
+    let cidr: String = sqlx::query_scalar("DELETE FROM ip_denylist WHERE id = $1 RETURNING cidr")
+        .bind(entry_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(db_err)?
+        .ok_or(ServerError(OpenConvError::NotFound))?;
+
+    state.ip_denylist.reload(&state.db).await.map_err(db_err)?;
+    tracing::info!(admin = %auth.user_id, entry = %cidr, "IP denylist entry removed");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(get, path = "/api/admin/ip-denylist/blocks", tag = "Admin", security(("bearer_auth" = [])), responses((status = 200, body = Vec<openconv_shared::api::admin::IpBlockResponse>), (status = 403, body = crate::error::ErrorResponse)))]
+/// The most recent blocked attempts, newest first. Nodes flush their
+/// counts every `ip_denylist.refresh_seconds`, so the latest attempts may
+/// not be listed yet. Instance admins only.
+pub async fn list_ip_blocks(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<Vec<IpBlockResponse>>, ServerError> {
+    auth.require_instance_admin(&state.config)?;
+
+    let rows = sqlx::query_as::<_, IpBlockRow>(
+        "SELECT ip, cidr, attempts, first_seen_at, last_seen_at FROM ip_denylist_blocks \
+         ORDER BY last_seen_at DESC LIMIT $1",
+    )
+    .bind(RECENT_IP_BLOCKS)
+    .fetch_all(&state.db)
+    .await
+    .map_err(db_err)?;
+
+    Ok(Json(
+        rows.into_iter()
+            .map(|row| IpBlockResponse {
+                ip: row.ip,
+                cidr: row.cidr,
+                attempts: row.attempts,
+                first_seen_at: row.first_seen_at,
+                last_seen_at: row.last_seen_at,
+            })
+            .collect(),
+    ))
+}
+
+/// Instance administration routes. Mounted at /api/admin.
+pub fn routes() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route(
+            "/users/{user_id}/suspension",
+            axum::routing::get(get_suspension)
+                .put(suspend_user)
+                .delete(unsuspend_user),
+        )
+        .route("/jobs", axum::routing::get(list_jobs))
+        .route("/email-queue", axum::routing::get(email_queue_stats))
+        .route("/config/reload", axum::routing::post(reload_config))
+        .route(
+            "/ip-denylist",
+            axum::routing::get(list_ip_denylist).post(add_ip_denylist_entry),
+        )
+        .route(
+            "/ip-denylist/{entry_id}",
+            axum::routing::delete(remove_ip_denylist_entry),
+        )
+        .route("/ip-denylist/blocks", axum::routing::get(list_ip_blocks))
+        .route(
+            "/health",
+            axum::routing::get(crate::handlers::health::detailed),
+        )
+}
+
+#[derive(sqlx::FromRow)]
+struct SuspensionRow {
+    id: UserId,
+    suspended_at: Option<chrono::DateTime<chrono::Utc>>,
+    suspension_reason: Option<String>,
+    suspended_by: Option<UserId>,
+}
+
+impl SuspensionRow {
+    fn into_response(self) -> UserSuspensionResponse {
+        UserSuspensionResponse {
+            user_id: self.id,
+            suspended: self.suspended_at.is_some(),
+            suspended_at: self.suspended_at,
+            reason: self.suspension_reason,
+            suspended_by: self.suspended_by,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct IpDenylistRow {
+    id: uuid::Uuid,
+    cidr: String,
+    reason: Option<String>,
+    created_by: Option<UserId>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl IpDenylistRow {
+    fn into_response(self) -> IpDenylistEntryResponse {
+        IpDenylistEntryResponse {
+            id: self.id,
+            cidr: self.cidr,
+            reason: self.reason,
+            created_by: self.created_by,
+            created_at: self.created_at,
+            expires_at: self.expires_at,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct IpBlockRow {
+    ip: String,
+    cidr: String,
+    attempts: i64,
+    first_seen_at: chrono::DateTime<chrono::Utc>,
+    last_seen_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_build_without_panic() {
+        let _ = routes();
+    }
+}
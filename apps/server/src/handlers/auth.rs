@@ -4,66 +4,117 @@ use axum::Json;
 use base64::Engine;
 use fred::interfaces::KeysInterface;
 use openconv_shared::api::auth::{
-    DeviceInfo, DevicesListResponse, LoginChallengeRequest, LoginChallengeResponse,
-    LoginVerifyRequest, LoginVerifyResponse, RecoverCompleteRequest, RecoverCompleteResponse,
-    RecoverStartRequest, RecoverStartResponse, RecoverVerifyRequest, RecoverVerifyResponse,
-    RefreshRequest, RefreshResponse, RegisterCompleteRequest, RegisterResponse,
-    RegisterStartRequest, RegisterStartResponse, RegisterVerifyRequest, RegisterVerifyResponse,
+    DevicesListResponse, LinkDeviceRequest, LoginChallengeRequest, LoginChallengeResponse,
+    LoginVerifyRequest, LoginVerifyResponse, ReauthRequest, ReauthResponse, RecoverCompleteRequest,
+    RecoverCompleteResponse, RecoverStartRequest, RecoverStartResponse, RecoverVerifyRequest,
+    RecoverVerifyResponse, RefreshRequest, RefreshResponse, RegisterCompleteRequest,
+    RegisterResponse, RegisterStartRequest, RegisterStartResponse, RegisterVerifyRequest,
+    RegisterVerifyResponse, SessionInfo, SessionsListResponse,
 };
-use openconv_shared::error::OpenConvError;
+use openconv_shared::error::{ErrorCode, ErrorMessage, OpenConvError};
 use openconv_shared::ids::{DeviceId, UserId};
 use rand::Rng;
-use subtle::ConstantTimeEq;
 
+use crate::email::outbox::{self, EmailKind};
 use crate::error::ServerError;
 use crate::extractors::auth::AuthUser;
+use crate::extractors::client_info::ClientInfo;
+use crate::one_time_code;
+use crate::redis::keys::{
+    ChallengeKey, RecoveryData, RecoveryKey, StoredChallenge, VerificationData, VerificationKey,
+};
+use crate::repo;
+use crate::repo::tokens::NewRefreshToken;
+use crate::repo::users::NewUser;
 use crate::state::AppState;
 use crate::validation::validate_display_name;
 
-fn validate_email(email: &str) -> Result<(), ServerError> {
+fn db_err(e: sqlx::Error) -> ServerError {
+    ServerError(OpenConvError::Internal(format!("database error: {e}")))
+}
+
+/// Issue an access token and a refresh token in `family`. The refresh token
+/// is recorded on `conn`, so it only becomes usable if the caller's
+/// transaction commits.
+async fn issue_token_pair(
+    state: &AppState,
+    conn: &mut sqlx::PgConnection,
+    user_id: UserId,
+    device_id: DeviceId,
+    family: uuid::Uuid,
+    client: &ClientInfo,
+) -> Result<(String, String), ServerError> {
+    let access_token =
+        crate::revocation::issue_tracked_access_token(state, &user_id, &device_id).await?;
+    let (refresh_token, jti) =
+        state
+            .jwt
+            .issue_refresh_token(&user_id, &device_id, &family.to_string())?;
+    let jti: uuid::Uuid = jti
+        .parse()
+        .map_err(|_| OpenConvError::Internal("invalid jti format".into()))?;
+
+    repo::tokens::insert_refresh(
+        conn,
+        &NewRefreshToken {
+            jti,
+            user_id,
+            device_id,
+            family,
+            expires_at: chrono::Utc::now() + state.jwt.refresh_ttl(),
+            client,
+        },
+    )
+    .await
+    .map_err(db_err)?;
+
+    Ok((access_token, refresh_token))
+}
+
+/// Check that an email address is plausibly deliverable.
+pub fn validate_email(email: &str) -> Result<(), ServerError> {
     let email = email.trim();
     if email.is_empty() {
-        return Err(OpenConvError::Validation("email is required".into()).into());
+        return Err(OpenConvError::Validation("email is required".into())
+            .with_message(ErrorMessage::new("email.required"))
+            .into());
     }
     let parts: Vec<&str> = email.split('@').collect();
     if parts.len() != 2 || parts[0].is_empty() || parts[1].is_empty() {
-        return Err(OpenConvError::Validation("invalid email format".into()).into());
+        return Err(OpenConvError::Validation("invalid email format".into())
+            .with_message(ErrorMessage::new("email.invalid"))
+            .into());
     }
     if !parts[1].contains('.') {
-        return Err(OpenConvError::Validation("invalid email format".into()).into());
+        return Err(OpenConvError::Validation("invalid email format".into())
+            .with_message(ErrorMessage::new("email.invalid"))
+            .into());
     }
     Ok(())
 }
 
 fn validate_verification_code(code: &str) -> Result<(), ServerError> {
     if code.len() != 6 || !code.chars().all(|c| c.is_ascii_digit()) {
-        return Err(OpenConvError::Validation("invalid code".into()).into());
+        return Err(OpenConvError::Validation("invalid code".into())
+            .with_code(ErrorCode::AuthCodeInvalid)
+            .into());
     }
     Ok(())
 }
 
-/// Redis storage format for verification codes.
-#[derive(serde::Serialize, serde::Deserialize)]
-struct VerificationData {
-    code: String,
-    display_name: String,
-    attempts_remaining: u32,
-}
-
-/// Lua script for atomic verification code check.
-/// Returns: [result_code, display_name_or_empty]
+/// Lua script that claims one attempt at a stored code. The attempt is
+/// spent before the code is checked, so concurrent guesses cannot share one.
+/// Returns: [result_code, attempts_remaining, code_hash, display_name]
 ///   result_code:
-///     1  = code matched, key deleted
-///     0  = code mismatch, attempts decremented
+///     1  = attempt claimed, attempts decremented
 ///     -1 = key not found / expired
 ///     -2 = attempts exhausted, key deleted
-const VERIFY_CODE_SCRIPT: &str = r#"
+const CLAIM_CODE_SCRIPT: &str = r#"
 local key = KEYS[1]
-local submitted_code = ARGV[1]
 
 local data = redis.call('GET', key)
 if not data then
-    return {-1, ""}
+    return {-1, 0, "", ""}
 end
 
 local decoded = cjson.decode(data)
@@ -71,12 +122,7 @@ local attempts = tonumber(decoded.attempts_remaining)
 
 if attempts <= 0 then
     redis.call('DEL', key)
-    return {-2, ""}
-end
-
-if submitted_code == decoded.code then
-    redis.call('DEL', key)
-    return {1, decoded.display_name}
+    return {-2, 0, "", ""}
 end
 
 decoded.attempts_remaining = attempts - 1
@@ -85,63 +131,151 @@ if ttl > 0 then
     redis.call('SET', key, cjson.encode(decoded), 'EX', ttl)
 end
 
-return {0, ""}
+return {1, decoded.attempts_remaining, decoded.code_hash or "", decoded.display_name or ""}
+"#;
+
+/// Lua script that deletes a stored code once it has been matched, unless
+/// it was replaced by a new code in the meantime.
+/// Returns 1 if this call consumed the code, 0 otherwise.
+const CONSUME_CODE_SCRIPT: &str = r#"
+local key = KEYS[1]
+
+local data = redis.call('GET', key)
+if not data then
+    return 0
+end
+
+if cjson.decode(data).code_hash ~= ARGV[1] then
+    return 0
+end
+
+redis.call('DEL', key)
+return 1
 "#;
 
-#[utoipa::path(post, path = "/api/auth/register/start", tag = "Auth", request_body = RegisterStartRequest, responses((status = 200, body = RegisterStartResponse), (status = 400, body = crate::error::ErrorResponse), (status = 429, body = crate::error::ErrorResponse)))]
+/// Outcome of [`CLAIM_CODE_SCRIPT`].
+enum CodeClaim {
+    Missing,
+    Exhausted,
+    Claimed {
+        attempts_remaining: i64,
+        code_hash: String,
+        display_name: String,
+    },
+}
+
+fn redis_string(value: &fred::types::Value) -> String {
+    match value {
+        fred::types::Value::String(s) => s.to_string(),
+        fred::types::Value::Bytes(b) => String::from_utf8_lossy(b).to_string(),
+        _ => String::new(),
+    }
+}
+
+async fn claim_code(state: &AppState, key: String) -> Result<CodeClaim, ServerError> {
+    use fred::interfaces::LuaInterface;
+    let result: Vec<fred::types::Value> = state
+        .redis
+        .guarded(
+            state
+                .redis
+                .eval(CLAIM_CODE_SCRIPT, vec![key], Vec::<String>::new()),
+        )
+        .await?;
+
+    if result.len() < 4 {
+        return Err(OpenConvError::Internal("unexpected redis response".into()).into());
+    }
+
+    let (result_code, attempts_remaining) = match (&result[0], &result[1]) {
+        (fred::types::Value::Integer(code), fred::types::Value::Integer(attempts)) => {
+            (*code, *attempts)
+        }
+        _ => return Err(OpenConvError::Internal("unexpected redis response type".into()).into()),
+    };
+
+    match result_code {
+        1 => Ok(CodeClaim::Claimed {
+            attempts_remaining,
+            code_hash: redis_string(&result[2]),
+            display_name: redis_string(&result[3]),
+        }),
+        -1 => Ok(CodeClaim::Missing),
+        -2 => Ok(CodeClaim::Exhausted),
+        _ => Err(OpenConvError::Internal("unexpected verification result".into()).into()),
+    }
+}
+
+/// Delete a matched code so it cannot be used twice. Returns false if a
+/// concurrent request already consumed it or a new code replaced it.
+async fn consume_code(
+    state: &AppState,
+    key: String,
+    code_hash: String,
+) -> Result<bool, ServerError> {
+    use fred::interfaces::LuaInterface;
+    let consumed: i64 = state
+        .redis
+        .guarded(
+            state
+                .redis
+                .eval(CONSUME_CODE_SCRIPT, vec![key], vec![code_hash]),
+        )
+        .await?;
+    Ok(consumed == 1)
+}
+
+#[utoipa::path(post, path = "/api/auth/register/start", tag = "Auth", request_body = RegisterStartRequest, responses((status = 200, body = RegisterStartResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse), (status = 429, body = crate::error::ErrorResponse), (status = 503, body = crate::error::ErrorResponse)))]
 pub async fn register_start(
     State(state): State<AppState>,
     Json(req): Json<RegisterStartRequest>,
 ) -> Result<Json<RegisterStartResponse>, ServerError> {
+    if !state.live.settings().features.registration {
+        return Err(ServerError(OpenConvError::Forbidden));
+    }
     validate_email(&req.email)?;
     let display_name = validate_display_name(&req.display_name)?;
 
     let email = req.email.trim().to_lowercase();
 
     // Per-email rate limiting
+    let settings = state.live.settings();
     crate::middleware::rate_limit::check_email_rate_limit(
         &state.redis,
         &email,
-        state.config.rate_limit.email_per_address_per_hour,
+        settings.rate_limit.email_per_address_per_hour,
         3600,
+        settings.rate_limit.fail_mode,
     )
-    .await
-    .map_err(|_| OpenConvError::RateLimited)?;
+    .await?;
 
     // Check if email already exists — always return the same response (privacy-first)
-    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM users WHERE email = $1)")
-        .bind(&email)
-        .fetch_one(&state.db)
+    let exists = repo::users::email_exists(&state.db, &email)
         .await
-        .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
+        .map_err(db_err)?;
 
     if !exists {
-        let code = format!("{:06}", rand::rng().random_range(0..1_000_000u32));
+        let code = one_time_code::generate();
 
         let data = VerificationData {
-            code: code.clone(),
+            code_hash: one_time_code::hash(&code).await?,
             display_name,
             attempts_remaining: 5,
         };
-        let json_data = serde_json::to_string(&data)
-            .map_err(|e| OpenConvError::Internal(format!("serialization error: {e}")))?;
-
-        let key = format!("verify:{email}");
         state
             .redis
-            .set::<(), _, _>(
-                &key,
-                json_data.as_str(),
-                Some(fred::types::Expiration::EX(600)),
-                None,
-                false,
-            )
-            .await
-            .map_err(|e| OpenConvError::Internal(format!("redis error: {e}")))?;
-
-        if let Err(e) = state.email.send_verification_code(&email, &code).await {
-            tracing::error!(error = %e, "failed to send verification email");
-        }
+            .set_json(&VerificationKey(&email), &data)
+            .await?;
+
+        outbox::enqueue(
+            &state.db,
+            &state.outbox_key,
+            EmailKind::Verification,
+            &email,
+            &code,
+        )
+        .await
+        .map_err(db_err)?;
     }
 
     Ok(Json(RegisterStartResponse {
@@ -149,7 +283,7 @@ pub async fn register_start(
     }))
 }
 
-#[utoipa::path(post, path = "/api/auth/register/verify", tag = "Auth", request_body = RegisterVerifyRequest, responses((status = 200, body = RegisterVerifyResponse), (status = 400, body = crate::error::ErrorResponse)))]
+#[utoipa::path(post, path = "/api/auth/register/verify", tag = "Auth", request_body = RegisterVerifyRequest, responses((status = 200, body = RegisterVerifyResponse), (status = 400, body = crate::error::ErrorResponse), (status = 503, body = crate::error::ErrorResponse)))]
 pub async fn register_verify(
     State(state): State<AppState>,
     Json(req): Json<RegisterVerifyRequest>,
@@ -158,50 +292,55 @@ pub async fn register_verify(
     validate_verification_code(&req.code)?;
 
     let email = req.email.trim().to_lowercase();
-    let key = format!("verify:{email}");
+    let key = state.redis.key(VerificationKey(&email));
 
-    // Atomic verification via Lua script
-    use fred::interfaces::LuaInterface;
-    let result: Vec<fred::types::Value> = state
-        .redis
-        .eval(VERIFY_CODE_SCRIPT, vec![key], vec![req.code.clone()])
-        .await
-        .map_err(|e| OpenConvError::Internal(format!("redis error: {e}")))?;
+    let (code_hash, display_name) = match claim_code(&state, key.clone()).await? {
+        CodeClaim::Claimed {
+            code_hash,
+            display_name,
+            ..
+        } => (code_hash, display_name),
+        CodeClaim::Missing => {
+            return Err(
+                OpenConvError::Validation("code expired or not found".into())
+                    .with_code(ErrorCode::AuthCodeExpired)
+                    .into(),
+            )
+        }
+        CodeClaim::Exhausted => {
+            return Err(
+                OpenConvError::Validation("code expired, request a new one".into())
+                    .with_code(ErrorCode::AuthCodeExpired)
+                    .into(),
+            )
+        }
+    };
 
-    if result.len() < 2 {
-        return Err(OpenConvError::Internal("unexpected redis response".into()).into());
+    if !one_time_code::verify(&req.code, &code_hash).await? {
+        return Err(OpenConvError::Validation("invalid code".into())
+            .with_code(ErrorCode::AuthCodeInvalid)
+            .into());
     }
 
-    let result_code: i64 = match &result[0] {
-        fred::types::Value::Integer(n) => *n,
-        _ => return Err(OpenConvError::Internal("unexpected redis response type".into()).into()),
-    };
-
-    match result_code {
-        1 => {
-            // Code matched — extract display_name from Lua response
-            let display_name = match &result[1] {
-                fred::types::Value::String(s) => s.to_string(),
-                fred::types::Value::Bytes(b) => String::from_utf8_lossy(b).to_string(),
-                _ => String::new(),
-            };
-
-            let token = state.jwt.issue_registration_token(&email, &display_name)?;
-
-            Ok(Json(RegisterVerifyResponse {
-                registration_token: token,
-            }))
-        }
-        0 => Err(OpenConvError::Validation("invalid code".into()).into()),
-        -1 => Err(OpenConvError::Validation("code expired or not found".into()).into()),
-        -2 => Err(OpenConvError::Validation("code expired, request a new one".into()).into()),
-        _ => Err(OpenConvError::Internal("unexpected verification result".into()).into()),
+    if !consume_code(&state, key, code_hash).await? {
+        return Err(
+            OpenConvError::Validation("code expired or not found".into())
+                .with_code(ErrorCode::AuthCodeExpired)
+                .into(),
+        );
     }
+
+    let token = state.jwt.issue_registration_token(&email, &display_name)?;
+
+    Ok(Json(RegisterVerifyResponse {
+        registration_token: token,
+    }))
 }
 
 #[utoipa::path(post, path = "/api/auth/register/complete", tag = "Auth", request_body = RegisterCompleteRequest, responses((status = 200, body = RegisterResponse), (status = 400, body = crate::error::ErrorResponse), (status = 409, body = crate::error::ErrorResponse)))]
 pub async fn register_complete(
     State(state): State<AppState>,
+    client: ClientInfo,
     Json(req): Json<RegisterCompleteRequest>,
 ) -> Result<Json<RegisterResponse>, ServerError> {
     // 1. Validate registration token
@@ -220,13 +359,14 @@ pub async fn register_complete(
         );
     }
 
-    libsignal_protocol::PublicKey::deserialize(&pk_bytes)
+    let identity_key = libsignal_protocol::PublicKey::deserialize(&pk_bytes)
         .map_err(|_| OpenConvError::Validation("invalid public key format".into()))?;
 
-    // Decode pre-key bundle
+    // Decode and validate pre-key bundle
     let pre_key_data = base64::engine::general_purpose::STANDARD
         .decode(&req.pre_key_bundle)
         .map_err(|_| OpenConvError::Validation("invalid pre-key bundle encoding".into()))?;
+    crate::crypto_verify::validate_pre_key_bundle(&pre_key_data, &identity_key)?;
 
     // 3. Begin transaction
     let mut tx = state
@@ -236,79 +376,61 @@ pub async fn register_complete(
         .map_err(|e| OpenConvError::Internal(format!("transaction start failed: {e}")))?;
 
     let user_id = UserId::new();
-    let pre_key_id = uuid::Uuid::now_v7();
 
-    // Insert user
-    let insert_result = sqlx::query(
-        "INSERT INTO users (id, public_key, email, display_name) VALUES ($1, $2, $3, $4)",
-    )
-    .bind(user_id.0)
-    .bind(&req.public_key)
-    .bind(&claims.email)
-    .bind(&claims.display_name)
-    .execute(&mut *tx)
-    .await;
-
-    if let Err(e) = insert_result {
-        if let Some(db_err) = e.as_database_error() {
-            if db_err.is_unique_violation() {
-                return Err(OpenConvError::Conflict("account already exists".into()).into());
-            }
+    let new_user = NewUser {
+        id: user_id,
+        public_key: &req.public_key,
+        email: &claims.email,
+        display_name: &claims.display_name,
+    };
+    if let Err(e) = repo::users::insert(&mut *tx, &new_user).await {
+        if e.as_database_error()
+            .is_some_and(|db_err| db_err.is_unique_violation())
+        {
+            return Err(OpenConvError::Conflict("account already exists".into())
+                .with_code(ErrorCode::AccountExists)
+                .into());
         }
-        return Err(OpenConvError::Internal(format!("database error: {e}")).into());
+        return Err(db_err(e));
+    }
+    if state.config.discovery.is_enabled() {
+        repo::users::refresh_discovery_hashes(
+            &mut *tx,
+            &state.config.discovery.salt,
+            Some(user_id),
+        )
+        .await
+        .map_err(db_err)?;
     }
 
-    // Insert device
-    sqlx::query(
-        "INSERT INTO devices (id, user_id, device_name, last_active, created_at) VALUES ($1, $2, $3, NOW(), NOW())",
-    )
-    .bind(req.device_id.0)
-    .bind(user_id.0)
-    .bind(&req.device_name)
-    .execute(&mut *tx)
-    .await
-    .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
-
-    // Insert pre-key bundle
-    sqlx::query(
-        "INSERT INTO pre_key_bundles (id, user_id, device_id, key_data, is_used) VALUES ($1, $2, $3, $4, false)",
+    repo::devices::insert(
+        &mut *tx,
+        req.device_id,
+        user_id,
+        &req.device_name,
+        Some(&req.public_key),
+        &client,
     )
-    .bind(pre_key_id)
-    .bind(user_id.0)
-    .bind(req.device_id.0)
-    .bind(&pre_key_data)
-    .execute(&mut *tx)
     .await
-    .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
-
-    // 4. Generate token family and issue tokens
-    let family = uuid::Uuid::now_v7().to_string();
-    let access_token = state.jwt.issue_access_token(&user_id, &req.device_id)?;
-    let (refresh_token, jti_str) =
-        state
-            .jwt
-            .issue_refresh_token(&user_id, &req.device_id, &family)?;
+    .map_err(device_insert_err)?;
+    repo::devices::insert_pre_key_bundle(&mut *tx, user_id, req.device_id, &pre_key_data)
+        .await
+        .map_err(db_err)?;
 
-    let jti: uuid::Uuid = jti_str
-        .parse()
-        .map_err(|_| OpenConvError::Internal("invalid jti format".into()))?;
-    let family_uuid: uuid::Uuid = family
-        .parse()
-        .expect("family UUID was just generated from Uuid::now_v7()");
-    let expires_at = chrono::Utc::now() + state.jwt.refresh_ttl();
+    crate::handlers::dm_channels::insert_notes_channel(&mut tx, user_id)
+        .await
+        .map_err(db_err)?;
 
-    // Store refresh token record
-    sqlx::query(
-        "INSERT INTO refresh_tokens (jti, user_id, device_id, family, expires_at, is_used) VALUES ($1, $2, $3, $4, $5, false)",
+    // 4. Start a token family and issue tokens
+    let (access_token, refresh_token) = issue_token_pair(
+        &state,
+        &mut tx,
+        user_id,
+        req.device_id,
+        uuid::Uuid::now_v7(),
+        &client,
     )
-    .bind(jti)
-    .bind(user_id.0)
-    .bind(req.device_id.0)
-    .bind(family_uuid)
-    .bind(expires_at)
-    .execute(&mut *tx)
-    .await
-    .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
+    .await?;
 
     // Commit transaction
     tx.commit()
@@ -323,14 +445,7 @@ pub async fn register_complete(
     }))
 }
 
-/// Redis storage format for login challenges.
-#[derive(serde::Serialize, serde::Deserialize)]
-struct StoredChallenge {
-    challenge: String,
-    exists: bool,
-}
-
-#[utoipa::path(post, path = "/api/auth/challenge", tag = "Auth", request_body = LoginChallengeRequest, responses((status = 200, body = LoginChallengeResponse), (status = 400, body = crate::error::ErrorResponse), (status = 429, body = crate::error::ErrorResponse)))]
+#[utoipa::path(post, path = "/api/auth/challenge", tag = "Auth", request_body = LoginChallengeRequest, responses((status = 200, body = LoginChallengeResponse), (status = 400, body = crate::error::ErrorResponse), (status = 429, body = crate::error::ErrorResponse), (status = 503, body = crate::error::ErrorResponse)))]
 pub async fn challenge(
     State(state): State<AppState>,
     Json(req): Json<LoginChallengeRequest>,
@@ -345,71 +460,90 @@ pub async fn challenge(
         );
     }
 
-    // Per-public-key rate limiting
-    crate::middleware::rate_limit::check_key_rate_limit(
-        &state.redis,
-        &req.public_key,
-        "challenge",
-        state.config.rate_limit.challenge_per_key_per_minute,
-        60,
-    )
-    .await
-    .map_err(|_| OpenConvError::RateLimited)?;
-
     // Generate 32 bytes of cryptographic randomness
     let challenge_bytes: [u8; 32] = rand::rng().random();
     let challenge_b64 = base64::engine::general_purpose::STANDARD.encode(challenge_bytes);
 
     // Check if user exists — always return a challenge regardless (privacy-first)
-    let exists: bool =
-        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM users WHERE public_key = $1)")
-            .bind(&req.public_key)
-            .fetch_one(&state.db)
-            .await
-            .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
+    let exists = repo::users::public_key_exists(&state.db, &req.public_key)
+        .await
+        .map_err(db_err)?;
 
-    // Store challenge in Redis with 60s TTL
     let stored = StoredChallenge {
         challenge: challenge_b64.clone(),
         exists,
     };
-    let json_data = serde_json::to_string(&stored)
-        .map_err(|e| OpenConvError::Internal(format!("serialization error: {e}")))?;
-
-    let key = format!("challenge:{}", req.public_key);
     state
         .redis
-        .set::<(), _, _>(
-            &key,
-            json_data.as_str(),
-            Some(fred::types::Expiration::EX(60)),
-            None,
-            false,
-        )
-        .await
-        .map_err(|e| OpenConvError::Internal(format!("redis error: {e}")))?;
+        .set_json(&ChallengeKey(&req.public_key), &stored)
+        .await?;
 
     Ok(Json(LoginChallengeResponse {
         challenge: challenge_b64,
     }))
 }
 
-#[utoipa::path(post, path = "/api/auth/verify", tag = "Auth", request_body = LoginVerifyRequest, responses((status = 200, body = LoginVerifyResponse), (status = 401, body = crate::error::ErrorResponse)))]
+/// Map a device insert failing because the identity key is already another
+/// device's to a conflict.
+fn device_insert_err(e: sqlx::Error) -> ServerError {
+    if e.as_database_error()
+        .is_some_and(|db_err| db_err.is_unique_violation())
+    {
+        return OpenConvError::Conflict("identity key or device already registered".into()).into();
+    }
+    db_err(e)
+}
+
+/// The account `public_key` signs in to as `device_id`, and the key to
+/// record on the device if it is not bound to one yet. `None` if the key
+/// may not sign in as that device.
+async fn resolve_login_key<'k>(
+    state: &AppState,
+    public_key: &'k str,
+    device_id: DeviceId,
+) -> Result<Option<(UserId, Option<&'k str>)>, ServerError> {
+    let bound = repo::devices::active_by_identity_key(&state.db, public_key)
+        .await
+        .map_err(db_err)?;
+    let account = repo::users::active_id_by_public_key(&state.db, public_key)
+        .await
+        .map_err(db_err)?;
+
+    match (bound, account) {
+        (Some((user_id, bound_device)), _) if bound_device == device_id => {
+            Ok(Some((user_id, None)))
+        }
+        (Some((user_id, _)), Some(account_id)) if account_id == user_id => {
+            let unkeyed = repo::devices::is_unkeyed(&state.db, user_id, device_id)
+                .await
+                .map_err(db_err)?;
+            Ok(unkeyed.then_some((user_id, None)))
+        }
+        (Some(_), _) => Ok(None),
+        (None, Some(user_id)) => {
+            // An unbound account key; a device with a key of its own must
+            // sign in with it
+            let own_key = repo::devices::identity_key(&state.db, device_id)
+                .await
+                .map_err(db_err)?;
+            Ok(own_key.is_none().then_some((user_id, Some(public_key))))
+        }
+        (None, None) => Ok(None),
+    }
+}
+
+#[utoipa::path(post, path = "/api/auth/verify", tag = "Auth", request_body = LoginVerifyRequest, responses((status = 200, body = LoginVerifyResponse), (status = 401, body = crate::error::ErrorResponse), (status = 503, body = crate::error::ErrorResponse)))]
 pub async fn login_verify(
     State(state): State<AppState>,
+    client: ClientInfo,
     Json(req): Json<LoginVerifyRequest>,
 ) -> Result<Json<LoginVerifyResponse>, ServerError> {
     // 1. Atomic fetch-and-delete challenge from Redis
-    let key = format!("challenge:{}", req.public_key);
-    let stored_json: Option<String> = state
+    let stored = state
         .redis
-        .getdel(&key)
-        .await
-        .map_err(|e| OpenConvError::Internal(format!("redis error: {e}")))?;
-
-    let stored_json = stored_json.ok_or(OpenConvError::Unauthorized)?;
-    let stored: StoredChallenge = serde_json::from_str(&stored_json)
-        .map_err(|_| OpenConvError::Internal("corrupt challenge data".into()))?;
+        .take_json(&ChallengeKey(&req.public_key))
+        .await?
+        .ok_or(OpenConvError::Unauthorized)?;
 
     // 2. Check exists flag — blind challenge means user doesn't exist
     if !stored.exists {
@@ -436,16 +570,14 @@ pub async fn login_verify(
         return Err(OpenConvError::Unauthorized.into());
     }
 
-    // 7. Look up user by public_key
-    let user_id: uuid::Uuid = sqlx::query_scalar("SELECT id FROM users WHERE public_key = $1")
-        .bind(&req.public_key)
-        .fetch_optional(&state.db)
-        .await
-        .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?
+    // 7. Resolve the key to an account. A device's own key signs in only as
+    //    that device; the account key also still signs in the account's
+    //    unkeyed devices from before per-device keys. Suspended users get
+    //    the same generic error as a bad signature.
+    let (user_id, identity_key) = resolve_login_key(&state, &req.public_key, req.device_id)
+        .await?
         .ok_or(OpenConvError::Unauthorized)?;
 
-    let user_id = UserId(user_id);
-
     // 8. Begin transaction for device upsert + refresh token storage
     let mut tx = state
         .db
@@ -454,48 +586,27 @@ pub async fn login_verify(
         .map_err(|e| OpenConvError::Internal(format!("transaction start failed: {e}")))?;
 
     // Upsert device record — scoped to current user via WHERE clause
-    sqlx::query(
-        "INSERT INTO devices (id, user_id, device_name, last_active, created_at) \
-         VALUES ($1, $2, $3, NOW(), NOW()) \
-         ON CONFLICT (id) DO UPDATE SET last_active = NOW(), device_name = EXCLUDED.device_name \
-         WHERE devices.user_id = $2",
+    repo::devices::upsert(
+        &mut *tx,
+        req.device_id,
+        user_id,
+        &req.device_name,
+        identity_key,
+        &client,
     )
-    .bind(req.device_id.0)
-    .bind(user_id.0)
-    .bind(&req.device_name)
-    .execute(&mut *tx)
     .await
-    .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
-
-    // 9. Issue tokens
-    let family = uuid::Uuid::now_v7().to_string();
-    let access_token = state.jwt.issue_access_token(&user_id, &req.device_id)?;
-    let (refresh_token, jti_str) =
-        state
-            .jwt
-            .issue_refresh_token(&user_id, &req.device_id, &family)?;
-
-    // 10. Store refresh token in database
-    let jti: uuid::Uuid = jti_str
-        .parse()
-        .map_err(|_| OpenConvError::Internal("invalid jti format".into()))?;
-    let family_uuid: uuid::Uuid = family
-        .parse()
-        .expect("family UUID was just generated from Uuid::now_v7()");
-    let expires_at = chrono::Utc::now() + state.jwt.refresh_ttl();
+    .map_err(db_err)?;
 
-    sqlx::query(
-        "INSERT INTO refresh_tokens (jti, user_id, device_id, family, expires_at, is_used) \
-         VALUES ($1, $2, $3, $4, $5, false)",
+    // 9. Issue tokens in a new family
+    let (access_token, refresh_token) = issue_token_pair(
+        &state,
+        &mut tx,
+        user_id,
+        req.device_id,
+        uuid::Uuid::now_v7(),
+        &client,
     )
-    .bind(jti)
-    .bind(user_id.0)
-    .bind(req.device_id.0)
-    .bind(family_uuid)
-    .bind(expires_at)
-    .execute(&mut *tx)
-    .await
-    .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
+    .await?;
 
     // Commit transaction
     tx.commit()
@@ -510,9 +621,69 @@ pub async fn login_verify(
     }))
 }
 
+#[utoipa::path(post, path = "/api/auth/reauth", tag = "Auth", security(("bearer_auth" = [])), request_body = ReauthRequest, responses((status = 200, body = ReauthResponse), (status = 401, body = crate::error::ErrorResponse), (status = 503, body = crate::error::ErrorResponse)))]
+/// Step-up re-authentication ("sudo mode").
+///
+/// The client requests a challenge for its device's identity key via
+/// `/api/auth/challenge`, signs it, and exchanges the signature here for an
+/// access token that sensitive endpoints accept for `reauth_window_seconds`.
+pub async fn reauth(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(req): Json<ReauthRequest>,
+) -> Result<Json<ReauthResponse>, ServerError> {
+    let account_key = repo::users::active_public_key(&state.db, auth.user_id)
+        .await
+        .map_err(db_err)?
+        .ok_or(OpenConvError::Unauthorized)?;
+    // Devices sign with their own identity key; unkeyed ones with the
+    // account key
+    let public_key_b64 = repo::devices::identity_key(&state.db, auth.device_id)
+        .await
+        .map_err(db_err)?
+        .unwrap_or(account_key);
+
+    // Same single-use challenge as login, keyed by the caller's public key
+    let stored = state
+        .redis
+        .take_json(&ChallengeKey(&public_key_b64))
+        .await?
+        .ok_or(OpenConvError::Unauthorized)?;
+    if !stored.exists {
+        return Err(OpenConvError::Unauthorized.into());
+    }
+
+    let public_key = crate::crypto_verify::parse_public_key(&public_key_b64)
+        .map_err(|_| OpenConvError::Unauthorized)?;
+    let sig_bytes = base64::engine::general_purpose::STANDARD
+        .decode(&req.signature)
+        .map_err(|_| OpenConvError::Unauthorized)?;
+    let challenge_bytes = base64::engine::general_purpose::STANDARD
+        .decode(&stored.challenge)
+        .map_err(|_| OpenConvError::Internal("corrupt challenge data".into()))?;
+
+    if !crate::crypto_verify::verify_challenge_signature(&public_key, &challenge_bytes, &sig_bytes)
+    {
+        return Err(OpenConvError::Unauthorized.into());
+    }
+
+    let access_token = crate::revocation::issue_tracked_elevated_access_token(
+        &state,
+        &auth.user_id,
+        &auth.device_id,
+    )
+    .await?;
+
+    Ok(Json(ReauthResponse {
+        access_token,
+        reauth_expires_in: state.config.jwt.reauth_window_seconds,
+    }))
+}
+
 #[utoipa::path(post, path = "/api/auth/refresh", tag = "Auth", request_body = RefreshRequest, responses((status = 200, body = RefreshResponse), (status = 401, body = crate::error::ErrorResponse)))]
 pub async fn refresh(
     State(state): State<AppState>,
+    client: ClientInfo,
     Json(body): Json<RefreshRequest>,
 ) -> Result<Json<RefreshResponse>, ServerError> {
     // 1. Validate the refresh JWT
@@ -544,23 +715,17 @@ pub async fn refresh(
         .map_err(|e| OpenConvError::Internal(format!("transaction start failed: {e}")))?;
 
     // 4. Look up the token record by jti (with FOR UPDATE to lock the row)
-    let record: Option<(bool,)> =
-        sqlx::query_as("SELECT is_used FROM refresh_tokens WHERE jti = $1 FOR UPDATE")
-            .bind(jti)
-            .fetch_optional(&mut *tx)
-            .await
-            .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
-
-    let (is_used,) = record.ok_or(OpenConvError::Unauthorized)?;
+    let is_used = repo::tokens::lock_for_rotation(&mut *tx, jti)
+        .await
+        .map_err(db_err)?
+        .ok_or(OpenConvError::Unauthorized)?;
 
     // 5. Check for token reuse (breach detection)
     if is_used {
         // Invalidate entire token family
-        sqlx::query("UPDATE refresh_tokens SET is_used = true, used_at = NOW() WHERE family = $1")
-            .bind(family_uuid)
-            .execute(&mut *tx)
+        repo::tokens::mark_family_used(&mut *tx, family_uuid)
             .await
-            .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
+            .map_err(db_err)?;
 
         tx.commit()
             .await
@@ -569,44 +734,28 @@ pub async fn refresh(
         return Err(OpenConvError::SessionCompromised.into());
     }
 
-    // 6. Mark the current token as used
-    sqlx::query("UPDATE refresh_tokens SET is_used = true, used_at = NOW() WHERE jti = $1")
-        .bind(jti)
-        .execute(&mut *tx)
+    // 6. Suspended users cannot refresh; suspension also deletes their
+    //    refresh tokens, this closes the race with an in-flight refresh.
+    let suspended = repo::users::is_suspended(&mut *tx, user_id)
         .await
-        .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
-
-    // 7. Issue new token pair in the same family
-    let access_token = state.jwt.issue_access_token(&user_id, &device_id)?;
-    let (refresh_token, new_jti_str) =
-        state
-            .jwt
-            .issue_refresh_token(&user_id, &device_id, &claims.family)?;
+        .map_err(db_err)?;
+    if suspended {
+        return Err(OpenConvError::Unauthorized.into());
+    }
 
-    let new_jti: uuid::Uuid = new_jti_str
-        .parse()
-        .map_err(|_| OpenConvError::Internal("invalid jti format".into()))?;
-    let expires_at = chrono::Utc::now() + state.jwt.refresh_ttl();
+    // 7. Mark the current token as used
+    repo::tokens::mark_used(&mut *tx, jti)
+        .await
+        .map_err(db_err)?;
 
-    // 8. Store the new refresh token record
-    sqlx::query(
-        "INSERT INTO refresh_tokens (jti, user_id, device_id, family, expires_at, is_used) VALUES ($1, $2, $3, $4, $5, false)",
-    )
-    .bind(new_jti)
-    .bind(user_id.0)
-    .bind(device_id.0)
-    .bind(family_uuid)
-    .bind(expires_at)
-    .execute(&mut *tx)
-    .await
-    .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
+    // 8. Issue and store a new token pair in the same family
+    let (access_token, refresh_token) =
+        issue_token_pair(&state, &mut tx, user_id, device_id, family_uuid, &client).await?;
 
-    // 9. Update device last_active
-    sqlx::query("UPDATE devices SET last_active = NOW() WHERE id = $1")
-        .bind(device_id.0)
-        .execute(&mut *tx)
+    // 9. Update device last_active and last-seen network metadata
+    repo::devices::touch(&mut *tx, device_id, &client)
         .await
-        .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
+        .map_err(db_err)?;
 
     tx.commit()
         .await
@@ -623,30 +772,31 @@ pub async fn logout(
     State(state): State<AppState>,
     auth: AuthUser,
 ) -> Result<StatusCode, ServerError> {
-    sqlx::query(
-        "UPDATE refresh_tokens SET is_used = true, used_at = NOW() WHERE user_id = $1 AND device_id = $2 AND is_used = false",
-    )
-    .bind(auth.user_id.0)
-    .bind(auth.device_id.0)
-    .execute(&state.db)
-    .await
-    .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
+    repo::tokens::invalidate_for_device(&state.db, auth.user_id, auth.device_id)
+        .await
+        .map_err(db_err)?;
+
+    crate::revocation::revoke_device_access_tokens(&state, auth.user_id, auth.device_id).await?;
 
     Ok(StatusCode::OK)
 }
 
-#[utoipa::path(post, path = "/api/auth/logout-all", tag = "Auth", security(("bearer_auth" = [])), responses((status = 200), (status = 401, body = crate::error::ErrorResponse)))]
+#[utoipa::path(post, path = "/api/auth/logout-all", tag = "Auth", security(("bearer_auth" = [])), responses((status = 200), (status = 401, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse)))]
+/// Sign out every device. Requires a recently re-authenticated token.
 pub async fn logout_all(
     State(state): State<AppState>,
     auth: AuthUser,
 ) -> Result<StatusCode, ServerError> {
-    sqlx::query(
-        "UPDATE refresh_tokens SET is_used = true, used_at = NOW() WHERE user_id = $1 AND is_used = false",
-    )
-    .bind(auth.user_id.0)
-    .execute(&state.db)
-    .await
-    .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
+    auth.require_recent_auth(state.config.jwt.reauth_window_seconds)?;
+
+    repo::tokens::invalidate_for_user(&state.db, auth.user_id)
+        .await
+        .map_err(db_err)?;
+
+    let device_ids = repo::devices::ids_for_user(&state.db, auth.user_id)
+        .await
+        .map_err(db_err)?;
+    crate::revocation::revoke_user_access_tokens(&state, auth.user_id, &device_ids).await?;
 
     Ok(StatusCode::OK)
 }
@@ -656,119 +806,147 @@ pub async fn list_devices(
     State(state): State<AppState>,
     auth: AuthUser,
 ) -> Result<Json<DevicesListResponse>, ServerError> {
-    type DeviceRow = (
-        uuid::Uuid,
-        String,
-        Option<chrono::DateTime<chrono::Utc>>,
-        chrono::DateTime<chrono::Utc>,
-    );
-    let rows: Vec<DeviceRow> =
-        sqlx::query_as(
-            "SELECT id, device_name, last_active, created_at FROM devices WHERE user_id = $1 ORDER BY last_active DESC",
-        )
-        .bind(auth.user_id.0)
-        .fetch_all(&state.db)
+    let devices = repo::devices::list_for_user(&state.db, auth.user_id)
         .await
-        .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
-
-    let devices = rows
-        .into_iter()
-        .map(|(id, device_name, last_active, created_at)| DeviceInfo {
-            id: DeviceId(id),
-            device_name,
-            last_active,
-            created_at,
-        })
-        .collect();
+        .map_err(db_err)?;
 
     Ok(Json(DevicesListResponse { devices }))
 }
 
+#[utoipa::path(post, path = "/api/auth/devices", tag = "Auth", security(("bearer_auth" = [])), request_body = LinkDeviceRequest, responses((status = 201), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse), (status = 409, body = crate::error::ErrorResponse)))]
+/// Link a new device to the account. Requires a recently re-authenticated
+/// token from an existing device; the new device then signs in through
+/// the usual challenge with its own identity key.
+pub async fn link_device(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    client: ClientInfo,
+    Json(req): Json<LinkDeviceRequest>,
+) -> Result<StatusCode, ServerError> {
+    auth.require_recent_auth(state.config.jwt.reauth_window_seconds)?;
+    auth.require_human()?;
+
+    let identity_key = crate::crypto_verify::parse_public_key(&req.identity_key)?;
+    let pre_key_data = base64::engine::general_purpose::STANDARD
+        .decode(&req.pre_key_bundle)
+        .map_err(|_| OpenConvError::Validation("invalid pre-key bundle encoding".into()))?;
+    crate::crypto_verify::validate_pre_key_bundle(&pre_key_data, &identity_key)?;
+
+    if repo::users::public_key_exists(&state.db, &req.identity_key)
+        .await
+        .map_err(db_err)?
+    {
+        return Err(OpenConvError::Conflict("identity key already registered".into()).into());
+    }
+
+    let mut tx = state
+        .db
+        .begin()
+        .await
+        .map_err(|e| OpenConvError::Internal(format!("transaction start failed: {e}")))?;
+
+    repo::devices::insert(
+        &mut *tx,
+        req.device_id,
+        auth.user_id,
+        &req.device_name,
+        Some(&req.identity_key),
+        &client,
+    )
+    .await
+    .map_err(device_insert_err)?;
+    repo::devices::insert_pre_key_bundle(&mut *tx, auth.user_id, req.device_id, &pre_key_data)
+        .await
+        .map_err(db_err)?;
+
+    tx.commit()
+        .await
+        .map_err(|e| OpenConvError::Internal(format!("transaction commit failed: {e}")))?;
+
+    Ok(StatusCode::CREATED)
+}
+
 #[utoipa::path(delete, path = "/api/auth/devices/{device_id}", tag = "Auth", security(("bearer_auth" = [])), params(("device_id" = DeviceId, Path, description = "Device to revoke")), responses((status = 200), (status = 401, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
 pub async fn revoke_device(
     State(state): State<AppState>,
     auth: AuthUser,
     Path(device_id): Path<DeviceId>,
 ) -> Result<StatusCode, ServerError> {
-    // Look up the device
-    let owner: Option<(uuid::Uuid,)> = sqlx::query_as("SELECT user_id FROM devices WHERE id = $1")
-        .bind(device_id.0)
-        .fetch_optional(&state.db)
+    let owner_id = repo::devices::owner(&state.db, device_id)
         .await
-        .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
-
-    let (owner_id,) = owner.ok_or(OpenConvError::NotFound)?;
+        .map_err(db_err)?
+        .ok_or(OpenConvError::NotFound)?;
 
-    if owner_id != auth.user_id.0 {
+    if owner_id != auth.user_id {
         return Err(OpenConvError::Forbidden.into());
     }
 
     // Soft-invalidate all refresh tokens for this device (audit trail)
-    sqlx::query(
-        "UPDATE refresh_tokens SET is_used = true, used_at = NOW() WHERE device_id = $1 AND is_used = false",
-    )
-    .bind(device_id.0)
-    .execute(&state.db)
-    .await
-    .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
+    repo::tokens::invalidate_for_device(&state.db, auth.user_id, device_id)
+        .await
+        .map_err(db_err)?;
 
     // Delete the device (refresh_tokens cascade-delete via FK)
-    sqlx::query("DELETE FROM devices WHERE id = $1")
-        .bind(device_id.0)
-        .execute(&state.db)
+    repo::devices::delete(&state.db, device_id)
         .await
-        .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
+        .map_err(db_err)?;
+
+    crate::revocation::revoke_device_access_tokens(&state, auth.user_id, device_id).await?;
 
     Ok(StatusCode::OK)
 }
 
-// ---------------------------------------------------------------------------
-// Account Recovery
-// ---------------------------------------------------------------------------
-
-/// Redis storage format for recovery codes.
-#[derive(serde::Serialize, serde::Deserialize)]
-struct RecoveryData {
-    code: String,
-    attempts_remaining: u32,
-}
+#[utoipa::path(get, path = "/api/auth/sessions", tag = "Auth", security(("bearer_auth" = [])), responses((status = 200, body = SessionsListResponse), (status = 401, body = crate::error::ErrorResponse)))]
+/// List active sessions (unexpired refresh token families), most recently used first.
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<SessionsListResponse>, ServerError> {
+    let rows = repo::tokens::list_sessions(&state.db, auth.user_id)
+        .await
+        .map_err(db_err)?;
 
-/// Lua script for atomic recovery code attempt decrement (mismatch path).
-/// Returns: [result_code, ""]
-///   result_code:
-///     0  = decremented successfully
-///     -1 = key not found / expired
-///     -2 = attempts exhausted, key deleted
-const RECOVER_DECREMENT_SCRIPT: &str = r#"
-local key = KEYS[1]
+    let sessions = rows
+        .into_iter()
+        .map(|r| SessionInfo {
+            id: r.family,
+            device_id: r.device_id,
+            device_name: r.device_name,
+            ip_address: r.ip_address,
+            geo_country: r.geo_country,
+            created_at: r.created_at,
+            last_used_at: r.last_used_at,
+            expires_at: r.expires_at,
+            current: r.device_id == auth.device_id,
+        })
+        .collect();
 
-local data = redis.call('GET', key)
-if not data then
-    return {-1, ""}
-end
+    Ok(Json(SessionsListResponse { sessions }))
+}
 
-local decoded = cjson.decode(data)
-local attempts = tonumber(decoded.attempts_remaining)
+#[utoipa::path(delete, path = "/api/auth/sessions/{session_id}", tag = "Auth", security(("bearer_auth" = [])), params(("session_id" = uuid::Uuid, Path, description = "Session (refresh token family) to revoke")), responses((status = 200), (status = 401, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// Revoke a single session. Unlike device revocation, the device record and
+/// its keys are kept; the session just has to sign in again.
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(session_id): Path<uuid::Uuid>,
+) -> Result<StatusCode, ServerError> {
+    let device_id = repo::tokens::delete_session(&state.db, auth.user_id, session_id)
+        .await
+        .map_err(db_err)?
+        .ok_or(OpenConvError::NotFound)?;
 
-if attempts <= 0 then
-    redis.call('DEL', key)
-    return {-2, ""}
-end
+    crate::revocation::revoke_device_access_tokens(&state, auth.user_id, device_id).await?;
 
-decoded.attempts_remaining = attempts - 1
-if decoded.attempts_remaining <= 0 then
-    redis.call('DEL', key)
-    return {-2, ""}
-end
-local ttl = redis.call('TTL', key)
-if ttl > 0 then
-    redis.call('SET', key, cjson.encode(decoded), 'EX', ttl)
-end
+    Ok(StatusCode::OK)
+}
 
-return {0, ""}
-"#;
+// ---------------------------------------------------------------------------
+// Account Recovery
+// ---------------------------------------------------------------------------
 
-#[utoipa::path(post, path = "/api/auth/recover/start", tag = "Auth", request_body = RecoverStartRequest, responses((status = 200, body = RecoverStartResponse), (status = 429, body = crate::error::ErrorResponse)))]
+#[utoipa::path(post, path = "/api/auth/recover/start", tag = "Auth", request_body = RecoverStartRequest, responses((status = 200, body = RecoverStartResponse), (status = 429, body = crate::error::ErrorResponse), (status = 503, body = crate::error::ErrorResponse)))]
 pub async fn recover_start(
     State(state): State<AppState>,
     Json(req): Json<RecoverStartRequest>,
@@ -778,49 +956,46 @@ pub async fn recover_start(
     let email = req.email.trim().to_lowercase();
 
     // Per-email rate limiting
+    let settings = state.live.settings();
     crate::middleware::rate_limit::check_email_rate_limit(
         &state.redis,
         &email,
-        state.config.rate_limit.email_per_address_per_hour,
+        settings.rate_limit.email_per_address_per_hour,
         3600,
+        settings.rate_limit.fail_mode,
     )
-    .await
-    .map_err(|_| OpenConvError::RateLimited)?;
+    .await?;
 
     // Always generate code and write to Redis to prevent timing-based email enumeration.
-    // Only send the actual email if the user exists.
-    let code = format!("{:06}", rand::rng().random_range(0..1_000_000u32));
+    // Only send the actual email if the user exists and is not suspended.
+    let code = one_time_code::generate();
 
     let data = RecoveryData {
-        code: code.clone(),
+        code_hash: one_time_code::hash(&code).await?,
         attempts_remaining: 5,
     };
-    let json_data = serde_json::to_string(&data)
-        .map_err(|e| OpenConvError::Internal(format!("serialization error: {e}")))?;
+    state.redis.set_json(&RecoveryKey(&email), &data).await?;
 
-    let key = format!("recover:{email}");
-    state
-        .redis
-        .set::<(), _, _>(
-            &key,
-            json_data.as_str(),
-            Some(fred::types::Expiration::EX(600)),
-            None,
-            false,
-        )
+    let user_id = repo::users::id_by_email(&state.db, &email)
         .await
-        .map_err(|e| OpenConvError::Internal(format!("redis error: {e}")))?;
+        .map_err(db_err)?;
+    let active = match user_id {
+        Some(user_id) => !repo::users::is_suspended(&state.db, user_id)
+            .await
+            .map_err(db_err)?,
+        None => false,
+    };
 
-    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM users WHERE email = $1)")
-        .bind(&email)
-        .fetch_one(&state.db)
+    if active {
+        outbox::enqueue(
+            &state.db,
+            &state.outbox_key,
+            EmailKind::Recovery,
+            &email,
+            &code,
+        )
         .await
-        .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
-
-    if exists {
-        if let Err(e) = state.email.send_recovery_code(&email, &code).await {
-            tracing::error!(error = %e, "failed to send recovery email");
-        }
+        .map_err(db_err)?;
     }
 
     Ok(Json(RecoverStartResponse {
@@ -828,7 +1003,7 @@ pub async fn recover_start(
     }))
 }
 
-#[utoipa::path(post, path = "/api/auth/recover/verify", tag = "Auth", request_body = RecoverVerifyRequest, responses((status = 200, body = RecoverVerifyResponse), (status = 400, body = crate::error::ErrorResponse)))]
+#[utoipa::path(post, path = "/api/auth/recover/verify", tag = "Auth", request_body = RecoverVerifyRequest, responses((status = 200, body = RecoverVerifyResponse), (status = 400, body = crate::error::ErrorResponse), (status = 503, body = crate::error::ErrorResponse)))]
 pub async fn recover_verify(
     State(state): State<AppState>,
     Json(req): Json<RecoverVerifyRequest>,
@@ -837,86 +1012,76 @@ pub async fn recover_verify(
     validate_verification_code(&req.code)?;
 
     let email = req.email.trim().to_lowercase();
-    let key = format!("recover:{email}");
-
-    // Fetch stored data from Redis for constant-time comparison in Rust
-    let stored: Option<String> = state
-        .redis
-        .get(&key)
-        .await
-        .map_err(|e| OpenConvError::Internal(format!("redis error: {e}")))?;
-
-    let stored = match stored {
-        Some(s) => s,
-        None => return Err(OpenConvError::Validation("invalid or expired code".into()).into()),
+    let key = state.redis.key(RecoveryKey(&email));
+
+    let (attempts_remaining, code_hash) = match claim_code(&state, key.clone()).await? {
+        CodeClaim::Claimed {
+            attempts_remaining,
+            code_hash,
+            ..
+        } => (attempts_remaining, code_hash),
+        CodeClaim::Missing => {
+            return Err(OpenConvError::Validation("invalid or expired code".into())
+                .with_code(ErrorCode::AuthCodeExpired)
+                .into())
+        }
+        CodeClaim::Exhausted => {
+            return Err(
+                OpenConvError::Validation("code expired, request a new one".into())
+                    .with_code(ErrorCode::AuthCodeExpired)
+                    .into(),
+            )
+        }
     };
 
-    let data: RecoveryData = serde_json::from_str(&stored)
-        .map_err(|e| OpenConvError::Internal(format!("deserialization error: {e}")))?;
-
-    if data.attempts_remaining == 0 {
-        state
-            .redis
-            .del::<(), _>(&key)
-            .await
-            .map_err(|e| OpenConvError::Internal(format!("redis error: {e}")))?;
-        return Err(OpenConvError::Validation("code expired, request a new one".into()).into());
+    if !one_time_code::verify(&req.code, &code_hash).await? {
+        if attempts_remaining <= 0 {
+            state.redis.guarded(state.redis.del::<(), _>(&key)).await?;
+            return Err(
+                OpenConvError::Validation("code expired, request a new one".into())
+                    .with_code(ErrorCode::AuthCodeExpired)
+                    .into(),
+            );
+        }
+        return Err(OpenConvError::Validation("invalid or expired code".into())
+            .with_code(ErrorCode::AuthCodeInvalid)
+            .into());
     }
 
-    // Constant-time comparison to prevent timing attacks
-    let codes_match: bool = data.code.as_bytes().ct_eq(req.code.as_bytes()).into();
+    if !consume_code(&state, key, code_hash).await? {
+        return Err(OpenConvError::Validation("invalid or expired code".into())
+            .with_code(ErrorCode::AuthCodeExpired)
+            .into());
+    }
 
-    if codes_match {
-        // Delete the consumed key
-        state
-            .redis
-            .del::<(), _>(&key)
-            .await
-            .map_err(|e| OpenConvError::Internal(format!("redis error: {e}")))?;
+    // Look up user_id by email; suspended accounts cannot recover
+    let user_id = repo::users::id_by_email(&state.db, &email)
+        .await
+        .map_err(db_err)?
+        .ok_or_else(|| {
+            OpenConvError::Validation("invalid or expired code".into())
+                .with_code(ErrorCode::AuthCodeExpired)
+        })?;
+    if repo::users::is_suspended(&state.db, user_id)
+        .await
+        .map_err(db_err)?
+    {
+        return Err(OpenConvError::Validation("invalid or expired code".into())
+            .with_code(ErrorCode::AuthCodeExpired)
+            .into());
+    }
 
-        // Look up user_id by email
-        let user_id: uuid::Uuid = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
-            .bind(&email)
-            .fetch_optional(&state.db)
-            .await
-            .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?
-            .ok_or_else(|| OpenConvError::Validation("invalid or expired code".into()))?;
-
-        let uid = UserId(user_id);
-        let token = state.jwt.issue_recovery_token(&email, &uid)?;
-
-        Ok(Json(RecoverVerifyResponse {
-            recovery_token: token,
-        }))
-    } else {
-        // Atomically decrement attempts via Lua script
-        use fred::interfaces::LuaInterface;
-        let result: Vec<fred::types::Value> = state
-            .redis
-            .eval(RECOVER_DECREMENT_SCRIPT, vec![key], Vec::<String>::new())
-            .await
-            .map_err(|e| OpenConvError::Internal(format!("redis error: {e}")))?;
-
-        let result_code: i64 = if result.is_empty() {
-            0
-        } else {
-            match &result[0] {
-                fred::types::Value::Integer(n) => *n,
-                _ => 0,
-            }
-        };
+    let token = state.jwt.issue_recovery_token(&email, &user_id)?;
 
-        if result_code == -2 {
-            Err(OpenConvError::Validation("code expired, request a new one".into()).into())
-        } else {
-            Err(OpenConvError::Validation("invalid or expired code".into()).into())
-        }
-    }
+    Ok(Json(RecoverVerifyResponse {
+        recovery_token: token,
+    }))
 }
 
 #[utoipa::path(post, path = "/api/auth/recover/complete", tag = "Auth", request_body = RecoverCompleteRequest, responses((status = 200, body = RecoverCompleteResponse), (status = 400, body = crate::error::ErrorResponse)))]
 pub async fn recover_complete(
     State(state): State<AppState>,
+    client: ClientInfo,
     Json(req): Json<RecoverCompleteRequest>,
 ) -> Result<Json<RecoverCompleteResponse>, ServerError> {
     // 1. Validate the recovery token
@@ -938,13 +1103,14 @@ pub async fn recover_complete(
         );
     }
 
-    libsignal_protocol::PublicKey::deserialize(&pk_bytes)
+    let identity_key = libsignal_protocol::PublicKey::deserialize(&pk_bytes)
         .map_err(|_| OpenConvError::Validation("invalid public key format".into()))?;
 
-    // 3. Decode pre-key bundle
+    // 3. Decode and validate pre-key bundle
     let pre_key_data = base64::engine::general_purpose::STANDARD
         .decode(&req.new_pre_key_bundle)
         .map_err(|_| OpenConvError::Validation("invalid pre-key bundle encoding".into()))?;
+    crate::crypto_verify::validate_pre_key_bundle(&pre_key_data, &identity_key)?;
 
     // 4. Begin transaction for atomic identity replacement
     let mut tx = state
@@ -953,99 +1119,73 @@ pub async fn recover_complete(
         .await
         .map_err(|e| OpenConvError::Internal(format!("transaction start failed: {e}")))?;
 
-    // a. Update public key and set public_key_changed_at
-    let rows = sqlx::query(
-        "UPDATE users SET public_key = $1, public_key_changed_at = NOW() WHERE id = $2",
-    )
-    .bind(&req.new_public_key)
-    .bind(user_id.0)
-    .execute(&mut *tx)
-    .await
-    .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
+    // a. Suspended users cannot recover; checked inside the transaction
+    //    to close the race with a suspension after the token was issued
+    let suspended = repo::users::is_suspended(&mut *tx, user_id)
+        .await
+        .map_err(db_err)?;
+    if suspended {
+        return Err(OpenConvError::Unauthorized.into());
+    }
 
-    if rows.rows_affected() == 0 {
+    // b. Update public key and set public_key_changed_at
+    let found = repo::users::replace_public_key(&mut *tx, user_id, &req.new_public_key)
+        .await
+        .map_err(db_err)?;
+    if !found {
         return Err(OpenConvError::NotFound.into());
     }
 
-    // b. Delete all existing refresh tokens for this user
-    sqlx::query("DELETE FROM refresh_tokens WHERE user_id = $1")
-        .bind(user_id.0)
-        .execute(&mut *tx)
+    // c. Delete all existing refresh tokens, pre-key bundles and devices
+    repo::tokens::delete_for_user(&mut *tx, user_id)
         .await
-        .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
-
-    // c. Delete all existing pre-key bundles for this user
-    sqlx::query("DELETE FROM pre_key_bundles WHERE user_id = $1")
-        .bind(user_id.0)
-        .execute(&mut *tx)
+        .map_err(db_err)?;
+    repo::devices::delete_pre_key_bundles_for_user(&mut *tx, user_id)
         .await
-        .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
-
-    // d. Delete all existing devices for this user
-    sqlx::query("DELETE FROM devices WHERE user_id = $1")
-        .bind(user_id.0)
-        .execute(&mut *tx)
+        .map_err(db_err)?;
+    let old_device_ids = repo::devices::delete_all_for_user(&mut *tx, user_id)
         .await
-        .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
+        .map_err(db_err)?;
 
-    // e. Create new device
-    sqlx::query(
-        "INSERT INTO devices (id, user_id, device_name, last_active, created_at) VALUES ($1, $2, $3, NOW(), NOW())",
-    )
-    .bind(req.device_id.0)
-    .bind(user_id.0)
-    .bind(&req.device_name)
-    .execute(&mut *tx)
-    .await
-    .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
-
-    // f. Store new pre-key bundle
-    let pre_key_id = uuid::Uuid::now_v7();
-    sqlx::query(
-        "INSERT INTO pre_key_bundles (id, user_id, device_id, key_data, is_used) VALUES ($1, $2, $3, $4, false)",
+    // d. Create the new device and store its pre-key bundle
+    repo::devices::insert(
+        &mut *tx,
+        req.device_id,
+        user_id,
+        &req.device_name,
+        Some(&req.new_public_key),
+        &client,
     )
-    .bind(pre_key_id)
-    .bind(user_id.0)
-    .bind(req.device_id.0)
-    .bind(&pre_key_data)
-    .execute(&mut *tx)
     .await
-    .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
-
-    // 5. Issue new tokens
-    let family = uuid::Uuid::now_v7().to_string();
-    let access_token = state.jwt.issue_access_token(&user_id, &req.device_id)?;
-    let (refresh_token, jti_str) =
-        state
-            .jwt
-            .issue_refresh_token(&user_id, &req.device_id, &family)?;
-
-    let jti: uuid::Uuid = jti_str
-        .parse()
-        .map_err(|_| OpenConvError::Internal("invalid jti format".into()))?;
-    let family_uuid: uuid::Uuid = family
-        .parse()
-        .expect("family UUID was just generated from Uuid::now_v7()");
-    let expires_at = chrono::Utc::now() + state.jwt.refresh_ttl();
+    .map_err(device_insert_err)?;
+    repo::devices::insert_pre_key_bundle(&mut *tx, user_id, req.device_id, &pre_key_data)
+        .await
+        .map_err(db_err)?;
 
-    // Store refresh token record
-    sqlx::query(
-        "INSERT INTO refresh_tokens (jti, user_id, device_id, family, expires_at, is_used) VALUES ($1, $2, $3, $4, $5, false)",
+    // 5. Issue new tokens in a new family
+    let (access_token, refresh_token) = issue_token_pair(
+        &state,
+        &mut tx,
+        user_id,
+        req.device_id,
+        uuid::Uuid::now_v7(),
+        &client,
     )
-    .bind(jti)
-    .bind(user_id.0)
-    .bind(req.device_id.0)
-    .bind(family_uuid)
-    .bind(expires_at)
-    .execute(&mut *tx)
-    .await
-    .map_err(|e| OpenConvError::Internal(format!("database error: {e}")))?;
+    .await?;
 
     // 6. Commit transaction
     tx.commit()
         .await
         .map_err(|e| OpenConvError::Internal(format!("transaction commit failed: {e}")))?;
 
+    // 7. Cut off access tokens still held by the replaced devices
+    // (skipping the recovering device, whose fresh token is already tracked)
+    let old_device_ids: Vec<DeviceId> = old_device_ids
+        .into_iter()
+        .filter(|id| *id != req.device_id)
+        .collect();
+    crate::revocation::revoke_user_access_tokens(&state, user_id, &old_device_ids).await?;
+
     Ok(Json(RecoverCompleteResponse {
         user_id,
         access_token,
@@ -1151,28 +1291,19 @@ mod tests {
         assert!(validate_verification_code("abcdef").is_err());
     }
 
-    #[test]
-    fn stored_challenge_roundtrip() {
-        let data = StoredChallenge {
-            challenge: "dGVzdA==".into(),
-            exists: true,
-        };
-        let json = serde_json::to_string(&data).unwrap();
-        let back: StoredChallenge = serde_json::from_str(&json).unwrap();
-        assert_eq!(back.challenge, "dGVzdA==");
-        assert!(back.exists);
-    }
-
     #[test]
     fn verification_data_roundtrip() {
         let data = VerificationData {
-            code: "123456".into(),
+            code_hash: "$argon2id$v=19$m=19456,t=2,p=1$c2FsdA$aGFzaA".into(),
             display_name: "Test User".into(),
             attempts_remaining: 5,
         };
         let json = serde_json::to_string(&data).unwrap();
         let back: VerificationData = serde_json::from_str(&json).unwrap();
-        assert_eq!(back.code, "123456");
+        assert_eq!(
+            back.code_hash,
+            "$argon2id$v=19$m=19456,t=2,p=1$c2FsdA$aGFzaA"
+        );
         assert_eq!(back.display_name, "Test User");
         assert_eq!(back.attempts_remaining, 5);
     }
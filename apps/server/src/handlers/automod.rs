@@ -0,0 +1,228 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use openconv_shared::api::automod::{
+    AutomodRule, AutomodRuleListResponse, AutomodRuleResponse, CreateAutomodRuleRequest,
+    UpdateAutomodRuleRequest, MAX_AUTOMOD_RULES_PER_GUILD,
+};
+use openconv_shared::error::OpenConvError;
+use openconv_shared::ids::{GuildId, UserId};
+use openconv_shared::permissions::Permissions;
+
+use crate::error::ServerError;
+use crate::extractors::guild_member::GuildMember;
+use crate::state::AppState;
+
+fn db_err(e: sqlx::Error) -> ServerError {
+    tracing::error!(error = %e, "database error");
+    ServerError(OpenConvError::Internal("database error".into()))
+}
+
+const MAX_RULE_NAME_LEN: usize = 100;
+
+fn validate_name(name: &str) -> Result<&str, ServerError> {
+    let trimmed = name.trim();
+    if trimmed.is_empty() || trimmed.chars().count() > MAX_RULE_NAME_LEN {
+        return Err(ServerError(OpenConvError::Validation(format!(
+            "Rule name must be between 1 and {MAX_RULE_NAME_LEN} characters"
+        ))));
+    }
+    Ok(trimmed)
+}
+
+fn validate_rule(rule: &AutomodRule) -> Result<(), ServerError> {
+    rule.validate()
+        .map_err(|e| ServerError(OpenConvError::Validation(e)))
+}
+
+#[utoipa::path(post, path = "/api/guilds/{guild_id}/automod/rules", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), request_body = openconv_shared::api::automod::CreateAutomodRuleRequest, responses((status = 201, body = openconv_shared::api::automod::AutomodRuleResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse), (status = 409, body = crate::error::ErrorResponse)))]
+/// Create an automod rule.
+pub async fn create_rule(
+    member: GuildMember,
+    State(state): State<AppState>,
+    Json(body): Json<CreateAutomodRuleRequest>,
+) -> Result<(StatusCode, Json<AutomodRuleResponse>), ServerError> {
+    member.require(Permissions::MANAGE_GUILD)?;
+
+    let name = validate_name(&body.name)?;
+    validate_rule(&body.rule)?;
+
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+
+    // Lock the guild row so concurrent creates cannot exceed the limit.
+    sqlx::query("SELECT 1 FROM guilds WHERE id = $1 FOR UPDATE")
+        .bind(member.guild_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?;
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM automod_rules WHERE guild_id = $1")
+        .bind(member.guild_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_err)?;
+    if count >= MAX_AUTOMOD_RULES_PER_GUILD as i64 {
+        return Err(ServerError(OpenConvError::Conflict(format!(
+            "guild already has {MAX_AUTOMOD_RULES_PER_GUILD} automod rules"
+        ))));
+    }
+
+    let row = sqlx::query_as::<_, RuleRow>(
+        "INSERT INTO automod_rules (guild_id, name, rule, enabled, created_by) \
+         VALUES ($1, $2, $3, $4, $5) \
+         RETURNING id, guild_id, name, rule, enabled, created_by, created_at, updated_at",
+    )
+    .bind(member.guild_id)
+    .bind(name)
+    .bind(sqlx::types::Json(&body.rule))
+    .bind(body.enabled.unwrap_or(true))
+    .bind(member.user_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_err)?;
+
+    tx.commit().await.map_err(db_err)?;
+
+    Ok((StatusCode::CREATED, Json(row.into_response())))
+}
+
+#[utoipa::path(get, path = "/api/guilds/{guild_id}/automod/rules", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), responses((status = 200, body = openconv_shared::api::automod::AutomodRuleListResponse), (status = 403, body = crate::error::ErrorResponse)))]
+/// List the guild's automod rules.
+///
+/// Open to every member, since clients enforce the rules the server cannot.
+pub async fn list_rules(
+    member: GuildMember,
+    State(state): State<AppState>,
+) -> Result<Json<AutomodRuleListResponse>, ServerError> {
+    let rows = sqlx::query_as::<_, RuleRow>(
+        "SELECT id, guild_id, name, rule, enabled, created_by, created_at, updated_at \
+         FROM automod_rules WHERE guild_id = $1 ORDER BY created_at",
+    )
+    .bind(member.guild_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(db_err)?;
+
+    Ok(Json(AutomodRuleListResponse {
+        rules: rows.into_iter().map(RuleRow::into_response).collect(),
+    }))
+}
+
+#[utoipa::path(patch, path = "/api/guilds/{guild_id}/automod/rules/{rule_id}", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID"), ("rule_id" = uuid::Uuid, Path, description = "Rule ID")), request_body = openconv_shared::api::automod::UpdateAutomodRuleRequest, responses((status = 200, body = openconv_shared::api::automod::AutomodRuleResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// Update a rule's name, parameters and/or enabled state.
+pub async fn update_rule(
+    member: GuildMember,
+    State(state): State<AppState>,
+    Path((_, rule_id)): Path<(GuildId, uuid::Uuid)>,
+    Json(body): Json<UpdateAutomodRuleRequest>,
+) -> Result<Json<AutomodRuleResponse>, ServerError> {
+    member.require(Permissions::MANAGE_GUILD)?;
+
+    if body.name.is_none() && body.rule.is_none() && body.enabled.is_none() {
+        return Err(ServerError(OpenConvError::Validation(
+            "At least one field must be provided".into(),
+        )));
+    }
+    let name = body.name.as_deref().map(validate_name).transpose()?;
+    if let Some(rule) = &body.rule {
+        validate_rule(rule)?;
+    }
+
+    let row = sqlx::query_as::<_, RuleRow>(
+        "UPDATE automod_rules \
+         SET name = COALESCE($3, name), rule = COALESCE($4, rule), \
+             enabled = COALESCE($5, enabled), updated_at = NOW() \
+         WHERE id = $1 AND guild_id = $2 \
+         RETURNING id, guild_id, name, rule, enabled, created_by, created_at, updated_at",
+    )
+    .bind(rule_id)
+    .bind(member.guild_id)
+    .bind(name)
+    .bind(body.rule.as_ref().map(sqlx::types::Json))
+    .bind(body.enabled)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(db_err)?
+    .ok_or(ServerError(OpenConvError::NotFound))?;
+
+    Ok(Json(row.into_response()))
+}
+
+#[utoipa::path(delete, path = "/api/guilds/{guild_id}/automod/rules/{rule_id}", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID"), ("rule_id" = uuid::Uuid, Path, description = "Rule ID")), responses((status = 204), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// Delete an automod rule.
+pub async fn delete_rule(
+    member: GuildMember,
+    State(state): State<AppState>,
+    Path((_, rule_id)): Path<(GuildId, uuid::Uuid)>,
+) -> Result<StatusCode, ServerError> {
+    member.require(Permissions::MANAGE_GUILD)?;
+
+    let deleted = sqlx::query("DELETE FROM automod_rules WHERE id = $1 AND guild_id = $2")
+        .bind(rule_id)
+        .bind(member.guild_id)
+        .execute(&state.db)
+        .await
+        .map_err(db_err)?
+        .rows_affected();
+
+    if deleted == 0 {
+        return Err(ServerError(OpenConvError::NotFound));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Routes for automod rules. Mounted at /api/guilds/:guild_id/automod/rules.
+pub fn routes() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route("/", axum::routing::get(list_rules).post(create_rule))
+        .route(
+            "/{rule_id}",
+            axum::routing::patch(update_rule).delete(delete_rule),
+        )
+}
+
+#[derive(sqlx::FromRow)]
+struct RuleRow {
+    id: uuid::Uuid,
+    guild_id: GuildId,
+    name: String,
+    rule: sqlx::types::Json<AutomodRule>,
+    enabled: bool,
+    created_by: UserId,
+    created_at: chrono::DateTime<chrono::Utc>,
+    updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl RuleRow {
+    fn into_response(self) -> AutomodRuleResponse {
+        let rule = self.rule.0;
+        AutomodRuleResponse {
+            id: self.id,
+            guild_id: self.guild_id,
+            name: self.name,
+            server_enforced: rule.is_server_enforced(),
+            rule,
+            enabled: self.enabled,
+            created_by: self.created_by,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_build_without_panic() {
+        let _ = routes();
+    }
+
+    #[test]
+    fn validate_name_trims_and_bounds() {
+        assert_eq!(validate_name("  no spam  ").unwrap(), "no spam");
+        assert!(validate_name("   ").is_err());
+        assert!(validate_name(&"x".repeat(MAX_RULE_NAME_LEN + 1)).is_err());
+    }
+}
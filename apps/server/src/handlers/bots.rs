@@ -0,0 +1,214 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use openconv_shared::api::bot::{BotListResponse, BotResponse, CreateBotRequest};
+use openconv_shared::error::OpenConvError;
+use openconv_shared::ids::{DeviceId, GuildId, UserId};
+use openconv_shared::permissions::Permissions;
+
+use crate::error::ServerError;
+use crate::extractors::auth::AuthUser;
+use crate::extractors::bot::BotUser;
+use crate::extractors::guild_member::GuildMember;
+use crate::member_events::{self, MemberEvent, MemberEventKind};
+use crate::repo::bots as repo;
+use crate::secret_token;
+use crate::state::AppState;
+use crate::validation::validate_display_name;
+
+fn db_err(e: sqlx::Error) -> ServerError {
+    tracing::error!(error = %e, "database error");
+    ServerError(OpenConvError::Internal("database error".into()))
+}
+
+const MAX_BOTS_PER_GUILD: i64 = 10;
+
+const DEFAULT_BOT_PERMISSIONS: Permissions =
+    Permissions::READ_MESSAGES.union(Permissions::SEND_MESSAGES);
+
+#[utoipa::path(post, path = "/api/guilds/{guild_id}/bots", tag = "Bots", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), request_body = openconv_shared::api::bot::CreateBotRequest, responses((status = 201, body = openconv_shared::api::bot::BotResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse), (status = 409, body = crate::error::ErrorResponse)))]
+/// Create a bot account in the guild. The token is only returned here.
+pub async fn create_bot(
+    auth: AuthUser,
+    member: GuildMember,
+    State(state): State<AppState>,
+    Json(body): Json<CreateBotRequest>,
+) -> Result<(StatusCode, Json<BotResponse>), ServerError> {
+    auth.require_human()?;
+    member.require(Permissions::MANAGE_GUILD)?;
+
+    let display_name = validate_display_name(&body.display_name)?;
+    let permissions = body
+        .permissions
+        .map(Permissions::from_bits_truncate)
+        .unwrap_or(DEFAULT_BOT_PERMISSIONS);
+    if !member.permissions.contains(permissions) {
+        return Err(ServerError(OpenConvError::Validation(
+            "Cannot grant a bot permissions you do not have".into(),
+        )));
+    }
+
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+
+    // Lock the guild row so concurrent creates cannot exceed the limit.
+    sqlx::query("SELECT 1 FROM guilds WHERE id = $1 FOR UPDATE")
+        .bind(member.guild_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?;
+
+    let count = repo::count_for_guild(&mut *tx, member.guild_id)
+        .await
+        .map_err(db_err)?;
+    if count >= MAX_BOTS_PER_GUILD {
+        return Err(ServerError(OpenConvError::Conflict(format!(
+            "guild already has {MAX_BOTS_PER_GUILD} bots"
+        ))));
+    }
+
+    let token = secret_token::generate();
+    let created = repo::create(
+        &mut tx,
+        &repo::NewBot {
+            user_id: UserId::new(),
+            device_id: DeviceId::new(),
+            guild_id: member.guild_id,
+            display_name: &display_name,
+            permissions,
+            token_hash: &secret_token::hash(&token),
+            created_by: member.user_id,
+        },
+    )
+    .await
+    .map_err(db_err)?;
+
+    tx.commit().await.map_err(db_err)?;
+
+    member_events::publish(
+        &state,
+        MemberEvent::new(member.guild_id, created.user_id, MemberEventKind::Join),
+    )
+    .await;
+
+    let mut response = created.into_response();
+    response.token = Some(token);
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[utoipa::path(get, path = "/api/guilds/{guild_id}/bots", tag = "Bots", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), responses((status = 200, body = openconv_shared::api::bot::BotListResponse), (status = 403, body = crate::error::ErrorResponse)))]
+/// List the guild's bots.
+pub async fn list_bots(
+    member: GuildMember,
+    State(state): State<AppState>,
+) -> Result<Json<BotListResponse>, ServerError> {
+    member.require(Permissions::MANAGE_GUILD)?;
+
+    let bots = repo::list_for_guild(&state.db, member.guild_id)
+        .await
+        .map_err(db_err)?;
+
+    Ok(Json(BotListResponse {
+        bots: bots.into_iter().map(repo::Bot::into_response).collect(),
+    }))
+}
+
+#[utoipa::path(post, path = "/api/guilds/{guild_id}/bots/{bot_id}/token", tag = "Bots", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID"), ("bot_id" = openconv_shared::ids::UserId, Path, description = "Bot user ID")), responses((status = 200, body = openconv_shared::api::bot::BotResponse), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// Replace a bot's token. The old token stops working and the bot's
+/// gateway connections are closed.
+pub async fn regenerate_token(
+    auth: AuthUser,
+    member: GuildMember,
+    State(state): State<AppState>,
+    Path((_, bot_id)): Path<(GuildId, UserId)>,
+) -> Result<Json<BotResponse>, ServerError> {
+    auth.require_human()?;
+    member.require(Permissions::MANAGE_GUILD)?;
+
+    let token = secret_token::generate();
+    let replaced = repo::replace_token(
+        &state.db,
+        member.guild_id,
+        bot_id,
+        &secret_token::hash(&token),
+    )
+    .await
+    .map_err(db_err)?;
+    if !replaced {
+        return Err(ServerError(OpenConvError::NotFound));
+    }
+    crate::ws::connection::disconnect_user(&state, bot_id).await;
+
+    let bot = repo::get(&state.db, member.guild_id, bot_id)
+        .await
+        .map_err(db_err)?
+        .ok_or(ServerError(OpenConvError::NotFound))?;
+    let mut response = bot.into_response();
+    response.token = Some(token);
+    Ok(Json(response))
+}
+
+#[utoipa::path(delete, path = "/api/guilds/{guild_id}/bots/{bot_id}", tag = "Bots", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID"), ("bot_id" = openconv_shared::ids::UserId, Path, description = "Bot user ID")), responses((status = 204), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// Delete a bot. Its token stops working and it leaves the guild; its
+/// messages stay.
+pub async fn delete_bot(
+    member: GuildMember,
+    State(state): State<AppState>,
+    Path((_, bot_id)): Path<(GuildId, UserId)>,
+) -> Result<StatusCode, ServerError> {
+    member.require(Permissions::MANAGE_GUILD)?;
+
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+    let deleted = repo::delete(&mut tx, member.guild_id, bot_id)
+        .await
+        .map_err(db_err)?;
+    if !deleted {
+        return Err(ServerError(OpenConvError::NotFound));
+    }
+    tx.commit().await.map_err(db_err)?;
+    crate::ws::connection::disconnect_user(&state, bot_id).await;
+
+    member_events::publish(
+        &state,
+        MemberEvent::new(member.guild_id, bot_id, MemberEventKind::Leave),
+    )
+    .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(get, path = "/api/bots/me", tag = "Bots", security(("bot_auth" = [])), responses((status = 200, body = openconv_shared::api::bot::BotResponse), (status = 401, body = crate::error::ErrorResponse)))]
+/// The calling bot's own account. Requires a bot token.
+pub async fn get_current_bot(
+    bot: BotUser,
+    State(state): State<AppState>,
+) -> Result<Json<BotResponse>, ServerError> {
+    let bot = repo::get(&state.db, bot.guild_id, bot.user_id)
+        .await
+        .map_err(db_err)?
+        .ok_or(ServerError(OpenConvError::NotFound))?;
+    Ok(Json(bot.into_response()))
+}
+
+/// Routes for guild bots. Mounted at /api/guilds/:guild_id/bots.
+pub fn routes() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route("/", axum::routing::get(list_bots).post(create_bot))
+        .route("/{bot_id}", axum::routing::delete(delete_bot))
+        .route("/{bot_id}/token", axum::routing::post(regenerate_token))
+}
+
+/// Routes for bots acting on their own account. Mounted at /api/bots.
+pub fn self_routes() -> axum::Router<AppState> {
+    axum::Router::new().route("/me", axum::routing::get(get_current_bot))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_build_without_panic() {
+        let _ = routes();
+        let _ = self_routes();
+    }
+}
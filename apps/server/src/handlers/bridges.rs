@@ -0,0 +1,381 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use openconv_shared::api::bridge::{
+    BridgeListResponse, BridgeResponse, CreateBridgeRequest, PuppetListResponse, PuppetResponse,
+    UpsertPuppetRequest,
+};
+use openconv_shared::error::OpenConvError;
+use openconv_shared::ids::{DeviceId, UserId};
+
+use crate::error::ServerError;
+use crate::extractors::auth::AuthUser;
+use crate::extractors::bridge::BridgeApp;
+use crate::member_events::{self, MemberEvent, MemberEventKind};
+use crate::repo::bridges as repo;
+use crate::secret_token;
+use crate::state::AppState;
+use crate::validation::validate_display_name;
+
+fn db_err(e: sqlx::Error) -> ServerError {
+    tracing::error!(error = %e, "database error");
+    ServerError(OpenConvError::Internal("database error".into()))
+}
+
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    e.as_database_error()
+        .is_some_and(|db_err| db_err.is_unique_violation())
+}
+
+const MAX_NAMESPACE_LEN: usize = 32;
+const MAX_REMOTE_ID_LEN: usize = 255;
+
+/// Namespaces are 2–32 characters of lowercase ASCII letters, digits and
+/// hyphens, starting with a letter or digit.
+fn validate_namespace(namespace: &str) -> Result<(), ServerError> {
+    let valid = (2..=MAX_NAMESPACE_LEN).contains(&namespace.len())
+        && !namespace.starts_with('-')
+        && namespace
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-');
+    if valid {
+        Ok(())
+    } else {
+        Err(ServerError(OpenConvError::Validation(format!(
+            "namespace must be 2-{MAX_NAMESPACE_LEN} lowercase letters, digits or hyphens"
+        ))))
+    }
+}
+
+fn validate_remote_id(remote_id: &str) -> Result<(), ServerError> {
+    if remote_id.is_empty()
+        || remote_id.len() > MAX_REMOTE_ID_LEN
+        || remote_id.chars().any(|c| c.is_control())
+    {
+        return Err(ServerError(OpenConvError::Validation(format!(
+            "remote ID must be 1-{MAX_REMOTE_ID_LEN} bytes without control characters"
+        ))));
+    }
+    Ok(())
+}
+
+#[utoipa::path(post, path = "/api/admin/bridges", tag = "Bridges", security(("bearer_auth" = [])), request_body = openconv_shared::api::bridge::CreateBridgeRequest, responses((status = 201, body = openconv_shared::api::bridge::BridgeResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse), (status = 409, body = crate::error::ErrorResponse)))]
+/// Register a bridge application. Instance admins only. The token is only
+/// returned here.
+pub async fn create_bridge(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(body): Json<CreateBridgeRequest>,
+) -> Result<(StatusCode, Json<BridgeResponse>), ServerError> {
+    auth.require_instance_admin(&state.config)?;
+
+    let name = validate_display_name(&body.name)?;
+    validate_namespace(&body.namespace)?;
+
+    let token = secret_token::generate();
+    let bridge = repo::insert(
+        &state.db,
+        &name,
+        &body.namespace,
+        &secret_token::hash(&token),
+        auth.user_id,
+    )
+    .await
+    .map_err(|e| {
+        if is_unique_violation(&e) {
+            ServerError(OpenConvError::Conflict("namespace is already taken".into()))
+        } else {
+            db_err(e)
+        }
+    })?;
+
+    tracing::info!(
+        bridge_id = %bridge.id,
+        namespace = %bridge.namespace,
+        admin = %auth.user_id,
+        "bridge registered"
+    );
+
+    let mut response = bridge.into_response();
+    response.token = Some(token);
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[utoipa::path(get, path = "/api/admin/bridges", tag = "Bridges", security(("bearer_auth" = [])), responses((status = 200, body = openconv_shared::api::bridge::BridgeListResponse), (status = 403, body = crate::error::ErrorResponse)))]
+/// List registered bridges. Instance admins only.
+pub async fn list_bridges(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<BridgeListResponse>, ServerError> {
+    auth.require_instance_admin(&state.config)?;
+
+    let bridges = repo::list(&state.db).await.map_err(db_err)?;
+    Ok(Json(BridgeListResponse {
+        bridges: bridges
+            .into_iter()
+            .map(repo::Bridge::into_response)
+            .collect(),
+    }))
+}
+
+#[utoipa::path(post, path = "/api/admin/bridges/{bridge_id}/token", tag = "Bridges", security(("bearer_auth" = [])), params(("bridge_id" = uuid::Uuid, Path, description = "Bridge ID")), responses((status = 200, body = openconv_shared::api::bridge::BridgeResponse), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// Replace a bridge's token. The old token stops working immediately.
+/// Instance admins only.
+pub async fn regenerate_token(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(bridge_id): Path<uuid::Uuid>,
+) -> Result<Json<BridgeResponse>, ServerError> {
+    auth.require_instance_admin(&state.config)?;
+
+    let token = secret_token::generate();
+    let replaced = repo::replace_token(&state.db, bridge_id, &secret_token::hash(&token))
+        .await
+        .map_err(db_err)?;
+    if !replaced {
+        return Err(ServerError(OpenConvError::NotFound));
+    }
+
+    let bridge = repo::get(&state.db, bridge_id)
+        .await
+        .map_err(db_err)?
+        .ok_or(ServerError(OpenConvError::NotFound))?;
+    let mut response = bridge.into_response();
+    response.token = Some(token);
+    Ok(Json(response))
+}
+
+#[utoipa::path(delete, path = "/api/admin/bridges/{bridge_id}", tag = "Bridges", security(("bearer_auth" = [])), params(("bridge_id" = uuid::Uuid, Path, description = "Bridge ID")), responses((status = 204), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// Unregister a bridge. Its puppets leave their guilds and are
+/// disconnected; their messages stay. Instance admins only.
+pub async fn delete_bridge(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(bridge_id): Path<uuid::Uuid>,
+) -> Result<StatusCode, ServerError> {
+    auth.require_instance_admin(&state.config)?;
+
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+    let puppets = repo::delete(&mut tx, bridge_id)
+        .await
+        .map_err(db_err)?
+        .ok_or(ServerError(OpenConvError::NotFound))?;
+    tx.commit().await.map_err(db_err)?;
+
+    for user_id in &puppets {
+        crate::ws::connection::disconnect_user(&state, *user_id).await;
+    }
+    tracing::info!(
+        %bridge_id,
+        puppets = puppets.len(),
+        admin = %auth.user_id,
+        "bridge deleted"
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(get, path = "/api/bridge/me", tag = "Bridges", security(("bridge_auth" = [])), responses((status = 200, body = openconv_shared::api::bridge::BridgeResponse), (status = 401, body = crate::error::ErrorResponse)))]
+/// The calling bridge's registration. Requires a bridge token.
+pub async fn get_current_bridge(
+    bridge: BridgeApp,
+    State(state): State<AppState>,
+) -> Result<Json<BridgeResponse>, ServerError> {
+    let bridge = repo::get(&state.db, bridge.bridge_id)
+        .await
+        .map_err(db_err)?
+        .ok_or(ServerError(OpenConvError::NotFound))?;
+    Ok(Json(bridge.into_response()))
+}
+
+#[utoipa::path(get, path = "/api/bridge/puppets", tag = "Bridges", security(("bridge_auth" = [])), responses((status = 200, body = openconv_shared::api::bridge::PuppetListResponse), (status = 401, body = crate::error::ErrorResponse)))]
+/// List the bridge's puppets.
+pub async fn list_puppets(
+    bridge: BridgeApp,
+    State(state): State<AppState>,
+) -> Result<Json<PuppetListResponse>, ServerError> {
+    let puppets = repo::list_puppets(&state.db, bridge.bridge_id)
+        .await
+        .map_err(db_err)?;
+    Ok(Json(PuppetListResponse {
+        puppets: puppets
+            .into_iter()
+            .map(repo::Puppet::into_response)
+            .collect(),
+    }))
+}
+
+#[utoipa::path(put, path = "/api/bridge/puppets/{remote_id}", tag = "Bridges", security(("bridge_auth" = [])), params(("remote_id" = String, Path, description = "The remote account's ID on the bridged network")), request_body = openconv_shared::api::bridge::UpsertPuppetRequest, responses((status = 201, body = openconv_shared::api::bridge::PuppetResponse), (status = 200, body = openconv_shared::api::bridge::PuppetResponse), (status = 400, body = crate::error::ErrorResponse), (status = 401, body = crate::error::ErrorResponse)))]
+/// Create the puppet for a remote account, or update its display name if
+/// it already exists.
+///
+/// Act as the puppet on any endpoint by sending the bridge token with an
+/// `X-Bridge-Puppet: <user_id>` header.
+pub async fn upsert_puppet(
+    bridge: BridgeApp,
+    State(state): State<AppState>,
+    Path(remote_id): Path<String>,
+    Json(body): Json<UpsertPuppetRequest>,
+) -> Result<(StatusCode, Json<PuppetResponse>), ServerError> {
+    validate_remote_id(&remote_id)?;
+    let display_name = validate_display_name(&body.display_name)?;
+
+    if let Some(existing) = repo::get_puppet(&state.db, bridge.bridge_id, &remote_id)
+        .await
+        .map_err(db_err)?
+    {
+        if existing.display_name == display_name {
+            return Ok((StatusCode::OK, Json(existing.into_response())));
+        }
+        repo::rename_puppet(&state.db, existing.user_id, &display_name)
+            .await
+            .map_err(db_err)?;
+        let updated = repo::Puppet {
+            display_name,
+            ..existing
+        };
+        return Ok((StatusCode::OK, Json(updated.into_response())));
+    }
+
+    let registration = repo::get(&state.db, bridge.bridge_id)
+        .await
+        .map_err(db_err)?
+        .ok_or(ServerError(OpenConvError::NotFound))?;
+
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+    let created = repo::create_puppet(
+        &mut tx,
+        &repo::NewPuppet {
+            bridge_id: bridge.bridge_id,
+            namespace: &registration.namespace,
+            remote_id: &remote_id,
+            user_id: UserId::new(),
+            device_id: DeviceId::new(),
+            display_name: &display_name,
+        },
+    )
+    .await;
+    let created = match created {
+        Ok(created) => created,
+        Err(e) if is_unique_violation(&e) => {
+            // A concurrent request created it first.
+            drop(tx);
+            let existing = repo::get_puppet(&state.db, bridge.bridge_id, &remote_id)
+                .await
+                .map_err(db_err)?
+                .ok_or(ServerError(OpenConvError::NotFound))?;
+            return Ok((StatusCode::OK, Json(existing.into_response())));
+        }
+        Err(e) => return Err(db_err(e)),
+    };
+    tx.commit().await.map_err(db_err)?;
+
+    Ok((StatusCode::CREATED, Json(created.into_response())))
+}
+
+#[utoipa::path(get, path = "/api/bridge/puppets/{remote_id}", tag = "Bridges", security(("bridge_auth" = [])), params(("remote_id" = String, Path, description = "The remote account's ID on the bridged network")), responses((status = 200, body = openconv_shared::api::bridge::PuppetResponse), (status = 401, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// Map a remote account to its puppet.
+pub async fn get_puppet(
+    bridge: BridgeApp,
+    State(state): State<AppState>,
+    Path(remote_id): Path<String>,
+) -> Result<Json<PuppetResponse>, ServerError> {
+    let puppet = repo::get_puppet(&state.db, bridge.bridge_id, &remote_id)
+        .await
+        .map_err(db_err)?
+        .ok_or(ServerError(OpenConvError::NotFound))?;
+    Ok(Json(puppet.into_response()))
+}
+
+#[utoipa::path(get, path = "/api/bridge/users/{user_id}", tag = "Bridges", security(("bridge_auth" = [])), params(("user_id" = openconv_shared::ids::UserId, Path, description = "Puppet user ID")), responses((status = 200, body = openconv_shared::api::bridge::PuppetResponse), (status = 401, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// Map a puppet user back to the remote account it mirrors. Returns 404
+/// for users that are not this bridge's puppets.
+pub async fn get_puppet_by_user(
+    bridge: BridgeApp,
+    State(state): State<AppState>,
+    Path(user_id): Path<UserId>,
+) -> Result<Json<PuppetResponse>, ServerError> {
+    let puppet = repo::get_puppet_by_user(&state.db, bridge.bridge_id, user_id)
+        .await
+        .map_err(db_err)?
+        .ok_or(ServerError(OpenConvError::NotFound))?;
+    Ok(Json(puppet.into_response()))
+}
+
+#[utoipa::path(delete, path = "/api/bridge/puppets/{remote_id}", tag = "Bridges", security(("bridge_auth" = [])), params(("remote_id" = String, Path, description = "The remote account's ID on the bridged network")), responses((status = 204), (status = 401, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// Remove a puppet. It leaves its guilds and is disconnected; its messages
+/// stay.
+pub async fn delete_puppet(
+    bridge: BridgeApp,
+    State(state): State<AppState>,
+    Path(remote_id): Path<String>,
+) -> Result<StatusCode, ServerError> {
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+    let (user_id, guilds) = repo::delete_puppet(&mut tx, bridge.bridge_id, &remote_id)
+        .await
+        .map_err(db_err)?
+        .ok_or(ServerError(OpenConvError::NotFound))?;
+    tx.commit().await.map_err(db_err)?;
+    crate::ws::connection::disconnect_user(&state, user_id).await;
+
+    for guild_id in guilds {
+        member_events::publish(
+            &state,
+            MemberEvent::new(guild_id, user_id, MemberEventKind::Leave),
+        )
+        .await;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Routes for managing bridges. Mounted at /api/admin/bridges.
+pub fn admin_routes() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route("/", axum::routing::get(list_bridges).post(create_bridge))
+        .route("/{bridge_id}", axum::routing::delete(delete_bridge))
+        .route("/{bridge_id}/token", axum::routing::post(regenerate_token))
+}
+
+/// Routes for bridges managing their puppets. Mounted at /api/bridge.
+pub fn routes() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route("/me", axum::routing::get(get_current_bridge))
+        .route("/puppets", axum::routing::get(list_puppets))
+        .route(
+            "/puppets/{remote_id}",
+            axum::routing::get(get_puppet)
+                .put(upsert_puppet)
+                .delete(delete_puppet),
+        )
+        .route("/users/{user_id}", axum::routing::get(get_puppet_by_user))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_build_without_panic() {
+        let _ = admin_routes();
+        let _ = routes();
+    }
+
+    #[test]
+    fn namespace_rules() {
+        assert!(validate_namespace("irc").is_ok());
+        assert!(validate_namespace("matrix-org").is_ok());
+        assert!(validate_namespace("x").is_err());
+        assert!(validate_namespace("-irc").is_err());
+        assert!(validate_namespace("IRC").is_err());
+        assert!(validate_namespace("irc.net").is_err());
+    }
+
+    #[test]
+    fn remote_id_rules() {
+        assert!(validate_remote_id("@alice:matrix.org").is_ok());
+        assert!(validate_remote_id("").is_err());
+        assert!(validate_remote_id("a\nb").is_err());
+        assert!(validate_remote_id(&"x".repeat(MAX_REMOTE_ID_LEN + 1)).is_err());
+    }
+}
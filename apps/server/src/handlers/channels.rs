@@ -7,7 +7,8 @@ use axum::Json;
 use openconv_shared::api::channel::{
     ChannelResponse, CreateChannelRequest, ReorderChannelsRequest, UpdateChannelRequest,
 };
-use openconv_shared::error::OpenConvError;
+use openconv_shared::api::voice::VOICE_CHANNEL_TYPE;
+use openconv_shared::error::{ErrorMessage, OpenConvError};
 use openconv_shared::ids::{ChannelId, GuildId};
 use openconv_shared::permissions::Permissions;
 use regex::Regex;
@@ -17,7 +18,7 @@ use crate::extractors::channel_member::ChannelMember;
 use crate::extractors::guild_member::GuildMember;
 use crate::state::AppState;
 
-const ALLOWED_CHANNEL_TYPES: &[&str] = &["text"];
+const ALLOWED_CHANNEL_TYPES: &[&str] = &["text", VOICE_CHANNEL_TYPE];
 const MAX_TOPIC_LENGTH: usize = 1024;
 
 fn db_err(e: sqlx::Error) -> ServerError {
@@ -30,15 +31,22 @@ static CHANNEL_NAME_RE: LazyLock<Regex> =
 
 fn validate_channel_name(name: &str) -> Result<(), ServerError> {
     if name.is_empty() || name.len() > 100 {
-        return Err(ServerError(OpenConvError::Validation(
-            "Channel name must be 1-100 characters".into(),
-        )));
+        return Err(ServerError(
+            OpenConvError::Validation("Channel name must be 1-100 characters".into()).with_message(
+                ErrorMessage::new("channel_name.length")
+                    .param("min", 1)
+                    .param("max", 100),
+            ),
+        ));
     }
     if !CHANNEL_NAME_RE.is_match(name) {
-        return Err(ServerError(OpenConvError::Validation(
-            "Channel name must be lowercase alphanumeric with hyphens, no leading/trailing hyphens"
-                .into(),
-        )));
+        return Err(ServerError(
+            OpenConvError::Validation(
+                "Channel name must be lowercase alphanumeric with hyphens, no leading/trailing hyphens"
+                    .into(),
+            )
+            .with_message(ErrorMessage::new("channel_name.format")),
+        ));
     }
     Ok(())
 }
@@ -148,9 +156,14 @@ pub async fn update_channel(
 
     if let Some(ref topic) = body.topic {
         if topic.len() > MAX_TOPIC_LENGTH {
-            return Err(ServerError(OpenConvError::Validation(format!(
-                "Topic must be at most {MAX_TOPIC_LENGTH} characters"
-            ))));
+            return Err(ServerError(
+                OpenConvError::Validation(format!(
+                    "Topic must be at most {MAX_TOPIC_LENGTH} characters"
+                ))
+                .with_message(
+                    ErrorMessage::new("channel_topic.too_long").param("max", MAX_TOPIC_LENGTH),
+                ),
+            ));
         }
     }
 
@@ -232,6 +245,9 @@ pub async fn delete_channel(
 
     tx.commit().await.map_err(db_err)?;
 
+    crate::ws::voice::clear_channel(&state, channel_member.guild_id, channel_member.channel_id)
+        .await;
+
     Ok(StatusCode::OK)
 }
 
@@ -0,0 +1,158 @@
+use axum::extract::State;
+use axum::Json;
+use openconv_shared::error::OpenConvError;
+
+use crate::discovery::{parse_hash, HASH_BYTES};
+use crate::error::ServerError;
+use crate::extractors::auth::AuthUser;
+use crate::handlers::users::PublicProfileResponse;
+use crate::middleware::rate_limit::check_discovery_budget;
+use crate::repo;
+use crate::state::AppState;
+
+fn db_err(e: sqlx::Error) -> ServerError {
+    tracing::error!(error = %e, "database error");
+    ServerError(OpenConvError::Internal("database error".into()))
+}
+
+/// How clients hash contacts for `POST /api/discovery`.
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct DiscoveryParamsResponse {
+    /// Prefixed to each normalized (trimmed, lowercase) email address
+    /// before hashing with SHA-256.
+    pub salt: String,
+    /// Leading bytes of the SHA-256 digest to send, hex encoded.
+    pub hash_bytes: usize,
+    pub max_hashes_per_request: usize,
+    /// Hashes each user may look up per day; 0 when unlimited.
+    pub daily_hash_budget: u32,
+}
+
+#[derive(Debug, serde::Deserialize, utoipa::ToSchema)]
+pub struct DiscoverContactsRequest {
+    /// Hex-encoded truncated hashes. Duplicates are counted once.
+    pub hashes: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct DiscoveredContact {
+    /// The requested hash this account matched, lowercase hex.
+    pub hash: String,
+    pub user: PublicProfileResponse,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct DiscoverContactsResponse {
+    /// Accounts matching any of the hashes, in no particular order. A hash
+    /// can match several accounts, so clients should compare the full
+    /// hash of the contact against each match where they can.
+    pub matches: Vec<DiscoveredContact>,
+}
+
+#[utoipa::path(get, path = "/api/discovery", tag = "Discovery", security(("bearer_auth" = [])), responses((status = 200, body = DiscoveryParamsResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// GET /api/discovery
+/// The salt and limits for contact discovery. 404 when the instance has
+/// discovery turned off.
+pub async fn get_discovery_params(
+    State(state): State<AppState>,
+    _auth: AuthUser,
+) -> Result<Json<DiscoveryParamsResponse>, ServerError> {
+    let config = &state.config.discovery;
+    if !config.is_enabled() {
+        return Err(ServerError(OpenConvError::NotFound));
+    }
+    Ok(Json(DiscoveryParamsResponse {
+        salt: config.salt.clone(),
+        hash_bytes: HASH_BYTES,
+        max_hashes_per_request: config.max_hashes_per_request,
+        daily_hash_budget: config.daily_hash_budget,
+    }))
+}
+
+#[utoipa::path(post, path = "/api/discovery", tag = "Discovery", security(("bearer_auth" = [])), request_body = DiscoverContactsRequest, responses((status = 200, body = DiscoverContactsResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse), (status = 429, body = crate::error::ErrorResponse)))]
+/// POST /api/discovery
+/// Which of a batch of contact hashes belong to active accounts. Each
+/// distinct hash counts against the caller's daily budget, whether or not
+/// it matches; a batch the remaining budget cannot cover is rejected
+/// whole with 429.
+pub async fn discover_contacts(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(req): Json<DiscoverContactsRequest>,
+) -> Result<Json<DiscoverContactsResponse>, ServerError> {
+    let config = &state.config.discovery;
+    if !config.is_enabled() {
+        return Err(ServerError(OpenConvError::NotFound));
+    }
+    auth.require_human()?;
+
+    if req.hashes.len() > config.max_hashes_per_request {
+        return Err(OpenConvError::Validation(format!(
+            "at most {} hashes per request",
+            config.max_hashes_per_request
+        ))
+        .into());
+    }
+    let mut hashes = req
+        .hashes
+        .iter()
+        .map(|hash| parse_hash(hash))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| {
+            OpenConvError::Validation(format!("hashes must be {HASH_BYTES} bytes of hex"))
+        })?;
+    hashes.sort_unstable();
+    hashes.dedup();
+    if hashes.is_empty() {
+        return Ok(Json(DiscoverContactsResponse {
+            matches: Vec::new(),
+        }));
+    }
+
+    if config.daily_hash_budget > 0 {
+        check_discovery_budget(
+            &state.redis,
+            auth.user_id,
+            hashes.len() as u32,
+            config.daily_hash_budget,
+            state.live.settings().rate_limit.fail_mode,
+        )
+        .await?;
+    }
+
+    let users = repo::users::discoverable_by_hashes(&state.db, &hashes, auth.user_id)
+        .await
+        .map_err(db_err)?;
+    Ok(Json(DiscoverContactsResponse {
+        matches: users
+            .into_iter()
+            .map(|user| DiscoveredContact {
+                hash: hex::encode(&user.discovery_hash),
+                user: PublicProfileResponse {
+                    id: user.id,
+                    display_name: user.display_name,
+                    avatar_url: user.avatar_url,
+                    public_key: user.public_key,
+                },
+            })
+            .collect(),
+    }))
+}
+
+/// Route builder for contact discovery.
+pub fn routes() -> axum::Router<AppState> {
+    axum::Router::new().route(
+        "/",
+        axum::routing::get(get_discovery_params).post(discover_contacts),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_build_without_panic() {
+        let _ = routes();
+    }
+}
@@ -2,9 +2,10 @@ use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 use axum::Json;
 use openconv_shared::api::dm_channel::{
-    AddDmMemberRequest, CreateDmChannelRequest, DmChannelResponse,
+    AddDmMemberRequest, CreateDmChannelRequest, DmChannelResponse, SetDmArchiveRequest,
 };
-use openconv_shared::error::OpenConvError;
+use openconv_shared::api::ws::ServerMessage;
+use openconv_shared::error::{ErrorMessage, OpenConvError};
 use openconv_shared::ids::{DmChannelId, UserId};
 
 use crate::error::ServerError;
@@ -16,6 +17,28 @@ fn db_err(e: sqlx::Error) -> ServerError {
     ServerError(OpenConvError::Internal("database error".into()))
 }
 
+/// Whether the member row `dcm` has its channel archived. An archive lapses
+/// once another member posts after it, unless the member chose to keep it.
+const ARCHIVED_SQL: &str =
+    "(dcm.archived_at IS NOT NULL AND NOT (dcm.unarchive_on_message AND EXISTS( \
+     SELECT 1 FROM messages m \
+     WHERE m.dm_channel_id = dcm.dm_channel_id AND m.sender_id <> dcm.user_id \
+       AND m.deleted = false AND m.created_at > dcm.archived_at)))";
+
+/// IDs of the DM channels `user_id` currently has archived.
+pub(crate) async fn archived_channel_ids(
+    db: &sqlx::PgPool,
+    user_id: UserId,
+) -> Result<Vec<DmChannelId>, sqlx::Error> {
+    sqlx::query_scalar(&format!(
+        "SELECT dcm.dm_channel_id FROM dm_channel_members dcm \
+         WHERE dcm.user_id = $1 AND {ARCHIVED_SQL}"
+    ))
+    .bind(user_id)
+    .fetch_all(db)
+    .await
+}
+
 /// Verify the user is a member of the DM channel. Returns 403 if not.
 async fn require_dm_membership(
     db: &sqlx::PgPool,
@@ -59,6 +82,8 @@ fn build_response(row: DmChannelRow, members: Vec<UserId>) -> DmChannelResponse
         name: row.name,
         creator_id: row.creator_id,
         is_group: row.is_group,
+        is_notes: row.is_notes,
+        archived: row.archived,
         members,
         created_at: row.created_at,
     }
@@ -72,17 +97,17 @@ pub async fn create(
     auth: AuthUser,
     Json(body): Json<CreateDmChannelRequest>,
 ) -> Result<(StatusCode, Json<DmChannelResponse>), ServerError> {
+    auth.require_human()?;
     if body.user_ids.is_empty() {
         return Err(ServerError(OpenConvError::Validation(
             "user_ids must not be empty".into(),
         )));
     }
 
-    // Prevent creating a DM with yourself only
+    // A DM with only yourself is your notes channel
     if body.user_ids.len() == 1 && body.user_ids[0] == auth.user_id {
-        return Err(ServerError(OpenConvError::Validation(
-            "cannot create a DM with yourself".into(),
-        )));
+        let channel = get_or_create_notes(&state, auth.user_id).await?;
+        return Ok((StatusCode::OK, Json(channel)));
     }
 
     if body.user_ids.len() == 1 {
@@ -115,7 +140,7 @@ async fn create_one_to_one(
 
     // Check for existing 1:1 DM between these two users (inside transaction)
     let existing = sqlx::query_as::<_, DmChannelRow>(
-        "SELECT dc.id, dc.name, dc.creator_id, dc.is_group, dc.created_at \
+        "SELECT dc.id, dc.name, dc.creator_id, dc.is_group, dc.is_notes, dc.created_at \
          FROM dm_channels dc \
          WHERE dc.is_group = false \
          AND EXISTS (SELECT 1 FROM dm_channel_members WHERE dm_channel_id = dc.id AND user_id = $1) \
@@ -138,7 +163,7 @@ async fn create_one_to_one(
     // Create new 1:1 DM
     let row = sqlx::query_as::<_, DmChannelRow>(
         "INSERT INTO dm_channels (creator_id, is_group) VALUES ($1, false) \
-         RETURNING id, name, creator_id, is_group, created_at",
+         RETURNING id, name, creator_id, is_group, is_notes, created_at",
     )
     .bind(auth.user_id)
     .fetch_one(&mut *tx)
@@ -169,9 +194,10 @@ async fn create_group(
     // Validate name length
     if let Some(ref name) = body.name {
         if name.len() > 100 {
-            return Err(ServerError(OpenConvError::Validation(
-                "group DM name must be 100 characters or fewer".into(),
-            )));
+            return Err(ServerError(
+                OpenConvError::Validation("group DM name must be 100 characters or fewer".into())
+                    .with_message(ErrorMessage::new("group_dm_name.too_long").param("max", 100)),
+            ));
         }
     }
 
@@ -208,7 +234,7 @@ async fn create_group(
 
     let row = sqlx::query_as::<_, DmChannelRow>(
         "INSERT INTO dm_channels (name, creator_id, is_group) VALUES ($1, $2, true) \
-         RETURNING id, name, creator_id, is_group, created_at",
+         RETURNING id, name, creator_id, is_group, is_notes, created_at",
     )
     .bind(&body.name)
     .bind(auth.user_id)
@@ -230,21 +256,84 @@ async fn create_group(
     Ok((StatusCode::CREATED, Json(build_response(row, participants))))
 }
 
-#[utoipa::path(get, path = "/api/dm-channels", tag = "DM Channels", security(("bearer_auth" = [])), responses((status = 200, body = Vec<openconv_shared::api::dm_channel::DmChannelResponse>)))]
+/// Create the user's notes channel if they don't have one yet.
+///
+/// Called inside the registration transaction so every account starts with
+/// one; [`get_or_create_notes`] covers accounts from before notes existed.
+pub(crate) async fn insert_notes_channel(
+    conn: &mut sqlx::PgConnection,
+    user_id: UserId,
+) -> Result<(), sqlx::Error> {
+    let created: Option<DmChannelId> = sqlx::query_scalar(
+        "INSERT INTO dm_channels (creator_id, is_group, is_notes) VALUES ($1, false, true) \
+         ON CONFLICT (creator_id) WHERE is_notes DO NOTHING \
+         RETURNING id",
+    )
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if let Some(id) = created {
+        sqlx::query("INSERT INTO dm_channel_members (dm_channel_id, user_id) VALUES ($1, $2)")
+            .bind(id)
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+async fn get_or_create_notes(
+    state: &AppState,
+    user_id: UserId,
+) -> Result<DmChannelResponse, ServerError> {
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+    insert_notes_channel(&mut tx, user_id)
+        .await
+        .map_err(db_err)?;
+    tx.commit().await.map_err(db_err)?;
+
+    let row = sqlx::query_as::<_, DmChannelRow>(
+        "SELECT id, name, creator_id, is_group, is_notes, created_at FROM dm_channels \
+         WHERE creator_id = $1 AND is_notes",
+    )
+    .bind(user_id)
+    .fetch_one(&state.db)
+    .await
+    .map_err(db_err)?;
+
+    Ok(build_response(row, vec![user_id]))
+}
+
+#[utoipa::path(get, path = "/api/dm-channels/notes", tag = "DM Channels", security(("bearer_auth" = [])), responses((status = 200, body = openconv_shared::api::dm_channel::DmChannelResponse)))]
+/// GET /api/dm-channels/notes
+/// The authenticated user's personal notes channel, created on first use.
+pub async fn notes(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<DmChannelResponse>, ServerError> {
+    Ok(Json(get_or_create_notes(&state, auth.user_id).await?))
+}
+
+#[utoipa::path(get, path = "/api/dm-channels", tag = "DM Channels", security(("bearer_auth" = [])), params(crate::handlers::dm_channels::ListQuery), responses((status = 200, body = Vec<openconv_shared::api::dm_channel::DmChannelResponse>)))]
 /// GET /api/dm-channels
-/// List the authenticated user's DM channels.
+/// List the authenticated user's DM channels. Archived channels are listed
+/// only with `?archived=true`, and then exclusively.
 pub async fn list(
     State(state): State<AppState>,
     auth: AuthUser,
+    Query(params): Query<ListQuery>,
 ) -> Result<Json<Vec<DmChannelResponse>>, ServerError> {
-    let rows = sqlx::query_as::<_, DmChannelRow>(
-        "SELECT dc.id, dc.name, dc.creator_id, dc.is_group, dc.created_at \
+    let rows = sqlx::query_as::<_, DmChannelRow>(&format!(
+        "SELECT dc.id, dc.name, dc.creator_id, dc.is_group, dc.is_notes, \
+                {ARCHIVED_SQL} AS archived, dc.created_at \
          FROM dm_channels dc \
          JOIN dm_channel_members dcm ON dcm.dm_channel_id = dc.id \
-         WHERE dcm.user_id = $1 \
-         ORDER BY dc.created_at DESC",
-    )
+         WHERE dcm.user_id = $1 AND {ARCHIVED_SQL} = $2 \
+         ORDER BY dc.created_at DESC"
+    ))
     .bind(auth.user_id)
+    .bind(params.archived)
     .fetch_all(&state.db)
     .await
     .map_err(db_err)?;
@@ -295,18 +384,69 @@ pub async fn get_one(
     Path(id): Path<DmChannelId>,
 ) -> Result<Json<DmChannelResponse>, ServerError> {
     require_dm_membership(&state.db, id, auth.user_id).await?;
+    Ok(Json(load_channel(&state.db, id, auth.user_id).await?))
+}
 
-    let row = sqlx::query_as::<_, DmChannelRow>(
-        "SELECT id, name, creator_id, is_group, created_at FROM dm_channels WHERE id = $1",
-    )
+/// Load a DM channel as seen by `user_id`, who must be a member.
+async fn load_channel(
+    db: &sqlx::PgPool,
+    id: DmChannelId,
+    user_id: UserId,
+) -> Result<DmChannelResponse, ServerError> {
+    let row = sqlx::query_as::<_, DmChannelRow>(&format!(
+        "SELECT dc.id, dc.name, dc.creator_id, dc.is_group, dc.is_notes, \
+                {ARCHIVED_SQL} AS archived, dc.created_at \
+         FROM dm_channels dc \
+         JOIN dm_channel_members dcm ON dcm.dm_channel_id = dc.id AND dcm.user_id = $2 \
+         WHERE dc.id = $1"
+    ))
     .bind(id)
-    .fetch_optional(&state.db)
+    .bind(user_id)
+    .fetch_optional(db)
     .await
     .map_err(db_err)?
     .ok_or(ServerError(OpenConvError::NotFound))?;
 
-    let members = fetch_members(&state.db, id).await?;
-    Ok(Json(build_response(row, members)))
+    let members = fetch_members(db, id).await?;
+    Ok(build_response(row, members))
+}
+
+#[utoipa::path(put, path = "/api/dm-channels/{id}/archive", tag = "DM Channels", security(("bearer_auth" = [])), params(("id" = openconv_shared::ids::DmChannelId, Path, description = "DM channel ID")), request_body = openconv_shared::api::dm_channel::SetDmArchiveRequest, responses((status = 200, body = openconv_shared::api::dm_channel::DmChannelResponse), (status = 403, body = crate::error::ErrorResponse)))]
+/// PUT /api/dm-channels/:id/archive
+/// Archive or unarchive a DM channel for the caller only. Membership and
+/// history are untouched; the caller's other devices are notified.
+pub async fn set_archive(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<DmChannelId>,
+    Json(body): Json<SetDmArchiveRequest>,
+) -> Result<Json<DmChannelResponse>, ServerError> {
+    require_dm_membership(&state.db, id, auth.user_id).await?;
+
+    sqlx::query(
+        "UPDATE dm_channel_members \
+         SET archived_at = CASE WHEN $3 THEN NOW() END, unarchive_on_message = $4 \
+         WHERE dm_channel_id = $1 AND user_id = $2",
+    )
+    .bind(id)
+    .bind(auth.user_id)
+    .bind(body.archived)
+    .bind(body.unarchive_on_message)
+    .execute(&state.db)
+    .await
+    .map_err(db_err)?;
+
+    crate::ws::journal::publish_to_user(
+        &state,
+        auth.user_id,
+        ServerMessage::DmArchiveUpdated {
+            dm_channel_id: id,
+            archived: body.archived,
+        },
+    )
+    .await;
+
+    Ok(Json(load_channel(&state.db, id, auth.user_id).await?))
 }
 
 #[utoipa::path(post, path = "/api/dm-channels/{id}/members", tag = "DM Channels", security(("bearer_auth" = [])), params(("id" = openconv_shared::ids::DmChannelId, Path, description = "DM channel ID")), request_body = openconv_shared::api::dm_channel::AddDmMemberRequest, responses((status = 200, body = openconv_shared::api::dm_channel::DmChannelResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse)))]
@@ -382,7 +522,7 @@ pub async fn add_member(
 
     // Return updated channel
     let row = sqlx::query_as::<_, DmChannelRow>(
-        "SELECT id, name, creator_id, is_group, created_at FROM dm_channels WHERE id = $1",
+        "SELECT id, name, creator_id, is_group, is_notes, created_at FROM dm_channels WHERE id = $1",
     )
     .bind(id)
     .fetch_one(&state.db)
@@ -508,7 +648,9 @@ pub async fn messages(
 pub fn routes() -> axum::Router<AppState> {
     axum::Router::new()
         .route("/", axum::routing::post(create).get(list))
+        .route("/notes", axum::routing::get(notes))
         .route("/{id}", axum::routing::get(get_one))
+        .route("/{id}/archive", axum::routing::put(set_archive))
         .route("/{id}/members", axum::routing::post(add_member))
         .route("/{id}/members/me", axum::routing::delete(leave))
         .route("/{id}/messages", axum::routing::get(messages))
@@ -522,6 +664,10 @@ struct DmChannelRow {
     name: Option<String>,
     creator_id: Option<UserId>,
     is_group: bool,
+    is_notes: bool,
+    /// Only selected where the caller's member row is joined.
+    #[sqlx(default)]
+    archived: bool,
     created_at: chrono::DateTime<chrono::Utc>,
 }
 
@@ -531,6 +677,13 @@ struct MemberRow {
     user_id: UserId,
 }
 
+#[derive(Debug, serde::Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
+pub struct ListQuery {
+    /// List archived channels instead of active ones.
+    #[serde(default)]
+    pub archived: bool,
+}
+
 #[derive(Debug, serde::Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
 pub struct MessageQuery {
     pub cursor: Option<String>,
@@ -0,0 +1,210 @@
+//! Federation endpoints (experimental, behind the `federation` feature).
+//!
+//! `server_routes` is the API other instances call; `client_routes` lets
+//! local users join and leave guilds hosted elsewhere. Neither is listed in
+//! the OpenAPI document while the protocol is experimental.
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use openconv_shared::error::OpenConvError;
+use openconv_shared::federation::{
+    FederatedGuildListResponse, FederationEvent, RemoteJoinRequest, RemoteUserId, ServerKey,
+    ServerKeysResponse, ServerName, TransactionResponse, MAX_TRANSACTION_EVENTS,
+};
+use openconv_shared::ids::GuildId;
+
+use crate::error::ServerError;
+use crate::extractors::auth::AuthUser;
+use crate::federation::signing::{self, FederationKeys};
+use crate::federation::{inbound, outbox, peers};
+use crate::repo::federation as repo;
+use crate::state::AppState;
+
+fn db_err(e: sqlx::Error) -> ServerError {
+    tracing::error!(error = %e, "database error");
+    ServerError(OpenConvError::Internal("database error".into()))
+}
+
+fn keys(state: &AppState) -> Result<FederationKeys, ServerError> {
+    FederationKeys::from_config(&state.config.federation).map_err(ServerError)
+}
+
+async fn peer_keys(
+    state: &AppState,
+    origin: &ServerName,
+    refresh: bool,
+) -> Result<Vec<ServerKey>, ServerError> {
+    peers::keys_for(&state.redis, origin, refresh)
+        .await
+        .map_err(|error| {
+            tracing::warn!(%origin, %error, "could not fetch federation keys");
+            ServerError(OpenConvError::ServiceUnavailable)
+        })
+}
+
+/// GET /_openconv/federation/v1/keys
+/// This instance's name and federation signing key.
+pub async fn server_keys(
+    State(state): State<AppState>,
+) -> Result<Json<ServerKeysResponse>, ServerError> {
+    Ok(Json(keys(&state)?.public_keys()))
+}
+
+/// POST /_openconv/federation/v1/transactions
+/// Receive a signed transaction (a compact JWS) from a trusted peer.
+pub async fn receive_transaction(
+    State(state): State<AppState>,
+    token: String,
+) -> Result<Json<TransactionResponse>, ServerError> {
+    let config = &state.config.federation;
+    let origin = signing::peek_issuer(&token).ok_or(ServerError(OpenConvError::Unauthorized))?;
+    if !crate::federation::is_trusted(config, &origin) {
+        return Err(ServerError(OpenConvError::Forbidden));
+    }
+    let local = crate::federation::local_server_name(config);
+
+    // A peer that rotated its key is refetched once before rejecting.
+    let txn = match signing::verify(
+        &token,
+        &origin,
+        &peer_keys(&state, &origin, false).await?,
+        &local,
+    ) {
+        Ok(txn) => txn,
+        Err(_) => signing::verify(
+            &token,
+            &origin,
+            &peer_keys(&state, &origin, true).await?,
+            &local,
+        )?,
+    };
+
+    if txn.events.len() > MAX_TRANSACTION_EVENTS {
+        return Err(ServerError(OpenConvError::Validation(format!(
+            "a transaction holds at most {MAX_TRANSACTION_EVENTS} events"
+        ))));
+    }
+    let accepted = inbound::apply(&state, &origin, txn).await?;
+    Ok(Json(TransactionResponse { accepted }))
+}
+
+/// POST /api/federation/joins
+/// Ask another instance to let the caller join one of its guilds with an
+/// invite. Accepted for delivery; the guild shows up in
+/// `GET /api/federation/guilds` once its host confirms the join.
+pub async fn request_join(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(req): Json<RemoteJoinRequest>,
+) -> Result<StatusCode, ServerError> {
+    auth.require_human()?;
+    let config = &state.config.federation;
+    let local = crate::federation::local_server_name(config);
+    if req.server == local || !crate::federation::is_trusted(config, &req.server) {
+        return Err(ServerError(OpenConvError::Validation(
+            "server is not a trusted federation peer".into(),
+        )));
+    }
+    if req.invite_code.is_empty() || req.invite_code.len() > 64 {
+        return Err(ServerError(OpenConvError::Validation(
+            "invite code is invalid".into(),
+        )));
+    }
+    // Stand-ins for remote users cannot federate onward.
+    if repo::remote_identity(&state.db, auth.user_id)
+        .await
+        .map_err(db_err)?
+        .is_some()
+    {
+        return Err(ServerError(OpenConvError::Forbidden));
+    }
+
+    let display_name: String = sqlx::query_scalar("SELECT display_name FROM users WHERE id = $1")
+        .bind(auth.user_id)
+        .fetch_one(&state.db)
+        .await
+        .map_err(db_err)?;
+    let event = FederationEvent::JoinRequest {
+        user: RemoteUserId {
+            user_id: auth.user_id,
+            server: local,
+        },
+        display_name,
+        invite_code: req.invite_code,
+    };
+    outbox::enqueue(&state, &req.server, &[event])
+        .await
+        .map_err(db_err)?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// GET /api/federation/guilds
+/// Guilds on other instances the caller belongs to.
+pub async fn list_guilds(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<FederatedGuildListResponse>, ServerError> {
+    let guilds = repo::list_memberships(&state.db, auth.user_id)
+        .await
+        .map_err(db_err)?;
+    Ok(Json(FederatedGuildListResponse { guilds }))
+}
+
+/// DELETE /api/federation/guilds/:server/:guild_id
+/// Leave a guild hosted on another instance. The membership is dropped here
+/// right away and the host is told in the background.
+pub async fn leave_guild(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((server, guild_id)): Path<(ServerName, GuildId)>,
+) -> Result<StatusCode, ServerError> {
+    let removed = repo::delete_membership(&state.db, auth.user_id, &server, guild_id)
+        .await
+        .map_err(db_err)?;
+    if !removed {
+        return Err(ServerError(OpenConvError::NotFound));
+    }
+
+    let event = FederationEvent::LeaveRequest {
+        user: RemoteUserId {
+            user_id: auth.user_id,
+            server: crate::federation::local_server_name(&state.config.federation),
+        },
+        guild_id,
+    };
+    outbox::enqueue(&state, &server, &[event])
+        .await
+        .map_err(db_err)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Route builder for the instance-to-instance API, nested at
+/// [`FEDERATION_PATH_PREFIX`](openconv_shared::federation::FEDERATION_PATH_PREFIX).
+pub fn server_routes() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route("/keys", axum::routing::get(server_keys))
+        .route("/transactions", axum::routing::post(receive_transaction))
+}
+
+/// Route builder for local users' federation endpoints.
+pub fn client_routes() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route("/joins", axum::routing::post(request_join))
+        .route("/guilds", axum::routing::get(list_guilds))
+        .route(
+            "/guilds/{server}/{guild_id}",
+            axum::routing::delete(leave_guild),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_build_without_panic() {
+        let _ = server_routes();
+        let _ = client_routes();
+    }
+}
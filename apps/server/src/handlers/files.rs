@@ -1,20 +1,29 @@
 use axum::body::Body;
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
 use axum::http::{header, StatusCode};
 use axum::response::Response;
 use axum::Json;
 use axum_extra::extract::Multipart;
+use hmac::{Hmac, Mac};
 use object_store::path::Path as StorePath;
-use object_store::{ObjectStore, PutPayload};
-use openconv_shared::api::file::{FileMetaResponse, FileResponse};
+use object_store::{ObjectStore, WriteMultipart};
+use openconv_shared::api::file::{
+    DownloadUrlResponse, FileMetaResponse, FileResponse, MessageAttachmentsResponse,
+    SetAttachmentsRequest,
+};
 use openconv_shared::error::OpenConvError;
-use openconv_shared::ids::{DmChannelId, FileId, GuildId, UserId};
+use openconv_shared::ids::{ChannelId, DmChannelId, FileId, GuildId, MessageId, UserId};
 use openconv_shared::permissions::Permissions;
+use sha2::{Digest, Sha256};
 
+use crate::config::ServerConfig;
 use crate::error::ServerError;
 use crate::extractors::auth::AuthUser;
 use crate::extractors::channel_member::ChannelMember;
+use crate::handlers::quotas;
 use crate::state::AppState;
+use crate::tasks::file_cleanup::{blob_path, file_object_path};
+use crate::tasks::upload_scan;
 
 /// Doc-only schema describing the multipart upload body.
 #[derive(utoipa::ToSchema)]
@@ -33,6 +42,8 @@ pub struct FileUploadBody {
 const MAX_FILE_NAME_LEN: usize = 255;
 const MAX_MIME_TYPE_LEN: usize = 127;
 const MAX_ENCRYPTED_BLOB_KEY_LEN: usize = 4096;
+/// Parts buffered in flight while streaming an upload to the object store.
+pub(crate) const UPLOAD_MAX_CONCURRENT_PARTS: usize = 4;
 
 fn db_err(e: sqlx::Error) -> ServerError {
     tracing::error!(error = %e, "database error");
@@ -69,21 +80,142 @@ fn validate_mime_type(mime: &str) -> Result<(), ServerError> {
     Ok(())
 }
 
+/// Validate and sanitize an uploaded file's name.
+pub(crate) fn validate_file_name(name: &str) -> Result<String, ServerError> {
+    if name.len() > MAX_FILE_NAME_LEN {
+        return Err(ServerError(OpenConvError::Validation(
+            "file_name too long".into(),
+        )));
+    }
+    let sanitized = sanitize_file_name(name);
+    if sanitized.is_empty() {
+        return Err(ServerError(OpenConvError::Validation(
+            "file_name is empty after sanitization".into(),
+        )));
+    }
+    Ok(sanitized)
+}
+
+/// Validate a MIME type's format and check it against the server allowlist.
+pub(crate) fn validate_upload_mime_type(
+    config: &crate::config::FileStorageConfig,
+    mime: &str,
+) -> Result<(), ServerError> {
+    validate_mime_type(mime)?;
+    if !config.mime_type_allowed(mime) {
+        return Err(ServerError(OpenConvError::Validation(format!(
+            "mime_type {mime} is not allowed on this server"
+        ))));
+    }
+    Ok(())
+}
+
+pub(crate) fn validate_encrypted_blob_key(key: &str) -> Result<(), ServerError> {
+    if key.len() > MAX_ENCRYPTED_BLOB_KEY_LEN {
+        return Err(ServerError(OpenConvError::Validation(
+            "encrypted_blob_key too long".into(),
+        )));
+    }
+    Ok(())
+}
+
 // ─── Shared multipart parsing ──────────────────────────────
 
 struct ParsedUpload {
-    file_bytes: Vec<u8>,
+    size_bytes: i64,
+    /// Hex SHA-256 of the uploaded ciphertext.
+    digest: String,
     file_name: String,
     mime_type: String,
     encrypted_blob_key: String,
 }
 
-/// Parse multipart fields for file upload. Validates sizes and field lengths.
+pub(crate) fn store_err(e: object_store::Error) -> ServerError {
+    tracing::error!(error = %e, "object store write failed");
+    ServerError(OpenConvError::Internal("file storage error".into()))
+}
+
+/// Delete a blob in the background, e.g. after a failed upload.
+pub(crate) fn delete_blob_later(state: &AppState, path: &StorePath) {
+    let store = state.object_store.clone();
+    let path = path.clone();
+    tokio::spawn(async move {
+        if let Err(e) = store.delete(&path).await {
+            if !matches!(e, object_store::Error::NotFound { .. }) {
+                tracing::error!(error = %e, "failed to clean up orphan blob");
+            }
+        }
+    });
+}
+
+/// Stream a multipart field into the object store at `path` without
+/// buffering the whole file. Returns the number of bytes written and the
+/// hex SHA-256 digest of the content.
+async fn stream_field_to_store(
+    state: &AppState,
+    mut field: axum_extra::extract::multipart::Field,
+    path: &StorePath,
+    max_size: u64,
+) -> Result<(u64, String), ServerError> {
+    let upload = state
+        .object_store
+        .put_multipart(path)
+        .await
+        .map_err(store_err)?;
+    let mut writer = WriteMultipart::new(upload);
+    let mut hasher = Sha256::new();
+    let mut written = 0u64;
+
+    loop {
+        let chunk = match field.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(_) => {
+                let _ = writer.abort().await;
+                return Err(ServerError(OpenConvError::Validation(
+                    "failed to read file data".into(),
+                )));
+            }
+        };
+        written += chunk.len() as u64;
+        if written > max_size {
+            let _ = writer.abort().await;
+            return Err(payload_too_large(max_size));
+        }
+        if let Err(e) = writer.wait_for_capacity(UPLOAD_MAX_CONCURRENT_PARTS).await {
+            let _ = writer.abort().await;
+            return Err(store_err(e));
+        }
+        hasher.update(&chunk);
+        writer.write(&chunk);
+    }
+
+    writer.finish().await.map_err(store_err)?;
+    Ok((written, hex::encode(hasher.finalize())))
+}
+
+/// Parse multipart fields for file upload, streaming the `file` field to
+/// `storage_path`. Validates sizes, field lengths and the MIME allowlist.
+/// The blob is removed again if validation fails.
 async fn parse_upload_multipart(
+    state: &AppState,
     multipart: &mut Multipart,
-    max_size: u64,
+    storage_path: &StorePath,
 ) -> Result<ParsedUpload, ServerError> {
-    let mut file_bytes: Option<Vec<u8>> = None;
+    let result = read_upload_fields(state, multipart, storage_path).await;
+    if result.is_err() {
+        delete_blob_later(state, storage_path);
+    }
+    result
+}
+
+async fn read_upload_fields(
+    state: &AppState,
+    multipart: &mut Multipart,
+    storage_path: &StorePath,
+) -> Result<ParsedUpload, ServerError> {
+    let config = &state.config.file_storage;
+    let mut stored: Option<(u64, String)> = None;
     let mut file_name: Option<String> = None;
     let mut mime_type: Option<String> = None;
     let mut encrypted_blob_key: Option<String> = None;
@@ -92,36 +224,27 @@ async fn parse_upload_multipart(
         let name = field.name().unwrap_or("").to_string();
         match name.as_str() {
             "file" => {
-                let data = field.bytes().await.map_err(|_| {
-                    ServerError(OpenConvError::Validation("failed to read file data".into()))
-                })?;
-                if data.len() as u64 > max_size {
-                    return Err(payload_too_large(max_size));
+                if stored.is_some() {
+                    return Err(ServerError(OpenConvError::Validation(
+                        "duplicate file field".into(),
+                    )));
                 }
-                file_bytes = Some(data.to_vec());
+                stored = Some(
+                    stream_field_to_store(state, field, storage_path, config.max_file_size_bytes)
+                        .await?,
+                );
             }
             "file_name" => {
                 let val = field.text().await.map_err(|_| {
                     ServerError(OpenConvError::Validation("failed to read file_name".into()))
                 })?;
-                if val.len() > MAX_FILE_NAME_LEN {
-                    return Err(ServerError(OpenConvError::Validation(
-                        "file_name too long".into(),
-                    )));
-                }
-                let sanitized = sanitize_file_name(&val);
-                if sanitized.is_empty() {
-                    return Err(ServerError(OpenConvError::Validation(
-                        "file_name is empty after sanitization".into(),
-                    )));
-                }
-                file_name = Some(sanitized);
+                file_name = Some(validate_file_name(&val)?);
             }
             "mime_type" => {
                 let val = field.text().await.map_err(|_| {
                     ServerError(OpenConvError::Validation("failed to read mime_type".into()))
                 })?;
-                validate_mime_type(&val)?;
+                validate_upload_mime_type(config, &val)?;
                 mime_type = Some(val);
             }
             "encrypted_blob_key" => {
@@ -130,18 +253,14 @@ async fn parse_upload_multipart(
                         "failed to read encrypted_blob_key".into(),
                     ))
                 })?;
-                if val.len() > MAX_ENCRYPTED_BLOB_KEY_LEN {
-                    return Err(ServerError(OpenConvError::Validation(
-                        "encrypted_blob_key too long".into(),
-                    )));
-                }
+                validate_encrypted_blob_key(&val)?;
                 encrypted_blob_key = Some(val);
             }
             _ => {}
         }
     }
 
-    let file_bytes = file_bytes
+    let (size_bytes, digest) = stored
         .ok_or_else(|| ServerError(OpenConvError::Validation("missing file field".into())))?;
     let file_name = file_name
         .ok_or_else(|| ServerError(OpenConvError::Validation("missing file_name field".into())))?;
@@ -154,72 +273,148 @@ async fn parse_upload_multipart(
     })?;
 
     Ok(ParsedUpload {
-        file_bytes,
+        size_bytes: size_bytes as i64,
+        digest,
         file_name,
         mime_type,
         encrypted_blob_key,
     })
 }
 
-/// Store file bytes in object store, insert DB record, and return the response.
-/// If DB insert fails, deletes the blob from the store (best-effort cleanup).
+/// Move a freshly written object at `staged` into content-addressed storage
+/// under its digest, or drop it when identical ciphertext is already stored.
+///
+/// Must run in the transaction that inserts the referencing file row: the
+/// upsert keeps the blob row locked until then, so the unreferenced-blob
+/// sweep cannot delete the shared object in between.
+pub(crate) async fn adopt_blob(
+    state: &AppState,
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    staged: &StorePath,
+    digest: &str,
+    size_bytes: i64,
+) -> Result<(), ServerError> {
+    let created: bool = sqlx::query_scalar(
+        "INSERT INTO blobs (digest, size_bytes) VALUES ($1, $2) \
+         ON CONFLICT (digest) DO UPDATE SET digest = EXCLUDED.digest \
+         RETURNING (xmax = 0)",
+    )
+    .bind(digest)
+    .bind(size_bytes)
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(db_err)?;
+
+    if created {
+        state
+            .object_store
+            .rename(staged, &blob_path(digest))
+            .await
+            .map_err(store_err)?;
+    } else {
+        delete_blob_later(state, staged);
+    }
+    Ok(())
+}
+
+/// Stream the upload into the object store, insert the DB record, and return
+/// the response. The upload is written to `storage_path` first and then
+/// deduplicated into a shared blob. If anything fails, the staged object is
+/// deleted (best-effort).
 async fn store_and_insert(
     state: &AppState,
     uploader_id: UserId,
+    guild_id: Option<GuildId>,
     storage_path: &str,
-    parsed: ParsedUpload,
+    multipart: &mut Multipart,
 ) -> Result<(StatusCode, Json<FileResponse>), ServerError> {
-    let size_bytes = parsed.file_bytes.len() as i64;
+    let config = &state.config.file_storage;
+    // Refuse before streaming anything if the quota is already used up
+    quotas::precheck(&state.db, config, uploader_id, guild_id, 1).await?;
 
     let store_path = StorePath::from(storage_path);
-    state
-        .object_store
-        .put(&store_path, PutPayload::from(parsed.file_bytes))
+    let parsed = parse_upload_multipart(state, multipart, &store_path).await?;
+
+    let result = async {
+        let mut tx = state.db.begin().await.map_err(db_err)?;
+        quotas::reserve(&mut tx, config, uploader_id, guild_id, parsed.size_bytes).await?;
+        adopt_blob(state, &mut tx, &store_path, &parsed.digest, parsed.size_bytes).await?;
+        let row = sqlx::query_as::<_, FileRow>(
+            "INSERT INTO files (uploader_id, guild_id, file_name, mime_type, size_bytes, storage_path, encrypted_blob_key, blob_digest, scan_status) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) \
+             RETURNING id, file_name, mime_type, size_bytes, created_at",
+        )
+        .bind(uploader_id)
+        .bind(guild_id)
+        .bind(&parsed.file_name)
+        .bind(&parsed.mime_type)
+        .bind(parsed.size_bytes)
+        .bind(storage_path)
+        .bind(&parsed.encrypted_blob_key)
+        .bind(&parsed.digest)
+        .bind(initial_scan_status(state))
+        .fetch_one(&mut *tx)
         .await
-        .map_err(|e| {
-            tracing::error!(error = %e, "object store put failed");
-            ServerError(OpenConvError::Internal("file storage error".into()))
-        })?;
-
-    let row = sqlx::query_as::<_, FileRow>(
-        "INSERT INTO files (uploader_id, file_name, mime_type, size_bytes, storage_path, encrypted_blob_key) \
-         VALUES ($1, $2, $3, $4, $5, $6) \
-         RETURNING id, file_name, mime_type, size_bytes, created_at",
-    )
-    .bind(uploader_id)
-    .bind(&parsed.file_name)
-    .bind(&parsed.mime_type)
-    .bind(size_bytes)
-    .bind(storage_path)
-    .bind(&parsed.encrypted_blob_key)
-    .fetch_one(&state.db)
-    .await
-    .map_err(|e| {
-        // Best-effort cleanup: delete the blob we just wrote
-        let store = state.object_store.clone();
-        let path = store_path.clone();
-        tokio::spawn(async move {
-            if let Err(del_err) = store.delete(&path).await {
-                tracing::error!(error = %del_err, "failed to clean up orphan blob after DB insert failure");
-            }
-        });
-        db_err(e)
-    })?;
+        .map_err(db_err)?;
+        tx.commit().await.map_err(db_err)?;
+        Ok::<_, ServerError>(row)
+    }
+    .await;
+
+    match result {
+        Ok(row) => {
+            scan_later(state, row.id);
+            Ok((StatusCode::CREATED, Json(row.into_response())))
+        }
+        Err(e) => {
+            delete_blob_later(state, &store_path);
+            Err(e)
+        }
+    }
+}
+
+/// `scan_status` for a new upload: held back for the scanner if one is
+/// configured, otherwise available straight away.
+pub(crate) fn initial_scan_status(state: &AppState) -> &'static str {
+    if state.scanner.enabled() {
+        "pending"
+    } else {
+        "clean"
+    }
+}
 
-    Ok((
-        StatusCode::CREATED,
-        Json(FileResponse {
-            id: row.id,
-            file_name: row.file_name,
-            mime_type: row.mime_type,
-            size_bytes: row.size_bytes,
-            created_at: row.created_at,
-        }),
-    ))
+/// Scan a new upload in the background. If this fails, the periodic scan
+/// task picks the file up later.
+pub(crate) fn scan_later(state: &AppState, file_id: FileId) {
+    if !state.scanner.enabled() {
+        return;
+    }
+    let db = state.db.clone();
+    let store = state.object_store.clone();
+    let scanner = state.scanner.clone();
+    tokio::spawn(async move {
+        if let Err(e) = upload_scan::scan_file(&db, &*store, &*scanner, file_id.0).await {
+            tracing::error!(error = %e, %file_id, "failed to scan upload");
+        }
+    });
+}
+
+/// Refuse to serve a file's bytes until the scanner has cleared it.
+fn require_scanned(file: &FullFileRow) -> Result<(), ServerError> {
+    match file.scan_status.as_str() {
+        "clean" => Ok(()),
+        "pending" => Err(ServerError(OpenConvError::Conflict(
+            "file is still being scanned".into(),
+        ))),
+        _ => Err(ServerError(OpenConvError::Forbidden)),
+    }
 }
 
 /// Pre-check Content-Length header to reject obviously oversized uploads early.
-fn check_content_length(headers: &axum::http::HeaderMap, max_size: u64) -> Result<(), ServerError> {
+pub(crate) fn check_content_length(
+    headers: &axum::http::HeaderMap,
+    max_size: u64,
+) -> Result<(), ServerError> {
     if let Some(content_length) = headers.get(header::CONTENT_LENGTH) {
         if let Ok(len_str) = content_length.to_str() {
             if let Ok(len) = len_str.parse::<u64>() {
@@ -233,7 +428,7 @@ fn check_content_length(headers: &axum::http::HeaderMap, max_size: u64) -> Resul
 }
 
 /// Return a 413 Payload Too Large response.
-fn payload_too_large(max_size: u64) -> ServerError {
+pub(crate) fn payload_too_large(max_size: u64) -> ServerError {
     ServerError(OpenConvError::PayloadTooLarge(format!(
         "file exceeds maximum size of {} bytes",
         max_size
@@ -244,7 +439,8 @@ fn payload_too_large(max_size: u64) -> ServerError {
 
 #[utoipa::path(post, path = "/api/channels/{channel_id}/files", tag = "Files", security(("bearer_auth" = [])), params(("channel_id" = openconv_shared::ids::ChannelId, Path, description = "Channel ID")), request_body(content = crate::handlers::files::FileUploadBody, content_type = "multipart/form-data"), responses((status = 201, body = openconv_shared::api::file::FileResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse), (status = 413, body = crate::error::ErrorResponse)))]
 /// POST /api/channels/:channel_id/files
-/// Upload an encrypted file to a guild channel.
+/// Upload an encrypted file to a guild channel. Counts against both the
+/// uploader's and the guild's storage quota.
 pub async fn upload(
     State(state): State<AppState>,
     channel_member: ChannelMember,
@@ -253,12 +449,8 @@ pub async fn upload(
 ) -> Result<(StatusCode, Json<FileResponse>), ServerError> {
     channel_member.require(Permissions::ATTACH_FILES)?;
 
-    let max_size = state.config.file_storage.max_file_size_bytes;
-
     // Pre-check Content-Length header to reject obviously oversized uploads early
-    check_content_length(&headers, max_size)?;
-
-    let parsed = parse_upload_multipart(&mut multipart, max_size).await?;
+    check_content_length(&headers, state.config.file_storage.max_file_size_bytes)?;
 
     let storage_uuid = uuid::Uuid::now_v7();
     let storage_path = format!(
@@ -266,7 +458,14 @@ pub async fn upload(
         channel_member.guild_id, channel_member.channel_id, storage_uuid
     );
 
-    store_and_insert(&state, channel_member.user_id, &storage_path, parsed).await
+    store_and_insert(
+        &state,
+        channel_member.user_id,
+        Some(channel_member.guild_id),
+        &storage_path,
+        &mut multipart,
+    )
+    .await
 }
 
 #[utoipa::path(post, path = "/api/dm-channels/{dm_channel_id}/files", tag = "Files", security(("bearer_auth" = [])), params(("dm_channel_id" = openconv_shared::ids::DmChannelId, Path, description = "DM channel ID")), request_body(content = crate::handlers::files::FileUploadBody, content_type = "multipart/form-data"), responses((status = 201, body = openconv_shared::api::file::FileResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse), (status = 413, body = crate::error::ErrorResponse)))]
@@ -293,22 +492,184 @@ pub async fn upload_dm(
         return Err(ServerError(OpenConvError::Forbidden));
     }
 
-    let max_size = state.config.file_storage.max_file_size_bytes;
+    // Pre-check Content-Length header to reject obviously oversized uploads early
+    check_content_length(&headers, state.config.file_storage.max_file_size_bytes)?;
 
-    // Pre-check Content-Length
-    check_content_length(&headers, max_size)?;
+    let storage_uuid = uuid::Uuid::now_v7();
+    let storage_path = format!("dm/{}/{}", dm_channel_id, storage_uuid);
 
-    let parsed = parse_upload_multipart(&mut multipart, max_size).await?;
+    store_and_insert(&state, auth.user_id, None, &storage_path, &mut multipart).await
+}
+
+#[utoipa::path(post, path = "/api/files", tag = "Files", security(("bearer_auth" = [])), request_body(content = crate::handlers::files::FileUploadBody, content_type = "multipart/form-data"), responses((status = 201, body = openconv_shared::api::file::FileResponse), (status = 400, body = crate::error::ErrorResponse), (status = 413, body = crate::error::ErrorResponse)))]
+/// POST /api/files
+/// Upload an encrypted file that is not yet tied to a channel.
+///
+/// Only the uploader can read it until it is attached to a message; then
+/// everyone who can see the message can download it.
+pub async fn upload_standalone(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    headers: axum::http::HeaderMap,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, Json<FileResponse>), ServerError> {
+    check_content_length(&headers, state.config.file_storage.max_file_size_bytes)?;
 
     let storage_uuid = uuid::Uuid::now_v7();
-    let storage_path = format!("dm/{}/{}", dm_channel_id, storage_uuid);
+    let storage_path = format!("users/{}/{}", auth.user_id, storage_uuid);
+
+    store_and_insert(&state, auth.user_id, None, &storage_path, &mut multipart).await
+}
 
-    store_and_insert(&state, auth.user_id, &storage_path, parsed).await
+// ─── Attachments ────────────────────────────────────────────
+
+async fn load_attachments<'e, E>(
+    executor: E,
+    message_id: MessageId,
+) -> Result<Vec<FileResponse>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let rows = sqlx::query_as::<_, FileRow>(
+        "SELECT f.id, f.file_name, f.mime_type, f.size_bytes, f.created_at \
+         FROM attachments a JOIN files f ON f.id = a.file_id \
+         WHERE a.message_id = $1 ORDER BY a.position",
+    )
+    .bind(message_id)
+    .fetch_all(executor)
+    .await?;
+    Ok(rows.into_iter().map(FileRow::into_response).collect())
+}
+
+/// Sender of a non-deleted message in the given guild channel.
+async fn message_sender(
+    db: &sqlx::PgPool,
+    channel_id: ChannelId,
+    message_id: MessageId,
+) -> Result<UserId, ServerError> {
+    sqlx::query_scalar(
+        "SELECT sender_id FROM messages WHERE id = $1 AND channel_id = $2 AND deleted = false",
+    )
+    .bind(message_id)
+    .bind(channel_id)
+    .fetch_optional(db)
+    .await
+    .map_err(db_err)?
+    .ok_or(ServerError(OpenConvError::NotFound))
+}
+
+#[utoipa::path(get, path = "/api/channels/{channel_id}/messages/{message_id}/attachments", tag = "Files", security(("bearer_auth" = [])), params(("channel_id" = openconv_shared::ids::ChannelId, Path, description = "Channel ID"), ("message_id" = openconv_shared::ids::MessageId, Path, description = "Message ID")), responses((status = 200, body = openconv_shared::api::file::MessageAttachmentsResponse), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// GET /api/channels/:channel_id/messages/:message_id/attachments
+/// List the files attached to a message.
+pub async fn list_attachments(
+    State(state): State<AppState>,
+    channel_member: ChannelMember,
+    Path((_, message_id)): Path<(ChannelId, MessageId)>,
+) -> Result<Json<MessageAttachmentsResponse>, ServerError> {
+    channel_member.require(Permissions::READ_MESSAGES)?;
+    message_sender(&state.db, channel_member.channel_id, message_id).await?;
+
+    let files = load_attachments(&state.db, message_id)
+        .await
+        .map_err(db_err)?;
+    Ok(Json(MessageAttachmentsResponse { message_id, files }))
+}
+
+#[utoipa::path(put, path = "/api/channels/{channel_id}/messages/{message_id}/attachments", tag = "Files", security(("bearer_auth" = [])), params(("channel_id" = openconv_shared::ids::ChannelId, Path, description = "Channel ID"), ("message_id" = openconv_shared::ids::MessageId, Path, description = "Message ID")), request_body = openconv_shared::api::file::SetAttachmentsRequest, responses((status = 200, body = openconv_shared::api::file::MessageAttachmentsResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// PUT /api/channels/:channel_id/messages/:message_id/attachments
+/// Set the files attached to the caller's own message.
+///
+/// Every file must have been uploaded by the caller. The list replaces any
+/// previous attachments, and its order is the display order. The guild's
+/// attachment rate automod rules apply.
+pub async fn set_attachments(
+    State(state): State<AppState>,
+    channel_member: ChannelMember,
+    Path((_, message_id)): Path<(ChannelId, MessageId)>,
+    Json(body): Json<SetAttachmentsRequest>,
+) -> Result<Json<MessageAttachmentsResponse>, ServerError> {
+    channel_member.require(Permissions::ATTACH_FILES)?;
+
+    let max = state.config.file_storage.max_attachments_per_message;
+    if body.file_ids.len() > max {
+        return Err(ServerError(OpenConvError::Validation(format!(
+            "a message can have at most {max} attachments"
+        ))));
+    }
+    let mut seen = std::collections::HashSet::new();
+    if !body.file_ids.iter().all(|id| seen.insert(*id)) {
+        return Err(ServerError(OpenConvError::Validation(
+            "file_ids must not contain duplicates".into(),
+        )));
+    }
+
+    let sender = message_sender(&state.db, channel_member.channel_id, message_id).await?;
+    if sender != channel_member.user_id {
+        return Err(ServerError(OpenConvError::Forbidden));
+    }
+
+    let file_uuids: Vec<uuid::Uuid> = body.file_ids.iter().map(|id| id.0).collect();
+    let owned: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM files WHERE id = ANY($1) AND uploader_id = $2")
+            .bind(&file_uuids)
+            .bind(channel_member.user_id)
+            .fetch_one(&state.db)
+            .await
+            .map_err(db_err)?;
+    if owned != file_uuids.len() as i64 {
+        return Err(ServerError(OpenConvError::Validation(
+            "file_ids must reference files you uploaded".into(),
+        )));
+    }
+
+    if crate::automod::applies_to(channel_member.permissions) {
+        let rules = crate::automod::active_rules(&state.db, channel_member.guild_id)
+            .await
+            .map_err(db_err)?;
+        let blocked = crate::automod::check_attachments(
+            &state.db,
+            &rules,
+            channel_member.user_id,
+            channel_member.guild_id,
+            message_id,
+            file_uuids.len(),
+        )
+        .await
+        .map_err(db_err)?;
+        if let Some(rule) = blocked {
+            return Err(ServerError(OpenConvError::Validation(
+                crate::automod::blocked_message(rule),
+            )));
+        }
+    }
+
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+    sqlx::query("DELETE FROM attachments WHERE message_id = $1")
+        .bind(message_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?;
+    sqlx::query(
+        "INSERT INTO attachments (message_id, file_id, position) \
+         SELECT $1, file_id, (ord - 1)::SMALLINT \
+         FROM UNNEST($2::UUID[]) WITH ORDINALITY AS t(file_id, ord)",
+    )
+    .bind(message_id)
+    .bind(&file_uuids)
+    .execute(&mut *tx)
+    .await
+    .map_err(db_err)?;
+    let files = load_attachments(&mut *tx, message_id)
+        .await
+        .map_err(db_err)?;
+    tx.commit().await.map_err(db_err)?;
+
+    Ok(Json(MessageAttachmentsResponse { message_id, files }))
 }
 
 // ─── Download ───────────────────────────────────────────────
 
-#[utoipa::path(get, path = "/api/files/{file_id}", tag = "Files", security(("bearer_auth" = [])), params(("file_id" = openconv_shared::ids::FileId, Path, description = "File ID")), responses((status = 200, description = "File bytes", content_type = "application/octet-stream"), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+#[utoipa::path(get, path = "/api/files/{file_id}", tag = "Files", security(("bearer_auth" = [])), params(("file_id" = openconv_shared::ids::FileId, Path, description = "File ID")), responses((status = 200, description = "File bytes", content_type = "application/octet-stream"), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse), (status = 409, body = crate::error::ErrorResponse)))]
 /// GET /api/files/:file_id
 /// Download an encrypted file.
 pub async fn download(
@@ -316,29 +677,38 @@ pub async fn download(
     auth: AuthUser,
     Path(file_id): Path<FileId>,
 ) -> Result<Response, ServerError> {
-    let file = sqlx::query_as::<_, FullFileRow>(
-        "SELECT id, uploader_id, file_name, mime_type, size_bytes, storage_path, created_at \
-         FROM files WHERE id = $1",
+    let file = load_file(&state.db, file_id).await?;
+    verify_file_access(&state.db, auth.user_id, &file).await?;
+    file_response(&state, &file).await
+}
+
+async fn load_file(db: &sqlx::PgPool, file_id: FileId) -> Result<FullFileRow, ServerError> {
+    sqlx::query_as::<_, FullFileRow>(
+        "SELECT f.id, f.uploader_id, f.file_name, f.mime_type, f.size_bytes, f.storage_path, \
+                f.blob_digest, f.scan_status, f.created_at, f.created_at + make_interval(days => g.file_retention_days) AS expires_at \
+         FROM files f \
+         LEFT JOIN guilds g ON g.id = f.guild_id \
+         WHERE f.id = $1",
     )
     .bind(file_id)
-    .fetch_optional(&state.db)
+    .fetch_optional(db)
     .await
     .map_err(db_err)?
-    .ok_or(ServerError(OpenConvError::NotFound))?;
-
-    verify_file_access(&state.db, auth.user_id, &file.storage_path).await?;
+    // Expired files are gone as far as clients are concerned, even before
+    // the cleanup task removes them
+    .filter(|file| file.expires_at.is_none_or(|at| at > chrono::Utc::now()))
+    .ok_or(ServerError(OpenConvError::NotFound))
+}
 
-    let store_path = StorePath::from(file.storage_path.as_str());
+/// Stream the file's blob from the object store.
+async fn file_response(state: &AppState, file: &FullFileRow) -> Result<Response, ServerError> {
+    require_scanned(file)?;
+    let store_path = file_object_path(&file.storage_path, file.blob_digest.as_deref());
     let result = state.object_store.get(&store_path).await.map_err(|e| {
         tracing::error!(error = %e, "object store get failed");
         ServerError(OpenConvError::Internal("file storage error".into()))
     })?;
 
-    let bytes = result.bytes().await.map_err(|e| {
-        tracing::error!(error = %e, "object store read failed");
-        ServerError(OpenConvError::Internal("file storage error".into()))
-    })?;
-
     // Always serve as octet-stream to prevent browser execution of content
     let content_disposition = format!(
         "attachment; filename=\"{}\"",
@@ -349,12 +719,102 @@ pub async fn download(
         .header(header::CONTENT_TYPE, "application/octet-stream")
         .header(header::CONTENT_DISPOSITION, content_disposition)
         .header(header::CONTENT_LENGTH, file.size_bytes.to_string())
-        .body(Body::from(bytes))
+        .body(Body::from_stream(result.into_stream()))
         .map_err(|_| ServerError(OpenConvError::Internal("response build error".into())))?;
 
     Ok(response)
 }
 
+// ─── Signed download URLs ───────────────────────────────────
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Key for signing download URLs: the configured secret, or one derived
+/// from the JWT private key so deployments work without extra setup.
+fn download_url_key(config: &ServerConfig) -> Vec<u8> {
+    let secret = &config.file_storage.download_url_secret;
+    if !secret.is_empty() {
+        return secret.as_bytes().to_vec();
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(b"openconv-file-download-url:");
+    hasher.update(config.jwt.private_key_pem.as_bytes());
+    hasher.finalize().to_vec()
+}
+
+fn download_mac(key: &[u8], file_id: FileId, expires: i64) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(format!("{file_id}:{expires}").as_bytes());
+    mac
+}
+
+fn sign_download(key: &[u8], file_id: FileId, expires: i64) -> String {
+    hex::encode(download_mac(key, file_id, expires).finalize().into_bytes())
+}
+
+/// Check a download signature in constant time. Expired URLs never verify.
+fn verify_download(key: &[u8], file_id: FileId, expires: i64, sig: &str, now: i64) -> bool {
+    if now > expires {
+        return false;
+    }
+    let Ok(sig) = hex::decode(sig) else {
+        return false;
+    };
+    download_mac(key, file_id, expires)
+        .verify_slice(&sig)
+        .is_ok()
+}
+
+#[utoipa::path(get, path = "/api/files/{file_id}/download-url", tag = "Files", security(("bearer_auth" = [])), params(("file_id" = openconv_shared::ids::FileId, Path, description = "File ID")), responses((status = 200, body = openconv_shared::api::file::DownloadUrlResponse), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse), (status = 409, body = crate::error::ErrorResponse)))]
+/// GET /api/files/:file_id/download-url
+/// Issue a short-lived URL that downloads the file without a bearer token.
+/// Access is checked now, when the URL is issued.
+pub async fn download_url(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(file_id): Path<FileId>,
+) -> Result<Json<DownloadUrlResponse>, ServerError> {
+    let file = load_file(&state.db, file_id).await?;
+    verify_file_access(&state.db, auth.user_id, &file).await?;
+    require_scanned(&file)?;
+
+    let ttl = state.config.file_storage.download_url_ttl_seconds as i64;
+    let expires_at = chrono::Utc::now() + chrono::Duration::seconds(ttl);
+    let expires = expires_at.timestamp();
+    let sig = sign_download(&download_url_key(&state.config), file_id, expires);
+
+    Ok(Json(DownloadUrlResponse {
+        url: format!("/api/files/{file_id}/signed?expires={expires}&sig={sig}"),
+        expires_at,
+    }))
+}
+
+#[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
+pub struct SignedDownloadQuery {
+    /// Unix timestamp after which the URL is rejected.
+    expires: i64,
+    /// Hex HMAC-SHA256 signature.
+    sig: String,
+}
+
+#[utoipa::path(get, path = "/api/files/{file_id}/signed", tag = "Files", params(("file_id" = openconv_shared::ids::FileId, Path, description = "File ID"), SignedDownloadQuery), responses((status = 200, description = "File bytes", content_type = "application/octet-stream"), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse), (status = 409, body = crate::error::ErrorResponse)))]
+/// GET /api/files/:file_id/signed
+/// Download a file through a URL from `download_url`.
+pub async fn signed_download(
+    State(state): State<AppState>,
+    Path(file_id): Path<FileId>,
+    Query(query): Query<SignedDownloadQuery>,
+) -> Result<Response, ServerError> {
+    let key = download_url_key(&state.config);
+    let now = chrono::Utc::now().timestamp();
+    if !verify_download(&key, file_id, query.expires, &query.sig, now) {
+        return Err(ServerError(OpenConvError::Forbidden));
+    }
+
+    let file = load_file(&state.db, file_id).await?;
+    file_response(&state, &file).await
+}
+
 // ─── Metadata ───────────────────────────────────────────────
 
 #[utoipa::path(get, path = "/api/files/{file_id}/meta", tag = "Files", security(("bearer_auth" = [])), params(("file_id" = openconv_shared::ids::FileId, Path, description = "File ID")), responses((status = 200, body = openconv_shared::api::file::FileMetaResponse), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
@@ -365,17 +825,8 @@ pub async fn meta(
     auth: AuthUser,
     Path(file_id): Path<FileId>,
 ) -> Result<Json<FileMetaResponse>, ServerError> {
-    let file = sqlx::query_as::<_, FullFileRow>(
-        "SELECT id, uploader_id, file_name, mime_type, size_bytes, storage_path, created_at \
-         FROM files WHERE id = $1",
-    )
-    .bind(file_id)
-    .fetch_optional(&state.db)
-    .await
-    .map_err(db_err)?
-    .ok_or(ServerError(OpenConvError::NotFound))?;
-
-    verify_file_access(&state.db, auth.user_id, &file.storage_path).await?;
+    let file = load_file(&state.db, file_id).await?;
+    verify_file_access(&state.db, auth.user_id, &file).await?;
 
     Ok(Json(FileMetaResponse {
         id: file.id,
@@ -384,6 +835,8 @@ pub async fn meta(
         size_bytes: file.size_bytes,
         uploader_id: file.uploader_id,
         created_at: file.created_at,
+        expires_at: file.expires_at,
+        scan_status: file.scan_status,
     }))
 }
 
@@ -394,9 +847,38 @@ pub async fn meta(
 async fn verify_file_access(
     db: &sqlx::PgPool,
     user_id: UserId,
-    storage_path: &str,
+    file: &FullFileRow,
 ) -> Result<(), ServerError> {
-    if let Some(rest) = storage_path.strip_prefix("guilds/") {
+    let storage_path = file.storage_path.as_str();
+    if storage_path.starts_with("users/") {
+        // Format: users/{uploader_id}/{uuid}. Readable by the uploader and by
+        // anyone who can see a message the file is attached to.
+        if file.uploader_id == user_id {
+            return Ok(());
+        }
+        let visible: bool = sqlx::query_scalar(
+            "SELECT EXISTS( \
+                 SELECT 1 FROM attachments a \
+                 JOIN messages m ON m.id = a.message_id AND m.deleted = false \
+                 LEFT JOIN channels c ON c.id = m.channel_id \
+                 WHERE a.file_id = $1 AND ( \
+                     EXISTS(SELECT 1 FROM guild_members gm \
+                            WHERE gm.guild_id = c.guild_id AND gm.user_id = $2) \
+                     OR EXISTS(SELECT 1 FROM dm_channel_members dm \
+                               WHERE dm.dm_channel_id = m.dm_channel_id AND dm.user_id = $2) \
+                 ) \
+             )",
+        )
+        .bind(file.id)
+        .bind(user_id)
+        .fetch_one(db)
+        .await
+        .map_err(db_err)?;
+
+        if !visible {
+            return Err(ServerError(OpenConvError::Forbidden));
+        }
+    } else if let Some(rest) = storage_path.strip_prefix("guilds/") {
         // Format: guilds/{guild_id}/{channel_id}/{uuid}
         let guild_id_str = rest
             .split('/')
@@ -467,25 +949,54 @@ pub fn dm_file_routes() -> axum::Router<AppState> {
     axum::Router::new().route("/", axum::routing::post(upload_dm))
 }
 
+/// Routes for standalone file upload.
+/// Mounted at /api/files
+pub fn upload_routes() -> axum::Router<AppState> {
+    axum::Router::new().route("/", axum::routing::post(upload_standalone))
+}
+
 /// Routes for file download and metadata.
 /// Mounted at /api/files
 pub fn file_routes() -> axum::Router<AppState> {
     axum::Router::new()
         .route("/{file_id}", axum::routing::get(download))
         .route("/{file_id}/meta", axum::routing::get(meta))
+        .route("/{file_id}/download-url", axum::routing::get(download_url))
+        .route("/{file_id}/signed", axum::routing::get(signed_download))
+}
+
+/// Routes for message attachments.
+/// Mounted at /api/channels/:channel_id/messages/:message_id/attachments
+pub fn attachment_routes() -> axum::Router<AppState> {
+    axum::Router::new().route(
+        "/",
+        axum::routing::get(list_attachments).put(set_attachments),
+    )
 }
 
 // ─── Internal row types ─────────────────────────────────────
 
 #[derive(sqlx::FromRow)]
-struct FileRow {
-    id: FileId,
+pub(crate) struct FileRow {
+    pub(crate) id: FileId,
     file_name: String,
     mime_type: String,
     size_bytes: i64,
     created_at: chrono::DateTime<chrono::Utc>,
 }
 
+impl FileRow {
+    pub(crate) fn into_response(self) -> FileResponse {
+        FileResponse {
+            id: self.id,
+            file_name: self.file_name,
+            mime_type: self.mime_type,
+            size_bytes: self.size_bytes,
+            created_at: self.created_at,
+        }
+    }
+}
+
 #[derive(sqlx::FromRow)]
 struct FullFileRow {
     id: FileId,
@@ -494,7 +1005,10 @@ struct FullFileRow {
     mime_type: String,
     size_bytes: i64,
     storage_path: String,
+    blob_digest: Option<String>,
+    scan_status: String,
     created_at: chrono::DateTime<chrono::Utc>,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 #[cfg(test)]
@@ -514,6 +1028,8 @@ mod tests {
     #[test]
     fn file_routes_build_without_panic() {
         let _ = file_routes();
+        let _ = upload_routes();
+        let _ = attachment_routes();
     }
 
     #[test]
@@ -544,6 +1060,38 @@ mod tests {
         assert_eq!(sanitize_file_name("normal file.pdf"), "normal file.pdf");
     }
 
+    #[test]
+    fn download_signature_round_trips() {
+        let key = b"test-key";
+        let file_id = FileId::new();
+        let sig = sign_download(key, file_id, 1_000);
+        assert!(verify_download(key, file_id, 1_000, &sig, 999));
+        assert!(verify_download(key, file_id, 1_000, &sig, 1_000));
+    }
+
+    #[test]
+    fn download_signature_rejects_tampering_and_expiry() {
+        let key = b"test-key";
+        let file_id = FileId::new();
+        let sig = sign_download(key, file_id, 1_000);
+        assert!(!verify_download(key, file_id, 1_000, &sig, 1_001));
+        assert!(!verify_download(key, file_id, 2_000, &sig, 999));
+        assert!(!verify_download(key, FileId::new(), 1_000, &sig, 999));
+        assert!(!verify_download(b"other-key", file_id, 1_000, &sig, 999));
+        assert!(!verify_download(key, file_id, 1_000, "not-hex", 999));
+    }
+
+    #[test]
+    fn download_url_key_prefers_configured_secret() {
+        let mut config = ServerConfig::default();
+        config.jwt.private_key_pem = "pem".into();
+        let derived = download_url_key(&config);
+        assert_eq!(derived.len(), 32);
+
+        config.file_storage.download_url_secret = "secret".into();
+        assert_eq!(download_url_key(&config), b"secret".to_vec());
+    }
+
     #[test]
     fn validate_mime_type_accepts_valid() {
         assert!(validate_mime_type("application/octet-stream").is_ok());
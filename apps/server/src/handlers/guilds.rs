@@ -1,173 +1,117 @@
 use axum::extract::{Path, State};
-use axum::http::StatusCode;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::Response;
 use axum::Json;
+use openconv_shared::api::gateway::{MemberEvent, MemberEventKind};
 use openconv_shared::api::guild::{
-    CreateGuildRequest, GuildListResponse, GuildMemberResponse, GuildResponse, RoleSummary,
-    UpdateGuildRequest,
+    BulkMembersRequest, CreateGuildRequest, GuildListResponse, GuildMemberResponse, GuildResponse,
+    UpdateGuildRequest, MAX_BULK_MEMBER_IDS, MAX_FILE_RETENTION_DAYS, MAX_MESSAGE_RETENTION_DAYS,
 };
-use openconv_shared::error::OpenConvError;
+use openconv_shared::error::{ErrorMessage, OpenConvError};
 use openconv_shared::ids::{ChannelId, GuildId, RoleId, UserId};
 use openconv_shared::permissions::Permissions;
 
 use crate::error::ServerError;
+use crate::etag::json_with_etag;
 use crate::extractors::auth::AuthUser;
 use crate::extractors::guild_member::GuildMember;
+use crate::member_events;
+use crate::repo::guilds::{self as repo, DefaultRoles, GuildChanges, Member};
 use crate::state::AppState;
+use crate::streaming::{stream_response, StreamFormat};
 
 fn db_err(e: sqlx::Error) -> ServerError {
     tracing::error!(error = %e, "database error");
     ServerError(OpenConvError::Internal("database error".into()))
 }
 
-async fn fetch_guild_owner(db: &sqlx::PgPool, guild_id: GuildId) -> Result<UserId, ServerError> {
-    sqlx::query_scalar::<_, UserId>("SELECT owner_id FROM guilds WHERE id = $1")
-        .bind(guild_id)
-        .fetch_optional(db)
+/// Hierarchy check: the actor's highest role position must be strictly above
+/// the target's. Callers handle the guild-owner bypass themselves.
+pub(crate) async fn ensure_outranks(
+    db: &sqlx::PgPool,
+    guild_id: GuildId,
+    actor_id: UserId,
+    target_id: UserId,
+) -> Result<(), ServerError> {
+    let actor_pos = repo::highest_role_position(db, guild_id, actor_id)
+        .await
+        .map_err(db_err)?;
+    let target_pos = repo::highest_role_position(db, guild_id, target_id)
+        .await
+        .map_err(db_err)?;
+
+    if actor_pos <= target_pos {
+        return Err(ServerError(OpenConvError::Forbidden));
+    }
+    Ok(())
+}
+
+pub(crate) async fn fetch_guild_owner(
+    db: &sqlx::PgPool,
+    guild_id: GuildId,
+) -> Result<UserId, ServerError> {
+    repo::owner(db, guild_id)
         .await
         .map_err(db_err)?
         .ok_or(ServerError(OpenConvError::NotFound))
 }
 
-#[utoipa::path(post, path = "/api/guilds", tag = "Guilds", security(("bearer_auth" = [])), request_body = openconv_shared::api::guild::CreateGuildRequest, responses((status = 201, body = openconv_shared::api::guild::GuildResponse), (status = 400, body = crate::error::ErrorResponse)))]
+#[utoipa::path(post, path = "/api/guilds", tag = "Guilds", security(("bearer_auth" = [])), request_body = openconv_shared::api::guild::CreateGuildRequest, responses((status = 201, body = openconv_shared::api::guild::GuildResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse)))]
 /// Create a new guild. Auth only -- no guild membership required.
 pub async fn create_guild(
     auth: AuthUser,
     State(state): State<AppState>,
     Json(body): Json<CreateGuildRequest>,
 ) -> Result<(StatusCode, Json<GuildResponse>), ServerError> {
+    auth.require_human()?;
+    if !state.live.settings().features.guild_creation {
+        return Err(ServerError(OpenConvError::Forbidden));
+    }
     let name = body.name.trim().to_string();
     if name.is_empty() || name.len() > 100 {
-        return Err(ServerError(OpenConvError::Validation(
-            "Guild name must be between 1 and 100 characters".into(),
-        )));
+        return Err(ServerError(
+            OpenConvError::Validation("Guild name must be between 1 and 100 characters".into())
+                .with_message(
+                    ErrorMessage::new("guild_name.length")
+                        .param("min", 1)
+                        .param("max", 100),
+                ),
+        ));
     }
 
-    let guild_id = GuildId::new();
-    let owner_role_id = RoleId::new();
-    let admin_role_id = RoleId::new();
-    let member_role_id = RoleId::new();
-    let channel_id = ChannelId::new();
-
-    let owner_perms = Permissions::all().bits() as i64;
-    let admin_perms = (Permissions::MANAGE_GUILD
-        | Permissions::MANAGE_CHANNELS
-        | Permissions::MANAGE_ROLES
-        | Permissions::MANAGE_INVITES
-        | Permissions::KICK_MEMBERS
-        | Permissions::SEND_MESSAGES
-        | Permissions::READ_MESSAGES
-        | Permissions::ATTACH_FILES
-        | Permissions::MENTION_EVERYONE
-        | Permissions::MANAGE_MESSAGES)
-        .bits() as i64;
-    let member_perms = (Permissions::SEND_MESSAGES
-        | Permissions::READ_MESSAGES
-        | Permissions::ATTACH_FILES)
-        .bits() as i64;
+    let roles = DefaultRoles::new(RoleId::new(), RoleId::new(), RoleId::new());
 
     let mut tx = state.db.begin().await.map_err(db_err)?;
-
-    // 1. Insert guild
-    let row = sqlx::query_as::<_, GuildRow>(
-        "INSERT INTO guilds (id, name, owner_id) VALUES ($1, $2, $3) \
-         RETURNING id, name, owner_id, icon_url, created_at",
-    )
-    .bind(guild_id)
-    .bind(&name)
-    .bind(auth.user_id)
-    .fetch_one(&mut *tx)
-    .await
-    .map_err(db_err)?;
-
-    // 2. Insert default roles
-    sqlx::query(
-        "INSERT INTO roles (id, guild_id, name, permissions, position, role_type) VALUES \
-         ($1, $2, 'owner', $3, 100, 'owner'), \
-         ($4, $2, 'admin', $5, 50, 'admin'), \
-         ($6, $2, 'member', $7, 1, 'member')",
+    let guild = repo::create(
+        &mut tx,
+        GuildId::new(),
+        &name,
+        auth.user_id,
+        &roles,
+        ChannelId::new(),
     )
-    .bind(owner_role_id)
-    .bind(guild_id)
-    .bind(owner_perms)
-    .bind(admin_role_id)
-    .bind(admin_perms)
-    .bind(member_role_id)
-    .bind(member_perms)
-    .execute(&mut *tx)
     .await
     .map_err(db_err)?;
-
-    // 3. Insert creator into guild_members
-    sqlx::query("INSERT INTO guild_members (user_id, guild_id) VALUES ($1, $2)")
-        .bind(auth.user_id)
-        .bind(guild_id)
-        .execute(&mut *tx)
-        .await
-        .map_err(db_err)?;
-
-    // 4. Assign owner role to creator
-    sqlx::query("INSERT INTO guild_member_roles (user_id, guild_id, role_id) VALUES ($1, $2, $3)")
-        .bind(auth.user_id)
-        .bind(guild_id)
-        .bind(owner_role_id)
-        .execute(&mut *tx)
-        .await
-        .map_err(db_err)?;
-
-    // 5. Insert #main channel
-    sqlx::query("INSERT INTO channels (id, guild_id, name, position) VALUES ($1, $2, 'main', 0)")
-        .bind(channel_id)
-        .bind(guild_id)
-        .execute(&mut *tx)
-        .await
-        .map_err(db_err)?;
-
-    // 6. Commit
     tx.commit().await.map_err(db_err)?;
 
-    let resp = GuildResponse {
-        id: row.id,
-        name: row.name,
-        owner_id: row.owner_id,
-        icon_url: row.icon_url,
-        created_at: row.created_at,
-        member_count: Some(1),
-    };
-
-    Ok((StatusCode::CREATED, Json(resp)))
+    Ok((StatusCode::CREATED, Json(guild.into_response(Some(1)))))
 }
 
-#[utoipa::path(get, path = "/api/guilds", tag = "Guilds", security(("bearer_auth" = [])), responses((status = 200, body = openconv_shared::api::guild::GuildListResponse)))]
+#[utoipa::path(get, path = "/api/guilds", tag = "Guilds", security(("bearer_auth" = [])), responses((status = 200, body = openconv_shared::api::guild::GuildListResponse), (status = 304, description = "Not modified since the ETag in If-None-Match")))]
 /// List guilds where the authenticated user is a member.
 pub async fn list_guilds(
     auth: AuthUser,
     State(state): State<AppState>,
-) -> Result<Json<GuildListResponse>, ServerError> {
-    let rows = sqlx::query_as::<_, GuildRow>(
-        "SELECT g.id, g.name, g.owner_id, g.icon_url, g.created_at \
-         FROM guilds g \
-         INNER JOIN guild_members gm ON gm.guild_id = g.id \
-         WHERE gm.user_id = $1 AND g.deleted_at IS NULL \
-         ORDER BY g.created_at DESC",
-    )
-    .bind(auth.user_id)
-    .fetch_all(&state.db)
-    .await
-    .map_err(db_err)?;
-
-    let guilds = rows
+    headers: HeaderMap,
+) -> Result<Response, ServerError> {
+    let guilds = repo::list_for_member(&state.db, auth.user_id)
+        .await
+        .map_err(db_err)?
         .into_iter()
-        .map(|r| GuildResponse {
-            id: r.id,
-            name: r.name,
-            owner_id: r.owner_id,
-            icon_url: r.icon_url,
-            created_at: r.created_at,
-            member_count: None,
-        })
+        .map(|g| g.into_response(None))
         .collect();
 
-    Ok(Json(GuildListResponse { guilds }))
+    Ok(json_with_etag(&headers, &GuildListResponse { guilds }))
 }
 
 #[utoipa::path(get, path = "/api/guilds/{guild_id}", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), responses((status = 200, body = openconv_shared::api::guild::GuildResponse), (status = 404, body = crate::error::ErrorResponse)))]
@@ -176,30 +120,17 @@ pub async fn get_guild(
     member: GuildMember,
     State(state): State<AppState>,
 ) -> Result<Json<GuildResponse>, ServerError> {
-    let row = sqlx::query_as::<_, GuildWithCountRow>(
-        "SELECT g.id, g.name, g.owner_id, g.icon_url, g.created_at, \
-         (SELECT COUNT(*) FROM guild_members WHERE guild_id = g.id) AS member_count \
-         FROM guilds g \
-         WHERE g.id = $1 AND g.deleted_at IS NULL",
-    )
-    .bind(member.guild_id)
-    .fetch_optional(&state.db)
-    .await
-    .map_err(db_err)?
-    .ok_or(ServerError(OpenConvError::NotFound))?;
-
-    Ok(Json(GuildResponse {
-        id: row.id,
-        name: row.name,
-        owner_id: row.owner_id,
-        icon_url: row.icon_url,
-        created_at: row.created_at,
-        member_count: Some(row.member_count),
-    }))
+    let (guild, member_count) = repo::get_with_member_count(&state.db, member.guild_id)
+        .await
+        .map_err(db_err)?
+        .ok_or(ServerError(OpenConvError::NotFound))?;
+
+    Ok(Json(guild.into_response(Some(member_count))))
 }
 
 #[utoipa::path(patch, path = "/api/guilds/{guild_id}", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), request_body = openconv_shared::api::guild::UpdateGuildRequest, responses((status = 200, body = openconv_shared::api::guild::GuildResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse)))]
-/// Update guild name/icon. Requires MANAGE_GUILD permission.
+/// Update guild name/icon. Requires MANAGE_GUILD permission; changing
+/// message retention additionally requires being the owner.
 pub async fn update_guild(
     member: GuildMember,
     State(state): State<AppState>,
@@ -207,62 +138,77 @@ pub async fn update_guild(
 ) -> Result<Json<GuildResponse>, ServerError> {
     member.require(Permissions::MANAGE_GUILD)?;
 
-    if body.name.is_none() && body.icon_url.is_none() {
+    let changes = GuildChanges {
+        name: body.name.as_deref().map(str::trim),
+        icon_url: body.icon_url.as_deref(),
+        prune_on_ban: body.prune_on_ban,
+        max_members: body.max_members,
+        file_retention_days: body.file_retention_days,
+        message_retention_days: body.message_retention_days,
+        verification_level: body.verification_level,
+        max_joins_per_minute: body.max_joins_per_minute,
+        invites_paused: body.invites_paused,
+    };
+
+    if changes.is_empty() {
         return Err(ServerError(OpenConvError::Validation(
             "At least one field must be provided".into(),
         )));
     }
 
-    if let Some(ref name) = body.name {
-        let trimmed = name.trim();
-        if trimmed.is_empty() || trimmed.len() > 100 {
-            return Err(ServerError(OpenConvError::Validation(
-                "Guild name must be between 1 and 100 characters".into(),
-            )));
-        }
+    if changes
+        .name
+        .is_some_and(|name| name.is_empty() || name.len() > 100)
+    {
+        return Err(ServerError(
+            OpenConvError::Validation("Guild name must be between 1 and 100 characters".into())
+                .with_message(
+                    ErrorMessage::new("guild_name.length")
+                        .param("min", 1)
+                        .param("max", 100),
+                ),
+        ));
     }
 
-    // Build dynamic update query
-    let mut set_clauses = Vec::new();
-    let mut param_idx = 2u32; // $1 is guild_id
-
-    if body.name.is_some() {
-        set_clauses.push(format!("name = ${param_idx}"));
-        param_idx += 1;
-    }
-    if body.icon_url.is_some() {
-        set_clauses.push(format!("icon_url = ${param_idx}"));
+    if changes.max_members.is_some_and(|m| m < 0) {
+        return Err(ServerError(OpenConvError::Validation(
+            "max_members must not be negative".into(),
+        )));
     }
 
-    let query_str = format!(
-        "UPDATE guilds SET {} WHERE id = $1 AND deleted_at IS NULL \
-         RETURNING id, name, owner_id, icon_url, created_at",
-        set_clauses.join(", ")
-    );
-
-    let mut query = sqlx::query_as::<_, GuildRow>(&query_str).bind(member.guild_id);
+    if changes.max_joins_per_minute.is_some_and(|m| m < 0) {
+        return Err(ServerError(OpenConvError::Validation(
+            "max_joins_per_minute must not be negative".into(),
+        )));
+    }
 
-    if let Some(ref name) = body.name {
-        query = query.bind(name.trim());
+    if changes
+        .file_retention_days
+        .is_some_and(|d| !(0..=MAX_FILE_RETENTION_DAYS).contains(&d))
+    {
+        return Err(ServerError(OpenConvError::Validation(format!(
+            "file_retention_days must be between 0 and {MAX_FILE_RETENTION_DAYS}"
+        ))));
     }
-    if let Some(ref icon_url) = body.icon_url {
-        query = query.bind(icon_url.as_str());
+
+    if let Some(days) = changes.message_retention_days {
+        if !(0..=MAX_MESSAGE_RETENTION_DAYS).contains(&days) {
+            return Err(ServerError(OpenConvError::Validation(format!(
+                "message_retention_days must be between 0 and {MAX_MESSAGE_RETENTION_DAYS}"
+            ))));
+        }
+        // Deleting history is irreversible, so only the owner may set it
+        if fetch_guild_owner(&state.db, member.guild_id).await? != member.user_id {
+            return Err(ServerError(OpenConvError::Forbidden));
+        }
     }
 
-    let row = query
-        .fetch_optional(&state.db)
+    let guild = repo::update(&state.db, member.guild_id, &changes)
         .await
         .map_err(db_err)?
         .ok_or(ServerError(OpenConvError::NotFound))?;
 
-    Ok(Json(GuildResponse {
-        id: row.id,
-        name: row.name,
-        owner_id: row.owner_id,
-        icon_url: row.icon_url,
-        created_at: row.created_at,
-        member_count: None,
-    }))
+    Ok(Json(guild.into_response(None)))
 }
 
 #[utoipa::path(delete, path = "/api/guilds/{guild_id}", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), responses((status = 204), (status = 403, body = crate::error::ErrorResponse)))]
@@ -272,17 +218,11 @@ pub async fn delete_guild(
     member: GuildMember,
     State(state): State<AppState>,
 ) -> Result<StatusCode, ServerError> {
-    let result = sqlx::query(
-        "UPDATE guilds SET deleted_at = NOW() \
-         WHERE id = $1 AND owner_id = $2 AND deleted_at IS NULL",
-    )
-    .bind(member.guild_id)
-    .bind(member.user_id)
-    .execute(&state.db)
-    .await
-    .map_err(db_err)?;
+    let deleted = repo::soft_delete(&state.db, member.guild_id, member.user_id)
+        .await
+        .map_err(db_err)?;
 
-    if result.rows_affected() == 0 {
+    if !deleted {
         // Either not the owner, or guild already deleted
         let owner_id = fetch_guild_owner(&state.db, member.guild_id).await?;
         if member.user_id != owner_id {
@@ -307,27 +247,12 @@ pub async fn restore_guild(
     State(state): State<AppState>,
 ) -> Result<Json<GuildResponse>, ServerError> {
     // Atomic owner check + restore in a single query
-    let row = sqlx::query_as::<_, GuildRow>(
-        "UPDATE guilds SET deleted_at = NULL \
-         WHERE id = $1 AND owner_id = $2 AND deleted_at IS NOT NULL \
-         AND deleted_at > NOW() - INTERVAL '7 days' \
-         RETURNING id, name, owner_id, icon_url, created_at",
-    )
-    .bind(member.guild_id)
-    .bind(member.user_id)
-    .fetch_optional(&state.db)
-    .await
-    .map_err(db_err)?;
+    let restored = repo::restore(&state.db, member.guild_id, member.user_id)
+        .await
+        .map_err(db_err)?;
 
-    match row {
-        Some(row) => Ok(Json(GuildResponse {
-            id: row.id,
-            name: row.name,
-            owner_id: row.owner_id,
-            icon_url: row.icon_url,
-            created_at: row.created_at,
-            member_count: None,
-        })),
+    match restored {
+        Some(guild) => Ok(Json(guild.into_response(None))),
         None => {
             // Determine specific error: not owner, or window expired
             let owner_id = fetch_guild_owner(&state.db, member.guild_id).await?;
@@ -356,13 +281,16 @@ pub async fn leave_guild(
         )));
     }
 
-    sqlx::query("DELETE FROM guild_members WHERE user_id = $1 AND guild_id = $2")
-        .bind(member.user_id)
-        .bind(member.guild_id)
-        .execute(&state.db)
+    repo::remove_member(&state.db, member.guild_id, member.user_id)
         .await
         .map_err(db_err)?;
 
+    member_events::publish(
+        &state,
+        MemberEvent::new(member.guild_id, member.user_id, MemberEventKind::Leave),
+    )
+    .await;
+
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -381,16 +309,9 @@ pub async fn kick_member(
         )));
     }
 
-    // Check if target is a member
-    let target_exists: bool = sqlx::query_scalar(
-        "SELECT EXISTS(SELECT 1 FROM guild_members WHERE user_id = $1 AND guild_id = $2)",
-    )
-    .bind(target_user_id)
-    .bind(member.guild_id)
-    .fetch_one(&state.db)
-    .await
-    .map_err(db_err)?;
-
+    let target_exists = repo::is_member(&state.db, member.guild_id, target_user_id)
+        .await
+        .map_err(db_err)?;
     if !target_exists {
         return Err(ServerError(OpenConvError::NotFound));
     }
@@ -399,100 +320,118 @@ pub async fn kick_member(
     let owner_id = fetch_guild_owner(&state.db, member.guild_id).await?;
 
     if member.user_id != owner_id {
-        // Hierarchy check: actor's highest role position must be > target's highest role position
-        let actor_max: Option<i32> = sqlx::query_scalar(
-            "SELECT MAX(r.position) FROM guild_member_roles gmr \
-             JOIN roles r ON r.id = gmr.role_id \
-             WHERE gmr.user_id = $1 AND gmr.guild_id = $2",
-        )
-        .bind(member.user_id)
-        .bind(member.guild_id)
-        .fetch_one(&state.db)
+        ensure_outranks(&state.db, member.guild_id, member.user_id, target_user_id).await?;
+    }
+
+    repo::remove_member(&state.db, member.guild_id, target_user_id)
         .await
         .map_err(db_err)?;
 
-        let target_max: Option<i32> = sqlx::query_scalar(
-            "SELECT MAX(r.position) FROM guild_member_roles gmr \
-             JOIN roles r ON r.id = gmr.role_id \
-             WHERE gmr.user_id = $1 AND gmr.guild_id = $2",
-        )
-        .bind(target_user_id)
-        .bind(member.guild_id)
-        .fetch_one(&state.db)
+    member_events::publish(
+        &state,
+        MemberEvent::new(member.guild_id, target_user_id, MemberEventKind::Kick)
+            .with_actor(member.user_id),
+    )
+    .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(get, path = "/api/guilds/{guild_id}/members", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), responses((status = 200, body = Vec<openconv_shared::api::guild::GuildMemberResponse>), (status = 304, description = "Not modified since the ETag in If-None-Match")))]
+/// List guild members with their roles.
+pub async fn list_members(
+    member: GuildMember,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, ServerError> {
+    let guild_id = member.guild_id;
+    let rows = state
+        .read(|db| async move { repo::list_members(&db, guild_id).await })
         .await
         .map_err(db_err)?;
 
-        let actor_pos = actor_max.unwrap_or(0);
-        let target_pos = target_max.unwrap_or(0);
+    let members: Vec<GuildMemberResponse> = rows.into_iter().map(Member::into_response).collect();
 
-        if actor_pos <= target_pos {
-            return Err(ServerError(OpenConvError::Forbidden));
-        }
+    Ok(json_with_etag(&headers, &members))
+}
+
+#[utoipa::path(post, path = "/api/guilds/{guild_id}/members/bulk", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), request_body = openconv_shared::api::guild::BulkMembersRequest, responses((status = 200, body = Vec<openconv_shared::api::guild::GuildMemberResponse>), (status = 400, body = crate::error::ErrorResponse)))]
+/// Look up several guild members at once, e.g. the authors in a page of
+/// messages. Users who are not members are left out of the response.
+pub async fn bulk_members(
+    member: GuildMember,
+    State(state): State<AppState>,
+    Json(req): Json<BulkMembersRequest>,
+) -> Result<Json<Vec<GuildMemberResponse>>, ServerError> {
+    if req.user_ids.len() > MAX_BULK_MEMBER_IDS {
+        return Err(ServerError(OpenConvError::Validation(format!(
+            "at most {MAX_BULK_MEMBER_IDS} user_ids per request"
+        ))));
+    }
+    if req.user_ids.is_empty() {
+        return Ok(Json(Vec::new()));
     }
 
-    sqlx::query("DELETE FROM guild_members WHERE user_id = $1 AND guild_id = $2")
-        .bind(target_user_id)
-        .bind(member.guild_id)
-        .execute(&state.db)
+    let guild_id = member.guild_id;
+    let user_ids = req.user_ids.as_slice();
+    let rows = state
+        .read(|db| async move { repo::members_by_ids(&db, guild_id, user_ids).await })
         .await
         .map_err(db_err)?;
 
-    Ok(StatusCode::NO_CONTENT)
+    Ok(Json(rows.into_iter().map(Member::into_response).collect()))
 }
 
-#[utoipa::path(get, path = "/api/guilds/{guild_id}/members", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), responses((status = 200, body = Vec<openconv_shared::api::guild::GuildMemberResponse>)))]
-/// List guild members with their roles.
-pub async fn list_members(
+/// Page size used when streaming the member export.
+const MEMBER_EXPORT_PAGE_SIZE: i64 = 500;
+
+#[utoipa::path(get, path = "/api/guilds/{guild_id}/members/export", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), responses((status = 200, content_type = "application/x-ndjson", body = openconv_shared::api::guild::GuildMemberResponse), (status = 403, body = crate::error::ErrorResponse)))]
+/// Stream the full member list as NDJSON (or SSE with `Accept: text/event-stream`).
+///
+/// Members are read a page at a time as the client consumes the body, so
+/// very large guilds never materialize the whole list in memory.
+pub async fn export_members(
     member: GuildMember,
     State(state): State<AppState>,
-) -> Result<Json<Vec<GuildMemberResponse>>, ServerError> {
-    let rows = sqlx::query_as::<_, MemberRow>(
-        "SELECT \
-             u.id AS user_id, \
-             u.display_name, \
-             gm.joined_at, \
-             COALESCE( \
-                 json_agg(json_build_object('id', r.id, 'name', r.name, 'position', r.position)) \
-                 FILTER (WHERE r.id IS NOT NULL), \
-                 '[]' \
-             ) AS roles \
-         FROM guild_members gm \
-         JOIN users u ON u.id = gm.user_id \
-         LEFT JOIN guild_member_roles gmr ON gmr.user_id = gm.user_id AND gmr.guild_id = gm.guild_id \
-         LEFT JOIN roles r ON r.id = gmr.role_id \
-         WHERE gm.guild_id = $1 \
-         GROUP BY u.id, u.display_name, gm.joined_at \
-         ORDER BY gm.joined_at ASC",
-    )
-    .bind(member.guild_id)
-    .fetch_all(&state.db)
-    .await
-    .map_err(db_err)?;
-
-    let members = rows
-        .into_iter()
-        .map(|r| {
-            let roles: Vec<RoleSummary> = match serde_json::from_value(r.roles) {
-                Ok(v) => v,
-                Err(e) => {
-                    tracing::warn!(
-                        user_id = %r.user_id,
-                        error = %e,
-                        "failed to deserialize member roles from JSON, defaulting to empty"
-                    );
-                    vec![]
+    headers: HeaderMap,
+) -> Response {
+    let format = StreamFormat::from_headers(&headers);
+    let db = state.read_pool();
+    let guild_id = member.guild_id;
+
+    // State: (cursor after the last member sent, whether the table is exhausted)
+    let pages = futures::stream::unfold(
+        (None::<(chrono::DateTime<chrono::Utc>, UserId)>, false),
+        move |(after, done)| {
+            let db = db.clone();
+            async move {
+                if done {
+                    return None;
+                }
+                let rows = repo::members_page(&db, guild_id, after, MEMBER_EXPORT_PAGE_SIZE).await;
+
+                let rows = match rows {
+                    Ok(rows) => rows,
+                    Err(e) => {
+                        // Headers are already sent; end the stream early
+                        tracing::error!(error = %e, guild_id = %guild_id, "member export query failed");
+                        return None;
+                    }
+                };
+                if rows.is_empty() {
+                    return None;
                 }
-            };
-            GuildMemberResponse {
-                user_id: r.user_id,
-                display_name: r.display_name,
-                joined_at: r.joined_at,
-                roles,
+
+                let exhausted = (rows.len() as i64) < MEMBER_EXPORT_PAGE_SIZE;
+                let next = rows.last().map(|r| (r.joined_at, r.user_id));
+                let page: Vec<GuildMemberResponse> =
+                    rows.into_iter().map(Member::into_response).collect();
+                Some((futures::stream::iter(page), (next, exhausted)))
             }
-        })
-        .collect();
+        },
+    );
 
-    Ok(Json(members))
+    stream_response(format, pages.flatten())
 }
 
 /// Route builder for guild endpoints.
@@ -511,10 +450,12 @@ pub fn routes() -> axum::Router<AppState> {
 /// Route builder for guild member endpoints.
 /// Mounted at /api/guilds/{guild_id}/members by the router.
 pub fn member_routes() -> axum::Router<AppState> {
-    use axum::routing::{delete, get, put};
+    use axum::routing::{delete, get, post, put};
 
     axum::Router::new()
         .route("/", get(list_members))
+        .route("/bulk", post(bulk_members))
+        .route("/export", get(export_members))
         .route("/me", delete(leave_guild))
         .route("/{user_id}", delete(kick_member))
         .route(
@@ -523,38 +464,10 @@ pub fn member_routes() -> axum::Router<AppState> {
         )
 }
 
-// Internal row types for sqlx queries
-
-#[derive(sqlx::FromRow)]
-struct GuildRow {
-    id: GuildId,
-    name: String,
-    owner_id: UserId,
-    icon_url: Option<String>,
-    created_at: chrono::DateTime<chrono::Utc>,
-}
-
-#[derive(sqlx::FromRow)]
-struct GuildWithCountRow {
-    id: GuildId,
-    name: String,
-    owner_id: UserId,
-    icon_url: Option<String>,
-    created_at: chrono::DateTime<chrono::Utc>,
-    member_count: i64,
-}
-
-#[derive(sqlx::FromRow)]
-struct MemberRow {
-    user_id: UserId,
-    display_name: String,
-    joined_at: chrono::DateTime<chrono::Utc>,
-    roles: serde_json::Value,
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use openconv_shared::api::guild::RoleSummary;
 
     #[test]
     fn default_owner_permissions_are_all() {
@@ -582,10 +495,14 @@ mod tests {
 
     #[test]
     fn default_member_permissions_minimal() {
-        let member_perms =
-            Permissions::SEND_MESSAGES | Permissions::READ_MESSAGES | Permissions::ATTACH_FILES;
+        let member_perms = Permissions::SEND_MESSAGES
+            | Permissions::READ_MESSAGES
+            | Permissions::ATTACH_FILES
+            | Permissions::CONNECT
+            | Permissions::SPEAK;
         assert!(member_perms.contains(Permissions::SEND_MESSAGES));
         assert!(member_perms.contains(Permissions::READ_MESSAGES));
+        assert!(member_perms.contains(Permissions::CONNECT));
         assert!(!member_perms.contains(Permissions::KICK_MEMBERS));
         assert!(!member_perms.contains(Permissions::MANAGE_GUILD));
     }
@@ -638,6 +555,13 @@ mod tests {
         let req = UpdateGuildRequest {
             name: None,
             icon_url: None,
+            prune_on_ban: None,
+            max_members: None,
+            file_retention_days: None,
+            message_retention_days: None,
+            verification_level: None,
+            max_joins_per_minute: None,
+            invites_paused: None,
         };
         assert!(req.name.is_none());
         assert!(req.icon_url.is_none());
@@ -1,36 +1,156 @@
+use std::future::Future;
+use std::time::{Duration, Instant};
+
 use axum::extract::State;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::Json;
 use fred::interfaces::ClientLike;
+use openconv_shared::api::admin::{DependencyHealth, HealthReportResponse};
 
+use crate::error::ServerError;
+use crate::extractors::auth::AuthUser;
 use crate::state::AppState;
+use crate::storage::ping_object_store;
+
+/// Upper bound for a single dependency check, so a hung backend fails the
+/// probe instead of stalling it past the orchestrator's own timeout.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
 
-#[utoipa::path(get, path = "/health/live", tag = "Health", responses((status = 200, description = "Service is alive")))]
-/// GET /health/live — returns 200 unconditionally.
-/// Used by load balancers to check if the process is alive.
+#[utoipa::path(get, path = "/healthz", tag = "Health", responses((status = 200, description = "Service is alive")))]
+/// GET /healthz — returns 200 unconditionally.
+/// Used as the liveness probe: only answers whether the process is serving.
+/// Also served at /health/live.
 pub async fn liveness() -> impl IntoResponse {
     Json(serde_json::json!({ "status": "ok" }))
 }
 
-#[utoipa::path(get, path = "/health/ready", tag = "Health", responses((status = 200, description = "Service is ready"), (status = 503, description = "Service unavailable")))]
-/// GET /health/ready — queries database and Redis to verify connectivity.
-/// Returns 200 on success, 503 on failure.
+#[utoipa::path(get, path = "/readyz", tag = "Health", responses((status = 200, description = "Service is ready"), (status = 503, description = "Service unavailable")))]
+/// GET /readyz — checks the database, Redis, object store and SMTP, each
+/// with a timeout. Returns 200 when all pass, 503 otherwise or while the
+/// server drains for shutdown. Also served at /health/ready.
 pub async fn readiness(State(state): State<AppState>) -> impl IntoResponse {
-    let db_ok = sqlx::query("SELECT 1").execute(&state.db).await.is_ok();
-    let redis_ok: bool = state.redis.ping::<()>(None).await.is_ok();
+    // Taken out of rotation as soon as shutdown starts
+    if state.ws.is_draining() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "status": "draining" })),
+        );
+    }
+
+    let dependencies = check_dependencies(&state).await;
+    let ready = dependencies.iter().all(|d| d.status == "ok");
+
+    let mut body = serde_json::json!({ "status": if ready { "ok" } else { "unavailable" } });
+    if !ready {
+        // Only pass/fail per dependency; error details are for admins
+        for dependency in &dependencies {
+            body[dependency.name.as_str()] = (dependency.status == "ok").into();
+        }
+    }
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(body))
+}
 
-    if db_ok && redis_ok {
-        (StatusCode::OK, Json(serde_json::json!({ "status": "ok" }))).into_response()
+#[utoipa::path(get, path = "/api/admin/health", tag = "Admin", security(("bearer_auth" = [])), responses((status = 200, body = openconv_shared::api::admin::HealthReportResponse), (status = 403, body = crate::error::ErrorResponse)))]
+/// GET /api/admin/health — per-dependency status, latency and error.
+/// Instance admins only. Always 200; read `status` for the verdict.
+pub async fn detailed(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<HealthReportResponse>, ServerError> {
+    auth.require_instance_admin(&state.config)?;
+
+    let dependencies = check_dependencies(&state).await;
+    let status = if dependencies.iter().all(|d| d.status == "ok") {
+        "ok"
     } else {
-        (
-            StatusCode::SERVICE_UNAVAILABLE,
-            Json(serde_json::json!({
-                "status": "unavailable",
-                "db": db_ok,
-                "redis": redis_ok,
-            })),
-        )
-            .into_response()
+        "unavailable"
+    };
+    Ok(Json(HealthReportResponse {
+        status: status.to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        dependencies,
+    }))
+}
+
+/// Check every backing service concurrently.
+async fn check_dependencies(state: &AppState) -> Vec<DependencyHealth> {
+    let (db, redis, object_store, smtp) = tokio::join!(
+        timed("database", CHECK_TIMEOUT, async {
+            sqlx::query("SELECT 1")
+                .execute(&state.db)
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }),
+        timed("redis", CHECK_TIMEOUT, async {
+            state
+                .redis
+                .ping::<()>(None)
+                .await
+                .map_err(|e| e.to_string())
+        }),
+        timed("object_store", CHECK_TIMEOUT, async {
+            ping_object_store(&*state.object_store)
+                .await
+                .map_err(|e| e.to_string())
+        }),
+        timed("smtp", CHECK_TIMEOUT, async {
+            state.email.health_check().await.map_err(|e| e.to_string())
+        }),
+    );
+    vec![db, redis, object_store, smtp]
+}
+
+async fn timed(
+    name: &str,
+    limit: Duration,
+    check: impl Future<Output = Result<(), String>>,
+) -> DependencyHealth {
+    let started = Instant::now();
+    let result = match tokio::time::timeout(limit, check).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("timed out after {}ms", limit.as_millis())),
+    };
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    if let Err(e) = &result {
+        tracing::warn!(dependency = name, error = %e, "health check failed");
+    }
+    DependencyHealth {
+        name: name.to_string(),
+        status: if result.is_ok() { "ok" } else { "unavailable" }.to_string(),
+        latency_ms,
+        error: result.err(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn timed_reports_success_and_failure() {
+        let ok = timed("database", CHECK_TIMEOUT, async { Ok(()) }).await;
+        assert_eq!(ok.status, "ok");
+        assert!(ok.error.is_none());
+
+        let failed = timed("redis", CHECK_TIMEOUT, async { Err("refused".to_string()) }).await;
+        assert_eq!(failed.status, "unavailable");
+        assert_eq!(failed.error.as_deref(), Some("refused"));
+    }
+
+    #[tokio::test]
+    async fn timed_gives_up_on_hung_checks() {
+        let limit = Duration::from_millis(20);
+        let hung = timed("smtp", limit, std::future::pending()).await;
+        assert_eq!(hung.status, "unavailable");
+        assert!(hung.error.unwrap().contains("timed out"));
+        assert!(hung.latency_ms >= 20);
     }
 }
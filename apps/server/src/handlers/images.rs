@@ -0,0 +1,421 @@
+use std::io::Cursor;
+
+use axum::body::{Body, Bytes};
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::Response;
+use axum::Json;
+use futures::TryStreamExt;
+use image::imageops::FilterType;
+use image::{ImageFormat, ImageReader, Limits};
+use object_store::path::Path as StorePath;
+use object_store::{ObjectStore, PutPayload};
+use openconv_shared::api::media::{
+    ImageAssetResponse, IMAGE_SIZES, MAX_IMAGE_DIMENSION, MAX_IMAGE_UPLOAD_BYTES,
+};
+use openconv_shared::error::OpenConvError;
+use openconv_shared::ids::{GuildId, UserId};
+use openconv_shared::permissions::Permissions;
+
+use crate::error::ServerError;
+use crate::extractors::auth::AuthUser;
+use crate::extractors::guild_member::GuildMember;
+use crate::handlers::files::store_err;
+use crate::state::AppState;
+
+/// Formats accepted for avatars and guild icons.
+const ACCEPTED_FORMATS: [ImageFormat; 4] = [
+    ImageFormat::Png,
+    ImageFormat::Jpeg,
+    ImageFormat::Gif,
+    ImageFormat::WebP,
+];
+
+fn db_err(e: sqlx::Error) -> ServerError {
+    tracing::error!(error = %e, "database error");
+    ServerError(OpenConvError::Internal("database error".into()))
+}
+
+/// Query for fetching an avatar or icon.
+#[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
+pub struct ImageQuery {
+    /// Wanted edge length in pixels. Rounded up to the next standard size;
+    /// omitted or larger than every standard size serves the original.
+    size: Option<u32>,
+}
+
+/// Name of the stored rendition that best serves a requested size.
+fn pick_variant(size: Option<u32>) -> String {
+    size.and_then(|size| IMAGE_SIZES.into_iter().find(|standard| *standard >= size))
+        .map(|standard| standard.to_string())
+        .unwrap_or_else(|| "original".to_string())
+}
+
+fn variant_path(prefix: &str, key: uuid::Uuid, variant: &str) -> StorePath {
+    StorePath::from(format!("{prefix}/{key}/{variant}.png"))
+}
+
+/// Decode an uploaded image and render the original plus every standard
+/// size as PNG. Re-encoding drops embedded metadata such as EXIF location.
+fn render_variants(bytes: &[u8]) -> Result<Vec<(String, Vec<u8>)>, ServerError> {
+    let invalid = |msg: &str| ServerError(OpenConvError::Validation(msg.to_string()));
+
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|_| invalid("unreadable image"))?;
+    if !reader
+        .format()
+        .is_some_and(|format| ACCEPTED_FORMATS.contains(&format))
+    {
+        return Err(invalid("image must be PNG, JPEG, GIF or WebP"));
+    }
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    reader.limits(limits);
+    let image = reader.decode().map_err(|e| match e {
+        image::ImageError::Limits(_) => ServerError(OpenConvError::Validation(format!(
+            "image must be at most {MAX_IMAGE_DIMENSION}x{MAX_IMAGE_DIMENSION} pixels"
+        ))),
+        _ => invalid("unreadable image"),
+    })?;
+
+    let encode = |image: &image::DynamicImage| {
+        let mut out = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut out), ImageFormat::Png)
+            .map_err(|e| ServerError(OpenConvError::Internal(format!("image encode: {e}"))))?;
+        Ok::<_, ServerError>(out)
+    };
+
+    let mut variants = vec![("original".to_string(), encode(&image)?)];
+    for size in IMAGE_SIZES {
+        let resized = image.resize_to_fill(size, size, FilterType::Lanczos3);
+        variants.push((size.to_string(), encode(&resized)?));
+    }
+    Ok(variants)
+}
+
+/// Render and store every variant of an uploaded image under a fresh key.
+async fn store_image(
+    state: &AppState,
+    prefix: &str,
+    body: Bytes,
+) -> Result<uuid::Uuid, ServerError> {
+    if body.len() > MAX_IMAGE_UPLOAD_BYTES {
+        return Err(ServerError(OpenConvError::PayloadTooLarge(format!(
+            "image exceeds maximum size of {MAX_IMAGE_UPLOAD_BYTES} bytes"
+        ))));
+    }
+    // Decoding and resizing are CPU-bound
+    let variants = tokio::task::spawn_blocking(move || render_variants(&body))
+        .await
+        .map_err(|_| ServerError(OpenConvError::Internal("image processing failed".into())))??;
+
+    let key = uuid::Uuid::now_v7();
+    for (variant, bytes) in variants {
+        state
+            .object_store
+            .put(
+                &variant_path(prefix, key, &variant),
+                PutPayload::from(bytes),
+            )
+            .await
+            .map_err(store_err)?;
+    }
+    Ok(key)
+}
+
+/// Delete a replaced image's variants in the background.
+fn delete_image_later(state: &AppState, prefix: String, key: uuid::Uuid) {
+    let store = state.object_store.clone();
+    tokio::spawn(async move {
+        let prefix = StorePath::from(format!("{prefix}/{key}"));
+        let result = async {
+            let objects: Vec<_> = store.list(Some(&prefix)).try_collect().await?;
+            for object in objects {
+                match store.delete(&object.location).await {
+                    Ok(()) | Err(object_store::Error::NotFound { .. }) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok::<_, object_store::Error>(())
+        }
+        .await;
+        if let Err(e) = result {
+            tracing::error!(error = %e, %prefix, "failed to delete replaced image");
+        }
+    });
+}
+
+/// Stream one stored variant. The URL is versioned by key, so responses
+/// can be cached for a long time.
+async fn image_response(
+    store: &dyn ObjectStore,
+    prefix: &str,
+    key: Option<uuid::Uuid>,
+    size: Option<u32>,
+) -> Result<Response, ServerError> {
+    let key = key.ok_or(ServerError(OpenConvError::NotFound))?;
+    let result = store
+        .get(&variant_path(prefix, key, &pick_variant(size)))
+        .await
+        .map_err(|e| match e {
+            object_store::Error::NotFound { .. } => ServerError(OpenConvError::NotFound),
+            e => {
+                tracing::error!(error = %e, "object store get failed");
+                ServerError(OpenConvError::Internal("file storage error".into()))
+            }
+        })?;
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, "image/png")
+        .header(header::CONTENT_LENGTH, result.meta.size.to_string())
+        .header(header::CACHE_CONTROL, "public, max-age=86400")
+        .body(Body::from_stream(result.into_stream()))
+        .map_err(|_| ServerError(OpenConvError::Internal("response build error".into())))
+}
+
+pub fn avatar_prefix(user_id: UserId) -> String {
+    format!("avatars/{user_id}")
+}
+
+fn icon_prefix(guild_id: GuildId) -> String {
+    format!("guild-icons/{guild_id}")
+}
+
+fn asset_response(url: String) -> Json<ImageAssetResponse> {
+    Json(ImageAssetResponse {
+        url,
+        sizes: IMAGE_SIZES.to_vec(),
+    })
+}
+
+// ─── Avatars ────────────────────────────────────────────────
+
+#[utoipa::path(put, path = "/api/users/me/avatar", tag = "Users", security(("bearer_auth" = [])), request_body(content = Vec<u8>, content_type = "image/*"), responses((status = 200, body = openconv_shared::api::media::ImageAssetResponse), (status = 400, body = crate::error::ErrorResponse), (status = 413, body = crate::error::ErrorResponse)))]
+/// PUT /api/users/me/avatar
+/// Upload a new avatar. The raw image is the request body; standard sizes
+/// are rendered now and `avatar_url` points at the result.
+pub async fn upload_avatar(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    body: Bytes,
+) -> Result<Json<ImageAssetResponse>, ServerError> {
+    let prefix = avatar_prefix(auth.user_id);
+    let key = store_image(&state, &prefix, body).await?;
+    let url = format!("/api/users/{}/avatar?v={key}", auth.user_id);
+
+    let previous: Option<uuid::Uuid> = sqlx::query_scalar(
+        "UPDATE users u SET avatar_key = $2, avatar_url = $3 \
+         FROM (SELECT avatar_key FROM users WHERE id = $1 FOR UPDATE) old \
+         WHERE u.id = $1 RETURNING old.avatar_key",
+    )
+    .bind(auth.user_id)
+    .bind(key)
+    .bind(&url)
+    .fetch_one(&state.db)
+    .await
+    .map_err(db_err)?;
+    if let Some(previous) = previous {
+        delete_image_later(&state, prefix, previous);
+    }
+
+    Ok(asset_response(url))
+}
+
+#[utoipa::path(delete, path = "/api/users/me/avatar", tag = "Users", security(("bearer_auth" = [])), responses((status = 204), (status = 401, body = crate::error::ErrorResponse)))]
+/// DELETE /api/users/me/avatar
+/// Remove an uploaded avatar and clear `avatar_url`.
+pub async fn delete_avatar(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<StatusCode, ServerError> {
+    let previous: Option<uuid::Uuid> = sqlx::query_scalar(
+        "UPDATE users u SET avatar_key = NULL, avatar_url = NULL \
+         FROM (SELECT avatar_key FROM users WHERE id = $1 FOR UPDATE) old \
+         WHERE u.id = $1 RETURNING old.avatar_key",
+    )
+    .bind(auth.user_id)
+    .fetch_one(&state.db)
+    .await
+    .map_err(db_err)?;
+    if let Some(previous) = previous {
+        delete_image_later(&state, avatar_prefix(auth.user_id), previous);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(get, path = "/api/users/{user_id}/avatar", tag = "Users", params(("user_id" = openconv_shared::ids::UserId, Path, description = "User ID"), ImageQuery), responses((status = 200, description = "PNG image", content_type = "image/png"), (status = 404, body = crate::error::ErrorResponse)))]
+/// GET /api/users/:user_id/avatar
+/// Serve a user's uploaded avatar. Public so it works in plain `<img>` tags.
+pub async fn get_avatar(
+    State(state): State<AppState>,
+    Path(user_id): Path<UserId>,
+    Query(query): Query<ImageQuery>,
+) -> Result<Response, ServerError> {
+    let key: Option<uuid::Uuid> = sqlx::query_scalar("SELECT avatar_key FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(db_err)?
+        .flatten();
+    image_response(
+        &*state.object_store,
+        &avatar_prefix(user_id),
+        key,
+        query.size,
+    )
+    .await
+}
+
+// ─── Guild icons ────────────────────────────────────────────
+
+#[utoipa::path(put, path = "/api/guilds/{guild_id}/icon", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), request_body(content = Vec<u8>, content_type = "image/*"), responses((status = 200, body = openconv_shared::api::media::ImageAssetResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse), (status = 413, body = crate::error::ErrorResponse)))]
+/// PUT /api/guilds/:guild_id/icon
+/// Upload a new guild icon. Requires MANAGE_GUILD permission.
+pub async fn upload_icon(
+    member: GuildMember,
+    State(state): State<AppState>,
+    body: Bytes,
+) -> Result<Json<ImageAssetResponse>, ServerError> {
+    member.require(Permissions::MANAGE_GUILD)?;
+
+    let prefix = icon_prefix(member.guild_id);
+    let key = store_image(&state, &prefix, body).await?;
+    let url = format!("/api/guilds/{}/icon?v={key}", member.guild_id);
+
+    let previous: Option<uuid::Uuid> = sqlx::query_scalar(
+        "UPDATE guilds g SET icon_key = $2, icon_url = $3 \
+         FROM (SELECT icon_key FROM guilds WHERE id = $1 FOR UPDATE) old \
+         WHERE g.id = $1 RETURNING old.icon_key",
+    )
+    .bind(member.guild_id)
+    .bind(key)
+    .bind(&url)
+    .fetch_one(&state.db)
+    .await
+    .map_err(db_err)?;
+    if let Some(previous) = previous {
+        delete_image_later(&state, prefix, previous);
+    }
+
+    Ok(asset_response(url))
+}
+
+#[utoipa::path(delete, path = "/api/guilds/{guild_id}/icon", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), responses((status = 204), (status = 403, body = crate::error::ErrorResponse)))]
+/// DELETE /api/guilds/:guild_id/icon
+/// Remove an uploaded guild icon. Requires MANAGE_GUILD permission.
+pub async fn delete_icon(
+    member: GuildMember,
+    State(state): State<AppState>,
+) -> Result<StatusCode, ServerError> {
+    member.require(Permissions::MANAGE_GUILD)?;
+
+    let previous: Option<uuid::Uuid> = sqlx::query_scalar(
+        "UPDATE guilds g SET icon_key = NULL, icon_url = NULL \
+         FROM (SELECT icon_key FROM guilds WHERE id = $1 FOR UPDATE) old \
+         WHERE g.id = $1 RETURNING old.icon_key",
+    )
+    .bind(member.guild_id)
+    .fetch_one(&state.db)
+    .await
+    .map_err(db_err)?;
+    if let Some(previous) = previous {
+        delete_image_later(&state, icon_prefix(member.guild_id), previous);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(get, path = "/api/guilds/{guild_id}/icon", tag = "Guilds", params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID"), ImageQuery), responses((status = 200, description = "PNG image", content_type = "image/png"), (status = 404, body = crate::error::ErrorResponse)))]
+/// GET /api/guilds/:guild_id/icon
+/// Serve a guild's uploaded icon. Public, like invite previews.
+pub async fn get_icon(
+    State(state): State<AppState>,
+    Path(guild_id): Path<GuildId>,
+    Query(query): Query<ImageQuery>,
+) -> Result<Response, ServerError> {
+    let key: Option<uuid::Uuid> =
+        sqlx::query_scalar("SELECT icon_key FROM guilds WHERE id = $1 AND deleted_at IS NULL")
+            .bind(guild_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(db_err)?
+            .flatten();
+    image_response(
+        &*state.object_store,
+        &icon_prefix(guild_id),
+        key,
+        query.size,
+    )
+    .await
+}
+
+/// Avatar routes. Mounted at /api/users.
+pub fn user_routes() -> axum::Router<AppState> {
+    use axum::routing::{get, put};
+
+    axum::Router::new()
+        .route("/me/avatar", put(upload_avatar).delete(delete_avatar))
+        .route("/{user_id}/avatar", get(get_avatar))
+}
+
+/// Guild icon routes. Mounted at /api/guilds.
+pub fn guild_routes() -> axum::Router<AppState> {
+    use axum::routing::put;
+
+    axum::Router::new().route(
+        "/{guild_id}/icon",
+        put(upload_icon).delete(delete_icon).get(get_icon),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = image::RgbaImage::from_pixel(width, height, image::Rgba([200, 40, 40, 255]));
+        let mut out = Vec::new();
+        image::DynamicImage::ImageRgba8(image)
+            .write_to(&mut Cursor::new(&mut out), ImageFormat::Png)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn routes_build_without_panic() {
+        let _ = user_routes();
+        let _ = guild_routes();
+    }
+
+    #[test]
+    fn requested_size_rounds_up_to_a_standard_size() {
+        assert_eq!(pick_variant(None), "original");
+        assert_eq!(pick_variant(Some(32)), "64");
+        assert_eq!(pick_variant(Some(64)), "64");
+        assert_eq!(pick_variant(Some(100)), "128");
+        assert_eq!(pick_variant(Some(4096)), "original");
+    }
+
+    #[test]
+    fn renders_square_variants_for_every_size() {
+        let variants = render_variants(&png(300, 200)).unwrap();
+        let names: Vec<_> = variants.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["original", "64", "128", "512"]);
+
+        for (name, bytes) in &variants {
+            let decoded = image::load_from_memory(bytes).unwrap();
+            match name.parse::<u32>() {
+                Ok(size) => assert_eq!((decoded.width(), decoded.height()), (size, size)),
+                Err(_) => assert_eq!((decoded.width(), decoded.height()), (300, 200)),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_non_images_and_oversized_dimensions() {
+        assert!(render_variants(b"definitely not an image").is_err());
+        assert!(render_variants(&png(MAX_IMAGE_DIMENSION + 1, 1)).is_err());
+    }
+}
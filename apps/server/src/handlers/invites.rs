@@ -1,15 +1,21 @@
 use axum::extract::{Path, State};
 use axum::http::StatusCode;
 use axum::Json;
-use openconv_shared::api::invite::{CreateInviteRequest, InviteInfoResponse, InviteResponse};
-use openconv_shared::error::OpenConvError;
-use openconv_shared::ids::GuildId;
+use openconv_shared::api::gateway::{MemberEvent, MemberEventKind};
+use openconv_shared::api::guild::VerificationLevel;
+use openconv_shared::api::invite::{
+    CreateInviteRequest, InviteBlocker, InviteInfoResponse, InvitePreflightResponse, InviteResponse,
+};
+use openconv_shared::error::{ErrorCode, OpenConvError};
+use openconv_shared::ids::{GuildId, UserId};
 use openconv_shared::permissions::Permissions;
 use rand::Rng;
 
 use crate::error::ServerError;
 use crate::extractors::auth::AuthUser;
 use crate::extractors::guild_member::GuildMember;
+use crate::member_events;
+use crate::middleware::rate_limit::check_guild_join_rate_limit;
 use crate::state::AppState;
 
 fn db_err(e: sqlx::Error) -> ServerError {
@@ -127,36 +133,91 @@ pub async fn get_invite_info(
     _auth: AuthUser,
     Path(code): Path<String>,
 ) -> Result<Json<InviteInfoResponse>, ServerError> {
-    let row = sqlx::query_as::<_, InviteInfoRow>(
+    let code = code.as_str();
+    let row = state
+        .read(|db| async move {
+            sqlx::query_as::<_, InviteInfoRow>(
+                "SELECT \
+                    gi.code, \
+                    g.name AS guild_name, \
+                    g.id AS guild_id, \
+                    (SELECT COUNT(*) FROM guild_members WHERE guild_id = g.id) AS member_count, \
+                    u.display_name AS inviter_display_name \
+                 FROM guild_invites gi \
+                 JOIN guilds g ON g.id = gi.guild_id AND g.deleted_at IS NULL \
+                 LEFT JOIN users u ON u.id = gi.inviter_id \
+                 WHERE gi.code = $1 \
+                   AND (gi.expires_at IS NULL OR gi.expires_at > NOW()) \
+                   AND (gi.max_uses IS NULL OR gi.use_count < gi.max_uses)",
+            )
+            .bind(code)
+            .fetch_optional(&db)
+            .await
+        })
+        .await
+        .map_err(db_err)?
+        .ok_or(ServerError(OpenConvError::NotFound))?;
+
+    Ok(Json(InviteInfoResponse {
+        code: row.code,
+        guild_name: row.guild_name,
+        guild_id: row.guild_id,
+        member_count: row.member_count,
+        inviter_display_name: row.inviter_display_name,
+    }))
+}
+
+#[utoipa::path(get, path = "/api/invites/{code}/preflight", tag = "Invites", security(("bearer_auth" = [])), params(("code" = String, Path, description = "Invite code")), responses((status = 200, body = openconv_shared::api::invite::InvitePreflightResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// GET /api/invites/:code/preflight
+/// Reports whether the caller could accept the invite and, if not, every
+/// reason why. Does not consume a use.
+pub async fn preflight_invite(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(code): Path<String>,
+) -> Result<Json<InvitePreflightResponse>, ServerError> {
+    let row = sqlx::query_as::<_, PreflightRow>(
         "SELECT \
             gi.code, \
-            g.name AS guild_name, \
             g.id AS guild_id, \
+            g.name AS guild_name, \
+            g.max_members, \
+            g.verification_level, \
+            g.invites_paused_at IS NOT NULL AS invites_paused, \
+            u.created_at AS account_created_at, \
+            u.email_verified_at, \
+            gi.max_uses, \
+            gi.use_count, \
+            gi.expires_at, \
             (SELECT COUNT(*) FROM guild_members WHERE guild_id = g.id) AS member_count, \
-            u.display_name AS inviter_display_name \
+            EXISTS(SELECT 1 FROM guild_bans WHERE guild_id = g.id AND user_id = $2) AS banned, \
+            EXISTS(SELECT 1 FROM guild_members WHERE guild_id = g.id AND user_id = $2) AS is_member \
          FROM guild_invites gi \
          JOIN guilds g ON g.id = gi.guild_id AND g.deleted_at IS NULL \
-         LEFT JOIN users u ON u.id = gi.inviter_id \
-         WHERE gi.code = $1 \
-           AND (gi.expires_at IS NULL OR gi.expires_at > NOW()) \
-           AND (gi.max_uses IS NULL OR gi.use_count < gi.max_uses)",
+         JOIN users u ON u.id = $2 \
+         WHERE gi.code = $1",
     )
     .bind(&code)
+    .bind(auth.user_id)
     .fetch_optional(&state.db)
     .await
     .map_err(db_err)?
     .ok_or(ServerError(OpenConvError::NotFound))?;
 
-    Ok(Json(InviteInfoResponse {
+    let blockers = row.blockers(chrono::Utc::now());
+
+    Ok(Json(InvitePreflightResponse {
         code: row.code,
-        guild_name: row.guild_name,
         guild_id: row.guild_id,
+        guild_name: row.guild_name,
         member_count: row.member_count,
-        inviter_display_name: row.inviter_display_name,
+        max_members: row.max_members,
+        can_join: blockers.is_empty(),
+        blockers,
     }))
 }
 
-#[utoipa::path(post, path = "/api/invites/{code}/accept", tag = "Invites", security(("bearer_auth" = [])), params(("code" = String, Path, description = "Invite code")), responses((status = 200), (status = 400, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse), (status = 409, body = crate::error::ErrorResponse)))]
+#[utoipa::path(post, path = "/api/invites/{code}/accept", tag = "Invites", security(("bearer_auth" = [])), params(("code" = String, Path, description = "Invite code")), responses((status = 200), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse), (status = 409, body = crate::error::ErrorResponse), (status = 429, body = crate::error::ErrorResponse)))]
 /// POST /api/invites/:code/accept
 /// Auth only -- any authenticated user can accept an invite.
 pub async fn accept_invite(
@@ -164,6 +225,18 @@ pub async fn accept_invite(
     auth: AuthUser,
     Path(code): Path<String>,
 ) -> Result<StatusCode, ServerError> {
+    auth.require_human()?;
+    join_with_invite(&state, auth.user_id, &code).await?;
+    Ok(StatusCode::OK)
+}
+
+/// Redeem an invite for `user_id` and publish the join. Shared with
+/// federation, which joins remote users' local stand-ins the same way.
+pub(crate) async fn join_with_invite(
+    state: &AppState,
+    user_id: UserId,
+    code: &str,
+) -> Result<GuildId, ServerError> {
     let mut tx = state.db.begin().await.map_err(db_err)?;
 
     // Step 1: Atomically validate and claim the invite
@@ -175,7 +248,7 @@ pub async fn accept_invite(
            AND (expires_at IS NULL OR expires_at > NOW()) \
          RETURNING code, guild_id, inviter_id, max_uses, use_count, expires_at, created_at",
     )
-    .bind(&code)
+    .bind(code)
     .fetch_optional(&mut *tx)
     .await
     .map_err(db_err)?;
@@ -183,50 +256,133 @@ pub async fn accept_invite(
     let invite = match invite {
         Some(inv) => inv,
         None => {
-            // Distinguish: does the invite exist at all?
-            let exists: Option<bool> =
-                sqlx::query_scalar("SELECT true FROM guild_invites WHERE code = $1")
-                    .bind(&code)
-                    .fetch_optional(&mut *tx)
-                    .await
-                    .map_err(db_err)?;
-
-            return if exists.is_some() {
-                Err(ServerError(OpenConvError::Validation(
-                    "invite is expired or at max uses".into(),
-                )))
-            } else {
-                Err(ServerError(OpenConvError::NotFound))
+            // Distinguish: does the invite exist at all, and why is it unusable?
+            let expired: Option<bool> = sqlx::query_scalar(
+                "SELECT expires_at IS NOT NULL AND expires_at <= NOW() \
+                 FROM guild_invites WHERE code = $1",
+            )
+            .bind(code)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(db_err)?;
+
+            return match expired {
+                Some(true) => Err(ServerError(
+                    OpenConvError::Validation("invite is expired".into())
+                        .with_code(ErrorCode::InviteExpired),
+                )),
+                Some(false) => Err(ServerError(
+                    OpenConvError::Validation("invite is at max uses".into())
+                        .with_code(ErrorCode::InviteMaxUses),
+                )),
+                None => Err(ServerError(OpenConvError::NotFound)),
             };
         }
     };
 
-    // Step 2: Verify guild is not soft-deleted
-    sqlx::query_scalar::<_, GuildId>("SELECT id FROM guilds WHERE id = $1 AND deleted_at IS NULL")
-        .bind(invite.guild_id)
-        .fetch_optional(&mut *tx)
-        .await
-        .map_err(db_err)?
-        .ok_or(ServerError(OpenConvError::NotFound))?;
+    // Step 2: Verify guild is not soft-deleted. The row lock serializes
+    // concurrent joins so the member limit below cannot be overshot.
+    let guild = sqlx::query_as::<_, JoinPolicyRow>(
+        "SELECT max_members, verification_level, max_joins_per_minute, \
+                invites_paused_at IS NOT NULL AS invites_paused \
+         FROM guilds WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+    )
+    .bind(invite.guild_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_err)?
+    .ok_or(ServerError(OpenConvError::NotFound))?;
+
+    // Step 2a: No invite works while the guild is in lockdown
+    if guild.invites_paused {
+        return Err(ServerError(
+            OpenConvError::Conflict("invites to this guild are paused".into())
+                .with_code(ErrorCode::InvitesPaused),
+        ));
+    }
+
+    // Step 2b: Banned users cannot rejoin
+    let banned: Option<bool> =
+        sqlx::query_scalar("SELECT true FROM guild_bans WHERE guild_id = $1 AND user_id = $2")
+            .bind(invite.guild_id)
+            .bind(user_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(db_err)?;
+
+    if banned.is_some() {
+        return Err(ServerError(
+            OpenConvError::Forbidden.with_code(ErrorCode::BannedFromGuild),
+        ));
+    }
 
     // Step 3: Check existing membership
     let existing: Option<bool> =
         sqlx::query_scalar("SELECT true FROM guild_members WHERE user_id = $1 AND guild_id = $2")
-            .bind(auth.user_id)
+            .bind(user_id)
             .bind(invite.guild_id)
             .fetch_optional(&mut *tx)
             .await
             .map_err(db_err)?;
 
     if existing.is_some() {
-        return Err(ServerError(OpenConvError::Conflict(
-            "already a member of this guild".into(),
-        )));
+        return Err(ServerError(
+            OpenConvError::Conflict("already a member of this guild".into())
+                .with_code(ErrorCode::AlreadyMember),
+        ));
+    }
+
+    // Step 3a: The account must meet the guild's verification level
+    let level = VerificationLevel::parse(&guild.verification_level).unwrap_or_default();
+    if level != VerificationLevel::None {
+        let (created_at, email_verified_at): (
+            chrono::DateTime<chrono::Utc>,
+            Option<chrono::DateTime<chrono::Utc>>,
+        ) = sqlx::query_as("SELECT created_at, email_verified_at FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(db_err)?;
+
+        if !level.is_met_by(created_at, email_verified_at, chrono::Utc::now()) {
+            return Err(ServerError(
+                OpenConvError::Forbidden.with_code(ErrorCode::VerificationLevelNotMet),
+            ));
+        }
+    }
+
+    // Step 3b: Enforce the guild's member limit
+    if let Some(max_members) = guild.max_members {
+        let member_count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM guild_members WHERE guild_id = $1")
+                .bind(invite.guild_id)
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(db_err)?;
+
+        if member_count >= i64::from(max_members) {
+            return Err(ServerError(
+                OpenConvError::Conflict("guild is full".into()).with_code(ErrorCode::GuildFull),
+            ));
+        }
+    }
+
+    // Step 3c: Enforce the join velocity limit. Counted last, so joins
+    // refused for other reasons do not use up the budget.
+    if let Some(max_joins) = guild.max_joins_per_minute {
+        check_guild_join_rate_limit(
+            &state.redis,
+            invite.guild_id,
+            max_joins.max(1) as u32,
+            state.live.settings().rate_limit.fail_mode,
+        )
+        .await
+        .map_err(ServerError)?;
     }
 
     // Step 4a: Add user to guild_members
     sqlx::query("INSERT INTO guild_members (user_id, guild_id) VALUES ($1, $2)")
-        .bind(auth.user_id)
+        .bind(user_id)
         .bind(invite.guild_id)
         .execute(&mut *tx)
         .await
@@ -241,7 +397,7 @@ pub async fn accept_invite(
             .map_err(db_err)?;
 
     sqlx::query("INSERT INTO guild_member_roles (user_id, guild_id, role_id) VALUES ($1, $2, $3)")
-        .bind(auth.user_id)
+        .bind(user_id)
         .bind(invite.guild_id)
         .bind(member_role_id)
         .execute(&mut *tx)
@@ -250,7 +406,13 @@ pub async fn accept_invite(
 
     tx.commit().await.map_err(db_err)?;
 
-    Ok(StatusCode::OK)
+    member_events::publish(
+        state,
+        MemberEvent::new(invite.guild_id, user_id, MemberEventKind::Join),
+    )
+    .await;
+
+    Ok(invite.guild_id)
 }
 
 /// Route builder for guild-scoped invite endpoints.
@@ -264,6 +426,7 @@ pub fn guild_routes() -> axum::Router<AppState> {
 pub fn public_routes() -> axum::Router<AppState> {
     axum::Router::new()
         .route("/{code}", axum::routing::get(get_invite_info))
+        .route("/{code}/preflight", axum::routing::get(preflight_invite))
         .route("/{code}/accept", axum::routing::post(accept_invite))
 }
 
@@ -292,6 +455,14 @@ impl InviteRow {
     }
 }
 
+#[derive(sqlx::FromRow)]
+struct JoinPolicyRow {
+    max_members: Option<i32>,
+    verification_level: String,
+    max_joins_per_minute: Option<i32>,
+    invites_paused: bool,
+}
+
 #[derive(sqlx::FromRow)]
 struct InviteInfoRow {
     code: String,
@@ -301,6 +472,58 @@ struct InviteInfoRow {
     inviter_display_name: Option<String>,
 }
 
+#[derive(sqlx::FromRow)]
+struct PreflightRow {
+    code: String,
+    guild_id: GuildId,
+    guild_name: String,
+    max_members: Option<i32>,
+    verification_level: String,
+    invites_paused: bool,
+    account_created_at: chrono::DateTime<chrono::Utc>,
+    email_verified_at: Option<chrono::DateTime<chrono::Utc>>,
+    max_uses: Option<i32>,
+    use_count: i32,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    member_count: i64,
+    banned: bool,
+    is_member: bool,
+}
+
+impl PreflightRow {
+    /// Every reason accept would currently fail, in the order accept checks them.
+    fn blockers(&self, now: chrono::DateTime<chrono::Utc>) -> Vec<InviteBlocker> {
+        let mut blockers = Vec::new();
+        if self.expires_at.is_some_and(|exp| exp <= now) {
+            blockers.push(InviteBlocker::InviteExpired);
+        }
+        if self.max_uses.is_some_and(|max| self.use_count >= max) {
+            blockers.push(InviteBlocker::InviteExhausted);
+        }
+        if self.invites_paused {
+            blockers.push(InviteBlocker::InvitesPaused);
+        }
+        if self.banned {
+            blockers.push(InviteBlocker::Banned);
+        }
+        if self.is_member {
+            blockers.push(InviteBlocker::AlreadyMember);
+            return blockers;
+        }
+        let level = VerificationLevel::parse(&self.verification_level).unwrap_or_default();
+        if !level.is_met_by(self.account_created_at, self.email_verified_at, now) {
+            blockers.push(InviteBlocker::VerificationRequired);
+        }
+        if self
+            .max_members
+            .is_some_and(|max| self.member_count >= i64::from(max))
+        {
+            blockers.push(InviteBlocker::GuildFull);
+        }
+        blockers
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -319,6 +542,91 @@ mod tests {
         }
     }
 
+    fn preflight_row() -> PreflightRow {
+        PreflightRow {
+            code: "AbCd1234".into(),
+            guild_id: GuildId::new(),
+            guild_name: "Test Guild".into(),
+            max_members: None,
+            verification_level: "none".into(),
+            invites_paused: false,
+            account_created_at: chrono::Utc::now(),
+            email_verified_at: Some(chrono::Utc::now()),
+            max_uses: None,
+            use_count: 0,
+            expires_at: None,
+            member_count: 1,
+            banned: false,
+            is_member: false,
+        }
+    }
+
+    #[test]
+    fn preflight_open_invite_has_no_blockers() {
+        assert!(preflight_row().blockers(chrono::Utc::now()).is_empty());
+    }
+
+    #[test]
+    fn preflight_reports_every_blocker() {
+        let now = chrono::Utc::now();
+        let row = PreflightRow {
+            max_uses: Some(1),
+            use_count: 1,
+            expires_at: Some(now - chrono::Duration::minutes(1)),
+            banned: true,
+            ..preflight_row()
+        };
+        assert_eq!(
+            row.blockers(now),
+            vec![
+                InviteBlocker::InviteExpired,
+                InviteBlocker::InviteExhausted,
+                InviteBlocker::Banned,
+            ]
+        );
+    }
+
+    #[test]
+    fn preflight_guild_full_only_for_non_members() {
+        let now = chrono::Utc::now();
+        let full = PreflightRow {
+            max_members: Some(1),
+            ..preflight_row()
+        };
+        assert_eq!(full.blockers(now), vec![InviteBlocker::GuildFull]);
+
+        let member = PreflightRow {
+            is_member: true,
+            ..full
+        };
+        assert_eq!(member.blockers(now), vec![InviteBlocker::AlreadyMember]);
+    }
+
+    #[test]
+    fn preflight_reports_lockdown_and_verification() {
+        let now = chrono::Utc::now();
+        let row = PreflightRow {
+            verification_level: "medium".into(),
+            invites_paused: true,
+            ..preflight_row()
+        };
+        assert_eq!(
+            row.blockers(now),
+            vec![
+                InviteBlocker::InvitesPaused,
+                InviteBlocker::VerificationRequired,
+            ]
+        );
+
+        let established = PreflightRow {
+            verification_level: "medium".into(),
+            account_created_at: now - chrono::Duration::hours(1),
+            email_verified_at: Some(now - chrono::Duration::hours(1)),
+            ..preflight_row()
+        };
+        assert!(established.blockers(now).is_empty());
+    }
+
     #[test]
     fn different_invites_get_different_codes() {
         let codes: HashSet<String> = (0..100).map(|_| generate_invite_code()).collect();
@@ -0,0 +1,12 @@
+use axum::extract::State;
+use axum::Json;
+
+use crate::jwt::JwkSet;
+use crate::state::AppState;
+
+#[utoipa::path(get, path = "/.well-known/jwks.json", tag = "Auth", responses((status = 200, body = crate::jwt::JwkSet)))]
+/// GET /.well-known/jwks.json — public keys for verifying issued tokens.
+/// Includes retired keys that are still accepted during rotation.
+pub async fn jwks(State(state): State<AppState>) -> Json<JwkSet> {
+    Json(state.jwt.jwks().clone())
+}
@@ -1,7 +1,12 @@
 use axum::extract::{Query, State};
+use axum::http::HeaderMap;
+use axum::response::Response;
 use axum::Json;
 use base64::Engine;
-use openconv_shared::api::message::{MessageHistoryQuery, MessageHistoryResponse, MessageResponse};
+use openconv_shared::api::message::{
+    HistoryBackfillFrame, HistoryBackfillRequest, MessageHistoryQuery, MessageHistoryResponse,
+    MessageResponse,
+};
 use openconv_shared::error::OpenConvError;
 use openconv_shared::ids::{ChannelId, MessageId, UserId};
 use openconv_shared::permissions::Permissions;
@@ -9,6 +14,7 @@ use openconv_shared::permissions::Permissions;
 use crate::error::ServerError;
 use crate::extractors::channel_member::ChannelMember;
 use crate::state::AppState;
+use crate::streaming::{stream_response, StreamFormat};
 
 fn db_err(e: sqlx::Error) -> ServerError {
     tracing::error!(error = %e, "database error");
@@ -61,37 +67,43 @@ pub async fn guild_messages(
 
     let limit = params.limit.unwrap_or(50).clamp(1, 100) as i64;
 
-    let rows = if let Some(ref cursor) = params.cursor {
-        let decoded = decode_cursor(cursor)?;
-        sqlx::query_as::<_, MessageRow>(
-            "SELECT id, channel_id, sender_id, encrypted_content, nonce, edited_at, created_at \
-             FROM messages \
-             WHERE channel_id = $1 AND deleted = false \
-               AND (created_at, id) < ($2, $3) \
-             ORDER BY created_at DESC, id DESC \
-             LIMIT $4",
-        )
-        .bind(channel_member.channel_id)
-        .bind(decoded.created_at)
-        .bind(decoded.id)
-        .bind(limit + 1)
-        .fetch_all(&state.db)
+    let cursor = params.cursor.as_deref().map(decode_cursor).transpose()?;
+    let cursor = cursor.as_ref();
+    let channel_id = channel_member.channel_id;
+
+    let rows = state
+        .read(|db| async move {
+            if let Some(cursor) = cursor {
+                sqlx::query_as::<_, MessageRow>(
+                    "SELECT id, channel_id, sender_id, encrypted_content, nonce, edited_at, created_at \
+                     FROM messages \
+                     WHERE channel_id = $1 AND deleted = false \
+                       AND (created_at, id) < ($2, $3) \
+                     ORDER BY created_at DESC, id DESC \
+                     LIMIT $4",
+                )
+                .bind(channel_id)
+                .bind(cursor.created_at)
+                .bind(cursor.id)
+                .bind(limit + 1)
+                .fetch_all(&db)
+                .await
+            } else {
+                sqlx::query_as::<_, MessageRow>(
+                    "SELECT id, channel_id, sender_id, encrypted_content, nonce, edited_at, created_at \
+                     FROM messages \
+                     WHERE channel_id = $1 AND deleted = false \
+                     ORDER BY created_at DESC, id DESC \
+                     LIMIT $2",
+                )
+                .bind(channel_id)
+                .bind(limit + 1)
+                .fetch_all(&db)
+                .await
+            }
+        })
         .await
-        .map_err(db_err)?
-    } else {
-        sqlx::query_as::<_, MessageRow>(
-            "SELECT id, channel_id, sender_id, encrypted_content, nonce, edited_at, created_at \
-             FROM messages \
-             WHERE channel_id = $1 AND deleted = false \
-             ORDER BY created_at DESC, id DESC \
-             LIMIT $2",
-        )
-        .bind(channel_member.channel_id)
-        .bind(limit + 1)
-        .fetch_all(&state.db)
-        .await
-        .map_err(db_err)?
-    };
+        .map_err(db_err)?;
 
     let has_more = rows.len() as i64 > limit;
     let msgs: Vec<MessageRow> = rows.into_iter().take(limit as usize).collect();
@@ -109,6 +121,142 @@ pub async fn guild_messages(
     }))
 }
 
+// ─── Bulk history backfill ──────────────────────────────────
+
+#[utoipa::path(post, path = "/api/channels/{channel_id}/history/backfill", tag = "Messages", security(("bearer_auth" = [])), params(("channel_id" = openconv_shared::ids::ChannelId, Path, description = "Channel ID")), request_body = openconv_shared::api::message::HistoryBackfillRequest, responses((status = 200, content_type = "application/x-ndjson", body = openconv_shared::api::message::HistoryBackfillFrame), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse), (status = 429, body = crate::error::ErrorResponse)))]
+/// POST /api/channels/:channel_id/history/backfill
+/// Streams channel history as NDJSON (or SSE with `Accept: text/event-stream`)
+/// for a newly linked device.
+///
+/// Batches are paced by `backfill.batch_interval_ms` and the stream stops
+/// after `backfill.max_batches_per_request`, ending with a checkpoint the
+/// client sends back to continue.
+pub async fn history_backfill(
+    State(state): State<AppState>,
+    channel_member: ChannelMember,
+    headers: HeaderMap,
+    Json(req): Json<HistoryBackfillRequest>,
+) -> Result<Response, ServerError> {
+    channel_member.require(Permissions::READ_MESSAGES)?;
+    let format = StreamFormat::from_headers(&headers);
+
+    // Validate the checkpoint up front so a bad one is a 400, not a stream error
+    let cursor = req.checkpoint.as_deref().map(decode_cursor).transpose()?;
+
+    let cfg = &state.config.backfill;
+    let stream_state = BackfillState {
+        db: state.read_pool(),
+        channel_id: channel_member.channel_id,
+        since: req.since,
+        cursor,
+        batch_size: cfg.batch_size.max(1) as i64,
+        max_batches: cfg.max_batches_per_request.max(1),
+        interval: std::time::Duration::from_millis(cfg.batch_interval_ms),
+        batches_sent: 0,
+        exhausted: false,
+        finished: false,
+    };
+
+    let frames = futures::stream::unfold(stream_state, next_backfill_frame);
+    Ok(stream_response(format, frames))
+}
+
+struct BackfillState {
+    db: sqlx::PgPool,
+    channel_id: ChannelId,
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    cursor: Option<CursorData>,
+    batch_size: i64,
+    max_batches: u32,
+    interval: std::time::Duration,
+    batches_sent: u32,
+    exhausted: bool,
+    finished: bool,
+}
+
+impl BackfillState {
+    fn checkpoint(&self) -> Option<String> {
+        self.cursor
+            .as_ref()
+            .map(|c| encode_cursor(c.created_at, c.id))
+    }
+}
+
+async fn next_backfill_frame(
+    mut st: BackfillState,
+) -> Option<(HistoryBackfillFrame, BackfillState)> {
+    if st.finished {
+        return None;
+    }
+
+    if st.exhausted || st.batches_sent >= st.max_batches {
+        st.finished = true;
+        let frame = HistoryBackfillFrame::Complete {
+            has_more: !st.exhausted,
+            checkpoint: st.checkpoint(),
+        };
+        return Some((frame, st));
+    }
+
+    if st.batches_sent > 0 && !st.interval.is_zero() {
+        tokio::time::sleep(st.interval).await;
+    }
+
+    let rows = sqlx::query_as::<_, MessageRow>(
+        "SELECT id, channel_id, sender_id, encrypted_content, nonce, edited_at, created_at \
+         FROM messages \
+         WHERE channel_id = $1 AND deleted = false \
+           AND ($2::timestamptz IS NULL OR (created_at, id) < ($2, $3)) \
+           AND ($4::timestamptz IS NULL OR created_at >= $4) \
+         ORDER BY created_at DESC, id DESC \
+         LIMIT $5",
+    )
+    .bind(st.channel_id)
+    .bind(st.cursor.as_ref().map(|c| c.created_at))
+    .bind(st.cursor.as_ref().map(|c| c.id))
+    .bind(st.since)
+    .bind(st.batch_size + 1)
+    .fetch_all(&st.db)
+    .await;
+
+    let rows = match rows {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::error!(error = %e, channel_id = %st.channel_id, "history backfill query failed");
+            st.finished = true;
+            let frame = HistoryBackfillFrame::Error {
+                error: "database error".into(),
+                checkpoint: st.checkpoint(),
+            };
+            return Some((frame, st));
+        }
+    };
+
+    st.exhausted = rows.len() as i64 <= st.batch_size;
+    let msgs: Vec<MessageRow> = rows.into_iter().take(st.batch_size as usize).collect();
+
+    let Some((last_created_at, last_id)) = msgs.last().map(|m| (m.created_at, m.id)) else {
+        st.finished = true;
+        let frame = HistoryBackfillFrame::Complete {
+            has_more: false,
+            checkpoint: st.checkpoint(),
+        };
+        return Some((frame, st));
+    };
+
+    st.cursor = Some(CursorData {
+        created_at: last_created_at,
+        id: last_id,
+    });
+    st.batches_sent += 1;
+
+    let frame = HistoryBackfillFrame::Batch {
+        messages: msgs.into_iter().map(|m| m.into_response()).collect(),
+        checkpoint: encode_cursor(last_created_at, last_id),
+    };
+    Some((frame, st))
+}
+
 // ─── Message edit/delete (WebSocket operation helpers) ───────
 
 /// Edit a message. Validates sender ownership and channel association.
@@ -211,6 +359,12 @@ pub fn guild_message_routes() -> axum::Router<AppState> {
     axum::Router::new().route("/", axum::routing::get(guild_messages))
 }
 
+/// Routes for bulk history transfer.
+/// Mounted at /api/channels/:channel_id/history with its own rate limit.
+pub fn history_routes() -> axum::Router<AppState> {
+    axum::Router::new().route("/backfill", axum::routing::post(history_backfill))
+}
+
 // ─── Internal row types ─────────────────────────────────────
 
 #[derive(sqlx::FromRow)]
@@ -301,4 +455,9 @@ mod tests {
     fn guild_message_routes_build_without_panic() {
         let _ = guild_message_routes();
     }
+
+    #[test]
+    fn history_routes_build_without_panic() {
+        let _ = history_routes();
+    }
 }
@@ -1,11 +1,28 @@
+pub mod admin;
 pub mod auth;
+pub mod automod;
+pub mod bots;
+pub mod bridges;
 pub mod channels;
+pub mod discovery;
 pub mod dm_channels;
+#[cfg(feature = "federation")]
+pub mod federation;
 pub mod files;
 pub mod guilds;
 pub mod health;
+pub mod images;
 pub mod invites;
+pub mod jwks;
 pub mod messages;
+pub mod moderation;
+pub mod oauth;
+pub mod policies;
+pub mod quotas;
 pub mod roles;
+pub mod settings;
+pub mod upload_sessions;
 pub mod users;
+pub mod voice;
+pub mod webhooks;
 pub mod ws;
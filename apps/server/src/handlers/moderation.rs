@@ -0,0 +1,422 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use openconv_shared::api::gateway::{MemberEvent, MemberEventKind};
+use openconv_shared::api::guild::{
+    BanMemberRequest, BanMemberResponse, InactivePruneJobListResponse, InactivePruneJobResponse,
+    InactivePruneRequest, InactivePruneResponse, PruneJobListResponse, PruneJobResponse,
+};
+use openconv_shared::error::OpenConvError;
+use openconv_shared::ids::{GuildId, UserId};
+use openconv_shared::permissions::Permissions;
+
+use crate::audit;
+use crate::error::ServerError;
+use crate::extractors::guild_member::GuildMember;
+use crate::handlers::guilds::{ensure_outranks, fetch_guild_owner};
+use crate::member_events;
+use crate::state::AppState;
+use crate::tasks::inactive_prune::count_inactive_members;
+
+fn db_err(e: sqlx::Error) -> ServerError {
+    tracing::error!(error = %e, "database error");
+    ServerError(OpenConvError::Internal("database error".into()))
+}
+
+const MAX_BAN_REASON_LEN: usize = 512;
+const MAX_PRUNE_DAYS: u32 = 365;
+
+#[utoipa::path(put, path = "/api/guilds/{guild_id}/bans/{user_id}", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID"), ("user_id" = openconv_shared::ids::UserId, Path, description = "User to ban")), request_body = openconv_shared::api::guild::BanMemberRequest, responses((status = 200, body = openconv_shared::api::guild::BanMemberResponse), (status = 403, body = crate::error::ErrorResponse), (status = 409, body = crate::error::ErrorResponse)))]
+/// Ban a user from the guild, removing their membership.
+///
+/// If the guild has `prune_on_ban` enabled, a background job is queued to
+/// tombstone the user's messages and delete their uploaded files.
+pub async fn ban_member(
+    member: GuildMember,
+    State(state): State<AppState>,
+    Path((_, target_user_id)): Path<(GuildId, UserId)>,
+    Json(body): Json<BanMemberRequest>,
+) -> Result<Json<BanMemberResponse>, ServerError> {
+    member.require(Permissions::BAN_MEMBERS)?;
+
+    if member.user_id == target_user_id {
+        return Err(ServerError(OpenConvError::Validation(
+            "Cannot ban yourself".into(),
+        )));
+    }
+
+    let reason = body
+        .reason
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty());
+    if reason
+        .as_ref()
+        .is_some_and(|r| r.len() > MAX_BAN_REASON_LEN)
+    {
+        return Err(ServerError(OpenConvError::Validation(format!(
+            "Ban reason must be at most {MAX_BAN_REASON_LEN} characters"
+        ))));
+    }
+
+    let owner_id = fetch_guild_owner(&state.db, member.guild_id).await?;
+    if target_user_id == owner_id {
+        return Err(ServerError(OpenConvError::Forbidden));
+    }
+    if member.user_id != owner_id {
+        ensure_outranks(&state.db, member.guild_id, member.user_id, target_user_id).await?;
+    }
+
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+
+    let inserted = sqlx::query(
+        "INSERT INTO guild_bans (guild_id, user_id, banned_by, reason) VALUES ($1, $2, $3, $4) \
+         ON CONFLICT (guild_id, user_id) DO NOTHING",
+    )
+    .bind(member.guild_id)
+    .bind(target_user_id)
+    .bind(member.user_id)
+    .bind(reason.as_deref())
+    .execute(&mut *tx)
+    .await
+    .map_err(db_err)?
+    .rows_affected();
+
+    if inserted == 0 {
+        return Err(ServerError(OpenConvError::Conflict(
+            "user is already banned".into(),
+        )));
+    }
+
+    sqlx::query("DELETE FROM guild_members WHERE user_id = $1 AND guild_id = $2")
+        .bind(target_user_id)
+        .bind(member.guild_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?;
+
+    audit::record(
+        &mut *tx,
+        member.guild_id,
+        Some(member.user_id),
+        audit::MEMBER_BAN,
+        Some(target_user_id),
+        serde_json::json!({ "reason": reason }),
+    )
+    .await
+    .map_err(db_err)?;
+
+    let prune_on_ban: bool = sqlx::query_scalar("SELECT prune_on_ban FROM guilds WHERE id = $1")
+        .bind(member.guild_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_err)?;
+
+    let prune_job_id = if prune_on_ban {
+        let job_id: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO member_prune_jobs (guild_id, user_id, requested_by) \
+             VALUES ($1, $2, $3) RETURNING id",
+        )
+        .bind(member.guild_id)
+        .bind(target_user_id)
+        .bind(member.user_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_err)?;
+
+        audit::record(
+            &mut *tx,
+            member.guild_id,
+            Some(member.user_id),
+            audit::MEMBER_PRUNE_QUEUED,
+            Some(target_user_id),
+            serde_json::json!({ "job_id": job_id }),
+        )
+        .await
+        .map_err(db_err)?;
+
+        Some(job_id)
+    } else {
+        None
+    };
+
+    tx.commit().await.map_err(db_err)?;
+
+    member_events::publish(
+        &state,
+        MemberEvent::new(member.guild_id, target_user_id, MemberEventKind::Ban)
+            .with_actor(member.user_id),
+    )
+    .await;
+
+    Ok(Json(BanMemberResponse {
+        user_id: target_user_id,
+        prune_job_id,
+    }))
+}
+
+#[utoipa::path(delete, path = "/api/guilds/{guild_id}/bans/{user_id}", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID"), ("user_id" = openconv_shared::ids::UserId, Path, description = "User to unban")), responses((status = 204), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// Lift a ban. The user can rejoin through a new invite.
+pub async fn unban_member(
+    member: GuildMember,
+    State(state): State<AppState>,
+    Path((_, target_user_id)): Path<(GuildId, UserId)>,
+) -> Result<StatusCode, ServerError> {
+    member.require(Permissions::BAN_MEMBERS)?;
+
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+
+    let removed = sqlx::query("DELETE FROM guild_bans WHERE guild_id = $1 AND user_id = $2")
+        .bind(member.guild_id)
+        .bind(target_user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?
+        .rows_affected();
+
+    if removed == 0 {
+        return Err(ServerError(OpenConvError::NotFound));
+    }
+
+    audit::record(
+        &mut *tx,
+        member.guild_id,
+        Some(member.user_id),
+        audit::MEMBER_UNBAN,
+        Some(target_user_id),
+        serde_json::json!({}),
+    )
+    .await
+    .map_err(db_err)?;
+
+    tx.commit().await.map_err(db_err)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(get, path = "/api/guilds/{guild_id}/prune-jobs", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), responses((status = 200, body = openconv_shared::api::guild::PruneJobListResponse), (status = 403, body = crate::error::ErrorResponse)))]
+/// List recent member prune jobs with their progress.
+pub async fn list_prune_jobs(
+    member: GuildMember,
+    State(state): State<AppState>,
+) -> Result<Json<PruneJobListResponse>, ServerError> {
+    member.require(Permissions::BAN_MEMBERS)?;
+
+    let rows = sqlx::query_as::<_, PruneJobRow>(
+        "SELECT id, user_id, requested_by, status, messages_tombstoned, files_deleted, \
+                error, created_at, completed_at \
+         FROM member_prune_jobs \
+         WHERE guild_id = $1 \
+         ORDER BY created_at DESC \
+         LIMIT 100",
+    )
+    .bind(member.guild_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(db_err)?;
+
+    Ok(Json(PruneJobListResponse {
+        jobs: rows.into_iter().map(PruneJobRow::into_response).collect(),
+    }))
+}
+
+#[utoipa::path(post, path = "/api/guilds/{guild_id}/prune", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), request_body = openconv_shared::api::guild::InactivePruneRequest, responses((status = 200, description = "Dry run", body = openconv_shared::api::guild::InactivePruneResponse), (status = 202, description = "Prune queued", body = openconv_shared::api::guild::InactivePruneResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse), (status = 409, body = crate::error::ErrorResponse)))]
+/// Remove members inactive for `days` days. Requires KICK_MEMBERS.
+///
+/// With `dry_run` only the matching member count is returned. Otherwise a
+/// background job is queued; only one prune per guild can be queued or running.
+pub async fn prune_inactive_members(
+    member: GuildMember,
+    State(state): State<AppState>,
+    Json(body): Json<InactivePruneRequest>,
+) -> Result<(StatusCode, Json<InactivePruneResponse>), ServerError> {
+    member.require(Permissions::KICK_MEMBERS)?;
+
+    if body.days == 0 || body.days > MAX_PRUNE_DAYS {
+        return Err(ServerError(OpenConvError::Validation(format!(
+            "days must be between 1 and {MAX_PRUNE_DAYS}"
+        ))));
+    }
+    let inactive_since = chrono::Utc::now() - chrono::Duration::days(i64::from(body.days));
+
+    if body.dry_run {
+        let member_count = count_inactive_members(&state.db, member.guild_id, inactive_since)
+            .await
+            .map_err(db_err)?;
+        return Ok((
+            StatusCode::OK,
+            Json(InactivePruneResponse {
+                member_count,
+                job_id: None,
+            }),
+        ));
+    }
+
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+
+    let member_count = count_inactive_members(&mut *tx, member.guild_id, inactive_since)
+        .await
+        .map_err(db_err)?;
+
+    let job_id: Option<uuid::Uuid> = sqlx::query_scalar(
+        "INSERT INTO inactive_prune_jobs (guild_id, requested_by, inactive_days, inactive_since) \
+         VALUES ($1, $2, $3, $4) \
+         ON CONFLICT (guild_id) WHERE status IN ('pending', 'running') DO NOTHING \
+         RETURNING id",
+    )
+    .bind(member.guild_id)
+    .bind(member.user_id)
+    .bind(body.days as i32)
+    .bind(inactive_since)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_err)?;
+
+    let Some(job_id) = job_id else {
+        return Err(ServerError(OpenConvError::Conflict(
+            "a prune is already in progress".into(),
+        )));
+    };
+
+    audit::record(
+        &mut *tx,
+        member.guild_id,
+        Some(member.user_id),
+        audit::INACTIVE_PRUNE_QUEUED,
+        None,
+        serde_json::json!({
+            "job_id": job_id,
+            "days": body.days,
+            "member_count": member_count,
+        }),
+    )
+    .await
+    .map_err(db_err)?;
+
+    tx.commit().await.map_err(db_err)?;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(InactivePruneResponse {
+            member_count,
+            job_id: Some(job_id),
+        }),
+    ))
+}
+
+#[utoipa::path(get, path = "/api/guilds/{guild_id}/prune", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), responses((status = 200, body = openconv_shared::api::guild::InactivePruneJobListResponse), (status = 403, body = crate::error::ErrorResponse)))]
+/// List recent inactive-member prunes with their progress.
+pub async fn list_inactive_prune_jobs(
+    member: GuildMember,
+    State(state): State<AppState>,
+) -> Result<Json<InactivePruneJobListResponse>, ServerError> {
+    member.require(Permissions::KICK_MEMBERS)?;
+
+    let rows = sqlx::query_as::<_, InactivePruneJobRow>(
+        "SELECT id, requested_by, inactive_days, status, members_removed, error, \
+                created_at, completed_at \
+         FROM inactive_prune_jobs \
+         WHERE guild_id = $1 \
+         ORDER BY created_at DESC \
+         LIMIT 100",
+    )
+    .bind(member.guild_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(db_err)?;
+
+    Ok(Json(InactivePruneJobListResponse {
+        jobs: rows
+            .into_iter()
+            .map(InactivePruneJobRow::into_response)
+            .collect(),
+    }))
+}
+
+/// Routes for guild bans. Mounted at /api/guilds/:guild_id/bans.
+pub fn ban_routes() -> axum::Router<AppState> {
+    axum::Router::new().route(
+        "/{user_id}",
+        axum::routing::put(ban_member).delete(unban_member),
+    )
+}
+
+/// Routes for prune job progress. Mounted at /api/guilds/:guild_id/prune-jobs.
+pub fn prune_job_routes() -> axum::Router<AppState> {
+    axum::Router::new().route("/", axum::routing::get(list_prune_jobs))
+}
+
+/// Routes for inactive-member prunes. Mounted at /api/guilds/:guild_id/prune.
+pub fn inactive_prune_routes() -> axum::Router<AppState> {
+    axum::Router::new().route(
+        "/",
+        axum::routing::get(list_inactive_prune_jobs).post(prune_inactive_members),
+    )
+}
+
+#[derive(sqlx::FromRow)]
+struct PruneJobRow {
+    id: uuid::Uuid,
+    user_id: UserId,
+    requested_by: Option<UserId>,
+    status: String,
+    messages_tombstoned: i64,
+    files_deleted: i64,
+    error: Option<String>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    completed_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl PruneJobRow {
+    fn into_response(self) -> PruneJobResponse {
+        PruneJobResponse {
+            id: self.id,
+            user_id: self.user_id,
+            requested_by: self.requested_by,
+            status: self.status,
+            messages_tombstoned: self.messages_tombstoned,
+            files_deleted: self.files_deleted,
+            error: self.error,
+            created_at: self.created_at,
+            completed_at: self.completed_at,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct InactivePruneJobRow {
+    id: uuid::Uuid,
+    requested_by: Option<UserId>,
+    inactive_days: i32,
+    status: String,
+    members_removed: i64,
+    error: Option<String>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    completed_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl InactivePruneJobRow {
+    fn into_response(self) -> InactivePruneJobResponse {
+        InactivePruneJobResponse {
+            id: self.id,
+            requested_by: self.requested_by,
+            inactive_days: self.inactive_days,
+            status: self.status,
+            members_removed: self.members_removed,
+            error: self.error,
+            created_at: self.created_at,
+            completed_at: self.completed_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_build_without_panic() {
+        let _ = ban_routes();
+        let _ = prune_job_routes();
+        let _ = inactive_prune_routes();
+    }
+}
@@ -0,0 +1,533 @@
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::{Form, Json};
+use openconv_shared::api::guild::GuildListResponse;
+use openconv_shared::api::oauth::{
+    AuthorizeInfoResponse, AuthorizeResponse, CreateOAuthClientRequest, OAuthClientListResponse,
+    OAuthClientResponse, OAuthConsentListResponse, OAuthScope, OAuthTokenResponse,
+};
+use openconv_shared::api::user::UserProfileResponse;
+use openconv_shared::error::OpenConvError;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ServerError;
+use crate::extractors::auth::AuthUser;
+use crate::extractors::oauth::OAuthUser;
+use crate::oauth::{self, StoredCode};
+use crate::repo::oauth as repo;
+use crate::secret_token;
+use crate::state::AppState;
+
+fn db_err(e: sqlx::Error) -> ServerError {
+    tracing::error!(error = %e, "database error");
+    ServerError(OpenConvError::Internal("database error".into()))
+}
+
+const MAX_CLIENTS_PER_USER: i64 = 25;
+
+/// Query (GET) or body (POST) of an authorization request, as the app
+/// sent it to the consent screen.
+#[derive(Debug, Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
+pub struct AuthorizeParams {
+    /// Must be `code`.
+    pub response_type: String,
+    pub client_id: uuid::Uuid,
+    pub redirect_uri: String,
+    /// Space-separated scopes.
+    pub scope: String,
+    pub state: Option<String>,
+    /// Base64url SHA-256 of the app's code verifier.
+    pub code_challenge: String,
+    /// Must be `S256`.
+    pub code_challenge_method: String,
+}
+
+/// Form body of POST /api/oauth/token.
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct TokenRequest {
+    /// `authorization_code` or `refresh_token`.
+    pub grant_type: String,
+    pub client_id: uuid::Uuid,
+    /// Required for confidential clients.
+    pub client_secret: Option<String>,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+    pub refresh_token: Option<String>,
+}
+
+/// RFC 6749 error body, returned by the token endpoint so standard OAuth
+/// libraries can read it.
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct OAuthErrorResponse {
+    pub error: &'static str,
+    pub error_description: String,
+}
+
+#[derive(Debug)]
+pub struct OAuthError {
+    status: StatusCode,
+    body: OAuthErrorResponse,
+}
+
+impl OAuthError {
+    fn new(status: StatusCode, error: &'static str, description: impl Into<String>) -> Self {
+        OAuthError {
+            status,
+            body: OAuthErrorResponse {
+                error,
+                error_description: description.into(),
+            },
+        }
+    }
+
+    fn invalid_request(description: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "invalid_request", description)
+    }
+
+    fn invalid_grant(description: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "invalid_grant", description)
+    }
+
+    fn invalid_client() -> Self {
+        Self::new(
+            StatusCode::UNAUTHORIZED,
+            "invalid_client",
+            "client authentication failed",
+        )
+    }
+
+    fn server_error() -> Self {
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "server_error",
+            "internal error",
+        )
+    }
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        (
+            self.status,
+            [(axum::http::header::CACHE_CONTROL, "no-store")],
+            Json(self.body),
+        )
+            .into_response()
+    }
+}
+
+#[utoipa::path(post, path = "/api/oauth/clients", tag = "OAuth", security(("bearer_auth" = [])), request_body = openconv_shared::api::oauth::CreateOAuthClientRequest, responses((status = 201, body = openconv_shared::api::oauth::OAuthClientResponse), (status = 400, body = crate::error::ErrorResponse), (status = 409, body = crate::error::ErrorResponse)))]
+/// Register a third-party app. The client secret is only returned here.
+pub async fn create_client(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Json(body): Json<CreateOAuthClientRequest>,
+) -> Result<(StatusCode, Json<OAuthClientResponse>), ServerError> {
+    auth.require_human()?;
+
+    let name = body.name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err(ServerError(OpenConvError::Validation(
+            "App name must be between 1 and 100 characters".into(),
+        )));
+    }
+    if body.redirect_uris.is_empty() || body.redirect_uris.len() > oauth::MAX_REDIRECT_URIS {
+        return Err(ServerError(OpenConvError::Validation(format!(
+            "Apps need between 1 and {} redirect URIs",
+            oauth::MAX_REDIRECT_URIS
+        ))));
+    }
+    for uri in &body.redirect_uris {
+        oauth::validate_redirect_uri(uri)?;
+    }
+
+    let count = repo::count_clients_for_owner(&state.db, auth.user_id)
+        .await
+        .map_err(db_err)?;
+    if count >= MAX_CLIENTS_PER_USER {
+        return Err(ServerError(OpenConvError::Conflict(format!(
+            "you already have {MAX_CLIENTS_PER_USER} apps"
+        ))));
+    }
+
+    let secret = body.confidential.then(secret_token::generate);
+    let client = repo::insert_client(
+        &state.db,
+        auth.user_id,
+        name,
+        &body.redirect_uris,
+        secret.as_deref().map(secret_token::hash).as_deref(),
+    )
+    .await
+    .map_err(db_err)?;
+
+    let mut response = client.into_response();
+    response.client_secret = secret;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[utoipa::path(get, path = "/api/oauth/clients", tag = "OAuth", security(("bearer_auth" = [])), responses((status = 200, body = openconv_shared::api::oauth::OAuthClientListResponse)))]
+/// List the apps the caller has registered.
+pub async fn list_clients(
+    auth: AuthUser,
+    State(state): State<AppState>,
+) -> Result<Json<OAuthClientListResponse>, ServerError> {
+    let clients = repo::list_clients_for_owner(&state.db, auth.user_id)
+        .await
+        .map_err(db_err)?;
+    Ok(Json(OAuthClientListResponse {
+        clients: clients
+            .into_iter()
+            .map(repo::Client::into_response)
+            .collect(),
+    }))
+}
+
+#[utoipa::path(delete, path = "/api/oauth/clients/{client_id}", tag = "OAuth", security(("bearer_auth" = [])), params(("client_id" = uuid::Uuid, Path, description = "Client ID")), responses((status = 204), (status = 404, body = crate::error::ErrorResponse)))]
+/// Delete an app. Every token issued to it stops working.
+pub async fn delete_client(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Path(client_id): Path<uuid::Uuid>,
+) -> Result<StatusCode, ServerError> {
+    let deleted = repo::delete_client(&state.db, auth.user_id, client_id)
+        .await
+        .map_err(db_err)?;
+    if !deleted {
+        return Err(ServerError(OpenConvError::NotFound));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Check an authorization request against the client's registration.
+async fn validate_authorize(
+    state: &AppState,
+    params: &AuthorizeParams,
+) -> Result<(repo::Client, Vec<OAuthScope>), ServerError> {
+    let invalid = |msg: &str| ServerError(OpenConvError::Validation(msg.into()));
+
+    if params.response_type != "code" {
+        return Err(invalid("response_type must be code"));
+    }
+    if params.code_challenge_method != "S256" {
+        return Err(invalid("code_challenge_method must be S256"));
+    }
+    if !oauth::is_valid_challenge(&params.code_challenge) {
+        return Err(invalid("code_challenge is not a valid S256 challenge"));
+    }
+    let client = repo::get_client(&state.db, params.client_id)
+        .await
+        .map_err(db_err)?
+        .ok_or_else(|| invalid("unknown client_id"))?;
+    if !client.redirect_uris.contains(&params.redirect_uri) {
+        return Err(invalid("redirect_uri is not registered for this client"));
+    }
+    let scopes = oauth::parse_scopes(&params.scope)?;
+    Ok((client, scopes))
+}
+
+#[utoipa::path(get, path = "/api/oauth/authorize", tag = "OAuth", security(("bearer_auth" = [])), params(AuthorizeParams), responses((status = 200, body = openconv_shared::api::oauth::AuthorizeInfoResponse), (status = 400, body = crate::error::ErrorResponse)))]
+/// Describe an authorization request for the consent screen.
+pub async fn authorize_info(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Query(params): Query<AuthorizeParams>,
+) -> Result<Json<AuthorizeInfoResponse>, ServerError> {
+    auth.require_human()?;
+    let (client, scopes) = validate_authorize(&state, &params).await?;
+
+    let granted = repo::consented_scopes(&state.db, auth.user_id, client.id)
+        .await
+        .map_err(db_err)?
+        .map(|stored| oauth::scopes_from_db(&stored))
+        .unwrap_or_default();
+
+    Ok(Json(AuthorizeInfoResponse {
+        client_id: client.id,
+        client_name: client.name,
+        already_granted: scopes.iter().all(|s| granted.contains(s)),
+        scopes,
+    }))
+}
+
+#[utoipa::path(post, path = "/api/oauth/authorize", tag = "OAuth", security(("bearer_auth" = [])), request_body = AuthorizeParams, responses((status = 200, body = openconv_shared::api::oauth::AuthorizeResponse), (status = 400, body = crate::error::ErrorResponse)))]
+/// Approve an authorization request. Records consent and returns the
+/// redirect carrying a single-use authorization code.
+pub async fn authorize(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Json(params): Json<AuthorizeParams>,
+) -> Result<Json<AuthorizeResponse>, ServerError> {
+    auth.require_human()?;
+    let (client, scopes) = validate_authorize(&state, &params).await?;
+
+    repo::upsert_consent(
+        &state.db,
+        auth.user_id,
+        client.id,
+        &oauth::scopes_to_db(&scopes),
+    )
+    .await
+    .map_err(db_err)?;
+
+    let code = oauth::issue_code(
+        &state.redis,
+        &StoredCode {
+            client_id: client.id,
+            user_id: auth.user_id,
+            redirect_uri: params.redirect_uri.clone(),
+            scopes,
+            code_challenge: params.code_challenge,
+        },
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!(error = %e, "failed to store authorization code");
+        ServerError(OpenConvError::Internal("code storage failed".into()))
+    })?;
+
+    Ok(Json(AuthorizeResponse {
+        redirect_to: oauth::redirect_with_code(
+            &params.redirect_uri,
+            &code,
+            params.state.as_deref(),
+        ),
+    }))
+}
+
+#[utoipa::path(post, path = "/api/oauth/token", tag = "OAuth", request_body(content = TokenRequest, content_type = "application/x-www-form-urlencoded"), responses((status = 200, body = openconv_shared::api::oauth::OAuthTokenResponse), (status = 400, body = OAuthErrorResponse), (status = 401, body = OAuthErrorResponse)))]
+/// Redeem an authorization code or refresh token for a new token pair.
+pub async fn token(
+    State(state): State<AppState>,
+    Form(req): Form<TokenRequest>,
+) -> Result<Response, OAuthError> {
+    let client = repo::get_client(&state.db, req.client_id)
+        .await
+        .map_err(|e| {
+            tracing::error!(error = %e, "database error");
+            OAuthError::server_error()
+        })?
+        .ok_or_else(OAuthError::invalid_client)?;
+    if let Some(expected) = &client.secret_hash {
+        let presented = req.client_secret.as_deref().map(secret_token::hash);
+        if presented.as_ref() != Some(expected) {
+            return Err(OAuthError::invalid_client());
+        }
+    }
+
+    let (user_id, scopes) = match req.grant_type.as_str() {
+        "authorization_code" => {
+            let (Some(code), Some(redirect_uri), Some(verifier)) =
+                (&req.code, &req.redirect_uri, &req.code_verifier)
+            else {
+                return Err(OAuthError::invalid_request(
+                    "code, redirect_uri and code_verifier are required",
+                ));
+            };
+            let stored = oauth::take_code(&state.redis, code)
+                .await
+                .map_err(|e| {
+                    tracing::error!(error = %e, "failed to read authorization code");
+                    OAuthError::server_error()
+                })?
+                .ok_or_else(|| OAuthError::invalid_grant("unknown or expired code"))?;
+            if stored.client_id != client.id || &stored.redirect_uri != redirect_uri {
+                return Err(OAuthError::invalid_grant(
+                    "code was issued to another client or redirect_uri",
+                ));
+            }
+            if !oauth::verify_pkce(verifier, &stored.code_challenge) {
+                return Err(OAuthError::invalid_grant("code_verifier does not match"));
+            }
+            (stored.user_id, stored.scopes)
+        }
+        "refresh_token" => {
+            let Some(refresh) = &req.refresh_token else {
+                return Err(OAuthError::invalid_request("refresh_token is required"));
+            };
+            let mut tx = state.db.begin().await.map_err(|e| {
+                tracing::error!(error = %e, "database error");
+                OAuthError::server_error()
+            })?;
+            let grant = repo::take_refresh_token(&mut tx, &secret_token::hash(refresh))
+                .await
+                .map_err(|e| {
+                    tracing::error!(error = %e, "database error");
+                    OAuthError::server_error()
+                })?
+                .ok_or_else(|| OAuthError::invalid_grant("unknown or expired refresh token"))?;
+            if grant.client_id != client.id {
+                return Err(OAuthError::invalid_grant(
+                    "refresh token was issued to another client",
+                ));
+            }
+            tx.commit().await.map_err(|e| {
+                tracing::error!(error = %e, "database error");
+                OAuthError::server_error()
+            })?;
+            (grant.user_id, oauth::scopes_from_db(&grant.scopes))
+        }
+        _ => {
+            return Err(OAuthError::new(
+                StatusCode::BAD_REQUEST,
+                "unsupported_grant_type",
+                "grant_type must be authorization_code or refresh_token",
+            ))
+        }
+    };
+
+    if crate::revocation::is_user_suspended(&state.redis, user_id).await {
+        return Err(OAuthError::invalid_grant("user is suspended"));
+    }
+
+    let access_token = secret_token::generate();
+    let refresh_token = secret_token::generate();
+    let mut conn = state.db.acquire().await.map_err(|e| {
+        tracing::error!(error = %e, "database error");
+        OAuthError::server_error()
+    })?;
+    repo::insert_grant(
+        &mut conn,
+        &repo::NewGrant {
+            client_id: client.id,
+            user_id,
+            scopes: &oauth::scopes_to_db(&scopes),
+            access_hash: &secret_token::hash(&access_token),
+            refresh_hash: &secret_token::hash(&refresh_token),
+        },
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!(error = %e, "database error");
+        OAuthError::server_error()
+    })?;
+
+    Ok((
+        [(axum::http::header::CACHE_CONTROL, "no-store")],
+        Json(OAuthTokenResponse {
+            access_token,
+            token_type: "Bearer".into(),
+            expires_in: oauth::ACCESS_TOKEN_TTL_SECS as u64,
+            refresh_token,
+            scope: oauth::format_scopes(&scopes),
+        }),
+    )
+        .into_response())
+}
+
+#[utoipa::path(get, path = "/api/oauth/@me", tag = "OAuth", security(("oauth" = ["identify"])), responses((status = 200, body = openconv_shared::api::user::UserProfileResponse), (status = 401, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse)))]
+/// The authorizing user's public profile. Requires the `identify` scope.
+pub async fn oauth_me(
+    user: OAuthUser,
+    State(state): State<AppState>,
+) -> Result<Json<UserProfileResponse>, ServerError> {
+    user.require(OAuthScope::Identify)?;
+
+    let (display_name, avatar_url): (String, Option<String>) =
+        sqlx::query_as("SELECT display_name, avatar_url FROM users WHERE id = $1")
+            .bind(user.user_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(db_err)?
+            .ok_or(ServerError(OpenConvError::NotFound))?;
+
+    Ok(Json(UserProfileResponse {
+        id: user.user_id,
+        display_name,
+        avatar_url,
+    }))
+}
+
+#[utoipa::path(get, path = "/api/oauth/@me/guilds", tag = "OAuth", security(("oauth" = ["guilds.read"])), responses((status = 200, body = openconv_shared::api::guild::GuildListResponse), (status = 401, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse)))]
+/// The guilds the authorizing user belongs to. Requires `guilds.read`.
+pub async fn oauth_guilds(
+    user: OAuthUser,
+    State(state): State<AppState>,
+) -> Result<Json<GuildListResponse>, ServerError> {
+    user.require(OAuthScope::GuildsRead)?;
+
+    let guilds = crate::repo::guilds::list_for_member(&state.db, user.user_id)
+        .await
+        .map_err(db_err)?
+        .into_iter()
+        .map(|g| g.into_response(None))
+        .collect();
+    Ok(Json(GuildListResponse { guilds }))
+}
+
+#[utoipa::path(get, path = "/api/oauth/consents", tag = "OAuth", security(("bearer_auth" = [])), responses((status = 200, body = openconv_shared::api::oauth::OAuthConsentListResponse)))]
+/// List the apps the caller has authorized.
+pub async fn list_consents(
+    auth: AuthUser,
+    State(state): State<AppState>,
+) -> Result<Json<OAuthConsentListResponse>, ServerError> {
+    let consents = repo::list_consents(&state.db, auth.user_id)
+        .await
+        .map_err(db_err)?;
+    Ok(Json(OAuthConsentListResponse { consents }))
+}
+
+#[utoipa::path(delete, path = "/api/oauth/consents/{client_id}", tag = "OAuth", security(("bearer_auth" = [])), params(("client_id" = uuid::Uuid, Path, description = "Client ID")), responses((status = 204), (status = 404, body = crate::error::ErrorResponse)))]
+/// Deauthorize an app. Its tokens for the caller stop working.
+pub async fn revoke_consent(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Path(client_id): Path<uuid::Uuid>,
+) -> Result<StatusCode, ServerError> {
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+    let revoked = repo::revoke_consent(&mut tx, auth.user_id, client_id)
+        .await
+        .map_err(db_err)?;
+    tx.commit().await.map_err(db_err)?;
+    if !revoked {
+        return Err(ServerError(OpenConvError::NotFound));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Routes for the OAuth server. Mounted at /api/oauth; the router adds the
+/// rate-limited `/token` endpoint.
+pub fn routes() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route(
+            "/clients",
+            axum::routing::get(list_clients).post(create_client),
+        )
+        .route("/clients/{client_id}", axum::routing::delete(delete_client))
+        .route(
+            "/authorize",
+            axum::routing::get(authorize_info).post(authorize),
+        )
+        .route("/@me", axum::routing::get(oauth_me))
+        .route("/@me/guilds", axum::routing::get(oauth_guilds))
+        .route("/consents", axum::routing::get(list_consents))
+        .route(
+            "/consents/{client_id}",
+            axum::routing::delete(revoke_consent),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_build_without_panic() {
+        let _ = routes();
+    }
+
+    #[tokio::test]
+    async fn oauth_errors_use_rfc_6749_body() {
+        let resp = OAuthError::invalid_grant("nope").into_response();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(resp.headers()["cache-control"], "no-store");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"], "invalid_grant");
+        assert_eq!(json["error_description"], "nope");
+    }
+}
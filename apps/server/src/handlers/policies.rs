@@ -0,0 +1,218 @@
+use axum::extract::State;
+use axum::Json;
+use chrono::{DateTime, Utc};
+use openconv_shared::api::policy::{AcceptPolicyRequest, PolicyStatusResponse};
+use openconv_shared::error::OpenConvError;
+use openconv_shared::ids::UserId;
+
+use crate::config::PolicyConfig;
+use crate::error::ServerError;
+use crate::extractors::auth::AuthUser;
+use crate::state::AppState;
+
+fn db_err(e: sqlx::Error) -> ServerError {
+    tracing::error!(error = %e, "database error");
+    ServerError(OpenConvError::Internal("database error".into()))
+}
+
+const DOCUMENT_TERMS: &str = "terms";
+const DOCUMENT_PRIVACY: &str = "privacy";
+
+/// Whether a published version still has to be accepted.
+fn update_required(published: Option<&str>, accepted: Option<&str>) -> bool {
+    published.is_some_and(|p| accepted != Some(p))
+}
+
+/// The most recent acceptance of each document by a user.
+#[derive(Default)]
+struct Acceptances {
+    terms: Option<AcceptanceRow>,
+    privacy: Option<AcceptanceRow>,
+}
+
+impl Acceptances {
+    fn update_required(&self, config: &PolicyConfig) -> bool {
+        update_required(
+            config.terms_version.as_deref(),
+            self.terms.as_ref().map(|a| a.version.as_str()),
+        ) || update_required(
+            config.privacy_version.as_deref(),
+            self.privacy.as_ref().map(|a| a.version.as_str()),
+        )
+    }
+}
+
+async fn load_acceptances<'e, E>(executor: E, user_id: UserId) -> Result<Acceptances, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let rows = sqlx::query_as::<_, AcceptanceRow>(
+        "SELECT DISTINCT ON (document) document, version, accepted_at \
+         FROM policy_acceptances WHERE user_id = $1 \
+         ORDER BY document, accepted_at DESC",
+    )
+    .bind(user_id)
+    .fetch_all(executor)
+    .await?;
+
+    let mut acceptances = Acceptances::default();
+    for row in rows {
+        match row.document.as_str() {
+            DOCUMENT_TERMS => acceptances.terms = Some(row),
+            DOCUMENT_PRIVACY => acceptances.privacy = Some(row),
+            _ => {}
+        }
+    }
+    Ok(acceptances)
+}
+
+/// Whether `user_id` has not yet accepted a published policy version.
+/// Always false when the instance publishes no versions.
+pub async fn policy_update_required(
+    db: &sqlx::PgPool,
+    config: &PolicyConfig,
+    user_id: UserId,
+) -> Result<bool, sqlx::Error> {
+    if !config.is_enabled() {
+        return Ok(false);
+    }
+    Ok(load_acceptances(db, user_id).await?.update_required(config))
+}
+
+fn status_response(config: &PolicyConfig, acceptances: Acceptances) -> PolicyStatusResponse {
+    let policy_update_required = acceptances.update_required(config);
+    let (accepted_terms_version, accepted_terms_at) = acceptances
+        .terms
+        .map(|a| (Some(a.version), Some(a.accepted_at)))
+        .unwrap_or_default();
+    let (accepted_privacy_version, accepted_privacy_at) = acceptances
+        .privacy
+        .map(|a| (Some(a.version), Some(a.accepted_at)))
+        .unwrap_or_default();
+    PolicyStatusResponse {
+        terms_version: config.terms_version.clone(),
+        privacy_version: config.privacy_version.clone(),
+        accepted_terms_version,
+        accepted_terms_at,
+        accepted_privacy_version,
+        accepted_privacy_at,
+        policy_update_required,
+    }
+}
+
+/// Check that an accepted version is the published one.
+fn check_version(
+    document: &str,
+    published: Option<&str>,
+    accepted: &str,
+) -> Result<(), ServerError> {
+    match published {
+        Some(p) if p == accepted => Ok(()),
+        Some(_) => Err(ServerError(OpenConvError::Validation(format!(
+            "{document}_version does not match the published version"
+        )))),
+        None => Err(ServerError(OpenConvError::Validation(format!(
+            "no {document} version is published"
+        )))),
+    }
+}
+
+#[utoipa::path(get, path = "/api/policies", tag = "Policies", security(("bearer_auth" = [])), responses((status = 200, body = openconv_shared::api::policy::PolicyStatusResponse), (status = 401, body = crate::error::ErrorResponse)))]
+/// Get the published policy versions and the caller's acceptance state.
+pub async fn get_policy_status(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<PolicyStatusResponse>, ServerError> {
+    let acceptances = load_acceptances(&state.db, auth.user_id)
+        .await
+        .map_err(db_err)?;
+    Ok(Json(status_response(&state.config.policy, acceptances)))
+}
+
+#[utoipa::path(post, path = "/api/policies/accept", tag = "Policies", security(("bearer_auth" = [])), request_body = openconv_shared::api::policy::AcceptPolicyRequest, responses((status = 200, body = openconv_shared::api::policy::PolicyStatusResponse), (status = 400, body = crate::error::ErrorResponse), (status = 401, body = crate::error::ErrorResponse)))]
+/// Record that the caller accepted the published terms and/or privacy policy.
+///
+/// Only the currently published version of a document can be accepted;
+/// clients should re-fetch `GET /api/policies` when this fails.
+pub async fn accept_policies(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(body): Json<AcceptPolicyRequest>,
+) -> Result<Json<PolicyStatusResponse>, ServerError> {
+    let config = &state.config.policy;
+
+    let mut accepted = Vec::new();
+    if let Some(version) = body.terms_version.as_deref() {
+        check_version(DOCUMENT_TERMS, config.terms_version.as_deref(), version)?;
+        accepted.push((DOCUMENT_TERMS, version));
+    }
+    if let Some(version) = body.privacy_version.as_deref() {
+        check_version(DOCUMENT_PRIVACY, config.privacy_version.as_deref(), version)?;
+        accepted.push((DOCUMENT_PRIVACY, version));
+    }
+    if accepted.is_empty() {
+        return Err(ServerError(OpenConvError::Validation(
+            "At least one policy version must be accepted".into(),
+        )));
+    }
+
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+    for (document, version) in accepted {
+        sqlx::query(
+            "INSERT INTO policy_acceptances (user_id, document, version) VALUES ($1, $2, $3) \
+             ON CONFLICT (user_id, document, version) DO UPDATE SET accepted_at = NOW()",
+        )
+        .bind(auth.user_id)
+        .bind(document)
+        .bind(version)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?;
+    }
+    let acceptances = load_acceptances(&mut *tx, auth.user_id)
+        .await
+        .map_err(db_err)?;
+    tx.commit().await.map_err(db_err)?;
+
+    Ok(Json(status_response(config, acceptances)))
+}
+
+/// Policy acceptance routes. Mounted at /api/policies.
+pub fn routes() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route("/", axum::routing::get(get_policy_status))
+        .route("/accept", axum::routing::post(accept_policies))
+}
+
+#[derive(sqlx::FromRow)]
+struct AcceptanceRow {
+    document: String,
+    version: String,
+    accepted_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_build_without_panic() {
+        let _ = routes();
+    }
+
+    #[test]
+    fn update_required_only_for_unaccepted_published_versions() {
+        assert!(!update_required(None, None));
+        assert!(!update_required(None, Some("1")));
+        assert!(update_required(Some("2"), None));
+        assert!(update_required(Some("2"), Some("1")));
+        assert!(!update_required(Some("2"), Some("2")));
+    }
+
+    #[test]
+    fn check_version_rejects_stale_and_unpublished_versions() {
+        assert!(check_version(DOCUMENT_TERMS, Some("2"), "2").is_ok());
+        assert!(check_version(DOCUMENT_TERMS, Some("2"), "1").is_err());
+        assert!(check_version(DOCUMENT_PRIVACY, None, "1").is_err());
+    }
+}
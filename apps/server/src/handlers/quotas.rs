@@ -0,0 +1,185 @@
+use axum::extract::State;
+use axum::Json;
+use openconv_shared::api::file::StorageUsageResponse;
+use openconv_shared::error::OpenConvError;
+use openconv_shared::ids::{GuildId, UserId};
+
+use crate::config::FileStorageConfig;
+use crate::error::ServerError;
+use crate::extractors::auth::AuthUser;
+use crate::extractors::guild_member::GuildMember;
+use crate::state::AppState;
+
+fn db_err(e: sqlx::Error) -> ServerError {
+    tracing::error!(error = %e, "database error");
+    ServerError(OpenConvError::Internal("database error".into()))
+}
+
+#[derive(sqlx::FromRow)]
+struct UsageRow {
+    used_bytes: i64,
+    file_count: i64,
+}
+
+async fn user_usage<'e, E>(executor: E, user_id: UserId) -> Result<UsageRow, ServerError>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    sqlx::query_as::<_, UsageRow>(
+        "SELECT COALESCE(SUM(size_bytes), 0)::BIGINT AS used_bytes, COUNT(*) AS file_count \
+         FROM files WHERE uploader_id = $1",
+    )
+    .bind(user_id)
+    .fetch_one(executor)
+    .await
+    .map_err(db_err)
+}
+
+async fn guild_usage<'e, E>(executor: E, guild_id: GuildId) -> Result<UsageRow, ServerError>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    sqlx::query_as::<_, UsageRow>(
+        "SELECT COALESCE(SUM(size_bytes), 0)::BIGINT AS used_bytes, COUNT(*) AS file_count \
+         FROM files WHERE guild_id = $1",
+    )
+    .bind(guild_id)
+    .fetch_one(executor)
+    .await
+    .map_err(db_err)
+}
+
+/// Error returned when a file would not fit in the remaining quota.
+fn quota_exceeded(owner: &str, used: i64, quota: u64, size_bytes: i64) -> ServerError {
+    ServerError(OpenConvError::PayloadTooLarge(format!(
+        "{owner} storage quota exceeded: {used} of {quota} bytes used, file needs {size_bytes}"
+    )))
+}
+
+fn fits(used: i64, size_bytes: i64, quota: u64) -> bool {
+    used.saturating_add(size_bytes) as u64 <= quota
+}
+
+/// Fail unless `size_bytes` more fits in the uploader's and guild's quotas.
+///
+/// Locks the user (and guild) row for the rest of `tx`, so concurrent
+/// uploads are checked one at a time. Call right before inserting the file.
+pub(crate) async fn reserve(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    config: &FileStorageConfig,
+    uploader_id: UserId,
+    guild_id: Option<GuildId>,
+    size_bytes: i64,
+) -> Result<(), ServerError> {
+    if let Some(quota) = config.user_quota_bytes {
+        sqlx::query("SELECT 1 FROM users WHERE id = $1 FOR UPDATE")
+            .bind(uploader_id)
+            .execute(&mut **tx)
+            .await
+            .map_err(db_err)?;
+        let usage = user_usage(&mut **tx, uploader_id).await?;
+        if !fits(usage.used_bytes, size_bytes, quota) {
+            return Err(quota_exceeded("user", usage.used_bytes, quota, size_bytes));
+        }
+    }
+    if let (Some(quota), Some(guild_id)) = (config.guild_quota_bytes, guild_id) {
+        sqlx::query("SELECT 1 FROM guilds WHERE id = $1 FOR UPDATE")
+            .bind(guild_id)
+            .execute(&mut **tx)
+            .await
+            .map_err(db_err)?;
+        let usage = guild_usage(&mut **tx, guild_id).await?;
+        if !fits(usage.used_bytes, size_bytes, quota) {
+            return Err(quota_exceeded("guild", usage.used_bytes, quota, size_bytes));
+        }
+    }
+    Ok(())
+}
+
+/// Cheap check before accepting an upload body: fail if the uploader is
+/// already over quota, or if the declared size cannot fit. [`reserve`] still
+/// does the authoritative check once the size is known.
+pub(crate) async fn precheck(
+    db: &sqlx::PgPool,
+    config: &FileStorageConfig,
+    uploader_id: UserId,
+    guild_id: Option<GuildId>,
+    size_bytes: i64,
+) -> Result<(), ServerError> {
+    if let Some(quota) = config.user_quota_bytes {
+        let usage = user_usage(db, uploader_id).await?;
+        if !fits(usage.used_bytes, size_bytes, quota) {
+            return Err(quota_exceeded("user", usage.used_bytes, quota, size_bytes));
+        }
+    }
+    if let (Some(quota), Some(guild_id)) = (config.guild_quota_bytes, guild_id) {
+        let usage = guild_usage(db, guild_id).await?;
+        if !fits(usage.used_bytes, size_bytes, quota) {
+            return Err(quota_exceeded("guild", usage.used_bytes, quota, size_bytes));
+        }
+    }
+    Ok(())
+}
+
+fn usage_response(usage: UsageRow, quota: Option<u64>) -> StorageUsageResponse {
+    StorageUsageResponse {
+        used_bytes: usage.used_bytes,
+        file_count: usage.file_count,
+        quota_bytes: quota.map(|q| q as i64),
+    }
+}
+
+#[utoipa::path(get, path = "/api/users/me/storage", tag = "Users", security(("bearer_auth" = [])), responses((status = 200, body = openconv_shared::api::file::StorageUsageResponse), (status = 401, body = crate::error::ErrorResponse)))]
+/// Storage used by the caller's uploads, and their quota.
+pub async fn user_storage(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<StorageUsageResponse>, ServerError> {
+    let usage = user_usage(&state.db, auth.user_id).await?;
+    Ok(Json(usage_response(
+        usage,
+        state.config.file_storage.user_quota_bytes,
+    )))
+}
+
+#[utoipa::path(get, path = "/api/guilds/{guild_id}/storage", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), responses((status = 200, body = openconv_shared::api::file::StorageUsageResponse), (status = 403, body = crate::error::ErrorResponse)))]
+/// Storage used by files uploaded to the guild's channels, and its quota.
+/// Any member can view it.
+pub async fn guild_storage(
+    member: GuildMember,
+    State(state): State<AppState>,
+) -> Result<Json<StorageUsageResponse>, ServerError> {
+    let usage = guild_usage(&state.db, member.guild_id).await?;
+    Ok(Json(usage_response(
+        usage,
+        state.config.file_storage.guild_quota_bytes,
+    )))
+}
+
+/// Storage usage route for the current user. Mounted at /api/users.
+pub fn user_routes() -> axum::Router<AppState> {
+    axum::Router::new().route("/me/storage", axum::routing::get(user_storage))
+}
+
+/// Storage usage route for guilds. Mounted at /api/guilds.
+pub fn guild_routes() -> axum::Router<AppState> {
+    axum::Router::new().route("/{guild_id}/storage", axum::routing::get(guild_storage))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_build_without_panic() {
+        let _ = user_routes();
+        let _ = guild_routes();
+    }
+
+    #[test]
+    fn file_fits_up_to_the_quota() {
+        assert!(fits(0, 100, 100));
+        assert!(fits(60, 40, 100));
+        assert!(!fits(60, 41, 100));
+    }
+}
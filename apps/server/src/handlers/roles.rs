@@ -1,13 +1,22 @@
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::Response;
 use axum::Json;
-use openconv_shared::api::role::{CreateRoleRequest, RoleResponse, UpdateRoleRequest};
-use openconv_shared::error::OpenConvError;
+use openconv_shared::api::gateway::{MemberEvent, MemberEventKind};
+use openconv_shared::api::role::{
+    CreateRoleRequest, PermissionPreviewQuery, PermissionPreviewResponse, RoleResponse,
+    UpdateRoleRequest,
+};
+use openconv_shared::error::{ErrorMessage, OpenConvError};
 use openconv_shared::ids::{GuildId, RoleId, UserId};
-use openconv_shared::permissions::Permissions;
+use openconv_shared::permissions::{self, Permissions};
 
 use crate::error::ServerError;
+use crate::etag::json_with_etag;
 use crate::extractors::guild_member::GuildMember;
+use crate::member_events;
+use crate::permission_cache;
+use crate::repo;
 use crate::state::AppState;
 
 fn db_err(e: sqlx::Error) -> ServerError {
@@ -29,17 +38,9 @@ async fn actor_highest_position(
     user_id: UserId,
     guild_id: GuildId,
 ) -> Result<i32, ServerError> {
-    let pos: Option<i32> = sqlx::query_scalar(
-        "SELECT MAX(r.position) FROM guild_member_roles gmr \
-         JOIN roles r ON r.id = gmr.role_id \
-         WHERE gmr.user_id = $1 AND gmr.guild_id = $2",
-    )
-    .bind(user_id)
-    .bind(guild_id)
-    .fetch_one(db)
-    .await
-    .map_err(db_err)?;
-    Ok(pos.unwrap_or(0))
+    repo::guilds::highest_role_position(db, guild_id, user_id)
+        .await
+        .map_err(db_err)
 }
 
 /// Check if the actor is the guild owner.
@@ -48,14 +49,17 @@ async fn is_guild_owner(
     user_id: UserId,
     guild_id: GuildId,
 ) -> Result<bool, ServerError> {
-    let owner_id: Option<UserId> = sqlx::query_scalar("SELECT owner_id FROM guilds WHERE id = $1")
-        .bind(guild_id)
-        .fetch_optional(db)
-        .await
-        .map_err(db_err)?;
+    let owner_id = repo::guilds::owner(db, guild_id).await.map_err(db_err)?;
     Ok(owner_id == Some(user_id))
 }
 
+/// Drop cached permissions for every member of the guild, after a role's
+/// permissions changed or a role was deleted.
+async fn invalidate_guild_permissions(state: &AppState, guild_id: GuildId) {
+    permission_cache::invalidate_guild(&state.redis, guild_id).await;
+    state.ws.permission_cache.invalidate_guild(guild_id);
+}
+
 /// Validates that the actor can modify the target role.
 fn check_role_hierarchy(
     actor_highest_position: i32,
@@ -99,9 +103,14 @@ pub async fn create_role(
 
     let name = body.name.trim().to_string();
     if name.is_empty() || name.len() > 100 {
-        return Err(ServerError(OpenConvError::Validation(
-            "Role name must be between 1 and 100 characters".into(),
-        )));
+        return Err(ServerError(
+            OpenConvError::Validation("Role name must be between 1 and 100 characters".into())
+                .with_message(
+                    ErrorMessage::new("role_name.length")
+                        .param("min", 1)
+                        .param("max", 100),
+                ),
+        ));
     }
 
     let new_perms = Permissions::from_bits_truncate(body.permissions);
@@ -110,75 +119,46 @@ pub async fn create_role(
 
     let mut tx = state.db.begin().await.map_err(db_err)?;
 
-    // Shift existing custom roles at position >= 2 up by 1.
-    // Two-step approach to avoid UNIQUE constraint violation:
-    // 1. Negate positions (guaranteed unique since all originals are positive)
-    // 2. Set to -(negated) + 1 = original + 1
-    sqlx::query(
-        "UPDATE roles SET position = -position \
-         WHERE guild_id = $1 AND position >= 2 AND role_type = 'custom'",
-    )
-    .bind(guild_id)
-    .execute(&mut *tx)
-    .await
-    .map_err(db_err)?;
-
-    sqlx::query(
-        "UPDATE roles SET position = -position + 1 \
-         WHERE guild_id = $1 AND position < 0",
-    )
-    .bind(guild_id)
-    .execute(&mut *tx)
-    .await
-    .map_err(db_err)?;
+    // Shift existing custom roles at position >= 2 up by 1
+    repo::roles::shift_custom_up(&mut tx, guild_id)
+        .await
+        .map_err(db_err)?;
 
     // Store only known permission bits (truncated value)
     let stored_perms = new_perms.bits() as i64;
 
     // Insert new role at position 2
-    let row = sqlx::query_as::<_, RoleRow>(
-        "INSERT INTO roles (id, guild_id, name, permissions, position, role_type) \
-         VALUES ($1, $2, $3, $4, 2, 'custom') \
-         RETURNING id, guild_id, name, permissions, position, role_type, created_at",
-    )
-    .bind(RoleId::new())
-    .bind(guild_id)
-    .bind(&name)
-    .bind(stored_perms)
-    .fetch_one(&mut *tx)
-    .await
-    .map_err(|e| {
-        if is_unique_violation(&e) {
-            ServerError(OpenConvError::Conflict(
-                "A role with that position already exists".into(),
-            ))
-        } else {
-            db_err(e)
-        }
-    })?;
+    let row = repo::roles::insert_custom(&mut *tx, RoleId::new(), guild_id, &name, stored_perms)
+        .await
+        .map_err(|e| {
+            if is_unique_violation(&e) {
+                ServerError(OpenConvError::Conflict(
+                    "A role with that position already exists".into(),
+                ))
+            } else {
+                db_err(e)
+            }
+        })?;
 
     tx.commit().await.map_err(db_err)?;
 
     Ok((StatusCode::CREATED, Json(row.into_response())))
 }
 
-#[utoipa::path(get, path = "/api/guilds/{guild_id}/roles", tag = "Roles", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), responses((status = 200, body = Vec<openconv_shared::api::role::RoleResponse>)))]
+#[utoipa::path(get, path = "/api/guilds/{guild_id}/roles", tag = "Roles", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), responses((status = 200, body = Vec<openconv_shared::api::role::RoleResponse>), (status = 304, description = "Not modified since the ETag in If-None-Match")))]
 /// List all roles in the guild, ordered by position ascending.
 pub async fn list_roles(
     State(state): State<AppState>,
     _guild_member: GuildMember,
     Path(guild_id): Path<GuildId>,
-) -> Result<Json<Vec<RoleResponse>>, ServerError> {
-    let rows = sqlx::query_as::<_, RoleRow>(
-        "SELECT id, guild_id, name, permissions, position, role_type, created_at \
-         FROM roles WHERE guild_id = $1 ORDER BY position ASC",
-    )
-    .bind(guild_id)
-    .fetch_all(&state.db)
-    .await
-    .map_err(db_err)?;
+    headers: HeaderMap,
+) -> Result<Response, ServerError> {
+    let rows = repo::roles::list(&state.db, guild_id)
+        .await
+        .map_err(db_err)?;
 
-    Ok(Json(rows.into_iter().map(|r| r.into_response()).collect()))
+    let roles: Vec<RoleResponse> = rows.into_iter().map(|r| r.into_response()).collect();
+    Ok(json_with_etag(&headers, &roles))
 }
 
 #[utoipa::path(patch, path = "/api/guilds/{guild_id}/roles/{role_id}", tag = "Roles", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID"), ("role_id" = openconv_shared::ids::RoleId, Path, description = "Role ID")), request_body = openconv_shared::api::role::UpdateRoleRequest, responses((status = 200, body = openconv_shared::api::role::RoleResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse)))]
@@ -200,23 +180,22 @@ pub async fn update_role(
     if let Some(ref name) = body.name {
         let trimmed = name.trim();
         if trimmed.is_empty() || trimmed.len() > 100 {
-            return Err(ServerError(OpenConvError::Validation(
-                "Role name must be between 1 and 100 characters".into(),
-            )));
+            return Err(ServerError(
+                OpenConvError::Validation("Role name must be between 1 and 100 characters".into())
+                    .with_message(
+                        ErrorMessage::new("role_name.length")
+                            .param("min", 1)
+                            .param("max", 100),
+                    ),
+            ));
         }
     }
 
     // Fetch the target role
-    let target = sqlx::query_as::<_, RoleRow>(
-        "SELECT id, guild_id, name, permissions, position, role_type, created_at \
-         FROM roles WHERE id = $1 AND guild_id = $2",
-    )
-    .bind(role_id)
-    .bind(guild_id)
-    .fetch_optional(&state.db)
-    .await
-    .map_err(db_err)?
-    .ok_or(ServerError(OpenConvError::NotFound))?;
+    let target = repo::roles::get(&state.db, guild_id, role_id)
+        .await
+        .map_err(db_err)?
+        .ok_or(ServerError(OpenConvError::NotFound))?;
 
     // Only custom roles can be updated
     if target.role_type != "custom" {
@@ -236,57 +215,31 @@ pub async fn update_role(
         check_privilege_escalation(new_perms, guild_member.permissions, is_owner)?;
     }
 
-    // Build dynamic update
-    let mut set_clauses = Vec::new();
-    let mut param_idx = 3u32; // $1 = role_id, $2 = guild_id
+    let row = repo::roles::update(
+        &state.db,
+        guild_id,
+        role_id,
+        body.name.as_deref().map(str::trim),
+        body.permissions
+            .map(|perms| Permissions::from_bits_truncate(perms).bits() as i64),
+        body.position,
+    )
+    .await
+    .map_err(|e| {
+        if is_unique_violation(&e) {
+            ServerError(OpenConvError::Conflict(
+                "A role with that position already exists in this guild".into(),
+            ))
+        } else {
+            db_err(e)
+        }
+    })?
+    .ok_or(ServerError(OpenConvError::NotFound))?;
 
-    if body.name.is_some() {
-        set_clauses.push(format!("name = ${param_idx}"));
-        param_idx += 1;
-    }
     if body.permissions.is_some() {
-        set_clauses.push(format!("permissions = ${param_idx}"));
-        param_idx += 1;
-    }
-    if body.position.is_some() {
-        set_clauses.push(format!("position = ${param_idx}"));
-    }
-
-    let query_str = format!(
-        "UPDATE roles SET {} WHERE id = $1 AND guild_id = $2 \
-         RETURNING id, guild_id, name, permissions, position, role_type, created_at",
-        set_clauses.join(", ")
-    );
-
-    let mut query = sqlx::query_as::<_, RoleRow>(&query_str)
-        .bind(role_id)
-        .bind(guild_id);
-
-    if let Some(ref name) = body.name {
-        query = query.bind(name.trim());
-    }
-    if let Some(perms) = body.permissions {
-        let truncated = Permissions::from_bits_truncate(perms).bits() as i64;
-        query = query.bind(truncated);
-    }
-    if let Some(pos) = body.position {
-        query = query.bind(pos);
+        invalidate_guild_permissions(&state, guild_id).await;
     }
 
-    let row = query
-        .fetch_optional(&state.db)
-        .await
-        .map_err(|e| {
-            if is_unique_violation(&e) {
-                ServerError(OpenConvError::Conflict(
-                    "A role with that position already exists in this guild".into(),
-                ))
-            } else {
-                db_err(e)
-            }
-        })?
-        .ok_or(ServerError(OpenConvError::NotFound))?;
-
     Ok(Json(row.into_response()))
 }
 
@@ -300,16 +253,10 @@ pub async fn delete_role(
     guild_member.require(Permissions::MANAGE_ROLES)?;
 
     // Fetch the target role
-    let target = sqlx::query_as::<_, RoleRow>(
-        "SELECT id, guild_id, name, permissions, position, role_type, created_at \
-         FROM roles WHERE id = $1 AND guild_id = $2",
-    )
-    .bind(role_id)
-    .bind(guild_id)
-    .fetch_optional(&state.db)
-    .await
-    .map_err(db_err)?
-    .ok_or(ServerError(OpenConvError::NotFound))?;
+    let target = repo::roles::get(&state.db, guild_id, role_id)
+        .await
+        .map_err(db_err)?
+        .ok_or(ServerError(OpenConvError::NotFound))?;
 
     if target.role_type != "custom" {
         return Err(ServerError(OpenConvError::Validation(
@@ -322,12 +269,10 @@ pub async fn delete_role(
     let actor_pos = actor_highest_position(&state.db, guild_member.user_id, guild_id).await?;
     check_role_hierarchy(actor_pos, target.position, is_owner)?;
 
-    sqlx::query("DELETE FROM roles WHERE id = $1 AND guild_id = $2")
-        .bind(role_id)
-        .bind(guild_id)
-        .execute(&state.db)
+    repo::roles::delete(&state.db, guild_id, role_id)
         .await
         .map_err(db_err)?;
+    invalidate_guild_permissions(&state, guild_id).await;
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -342,16 +287,10 @@ pub async fn assign_role(
     guild_member.require(Permissions::MANAGE_ROLES)?;
 
     // Fetch the target role
-    let target = sqlx::query_as::<_, RoleRow>(
-        "SELECT id, guild_id, name, permissions, position, role_type, created_at \
-         FROM roles WHERE id = $1 AND guild_id = $2",
-    )
-    .bind(role_id)
-    .bind(guild_id)
-    .fetch_optional(&state.db)
-    .await
-    .map_err(db_err)?
-    .ok_or(ServerError(OpenConvError::NotFound))?;
+    let target = repo::roles::get(&state.db, guild_id, role_id)
+        .await
+        .map_err(db_err)?
+        .ok_or(ServerError(OpenConvError::NotFound))?;
 
     // Hierarchy check
     let is_owner = is_guild_owner(&state.db, guild_member.user_id, guild_id).await?;
@@ -359,29 +298,27 @@ pub async fn assign_role(
     check_role_hierarchy(actor_pos, target.position, is_owner)?;
 
     // Verify target user is a guild member
-    let is_member: bool = sqlx::query_scalar(
-        "SELECT EXISTS(SELECT 1 FROM guild_members WHERE user_id = $1 AND guild_id = $2)",
-    )
-    .bind(user_id)
-    .bind(guild_id)
-    .fetch_one(&state.db)
-    .await
-    .map_err(db_err)?;
+    let is_member = repo::guilds::is_member(&state.db, guild_id, user_id)
+        .await
+        .map_err(db_err)?;
 
     if !is_member {
         return Err(ServerError(OpenConvError::NotFound));
     }
 
-    sqlx::query(
-        "INSERT INTO guild_member_roles (user_id, guild_id, role_id) \
-         VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
-    )
-    .bind(user_id)
-    .bind(guild_id)
-    .bind(role_id)
-    .execute(&state.db)
-    .await
-    .map_err(db_err)?;
+    let inserted = repo::roles::assign(&state.db, guild_id, user_id, role_id)
+        .await
+        .map_err(db_err)?;
+
+    if inserted {
+        member_events::publish(
+            &state,
+            MemberEvent::new(guild_id, user_id, MemberEventKind::RoleAdded)
+                .with_actor(guild_member.user_id)
+                .with_role(role_id),
+        )
+        .await;
+    }
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -396,35 +333,113 @@ pub async fn remove_role(
     guild_member.require(Permissions::MANAGE_ROLES)?;
 
     // Fetch the target role
-    let target = sqlx::query_as::<_, RoleRow>(
-        "SELECT id, guild_id, name, permissions, position, role_type, created_at \
-         FROM roles WHERE id = $1 AND guild_id = $2",
-    )
-    .bind(role_id)
-    .bind(guild_id)
-    .fetch_optional(&state.db)
-    .await
-    .map_err(db_err)?
-    .ok_or(ServerError(OpenConvError::NotFound))?;
+    let target = repo::roles::get(&state.db, guild_id, role_id)
+        .await
+        .map_err(db_err)?
+        .ok_or(ServerError(OpenConvError::NotFound))?;
 
     // Hierarchy check
     let is_owner = is_guild_owner(&state.db, guild_member.user_id, guild_id).await?;
     let actor_pos = actor_highest_position(&state.db, guild_member.user_id, guild_id).await?;
     check_role_hierarchy(actor_pos, target.position, is_owner)?;
 
-    sqlx::query(
-        "DELETE FROM guild_member_roles WHERE user_id = $1 AND guild_id = $2 AND role_id = $3",
-    )
-    .bind(user_id)
-    .bind(guild_id)
-    .bind(role_id)
-    .execute(&state.db)
-    .await
-    .map_err(db_err)?;
+    let removed = repo::roles::unassign(&state.db, guild_id, user_id, role_id)
+        .await
+        .map_err(db_err)?;
+
+    if removed {
+        member_events::publish(
+            &state,
+            MemberEvent::new(guild_id, user_id, MemberEventKind::RoleRemoved)
+                .with_actor(guild_member.user_id)
+                .with_role(role_id),
+        )
+        .await;
+    }
 
     Ok(StatusCode::NO_CONTENT)
 }
 
+#[utoipa::path(get, path = "/api/guilds/{guild_id}/permissions/preview", tag = "Roles", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID"), openconv_shared::api::role::PermissionPreviewQuery), responses((status = 200, body = openconv_shared::api::role::PermissionPreviewResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// Preview the effective permissions of a role or member.
+///
+/// A role is previewed as held by a plain member, i.e. combined with the
+/// guild's base member role. Permissions are resolved exactly as the
+/// `GuildMember` extractor does. There are no channel-level overrides, so a
+/// `channel_id` only validates that the channel belongs to the guild and the
+/// result equals the guild-level set.
+pub async fn preview_permissions(
+    State(state): State<AppState>,
+    guild_member: GuildMember,
+    Path(guild_id): Path<GuildId>,
+    Query(query): Query<PermissionPreviewQuery>,
+) -> Result<Json<PermissionPreviewResponse>, ServerError> {
+    guild_member.require(Permissions::MANAGE_ROLES)?;
+
+    let roles = match (query.role_id, query.user_id) {
+        (Some(role_id), None) => {
+            let roles = repo::roles::permissions_with_member_role(&state.db, guild_id, role_id)
+                .await
+                .map_err(db_err)?;
+
+            if !roles.iter().any(|(id, _)| *id == role_id) {
+                return Err(ServerError(OpenConvError::NotFound));
+            }
+            roles
+        }
+        (None, Some(user_id)) => {
+            let is_member = repo::guilds::is_member(&state.db, guild_id, user_id)
+                .await
+                .map_err(db_err)?;
+
+            if !is_member {
+                return Err(ServerError(OpenConvError::NotFound));
+            }
+
+            repo::roles::permissions_held_by(&state.db, guild_id, user_id)
+                .await
+                .map_err(db_err)?
+        }
+        _ => {
+            return Err(ServerError(OpenConvError::Validation(
+                "Exactly one of role_id or user_id must be provided".into(),
+            )));
+        }
+    };
+
+    if let Some(channel_id) = query.channel_id {
+        let in_guild = repo::guilds::has_channel(&state.db, guild_id, channel_id)
+            .await
+            .map_err(db_err)?;
+
+        if !in_guild {
+            return Err(ServerError(OpenConvError::NotFound));
+        }
+    }
+
+    let is_owner = match query.user_id {
+        Some(user_id) => is_guild_owner(&state.db, user_id, guild_id).await?,
+        None => false,
+    };
+    let role_perms: Vec<Permissions> = roles
+        .iter()
+        .map(|(_, bits)| Permissions::from_bits_truncate(*bits as u64))
+        .collect();
+    let effective = if is_owner {
+        Permissions::all()
+    } else {
+        permissions::resolve(&role_perms)
+    };
+
+    Ok(Json(PermissionPreviewResponse {
+        permissions: effective.bits(),
+        permission_names: permissions::names(effective),
+        is_owner,
+        role_ids: roles.into_iter().map(|(id, _)| id).collect(),
+        channel_id: query.channel_id,
+    }))
+}
+
 /// Route builder for role CRUD endpoints.
 pub fn routes() -> axum::Router<AppState> {
     axum::Router::new()
@@ -443,29 +458,9 @@ pub fn assignment_routes() -> axum::Router<AppState> {
     )
 }
 
-#[derive(sqlx::FromRow)]
-struct RoleRow {
-    id: RoleId,
-    guild_id: GuildId,
-    name: String,
-    permissions: i64,
-    position: i32,
-    role_type: String,
-    created_at: chrono::DateTime<chrono::Utc>,
-}
-
-impl RoleRow {
-    fn into_response(self) -> RoleResponse {
-        RoleResponse {
-            id: self.id,
-            guild_id: self.guild_id,
-            name: self.name,
-            permissions: self.permissions as u64,
-            position: self.position,
-            role_type: self.role_type,
-            created_at: self.created_at,
-        }
-    }
+/// Route builder for permission tooling. Mounted at /api/guilds/:guild_id/permissions.
+pub fn permission_routes() -> axum::Router<AppState> {
+    axum::Router::new().route("/preview", axum::routing::get(preview_permissions))
 }
 
 #[cfg(test)]
@@ -517,5 +512,6 @@ mod tests {
     fn routes_builds_without_panic() {
         let _ = routes();
         let _ = assignment_routes();
+        let _ = permission_routes();
     }
 }
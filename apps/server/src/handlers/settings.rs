@@ -0,0 +1,273 @@
+use axum::extract::State;
+use axum::Json;
+use chrono::{DateTime, Utc};
+use openconv_shared::api::settings::{
+    EmojiPreferences, RenderSettings, RenderSettingsOverrides, UpdateEmojiPreferencesRequest,
+    UpdateUserSettingsRequest, UserSettingsResponse, MAX_EMOJI_SHORTCODE_LEN,
+    MAX_EMOJI_USES_PER_UPDATE, MAX_FREQUENT_EMOJI, MAX_MESSAGE_FONT_SIZE, MIN_MESSAGE_FONT_SIZE,
+};
+use openconv_shared::error::OpenConvError;
+
+use crate::error::ServerError;
+use crate::extractors::auth::AuthUser;
+use crate::state::AppState;
+
+fn db_err(e: sqlx::Error) -> ServerError {
+    tracing::error!(error = %e, "database error");
+    ServerError(OpenConvError::Internal("database error".into()))
+}
+
+fn validate_overrides(overrides: &RenderSettingsOverrides) -> Result<(), ServerError> {
+    if let Some(size) = overrides.message_font_size {
+        if !(MIN_MESSAGE_FONT_SIZE..=MAX_MESSAGE_FONT_SIZE).contains(&size) {
+            return Err(ServerError(OpenConvError::Validation(format!(
+                "message_font_size must be between {MIN_MESSAGE_FONT_SIZE} and {MAX_MESSAGE_FONT_SIZE}"
+            ))));
+        }
+    }
+    Ok(())
+}
+
+fn validate_shortcode(shortcode: &str) -> Result<(), ServerError> {
+    let valid = !shortcode.is_empty()
+        && shortcode.len() <= MAX_EMOJI_SHORTCODE_LEN
+        && shortcode
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+'));
+    if !valid {
+        return Err(ServerError(OpenConvError::Validation(format!(
+            "invalid emoji shortcode: {shortcode:?}"
+        ))));
+    }
+    Ok(())
+}
+
+fn validate_emoji_update(body: &UpdateEmojiPreferencesRequest) -> Result<(), ServerError> {
+    if body.frequent.len() > MAX_FREQUENT_EMOJI {
+        return Err(ServerError(OpenConvError::Validation(format!(
+            "frequent must have at most {MAX_FREQUENT_EMOJI} entries"
+        ))));
+    }
+    if body.used.len() > MAX_EMOJI_USES_PER_UPDATE {
+        return Err(ServerError(OpenConvError::Validation(format!(
+            "used must have at most {MAX_EMOJI_USES_PER_UPDATE} entries"
+        ))));
+    }
+    for entry in &body.frequent {
+        validate_shortcode(&entry.shortcode)?;
+    }
+    for shortcode in &body.used {
+        validate_shortcode(shortcode)?;
+    }
+    Ok(())
+}
+
+fn settings_response(defaults: &RenderSettings, row: Option<SettingsRow>) -> UserSettingsResponse {
+    let (overrides, emoji, version, updated_at) = match row {
+        Some(row) => (row.render.0, row.emoji.0, row.version, Some(row.updated_at)),
+        None => (
+            RenderSettingsOverrides::default(),
+            EmojiPreferences::default(),
+            0,
+            None,
+        ),
+    };
+    UserSettingsResponse {
+        render: overrides.apply_to(defaults),
+        render_overrides: overrides,
+        render_defaults: *defaults,
+        emoji,
+        version,
+        updated_at,
+    }
+}
+
+#[utoipa::path(get, path = "/api/users/me/settings", tag = "Users", security(("bearer_auth" = [])), responses((status = 200, body = openconv_shared::api::settings::UserSettingsResponse), (status = 401, body = crate::error::ErrorResponse)))]
+/// Get the caller's synced settings, resolved against the instance defaults.
+pub async fn get_settings(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<UserSettingsResponse>, ServerError> {
+    let row = sqlx::query_as::<_, SettingsRow>(
+        "SELECT render, emoji, version, updated_at FROM user_settings WHERE user_id = $1",
+    )
+    .bind(auth.user_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(db_err)?;
+
+    Ok(Json(settings_response(
+        &state.config.branding.render_defaults,
+        row,
+    )))
+}
+
+#[utoipa::path(put, path = "/api/users/me/settings", tag = "Users", security(("bearer_auth" = [])), request_body = openconv_shared::api::settings::UpdateUserSettingsRequest, responses((status = 200, body = openconv_shared::api::settings::UserSettingsResponse), (status = 400, body = crate::error::ErrorResponse), (status = 409, body = crate::error::ErrorResponse)))]
+/// Replace the caller's render overrides.
+///
+/// With `expected_version`, the write only succeeds if no other device has
+/// saved since that version was read.
+pub async fn update_settings(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(body): Json<UpdateUserSettingsRequest>,
+) -> Result<Json<UserSettingsResponse>, ServerError> {
+    validate_overrides(&body.render)?;
+
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+
+    if let Some(expected) = body.expected_version {
+        let current: Option<i64> =
+            sqlx::query_scalar("SELECT version FROM user_settings WHERE user_id = $1 FOR UPDATE")
+                .bind(auth.user_id)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(db_err)?;
+        if current.unwrap_or(0) != expected {
+            return Err(ServerError(OpenConvError::Conflict(
+                "settings were changed on another device".into(),
+            )));
+        }
+    }
+
+    let row = sqlx::query_as::<_, SettingsRow>(
+        "INSERT INTO user_settings (user_id, render) VALUES ($1, $2) \
+         ON CONFLICT (user_id) DO UPDATE \
+             SET render = EXCLUDED.render, \
+                 version = user_settings.version + 1, \
+                 updated_at = NOW() \
+         RETURNING render, emoji, version, updated_at",
+    )
+    .bind(auth.user_id)
+    .bind(sqlx::types::Json(body.render))
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_err)?;
+
+    tx.commit().await.map_err(db_err)?;
+
+    Ok(Json(settings_response(
+        &state.config.branding.render_defaults,
+        Some(row),
+    )))
+}
+
+#[utoipa::path(patch, path = "/api/users/me/settings/emoji", tag = "Users", security(("bearer_auth" = [])), request_body = openconv_shared::api::settings::UpdateEmojiPreferencesRequest, responses((status = 200, body = openconv_shared::api::settings::UserSettingsResponse), (status = 400, body = crate::error::ErrorResponse)))]
+/// Merge emoji preferences from one of the caller's devices.
+///
+/// The stored frequently-used ranking is merged with the one sent, then
+/// `used` shortcodes are counted on top. Does not bump the settings version.
+pub async fn update_emoji_preferences(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(body): Json<UpdateEmojiPreferencesRequest>,
+) -> Result<Json<UserSettingsResponse>, ServerError> {
+    validate_emoji_update(&body)?;
+
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+
+    let stored: Option<sqlx::types::Json<EmojiPreferences>> =
+        sqlx::query_scalar("SELECT emoji FROM user_settings WHERE user_id = $1 FOR UPDATE")
+            .bind(auth.user_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(db_err)?;
+
+    let mut emoji = stored.map(|s| s.0).unwrap_or_default();
+    if let Some(tone) = body.skin_tone {
+        emoji.skin_tone = tone;
+    }
+    emoji.merge_frequent(&body.frequent);
+    emoji.record_use(&body.used, Utc::now());
+
+    // A first write from here starts at version 0 so a device that has never
+    // saved render settings can still use expected_version 0.
+    let row = sqlx::query_as::<_, SettingsRow>(
+        "INSERT INTO user_settings (user_id, emoji, version) VALUES ($1, $2, 0) \
+         ON CONFLICT (user_id) DO UPDATE \
+             SET emoji = EXCLUDED.emoji, \
+                 updated_at = NOW() \
+         RETURNING render, emoji, version, updated_at",
+    )
+    .bind(auth.user_id)
+    .bind(sqlx::types::Json(emoji))
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_err)?;
+
+    tx.commit().await.map_err(db_err)?;
+
+    Ok(Json(settings_response(
+        &state.config.branding.render_defaults,
+        Some(row),
+    )))
+}
+
+/// Synced settings routes. Mounted at /api/users alongside the profile routes.
+pub fn routes() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route(
+            "/me/settings",
+            axum::routing::get(get_settings).put(update_settings),
+        )
+        .route(
+            "/me/settings/emoji",
+            axum::routing::patch(update_emoji_preferences),
+        )
+}
+
+#[derive(sqlx::FromRow)]
+struct SettingsRow {
+    render: sqlx::types::Json<RenderSettingsOverrides>,
+    emoji: sqlx::types::Json<EmojiPreferences>,
+    version: i64,
+    updated_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_build_without_panic() {
+        let _ = routes();
+    }
+
+    #[test]
+    fn font_size_override_must_be_in_range() {
+        let mut overrides = RenderSettingsOverrides {
+            message_font_size: Some(MAX_MESSAGE_FONT_SIZE + 1),
+            ..Default::default()
+        };
+        assert!(validate_overrides(&overrides).is_err());
+        overrides.message_font_size = Some(MIN_MESSAGE_FONT_SIZE);
+        assert!(validate_overrides(&overrides).is_ok());
+    }
+
+    #[test]
+    fn emoji_shortcodes_are_validated() {
+        let mut body = UpdateEmojiPreferencesRequest {
+            used: vec!["thumbsup".into(), "+1".into(), "flag-us".into()],
+            ..Default::default()
+        };
+        assert!(validate_emoji_update(&body).is_ok());
+        body.used.push(":smile:".into());
+        assert!(validate_emoji_update(&body).is_err());
+        body.used = vec!["x".repeat(MAX_EMOJI_SHORTCODE_LEN + 1)];
+        assert!(validate_emoji_update(&body).is_err());
+        body.used = vec!["a".into(); MAX_EMOJI_USES_PER_UPDATE + 1];
+        assert!(validate_emoji_update(&body).is_err());
+    }
+
+    #[test]
+    fn missing_row_resolves_to_defaults() {
+        let defaults = RenderSettings {
+            compact_mode: true,
+            ..Default::default()
+        };
+        let resp = settings_response(&defaults, None);
+        assert_eq!(resp.render, defaults);
+        assert_eq!(resp.version, 0);
+        assert!(resp.updated_at.is_none());
+    }
+}
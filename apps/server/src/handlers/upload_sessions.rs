@@ -0,0 +1,407 @@
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use futures::StreamExt;
+use object_store::path::Path as StorePath;
+use object_store::{ObjectStore, WriteMultipart};
+use openconv_shared::api::file::{CreateUploadSessionRequest, FileResponse, UploadSessionResponse};
+use openconv_shared::error::OpenConvError;
+use openconv_shared::ids::{UploadSessionId, UserId};
+use sha2::{Digest, Sha256};
+
+use crate::error::ServerError;
+use crate::extractors::auth::AuthUser;
+use crate::handlers::files::{
+    adopt_blob, delete_blob_later, initial_scan_status, payload_too_large, scan_later, store_err,
+    validate_encrypted_blob_key, validate_file_name, validate_upload_mime_type, FileRow,
+    UPLOAD_MAX_CONCURRENT_PARTS,
+};
+use crate::handlers::quotas;
+use crate::state::AppState;
+use crate::tasks::file_cleanup::{delete_session_chunks, session_chunk_path};
+
+fn db_err(e: sqlx::Error) -> ServerError {
+    tracing::error!(error = %e, "database error");
+    ServerError(OpenConvError::Internal("database error".into()))
+}
+
+/// Number of chunks needed for `size_bytes` at `chunk_size` per chunk.
+fn chunk_count(size_bytes: i64, chunk_size: i64) -> i64 {
+    (size_bytes + chunk_size - 1) / chunk_size
+}
+
+/// Exact size chunk `index` must have. Only the last chunk may be short.
+fn expected_chunk_size(size_bytes: i64, chunk_size: i64, index: i64) -> i64 {
+    if index == chunk_count(size_bytes, chunk_size) - 1 {
+        size_bytes - chunk_size * index
+    } else {
+        chunk_size
+    }
+}
+
+/// Load a live session owned by `user_id`. Sessions of other users and
+/// expired sessions are reported as not found.
+async fn load_session(
+    db: &sqlx::PgPool,
+    id: UploadSessionId,
+    user_id: UserId,
+) -> Result<SessionRow, ServerError> {
+    sqlx::query_as::<_, SessionRow>(
+        "SELECT id, file_name, mime_type, encrypted_blob_key, size_bytes, chunk_size, expires_at \
+         FROM upload_sessions \
+         WHERE id = $1 AND uploader_id = $2 AND expires_at > NOW()",
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(db)
+    .await
+    .map_err(db_err)?
+    .ok_or(ServerError(OpenConvError::NotFound))
+}
+
+async fn session_response(
+    db: &sqlx::PgPool,
+    session: &SessionRow,
+) -> Result<UploadSessionResponse, ServerError> {
+    let received_chunks: Vec<i32> = sqlx::query_scalar(
+        "SELECT chunk_index FROM upload_session_chunks WHERE session_id = $1 ORDER BY chunk_index",
+    )
+    .bind(session.id)
+    .fetch_all(db)
+    .await
+    .map_err(db_err)?;
+
+    Ok(UploadSessionResponse {
+        id: session.id,
+        size_bytes: session.size_bytes,
+        chunk_size: session.chunk_size,
+        chunk_count: chunk_count(session.size_bytes, session.chunk_size) as i32,
+        received_chunks,
+        expires_at: session.expires_at,
+    })
+}
+
+#[utoipa::path(post, path = "/api/files/sessions", tag = "Files", security(("bearer_auth" = [])), request_body = openconv_shared::api::file::CreateUploadSessionRequest, responses((status = 201, body = openconv_shared::api::file::UploadSessionResponse), (status = 400, body = crate::error::ErrorResponse), (status = 413, body = crate::error::ErrorResponse)))]
+/// POST /api/files/sessions
+/// Start a resumable chunked upload.
+pub async fn create(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(body): Json<CreateUploadSessionRequest>,
+) -> Result<(StatusCode, Json<UploadSessionResponse>), ServerError> {
+    let config = &state.config.file_storage;
+    let file_name = validate_file_name(&body.file_name)?;
+    validate_upload_mime_type(config, &body.mime_type)?;
+    validate_encrypted_blob_key(&body.encrypted_blob_key)?;
+
+    if body.size_bytes <= 0 {
+        return Err(ServerError(OpenConvError::Validation(
+            "size_bytes must be positive".into(),
+        )));
+    }
+    if body.size_bytes as u64 > config.max_session_upload_bytes {
+        return Err(payload_too_large(config.max_session_upload_bytes));
+    }
+    quotas::precheck(&state.db, config, auth.user_id, None, body.size_bytes).await?;
+
+    let chunk_size = config.upload_chunk_size_bytes as i64;
+    let expires_at =
+        chrono::Utc::now() + chrono::Duration::seconds(config.upload_session_ttl_seconds as i64);
+
+    let session = sqlx::query_as::<_, SessionRow>(
+        "INSERT INTO upload_sessions \
+             (id, uploader_id, file_name, mime_type, encrypted_blob_key, size_bytes, chunk_size, expires_at) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+         RETURNING id, file_name, mime_type, encrypted_blob_key, size_bytes, chunk_size, expires_at",
+    )
+    .bind(UploadSessionId::new())
+    .bind(auth.user_id)
+    .bind(&file_name)
+    .bind(&body.mime_type)
+    .bind(&body.encrypted_blob_key)
+    .bind(body.size_bytes)
+    .bind(chunk_size)
+    .bind(expires_at)
+    .fetch_one(&state.db)
+    .await
+    .map_err(db_err)?;
+
+    let resp = session_response(&state.db, &session).await?;
+    Ok((StatusCode::CREATED, Json(resp)))
+}
+
+#[utoipa::path(get, path = "/api/files/sessions/{session_id}", tag = "Files", security(("bearer_auth" = [])), params(("session_id" = openconv_shared::ids::UploadSessionId, Path, description = "Upload session ID")), responses((status = 200, body = openconv_shared::api::file::UploadSessionResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// GET /api/files/sessions/:session_id
+/// Session progress, used to resume after a dropped connection.
+pub async fn get_one(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(session_id): Path<UploadSessionId>,
+) -> Result<Json<UploadSessionResponse>, ServerError> {
+    let session = load_session(&state.db, session_id, auth.user_id).await?;
+    Ok(Json(session_response(&state.db, &session).await?))
+}
+
+#[utoipa::path(put, path = "/api/files/sessions/{session_id}/chunks/{index}", tag = "Files", security(("bearer_auth" = [])), params(("session_id" = openconv_shared::ids::UploadSessionId, Path, description = "Upload session ID"), ("index" = i64, Path, description = "Zero-based chunk index")), request_body(content = Vec<u8>, content_type = "application/octet-stream"), responses((status = 200, body = openconv_shared::api::file::UploadSessionResponse), (status = 400, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// PUT /api/files/sessions/:session_id/chunks/:index
+/// Store one chunk. Re-sending a chunk replaces it, so a chunk whose
+/// response was lost can simply be sent again.
+pub async fn put_chunk(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((session_id, index)): Path<(UploadSessionId, i64)>,
+    body: Bytes,
+) -> Result<Json<UploadSessionResponse>, ServerError> {
+    let session = load_session(&state.db, session_id, auth.user_id).await?;
+
+    if !(0..chunk_count(session.size_bytes, session.chunk_size)).contains(&index) {
+        return Err(ServerError(OpenConvError::Validation(
+            "chunk index out of range".into(),
+        )));
+    }
+    let expected = expected_chunk_size(session.size_bytes, session.chunk_size, index);
+    if body.len() as i64 != expected {
+        return Err(ServerError(OpenConvError::Validation(format!(
+            "chunk {index} must be {expected} bytes"
+        ))));
+    }
+
+    state
+        .object_store
+        .put(&session_chunk_path(session_id, index), body.into())
+        .await
+        .map_err(store_err)?;
+
+    sqlx::query(
+        "INSERT INTO upload_session_chunks (session_id, chunk_index, size_bytes) \
+         VALUES ($1, $2, $3) \
+         ON CONFLICT (session_id, chunk_index) DO UPDATE \
+             SET size_bytes = EXCLUDED.size_bytes, received_at = NOW()",
+    )
+    .bind(session_id)
+    .bind(index as i32)
+    .bind(expected)
+    .execute(&state.db)
+    .await
+    .map_err(db_err)?;
+
+    Ok(Json(session_response(&state.db, &session).await?))
+}
+
+/// Concatenate the session's chunks into one object at `path` using a
+/// streaming multipart write. Returns the hex SHA-256 digest of the result.
+async fn assemble_chunks(
+    store: &dyn ObjectStore,
+    session_id: UploadSessionId,
+    chunks: i64,
+    path: &StorePath,
+) -> Result<String, object_store::Error> {
+    let mut writer = WriteMultipart::new(store.put_multipart(path).await?);
+    let mut hasher = Sha256::new();
+    for index in 0..chunks {
+        let result = async {
+            let mut stream = store
+                .get(&session_chunk_path(session_id, index))
+                .await?
+                .into_stream();
+            while let Some(bytes) = stream.next().await {
+                let bytes = bytes?;
+                writer
+                    .wait_for_capacity(UPLOAD_MAX_CONCURRENT_PARTS)
+                    .await?;
+                hasher.update(&bytes);
+                writer.write(&bytes);
+            }
+            Ok::<_, object_store::Error>(())
+        }
+        .await;
+        if let Err(e) = result {
+            let _ = writer.abort().await;
+            return Err(e);
+        }
+    }
+    writer.finish().await?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[utoipa::path(post, path = "/api/files/sessions/{session_id}/complete", tag = "Files", security(("bearer_auth" = [])), params(("session_id" = openconv_shared::ids::UploadSessionId, Path, description = "Upload session ID")), responses((status = 201, body = openconv_shared::api::file::FileResponse), (status = 400, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// POST /api/files/sessions/:session_id/complete
+/// Assemble the uploaded chunks into a file.
+///
+/// The file behaves like one from POST /api/files: private to the uploader
+/// until it is attached to a message.
+pub async fn complete(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(session_id): Path<UploadSessionId>,
+) -> Result<(StatusCode, Json<FileResponse>), ServerError> {
+    let session = load_session(&state.db, session_id, auth.user_id).await?;
+    let chunks = chunk_count(session.size_bytes, session.chunk_size);
+
+    let received: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM upload_session_chunks WHERE session_id = $1")
+            .bind(session_id)
+            .fetch_one(&state.db)
+            .await
+            .map_err(db_err)?;
+    if received != chunks {
+        return Err(ServerError(OpenConvError::Validation(format!(
+            "{} of {chunks} chunks are missing",
+            chunks - received
+        ))));
+    }
+
+    let storage_path = format!("users/{}/{}", auth.user_id, session_id);
+    let store_path = StorePath::from(storage_path.as_str());
+    let digest = assemble_chunks(&*state.object_store, session_id, chunks, &store_path)
+        .await
+        .map_err(store_err)?;
+
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+
+    // A concurrent complete for the same session may have won the race
+    let claimed = sqlx::query("DELETE FROM upload_sessions WHERE id = $1")
+        .bind(session_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?
+        .rows_affected();
+    if claimed == 0 {
+        return Err(ServerError(OpenConvError::NotFound));
+    }
+
+    // Other uploads may have finished since the session was created
+    if let Err(e) = quotas::reserve(
+        &mut tx,
+        &state.config.file_storage,
+        auth.user_id,
+        None,
+        session.size_bytes,
+    )
+    .await
+    {
+        delete_blob_later(&state, &store_path);
+        return Err(e);
+    }
+    if let Err(e) = adopt_blob(&state, &mut tx, &store_path, &digest, session.size_bytes).await {
+        delete_blob_later(&state, &store_path);
+        return Err(e);
+    }
+
+    let row = sqlx::query_as::<_, FileRow>(
+        "INSERT INTO files (uploader_id, file_name, mime_type, size_bytes, storage_path, encrypted_blob_key, blob_digest, scan_status) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+         RETURNING id, file_name, mime_type, size_bytes, created_at",
+    )
+    .bind(auth.user_id)
+    .bind(&session.file_name)
+    .bind(&session.mime_type)
+    .bind(session.size_bytes)
+    .bind(&storage_path)
+    .bind(&session.encrypted_blob_key)
+    .bind(&digest)
+    .bind(initial_scan_status(&state))
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| {
+        delete_blob_later(&state, &store_path);
+        db_err(e)
+    })?;
+
+    tx.commit().await.map_err(db_err)?;
+
+    delete_chunks_later(&state, session_id);
+    scan_later(&state, row.id);
+    Ok((StatusCode::CREATED, Json(row.into_response())))
+}
+
+#[utoipa::path(delete, path = "/api/files/sessions/{session_id}", tag = "Files", security(("bearer_auth" = [])), params(("session_id" = openconv_shared::ids::UploadSessionId, Path, description = "Upload session ID")), responses((status = 204), (status = 404, body = crate::error::ErrorResponse)))]
+/// DELETE /api/files/sessions/:session_id
+/// Abandon an upload and discard its chunks.
+pub async fn abort(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(session_id): Path<UploadSessionId>,
+) -> Result<StatusCode, ServerError> {
+    let deleted = sqlx::query("DELETE FROM upload_sessions WHERE id = $1 AND uploader_id = $2")
+        .bind(session_id)
+        .bind(auth.user_id)
+        .execute(&state.db)
+        .await
+        .map_err(db_err)?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(ServerError(OpenConvError::NotFound));
+    }
+
+    delete_chunks_later(&state, session_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn delete_chunks_later(state: &AppState, session_id: UploadSessionId) {
+    let store = state.object_store.clone();
+    tokio::spawn(async move {
+        if let Err(e) = delete_session_chunks(&*store, session_id).await {
+            tracing::error!(error = %e, %session_id, "failed to delete upload session chunks");
+        }
+    });
+}
+
+/// Route builder for upload sessions, excluding chunk uploads.
+/// Mounted at /api/files/sessions
+pub fn routes() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route("/", axum::routing::post(create))
+        .route("/{session_id}", axum::routing::get(get_one).delete(abort))
+        .route("/{session_id}/complete", axum::routing::post(complete))
+}
+
+/// Route builder for chunk uploads, which need a body limit sized to the
+/// chunk size. Mounted at /api/files/sessions
+pub fn chunk_routes() -> axum::Router<AppState> {
+    axum::Router::new().route(
+        "/{session_id}/chunks/{index}",
+        axum::routing::put(put_chunk),
+    )
+}
+
+// ─── Internal types ─────────────────────────────────────────
+
+#[derive(sqlx::FromRow)]
+struct SessionRow {
+    id: UploadSessionId,
+    file_name: String,
+    mime_type: String,
+    encrypted_blob_key: String,
+    size_bytes: i64,
+    chunk_size: i64,
+    expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_build_without_panic() {
+        let _ = routes();
+        let _ = chunk_routes();
+    }
+
+    #[test]
+    fn chunk_count_rounds_up() {
+        assert_eq!(chunk_count(1, 8), 1);
+        assert_eq!(chunk_count(8, 8), 1);
+        assert_eq!(chunk_count(9, 8), 2);
+        assert_eq!(chunk_count(1 << 30, 8 << 20), 128);
+    }
+
+    #[test]
+    fn only_last_chunk_may_be_short() {
+        assert_eq!(expected_chunk_size(20, 8, 0), 8);
+        assert_eq!(expected_chunk_size(20, 8, 1), 8);
+        assert_eq!(expected_chunk_size(20, 8, 2), 4);
+        assert_eq!(expected_chunk_size(16, 8, 1), 8);
+    }
+}
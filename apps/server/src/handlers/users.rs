@@ -2,7 +2,7 @@ use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 use axum::Json;
 use openconv_shared::error::OpenConvError;
-use openconv_shared::ids::UserId;
+use openconv_shared::ids::{DeviceId, UserId};
 use sqlx::Row;
 
 use crate::error::ServerError;
@@ -52,6 +52,20 @@ pub struct SearchUsersResponse {
     pub total: i64,
 }
 
+/// Most user IDs accepted by one bulk lookup.
+pub const MAX_BULK_USER_IDS: usize = 100;
+
+#[derive(Debug, serde::Deserialize, utoipa::ToSchema)]
+pub struct BulkUsersRequest {
+    pub user_ids: Vec<UserId>,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct BulkUsersResponse {
+    /// Profiles of the users that exist, in no particular order.
+    pub users: Vec<PublicProfileResponse>,
+}
+
 #[derive(Debug, serde::Deserialize, utoipa::ToSchema)]
 pub struct UploadPreKeysRequest {
     pub pre_key_bundles: Vec<Vec<u8>>,
@@ -62,6 +76,19 @@ pub struct PreKeyBundleResponse {
     pub key_data: Vec<u8>,
 }
 
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct DeviceIdentityKey {
+    pub device_id: DeviceId,
+    /// Base64-encoded identity public key of the device.
+    pub identity_key: String,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct DeviceKeysResponse {
+    /// Devices with an identity key, oldest first. Senders encrypt for each.
+    pub devices: Vec<DeviceIdentityKey>,
+}
+
 // ---------------------------------------------------------------------------
 // Handlers
 // ---------------------------------------------------------------------------
@@ -162,6 +189,38 @@ pub async fn get_user(
     Ok(Json(public_profile_from_row(&row)))
 }
 
+#[utoipa::path(post, path = "/api/users/bulk", tag = "Users", security(("bearer_auth" = [])), request_body = BulkUsersRequest, responses((status = 200, body = BulkUsersResponse), (status = 400, body = crate::error::ErrorResponse)))]
+/// POST /api/users/bulk — public profiles for up to 100 users at once.
+/// Unknown IDs are left out of the response.
+pub async fn bulk_users(
+    State(state): State<AppState>,
+    _auth_user: AuthUser,
+    Json(req): Json<BulkUsersRequest>,
+) -> Result<Json<BulkUsersResponse>, ServerError> {
+    if req.user_ids.len() > MAX_BULK_USER_IDS {
+        return Err(OpenConvError::Validation(format!(
+            "at most {MAX_BULK_USER_IDS} user_ids per request"
+        ))
+        .into());
+    }
+    if req.user_ids.is_empty() {
+        return Ok(Json(BulkUsersResponse { users: Vec::new() }));
+    }
+
+    let user_ids: Vec<uuid::Uuid> = req.user_ids.iter().map(|id| id.0).collect();
+    let rows = sqlx::query(
+        "SELECT id, display_name, avatar_url, public_key FROM users WHERE id = ANY($1)",
+    )
+    .bind(&user_ids)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| ServerError(OpenConvError::Internal(e.to_string())))?;
+
+    Ok(Json(BulkUsersResponse {
+        users: rows.iter().map(public_profile_from_row).collect(),
+    }))
+}
+
 #[utoipa::path(get, path = "/api/users/search", tag = "Users", security(("bearer_auth" = [])), params(SearchUsersQuery), responses((status = 200, body = SearchUsersResponse), (status = 400, body = crate::error::ErrorResponse)))]
 /// GET /api/users/search — search by display_name (ILIKE).
 pub async fn search_users(
@@ -209,12 +268,56 @@ pub async fn search_users(
 }
 
 #[utoipa::path(get, path = "/api/users/{user_id}/prekeys", tag = "Users", security(("bearer_auth" = [])), params(("user_id" = uuid::Uuid, Path, description = "User ID")), responses((status = 200, body = PreKeyBundleResponse), (status = 404, body = crate::error::ErrorResponse)))]
-/// GET /api/users/:user_id/prekeys — fetch one unused pre-key bundle.
+/// GET /api/users/:user_id/prekeys — fetch one unused pre-key bundle from
+/// any of the user's devices.
 pub async fn get_prekeys(
     State(state): State<AppState>,
     _auth_user: AuthUser,
     Path(user_id): Path<uuid::Uuid>,
 ) -> Result<Json<PreKeyBundleResponse>, ServerError> {
+    let key_data = claim_pre_key_bundle(&state, user_id, None).await?;
+    Ok(Json(PreKeyBundleResponse { key_data }))
+}
+
+#[utoipa::path(get, path = "/api/users/{user_id}/devices", tag = "Users", security(("bearer_auth" = [])), params(("user_id" = uuid::Uuid, Path, description = "User ID")), responses((status = 200, body = DeviceKeysResponse)))]
+/// GET /api/users/:user_id/devices — the identity keys of the user's
+/// devices, so a sender can set up a session with each.
+pub async fn get_device_keys(
+    State(state): State<AppState>,
+    _auth_user: AuthUser,
+    Path(user_id): Path<UserId>,
+) -> Result<Json<DeviceKeysResponse>, ServerError> {
+    let devices = crate::repo::devices::identity_keys_for_user(&state.db, user_id)
+        .await
+        .map_err(|e| ServerError(OpenConvError::Internal(e.to_string())))?
+        .into_iter()
+        .map(|(device_id, identity_key)| DeviceIdentityKey {
+            device_id,
+            identity_key,
+        })
+        .collect();
+    Ok(Json(DeviceKeysResponse { devices }))
+}
+
+#[utoipa::path(get, path = "/api/users/{user_id}/devices/{device_id}/prekeys", tag = "Users", security(("bearer_auth" = [])), params(("user_id" = uuid::Uuid, Path, description = "User ID"), ("device_id" = uuid::Uuid, Path, description = "Device ID")), responses((status = 200, body = PreKeyBundleResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// GET /api/users/:user_id/devices/:device_id/prekeys — fetch one unused
+/// pre-key bundle published by a specific device.
+pub async fn get_device_prekeys(
+    State(state): State<AppState>,
+    _auth_user: AuthUser,
+    Path((user_id, device_id)): Path<(uuid::Uuid, uuid::Uuid)>,
+) -> Result<Json<PreKeyBundleResponse>, ServerError> {
+    let key_data = claim_pre_key_bundle(&state, user_id, Some(device_id)).await?;
+    Ok(Json(PreKeyBundleResponse { key_data }))
+}
+
+/// Mark one unused bundle of the user (optionally of one device) as used
+/// and return it.
+async fn claim_pre_key_bundle(
+    state: &AppState,
+    user_id: uuid::Uuid,
+    device_id: Option<uuid::Uuid>,
+) -> Result<Vec<u8>, ServerError> {
     let mut tx = state
         .db
         .begin()
@@ -223,10 +326,11 @@ pub async fn get_prekeys(
 
     let row = sqlx::query(
         "SELECT id, key_data FROM pre_key_bundles \
-         WHERE user_id = $1 AND is_used = false \
+         WHERE user_id = $1 AND ($2::uuid IS NULL OR device_id = $2) AND is_used = false \
          LIMIT 1 FOR UPDATE SKIP LOCKED",
     )
     .bind(user_id)
+    .bind(device_id)
     .fetch_optional(&mut *tx)
     .await
     .map_err(|e| ServerError(OpenConvError::Internal(e.to_string())))?
@@ -245,7 +349,7 @@ pub async fn get_prekeys(
         .await
         .map_err(|e| ServerError(OpenConvError::Internal(e.to_string())))?;
 
-    Ok(Json(PreKeyBundleResponse { key_data }))
+    Ok(key_data)
 }
 
 const MAX_BUNDLE_SIZE: usize = 1024;
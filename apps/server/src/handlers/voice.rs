@@ -0,0 +1,107 @@
+use axum::extract::State;
+use axum::Json;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use openconv_shared::api::voice::{TurnCredentialsResponse, VoiceStateListResponse};
+use openconv_shared::error::OpenConvError;
+use openconv_shared::ids::UserId;
+use openconv_shared::permissions::Permissions;
+use sha1::Sha1;
+
+use crate::error::ServerError;
+use crate::extractors::auth::AuthUser;
+use crate::extractors::guild_member::GuildMember;
+use crate::state::AppState;
+
+/// TURN REST API credentials: the username is `<expiry>:<user ID>` and the
+/// password is the base64 HMAC-SHA1 of the username under the shared
+/// secret, which the TURN server recomputes to authenticate the client.
+fn turn_credentials(secret: &str, user_id: UserId, expires_at: i64) -> (String, String) {
+    let username = format!("{expires_at}:{user_id}");
+    let mut mac =
+        Hmac::<Sha1>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(username.as_bytes());
+    let credential = base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes());
+    (username, credential)
+}
+
+#[utoipa::path(get, path = "/api/voice/turn-credentials", tag = "Voice", security(("bearer_auth" = [])), responses((status = 200, body = TurnCredentialsResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// GET /api/voice/turn-credentials
+/// Short-lived STUN/TURN credentials for WebRTC calls. 404 when the instance
+/// has no TURN server configured.
+pub async fn get_turn_credentials(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<TurnCredentialsResponse>, ServerError> {
+    let config = &state.config.voice;
+    if !config.is_enabled() {
+        return Err(ServerError(OpenConvError::NotFound));
+    }
+
+    let ttl = config.turn_credential_ttl_secs;
+    let expires_at = chrono::Utc::now() + chrono::Duration::seconds(ttl as i64);
+    let (username, credential) = turn_credentials(
+        &config.turn_shared_secret,
+        auth.user_id,
+        expires_at.timestamp(),
+    );
+    Ok(Json(TurnCredentialsResponse {
+        urls: config.turn_uris.clone(),
+        username,
+        credential,
+        ttl,
+        expires_at,
+    }))
+}
+
+#[utoipa::path(get, path = "/api/guilds/{guild_id}/voice-states", tag = "Voice", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), responses((status = 200, body = VoiceStateListResponse), (status = 403, body = crate::error::ErrorResponse)))]
+/// GET /api/guilds/{guild_id}/voice-states
+/// Who is in the guild's voice channels right now, so clients can render
+/// them before the first `VoiceStateUpdate` arrives.
+pub async fn list_voice_states(
+    member: GuildMember,
+    State(state): State<AppState>,
+) -> Result<Json<VoiceStateListResponse>, ServerError> {
+    member.require(Permissions::READ_MESSAGES)?;
+
+    let voice_states = crate::ws::voice::list(&state.redis, member.guild_id)
+        .await
+        .map_err(|e| {
+            tracing::error!(error = %e, "redis error");
+            ServerError(OpenConvError::Internal("redis error".into()))
+        })?;
+    Ok(Json(VoiceStateListResponse { voice_states }))
+}
+
+/// Route builder for voice endpoints.
+pub fn routes() -> axum::Router<AppState> {
+    axum::Router::new().route(
+        "/turn-credentials",
+        axum::routing::get(get_turn_credentials),
+    )
+}
+
+/// Route builder for guild-scoped voice endpoints.
+pub fn guild_routes() -> axum::Router<AppState> {
+    axum::Router::new().route("/", axum::routing::get(list_voice_states))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turn_credentials_match_coturn_convention() {
+        let user_id: UserId = "00000000-0000-0000-0000-000000000001".parse().unwrap();
+        let (username, credential) = turn_credentials("north", user_id, 1_700_000_000);
+        assert_eq!(username, "1700000000:00000000-0000-0000-0000-000000000001");
+        // printf %s "$username" | openssl dgst -sha1 -hmac north -binary | base64
+        assert_eq!(credential, "MH0lSH7233/mkU/wdF5esCY1w0U=");
+    }
+
+    #[test]
+    fn routes_build_without_panic() {
+        let _ = routes();
+        let _ = guild_routes();
+    }
+}
@@ -0,0 +1,274 @@
+use std::net::IpAddr;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use openconv_shared::api::gateway::GatewayIntents;
+use openconv_shared::api::webhook::{CreateWebhookRequest, WebhookListResponse, WebhookResponse};
+use openconv_shared::error::OpenConvError;
+use openconv_shared::ids::{GuildId, UserId};
+use openconv_shared::permissions::Permissions;
+use rand::Rng;
+
+use crate::error::ServerError;
+use crate::extractors::guild_member::GuildMember;
+use crate::state::AppState;
+
+fn db_err(e: sqlx::Error) -> ServerError {
+    tracing::error!(error = %e, "database error");
+    ServerError(OpenConvError::Internal("database error".into()))
+}
+
+const MAX_WEBHOOKS_PER_GUILD: i64 = 10;
+const MAX_WEBHOOK_URL_LEN: usize = 2048;
+
+/// Validate a webhook target URL.
+///
+/// Only http(s) URLs are accepted, and hosts that are loopback, private or
+/// link-local addresses are rejected so webhooks cannot be pointed at the
+/// server's own network.
+fn validate_webhook_url(raw: &str) -> Result<reqwest::Url, ServerError> {
+    let invalid = |msg: &str| ServerError(OpenConvError::Validation(msg.into()));
+
+    if raw.len() > MAX_WEBHOOK_URL_LEN {
+        return Err(invalid("Webhook URL is too long"));
+    }
+    let url = reqwest::Url::parse(raw).map_err(|_| invalid("Webhook URL is not a valid URL"))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(invalid("Webhook URL must use http or https"));
+    }
+
+    let host = url
+        .host_str()
+        .ok_or_else(|| invalid("Webhook URL must have a host"))?;
+    let blocked = match host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        Ok(ip) => !is_public_ip(ip),
+        Err(_) => {
+            let domain = host.trim_end_matches('.').to_ascii_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost")
+        }
+    };
+    if blocked {
+        return Err(invalid("Webhook URL must point to a public host"));
+    }
+    Ok(url)
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast())
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(v4));
+            }
+            let segments = v6.segments();
+            let unique_local = (segments[0] & 0xfe00) == 0xfc00;
+            let link_local = (segments[0] & 0xffc0) == 0xfe80;
+            !(v6.is_loopback() || v6.is_unspecified() || unique_local || link_local)
+        }
+    }
+}
+
+fn generate_secret() -> String {
+    let bytes: [u8; 32] = rand::rng().random();
+    hex::encode(bytes)
+}
+
+#[utoipa::path(post, path = "/api/guilds/{guild_id}/webhooks", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), request_body = openconv_shared::api::webhook::CreateWebhookRequest, responses((status = 201, body = openconv_shared::api::webhook::WebhookResponse), (status = 400, body = crate::error::ErrorResponse), (status = 403, body = crate::error::ErrorResponse), (status = 409, body = crate::error::ErrorResponse)))]
+/// Create an outgoing webhook. The signing secret is only returned here.
+pub async fn create_webhook(
+    member: GuildMember,
+    State(state): State<AppState>,
+    Json(body): Json<CreateWebhookRequest>,
+) -> Result<(StatusCode, Json<WebhookResponse>), ServerError> {
+    member.require(Permissions::MANAGE_GUILD)?;
+
+    let url = validate_webhook_url(body.url.trim())?;
+    let intents = match body.intents {
+        Some(bits) => GatewayIntents::from_bits_truncate(bits),
+        None => GatewayIntents::GUILD_MEMBERS,
+    };
+    if intents.is_empty() {
+        return Err(ServerError(OpenConvError::Validation(
+            "Webhook must subscribe to at least one intent".into(),
+        )));
+    }
+
+    let mut tx = state.db.begin().await.map_err(db_err)?;
+
+    // Lock the guild row so concurrent creates cannot exceed the limit.
+    sqlx::query("SELECT 1 FROM guilds WHERE id = $1 FOR UPDATE")
+        .bind(member.guild_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?;
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM guild_webhooks WHERE guild_id = $1")
+        .bind(member.guild_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_err)?;
+    if count >= MAX_WEBHOOKS_PER_GUILD {
+        return Err(ServerError(OpenConvError::Conflict(format!(
+            "guild already has {MAX_WEBHOOKS_PER_GUILD} webhooks"
+        ))));
+    }
+
+    let secret = generate_secret();
+    let row = sqlx::query_as::<_, WebhookRow>(
+        "INSERT INTO guild_webhooks (guild_id, url, secret, intents, created_by) \
+         VALUES ($1, $2, $3, $4, $5) \
+         RETURNING id, guild_id, url, intents, created_by, created_at",
+    )
+    .bind(member.guild_id)
+    .bind(url.as_str())
+    .bind(&secret)
+    .bind(intents.bits() as i64)
+    .bind(member.user_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_err)?;
+
+    tx.commit().await.map_err(db_err)?;
+
+    let mut response = row.into_response();
+    response.secret = Some(secret);
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[utoipa::path(get, path = "/api/guilds/{guild_id}/webhooks", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID")), responses((status = 200, body = openconv_shared::api::webhook::WebhookListResponse), (status = 403, body = crate::error::ErrorResponse)))]
+/// List the guild's outgoing webhooks.
+pub async fn list_webhooks(
+    member: GuildMember,
+    State(state): State<AppState>,
+) -> Result<Json<WebhookListResponse>, ServerError> {
+    member.require(Permissions::MANAGE_GUILD)?;
+
+    let rows = sqlx::query_as::<_, WebhookRow>(
+        "SELECT id, guild_id, url, intents, created_by, created_at \
+         FROM guild_webhooks WHERE guild_id = $1 ORDER BY created_at",
+    )
+    .bind(member.guild_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(db_err)?;
+
+    Ok(Json(WebhookListResponse {
+        webhooks: rows.into_iter().map(WebhookRow::into_response).collect(),
+    }))
+}
+
+#[utoipa::path(delete, path = "/api/guilds/{guild_id}/webhooks/{webhook_id}", tag = "Guilds", security(("bearer_auth" = [])), params(("guild_id" = openconv_shared::ids::GuildId, Path, description = "Guild ID"), ("webhook_id" = uuid::Uuid, Path, description = "Webhook ID")), responses((status = 204), (status = 403, body = crate::error::ErrorResponse), (status = 404, body = crate::error::ErrorResponse)))]
+/// Delete a webhook. Pending deliveries are dropped with it.
+pub async fn delete_webhook(
+    member: GuildMember,
+    State(state): State<AppState>,
+    Path((_, webhook_id)): Path<(GuildId, uuid::Uuid)>,
+) -> Result<StatusCode, ServerError> {
+    member.require(Permissions::MANAGE_GUILD)?;
+
+    let deleted = sqlx::query("DELETE FROM guild_webhooks WHERE id = $1 AND guild_id = $2")
+        .bind(webhook_id)
+        .bind(member.guild_id)
+        .execute(&state.db)
+        .await
+        .map_err(db_err)?
+        .rows_affected();
+
+    if deleted == 0 {
+        return Err(ServerError(OpenConvError::NotFound));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Routes for guild webhooks. Mounted at /api/guilds/:guild_id/webhooks.
+pub fn routes() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route("/", axum::routing::get(list_webhooks).post(create_webhook))
+        .route("/{webhook_id}", axum::routing::delete(delete_webhook))
+}
+
+#[derive(sqlx::FromRow)]
+struct WebhookRow {
+    id: uuid::Uuid,
+    guild_id: GuildId,
+    url: String,
+    intents: i64,
+    created_by: UserId,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl WebhookRow {
+    fn into_response(self) -> WebhookResponse {
+        WebhookResponse {
+            id: self.id,
+            guild_id: self.guild_id,
+            url: self.url,
+            intents: self.intents as u64,
+            created_by: self.created_by,
+            created_at: self.created_at,
+            secret: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_build_without_panic() {
+        let _ = routes();
+    }
+
+    #[test]
+    fn validate_webhook_url_accepts_public_https() {
+        assert!(validate_webhook_url("https://hooks.example.com/openconv").is_ok());
+        assert!(validate_webhook_url("http://203.0.113.7:8080/hook").is_ok());
+    }
+
+    #[test]
+    fn validate_webhook_url_rejects_other_schemes() {
+        assert!(validate_webhook_url("ftp://example.com/hook").is_err());
+        assert!(validate_webhook_url("not a url").is_err());
+    }
+
+    #[test]
+    fn validate_webhook_url_rejects_internal_hosts() {
+        for url in [
+            "http://localhost/hook",
+            "http://api.localhost/hook",
+            "http://127.0.0.1/hook",
+            "http://10.0.0.5/hook",
+            "http://192.168.1.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+        ] {
+            assert!(
+                validate_webhook_url(url).is_err(),
+                "{url} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn generated_secrets_are_unique_hex() {
+        let a = generate_secret();
+        let b = generate_secret();
+        assert_eq!(a.len(), 64);
+        assert!(a.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(a, b);
+    }
+}
@@ -3,18 +3,25 @@ use axum::extract::{Query, State};
 use axum::response::Response;
 use axum::Json;
 use fred::prelude::*;
+use openconv_shared::api::gateway::GatewayIntents;
 use openconv_shared::error::OpenConvError;
 use openconv_shared::ids::{DeviceId, UserId};
 use serde::{Deserialize, Serialize};
 
 use crate::error::ServerError;
 use crate::extractors::auth::AuthUser;
+use crate::redis::RedisError;
 use crate::state::AppState;
 use crate::ws::connection::handle_connection;
 
 #[derive(Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
 pub struct WsQueryParams {
     pub ticket: String,
+    /// `GatewayIntents` bits selecting which guild events to receive. Defaults to all.
+    pub intents: Option<u64>,
+    /// Last journal sequence number the client handled; journaled events
+    /// after it are replayed.
+    pub resume_from: Option<u64>,
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
@@ -28,14 +35,23 @@ struct TicketData {
     device_id: DeviceId,
 }
 
-#[utoipa::path(post, path = "/api/ws/ticket", tag = "WebSocket", security(("bearer_auth" = [])), responses((status = 200, body = TicketResponse), (status = 401, body = crate::error::ErrorResponse)))]
+/// An outage is a retryable 503; anything else keeps its details out of
+/// the response.
+fn ticket_error(e: RedisError, message: &str) -> ServerError {
+    match e {
+        RedisError::Unavailable { .. } => e.into(),
+        RedisError::Command(_) => ServerError(OpenConvError::Internal(message.into())),
+    }
+}
+
+#[utoipa::path(post, path = "/api/ws/ticket", tag = "WebSocket", security(("bearer_auth" = [])), responses((status = 200, body = TicketResponse), (status = 401, body = crate::error::ErrorResponse), (status = 503, body = crate::error::ErrorResponse)))]
 /// POST /api/ws/ticket -- Issue a single-use WebSocket ticket.
 pub async fn create_ws_ticket(
     auth: AuthUser,
     State(state): State<AppState>,
 ) -> Result<Json<TicketResponse>, ServerError> {
     let ticket_id = uuid::Uuid::new_v4().to_string();
-    let key = format!("ws:ticket:{ticket_id}");
+    let key = state.redis.key(format_args!("ws:ticket:{ticket_id}"));
 
     let data = TicketData {
         user_id: auth.user_id,
@@ -47,35 +63,52 @@ pub async fn create_ws_ticket(
 
     state
         .redis
-        .set::<(), _, _>(&key, value.as_str(), Some(Expiration::EX(30)), None, false)
+        .guarded(state.redis.set::<(), _, _>(
+            &key,
+            value.as_str(),
+            Some(Expiration::EX(30)),
+            None,
+            false,
+        ))
         .await
         .map_err(|e| {
             tracing::error!(error = %e, "failed to store ws ticket in redis");
-            ServerError(OpenConvError::Internal("ticket storage failed".into()))
+            ticket_error(e, "ticket storage failed")
         })?;
 
     Ok(Json(TicketResponse { ticket: ticket_id }))
 }
 
-#[utoipa::path(get, path = "/ws", tag = "WebSocket", params(WsQueryParams), responses((status = 101, description = "WebSocket upgrade"), (status = 401, body = crate::error::ErrorResponse)))]
-/// GET /ws?ticket=<uuid> -- Upgrade to WebSocket.
+#[utoipa::path(get, path = "/ws", tag = "WebSocket", params(WsQueryParams), responses((status = 101, description = "WebSocket upgrade"), (status = 401, body = crate::error::ErrorResponse), (status = 503, body = crate::error::ErrorResponse)))]
+/// GET /ws?ticket=<uuid>&intents=<bits>&resume_from=<seq> -- Upgrade to WebSocket.
 pub async fn ws_upgrade(
     State(state): State<AppState>,
     Query(params): Query<WsQueryParams>,
     ws: WebSocketUpgrade,
 ) -> Result<Response, ServerError> {
+    // Shutting down: send the client to another node
+    if state.ws.is_draining() {
+        return Err(ServerError(OpenConvError::ServiceUnavailable(
+            "server is shutting down".into(),
+        )));
+    }
+
     // Validate ticket is a valid UUID
     if uuid::Uuid::parse_str(&params.ticket).is_err() {
         return Err(ServerError(OpenConvError::Unauthorized));
     }
 
-    let key = format!("ws:ticket:{}", params.ticket);
+    let key = state.redis.key(format_args!("ws:ticket:{}", params.ticket));
 
     // Atomic get-and-delete (single-use)
-    let data: Option<String> = state.redis.getdel(&key).await.map_err(|e| {
-        tracing::error!(error = %e, "failed to consume ws ticket from redis");
-        ServerError(OpenConvError::Internal("ticket validation failed".into()))
-    })?;
+    let data: Option<String> = state
+        .redis
+        .guarded(state.redis.getdel(&key))
+        .await
+        .map_err(|e| {
+            tracing::error!(error = %e, "failed to consume ws ticket from redis");
+            ticket_error(e, "ticket validation failed")
+        })?;
 
     let data = data.ok_or(ServerError(OpenConvError::Unauthorized))?;
 
@@ -87,7 +120,15 @@ pub async fn ws_upgrade(
     let user_id = ticket.user_id;
     let device_id = ticket.device_id;
 
-    Ok(ws.on_upgrade(move |socket| handle_connection(socket, state, user_id, device_id)))
+    let intents = params
+        .intents
+        .map(GatewayIntents::from_bits_truncate)
+        .unwrap_or_default();
+
+    let resume_from = params.resume_from;
+    Ok(ws.on_upgrade(move |socket| {
+        handle_connection(socket, state, user_id, device_id, intents, resume_from)
+    }))
 }
 
 #[cfg(test)]
@@ -120,6 +161,8 @@ mod tests {
         let json = r#"{"ticket": "some-uuid"}"#;
         let params: WsQueryParams = serde_json::from_str(json).unwrap();
         assert_eq!(params.ticket, "some-uuid");
+        assert!(params.intents.is_none());
+        assert!(params.resume_from.is_none());
     }
 
     #[test]
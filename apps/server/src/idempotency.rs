@@ -0,0 +1,235 @@
+//! Idempotency keys for retried writes.
+//!
+//! A client whose request timed out cannot tell whether it took effect.
+//! Retrying with the same `Idempotency-Key` makes the server replay the
+//! first outcome instead of acting twice. Keys are scoped to the user and
+//! remembered in Redis for [`TTL`].
+//!
+//! HTTP routes opt in through
+//! [`idempotency_middleware`](crate::middleware::idempotency::idempotency_middleware);
+//! gateway `SendMessage` frames carry the key in the frame itself.
+
+use std::time::Duration;
+
+use fred::interfaces::KeysInterface;
+use fred::types::{Expiration, SetOptions};
+use openconv_shared::error::OpenConvError;
+use openconv_shared::ids::{ChannelId, MessageId, UserId};
+
+use crate::redis::RedisPool;
+
+/// Request header carrying the key.
+pub const HEADER: &str = "idempotency-key";
+
+/// How long a completed request is remembered.
+pub const TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long a request may run before its key is released. Bounds how long
+/// a key stays blocked if the node handling it dies mid-request.
+const PENDING_TTL: Duration = Duration::from_secs(10 * 60);
+
+pub const MAX_KEY_LEN: usize = 255;
+
+/// Keys are 1–255 visible ASCII characters; clients typically send a UUID.
+pub fn validate_key(key: &str) -> Result<(), OpenConvError> {
+    if key.is_empty() || key.len() > MAX_KEY_LEN || !key.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err(OpenConvError::Validation(format!(
+            "Idempotency-Key must be 1 to {MAX_KEY_LEN} visible ASCII characters"
+        )));
+    }
+    Ok(())
+}
+
+fn request_key(user_id: UserId, key: &str) -> String {
+    format!("idempotency:{user_id}:{key}")
+}
+
+fn message_key(user_id: UserId, channel_id: ChannelId, key: &str) -> String {
+    format!("idempotency:{user_id}:message:{channel_id}:{key}")
+}
+
+/// A recorded HTTP response, replayed verbatim on retries.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    /// Base64 of the response body.
+    pub body: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+enum Entry {
+    /// The first request is still running.
+    Pending { fingerprint: String },
+    Done {
+        fingerprint: String,
+        response: StoredResponse,
+    },
+}
+
+/// What to do with a request carrying an idempotency key.
+#[derive(Debug, PartialEq)]
+pub enum Begin {
+    /// First use of the key: run the request, then [`complete`] or
+    /// [`release`].
+    Proceed,
+    /// An earlier request with this key has not finished yet.
+    InProgress,
+    /// The key was used for a different endpoint.
+    Mismatch,
+    /// An earlier request with this key finished; send its response again.
+    Replay(StoredResponse),
+}
+
+/// Claim `key` for a request identified by `fingerprint` (method and path),
+/// or report what an earlier request with the key did.
+pub async fn begin(
+    redis: &RedisPool,
+    user_id: UserId,
+    key: &str,
+    fingerprint: &str,
+) -> Result<Begin, fred::error::Error> {
+    let redis_key = redis.key(request_key(user_id, key));
+    let pending = serde_json::to_string(&Entry::Pending {
+        fingerprint: fingerprint.to_string(),
+    })
+    .expect("Entry serializes");
+
+    let claimed: Option<String> = redis
+        .set(
+            &redis_key,
+            pending,
+            Some(Expiration::EX(PENDING_TTL.as_secs() as i64)),
+            Some(SetOptions::NX),
+            false,
+        )
+        .await?;
+    if claimed.is_some() {
+        return Ok(Begin::Proceed);
+    }
+
+    let existing: Option<String> = redis.get(&redis_key).await?;
+    let entry = existing.and_then(|json| serde_json::from_str::<Entry>(&json).ok());
+    Ok(match entry {
+        Some(Entry::Done {
+            fingerprint: stored,
+            response,
+        }) => {
+            if stored == fingerprint {
+                Begin::Replay(response)
+            } else {
+                Begin::Mismatch
+            }
+        }
+        Some(Entry::Pending {
+            fingerprint: stored,
+        }) if stored != fingerprint => Begin::Mismatch,
+        // Still pending, or expired between the two commands
+        _ => Begin::InProgress,
+    })
+}
+
+/// Record the response to replay for `key`.
+pub async fn complete(
+    redis: &RedisPool,
+    user_id: UserId,
+    key: &str,
+    fingerprint: &str,
+    response: StoredResponse,
+) -> Result<(), fred::error::Error> {
+    let entry = serde_json::to_string(&Entry::Done {
+        fingerprint: fingerprint.to_string(),
+        response,
+    })
+    .expect("Entry serializes");
+    redis
+        .set::<(), _, _>(
+            redis.key(request_key(user_id, key)),
+            entry,
+            Some(Expiration::EX(TTL.as_secs() as i64)),
+            None,
+            false,
+        )
+        .await
+}
+
+/// Forget `key` after a failed request, so the client can retry it.
+pub async fn release(
+    redis: &RedisPool,
+    user_id: UserId,
+    key: &str,
+) -> Result<(), fred::error::Error> {
+    redis
+        .del::<(), _>(redis.key(request_key(user_id, key)))
+        .await
+}
+
+/// The message already sent to `channel_id` with `key`, if any.
+pub async fn sent_message(
+    redis: &RedisPool,
+    user_id: UserId,
+    channel_id: ChannelId,
+    key: &str,
+) -> Result<Option<MessageId>, fred::error::Error> {
+    let value: Option<String> = redis
+        .get(redis.key(message_key(user_id, channel_id, key)))
+        .await?;
+    Ok(value.and_then(|v| v.parse().ok()))
+}
+
+/// Remember that `key` produced `message_id`.
+pub async fn remember_message(
+    redis: &RedisPool,
+    user_id: UserId,
+    channel_id: ChannelId,
+    key: &str,
+    message_id: MessageId,
+) -> Result<(), fred::error::Error> {
+    redis
+        .set::<(), _, _>(
+            redis.key(message_key(user_id, channel_id, key)),
+            message_id.to_string(),
+            Some(Expiration::EX(TTL.as_secs() as i64)),
+            None,
+            false,
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_uuid_keys() {
+        assert!(validate_key(&uuid::Uuid::new_v4().to_string()).is_ok());
+        assert!(validate_key(&"k".repeat(MAX_KEY_LEN)).is_ok());
+    }
+
+    #[test]
+    fn rejects_empty_long_and_non_ascii_keys() {
+        assert!(validate_key("").is_err());
+        assert!(validate_key(&"k".repeat(MAX_KEY_LEN + 1)).is_err());
+        assert!(validate_key("has space").is_err());
+        assert!(validate_key("clé").is_err());
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let entry = Entry::Done {
+            fingerprint: "POST /api/guilds".into(),
+            response: StoredResponse {
+                status: 201,
+                content_type: Some("application/json".into()),
+                body: "e30=".into(),
+            },
+        };
+        let json = serde_json::to_string(&entry).unwrap();
+        assert!(json.contains(r#""state":"done""#), "{json}");
+        match serde_json::from_str(&json).unwrap() {
+            Entry::Done { response, .. } => assert_eq!(response.status, 201),
+            Entry::Pending { .. } => panic!("wrong state"),
+        }
+    }
+}
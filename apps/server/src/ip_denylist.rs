@@ -0,0 +1,335 @@
+//! IP denylist enforced before routing and rate limiting.
+//!
+//! Entries are CIDR ranges (a bare address is a single-host range). They
+//! come from `ip_denylist.entries` in config, which only change with a
+//! restart, and from the `ip_denylist` table, managed at runtime through
+//! `/api/admin/ip-denylist`. Each node caches the table in memory; a
+//! change takes effect on the node that made it at once and on the others
+//! at their next [`IpDenylistRefreshJob`] run.
+//!
+//! Blocked attempts are tallied in memory per address and entry, and the
+//! refresh job writes the tallies to `ip_denylist_blocks`. Logging every
+//! attempt as it happens would let a blocked client turn each request into
+//! a database write.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use arc_swap::ArcSwap;
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use openconv_shared::error::OpenConvError;
+
+use crate::error::ServerError;
+use crate::state::AppState;
+use crate::tasks::scheduler::{JobResult, PeriodicJob, Schedule};
+
+/// An IPv4 or IPv6 address range. Host bits are cleared on parse, so
+/// equal ranges compare and display equal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNet {
+    /// Whether `ip` is in the range. IPv4-mapped IPv6 addresses match IPv4
+    /// ranges.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                u32::from(ip) & v4_mask(self.prefix) == u32::from(net)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                u128::from(ip) & v6_mask(self.prefix) == u128::from(net)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn v4_mask(prefix: u8) -> u32 {
+    u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0)
+}
+
+fn v6_mask(prefix: u8) -> u128 {
+    u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0)
+}
+
+impl FromStr for IpNet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("invalid IP address in \"{s}\""))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| format!("invalid prefix length in \"{s}\""))?,
+            None => max,
+        };
+        let addr = match addr {
+            IpAddr::V4(a) => IpAddr::V4(Ipv4Addr::from(u32::from(a) & v4_mask(prefix))),
+            IpAddr::V6(a) => IpAddr::V6(Ipv6Addr::from(u128::from(a) & v6_mask(prefix))),
+        };
+        Ok(Self { addr, prefix })
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// Blocked attempts from one address against one entry since the last flush.
+#[derive(Debug, Clone, Copy)]
+struct BlockTally {
+    attempts: i64,
+    first_seen_at: chrono::DateTime<chrono::Utc>,
+    last_seen_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// The denylist a node enforces.
+pub struct IpDenylist {
+    configured: Vec<IpNet>,
+    stored: ArcSwap<Vec<IpNet>>,
+    blocked: Mutex<HashMap<(IpAddr, IpNet), BlockTally>>,
+}
+
+impl IpDenylist {
+    /// A denylist with the configured entries and none loaded from the
+    /// database yet.
+    pub fn new(configured: Vec<IpNet>) -> Self {
+        Self {
+            configured,
+            stored: ArcSwap::from_pointee(Vec::new()),
+            blocked: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The entry denying `ip`, if any.
+    pub fn matching(&self, ip: IpAddr) -> Option<IpNet> {
+        self.configured
+            .iter()
+            .chain(self.stored.load().iter())
+            .find(|net| net.contains(ip))
+            .copied()
+    }
+
+    /// Replace the entries loaded from the database.
+    pub fn set_stored(&self, entries: Vec<IpNet>) {
+        self.stored.store(Arc::new(entries));
+    }
+
+    /// Reload the unexpired entries from the database. Returns how many
+    /// were loaded.
+    pub async fn reload(&self, db: &sqlx::PgPool) -> Result<usize, sqlx::Error> {
+        let cidrs: Vec<String> = sqlx::query_scalar(
+            "SELECT cidr FROM ip_denylist WHERE expires_at IS NULL OR expires_at > NOW()",
+        )
+        .fetch_all(db)
+        .await?;
+        let entries: Vec<IpNet> = cidrs
+            .iter()
+            .filter_map(|cidr| match cidr.parse() {
+                Ok(net) => Some(net),
+                Err(e) => {
+                    tracing::warn!(%cidr, error = %e, "skipping invalid IP denylist entry");
+                    None
+                }
+            })
+            .collect();
+        let loaded = entries.len();
+        self.set_stored(entries);
+        Ok(loaded)
+    }
+
+    /// Count a blocked attempt. Returns true for the first attempt from
+    /// `ip` against `net` since the last flush.
+    fn record_block(&self, ip: IpAddr, net: IpNet, now: chrono::DateTime<chrono::Utc>) -> bool {
+        let mut blocked = self.blocked.lock().unwrap_or_else(|e| e.into_inner());
+        let tally = blocked.entry((ip, net)).or_insert(BlockTally {
+            attempts: 0,
+            first_seen_at: now,
+            last_seen_at: now,
+        });
+        tally.attempts += 1;
+        tally.last_seen_at = now;
+        tally.attempts == 1
+    }
+
+    fn take_blocked(&self) -> HashMap<(IpAddr, IpNet), BlockTally> {
+        std::mem::take(&mut *self.blocked.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Write the blocked attempt tallies to `ip_denylist_blocks`. Returns
+    /// the number of attempts written.
+    pub async fn flush_blocked(&self, db: &sqlx::PgPool) -> Result<u64, sqlx::Error> {
+        let blocked = self.take_blocked();
+        if blocked.is_empty() {
+            return Ok(0);
+        }
+        let mut ips = Vec::with_capacity(blocked.len());
+        let mut cidrs = Vec::with_capacity(blocked.len());
+        let mut attempts = Vec::with_capacity(blocked.len());
+        let mut first_seen = Vec::with_capacity(blocked.len());
+        let mut last_seen = Vec::with_capacity(blocked.len());
+        for ((ip, net), tally) in &blocked {
+            ips.push(ip.to_string());
+            cidrs.push(net.to_string());
+            attempts.push(tally.attempts);
+            first_seen.push(tally.first_seen_at);
+            last_seen.push(tally.last_seen_at);
+        }
+        sqlx::query(
+            "INSERT INTO ip_denylist_blocks (ip, cidr, attempts, first_seen_at, last_seen_at) \
+             SELECT * FROM UNNEST($1::text[], $2::text[], $3::bigint[], \
+                                  $4::timestamptz[], $5::timestamptz[])",
+        )
+        .bind(&ips)
+        .bind(&cidrs)
+        .bind(&attempts)
+        .bind(&first_seen)
+        .bind(&last_seen)
+        .execute(db)
+        .await?;
+        Ok(attempts.iter().sum::<i64>() as u64)
+    }
+}
+
+/// Reject requests from denied addresses with 403 before any other work.
+pub async fn ip_denylist_middleware(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Ok(ip) = crate::middleware::rate_limit::extract_client_ip(&req).parse::<IpAddr>() else {
+        return next.run(req).await;
+    };
+    let Some(net) = state.ip_denylist.matching(ip) else {
+        return next.run(req).await;
+    };
+
+    if state.ip_denylist.record_block(ip, net, chrono::Utc::now()) {
+        tracing::warn!(
+            %ip,
+            entry = %net,
+            method = %req.method(),
+            path = req.uri().path(),
+            "Blocked request from denylisted address"
+        );
+    }
+    ServerError(OpenConvError::Forbidden).into_response()
+}
+
+/// Reloads the denylist from the database and flushes blocked attempt
+/// tallies, on every node.
+pub struct IpDenylistRefreshJob {
+    pub db: sqlx::PgPool,
+    pub denylist: Arc<IpDenylist>,
+    pub refresh_seconds: u64,
+}
+
+#[async_trait::async_trait]
+impl PeriodicJob for IpDenylistRefreshJob {
+    fn name(&self) -> &'static str {
+        "ip_denylist_refresh"
+    }
+
+    fn schedule(&self) -> Schedule {
+        Schedule::every_secs(self.refresh_seconds)
+    }
+
+    fn per_node(&self) -> bool {
+        true
+    }
+
+    async fn run(&self) -> JobResult {
+        let flushed = self.denylist.flush_blocked(&self.db).await?;
+        self.denylist.reload(&self.db).await?;
+        Ok(flushed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_and_normalizes_ranges() {
+        let net: IpNet = "10.1.2.3/8".parse().unwrap();
+        assert_eq!(net.to_string(), "10.0.0.0/8");
+        assert_eq!(
+            "192.0.2.7".parse::<IpNet>().unwrap().to_string(),
+            "192.0.2.7/32"
+        );
+        assert_eq!(
+            "2001:db8::1/32".parse::<IpNet>().unwrap().to_string(),
+            "2001:db8::/32"
+        );
+        assert!("10.0.0.0/33".parse::<IpNet>().is_err());
+        assert!("not-an-ip".parse::<IpNet>().is_err());
+        assert!("10.0.0.0/".parse::<IpNet>().is_err());
+    }
+
+    #[test]
+    fn ranges_match_their_addresses() {
+        let net: IpNet = "203.0.113.0/24".parse().unwrap();
+        assert!(net.contains(ip("203.0.113.200")));
+        assert!(!net.contains(ip("203.0.114.1")));
+        // IPv4 clients seen through a dual-stack socket
+        assert!(net.contains(ip("::ffff:203.0.113.9")));
+
+        let v6: IpNet = "2001:db8::/32".parse().unwrap();
+        assert!(v6.contains(ip("2001:db8:ffff::1")));
+        assert!(!v6.contains(ip("203.0.113.9")));
+
+        let everything: IpNet = "0.0.0.0/0".parse().unwrap();
+        assert!(everything.contains(ip("198.51.100.1")));
+    }
+
+    #[test]
+    fn denylist_checks_configured_and_stored_entries() {
+        let denylist = IpDenylist::new(vec!["198.51.100.0/24".parse().unwrap()]);
+        assert!(denylist.matching(ip("198.51.100.5")).is_some());
+        assert!(denylist.matching(ip("192.0.2.1")).is_none());
+
+        denylist.set_stored(vec!["192.0.2.1".parse().unwrap()]);
+        assert_eq!(
+            denylist.matching(ip("192.0.2.1")).unwrap().to_string(),
+            "192.0.2.1/32"
+        );
+    }
+
+    #[test]
+    fn blocked_attempts_are_tallied_until_taken() {
+        let denylist = IpDenylist::new(Vec::new());
+        let net: IpNet = "192.0.2.0/24".parse().unwrap();
+        let now = chrono::Utc::now();
+        assert!(denylist.record_block(ip("192.0.2.1"), net, now));
+        assert!(!denylist.record_block(ip("192.0.2.1"), net, now));
+
+        let taken = denylist.take_blocked();
+        assert_eq!(taken[&(ip("192.0.2.1"), net)].attempts, 2);
+        assert!(denylist.take_blocked().is_empty());
+        assert!(denylist.record_block(ip("192.0.2.1"), net, now));
+    }
+}
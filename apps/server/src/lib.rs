@@ -1,18 +1,42 @@
+pub mod admin_cli;
+pub mod audit;
+pub mod automod;
+pub mod cli;
 pub mod config;
 pub mod crypto_verify;
+pub mod db;
+pub mod discovery;
 pub mod email;
 pub mod error;
+pub mod error_reporting;
+pub mod etag;
 pub mod extractors;
+#[cfg(feature = "federation")]
+pub mod federation;
 pub mod handlers;
+pub mod idempotency;
+pub mod ip_denylist;
 pub mod jwt;
+pub mod listen;
+pub mod live_config;
+pub mod member_events;
 pub mod middleware;
+pub mod oauth;
+pub mod one_time_code;
 pub mod openapi;
+pub mod permission_cache;
 pub mod permissions;
 pub mod redis;
+pub mod repo;
+pub mod revocation;
 pub mod router;
+pub mod scan;
+pub mod secret_token;
 pub mod shutdown;
 pub mod state;
 pub mod storage;
+pub mod streaming;
 pub mod tasks;
+pub mod tls;
 pub mod validation;
 pub mod ws;
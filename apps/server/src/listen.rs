@@ -0,0 +1,218 @@
+//! Binding and serving on several listeners at once.
+//!
+//! The server can listen on any mix of TCP addresses and Unix sockets, or
+//! on sockets passed in by systemd socket activation. TLS, when enabled,
+//! applies to TCP listeners; Unix sockets are for local reverse proxies
+//! and always speak plain HTTP.
+
+use std::future::{Future, IntoFuture};
+use std::sync::Arc;
+
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+use crate::config::{ListenAddr, ServerConfig};
+use crate::tls::{self, TlsServer};
+
+/// A bound socket ready to serve.
+pub enum Listener {
+    Tcp(tokio::net::TcpListener),
+    #[cfg(unix)]
+    Unix {
+        listener: tokio::net::UnixListener,
+        /// Socket file to remove on shutdown; `None` for inherited sockets.
+        path: Option<std::path::PathBuf>,
+    },
+}
+
+impl Listener {
+    /// Address for logs.
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => addr.to_string(),
+                Err(_) => "tcp".to_string(),
+            },
+            #[cfg(unix)]
+            Self::Unix { listener, .. } => match listener.local_addr() {
+                Ok(addr) => match addr.as_pathname() {
+                    Some(path) => format!("unix:{}", path.display()),
+                    None => "unix".to_string(),
+                },
+                Err(_) => "unix".to_string(),
+            },
+        }
+    }
+}
+
+/// Bind every configured address, or take the sockets systemd passed in.
+pub async fn bind_all(config: &ServerConfig) -> Result<Vec<Listener>, String> {
+    let inherited = inherited_listeners()?;
+    if !inherited.is_empty() {
+        tracing::info!(
+            count = inherited.len(),
+            "Using sockets from systemd socket activation"
+        );
+        return Ok(inherited);
+    }
+
+    let mut listeners = Vec::new();
+    for addr in config.listen_addrs()? {
+        let listener = match &addr {
+            ListenAddr::Tcp(addr) => tokio::net::TcpListener::bind(addr).await.map(Listener::Tcp),
+            #[cfg(unix)]
+            ListenAddr::Unix(path) => bind_unix(path, config.unix_socket_mode),
+            #[cfg(not(unix))]
+            ListenAddr::Unix(_) => unreachable!("rejected by config validation"),
+        };
+        listeners.push(listener.map_err(|e| format!("cannot listen on {addr}: {e}"))?);
+    }
+    Ok(listeners)
+}
+
+#[cfg(unix)]
+fn bind_unix(path: &std::path::Path, mode: Option<u32>) -> std::io::Result<Listener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    // A socket left behind by an unclean exit would make bind fail
+    if std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+        std::fs::remove_file(path)?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)?;
+    if let Some(mode) = mode {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    }
+    Ok(Listener::Unix {
+        listener,
+        path: Some(path.to_path_buf()),
+    })
+}
+
+/// Sockets passed by systemd (`LISTEN_FDS`), in order.
+fn inherited_listeners() -> Result<Vec<Listener>, String> {
+    let mut fds = listenfd::ListenFd::from_env();
+    let mut listeners = Vec::new();
+    for idx in 0..fds.len() {
+        let err = |e: std::io::Error| format!("inherited socket {idx}: {e}");
+        if let Ok(Some(listener)) = fds.take_tcp_listener(idx) {
+            listener.set_nonblocking(true).map_err(err)?;
+            let listener = tokio::net::TcpListener::from_std(listener).map_err(err)?;
+            listeners.push(Listener::Tcp(listener));
+            continue;
+        }
+        #[cfg(unix)]
+        if let Some(listener) = fds.take_unix_listener(idx).map_err(err)? {
+            listener.set_nonblocking(true).map_err(err)?;
+            let listener = tokio::net::UnixListener::from_std(listener).map_err(err)?;
+            listeners.push(Listener::Unix {
+                listener,
+                path: None,
+            });
+            continue;
+        }
+        return Err(format!(
+            "inherited socket {idx} is not a TCP or Unix stream socket"
+        ));
+    }
+    Ok(listeners)
+}
+
+/// Serve `app` on every listener until `shutdown` resolves, then let open
+/// connections finish. If one listener fails, the others are shut down
+/// too and its error is returned.
+pub async fn serve_all(
+    listeners: Vec<Listener>,
+    app: axum::Router,
+    tls: Option<TlsServer>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    let (stop_tx, stop_rx) = watch::channel(false);
+    let stop_tx = Arc::new(stop_tx);
+    let signal_tx = stop_tx.clone();
+    tokio::spawn(async move {
+        shutdown.await;
+        let _ = signal_tx.send(true);
+    });
+    let stopped = move || {
+        let mut stop_rx = stop_rx.clone();
+        async move {
+            let _ = stop_rx.wait_for(|stop| *stop).await;
+        }
+    };
+
+    let mut servers = JoinSet::new();
+    for listener in listeners {
+        let name = listener.describe();
+        let app = app.clone();
+        match (listener, &tls) {
+            (Listener::Tcp(listener), Some(server)) => {
+                tracing::info!("Server listening on https://{name}");
+                servers.spawn(tls::serve(server.clone(), listener, app, stopped()));
+            }
+            (Listener::Tcp(listener), None) => {
+                tracing::info!("Server listening on {name}");
+                servers.spawn(
+                    axum::serve(listener, app)
+                        .with_graceful_shutdown(stopped())
+                        .into_future(),
+                );
+            }
+            #[cfg(unix)]
+            (Listener::Unix { listener, path }, _) => {
+                tracing::info!("Server listening on {name}");
+                let serve = axum::serve(listener, app).with_graceful_shutdown(stopped());
+                servers.spawn(async move {
+                    let result = serve.await;
+                    if let Some(path) = path {
+                        let _ = std::fs::remove_file(path);
+                    }
+                    result
+                });
+            }
+        }
+    }
+
+    let mut result = Ok(());
+    while let Some(joined) = servers.join_next().await {
+        let outcome = joined.unwrap_or_else(|e| Err(std::io::Error::other(e)));
+        if let Err(e) = outcome {
+            tracing::error!(error = %e, "listener failed, shutting down");
+            let _ = stop_tx.send(true);
+            if result.is_ok() {
+                result = Err(e);
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn binds_tcp_and_unix_listeners() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("openconv.sock");
+        // Stale socket from an earlier run is replaced
+        drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+
+        let config = ServerConfig {
+            listen: vec!["127.0.0.1:0".into(), format!("unix:{}", socket.display())],
+            unix_socket_mode: Some(0o660),
+            ..ServerConfig::default()
+        };
+        let listeners = bind_all(&config).await.unwrap();
+        assert_eq!(listeners.len(), 2);
+        assert!(listeners[0].describe().starts_with("127.0.0.1:"));
+        assert_eq!(
+            listeners[1].describe(),
+            format!("unix:{}", socket.display())
+        );
+        let mode = std::fs::metadata(&socket).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o660);
+    }
+}
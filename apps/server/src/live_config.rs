@@ -0,0 +1,226 @@
+//! Settings that change without a restart.
+//!
+//! Rate limits, the log level and feature flags are read through
+//! [`LiveConfig`] rather than `AppState::config`. A reload reads the config
+//! file and environment the same way startup does and swaps the new values
+//! in atomically. Everything else keeps its startup value until restart.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+use arc_swap::ArcSwap;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+use crate::config::{FeatureFlags, RateLimitConfig, ServerConfig};
+
+/// Handle for replacing the process-wide log filter.
+pub type LogFilterHandle = reload::Handle<EnvFilter, Registry>;
+
+/// The part of [`ServerConfig`] applied on reload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeSettings {
+    pub rate_limit: RateLimitConfig,
+    pub log_level: String,
+    pub features: FeatureFlags,
+}
+
+impl RuntimeSettings {
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Self {
+            rate_limit: config.rate_limit.clone(),
+            log_level: config.log_level.clone(),
+            features: config.features.clone(),
+        }
+    }
+
+    /// Names of the settings that differ from `other`.
+    fn changed_from(&self, other: &Self) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.rate_limit != other.rate_limit {
+            changed.push("rate_limit");
+        }
+        if self.log_level != other.log_level {
+            changed.push("log_level");
+        }
+        if self.features != other.features {
+            changed.push("features");
+        }
+        changed
+    }
+}
+
+/// Size and modification time, to notice edits without reading the file.
+pub(crate) type FileStamp = (SystemTime, u64);
+
+pub(crate) fn file_stamp(path: &Path) -> Option<FileStamp> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+/// Current [`RuntimeSettings`], shared by every handler and middleware.
+pub struct LiveConfig {
+    current: ArcSwap<RuntimeSettings>,
+    source: Option<PathBuf>,
+    log_filter: Option<LogFilterHandle>,
+    /// Stamp of `source` at the last reload attempt. The lock also keeps
+    /// two reloads from interleaving.
+    last_stamp: Mutex<Option<FileStamp>>,
+}
+
+impl LiveConfig {
+    /// Settings from `config`, without a file to reload from.
+    pub fn new(config: &ServerConfig) -> Self {
+        Self {
+            current: ArcSwap::from_pointee(RuntimeSettings::from_config(config)),
+            source: None,
+            log_filter: None,
+            last_stamp: Mutex::new(None),
+        }
+    }
+
+    /// Reload from the file the server started with.
+    pub fn with_source(mut self, source: Option<PathBuf>) -> Self {
+        self.last_stamp = Mutex::new(source.as_deref().and_then(file_stamp));
+        self.source = source;
+        self
+    }
+
+    /// Apply `log_level` changes to the running subscriber. Leave unset
+    /// when RUST_LOG chose the filter, which then stays in charge.
+    pub fn with_log_filter(mut self, handle: LogFilterHandle) -> Self {
+        self.log_filter = Some(handle);
+        self
+    }
+
+    /// Snapshot of the current settings.
+    pub fn settings(&self) -> Arc<RuntimeSettings> {
+        self.current.load_full()
+    }
+
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    /// Load the configuration again and apply the reloadable settings.
+    /// Returns the names of the settings that changed. An invalid file is
+    /// rejected as a whole and the current settings stay in place.
+    pub fn reload(&self) -> Result<Vec<&'static str>, String> {
+        let mut last_stamp = self
+            .last_stamp
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *last_stamp = self.source.as_deref().and_then(file_stamp);
+        self.load_and_apply()
+    }
+
+    /// [`LiveConfig::reload`] if the config file changed since the last
+    /// attempt, else `Ok(None)`. A broken edit is reported once, not on
+    /// every check.
+    pub fn reload_if_changed(&self) -> Result<Option<Vec<&'static str>>, String> {
+        let Some(source) = self.source.as_deref() else {
+            return Ok(None);
+        };
+        let mut last_stamp = self
+            .last_stamp
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let stamp = file_stamp(source);
+        if stamp == *last_stamp {
+            return Ok(None);
+        }
+        *last_stamp = stamp;
+        self.load_and_apply().map(Some)
+    }
+
+    fn load_and_apply(&self) -> Result<Vec<&'static str>, String> {
+        let loaded = ServerConfig::load_from(self.source.as_deref()).map_err(|e| e.to_string())?;
+        self.apply(RuntimeSettings::from_config(&loaded.config))
+    }
+
+    fn apply(&self, settings: RuntimeSettings) -> Result<Vec<&'static str>, String> {
+        let changed = settings.changed_from(&self.current.load());
+        if changed.contains(&"log_level") {
+            let filter = EnvFilter::try_new(&settings.log_level)
+                .map_err(|e| format!("invalid log_level {:?}: {e}", settings.log_level))?;
+            if let Some(handle) = &self.log_filter {
+                handle
+                    .reload(filter)
+                    .map_err(|e| format!("cannot apply log_level: {e}"))?;
+            }
+        }
+        self.current.store(Arc::new(settings));
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    const BASE_TOML: &str = "database_url = \"postgresql://localhost/db\"\n\
+                             [jwt]\nprivate_key_pem = \"k\"\npublic_key_pem = \"p\"\n";
+
+    #[test]
+    fn apply_swaps_settings_and_reports_changes() {
+        let live = LiveConfig::new(&ServerConfig::default());
+        let mut settings = (*live.settings()).clone();
+        settings.rate_limit.guild_per_user_per_minute = 99;
+        settings.features.registration = false;
+
+        let changed = live.apply(settings).unwrap();
+        assert_eq!(changed, vec!["rate_limit", "features"]);
+        assert_eq!(live.settings().rate_limit.guild_per_user_per_minute, 99);
+        assert!(!live.settings().features.registration);
+    }
+
+    #[test]
+    fn apply_rejects_invalid_log_level() {
+        let live = LiveConfig::new(&ServerConfig::default());
+        let mut settings = (*live.settings()).clone();
+        settings.log_level = "info,openconv=loud".into();
+        settings.features.guild_creation = false;
+
+        assert!(live.apply(settings).is_err());
+        assert_eq!(live.settings().log_level, "info");
+        assert!(live.settings().features.guild_creation);
+    }
+
+    #[test]
+    #[serial]
+    fn reload_if_changed_picks_up_file_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, BASE_TOML).unwrap();
+        let config = ServerConfig::load_from(Some(&path)).unwrap().config;
+        let live = LiveConfig::new(&config).with_source(Some(path.clone()));
+
+        assert_eq!(live.reload_if_changed(), Ok(None));
+
+        std::fs::write(
+            &path,
+            format!("{BASE_TOML}[rate_limit]\nauth_per_ip_per_minute = 120\n"),
+        )
+        .unwrap();
+        assert_eq!(live.reload_if_changed(), Ok(Some(vec!["rate_limit"])));
+        assert_eq!(live.settings().rate_limit.auth_per_ip_per_minute, 120);
+        assert_eq!(live.reload_if_changed(), Ok(None));
+    }
+
+    #[test]
+    #[serial]
+    fn reload_keeps_settings_when_file_is_broken() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, BASE_TOML).unwrap();
+        let config = ServerConfig::load_from(Some(&path)).unwrap().config;
+        let live = LiveConfig::new(&config).with_source(Some(path.clone()));
+
+        std::fs::write(&path, "[rate_limit\n").unwrap();
+        assert!(live.reload_if_changed().is_err());
+        // Reported once; the next check waits for another edit
+        assert_eq!(live.reload_if_changed(), Ok(None));
+        assert_eq!(live.settings().rate_limit, RateLimitConfig::default());
+    }
+}
@@ -1,29 +1,86 @@
 use std::sync::Arc;
 
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{reload, EnvFilter};
 
-use openconv_server::config::ServerConfig;
-use openconv_server::email::{EmailService, MockEmailService, SmtpEmailService};
+use openconv_server::cli::{self, Command, USAGE};
+use openconv_server::config::{LoadedConfig, ServerConfig};
+use openconv_server::db::{ReadReplica, ReplicaHealthJob};
+use openconv_server::email::create_email_service;
+use openconv_server::email::outbox::OutboxKey;
+use openconv_server::error_reporting;
+use openconv_server::ip_denylist::{IpDenylist, IpDenylistRefreshJob};
 use openconv_server::jwt::JwtService;
+use openconv_server::listen;
+use openconv_server::live_config::LiveConfig;
 use openconv_server::redis::create_redis_pool;
 use openconv_server::router::build_router;
+use openconv_server::scan::create_scanner;
 use openconv_server::shutdown::shutdown_signal;
 use openconv_server::state::AppState;
-use openconv_server::storage::create_object_store;
+use openconv_server::storage::{check_object_store, create_object_store};
+use openconv_server::tasks::cleanup::RefreshTokenCleanupJob;
+use openconv_server::tasks::config_watch::ConfigWatchJob;
+use openconv_server::tasks::email_delivery::EmailDeliveryJob;
+use openconv_server::tasks::file_cleanup::{
+    BlobCleanupJob, ExpiredFileCleanupJob, OrphanFileCleanupJob, UploadSessionCleanupJob,
+};
+use openconv_server::tasks::guild_cleanup::GuildCleanupJob;
+use openconv_server::tasks::inactive_prune::InactivePruneJob;
+use openconv_server::tasks::log_retention::LogRetentionJob;
+use openconv_server::tasks::member_prune::MemberPruneJob;
+use openconv_server::tasks::message_retention::MessageRetentionJob;
+use openconv_server::tasks::scheduler::{JobRegistry, Schedule, Scheduler};
+use openconv_server::tasks::upload_scan::UploadScanJob;
+use openconv_server::tasks::webhook_delivery::WebhookDeliveryJob;
+use openconv_server::tls::{self, CertReloadJob, TlsServer};
 use openconv_server::ws::state::WsState;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenvy::dotenv().ok();
 
-    let config = ServerConfig::load()?;
+    let config_path = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(Command::Serve { config }) => config,
+        Ok(Command::ConfigCheck { config }) => {
+            std::process::exit(check_config(config.as_deref()));
+        }
+        Ok(Command::Help) => {
+            println!("{USAGE}");
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
 
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.log_level)),
-        )
+    let LoadedConfig {
+        config,
+        source,
+        unknown_keys,
+    } = ServerConfig::load_from(config_path.as_deref())?;
+
+    // RUST_LOG wins over `log_level`; only the latter follows reloads
+    let env_filter = EnvFilter::try_from_default_env().ok();
+    let log_level_reloadable = env_filter.is_none();
+    let (log_filter, log_filter_handle) =
+        reload::Layer::new(env_filter.unwrap_or_else(|| EnvFilter::new(&config.log_level)));
+    tracing_subscriber::registry()
+        .with(log_filter)
+        .with(tracing_subscriber::fmt::layer())
         .init();
 
+    match &source {
+        Some(path) => tracing::info!(path = %path.display(), "Configuration loaded"),
+        None => tracing::info!("No config file found, using defaults and environment"),
+    }
+    for key in &unknown_keys {
+        tracing::warn!(key, "Unknown config key ignored");
+    }
+
+    let _error_reporting = error_reporting::init(&config.error_reporting)?;
+
     let pool = sqlx::postgres::PgPoolOptions::new()
         .max_connections(config.max_db_connections)
         .connect(&config.database_url)
@@ -31,130 +88,228 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     sqlx::migrate!().run(&pool).await?;
 
+    // Hashes follow the configured salt, so a changed salt is applied here
+    if config.discovery.is_enabled() {
+        let updated = openconv_server::repo::users::refresh_discovery_hashes(
+            &pool,
+            &config.discovery.salt,
+            None,
+        )
+        .await?;
+        tracing::info!(updated, "Contact discovery hashes refreshed");
+    }
+
+    let db_replica = match &config.database_replica_url {
+        Some(url) => {
+            tracing::info!("Read replica configured");
+            Some(Arc::new(ReadReplica::connect_lazy(
+                url,
+                config.max_db_connections,
+            )?))
+        }
+        None => None,
+    };
+
     let redis = create_redis_pool(&config.redis).await?;
     tracing::info!("Redis connected");
 
     let jwt = Arc::new(JwtService::new(&config.jwt)?);
     tracing::info!("JWT service initialized");
 
-    let email: Arc<dyn EmailService> = if config.email.smtp_host.is_empty() {
-        tracing::warn!("SMTP not configured, using mock email service");
-        Arc::new(MockEmailService::new())
-    } else {
-        Arc::new(SmtpEmailService::new(&config.email)?)
-    };
+    let email = create_email_service(&config.email, &config.branding)?;
+    tracing::info!(backend = ?config.email.backend, "Email service initialized");
 
     let object_store = create_object_store(&config.file_storage)?;
+    check_object_store(object_store.as_ref())
+        .await
+        .map_err(|e| format!("object store health check failed: {e}"))?;
     tracing::info!(backend = %config.file_storage.backend, "Object store initialized");
 
-    // Shutdown coordination: cleanup task stops when the server does
+    let scanner = create_scanner(&config.file_storage.scan);
+
+    let mut live = LiveConfig::new(&config).with_source(source.clone());
+    if log_level_reloadable {
+        live = live.with_log_filter(log_filter_handle);
+    }
+    let live = Arc::new(live);
+
+    let tls = tls::setup(&config.tls).await?;
+    if tls.is_some() {
+        tracing::info!(mode = ?config.tls.mode, "TLS enabled");
+    }
+    let listeners = listen::bind_all(&config).await?;
+
+    // Background jobs stop when the server does
     let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let jobs = Arc::new(JobRegistry::default());
+    let mut scheduler = Scheduler::new(jobs.clone()).with_redis_lock(redis.clone());
+    let schedules = &config.schedules;
 
-    let cleanup_pool = pool.clone();
-    let mut cleanup_shutdown_rx = shutdown_rx.clone();
-    tokio::spawn(async move {
-        loop {
-            match openconv_server::tasks::cleanup::cleanup_expired_refresh_tokens(&cleanup_pool)
-                .await
-            {
-                Ok(count) => {
-                    if count > 0 {
-                        tracing::info!("Cleaned up {count} expired refresh tokens");
-                    }
-                }
-                Err(e) => tracing::error!("Refresh token cleanup failed: {e}"),
-            }
-            tokio::select! {
-                _ = tokio::time::sleep(std::time::Duration::from_secs(3600)) => {}
-                _ = cleanup_shutdown_rx.changed() => {
-                    tracing::info!("Refresh token cleanup task shutting down");
-                    break;
-                }
-            }
-        }
+    scheduler.add(RefreshTokenCleanupJob {
+        pool: pool.clone(),
+        schedule: Schedule::cron(&schedules.refresh_token_cleanup)?,
     });
-
-    let file_cleanup_pool = pool.clone();
-    let file_cleanup_store = object_store.clone();
-    let mut file_cleanup_shutdown_rx = shutdown_rx.clone();
-
-    let guild_cleanup_pool = pool.clone();
-    let guild_cleanup_store = object_store.clone();
-    let mut guild_cleanup_shutdown_rx = shutdown_rx.clone();
-    tokio::spawn(async move {
-        loop {
-            match openconv_server::tasks::guild_cleanup::cleanup_expired_guilds(
-                &guild_cleanup_pool,
-                &*guild_cleanup_store,
-            )
-            .await
-            {
-                Ok(count) => {
-                    if count > 0 {
-                        tracing::info!("Cleaned up {count} expired guilds");
-                    }
-                }
-                Err(e) => tracing::error!("Guild cleanup failed: {e}"),
-            }
-            tokio::select! {
-                _ = tokio::time::sleep(std::time::Duration::from_secs(3600)) => {}
-                _ = guild_cleanup_shutdown_rx.changed() => {
-                    tracing::info!("Guild cleanup task shutting down");
-                    break;
-                }
-            }
-        }
+    scheduler.add(GuildCleanupJob {
+        pool: pool.clone(),
+        store: object_store.clone(),
+        schedule: Schedule::cron(&schedules.guild_cleanup)?,
     });
-    tokio::spawn(async move {
-        loop {
-            match openconv_server::tasks::file_cleanup::cleanup_orphan_files(
-                &file_cleanup_pool,
-                &*file_cleanup_store,
-            )
-            .await
-            {
-                Ok(count) => {
-                    if count > 0 {
-                        tracing::info!(count, "Orphan file cleanup completed");
-                    }
-                }
-                Err(e) => tracing::error!("Orphan file cleanup failed: {e}"),
-            }
-            tokio::select! {
-                _ = tokio::time::sleep(std::time::Duration::from_secs(3600)) => {}
-                _ = file_cleanup_shutdown_rx.changed() => {
-                    tracing::info!("File cleanup task shutting down");
-                    break;
-                }
-            }
-        }
+    scheduler.add(OrphanFileCleanupJob {
+        pool: pool.clone(),
+        store: object_store.clone(),
+        schedule: Schedule::cron(&schedules.orphan_file_cleanup)?,
+    });
+    scheduler.add(UploadSessionCleanupJob {
+        pool: pool.clone(),
+        store: object_store.clone(),
+        schedule: Schedule::cron(&schedules.upload_session_cleanup)?,
+    });
+    scheduler.add(ExpiredFileCleanupJob {
+        pool: pool.clone(),
+        store: object_store.clone(),
+        schedule: Schedule::cron(&schedules.expired_file_cleanup)?,
+    });
+    scheduler.add(BlobCleanupJob {
+        pool: pool.clone(),
+        store: object_store.clone(),
+        schedule: Schedule::cron(&schedules.blob_cleanup)?,
+    });
+    scheduler.add(LogRetentionJob {
+        pool: pool.clone(),
+        store: object_store.clone(),
+        config: config.log_retention.clone(),
+        schedule: Schedule::cron(&schedules.log_retention)?,
+    });
+    scheduler.add(MemberPruneJob {
+        pool: pool.clone(),
+        store: object_store.clone(),
     });
+    scheduler.add(InactivePruneJob {
+        pool: pool.clone(),
+        redis: redis.clone(),
+    });
+    let outbox_key = OutboxKey::from_config(&config);
+    scheduler.add(EmailDeliveryJob {
+        pool: pool.clone(),
+        email: email.clone(),
+        key: outbox_key.clone(),
+    });
+    scheduler.add(WebhookDeliveryJob {
+        pool: pool.clone(),
+        client: reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?,
+    });
+    #[cfg(feature = "federation")]
+    if config.federation.is_active() {
+        scheduler.add(openconv_server::federation::outbox::FederationDeliveryJob {
+            pool: pool.clone(),
+            keys: openconv_server::federation::signing::FederationKeys::from_config(
+                &config.federation,
+            )?,
+        });
+    }
+    if source.is_some() {
+        scheduler.add(ConfigWatchJob { live: live.clone() });
+    }
+    // Runtime-managed denylist entries are loaded before serving, so a
+    // restart never opens a window for denied addresses
+    let ip_denylist = Arc::new(IpDenylist::new(config.ip_denylist.ranges()));
+    ip_denylist.reload(&pool).await?;
+    scheduler.add(IpDenylistRefreshJob {
+        db: pool.clone(),
+        denylist: ip_denylist.clone(),
+        refresh_seconds: config.ip_denylist.refresh_seconds,
+    });
+    if let Some(replica) = &db_replica {
+        scheduler.add(ReplicaHealthJob {
+            replica: replica.clone(),
+        });
+    }
+    if let Some(TlsServer::Files(rustls)) = &tls {
+        scheduler.add(CertReloadJob::new(rustls.clone(), &config.tls));
+    }
+    if scanner.enabled() {
+        scheduler.add(UploadScanJob {
+            pool: pool.clone(),
+            store: object_store.clone(),
+            scanner: scanner.clone(),
+        });
+    }
+    let message_retention = Schedule::cron(&schedules.message_retention)?;
 
     let ws = Arc::new(WsState::new());
+    let drain_window = std::time::Duration::from_secs(config.gateway.drain_seconds);
 
-    let addr = format!("{}:{}", config.host, config.port);
     let state = AppState {
         db: pool,
+        db_replica,
         config: Arc::new(config),
+        live,
         redis,
         jwt,
         email,
+        outbox_key,
         object_store,
+        scanner,
+        jobs,
         ws: ws.clone(),
+        ip_denylist,
     };
-    let app = build_router(state);
 
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    tracing::info!("Server listening on {addr}");
+    // Purge notices go out through the gateway, so this job needs the state
+    scheduler.add(MessageRetentionJob {
+        state: state.clone(),
+        schedule: message_retention,
+    });
+    let scheduler = scheduler.spawn(shutdown_rx);
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    let app = build_router(state);
+
+    // Gateway clients move to other nodes before the listeners stop
+    let drain_ws = ws.clone();
+    let shutdown = async move {
+        shutdown_signal().await;
+        drain_ws.drain(drain_window).await;
+    };
+    listen::serve_all(listeners, app, tls, shutdown).await?;
 
+    // Let in-progress job runs finish, within reason
     let _ = shutdown_tx.send(true);
+    if tokio::time::timeout(std::time::Duration::from_secs(30), scheduler.join())
+        .await
+        .is_err()
+    {
+        tracing::warn!("Background jobs still running after 30s, exiting anyway");
+    }
 
     // Close all WebSocket connections gracefully
     ws.shutdown_all().await;
 
     Ok(())
 }
+
+/// `config check`: load and validate the configuration without starting
+/// anything. Returns the process exit code.
+fn check_config(path: Option<&std::path::Path>) -> i32 {
+    let loaded = match ServerConfig::load_from(path) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("Configuration invalid: {e}");
+            return 1;
+        }
+    };
+    if let Err(e) = JwtService::new(&loaded.config.jwt) {
+        eprintln!("Configuration invalid: {e}");
+        return 1;
+    }
+
+    match &loaded.source {
+        Some(path) => println!("Configuration OK: {}", path.display()),
+        None => println!("Configuration OK: defaults and environment (no config file)"),
+    }
+    for key in &loaded.unknown_keys {
+        println!("warning: unknown key ignored: {key}");
+    }
+    0
+}
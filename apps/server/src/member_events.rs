@@ -0,0 +1,77 @@
+//! Guild membership events.
+//!
+//! Each membership change is published to connected WebSocket clients through
+//! the guild broadcast, and queued in `webhook_deliveries` for every guild
+//! webhook subscribed to `GUILD_MEMBERS`. `tasks::webhook_delivery` drains the
+//! queue. Both transports carry the same `MemberEvent` body.
+//!
+//! Publishing is best effort and happens after the change has committed, so a
+//! failure here never rolls back the membership change itself. It also drops
+//! the member's cached permissions, since every event kind can change them.
+//! Members who leave or are removed also drop out of voice. With federation
+//! active, joins and leaves are also forwarded to other instances with
+//! members in the guild. Gateway events are journaled so members who
+//! reconnect shortly after still receive them.
+
+use openconv_shared::api::gateway::{GatewayIntents, MemberEvent, MemberEventKind};
+
+use crate::permission_cache;
+use crate::state::AppState;
+use crate::ws::types::ServerMessage;
+
+/// Publish a membership event to gateway subscribers and guild webhooks.
+pub async fn publish(state: &AppState, event: MemberEvent) {
+    permission_cache::invalidate_member(&state.redis, event.user_id, event.guild_id).await;
+    state
+        .ws
+        .permission_cache
+        .invalidate(event.user_id, event.guild_id);
+
+    if matches!(
+        event.kind,
+        MemberEventKind::Leave | MemberEventKind::Kick | MemberEventKind::Ban
+    ) {
+        crate::ws::voice::clear_member(state, event.guild_id, event.user_id).await;
+    }
+
+    if let Err(e) = enqueue_webhook_deliveries(&state.db, &event).await {
+        tracing::error!(error = %e, guild_id = %event.guild_id, "failed to enqueue webhook deliveries");
+    }
+
+    #[cfg(feature = "federation")]
+    if state.config.federation.is_active() {
+        crate::federation::outbox::forward_member_event(state, &event).await;
+    }
+
+    let guild_id = event.guild_id;
+    crate::ws::journal::publish_to_guild(
+        state,
+        guild_id,
+        ServerMessage::GuildMemberEvent { event },
+    )
+    .await;
+}
+
+/// Queue a delivery of `event` for each webhook in its guild that subscribed
+/// to member events. Returns the number of deliveries queued.
+pub async fn enqueue_webhook_deliveries<'e, E>(
+    executor: E,
+    event: &MemberEvent,
+) -> Result<u64, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let payload = serde_json::to_value(event).expect("MemberEvent serializes");
+    let result = sqlx::query(
+        "INSERT INTO webhook_deliveries (webhook_id, event_type, payload) \
+         SELECT id, $2, $3 FROM guild_webhooks \
+         WHERE guild_id = $1 AND intents & $4 <> 0",
+    )
+    .bind(event.guild_id)
+    .bind(event.kind.event_name())
+    .bind(payload)
+    .bind(GatewayIntents::GUILD_MEMBERS.bits() as i64)
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected())
+}
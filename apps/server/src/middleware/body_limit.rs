@@ -0,0 +1,54 @@
+//! Uniform 413 responses.
+//!
+//! Body limits are enforced by `DefaultBodyLimit` when an extractor reads
+//! the body, and its rejection is plain text. This rewrites any such 413
+//! into the usual JSON error body so clients handle it like every other
+//! `ServerError`.
+
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use openconv_shared::error::OpenConvError;
+
+use crate::error::ServerError;
+
+pub async fn typed_payload_too_large(response: Response) -> Response {
+    if response.status() != StatusCode::PAYLOAD_TOO_LARGE {
+        return response;
+    }
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json"));
+    if is_json {
+        return response;
+    }
+    ServerError(OpenConvError::PayloadTooLarge(
+        "request body too large".into(),
+    ))
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn plain_413_becomes_json() {
+        let plain = (StatusCode::PAYLOAD_TOO_LARGE, "length limit exceeded").into_response();
+        let response = typed_payload_too_large(plain).await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    }
+
+    #[tokio::test]
+    async fn other_responses_pass_through() {
+        let ok = (StatusCode::OK, "fine").into_response();
+        let response = typed_payload_too_large(ok).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain"));
+    }
+}
@@ -0,0 +1,158 @@
+use axum::body::Body;
+use axum::extract::{OriginalUri, Request, State};
+use axum::http::{header, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use base64::Engine;
+use openconv_shared::error::OpenConvError;
+
+use crate::error::ServerError;
+use crate::idempotency::{self, Begin, StoredResponse};
+use crate::state::AppState;
+
+/// Response header set when a response is a replay of an earlier request.
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Make `POST` requests carrying an `Idempotency-Key` header safe to retry.
+///
+/// The first request with a key runs normally and its successful response
+/// is stored; later requests with the same key get that response back
+/// without running the handler. Failed requests release the key so the
+/// client can try again. Requests without the header, or without a valid
+/// bearer token, pass straight through.
+///
+/// Redis errors are logged and the request runs without protection.
+pub async fn idempotency_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() != Method::POST {
+        return next.run(request).await;
+    }
+    let Some(key) = request.headers().get(idempotency::HEADER) else {
+        return next.run(request).await;
+    };
+    let key = match key.to_str() {
+        Ok(key) => key.to_string(),
+        Err(_) => {
+            return ServerError(OpenConvError::Validation(
+                "Idempotency-Key must be visible ASCII".into(),
+            ))
+            .into_response()
+        }
+    };
+    if let Err(e) = idempotency::validate_key(&key) {
+        return ServerError(e).into_response();
+    }
+    let Some(user_id) = super::bearer_user_id(&state, request.headers()) else {
+        return next.run(request).await;
+    };
+
+    // Routers are nested, so only the original URI has the full path
+    let path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or_else(|| request.uri().path(), |uri| uri.path());
+    let fingerprint = format!("{} {path}", request.method());
+    match idempotency::begin(&state.redis, user_id, &key, &fingerprint).await {
+        Ok(Begin::Proceed) => {}
+        Ok(Begin::InProgress) => {
+            return ServerError(OpenConvError::Conflict(
+                "a request with this Idempotency-Key is still in progress".into(),
+            ))
+            .into_response()
+        }
+        Ok(Begin::Mismatch) => {
+            return ServerError(OpenConvError::Validation(
+                "Idempotency-Key was already used for a different request".into(),
+            ))
+            .into_response()
+        }
+        Ok(Begin::Replay(stored)) => return replay(stored),
+        Err(e) => {
+            tracing::warn!(error = %e, "idempotency key lookup failed");
+            return next.run(request).await;
+        }
+    }
+
+    let response = next.run(request).await;
+
+    if !response.status().is_success() {
+        if let Err(e) = idempotency::release(&state.redis, user_id, &key).await {
+            tracing::warn!(error = %e, "failed to release idempotency key");
+        }
+        return response;
+    }
+
+    // Responses on these routes are small JSON documents
+    let (parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to buffer response for idempotency key");
+            let _ = idempotency::release(&state.redis, user_id, &key).await;
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let stored = StoredResponse {
+        status: parts.status.as_u16(),
+        content_type: parts
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string),
+        body: base64::engine::general_purpose::STANDARD.encode(&bytes),
+    };
+    if let Err(e) = idempotency::complete(&state.redis, user_id, &key, &fingerprint, stored).await {
+        tracing::warn!(error = %e, "failed to store idempotent response");
+    }
+
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let body = match base64::engine::general_purpose::STANDARD.decode(&stored.body) {
+        Ok(body) => body,
+        Err(_) => {
+            return ServerError(OpenConvError::Internal(
+                "corrupt idempotent response".into(),
+            ))
+            .into_response()
+        }
+    };
+    let status = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+
+    let mut response = (status, body).into_response();
+    let headers = response.headers_mut();
+    if let Some(content_type) = stored
+        .content_type
+        .and_then(|ct| HeaderValue::from_str(&ct).ok())
+    {
+        headers.insert(header::CONTENT_TYPE, content_type);
+    }
+    headers.insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn replay_restores_status_body_and_content_type() {
+        let resp = replay(StoredResponse {
+            status: 201,
+            content_type: Some("application/json".into()),
+            body: base64::engine::general_purpose::STANDARD.encode(br#"{"id":1}"#),
+        });
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], "application/json");
+        assert_eq!(resp.headers()[REPLAYED_HEADER], "true");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], br#"{"id":1}"#);
+    }
+}
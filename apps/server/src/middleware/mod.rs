@@ -1 +1,23 @@
+pub mod body_limit;
+pub mod idempotency;
+pub mod policy;
 pub mod rate_limit;
+pub mod request_id;
+
+use axum::http::HeaderMap;
+use openconv_shared::ids::UserId;
+
+use crate::state::AppState;
+
+/// The user a request's bearer token belongs to, if it carries a valid one.
+///
+/// For middleware that needs to know the caller before the handler runs;
+/// authentication itself is still enforced by the `AuthUser` extractor.
+pub(crate) fn bearer_user_id(state: &AppState, headers: &HeaderMap) -> Option<UserId> {
+    headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .and_then(|token| state.jwt.validate_access_token(token).ok())
+        .and_then(|claims| claims.sub.parse::<UserId>().ok())
+}
@@ -0,0 +1,58 @@
+use axum::extract::{Request, State};
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::state::AppState;
+
+/// Response header set on authenticated responses while the user has not
+/// accepted the instance's published terms or privacy policy.
+pub const POLICY_UPDATE_REQUIRED_HEADER: &str = "x-policy-update-required";
+
+/// Flag successful authenticated responses with
+/// `X-Policy-Update-Required: true` when the caller has a pending policy
+/// acceptance.
+///
+/// The bearer token is only decoded to find the user; authentication itself
+/// is still enforced by the handler's `AuthUser` extractor. Nothing is
+/// checked when the instance publishes no policy versions.
+pub async fn policy_update_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.config.policy.is_enabled() {
+        return next.run(request).await;
+    }
+
+    let user_id = super::bearer_user_id(&state, request.headers());
+
+    let mut response = next.run(request).await;
+
+    let Some(user_id) = user_id else {
+        return response;
+    };
+    if !response.status().is_success() {
+        return response;
+    }
+
+    match crate::handlers::policies::policy_update_required(
+        &state.db,
+        &state.config.policy,
+        user_id,
+    )
+    .await
+    {
+        Ok(true) => {
+            response.headers_mut().insert(
+                POLICY_UPDATE_REQUIRED_HEADER,
+                HeaderValue::from_static("true"),
+            );
+        }
+        Ok(false) => {}
+        Err(e) => {
+            tracing::warn!(error = %e, "failed to check policy acceptance");
+        }
+    }
+    response
+}
@@ -5,36 +5,113 @@ use std::task::{Context, Poll};
 
 use axum::body::Body;
 use axum::extract::ConnectInfo;
-use axum::http::{Request, StatusCode};
+use axum::http::{HeaderMap, Request, StatusCode};
 use axum::response::{IntoResponse, Response};
-use axum::Json;
+use openconv_shared::error::{OpenConvError, RateLimitInfo};
+use openconv_shared::ids::{GuildId, UserId};
 use tower::{Layer, Service};
 
+use crate::config::{RateLimitFailMode, RateLimitKey, RouteClass, RouteRateLimit};
+use crate::error::{insert_rate_limit_headers, ServerError};
 use crate::jwt::JwtService;
-
-/// Tower layer that applies per-IP rate limiting using Redis.
+use crate::live_config::LiveConfig;
+use crate::redis::keys;
+use crate::redis::{RedisError, RedisPool};
+
+/// Largest request body buffered to find a `public_key` for keyed limits.
+const MAX_KEYED_BODY_BYTES: usize = 64 * 1024;
+
+/// Tower layer that applies a Redis-backed GCRA rate limit to a group of
+/// routes.
+///
+/// Requests are counted per IP, per authenticated user or per public key
+/// (see [`RateLimitKey`]). Every counted response carries the standard
+/// `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset` headers;
+/// rejected requests also get `Retry-After`. While Redis is unavailable,
+/// requests are let through or rejected with 503 according to the policy's
+/// [`RateLimitFailMode`].
 #[derive(Clone)]
 pub struct RateLimitLayer {
-    redis: fred::clients::Pool,
-    max_requests: u32,
-    window_seconds: u64,
+    redis: RedisPool,
+    jwt: Option<Arc<JwtService>>,
+    policy: Policy,
     endpoint_prefix: String,
 }
 
+/// Where a layer gets its limit from.
+#[derive(Clone)]
+enum Policy {
+    Fixed(RouteRateLimit),
+    /// Looked up per request, so config reloads apply immediately.
+    Live(Arc<LiveConfig>, RouteClass),
+}
+
+impl Policy {
+    fn current(&self) -> RouteRateLimit {
+        match self {
+            Self::Fixed(policy) => *policy,
+            Self::Live(live, class) => live.settings().rate_limit.route(*class),
+        }
+    }
+}
+
 impl RateLimitLayer {
+    /// Per-IP limit under an ad-hoc prefix.
     pub fn new(
-        redis: fred::clients::Pool,
+        redis: RedisPool,
         max_requests: u32,
         window_seconds: u64,
         endpoint_prefix: String,
     ) -> Self {
         Self {
             redis,
-            max_requests,
-            window_seconds,
+            jwt: None,
+            policy: Policy::Fixed(RouteRateLimit {
+                limit: max_requests,
+                window_seconds,
+                key: RateLimitKey::Ip,
+                fail_mode: RateLimitFailMode::Open,
+            }),
             endpoint_prefix,
         }
     }
+
+    /// Per-user limit under an ad-hoc prefix. Falls back to per-IP when no
+    /// valid access token is present.
+    pub fn per_user(
+        redis: RedisPool,
+        jwt: Arc<JwtService>,
+        max_requests: u32,
+        window_seconds: u64,
+        endpoint_prefix: String,
+    ) -> Self {
+        Self {
+            redis,
+            jwt: Some(jwt),
+            policy: Policy::Fixed(RouteRateLimit {
+                limit: max_requests,
+                window_seconds,
+                key: RateLimitKey::User,
+                fail_mode: RateLimitFailMode::Open,
+            }),
+            endpoint_prefix,
+        }
+    }
+
+    /// The configured limit for a route class, following config reloads.
+    pub fn for_route(
+        redis: RedisPool,
+        jwt: Arc<JwtService>,
+        live: Arc<LiveConfig>,
+        class: RouteClass,
+    ) -> Self {
+        Self {
+            redis,
+            jwt: Some(jwt),
+            policy: Policy::Live(live, class),
+            endpoint_prefix: class.as_str().to_string(),
+        }
+    }
 }
 
 impl<S> Layer<S> for RateLimitLayer {
@@ -44,8 +121,8 @@ impl<S> Layer<S> for RateLimitLayer {
         RateLimitService {
             inner,
             redis: self.redis.clone(),
-            max_requests: self.max_requests,
-            window_seconds: self.window_seconds,
+            jwt: self.jwt.clone(),
+            policy: self.policy.clone(),
             endpoint_prefix: self.endpoint_prefix.clone(),
         }
     }
@@ -54,35 +131,67 @@ impl<S> Layer<S> for RateLimitLayer {
 #[derive(Clone)]
 pub struct RateLimitService<S> {
     inner: S,
-    redis: fred::clients::Pool,
-    max_requests: u32,
-    window_seconds: u64,
+    redis: RedisPool,
+    jwt: Option<Arc<JwtService>>,
+    policy: Policy,
     endpoint_prefix: String,
 }
 
-pub struct RateLimitError {
-    pub retry_after_seconds: u64,
+/// Limiter state after a request was checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RateLimitStatus {
+    limit: u32,
+    remaining: u32,
+    /// Seconds until the full quota is available again.
+    reset_seconds: u64,
 }
 
-impl IntoResponse for RateLimitError {
-    fn into_response(self) -> Response {
-        let mut response = (
-            StatusCode::TOO_MANY_REQUESTS,
-            Json(serde_json::json!({ "error": "rate limit exceeded" })),
-        )
-            .into_response();
-        response.headers_mut().insert(
-            "Retry-After",
-            self.retry_after_seconds
-                .to_string()
-                .parse()
-                .expect("valid header value"),
-        );
-        response
+fn ms_to_secs(ms: i64) -> u64 {
+    (ms.max(0) as u64).div_ceil(1000).max(1)
+}
+
+impl RateLimitStatus {
+    fn apply_headers(&self, headers: &mut HeaderMap) {
+        insert_rate_limit_headers(headers, self.limit, self.remaining, self.reset_seconds);
+    }
+}
+
+enum RateLimitDecision {
+    /// Within the limit. `None` when the check failed open.
+    Allowed(Option<RateLimitStatus>),
+    Exceeded(RateLimitInfo),
+    /// Redis could not be asked and the policy fails closed.
+    Unavailable(OpenConvError),
+}
+
+impl RateLimitDecision {
+    /// Interpret the `{allowed, remaining, retry_after_ms, reset_ms}` reply
+    /// of [`RATE_LIMIT_SCRIPT`].
+    fn from_script_reply(limit: u32, reply: &[i64]) -> Self {
+        let &[allowed, remaining, retry_after_ms, reset_ms] = reply else {
+            return Self::Allowed(None);
+        };
+        let status = RateLimitStatus {
+            limit,
+            remaining: remaining.clamp(0, limit as i64) as u32,
+            reset_seconds: ms_to_secs(reset_ms),
+        };
+        if allowed == 1 {
+            Self::Allowed(Some(status))
+        } else {
+            Self::Exceeded(RateLimitInfo {
+                limit: status.limit,
+                remaining: status.remaining,
+                reset_seconds: status.reset_seconds,
+                retry_after_seconds: ms_to_secs(retry_after_ms),
+            })
+        }
     }
 }
 
-fn extract_client_ip<B>(req: &Request<B>) -> String {
+/// The client's address: the peer address, else the first `X-Forwarded-For`
+/// hop, else `X-Real-IP`. "unknown" when none is available.
+pub(crate) fn extract_client_ip<B>(req: &Request<B>) -> String {
     if let Some(ConnectInfo(addr)) = req.extensions().get::<ConnectInfo<std::net::SocketAddr>>() {
         return addr.ip().to_string();
     }
@@ -101,54 +210,194 @@ fn extract_client_ip<B>(req: &Request<B>) -> String {
     "unknown".to_string()
 }
 
-/// Lua script that atomically increments a key and sets expiration.
-/// Returns the new count. Sets EXPIRE only on the first increment (count == 1).
+/// Extract user ID from the Authorization Bearer token without performing
+/// full authentication. Returns None if the header is missing, malformed,
+/// or the JWT is invalid.
+fn extract_user_id_from_jwt<B>(req: &Request<B>, jwt: &JwtService) -> Option<String> {
+    let auth_header = req.headers().get("authorization")?.to_str().ok()?;
+    let token = auth_header.strip_prefix("Bearer ")?;
+    let claims = jwt.validate_access_token(token).ok()?;
+    Some(claims.sub)
+}
+
+/// Read `public_key` from a JSON body. Only plausible base64 keys are used,
+/// so arbitrary client input never ends up in a Redis key.
+fn extract_public_key(body: &[u8]) -> Option<String> {
+    #[derive(serde::Deserialize)]
+    struct KeyedBody {
+        public_key: String,
+    }
+    let key = serde_json::from_slice::<KeyedBody>(body).ok()?.public_key;
+    let plausible = !key.is_empty()
+        && key.len() <= 128
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'='));
+    plausible.then_some(key)
+}
+
+fn ip_key<B>(req: &Request<B>, prefix: &str) -> String {
+    keys::RateLimitKey::Ip {
+        ip: &extract_client_ip(req),
+        endpoint: prefix,
+    }
+    .to_string()
+}
+
+/// Build the Redis counter key for a request. Keyed by public key, the body
+/// is buffered and the request rebuilt around it.
+async fn rate_limit_key(
+    req: Request<Body>,
+    strategy: RateLimitKey,
+    jwt: Option<&JwtService>,
+    prefix: &str,
+) -> Result<(Request<Body>, String), Response> {
+    match strategy {
+        RateLimitKey::Ip => {
+            let key = ip_key(&req, prefix);
+            Ok((req, key))
+        }
+        RateLimitKey::User => {
+            let key = match jwt.and_then(|jwt| extract_user_id_from_jwt(&req, jwt)) {
+                Some(user_id) => keys::RateLimitKey::User {
+                    user_id: &user_id,
+                    endpoint: prefix,
+                }
+                .to_string(),
+                None => ip_key(&req, prefix),
+            };
+            Ok((req, key))
+        }
+        RateLimitKey::PublicKey => {
+            let (parts, body) = req.into_parts();
+            let bytes = axum::body::to_bytes(body, MAX_KEYED_BODY_BYTES)
+                .await
+                .map_err(|_| StatusCode::PAYLOAD_TOO_LARGE.into_response())?;
+            let req = Request::from_parts(parts, Body::from(bytes.clone()));
+            let key = match extract_public_key(&bytes) {
+                Some(public_key) => keys::RateLimitKey::PublicKey {
+                    public_key: &public_key,
+                    endpoint: prefix,
+                }
+                .to_string(),
+                None => ip_key(&req, prefix),
+            };
+            Ok((req, key))
+        }
+    }
+}
+
+/// Generic cell rate algorithm (GCRA) as a single atomic script.
+///
+/// `KEYS[1]` stores the theoretical arrival time (TAT) in milliseconds.
+/// Each request advances the TAT by `window / limit`; a request is allowed
+/// while the TAT stays within one window of now. This spreads the quota
+/// evenly, so clients cannot burst twice the limit across a window
+/// boundary as with fixed-window counters. Uses Redis server time so all
+/// instances agree on "now".
+///
+/// `ARGV`: limit, window in ms, and optionally the cost of this request
+/// in units of the limit (default 1). Returns
+/// `{allowed (0/1), remaining, retry_after_ms, reset_ms}`.
 const RATE_LIMIT_SCRIPT: &str = r#"
-local count = redis.call('INCR', KEYS[1])
-if count == 1 then
-    redis.call('EXPIRE', KEYS[1], ARGV[1])
+local limit = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local cost = tonumber(ARGV[3]) or 1
+if limit <= 0 then
+    return {0, 0, window, window}
+end
+local interval = window / limit
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local tat = tonumber(redis.call('GET', KEYS[1])) or now
+if tat < now then
+    tat = now
+end
+local new_tat = tat + interval * cost
+local allow_at = new_tat - window
+if now < allow_at then
+    return {0, 0, math.ceil(allow_at - now), math.ceil(tat - now)}
 end
-return count
+local ttl = math.ceil(new_tat - now)
+redis.call('SET', KEYS[1], string.format('%d', math.ceil(new_tat)), 'PX', ttl)
+return {1, math.floor((window - (new_tat - now)) / interval), 0, ttl}
 "#;
 
-/// Check rate limit against Redis. Returns Ok(()) if within limit,
-/// Err(retry_after_seconds) if exceeded. Fails open on Redis errors.
+/// Check a request against `key`, inside the pool's namespace. When Redis
+/// cannot be asked, `fail_mode` decides whether the request goes through.
 async fn check_redis_rate_limit(
-    redis: &fred::clients::Pool,
+    redis: &RedisPool,
     key: &str,
     max_requests: u32,
     window_seconds: u64,
-) -> Result<(), u64> {
-    use fred::interfaces::{ClientLike, KeysInterface, LuaInterface};
+    fail_mode: RateLimitFailMode,
+) -> RateLimitDecision {
+    check_weighted_rate_limit(redis, key, max_requests, window_seconds, 1, fail_mode).await
+}
 
-    // Fail open if pool is not connected
-    if !redis.is_connected() {
-        tracing::warn!(key, "rate limiter: Redis not connected, failing open");
-        return Ok(());
-    }
+/// Like [`check_redis_rate_limit`], but the request uses up `cost` of the
+/// `max_requests` allowed per window. A cost above `max_requests` is never
+/// allowed.
+async fn check_weighted_rate_limit(
+    redis: &RedisPool,
+    key: &str,
+    max_requests: u32,
+    window_seconds: u64,
+    cost: u32,
+    fail_mode: RateLimitFailMode,
+) -> RateLimitDecision {
+    use fred::interfaces::LuaInterface;
 
-    // Atomic INCR + conditional EXPIRE via Lua script
-    let count: i64 = match redis
-        .eval(
+    let result: Result<Vec<i64>, RedisError> = redis
+        .guarded(redis.eval(
             RATE_LIMIT_SCRIPT,
-            vec![key.to_string()],
-            vec![window_seconds.to_string()],
-        )
-        .await
-    {
-        Ok(c) => c,
+            vec![redis.key(key)],
+            vec![
+                max_requests.to_string(),
+                (window_seconds * 1000).to_string(),
+                cost.to_string(),
+            ],
+        ))
+        .await;
+
+    let reply = match result {
+        Ok(r) => r,
         Err(e) => {
-            tracing::warn!(error = %e, key, "rate limiter: Redis eval failed, failing open");
-            return Ok(());
+            return match fail_mode {
+                RateLimitFailMode::Open => {
+                    tracing::warn!(
+                        error = %e,
+                        key,
+                        "rate limiter: Redis unavailable, failing open"
+                    );
+                    RateLimitDecision::Allowed(None)
+                }
+                RateLimitFailMode::Closed => {
+                    tracing::warn!(
+                        error = %e,
+                        key,
+                        "rate limiter: Redis unavailable, failing closed"
+                    );
+                    RateLimitDecision::Unavailable(fail_closed_error(redis, e))
+                }
+            };
         }
     };
 
-    if count > max_requests as i64 {
-        let ttl: i64 = redis.ttl(key).await.unwrap_or(window_seconds as i64);
-        return Err(ttl.max(1) as u64);
-    }
+    RateLimitDecision::from_script_reply(max_requests, &reply)
+}
 
-    Ok(())
+/// The 503 for a fail-closed limiter. A script error is not an outage but
+/// is still answered with a retryable 503, since the request was never
+/// checked.
+fn fail_closed_error(redis: &RedisPool, e: RedisError) -> OpenConvError {
+    let e = match e {
+        RedisError::Command(_) => RedisError::Unavailable {
+            retry_after: redis.breaker_cooldown(),
+        },
+        unavailable => unavailable,
+    };
+    e.into()
 }
 
 impl<S> Service<Request<Body>> for RateLimitService<S>
@@ -166,166 +415,93 @@ where
 
     fn call(&mut self, req: Request<Body>) -> Self::Future {
         let redis = self.redis.clone();
-        let max = self.max_requests;
-        let window = self.window_seconds;
+        let jwt = self.jwt.clone();
+        let policy = self.policy.current();
         let prefix = self.endpoint_prefix.clone();
         let mut inner = self.inner.clone();
         std::mem::swap(&mut self.inner, &mut inner);
 
         Box::pin(async move {
-            let ip = extract_client_ip(&req);
-            let key = format!("rl:ip:{ip}:{prefix}");
+            let (req, key) = match rate_limit_key(req, policy.key, jwt.as_deref(), &prefix).await {
+                Ok(keyed) => keyed,
+                Err(response) => return Ok(response),
+            };
 
-            match check_redis_rate_limit(&redis, &key, max, window).await {
-                Ok(()) => inner.call(req).await,
-                Err(retry_after) => Ok(RateLimitError {
-                    retry_after_seconds: retry_after,
+            match check_redis_rate_limit(
+                &redis,
+                &key,
+                policy.limit,
+                policy.window_seconds,
+                policy.fail_mode,
+            )
+            .await
+            {
+                RateLimitDecision::Allowed(status) => {
+                    let mut response = inner.call(req).await?;
+                    if let Some(status) = status {
+                        status.apply_headers(response.headers_mut());
+                    }
+                    Ok(response)
+                }
+                RateLimitDecision::Exceeded(info) => {
+                    Ok(ServerError(OpenConvError::RateLimited(info)).into_response())
                 }
-                .into_response()),
+                RateLimitDecision::Unavailable(e) => Ok(ServerError(e).into_response()),
             }
         })
     }
 }
 
-/// Check per-public-key rate limit. Returns Ok(()) if within limit,
-/// Err(RateLimitError) if exceeded.
-pub async fn check_key_rate_limit(
-    redis: &fred::clients::Pool,
-    public_key: &str,
-    endpoint: &str,
-    max_requests: u32,
-    window_seconds: u64,
-) -> Result<(), RateLimitError> {
-    let key = format!("rl:pk:{public_key}:{endpoint}");
-    check_redis_rate_limit(redis, &key, max_requests, window_seconds)
-        .await
-        .map_err(|retry_after| RateLimitError {
-            retry_after_seconds: retry_after,
-        })
-}
-
-/// Check per-email rate limit. Returns Ok(()) if within limit,
-/// Err(RateLimitError) if exceeded.
+/// Check per-email rate limit. Returns `OpenConvError::RateLimited` with the
+/// limiter state if exceeded.
 pub async fn check_email_rate_limit(
-    redis: &fred::clients::Pool,
+    redis: &RedisPool,
     email: &str,
     max_requests: u32,
     window_seconds: u64,
-) -> Result<(), RateLimitError> {
-    let key = format!("rl:email:{email}");
-    check_redis_rate_limit(redis, &key, max_requests, window_seconds)
-        .await
-        .map_err(|retry_after| RateLimitError {
-            retry_after_seconds: retry_after,
-        })
-}
-
-// ---------------------------------------------------------------------------
-// Per-user rate limiter (authenticated endpoints)
-// ---------------------------------------------------------------------------
-
-/// Tower layer that applies per-user rate limiting using Redis.
-/// Falls back to per-IP rate limiting if no valid JWT is present.
-#[derive(Clone)]
-pub struct UserRateLimitLayer {
-    redis: fred::clients::Pool,
-    jwt: Arc<JwtService>,
-    max_requests: u32,
-    window_seconds: u64,
-    endpoint_prefix: String,
-}
-
-impl UserRateLimitLayer {
-    pub fn new(
-        redis: fred::clients::Pool,
-        jwt: Arc<JwtService>,
-        max_requests: u32,
-        window_seconds: u64,
-        endpoint_prefix: String,
-    ) -> Self {
-        Self {
-            redis,
-            jwt,
-            max_requests,
-            window_seconds,
-            endpoint_prefix,
-        }
+    fail_mode: RateLimitFailMode,
+) -> Result<(), OpenConvError> {
+    let key = keys::RateLimitKey::Email(email).to_string();
+    match check_redis_rate_limit(redis, &key, max_requests, window_seconds, fail_mode).await {
+        RateLimitDecision::Allowed(_) => Ok(()),
+        RateLimitDecision::Exceeded(info) => Err(OpenConvError::RateLimited(info)),
+        RateLimitDecision::Unavailable(e) => Err(e),
     }
 }
 
-impl<S> Layer<S> for UserRateLimitLayer {
-    type Service = UserRateLimitService<S>;
-
-    fn layer(&self, inner: S) -> Self::Service {
-        UserRateLimitService {
-            inner,
-            redis: self.redis.clone(),
-            jwt: self.jwt.clone(),
-            max_requests: self.max_requests,
-            window_seconds: self.window_seconds,
-            endpoint_prefix: self.endpoint_prefix.clone(),
-        }
+/// Check a guild's join velocity limit, counting one invite join. Returns
+/// `OpenConvError::RateLimited` with the limiter state if exceeded.
+pub async fn check_guild_join_rate_limit(
+    redis: &RedisPool,
+    guild_id: GuildId,
+    max_joins_per_minute: u32,
+    fail_mode: RateLimitFailMode,
+) -> Result<(), OpenConvError> {
+    let guild_id = guild_id.to_string();
+    let key = keys::RateLimitKey::GuildJoins(&guild_id).to_string();
+    match check_redis_rate_limit(redis, &key, max_joins_per_minute, 60, fail_mode).await {
+        RateLimitDecision::Allowed(_) => Ok(()),
+        RateLimitDecision::Exceeded(info) => Err(OpenConvError::RateLimited(info)),
+        RateLimitDecision::Unavailable(e) => Err(e),
     }
 }
 
-#[derive(Clone)]
-pub struct UserRateLimitService<S> {
-    inner: S,
-    redis: fred::clients::Pool,
-    jwt: Arc<JwtService>,
-    max_requests: u32,
-    window_seconds: u64,
-    endpoint_prefix: String,
-}
-
-/// Extract user ID from the Authorization Bearer token without performing
-/// full authentication. Returns None if the header is missing, malformed,
-/// or the JWT is invalid.
-fn extract_user_id_from_jwt<B>(req: &Request<B>, jwt: &JwtService) -> Option<String> {
-    let auth_header = req.headers().get("authorization")?.to_str().ok()?;
-    let token = auth_header.strip_prefix("Bearer ")?;
-    let claims = jwt.validate_access_token(token).ok()?;
-    Some(claims.sub)
-}
-
-impl<S> Service<Request<Body>> for UserRateLimitService<S>
-where
-    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
-    S::Future: Send,
-{
-    type Response = Response;
-    type Error = S::Error;
-    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
-
-    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        self.inner.poll_ready(cx)
-    }
-
-    fn call(&mut self, req: Request<Body>) -> Self::Future {
-        let redis = self.redis.clone();
-        let jwt = self.jwt.clone();
-        let max = self.max_requests;
-        let window = self.window_seconds;
-        let prefix = self.endpoint_prefix.clone();
-        let mut inner = self.inner.clone();
-        std::mem::swap(&mut self.inner, &mut inner);
-
-        Box::pin(async move {
-            let key = if let Some(user_id) = extract_user_id_from_jwt(&req, &jwt) {
-                format!("rl:user:{user_id}:{prefix}")
-            } else {
-                let ip = extract_client_ip(&req);
-                format!("rl:ip:{ip}:{prefix}")
-            };
-
-            match check_redis_rate_limit(&redis, &key, max, window).await {
-                Ok(()) => inner.call(req).await,
-                Err(retry_after) => Ok(RateLimitError {
-                    retry_after_seconds: retry_after,
-                }
-                .into_response()),
-            }
-        })
+/// Charge `hashes` contact discovery lookups against a user's daily budget.
+/// Returns `OpenConvError::RateLimited` with the limiter state if the
+/// budget cannot cover them.
+pub async fn check_discovery_budget(
+    redis: &RedisPool,
+    user_id: UserId,
+    hashes: u32,
+    daily_budget: u32,
+    fail_mode: RateLimitFailMode,
+) -> Result<(), OpenConvError> {
+    let user_id = user_id.to_string();
+    let key = keys::RateLimitKey::DiscoveryHashes(&user_id).to_string();
+    match check_weighted_rate_limit(redis, &key, daily_budget, 86_400, hashes, fail_mode).await {
+        RateLimitDecision::Allowed(_) => Ok(()),
+        RateLimitDecision::Exceeded(info) => Err(OpenConvError::RateLimited(info)),
+        RateLimitDecision::Unavailable(e) => Err(e),
     }
 }
 
@@ -337,21 +513,21 @@ mod tests {
     use axum::Router;
     use tower::ServiceExt;
 
-    async fn get_test_redis() -> Option<fred::clients::Pool> {
+    async fn get_test_redis() -> Option<RedisPool> {
         use fred::interfaces::ClientLike;
         let config = fred::types::config::Config::from_url("redis://localhost:6379").ok()?;
         let pool = fred::clients::Pool::new(config, None, None, None, 1).ok()?;
         let _ = pool.init().await.ok()?;
         pool.wait_for_connect().await.ok()?;
-        Some(pool)
+        Some(RedisPool::new(pool, "rate_limit_test:"))
     }
 
-    async fn cleanup_redis_key(redis: &fred::clients::Pool, key: &str) {
+    async fn cleanup_redis_key(redis: &RedisPool, key: &str) {
         use fred::interfaces::KeysInterface;
-        let _: i64 = redis.del(key).await.unwrap_or_default();
+        let _: i64 = redis.del(redis.key(key)).await.unwrap_or_default();
     }
 
-    fn test_app(redis: fred::clients::Pool, max_requests: u32, window_seconds: u64) -> Router {
+    fn test_app(redis: RedisPool, max_requests: u32, window_seconds: u64) -> Router {
         let handler = || async { "ok" };
         Router::new()
             .route("/test", get(handler))
@@ -363,6 +539,178 @@ mod tests {
             ))
     }
 
+    #[test]
+    fn decision_from_script_reply() {
+        let RateLimitDecision::Allowed(Some(status)) =
+            RateLimitDecision::from_script_reply(5, &[1, 3, 0, 24_001])
+        else {
+            panic!("expected allowed");
+        };
+        assert_eq!(status.remaining, 3);
+        assert_eq!(status.reset_seconds, 25);
+
+        let RateLimitDecision::Exceeded(info) =
+            RateLimitDecision::from_script_reply(5, &[0, 0, 1, 60_000])
+        else {
+            panic!("expected exceeded");
+        };
+        assert_eq!(info.remaining, 0);
+        assert_eq!(info.reset_seconds, 60);
+        assert_eq!(info.retry_after_seconds, 1);
+
+        assert!(matches!(
+            RateLimitDecision::from_script_reply(5, &[1]),
+            RateLimitDecision::Allowed(None)
+        ));
+    }
+
+    #[tokio::test]
+    async fn quota_is_spread_across_the_window() {
+        let Some(redis) = get_test_redis().await else {
+            eprintln!("skipping: Redis not available");
+            return;
+        };
+        let key = "rl:ip:10.0.0.7:test";
+        cleanup_redis_key(&redis, key).await;
+
+        // 2 per second: after the burst, one request frees up every 500ms
+        for _ in 0..2 {
+            assert!(matches!(
+                check_redis_rate_limit(&redis, key, 2, 1, RateLimitFailMode::Open).await,
+                RateLimitDecision::Allowed(Some(_))
+            ));
+        }
+        let RateLimitDecision::Exceeded(info) =
+            check_redis_rate_limit(&redis, key, 2, 1, RateLimitFailMode::Open).await
+        else {
+            panic!("third request should be limited");
+        };
+        assert_eq!(info.retry_after_seconds, 1);
+
+        tokio::time::sleep(std::time::Duration::from_millis(600)).await;
+        assert!(matches!(
+            check_redis_rate_limit(&redis, key, 2, 1, RateLimitFailMode::Open).await,
+            RateLimitDecision::Allowed(Some(_))
+        ));
+        assert!(matches!(
+            check_redis_rate_limit(&redis, key, 2, 1, RateLimitFailMode::Open).await,
+            RateLimitDecision::Exceeded(_)
+        ));
+
+        cleanup_redis_key(&redis, key).await;
+    }
+
+    #[tokio::test]
+    async fn weighted_requests_use_up_their_cost() {
+        let Some(redis) = get_test_redis().await else {
+            eprintln!("skipping: Redis not available");
+            return;
+        };
+        let key = "rl:discovery:weighted-test";
+        cleanup_redis_key(&redis, key).await;
+
+        let check =
+            |cost| check_weighted_rate_limit(&redis, key, 10, 60, cost, RateLimitFailMode::Open);
+        let RateLimitDecision::Allowed(Some(status)) = check(7).await else {
+            panic!("first request fits the budget");
+        };
+        assert_eq!(status.remaining, 3);
+        assert!(matches!(check(4).await, RateLimitDecision::Exceeded(_)));
+        assert!(matches!(
+            check(3).await,
+            RateLimitDecision::Allowed(Some(_))
+        ));
+        assert!(matches!(check(1).await, RateLimitDecision::Exceeded(_)));
+
+        cleanup_redis_key(&redis, key).await;
+        assert!(matches!(check(11).await, RateLimitDecision::Exceeded(_)));
+
+        cleanup_redis_key(&redis, key).await;
+    }
+
+    #[test]
+    fn extract_public_key_accepts_only_base64_keys() {
+        assert_eq!(
+            extract_public_key(br#"{"public_key":"BQab+/9="}"#).as_deref(),
+            Some("BQab+/9=")
+        );
+        assert!(extract_public_key(br#"{"public_key":"a:b*c"}"#).is_none());
+        assert!(extract_public_key(br#"{"other":"x"}"#).is_none());
+        assert!(extract_public_key(b"not json").is_none());
+    }
+
+    #[tokio::test]
+    async fn public_key_strategy_keys_by_body_and_preserves_it() {
+        let Some(redis) = get_test_redis().await else {
+            eprintln!("skipping: Redis not available");
+            return;
+        };
+        let key = "rl:pk:dGVzdGtleQ==:pktest";
+        cleanup_redis_key(&redis, key).await;
+
+        let mut layer = RateLimitLayer::new(redis.clone(), 10, 60, "pktest".to_string());
+        layer.policy.key = RateLimitKey::PublicKey;
+        let app = Router::new()
+            .route(
+                "/test",
+                axum::routing::post(|body: String| async move { body }),
+            )
+            .layer(layer);
+        let payload = r#"{"public_key":"dGVzdGtleQ=="}"#;
+        let request = Request::builder()
+            .method("POST")
+            .uri("/test")
+            .header("X-Forwarded-For", "10.0.0.60")
+            .body(Body::from(payload))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, payload.as_bytes());
+
+        use fred::interfaces::KeysInterface;
+        let exists: bool = redis.exists(redis.key(key)).await.unwrap();
+        assert!(exists, "expected public key rate limit key to exist");
+
+        cleanup_redis_key(&redis, key).await;
+    }
+
+    #[tokio::test]
+    async fn responses_carry_ratelimit_headers() {
+        let Some(redis) = get_test_redis().await else {
+            eprintln!("skipping: Redis not available");
+            return;
+        };
+        cleanup_redis_key(&redis, "rl:ip:10.0.0.6:test").await;
+
+        let mut remaining = Vec::new();
+        for _ in 0..3 {
+            let app = test_app(redis.clone(), 2, 60);
+            let request = Request::builder()
+                .uri("/test")
+                .header("X-Forwarded-For", "10.0.0.6")
+                .body(Body::empty())
+                .unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            let headers = response.headers();
+            assert_eq!(headers.get("RateLimit-Limit").unwrap(), "2");
+            assert!(headers.contains_key("RateLimit-Reset"));
+            remaining.push(
+                headers
+                    .get("RateLimit-Remaining")
+                    .unwrap()
+                    .to_str()
+                    .unwrap()
+                    .to_string(),
+            );
+        }
+        assert_eq!(remaining, ["1", "0", "0"]);
+
+        cleanup_redis_key(&redis, "rl:ip:10.0.0.6:test").await;
+    }
+
     #[tokio::test]
     async fn first_request_within_limit_returns_200() {
         let Some(redis) = get_test_redis().await else {
@@ -429,7 +777,7 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn rate_limit_counters_expire_after_window() {
+    async fn rate_limit_quota_recovers_after_window() {
         let Some(redis) = get_test_redis().await else {
             eprintln!("skipping: Redis not available");
             return;
@@ -511,7 +859,7 @@ mod tests {
         let pool = fred::clients::Pool::new(config, None, None, None, 1).unwrap();
         // Don't init -- pool is not connected
 
-        let app = test_app(pool, 1, 60);
+        let app = test_app(RedisPool::new(pool, ""), 1, 60);
         let request = Request::builder()
             .uri("/test")
             .header("X-Forwarded-For", "10.0.0.99")
@@ -522,7 +870,33 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
     }
 
-    // --- UserRateLimitLayer tests ---
+    #[tokio::test]
+    async fn rate_limit_fails_closed_with_retry_after_when_configured() {
+        let config = fred::types::config::Config::from_url("redis://localhost:59999").unwrap();
+        let pool = fred::clients::Pool::new(config, None, None, None, 1).unwrap();
+        let redis = RedisPool::new(pool, "").with_breaker(1, std::time::Duration::from_secs(7));
+
+        let mut layer = RateLimitLayer::new(redis, 1, 60, "test".to_string());
+        layer.policy = Policy::Fixed(RouteRateLimit {
+            limit: 1,
+            window_seconds: 60,
+            key: RateLimitKey::Ip,
+            fail_mode: RateLimitFailMode::Closed,
+        });
+        let app = Router::new()
+            .route("/test", get(|| async { "ok" }))
+            .layer(layer);
+        let request = Request::builder()
+            .uri("/test")
+            .header("X-Forwarded-For", "10.0.0.98")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers().get("Retry-After").unwrap(), "7");
+    }
+
+    // --- Per-user tests ---
 
     fn test_jwt_service() -> Arc<crate::jwt::JwtService> {
         let config = crate::config::JwtConfig {
@@ -530,12 +904,13 @@ mod tests {
             public_key_pem: "-----BEGIN PUBLIC KEY-----\nMCowBQYDK2VwAyEA9eB0735gPgffPc6aheXCqzsXb4ylG7Yi6I0yUIb2vZ4=\n-----END PUBLIC KEY-----".to_string(),
             access_token_ttl_seconds: 300,
             refresh_token_ttl_seconds: 604800,
+            ..Default::default()
         };
         Arc::new(crate::jwt::JwtService::new(&config).unwrap())
     }
 
     fn user_test_app(
-        redis: fred::clients::Pool,
+        redis: RedisPool,
         jwt: Arc<crate::jwt::JwtService>,
         max_requests: u32,
         window_seconds: u64,
@@ -543,7 +918,7 @@ mod tests {
         let handler = || async { "ok" };
         Router::new()
             .route("/test", get(handler))
-            .layer(UserRateLimitLayer::new(
+            .layer(RateLimitLayer::per_user(
                 redis,
                 jwt,
                 max_requests,
@@ -577,7 +952,7 @@ mod tests {
 
         // Verify the user key was incremented (not the IP key)
         use fred::interfaces::KeysInterface;
-        let exists: bool = redis.exists(&key).await.unwrap();
+        let exists: bool = redis.exists(redis.key(&key)).await.unwrap();
         assert!(exists, "expected user rate limit key to exist");
 
         cleanup_redis_key(&redis, &key).await;
@@ -604,7 +979,7 @@ mod tests {
 
         // Verify the IP key was used
         use fred::interfaces::KeysInterface;
-        let exists: bool = redis.exists(ip_key).await.unwrap();
+        let exists: bool = redis.exists(redis.key(ip_key)).await.unwrap();
         assert!(exists, "expected IP rate limit key to exist as fallback");
 
         cleanup_redis_key(&redis, ip_key).await;
@@ -0,0 +1,85 @@
+//! Request correlation IDs.
+//!
+//! Every request gets an ID, returned in the `X-Request-Id` response
+//! header, recorded on the request's tracing span and included in every
+//! `ServerError` body, so an error a user reports can be found in the logs.
+//! With `http.trust_request_id` set, a well-formed ID sent by a reverse
+//! proxy is kept instead of generating a new one.
+
+use axum::extract::{Request, State};
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::state::AppState;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest inbound ID accepted.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// The ID of the request being handled, if called while handling one.
+///
+/// Work spawned onto another task does not inherit it.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Whether an inbound ID is safe to log and echo back: 1–128 ASCII
+/// letters, digits, `-`, `_`, `.` or `:`.
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'))
+}
+
+pub async fn request_id_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let inbound = state
+        .config
+        .http
+        .trust_request_id
+        .then(|| request.headers().get(REQUEST_ID_HEADER))
+        .flatten()
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map(str::to_string);
+    let request_id = inbound.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    tracing::Span::current().record("request_id", request_id.as_str());
+    let header = HeaderValue::from_str(&request_id).unwrap();
+    let mut response = REQUEST_ID.scope(request_id, next.run(request)).await;
+    response.headers_mut().insert(REQUEST_ID_HEADER, header);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_inbound_ids() {
+        assert!(is_valid_request_id("0b6a1f3c-5d1e-4a47-9a8f-2c2f7f9a6b11"));
+        assert!(is_valid_request_id("edge-1:abc.123_x"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("has space"));
+        assert!(!is_valid_request_id("line\nbreak"));
+        assert!(!is_valid_request_id(&"a".repeat(MAX_REQUEST_ID_LEN + 1)));
+    }
+
+    #[tokio::test]
+    async fn current_is_set_only_inside_the_scope() {
+        assert_eq!(current(), None);
+        let seen = REQUEST_ID.scope("req-1".into(), async { current() }).await;
+        assert_eq!(seen.as_deref(), Some("req-1"));
+    }
+}
@@ -0,0 +1,223 @@
+//! OAuth 2 authorization server for third-party apps.
+//!
+//! Apps register a client, send the user to the OpenConv client's consent
+//! screen, and redeem the returned authorization code at the token
+//! endpoint. Every flow uses PKCE (S256 only); confidential clients also
+//! present their secret. Tokens are opaque, scoped to [`OAuthScope`]s, and
+//! never grant access to the user's keys or to the regular API.
+//!
+//! Authorization codes live in Redis for a minute and are
+//! redeemable once; issued tokens are stored hashed in `oauth_tokens`.
+
+use base64::Engine;
+use fred::interfaces::KeysInterface;
+use openconv_shared::api::oauth::OAuthScope;
+use openconv_shared::error::OpenConvError;
+use openconv_shared::ids::UserId;
+use sha2::{Digest, Sha256};
+
+use crate::redis::RedisPool;
+use crate::secret_token;
+
+/// How long an authorization code may wait to be redeemed, in seconds.
+const CODE_TTL_SECS: i64 = 60;
+
+/// How long an access token is valid, in seconds.
+pub const ACCESS_TOKEN_TTL_SECS: i64 = 60 * 60;
+
+/// How long a refresh token is valid, in seconds. Refreshing issues a new
+/// one.
+pub const REFRESH_TOKEN_TTL_SECS: i64 = 30 * 24 * 60 * 60;
+
+/// Most redirect URIs a client may register.
+pub const MAX_REDIRECT_URIS: usize = 10;
+
+/// Parse a space-separated `scope` parameter. Unknown scopes are rejected;
+/// the result is sorted and free of duplicates.
+pub fn parse_scopes(raw: &str) -> Result<Vec<OAuthScope>, OpenConvError> {
+    let mut scopes = raw
+        .split_ascii_whitespace()
+        .map(|s| {
+            OAuthScope::parse(s)
+                .ok_or_else(|| OpenConvError::Validation(format!("unknown scope: {s}")))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if scopes.is_empty() {
+        return Err(OpenConvError::Validation("scope is required".into()));
+    }
+    scopes.sort();
+    scopes.dedup();
+    Ok(scopes)
+}
+
+pub fn format_scopes(scopes: &[OAuthScope]) -> String {
+    scopes
+        .iter()
+        .map(|s| s.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Scopes as stored in `TEXT[]` columns. Names no longer known are dropped.
+pub fn scopes_from_db(stored: &[String]) -> Vec<OAuthScope> {
+    stored.iter().filter_map(|s| OAuthScope::parse(s)).collect()
+}
+
+pub fn scopes_to_db(scopes: &[OAuthScope]) -> Vec<String> {
+    scopes.iter().map(|s| s.as_str().to_string()).collect()
+}
+
+/// RFC 7636 verifiers are 43–128 characters from the unreserved set.
+fn is_valid_verifier(verifier: &str) -> bool {
+    (43..=128).contains(&verifier.len())
+        && verifier
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~'))
+}
+
+/// Check a PKCE verifier against the S256 challenge sent when authorizing.
+pub fn verify_pkce(verifier: &str, challenge: &str) -> bool {
+    if !is_valid_verifier(verifier) {
+        return false;
+    }
+    let computed = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .encode(Sha256::digest(verifier.as_bytes()));
+    computed == challenge
+}
+
+/// Whether `challenge` looks like an S256 challenge: 32 bytes, base64url.
+pub fn is_valid_challenge(challenge: &str) -> bool {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(challenge)
+        .is_ok_and(|bytes| bytes.len() == 32)
+}
+
+/// What an authorization code stands for, until it is redeemed.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct StoredCode {
+    pub client_id: uuid::Uuid,
+    pub user_id: UserId,
+    pub redirect_uri: String,
+    pub scopes: Vec<OAuthScope>,
+    pub code_challenge: String,
+}
+
+fn code_key(code: &str) -> String {
+    format!("oauth:code:{}", secret_token::hash(code))
+}
+
+/// Issue an authorization code for an approved request.
+pub async fn issue_code(
+    redis: &RedisPool,
+    stored: &StoredCode,
+) -> Result<String, fred::error::Error> {
+    let code = secret_token::generate();
+    let json = serde_json::to_string(stored).expect("StoredCode serializes");
+    redis
+        .set::<(), _, _>(
+            redis.key(code_key(&code)),
+            json,
+            Some(fred::types::Expiration::EX(CODE_TTL_SECS)),
+            None,
+            false,
+        )
+        .await?;
+    Ok(code)
+}
+
+/// Fetch and delete an authorization code, so each one is redeemed once.
+pub async fn take_code(
+    redis: &RedisPool,
+    code: &str,
+) -> Result<Option<StoredCode>, fred::error::Error> {
+    let json: Option<String> = redis.getdel(redis.key(code_key(code))).await?;
+    json.map(|json| {
+        serde_json::from_str(&json).map_err(|_| {
+            fred::error::Error::new(fred::error::ErrorKind::Parse, "corrupt authorization code")
+        })
+    })
+    .transpose()
+}
+
+/// Append the query parameters of a successful authorization to the
+/// client's redirect URI.
+pub fn redirect_with_code(redirect_uri: &str, code: &str, state: Option<&str>) -> String {
+    let mut url = reqwest::Url::parse(redirect_uri).expect("registered redirect URIs are valid");
+    {
+        let mut query = url.query_pairs_mut();
+        query.append_pair("code", code);
+        if let Some(state) = state {
+            query.append_pair("state", state);
+        }
+    }
+    url.into()
+}
+
+/// Validate a redirect URI at registration. Loopback http is allowed for
+/// native apps; everything else must be https. Fragments are not allowed.
+pub fn validate_redirect_uri(raw: &str) -> Result<(), OpenConvError> {
+    let invalid = |msg: &str| OpenConvError::Validation(msg.into());
+    let url = reqwest::Url::parse(raw).map_err(|_| invalid("redirect URI is not a valid URL"))?;
+    if url.fragment().is_some() {
+        return Err(invalid("redirect URI must not contain a fragment"));
+    }
+    let loopback = matches!(url.host_str(), Some("127.0.0.1" | "[::1]" | "localhost"));
+    match url.scheme() {
+        "https" => Ok(()),
+        "http" if loopback => Ok(()),
+        _ => Err(invalid(
+            "redirect URI must use https, or http on a loopback address",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_scopes_sorts_and_dedups() {
+        assert_eq!(
+            parse_scopes("guilds.read identify identify").unwrap(),
+            vec![OAuthScope::Identify, OAuthScope::GuildsRead]
+        );
+        assert!(parse_scopes("").is_err());
+        assert!(parse_scopes("identify messages.write").is_err());
+    }
+
+    #[test]
+    fn format_scopes_round_trips() {
+        let scopes = vec![OAuthScope::Identify, OAuthScope::GuildsRead];
+        assert_eq!(parse_scopes(&format_scopes(&scopes)).unwrap(), scopes);
+        assert_eq!(scopes_from_db(&scopes_to_db(&scopes)), scopes);
+    }
+
+    #[test]
+    fn verify_pkce_matches_rfc_7636_example() {
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        let challenge = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+        assert!(is_valid_challenge(challenge));
+        assert!(verify_pkce(verifier, challenge));
+        assert!(!verify_pkce(
+            "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXx",
+            challenge
+        ));
+        assert!(!verify_pkce("short", challenge));
+    }
+
+    #[test]
+    fn redirect_uri_rules() {
+        assert!(validate_redirect_uri("https://app.example/callback").is_ok());
+        assert!(validate_redirect_uri("http://127.0.0.1:8123/cb").is_ok());
+        assert!(validate_redirect_uri("http://localhost/cb").is_ok());
+        assert!(validate_redirect_uri("http://app.example/cb").is_err());
+        assert!(validate_redirect_uri("https://app.example/cb#frag").is_err());
+        assert!(validate_redirect_uri("custom-scheme:/cb").is_err());
+    }
+
+    #[test]
+    fn redirect_with_code_keeps_existing_query() {
+        let url = redirect_with_code("https://app.example/cb?x=1", "abc", Some("s t"));
+        assert_eq!(url, "https://app.example/cb?x=1&code=abc&state=s+t");
+    }
+}
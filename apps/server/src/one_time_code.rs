@@ -0,0 +1,90 @@
+//! Six-digit codes emailed for registration and account recovery. Redis
+//! holds only an Argon2id hash of each code, and the copy queued for
+//! delivery is sealed with [`OutboxKey`](crate::email::outbox::OutboxKey),
+//! so read access to Redis or the database is not enough to complete someone
+//! else's registration or recovery.
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use openconv_shared::error::OpenConvError;
+use rand::Rng;
+
+use crate::error::ServerError;
+
+/// A new random code. Only its [`hash`] should be stored.
+pub fn generate() -> String {
+    format!("{:06}", rand::rng().random_range(0..1_000_000u32))
+}
+
+/// A salted Argon2id hash of `code` in PHC string format.
+///
+/// Hashing is deliberately slow, so it runs on the blocking pool. A
+/// six-digit code is still guessable offline given enough time; the hash
+/// only has to hold out for the code's ten-minute lifetime.
+pub async fn hash(code: &str) -> Result<String, ServerError> {
+    let code = code.to_owned();
+    tokio::task::spawn_blocking(move || hash_blocking(&code))
+        .await
+        .map_err(|e| OpenConvError::Internal(format!("code hashing task failed: {e}")))?
+}
+
+/// Whether `code` matches a hash produced by [`hash`]. A malformed hash
+/// never matches.
+pub async fn verify(code: &str, hash: &str) -> Result<bool, ServerError> {
+    let code = code.to_owned();
+    let hash = hash.to_owned();
+    tokio::task::spawn_blocking(move || verify_blocking(&code, &hash))
+        .await
+        .map_err(|e| OpenConvError::Internal(format!("code hashing task failed: {e}")).into())
+}
+
+fn hash_blocking(code: &str) -> Result<String, ServerError> {
+    let salt_bytes: [u8; 16] = rand::rng().random();
+    let salt = SaltString::encode_b64(&salt_bytes)
+        .map_err(|e| OpenConvError::Internal(format!("invalid code salt: {e}")))?;
+    let hash = Argon2::default()
+        .hash_password(code.as_bytes(), &salt)
+        .map_err(|e| OpenConvError::Internal(format!("code hashing failed: {e}")))?;
+    Ok(hash.to_string())
+}
+
+fn verify_blocking(code: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(code.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_codes_are_six_digits() {
+        let code = generate();
+        assert_eq!(code.len(), 6);
+        assert!(code.chars().all(|c| c.is_ascii_digit()));
+    }
+
+    #[tokio::test]
+    async fn hash_is_salted_argon2id_and_verifies() {
+        let a = hash("123456").await.unwrap();
+        let b = hash("123456").await.unwrap();
+        assert!(a.starts_with("$argon2id$"));
+        assert!(!a.contains("123456"));
+        assert_ne!(a, b, "each hash has its own salt");
+
+        assert!(verify("123456", &a).await.unwrap());
+        assert!(verify("123456", &b).await.unwrap());
+        assert!(!verify("654321", &a).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn malformed_hash_never_matches() {
+        assert!(!verify("123456", "123456").await.unwrap());
+        assert!(!verify("123456", "").await.unwrap());
+    }
+}
@@ -1,4 +1,8 @@
-use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use openconv_shared::api::oauth::OAuthScope;
+use utoipa::openapi::security::{
+    ApiKey, ApiKeyValue, AuthorizationCode, Flow, HttpAuthScheme, HttpBuilder, OAuth2, Scopes,
+    SecurityScheme,
+};
 use utoipa::{Modify, OpenApi};
 
 #[derive(OpenApi)]
@@ -19,20 +23,31 @@ use utoipa::{Modify, OpenApi};
         crate::handlers::auth::challenge,
         crate::handlers::auth::login_verify,
         crate::handlers::auth::refresh,
+        crate::handlers::auth::reauth,
         crate::handlers::auth::logout,
         crate::handlers::auth::logout_all,
         crate::handlers::auth::list_devices,
+        crate::handlers::auth::link_device,
         crate::handlers::auth::revoke_device,
+        crate::handlers::auth::list_sessions,
+        crate::handlers::auth::revoke_session,
         crate::handlers::auth::recover_start,
         crate::handlers::auth::recover_verify,
         crate::handlers::auth::recover_complete,
+        crate::handlers::jwks::jwks,
         // Users
         crate::handlers::users::get_me,
         crate::handlers::users::update_me,
         crate::handlers::users::get_user,
         crate::handlers::users::search_users,
+        crate::handlers::users::bulk_users,
         crate::handlers::users::get_prekeys,
+        crate::handlers::users::get_device_keys,
+        crate::handlers::users::get_device_prekeys,
         crate::handlers::users::upload_prekeys,
+        crate::handlers::settings::get_settings,
+        crate::handlers::settings::update_settings,
+        crate::handlers::settings::update_emoji_preferences,
         // Guilds
         crate::handlers::guilds::create_guild,
         crate::handlers::guilds::list_guilds,
@@ -43,6 +58,48 @@ use utoipa::{Modify, OpenApi};
         crate::handlers::guilds::leave_guild,
         crate::handlers::guilds::kick_member,
         crate::handlers::guilds::list_members,
+        crate::handlers::guilds::bulk_members,
+        crate::handlers::guilds::export_members,
+        // Moderation
+        crate::handlers::moderation::ban_member,
+        crate::handlers::moderation::unban_member,
+        crate::handlers::moderation::list_prune_jobs,
+        crate::handlers::moderation::prune_inactive_members,
+        crate::handlers::moderation::list_inactive_prune_jobs,
+        crate::handlers::webhooks::create_webhook,
+        crate::handlers::webhooks::list_webhooks,
+        crate::handlers::webhooks::delete_webhook,
+        crate::handlers::automod::create_rule,
+        crate::handlers::automod::list_rules,
+        crate::handlers::automod::update_rule,
+        crate::handlers::automod::delete_rule,
+        // Bots
+        crate::handlers::bots::create_bot,
+        crate::handlers::bots::list_bots,
+        crate::handlers::bots::regenerate_token,
+        crate::handlers::bots::delete_bot,
+        crate::handlers::bots::get_current_bot,
+        crate::handlers::bridges::create_bridge,
+        crate::handlers::bridges::list_bridges,
+        crate::handlers::bridges::regenerate_token,
+        crate::handlers::bridges::delete_bridge,
+        crate::handlers::bridges::get_current_bridge,
+        crate::handlers::bridges::list_puppets,
+        crate::handlers::bridges::upsert_puppet,
+        crate::handlers::bridges::get_puppet,
+        crate::handlers::bridges::get_puppet_by_user,
+        crate::handlers::bridges::delete_puppet,
+        // OAuth
+        crate::handlers::oauth::create_client,
+        crate::handlers::oauth::list_clients,
+        crate::handlers::oauth::delete_client,
+        crate::handlers::oauth::authorize_info,
+        crate::handlers::oauth::authorize,
+        crate::handlers::oauth::token,
+        crate::handlers::oauth::oauth_me,
+        crate::handlers::oauth::oauth_guilds,
+        crate::handlers::oauth::list_consents,
+        crate::handlers::oauth::revoke_consent,
         // Channels
         crate::handlers::channels::create_channel,
         crate::handlers::channels::list_channels,
@@ -57,33 +114,78 @@ use utoipa::{Modify, OpenApi};
         crate::handlers::roles::delete_role,
         crate::handlers::roles::assign_role,
         crate::handlers::roles::remove_role,
+        crate::handlers::roles::preview_permissions,
         // Invites
         crate::handlers::invites::create_invite,
         crate::handlers::invites::list_invites,
         crate::handlers::invites::revoke_invite,
         crate::handlers::invites::get_invite_info,
+        crate::handlers::invites::preflight_invite,
         crate::handlers::invites::accept_invite,
         // DM Channels
         crate::handlers::dm_channels::create,
         crate::handlers::dm_channels::list,
+        crate::handlers::dm_channels::notes,
         crate::handlers::dm_channels::get_one,
+        crate::handlers::dm_channels::set_archive,
         crate::handlers::dm_channels::add_member,
         crate::handlers::dm_channels::leave,
         crate::handlers::dm_channels::messages,
         // Messages
         crate::handlers::messages::guild_messages,
+        crate::handlers::messages::history_backfill,
         // Files
         crate::handlers::files::upload,
         crate::handlers::files::upload_dm,
+        crate::handlers::files::upload_standalone,
+        crate::handlers::files::list_attachments,
+        crate::handlers::files::set_attachments,
         crate::handlers::files::download,
         crate::handlers::files::meta,
+        crate::handlers::files::download_url,
+        crate::handlers::files::signed_download,
+        crate::handlers::upload_sessions::create,
+        crate::handlers::upload_sessions::get_one,
+        crate::handlers::upload_sessions::put_chunk,
+        crate::handlers::upload_sessions::complete,
+        crate::handlers::upload_sessions::abort,
+        crate::handlers::quotas::user_storage,
+        crate::handlers::quotas::guild_storage,
+        crate::handlers::images::upload_avatar,
+        crate::handlers::images::delete_avatar,
+        crate::handlers::images::get_avatar,
+        crate::handlers::images::upload_icon,
+        crate::handlers::images::delete_icon,
+        crate::handlers::images::get_icon,
         // WebSocket
         crate::handlers::ws::create_ws_ticket,
         crate::handlers::ws::ws_upgrade,
+        // Admin
+        crate::handlers::admin::get_suspension,
+        crate::handlers::admin::suspend_user,
+        crate::handlers::admin::unsuspend_user,
+        crate::handlers::health::detailed,
+        crate::handlers::admin::list_jobs,
+        crate::handlers::admin::email_queue_stats,
+        crate::handlers::admin::reload_config,
+        crate::handlers::admin::list_ip_denylist,
+        crate::handlers::admin::add_ip_denylist_entry,
+        crate::handlers::admin::remove_ip_denylist_entry,
+        crate::handlers::admin::list_ip_blocks,
+        crate::handlers::policies::get_policy_status,
+        crate::handlers::policies::accept_policies,
+        // Discovery
+        crate::handlers::discovery::get_discovery_params,
+        crate::handlers::discovery::discover_contacts,
+        // Voice
+        crate::handlers::voice::get_turn_credentials,
+        crate::handlers::voice::list_voice_states,
     ),
     components(schemas(
         // Error
         crate::error::ErrorResponse,
+        openconv_shared::error::ErrorCode,
+        openconv_shared::error::ErrorMessage,
         // IDs
         openconv_shared::ids::UserId,
         openconv_shared::ids::GuildId,
@@ -93,6 +195,7 @@ use utoipa::{Modify, OpenApi};
         openconv_shared::ids::FileId,
         openconv_shared::ids::DmChannelId,
         openconv_shared::ids::DeviceId,
+        openconv_shared::ids::UploadSessionId,
         // Auth
         openconv_shared::api::auth::RegisterStartRequest,
         openconv_shared::api::auth::RegisterStartResponse,
@@ -106,6 +209,8 @@ use utoipa::{Modify, OpenApi};
         openconv_shared::api::auth::LoginVerifyResponse,
         openconv_shared::api::auth::RefreshRequest,
         openconv_shared::api::auth::RefreshResponse,
+        openconv_shared::api::auth::ReauthRequest,
+        openconv_shared::api::auth::ReauthResponse,
         openconv_shared::api::auth::RecoverStartRequest,
         openconv_shared::api::auth::RecoverStartResponse,
         openconv_shared::api::auth::RecoverVerifyRequest,
@@ -114,13 +219,60 @@ use utoipa::{Modify, OpenApi};
         openconv_shared::api::auth::RecoverCompleteResponse,
         openconv_shared::api::auth::DeviceInfo,
         openconv_shared::api::auth::DevicesListResponse,
+        openconv_shared::api::auth::LinkDeviceRequest,
+        openconv_shared::api::auth::SessionInfo,
+        openconv_shared::api::auth::SessionsListResponse,
+        crate::jwt::Jwk,
+        crate::jwt::JwkSet,
         // Guild
         openconv_shared::api::guild::CreateGuildRequest,
         openconv_shared::api::guild::UpdateGuildRequest,
+        openconv_shared::api::guild::VerificationLevel,
         openconv_shared::api::guild::GuildResponse,
         openconv_shared::api::guild::GuildListResponse,
         openconv_shared::api::guild::GuildMemberResponse,
+        openconv_shared::api::guild::BulkMembersRequest,
         openconv_shared::api::guild::RoleSummary,
+        openconv_shared::api::guild::BanMemberRequest,
+        openconv_shared::api::guild::BanMemberResponse,
+        openconv_shared::api::guild::PruneJobResponse,
+        openconv_shared::api::guild::PruneJobListResponse,
+        openconv_shared::api::guild::InactivePruneRequest,
+        openconv_shared::api::guild::InactivePruneResponse,
+        openconv_shared::api::guild::InactivePruneJobResponse,
+        openconv_shared::api::guild::InactivePruneJobListResponse,
+        openconv_shared::api::webhook::CreateWebhookRequest,
+        openconv_shared::api::webhook::WebhookResponse,
+        openconv_shared::api::webhook::WebhookListResponse,
+        openconv_shared::api::automod::AutomodRule,
+        openconv_shared::api::automod::MessageMetadata,
+        openconv_shared::api::automod::CreateAutomodRuleRequest,
+        openconv_shared::api::automod::UpdateAutomodRuleRequest,
+        openconv_shared::api::automod::AutomodRuleResponse,
+        openconv_shared::api::automod::AutomodRuleListResponse,
+        openconv_shared::api::bot::CreateBotRequest,
+        openconv_shared::api::bot::BotResponse,
+        openconv_shared::api::bot::BotListResponse,
+        openconv_shared::api::bridge::CreateBridgeRequest,
+        openconv_shared::api::bridge::BridgeResponse,
+        openconv_shared::api::bridge::BridgeListResponse,
+        openconv_shared::api::bridge::UpsertPuppetRequest,
+        openconv_shared::api::bridge::PuppetResponse,
+        openconv_shared::api::bridge::PuppetListResponse,
+        openconv_shared::api::oauth::OAuthScope,
+        openconv_shared::api::oauth::CreateOAuthClientRequest,
+        openconv_shared::api::oauth::OAuthClientResponse,
+        openconv_shared::api::oauth::OAuthClientListResponse,
+        openconv_shared::api::oauth::AuthorizeInfoResponse,
+        openconv_shared::api::oauth::AuthorizeResponse,
+        openconv_shared::api::oauth::OAuthTokenResponse,
+        openconv_shared::api::oauth::OAuthConsentResponse,
+        openconv_shared::api::oauth::OAuthConsentListResponse,
+        crate::handlers::oauth::AuthorizeParams,
+        crate::handlers::oauth::TokenRequest,
+        crate::handlers::oauth::OAuthErrorResponse,
+        openconv_shared::api::gateway::MemberEvent,
+        openconv_shared::api::gateway::MemberEventKind,
         // Channel
         openconv_shared::api::channel::CreateChannelRequest,
         openconv_shared::api::channel::UpdateChannelRequest,
@@ -131,35 +283,87 @@ use utoipa::{Modify, OpenApi};
         openconv_shared::api::role::CreateRoleRequest,
         openconv_shared::api::role::UpdateRoleRequest,
         openconv_shared::api::role::RoleResponse,
+        openconv_shared::api::role::PermissionPreviewResponse,
         // Invite
         openconv_shared::api::invite::CreateInviteRequest,
         openconv_shared::api::invite::InviteResponse,
         openconv_shared::api::invite::InviteInfoResponse,
+        openconv_shared::api::invite::InvitePreflightResponse,
+        openconv_shared::api::invite::InviteBlocker,
         // DM Channel
         openconv_shared::api::dm_channel::CreateDmChannelRequest,
         openconv_shared::api::dm_channel::DmChannelResponse,
         openconv_shared::api::dm_channel::AddDmMemberRequest,
+        openconv_shared::api::dm_channel::SetDmArchiveRequest,
         // File
         openconv_shared::api::file::FileResponse,
         openconv_shared::api::file::FileMetaResponse,
         crate::handlers::files::FileUploadBody,
+        openconv_shared::api::file::SetAttachmentsRequest,
+        openconv_shared::api::file::MessageAttachmentsResponse,
+        openconv_shared::api::file::CreateUploadSessionRequest,
+        openconv_shared::api::file::UploadSessionResponse,
+        openconv_shared::api::file::DownloadUrlResponse,
+        openconv_shared::api::file::StorageUsageResponse,
+        openconv_shared::api::media::ImageAssetResponse,
         // Message
+        openconv_shared::api::envelope::PayloadKind,
         openconv_shared::api::message::SendMessageRequest,
         openconv_shared::api::message::MessageResponse,
         openconv_shared::api::message::MessageHistoryQuery,
         openconv_shared::api::message::MessageHistoryResponse,
+        openconv_shared::api::message::HistoryBackfillRequest,
+        openconv_shared::api::message::HistoryBackfillFrame,
         // WS
         openconv_shared::api::ws::PresenceStatus,
         openconv_shared::api::ws::ClientMessage,
         openconv_shared::api::ws::ServerMessage,
+        // Admin
+        openconv_shared::api::admin::SuspendUserRequest,
+        openconv_shared::api::admin::UserSuspensionResponse,
+        openconv_shared::api::admin::DependencyHealth,
+        openconv_shared::api::admin::HealthReportResponse,
+        openconv_shared::api::admin::JobStatusResponse,
+        openconv_shared::api::admin::ConfigReloadResponse,
+        openconv_shared::api::admin::EmailDeadLetterResponse,
+        openconv_shared::api::admin::EmailQueueStatsResponse,
+        openconv_shared::api::admin::CreateIpDenylistEntryRequest,
+        openconv_shared::api::admin::IpDenylistEntryResponse,
+        openconv_shared::api::admin::IpDenylistResponse,
+        openconv_shared::api::admin::IpBlockResponse,
+        openconv_shared::api::policy::AcceptPolicyRequest,
+        openconv_shared::api::policy::PolicyStatusResponse,
+        // Discovery
+        crate::handlers::discovery::DiscoveryParamsResponse,
+        crate::handlers::discovery::DiscoverContactsRequest,
+        crate::handlers::discovery::DiscoveredContact,
+        crate::handlers::discovery::DiscoverContactsResponse,
+        // Voice
+        openconv_shared::api::voice::TurnCredentialsResponse,
+        openconv_shared::api::voice::VoiceState,
+        openconv_shared::api::voice::VoiceStateListResponse,
         // Server-local
         crate::handlers::users::UserProfileResponse,
         crate::handlers::users::PublicProfileResponse,
         crate::handlers::users::UpdateProfileRequest,
         crate::handlers::users::SearchUsersQuery,
         crate::handlers::users::SearchUsersResponse,
+        crate::handlers::users::BulkUsersRequest,
+        crate::handlers::users::BulkUsersResponse,
         crate::handlers::users::UploadPreKeysRequest,
         crate::handlers::users::PreKeyBundleResponse,
+        crate::handlers::users::DeviceIdentityKey,
+        crate::handlers::users::DeviceKeysResponse,
+        openconv_shared::api::settings::EmojiPreferences,
+        openconv_shared::api::settings::EmojiSize,
+        openconv_shared::api::settings::FrequentEmoji,
+        openconv_shared::api::settings::SkinTone,
+        openconv_shared::api::settings::UpdateEmojiPreferencesRequest,
+        openconv_shared::api::settings::RenderSettings,
+        openconv_shared::api::settings::RenderSettingsOverrides,
+        openconv_shared::api::settings::UpdateUserSettingsRequest,
+        openconv_shared::api::settings::UserSettingsResponse,
+        crate::handlers::dm_channels::ListQuery,
         crate::handlers::dm_channels::MessageQuery,
         crate::handlers::dm_channels::MessagePage,
         crate::handlers::dm_channels::MessageResponse,
@@ -178,6 +382,13 @@ use utoipa::{Modify, OpenApi};
         (name = "Messages", description = "Message history"),
         (name = "Files", description = "Encrypted file upload and download"),
         (name = "WebSocket", description = "WebSocket ticket and upgrade"),
+        (name = "Bots", description = "Guild bot accounts"),
+        (name = "Bridges", description = "Bridge applications and their puppet users"),
+        (name = "OAuth", description = "OAuth 2 authorization for third-party apps"),
+        (name = "Admin", description = "Instance administration"),
+        (name = "Policies", description = "Terms of service and privacy policy acceptance"),
+        (name = "Discovery", description = "Finding contacts' accounts by truncated salted email hashes"),
+        (name = "Voice", description = "Voice channel state and STUN/TURN credentials for voice calls"),
     ),
     modifiers(&BearerAuth),
 )]
@@ -197,6 +408,30 @@ impl Modify for BearerAuth {
                         .build(),
                 ),
             );
+            components.add_security_scheme(
+                "bot_auth",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                    "Authorization",
+                    "Bot <token>",
+                ))),
+            );
+            components.add_security_scheme(
+                "bridge_auth",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                    "Authorization",
+                    "Bridge <token>",
+                ))),
+            );
+            components.add_security_scheme(
+                "oauth",
+                SecurityScheme::OAuth2(OAuth2::new([Flow::AuthorizationCode(
+                    AuthorizationCode::new(
+                        "/api/oauth/authorize",
+                        "/api/oauth/token",
+                        Scopes::from_iter(OAuthScope::ALL.map(|s| (s.as_str(), s.as_str()))),
+                    ),
+                )])),
+            );
         }
     }
 }
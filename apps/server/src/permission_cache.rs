@@ -0,0 +1,129 @@
+//! Effective guild permissions cached in Redis.
+//!
+//! Every guild- and channel-scoped request resolves the caller's
+//! permissions, which joins three tables. Results are cached in one Redis
+//! hash per guild, keyed by user. A change to a member's roles or
+//! membership drops that member's entry (see `member_events::publish`); a
+//! change to a role's permissions drops the whole guild. Entries also age
+//! out after [`TTL`], which bounds the damage of a missed invalidation.
+//!
+//! Only members are cached, so joining never has to clear a negative
+//! entry. Redis failures are logged and the caller falls through to the
+//! database.
+
+use std::time::Duration;
+
+use fred::interfaces::{HashesInterface, KeysInterface, LuaInterface};
+use openconv_shared::ids::{GuildId, UserId};
+use openconv_shared::permissions::Permissions;
+
+use crate::redis::RedisPool;
+
+/// How long an entry is trusted without an invalidation.
+pub const TTL: Duration = Duration::from_secs(60);
+
+/// Stores one entry and keeps the guild hash alive for another TTL.
+const PUT_SCRIPT: &str = r#"
+redis.call('HSET', KEYS[1], ARGV[1], ARGV[2])
+redis.call('EXPIRE', KEYS[1], ARGV[3])
+return 1
+"#;
+
+fn guild_key(guild_id: GuildId) -> String {
+    format!("guild_perms:{guild_id}")
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// `<bits>:<cached at>`. The timestamp expires single entries, since the
+/// hash TTL is refreshed by every write.
+fn encode(perms: Permissions, cached_at: u64) -> String {
+    format!("{}:{cached_at}", perms.bits())
+}
+
+fn decode(value: &str, now: u64) -> Option<Permissions> {
+    let (bits, cached_at) = value.split_once(':')?;
+    let cached_at: u64 = cached_at.parse().ok()?;
+    if now.saturating_sub(cached_at) >= TTL.as_secs() {
+        return None;
+    }
+    Some(Permissions::from_bits_truncate(bits.parse().ok()?))
+}
+
+/// Cached permissions of a guild member, if fresh.
+pub async fn get(redis: &RedisPool, user_id: UserId, guild_id: GuildId) -> Option<Permissions> {
+    let value: Option<String> = match redis
+        .hget(redis.key(guild_key(guild_id)), user_id.to_string())
+        .await
+    {
+        Ok(value) => value,
+        Err(e) => {
+            tracing::warn!(error = %e, "permission cache read failed");
+            return None;
+        }
+    };
+    decode(&value?, now_secs())
+}
+
+/// Cache the resolved permissions of a guild member.
+pub async fn put(redis: &RedisPool, user_id: UserId, guild_id: GuildId, perms: Permissions) {
+    let result: Result<i64, _> = redis
+        .eval(
+            PUT_SCRIPT,
+            vec![redis.key(guild_key(guild_id))],
+            vec![
+                user_id.to_string(),
+                encode(perms, now_secs()),
+                TTL.as_secs().to_string(),
+            ],
+        )
+        .await;
+    if let Err(e) = result {
+        tracing::warn!(error = %e, "permission cache write failed");
+    }
+}
+
+/// Drop one member's entry after their roles or membership changed.
+pub async fn invalidate_member(redis: &RedisPool, user_id: UserId, guild_id: GuildId) {
+    let result: Result<i64, _> = redis
+        .hdel(redis.key(guild_key(guild_id)), user_id.to_string())
+        .await;
+    if let Err(e) = result {
+        tracing::warn!(error = %e, guild_id = %guild_id, user_id = %user_id, "permission cache invalidation failed");
+    }
+}
+
+/// Drop every entry for a guild after a role's permissions changed.
+pub async fn invalidate_guild(redis: &RedisPool, guild_id: GuildId) {
+    let result: Result<i64, _> = redis.del(redis.key(guild_key(guild_id))).await;
+    if let Err(e) = result {
+        tracing::warn!(error = %e, guild_id = %guild_id, "permission cache invalidation failed");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_round_trip_until_ttl() {
+        let perms = Permissions::SEND_MESSAGES | Permissions::MANAGE_ROLES;
+        let value = encode(perms, 1_000);
+        assert_eq!(decode(&value, 1_000), Some(perms));
+        assert_eq!(decode(&value, 1_000 + TTL.as_secs() - 1), Some(perms));
+        assert_eq!(decode(&value, 1_000 + TTL.as_secs()), None);
+    }
+
+    #[test]
+    fn malformed_entries_are_misses() {
+        assert_eq!(decode("", 0), None);
+        assert_eq!(decode("12", 0), None);
+        assert_eq!(decode("x:0", 0), None);
+        assert_eq!(decode("12:y", 0), None);
+    }
+}
@@ -0,0 +1,233 @@
+//! Circuit breaker for Redis.
+//!
+//! After `redis.breaker_failure_threshold` consecutive connection failures
+//! the breaker opens and commands fail immediately for
+//! `redis.breaker_cooldown_seconds`, instead of each request waiting out
+//! its own timeout against a dead server. Once the cooldown passes,
+//! commands are let through again; the first success closes the breaker and
+//! a failure reopens it for another cooldown.
+//!
+//! Only connectivity failures count. A script error or a corrupt value is a
+//! bug, not an outage, and is passed through as [`RedisError::Command`].
+
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use fred::error::{Error, ErrorKind};
+use openconv_shared::error::OpenConvError;
+
+use crate::error::ServerError;
+
+/// Why a guarded Redis command did not produce a value.
+#[derive(Debug)]
+pub enum RedisError {
+    /// Redis is unreachable, or was recently enough that the breaker is
+    /// open. Callers should retry after `retry_after`.
+    Unavailable { retry_after: Duration },
+    /// Redis answered, but with an error.
+    Command(Error),
+}
+
+impl fmt::Display for RedisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unavailable { retry_after } => {
+                write!(f, "redis unavailable, retry in {}s", retry_after.as_secs())
+            }
+            Self::Command(e) => write!(f, "redis error: {e}"),
+        }
+    }
+}
+
+impl From<RedisError> for OpenConvError {
+    fn from(e: RedisError) -> Self {
+        match e {
+            RedisError::Unavailable { retry_after } => OpenConvError::TemporarilyUnavailable {
+                service: "redis".into(),
+                retry_after_seconds: retry_after.as_secs().max(1),
+            },
+            RedisError::Command(e) => OpenConvError::Internal(format!("redis error: {e}")),
+        }
+    }
+}
+
+impl From<RedisError> for ServerError {
+    fn from(e: RedisError) -> Self {
+        ServerError(e.into())
+    }
+}
+
+/// Whether `e` means Redis could not be reached, as opposed to Redis
+/// rejecting the command.
+fn is_connectivity_error(e: &Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::IO
+            | ErrorKind::Timeout
+            | ErrorKind::Canceled
+            | ErrorKind::Routing
+            | ErrorKind::Cluster
+            | ErrorKind::Sentinel
+            | ErrorKind::Backpressure
+    )
+}
+
+#[derive(Debug, Default)]
+struct State {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+/// Consecutive-failure circuit breaker shared by every clone of a
+/// [`RedisPool`](super::RedisPool).
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// How long the breaker stays open.
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+
+    /// `Err` with the remaining cooldown while the breaker is open.
+    pub fn check(&self) -> Result<(), RedisError> {
+        self.check_at(Instant::now())
+    }
+
+    /// Whether commands are currently being let through.
+    pub fn is_closed(&self) -> bool {
+        self.check().is_ok()
+    }
+
+    fn check_at(&self, now: Instant) -> Result<(), RedisError> {
+        let state = self.state.lock().unwrap();
+        match state.open_until {
+            Some(until) if until > now => Err(RedisError::Unavailable {
+                retry_after: until - now,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Count the outcome of a command, translating connectivity failures to
+    /// [`RedisError::Unavailable`].
+    pub fn record<T>(&self, result: Result<T, Error>) -> Result<T, RedisError> {
+        self.record_at(Instant::now(), result)
+    }
+
+    fn record_at<T>(&self, now: Instant, result: Result<T, Error>) -> Result<T, RedisError> {
+        let mut state = self.state.lock().unwrap();
+        match result {
+            Ok(value) => {
+                if state.open_until.take().is_some() {
+                    tracing::info!("redis circuit breaker closed");
+                }
+                state.consecutive_failures = 0;
+                Ok(value)
+            }
+            Err(e) if is_connectivity_error(&e) => {
+                state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+                // A failed probe after the cooldown reopens immediately
+                if state.consecutive_failures >= self.failure_threshold
+                    || state.open_until.is_some()
+                {
+                    if state.open_until.is_none_or(|until| until <= now) {
+                        tracing::warn!(
+                            error = %e,
+                            failures = state.consecutive_failures,
+                            cooldown_secs = self.cooldown.as_secs(),
+                            "redis circuit breaker opened"
+                        );
+                    }
+                    state.open_until = Some(now + self.cooldown);
+                }
+                Err(RedisError::Unavailable {
+                    retry_after: self.cooldown,
+                })
+            }
+            Err(e) => Err(RedisError::Command(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn io_error() -> Result<(), Error> {
+        Err(Error::new(ErrorKind::IO, "connection refused"))
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures_and_recovers() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(10));
+        let start = Instant::now();
+
+        assert!(breaker.record_at(start, io_error()).is_err());
+        assert!(breaker.check_at(start).is_ok());
+        assert!(breaker.record_at(start, io_error()).is_err());
+
+        let Err(RedisError::Unavailable { retry_after }) =
+            breaker.check_at(start + Duration::from_secs(4))
+        else {
+            panic!("expected breaker to be open");
+        };
+        assert_eq!(retry_after, Duration::from_secs(6));
+
+        // Cooldown over: a probe is let through and its success closes the breaker
+        let later = start + Duration::from_secs(11);
+        assert!(breaker.check_at(later).is_ok());
+        assert!(breaker.record_at(later, Ok(())).is_ok());
+        assert!(breaker.record_at(later, io_error()).is_err());
+        assert!(breaker.check_at(later).is_ok());
+    }
+
+    #[test]
+    fn failed_probe_reopens_immediately() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(5));
+        let start = Instant::now();
+        for _ in 0..3 {
+            let _ = breaker.record_at(start, io_error());
+        }
+        let later = start + Duration::from_secs(6);
+        assert!(breaker.check_at(later).is_ok());
+        let _ = breaker.record_at(later, io_error());
+        assert!(breaker.check_at(later).is_err());
+    }
+
+    #[test]
+    fn command_errors_do_not_trip_the_breaker() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(5));
+        let result = breaker.record::<()>(Err(Error::new(ErrorKind::Parse, "bad reply")));
+        assert!(matches!(result, Err(RedisError::Command(_))));
+        assert!(breaker.is_closed());
+    }
+
+    #[test]
+    fn unavailable_maps_to_temporarily_unavailable() {
+        let err: OpenConvError = RedisError::Unavailable {
+            retry_after: Duration::from_millis(300),
+        }
+        .into();
+        assert!(matches!(
+            err,
+            OpenConvError::TemporarilyUnavailable {
+                retry_after_seconds: 1,
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,226 @@
+//! Typed Redis keys.
+//!
+//! Each key type owns its format, and keys holding JSON also name the type
+//! stored under them and how long it lives, so writers and readers cannot
+//! drift apart. Keys format without the deployment prefix; the
+//! [`RedisPool`] helpers add it.
+
+use std::fmt;
+use std::time::Duration;
+
+use fred::error::{Error, ErrorKind};
+use fred::interfaces::KeysInterface;
+use fred::types::Expiration;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::{RedisError, RedisPool};
+
+/// A key whose value is JSON of a known type.
+pub trait JsonKey: fmt::Display {
+    type Value: Serialize + DeserializeOwned;
+
+    /// Lifetime of a stored value.
+    const TTL: Duration;
+}
+
+/// A pending registration, keyed by normalized email: `verify:{email}`.
+pub struct VerificationKey<'a>(pub &'a str);
+
+/// Redis storage format for verification codes. Also read by the code
+/// scripts in the auth handlers, which decrement `attempts_remaining` in
+/// place.
+#[derive(Serialize, Deserialize)]
+pub struct VerificationData {
+    /// Argon2id hash of the emailed code; see [`crate::one_time_code`].
+    pub code_hash: String,
+    pub display_name: String,
+    pub attempts_remaining: u32,
+}
+
+impl fmt::Display for VerificationKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "verify:{}", self.0)
+    }
+}
+
+impl JsonKey for VerificationKey<'_> {
+    type Value = VerificationData;
+    const TTL: Duration = Duration::from_secs(600);
+}
+
+/// A pending account recovery, keyed by normalized email: `recover:{email}`.
+pub struct RecoveryKey<'a>(pub &'a str);
+
+/// Redis storage format for recovery codes.
+#[derive(Serialize, Deserialize)]
+pub struct RecoveryData {
+    /// Argon2id hash of the emailed code; see [`crate::one_time_code`].
+    pub code_hash: String,
+    pub attempts_remaining: u32,
+}
+
+impl fmt::Display for RecoveryKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "recover:{}", self.0)
+    }
+}
+
+impl JsonKey for RecoveryKey<'_> {
+    type Value = RecoveryData;
+    const TTL: Duration = Duration::from_secs(600);
+}
+
+/// The single-use login challenge for a public key: `challenge:{public_key}`.
+pub struct ChallengeKey<'a>(pub &'a str);
+
+/// Redis storage format for login challenges.
+#[derive(Serialize, Deserialize)]
+pub struct StoredChallenge {
+    pub challenge: String,
+    /// Whether an account holds the key. Challenges are issued either way
+    /// so the endpoint does not reveal which keys are registered.
+    pub exists: bool,
+}
+
+impl fmt::Display for ChallengeKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "challenge:{}", self.0)
+    }
+}
+
+impl JsonKey for ChallengeKey<'_> {
+    type Value = StoredChallenge;
+    const TTL: Duration = Duration::from_secs(60);
+}
+
+/// A rate limiter counter. The value is the GCRA state kept by the
+/// limiter's script, so it has no JSON type.
+pub enum RateLimitKey<'a> {
+    /// `rl:ip:{ip}:{endpoint}`
+    Ip { ip: &'a str, endpoint: &'a str },
+    /// `rl:user:{user_id}:{endpoint}`
+    User { user_id: &'a str, endpoint: &'a str },
+    /// `rl:pk:{public_key}:{endpoint}`
+    PublicKey {
+        public_key: &'a str,
+        endpoint: &'a str,
+    },
+    /// `rl:email:{email}`, shared by every endpoint that sends email.
+    Email(&'a str),
+    /// `rl:joins:{guild_id}`, counting joins through invites.
+    GuildJoins(&'a str),
+    /// `rl:discovery:{user_id}`, counting hashes looked up by contact
+    /// discovery rather than requests.
+    DiscoveryHashes(&'a str),
+}
+
+impl fmt::Display for RateLimitKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ip { ip, endpoint } => write!(f, "rl:ip:{ip}:{endpoint}"),
+            Self::User { user_id, endpoint } => write!(f, "rl:user:{user_id}:{endpoint}"),
+            Self::PublicKey {
+                public_key,
+                endpoint,
+            } => write!(f, "rl:pk:{public_key}:{endpoint}"),
+            Self::Email(email) => write!(f, "rl:email:{email}"),
+            Self::GuildJoins(guild_id) => write!(f, "rl:joins:{guild_id}"),
+            Self::DiscoveryHashes(user_id) => write!(f, "rl:discovery:{user_id}"),
+        }
+    }
+}
+
+fn decode<T: DeserializeOwned>(key: &impl fmt::Display, json: &str) -> Result<T, Error> {
+    serde_json::from_str(json)
+        .map_err(|e| Error::new(ErrorKind::Parse, format!("corrupt value at {key}: {e}")))
+}
+
+impl RedisPool {
+    /// The value stored under `key`, if any.
+    pub async fn get_json<K: JsonKey>(&self, key: &K) -> Result<Option<K::Value>, RedisError> {
+        let json: Option<String> = self.guarded(self.get(self.key(key))).await?;
+        json.map(|json| decode(key, &json))
+            .transpose()
+            .map_err(RedisError::Command)
+    }
+
+    /// Fetch and delete the value under `key`, so it can be used once.
+    pub async fn take_json<K: JsonKey>(&self, key: &K) -> Result<Option<K::Value>, RedisError> {
+        let json: Option<String> = self.guarded(self.getdel(self.key(key))).await?;
+        json.map(|json| decode(key, &json))
+            .transpose()
+            .map_err(RedisError::Command)
+    }
+
+    /// Store `value` under `key` for [`JsonKey::TTL`], replacing any value.
+    pub async fn set_json<K: JsonKey>(&self, key: &K, value: &K::Value) -> Result<(), RedisError> {
+        let json = serde_json::to_string(value).map_err(|e| {
+            RedisError::Command(Error::new(
+                ErrorKind::Parse,
+                format!("serialize {key}: {e}"),
+            ))
+        })?;
+        self.guarded(self.set(
+            self.key(key),
+            json,
+            Some(Expiration::EX(K::TTL.as_secs() as i64)),
+            None,
+            false,
+        ))
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_keep_their_formats() {
+        assert_eq!(VerificationKey("a@b.c").to_string(), "verify:a@b.c");
+        assert_eq!(RecoveryKey("a@b.c").to_string(), "recover:a@b.c");
+        assert_eq!(ChallengeKey("dGVzdA==").to_string(), "challenge:dGVzdA==");
+        assert_eq!(
+            RateLimitKey::Ip {
+                ip: "10.0.0.1",
+                endpoint: "auth"
+            }
+            .to_string(),
+            "rl:ip:10.0.0.1:auth"
+        );
+        assert_eq!(
+            RateLimitKey::PublicKey {
+                public_key: "dGVzdA==",
+                endpoint: "challenge"
+            }
+            .to_string(),
+            "rl:pk:dGVzdA==:challenge"
+        );
+        assert_eq!(RateLimitKey::Email("a@b.c").to_string(), "rl:email:a@b.c");
+        assert_eq!(RateLimitKey::GuildJoins("g1").to_string(), "rl:joins:g1");
+        assert_eq!(
+            RateLimitKey::DiscoveryHashes("u1").to_string(),
+            "rl:discovery:u1"
+        );
+    }
+
+    #[test]
+    fn stored_challenge_roundtrip() {
+        let data = StoredChallenge {
+            challenge: "dGVzdA==".into(),
+            exists: true,
+        };
+        let json = serde_json::to_string(&data).unwrap();
+        let back: StoredChallenge = decode(&ChallengeKey("k"), &json).unwrap();
+        assert_eq!(back.challenge, "dGVzdA==");
+        assert!(back.exists);
+    }
+
+    #[test]
+    fn corrupt_values_are_parse_errors() {
+        let err = decode::<RecoveryData>(&RecoveryKey("a@b.c"), "{").unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::Parse);
+        assert!(err.details().contains("recover:a@b.c"));
+    }
+}
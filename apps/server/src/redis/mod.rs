@@ -0,0 +1,188 @@
+//! Redis connections and key namespacing.
+//!
+//! [`RedisPool`] wraps the fred pool with the configured `redis.key_prefix`.
+//! Every key the server reads or writes is built with [`RedisPool::key`], so
+//! several deployments or test runs can share one Redis without seeing each
+//! other's counters, codes and caches. Keys with a fixed format and their
+//! stored values are typed in [`keys`].
+//!
+//! Commands run through [`RedisPool::guarded`] feed the pool's
+//! [`breaker::CircuitBreaker`], so an outage fails fast with
+//! [`RedisError::Unavailable`] rather than a timeout per request.
+
+pub mod breaker;
+pub mod keys;
+
+use std::fmt::Display;
+use std::future::Future;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
+
+use fred::prelude::*;
+use fred::types::config::ServerConfig;
+
+use crate::config::{RedisConfig, RedisMode};
+
+use breaker::CircuitBreaker;
+pub use breaker::RedisError;
+
+/// A fred pool plus the prefix for this deployment's keys and the breaker
+/// guarding it. Commands go straight to the pool through `Deref`; only key
+/// names go through [`RedisPool::key`].
+#[derive(Clone)]
+pub struct RedisPool {
+    pool: fred::clients::Pool,
+    prefix: Arc<str>,
+    breaker: Arc<CircuitBreaker>,
+}
+
+impl RedisPool {
+    /// Wrap `pool` with the default breaker settings.
+    pub fn new(pool: fred::clients::Pool, prefix: &str) -> Self {
+        let defaults = RedisConfig::default();
+        Self {
+            pool,
+            prefix: prefix.into(),
+            breaker: Arc::new(CircuitBreaker::new(
+                defaults.breaker_failure_threshold,
+                Duration::from_secs(defaults.breaker_cooldown_seconds),
+            )),
+        }
+    }
+
+    /// Replace the breaker settings.
+    pub fn with_breaker(mut self, failure_threshold: u32, cooldown: Duration) -> Self {
+        self.breaker = Arc::new(CircuitBreaker::new(failure_threshold, cooldown));
+        self
+    }
+
+    /// Run `command` unless the breaker is open, counting its outcome.
+    /// A pool that has not connected yet counts as unavailable.
+    pub async fn guarded<T>(
+        &self,
+        command: impl Future<Output = Result<T, fred::error::Error>>,
+    ) -> Result<T, RedisError> {
+        self.breaker.check()?;
+        if !self.pool.is_connected() {
+            return Err(RedisError::Unavailable {
+                retry_after: self.breaker_cooldown(),
+            });
+        }
+        self.breaker.record(command.await)
+    }
+
+    /// How long an open breaker rejects commands.
+    pub fn breaker_cooldown(&self) -> Duration {
+        self.breaker.cooldown()
+    }
+
+    /// `key` inside this deployment's namespace.
+    pub fn key(&self, key: impl Display) -> String {
+        format!("{}{key}", self.prefix)
+    }
+
+    /// A SCAN/KEYS glob over this deployment's keys. Glob characters in
+    /// the prefix match literally.
+    pub fn key_pattern(&self, pattern: impl Display) -> String {
+        let mut escaped = String::with_capacity(self.prefix.len());
+        for c in self.prefix.chars() {
+            if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        format!("{escaped}{pattern}")
+    }
+}
+
+impl Deref for RedisPool {
+    type Target = fred::clients::Pool;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pool
+    }
+}
+
+/// The fred config for `config.mode`. Cluster and Sentinel deployments take
+/// their hosts from `nodes` and everything else from `url`.
+fn client_config(config: &RedisConfig) -> Result<Config, fred::error::Error> {
+    let mut redis_config = Config::from_url(&config.url)?;
+    if config.mode != RedisMode::Standalone {
+        let nodes = config
+            .node_addresses()
+            .map_err(|e| fred::error::Error::new(fred::error::ErrorKind::Config, e))?;
+        redis_config.server = match config.mode {
+            RedisMode::Cluster => ServerConfig::new_clustered(nodes),
+            _ => ServerConfig::new_sentinel(nodes, config.sentinel_service_name.clone()),
+        };
+    }
+    Ok(redis_config)
+}
+
+/// Initialize a Redis connection pool from config.
+/// Returns an error if the connection cannot be established.
+pub async fn create_redis_pool(config: &RedisConfig) -> Result<RedisPool, fred::error::Error> {
+    let pool = fred::clients::Pool::new(client_config(config)?, None, None, None, 5)?;
+    pool.init().await?;
+    pool.wait_for_connect().await?;
+    Ok(RedisPool::new(pool, &config.key_prefix).with_breaker(
+        config.breaker_failure_threshold,
+        Duration::from_secs(config.breaker_cooldown_seconds),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn redis_pool_fails_gracefully_with_invalid_url() {
+        let config = RedisConfig {
+            url: "redis://invalid-host-that-does-not-exist:9999".to_string(),
+            ..Default::default()
+        };
+        let result: Result<RedisPool, _> = create_redis_pool(&config).await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn client_config_uses_nodes_outside_standalone_mode() {
+        let config = RedisConfig {
+            url: "redis://:secret@ignored:6379/3".to_string(),
+            mode: RedisMode::Cluster,
+            nodes: vec!["redis-1:7000".into(), "redis-2:7001".into()],
+            ..Default::default()
+        };
+        let redis_config = client_config(&config).unwrap();
+        assert!(redis_config.server.is_clustered());
+        assert_eq!(redis_config.password.as_deref(), Some("secret"));
+        assert_eq!(redis_config.server.hosts().len(), 2);
+
+        let config = RedisConfig {
+            mode: RedisMode::Sentinel,
+            nodes: vec!["sentinel-a:26379".into()],
+            sentinel_service_name: "mymaster".into(),
+            ..Default::default()
+        };
+        let redis_config = client_config(&config).unwrap();
+        assert!(redis_config.server.is_sentinel());
+    }
+
+    #[test]
+    fn keys_and_patterns_carry_the_prefix() {
+        let pool = fred::clients::Pool::new(Config::default(), None, None, None, 1).unwrap();
+        assert_eq!(
+            RedisPool::new(pool.clone(), "openconv:test:").key("verify:a@b.c"),
+            "openconv:test:verify:a@b.c"
+        );
+        assert_eq!(
+            RedisPool::new(pool.clone(), "").key("verify:a@b.c"),
+            "verify:a@b.c"
+        );
+        assert_eq!(
+            RedisPool::new(pool, "ci[3]*:").key_pattern("rl:*"),
+            r"ci\[3\]\*:rl:*"
+        );
+    }
+}
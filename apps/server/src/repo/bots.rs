@@ -0,0 +1,221 @@
+//! Bot accounts: guild-owned users that authenticate with a long-lived
+//! token instead of a key pair.
+//!
+//! A bot is an ordinary `users` row flagged `is_bot`, with one device and a
+//! guild membership, so messages, presence and the gateway treat it like
+//! any other member. Its `bots` row holds the token hash and the
+//! permission ceiling applied on top of its roles. Deleting a bot keeps
+//! its account row so its messages keep their sender.
+
+use openconv_shared::api::bot::BotResponse;
+use openconv_shared::ids::{DeviceId, GuildId, UserId};
+use openconv_shared::permissions::Permissions;
+
+use crate::extractors::client_info::ClientInfo;
+
+/// A bot to create in a guild.
+pub struct NewBot<'a> {
+    pub user_id: UserId,
+    pub device_id: DeviceId,
+    pub guild_id: GuildId,
+    pub display_name: &'a str,
+    pub permissions: Permissions,
+    pub token_hash: &'a str,
+    pub created_by: UserId,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct Bot {
+    pub user_id: UserId,
+    pub guild_id: GuildId,
+    pub display_name: String,
+    pub permissions: i64,
+    pub created_by: Option<UserId>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Bot {
+    pub fn into_response(self) -> BotResponse {
+        BotResponse {
+            id: self.user_id,
+            guild_id: self.guild_id,
+            display_name: self.display_name,
+            permissions: self.permissions as u64,
+            created_by: self.created_by,
+            created_at: self.created_at,
+            token: None,
+        }
+    }
+}
+
+const BOT_COLUMNS: &str =
+    "b.user_id, b.guild_id, u.display_name, b.permissions, b.created_by, b.created_at";
+
+/// Create the bot's account, device and bot row, and add it to the guild
+/// with the default member role.
+///
+/// Bots never sign in with a key, so the account gets placeholder
+/// identity key and email values that no client can produce.
+pub async fn create(conn: &mut sqlx::PgConnection, bot: &NewBot<'_>) -> Result<Bot, sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO users (id, public_key, email, display_name, is_bot) VALUES ($1, $2, $3, $4, true)",
+    )
+    .bind(bot.user_id)
+    .bind(format!("bot:{}", bot.user_id))
+    .bind(format!("{}@bots.invalid", bot.user_id))
+    .bind(bot.display_name)
+    .execute(&mut *conn)
+    .await?;
+
+    super::devices::insert(
+        &mut *conn,
+        bot.device_id,
+        bot.user_id,
+        "bot",
+        &ClientInfo::default(),
+    )
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO bots (user_id, guild_id, device_id, permissions, token_hash, created_by) \
+         VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(bot.user_id)
+    .bind(bot.guild_id)
+    .bind(bot.device_id)
+    .bind(bot.permissions.bits() as i64)
+    .bind(bot.token_hash)
+    .bind(bot.created_by)
+    .execute(&mut *conn)
+    .await?;
+
+    super::guilds::add_member(&mut *conn, bot.guild_id, bot.user_id).await?;
+    sqlx::query(
+        "INSERT INTO guild_member_roles (user_id, guild_id, role_id) \
+         SELECT $1, $2, id FROM roles WHERE guild_id = $2 AND role_type = 'member'",
+    )
+    .bind(bot.user_id)
+    .bind(bot.guild_id)
+    .execute(&mut *conn)
+    .await?;
+
+    get(&mut *conn, bot.guild_id, bot.user_id)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)
+}
+
+pub async fn get<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<Option<Bot>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as(&format!(
+        "SELECT {BOT_COLUMNS} FROM bots b JOIN users u ON u.id = b.user_id \
+         WHERE b.guild_id = $1 AND b.user_id = $2"
+    ))
+    .bind(guild_id)
+    .bind(user_id)
+    .fetch_optional(executor)
+    .await
+}
+
+pub async fn list_for_guild<'e, E>(executor: E, guild_id: GuildId) -> Result<Vec<Bot>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as(&format!(
+        "SELECT {BOT_COLUMNS} FROM bots b JOIN users u ON u.id = b.user_id \
+         WHERE b.guild_id = $1 ORDER BY b.created_at"
+    ))
+    .bind(guild_id)
+    .fetch_all(executor)
+    .await
+}
+
+pub async fn count_for_guild<'e, E>(executor: E, guild_id: GuildId) -> Result<i64, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar("SELECT COUNT(*) FROM bots WHERE guild_id = $1")
+        .bind(guild_id)
+        .fetch_one(executor)
+        .await
+}
+
+/// Swap in a new token hash. Returns false when the guild has no such bot.
+pub async fn replace_token<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    user_id: UserId,
+    token_hash: &str,
+) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let result = sqlx::query(
+        "UPDATE bots SET token_hash = $3, token_rotated_at = NOW() \
+         WHERE guild_id = $1 AND user_id = $2",
+    )
+    .bind(guild_id)
+    .bind(user_id)
+    .bind(token_hash)
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Retire a bot: drop its bot row, device and guild membership. The
+/// account row stays so the bot's messages keep their sender. Returns false
+/// when the guild has no such bot.
+pub async fn delete(
+    conn: &mut sqlx::PgConnection,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<bool, sqlx::Error> {
+    let device_id: Option<DeviceId> = sqlx::query_scalar(
+        "DELETE FROM bots WHERE guild_id = $1 AND user_id = $2 RETURNING device_id",
+    )
+    .bind(guild_id)
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(device_id) = device_id else {
+        return Ok(false);
+    };
+
+    sqlx::query("DELETE FROM devices WHERE id = $1")
+        .bind(device_id)
+        .execute(&mut *conn)
+        .await?;
+    super::guilds::remove_member(&mut *conn, guild_id, user_id).await?;
+    Ok(true)
+}
+
+/// The identity a bot token stands for.
+#[derive(Debug, sqlx::FromRow)]
+pub struct BotIdentity {
+    pub user_id: UserId,
+    pub device_id: DeviceId,
+    pub guild_id: GuildId,
+}
+
+/// Look up the bot holding a token, unless its account is suspended.
+pub async fn authenticate<'e, E>(
+    executor: E,
+    token_hash: &str,
+) -> Result<Option<BotIdentity>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as(
+        "SELECT b.user_id, b.device_id, b.guild_id FROM bots b \
+         JOIN users u ON u.id = b.user_id \
+         WHERE b.token_hash = $1 AND u.suspended_at IS NULL",
+    )
+    .bind(token_hash)
+    .fetch_optional(executor)
+    .await
+}
@@ -0,0 +1,357 @@
+//! Bridge applications and the puppet users they manage.
+//!
+//! A puppet is an ordinary `users` row with one device, mapped to the
+//! remote account it mirrors by `(bridge_id, remote_id)`. Bridges act as
+//! their puppets through the regular API, so puppets join guilds and send
+//! messages like anyone else. As with bots, removing a puppet keeps its
+//! account row so its messages keep their sender.
+
+use openconv_shared::api::bridge::{BridgeResponse, PuppetResponse};
+use openconv_shared::ids::{DeviceId, GuildId, UserId};
+
+use crate::extractors::client_info::ClientInfo;
+
+#[derive(sqlx::FromRow)]
+pub struct Bridge {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub namespace: String,
+    pub created_by: Option<UserId>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Bridge {
+    pub fn into_response(self) -> BridgeResponse {
+        BridgeResponse {
+            id: self.id,
+            name: self.name,
+            namespace: self.namespace,
+            created_by: self.created_by,
+            created_at: self.created_at,
+            token: None,
+        }
+    }
+}
+
+const BRIDGE_COLUMNS: &str = "id, name, namespace, created_by, created_at";
+
+/// Register a bridge. Fails with a unique violation when the namespace is
+/// taken.
+pub async fn insert<'e, E>(
+    executor: E,
+    name: &str,
+    namespace: &str,
+    token_hash: &str,
+    created_by: UserId,
+) -> Result<Bridge, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as(&format!(
+        "INSERT INTO bridges (name, namespace, token_hash, created_by) \
+         VALUES ($1, $2, $3, $4) RETURNING {BRIDGE_COLUMNS}"
+    ))
+    .bind(name)
+    .bind(namespace)
+    .bind(token_hash)
+    .bind(created_by)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn get<'e, E>(executor: E, bridge_id: uuid::Uuid) -> Result<Option<Bridge>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as(&format!(
+        "SELECT {BRIDGE_COLUMNS} FROM bridges WHERE id = $1"
+    ))
+    .bind(bridge_id)
+    .fetch_optional(executor)
+    .await
+}
+
+pub async fn list<'e, E>(executor: E) -> Result<Vec<Bridge>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as(&format!(
+        "SELECT {BRIDGE_COLUMNS} FROM bridges ORDER BY created_at"
+    ))
+    .fetch_all(executor)
+    .await
+}
+
+/// Swap in a new token hash. Returns false when there is no such bridge.
+pub async fn replace_token<'e, E>(
+    executor: E,
+    bridge_id: uuid::Uuid,
+    token_hash: &str,
+) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let result =
+        sqlx::query("UPDATE bridges SET token_hash = $2, token_rotated_at = NOW() WHERE id = $1")
+            .bind(bridge_id)
+            .bind(token_hash)
+            .execute(executor)
+            .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Look up the bridge holding a token.
+pub async fn authenticate<'e, E>(
+    executor: E,
+    token_hash: &str,
+) -> Result<Option<uuid::Uuid>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar("SELECT id FROM bridges WHERE token_hash = $1")
+        .bind(token_hash)
+        .fetch_optional(executor)
+        .await
+}
+
+#[derive(sqlx::FromRow)]
+pub struct Puppet {
+    pub user_id: UserId,
+    pub remote_id: String,
+    pub display_name: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Puppet {
+    pub fn into_response(self) -> PuppetResponse {
+        PuppetResponse {
+            user_id: self.user_id,
+            remote_id: self.remote_id,
+            display_name: self.display_name,
+            created_at: self.created_at,
+        }
+    }
+}
+
+const PUPPET_COLUMNS: &str = "p.user_id, p.remote_id, u.display_name, p.created_at";
+
+/// A puppet to create for a bridge.
+pub struct NewPuppet<'a> {
+    pub bridge_id: uuid::Uuid,
+    pub namespace: &'a str,
+    pub remote_id: &'a str,
+    pub user_id: UserId,
+    pub device_id: DeviceId,
+    pub display_name: &'a str,
+}
+
+/// Create a puppet's account, device and mapping.
+///
+/// Puppets never sign in with a key, so the account gets placeholder
+/// identity key and email values that no client can produce.
+pub async fn create_puppet(
+    conn: &mut sqlx::PgConnection,
+    puppet: &NewPuppet<'_>,
+) -> Result<Puppet, sqlx::Error> {
+    sqlx::query("INSERT INTO users (id, public_key, email, display_name) VALUES ($1, $2, $3, $4)")
+        .bind(puppet.user_id)
+        .bind(format!("puppet:{}", puppet.user_id))
+        .bind(format!(
+            "{}@{}.bridge.invalid",
+            puppet.user_id, puppet.namespace
+        ))
+        .bind(puppet.display_name)
+        .execute(&mut *conn)
+        .await?;
+
+    super::devices::insert(
+        &mut *conn,
+        puppet.device_id,
+        puppet.user_id,
+        "bridge",
+        &ClientInfo::default(),
+    )
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO bridge_puppets (user_id, bridge_id, device_id, remote_id) \
+         VALUES ($1, $2, $3, $4)",
+    )
+    .bind(puppet.user_id)
+    .bind(puppet.bridge_id)
+    .bind(puppet.device_id)
+    .bind(puppet.remote_id)
+    .execute(&mut *conn)
+    .await?;
+
+    get_puppet(&mut *conn, puppet.bridge_id, puppet.remote_id)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)
+}
+
+/// Update the display name the bridge mirrors from the remote account.
+pub async fn rename_puppet<'e, E>(
+    executor: E,
+    user_id: UserId,
+    display_name: &str,
+) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query("UPDATE users SET display_name = $2 WHERE id = $1")
+        .bind(user_id)
+        .bind(display_name)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// Find a puppet by the remote account it mirrors.
+pub async fn get_puppet<'e, E>(
+    executor: E,
+    bridge_id: uuid::Uuid,
+    remote_id: &str,
+) -> Result<Option<Puppet>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as(&format!(
+        "SELECT {PUPPET_COLUMNS} FROM bridge_puppets p JOIN users u ON u.id = p.user_id \
+         WHERE p.bridge_id = $1 AND p.remote_id = $2"
+    ))
+    .bind(bridge_id)
+    .bind(remote_id)
+    .fetch_optional(executor)
+    .await
+}
+
+/// Find a puppet by its local user ID.
+pub async fn get_puppet_by_user<'e, E>(
+    executor: E,
+    bridge_id: uuid::Uuid,
+    user_id: UserId,
+) -> Result<Option<Puppet>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as(&format!(
+        "SELECT {PUPPET_COLUMNS} FROM bridge_puppets p JOIN users u ON u.id = p.user_id \
+         WHERE p.bridge_id = $1 AND p.user_id = $2"
+    ))
+    .bind(bridge_id)
+    .bind(user_id)
+    .fetch_optional(executor)
+    .await
+}
+
+pub async fn list_puppets<'e, E>(
+    executor: E,
+    bridge_id: uuid::Uuid,
+) -> Result<Vec<Puppet>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as(&format!(
+        "SELECT {PUPPET_COLUMNS} FROM bridge_puppets p JOIN users u ON u.id = p.user_id \
+         WHERE p.bridge_id = $1 ORDER BY p.created_at"
+    ))
+    .bind(bridge_id)
+    .fetch_all(executor)
+    .await
+}
+
+/// The puppet a bridge may act as, with the device its requests use.
+/// Suspended puppets are skipped.
+pub async fn puppet_device<'e, E>(
+    executor: E,
+    bridge_id: uuid::Uuid,
+    user_id: UserId,
+) -> Result<Option<DeviceId>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar(
+        "SELECT p.device_id FROM bridge_puppets p JOIN users u ON u.id = p.user_id \
+         WHERE p.bridge_id = $1 AND p.user_id = $2 AND u.suspended_at IS NULL",
+    )
+    .bind(bridge_id)
+    .bind(user_id)
+    .fetch_optional(executor)
+    .await
+}
+
+/// Retire a puppet: drop its mapping, device and guild memberships. The
+/// account row stays so its messages keep their sender. Returns the
+/// puppet's user ID and the guilds it left, or `None` when the bridge has
+/// no such puppet.
+pub async fn delete_puppet(
+    conn: &mut sqlx::PgConnection,
+    bridge_id: uuid::Uuid,
+    remote_id: &str,
+) -> Result<Option<(UserId, Vec<GuildId>)>, sqlx::Error> {
+    let row: Option<(UserId, DeviceId)> = sqlx::query_as(
+        "DELETE FROM bridge_puppets WHERE bridge_id = $1 AND remote_id = $2 \
+         RETURNING user_id, device_id",
+    )
+    .bind(bridge_id)
+    .bind(remote_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some((user_id, device_id)) = row else {
+        return Ok(None);
+    };
+
+    sqlx::query("DELETE FROM devices WHERE id = $1")
+        .bind(device_id)
+        .execute(&mut *conn)
+        .await?;
+    let guilds =
+        sqlx::query_scalar("DELETE FROM guild_members WHERE user_id = $1 RETURNING guild_id")
+            .bind(user_id)
+            .fetch_all(&mut *conn)
+            .await?;
+    Ok(Some((user_id, guilds)))
+}
+
+/// Unregister a bridge, retiring all of its puppets the same way as
+/// [`delete_puppet`]. Returns the puppets' user IDs, or `None` when there
+/// is no such bridge.
+pub async fn delete(
+    conn: &mut sqlx::PgConnection,
+    bridge_id: uuid::Uuid,
+) -> Result<Option<Vec<UserId>>, sqlx::Error> {
+    let locked: Option<uuid::Uuid> =
+        sqlx::query_scalar("SELECT id FROM bridges WHERE id = $1 FOR UPDATE")
+            .bind(bridge_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+    if locked.is_none() {
+        return Ok(None);
+    }
+
+    let user_ids: Vec<UserId> =
+        sqlx::query_scalar("SELECT user_id FROM bridge_puppets WHERE bridge_id = $1")
+            .bind(bridge_id)
+            .fetch_all(&mut *conn)
+            .await?;
+    sqlx::query(
+        "DELETE FROM guild_members WHERE user_id IN \
+         (SELECT user_id FROM bridge_puppets WHERE bridge_id = $1)",
+    )
+    .bind(bridge_id)
+    .execute(&mut *conn)
+    .await?;
+    // Deleting the devices also drops the puppet mappings.
+    sqlx::query(
+        "DELETE FROM devices WHERE id IN \
+         (SELECT device_id FROM bridge_puppets WHERE bridge_id = $1)",
+    )
+    .bind(bridge_id)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query("DELETE FROM bridges WHERE id = $1")
+        .bind(bridge_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(Some(user_ids))
+}
@@ -0,0 +1,293 @@
+//! Devices and the pre-key bundles published for them.
+
+use openconv_shared::api::auth::DeviceInfo;
+use openconv_shared::ids::{DeviceId, UserId};
+
+use crate::extractors::client_info::ClientInfo;
+
+/// Register a new device, recording where it signed in from. Fails with a
+/// unique violation if `identity_key` already belongs to another device.
+pub async fn insert<'e, E>(
+    executor: E,
+    device_id: DeviceId,
+    user_id: UserId,
+    device_name: &str,
+    identity_key: Option<&str>,
+    client: &ClientInfo,
+) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        "INSERT INTO devices (id, user_id, device_name, identity_key, last_active, created_at, last_ip, last_geo_country) \
+         VALUES ($1, $2, $3, $4, NOW(), NOW(), $5, $6)",
+        device_id as DeviceId,
+        user_id as UserId,
+        device_name,
+        identity_key,
+        client.ip.as_deref(),
+        client.country.as_deref(),
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Register a device at login, or refresh it if it already exists. A
+/// device ID belonging to another user is left untouched. `identity_key`
+/// is only recorded on a device that has none yet.
+pub async fn upsert<'e, E>(
+    executor: E,
+    device_id: DeviceId,
+    user_id: UserId,
+    device_name: &str,
+    identity_key: Option<&str>,
+    client: &ClientInfo,
+) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        "INSERT INTO devices (id, user_id, device_name, identity_key, last_active, created_at, last_ip, last_geo_country) \
+         VALUES ($1, $2, $3, $4, NOW(), NOW(), $5, $6) \
+         ON CONFLICT (id) DO UPDATE SET last_active = NOW(), device_name = EXCLUDED.device_name, \
+             identity_key = COALESCE(devices.identity_key, EXCLUDED.identity_key), \
+             last_ip = EXCLUDED.last_ip, last_geo_country = EXCLUDED.last_geo_country \
+         WHERE devices.user_id = $2",
+        device_id as DeviceId,
+        user_id as UserId,
+        device_name,
+        identity_key,
+        client.ip.as_deref(),
+        client.country.as_deref(),
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// The device holding `identity_key` and its owner, unless the owner is
+/// suspended.
+pub async fn active_by_identity_key<'e, E>(
+    executor: E,
+    identity_key: &str,
+) -> Result<Option<(UserId, DeviceId)>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let row = sqlx::query!(
+        r#"SELECT d.user_id AS "user_id: UserId", d.id AS "id: DeviceId"
+         FROM devices d JOIN users u ON u.id = d.user_id
+         WHERE d.identity_key = $1 AND u.suspended_at IS NULL"#,
+        identity_key,
+    )
+    .fetch_optional(executor)
+    .await?;
+    Ok(row.map(|r| (r.user_id, r.id)))
+}
+
+/// A device's own identity key, if it has one.
+pub async fn identity_key<'e, E>(
+    executor: E,
+    device_id: DeviceId,
+) -> Result<Option<String>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let key = sqlx::query_scalar!(
+        "SELECT identity_key FROM devices WHERE id = $1",
+        device_id as DeviceId,
+    )
+    .fetch_optional(executor)
+    .await?;
+    Ok(key.flatten())
+}
+
+/// Whether `device_id` is a device of `user_id` that predates per-device
+/// identity keys and so still signs in with the account key.
+pub async fn is_unkeyed<'e, E>(
+    executor: E,
+    user_id: UserId,
+    device_id: DeviceId,
+) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM devices WHERE id = $1 AND user_id = $2 AND identity_key IS NULL) AS "exists!""#,
+        device_id as DeviceId,
+        user_id as UserId,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+/// The identity keys of a user's keyed devices, oldest device first.
+pub async fn identity_keys_for_user<'e, E>(
+    executor: E,
+    user_id: UserId,
+) -> Result<Vec<(DeviceId, String)>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let rows = sqlx::query!(
+        r#"SELECT id AS "id: DeviceId", identity_key AS "identity_key!" FROM devices
+         WHERE user_id = $1 AND identity_key IS NOT NULL ORDER BY created_at, id"#,
+        user_id as UserId,
+    )
+    .fetch_all(executor)
+    .await?;
+    Ok(rows.into_iter().map(|r| (r.id, r.identity_key)).collect())
+}
+
+/// Bump `last_active` and the last-seen network metadata.
+pub async fn touch<'e, E>(
+    executor: E,
+    device_id: DeviceId,
+    client: &ClientInfo,
+) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        "UPDATE devices SET last_active = NOW(), last_ip = $2, last_geo_country = $3 WHERE id = $1",
+        device_id as DeviceId,
+        client.ip.as_deref(),
+        client.country.as_deref(),
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// A user's devices, most recently active first.
+pub async fn list_for_user<'e, E>(
+    executor: E,
+    user_id: UserId,
+) -> Result<Vec<DeviceInfo>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let rows = sqlx::query_as!(
+        DeviceRow,
+        r#"SELECT id AS "id: DeviceId", device_name, last_active AS "last_active?", created_at,
+             last_ip, last_geo_country
+         FROM devices WHERE user_id = $1 ORDER BY last_active DESC"#,
+        user_id as UserId,
+    )
+    .fetch_all(executor)
+    .await?;
+    Ok(rows.into_iter().map(DeviceRow::into_info).collect())
+}
+
+pub async fn ids_for_user<'e, E>(executor: E, user_id: UserId) -> Result<Vec<DeviceId>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar!(
+        r#"SELECT id AS "id: DeviceId" FROM devices WHERE user_id = $1"#,
+        user_id as UserId,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// The user a device belongs to, if it exists.
+pub async fn owner<'e, E>(executor: E, device_id: DeviceId) -> Result<Option<UserId>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar!(
+        r#"SELECT user_id AS "user_id: UserId" FROM devices WHERE id = $1"#,
+        device_id as DeviceId,
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+/// Delete a device. Its refresh tokens cascade through the foreign key.
+pub async fn delete<'e, E>(executor: E, device_id: DeviceId) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!("DELETE FROM devices WHERE id = $1", device_id as DeviceId)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// Delete every device of a user, returning the IDs removed.
+pub async fn delete_all_for_user<'e, E>(
+    executor: E,
+    user_id: UserId,
+) -> Result<Vec<DeviceId>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar!(
+        r#"DELETE FROM devices WHERE user_id = $1 RETURNING id AS "id: DeviceId""#,
+        user_id as UserId,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// Publish an unused pre-key bundle for a device.
+pub async fn insert_pre_key_bundle<'e, E>(
+    executor: E,
+    user_id: UserId,
+    device_id: DeviceId,
+    key_data: &[u8],
+) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        "INSERT INTO pre_key_bundles (id, user_id, device_id, key_data, is_used) VALUES ($1, $2, $3, $4, false)",
+        uuid::Uuid::now_v7(),
+        user_id as UserId,
+        device_id as DeviceId,
+        key_data,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_pre_key_bundles_for_user<'e, E>(
+    executor: E,
+    user_id: UserId,
+) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        "DELETE FROM pre_key_bundles WHERE user_id = $1",
+        user_id as UserId
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+struct DeviceRow {
+    id: DeviceId,
+    device_name: String,
+    last_active: Option<chrono::DateTime<chrono::Utc>>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    last_ip: Option<String>,
+    last_geo_country: Option<String>,
+}
+
+impl DeviceRow {
+    fn into_info(self) -> DeviceInfo {
+        DeviceInfo {
+            id: self.id,
+            device_name: self.device_name,
+            last_active: self.last_active,
+            created_at: self.created_at,
+            last_ip: self.last_ip,
+            last_geo_country: self.last_geo_country,
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Federation state: local stand-ins for remote users, guilds hosted
+//! elsewhere that local users belong to, and the outgoing event queue.
+//!
+//! A stand-in is an ordinary `users` row with no devices, mapped to the
+//! remote account by `(server_name, remote_user_id)`. It holds guild
+//! memberships here on the remote user's behalf and cannot sign in.
+
+use openconv_shared::federation::{
+    FederatedGuildResponse, FederationEvent, RemoteUserId, ServerName,
+};
+use openconv_shared::ids::{GuildId, UserId};
+
+/// The local stand-in for a remote user, created on first sight. Keeps the
+/// display name in step with what the user's home instance sent.
+///
+/// Stand-ins get placeholder identity key and email values that no client
+/// can produce, as bridge puppets do.
+pub async fn map_remote_user(
+    conn: &mut sqlx::PgConnection,
+    remote: &RemoteUserId,
+    display_name: &str,
+) -> Result<UserId, sqlx::Error> {
+    let existing: Option<UserId> = sqlx::query_scalar(
+        "SELECT user_id FROM federated_users WHERE server_name = $1 AND remote_user_id = $2",
+    )
+    .bind(remote.server.as_str())
+    .bind(remote.user_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    if let Some(user_id) = existing {
+        sqlx::query("UPDATE users SET display_name = $2 WHERE id = $1 AND display_name <> $2")
+            .bind(user_id)
+            .bind(display_name)
+            .execute(&mut *conn)
+            .await?;
+        return Ok(user_id);
+    }
+
+    let user_id = UserId::new();
+    sqlx::query("INSERT INTO users (id, public_key, email, display_name) VALUES ($1, $2, $3, $4)")
+        .bind(user_id)
+        .bind(format!("remote:{remote}"))
+        .bind(format!(
+            "{}@{}.federated.invalid",
+            remote.user_id,
+            remote.server.as_str().replace(':', ".")
+        ))
+        .bind(display_name)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query(
+        "INSERT INTO federated_users (user_id, server_name, remote_user_id) VALUES ($1, $2, $3)",
+    )
+    .bind(user_id)
+    .bind(remote.server.as_str())
+    .bind(remote.user_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(user_id)
+}
+
+/// The local stand-in for a remote user, if one exists.
+pub async fn find_remote_user<'e, E>(
+    executor: E,
+    remote: &RemoteUserId,
+) -> Result<Option<UserId>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar(
+        "SELECT user_id FROM federated_users WHERE server_name = $1 AND remote_user_id = $2",
+    )
+    .bind(remote.server.as_str())
+    .bind(remote.user_id)
+    .fetch_optional(executor)
+    .await
+}
+
+/// Who a stand-in stands for, or `None` for a local user.
+pub async fn remote_identity<'e, E>(
+    executor: E,
+    user_id: UserId,
+) -> Result<Option<RemoteUserId>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let row: Option<(String, UserId)> = sqlx::query_as(
+        "SELECT server_name, remote_user_id FROM federated_users WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(executor)
+    .await?;
+    Ok(row.and_then(|(server, user_id)| {
+        Some(RemoteUserId {
+            user_id,
+            server: server.parse().ok()?,
+        })
+    }))
+}
+
+/// Whether `user_id` is a local account, as opposed to a stand-in.
+pub async fn is_local_user<'e, E>(executor: E, user_id: UserId) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM users u WHERE u.id = $1 \
+         AND NOT EXISTS(SELECT 1 FROM federated_users f WHERE f.user_id = u.id))",
+    )
+    .bind(user_id)
+    .fetch_one(executor)
+    .await
+}
+
+/// Instances with at least one member in a guild hosted here.
+pub async fn remote_servers_in_guild<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+) -> Result<Vec<String>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar(
+        "SELECT DISTINCT f.server_name FROM federated_users f \
+         JOIN guild_members m ON m.user_id = f.user_id \
+         WHERE m.guild_id = $1 ORDER BY f.server_name",
+    )
+    .bind(guild_id)
+    .fetch_all(executor)
+    .await
+}
+
+/// Record that a local user joined a guild hosted on `server`.
+pub async fn upsert_membership<'e, E>(
+    executor: E,
+    user_id: UserId,
+    server: &ServerName,
+    guild_id: GuildId,
+    guild_name: &str,
+) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query(
+        "INSERT INTO federated_memberships (user_id, server_name, guild_id, guild_name) \
+         VALUES ($1, $2, $3, $4) \
+         ON CONFLICT (user_id, server_name, guild_id) DO UPDATE SET guild_name = EXCLUDED.guild_name",
+    )
+    .bind(user_id)
+    .bind(server.as_str())
+    .bind(guild_id)
+    .bind(guild_name)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Forget a remote guild membership. Returns false when there was none.
+pub async fn delete_membership<'e, E>(
+    executor: E,
+    user_id: UserId,
+    server: &ServerName,
+    guild_id: GuildId,
+) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let result = sqlx::query(
+        "DELETE FROM federated_memberships \
+         WHERE user_id = $1 AND server_name = $2 AND guild_id = $3",
+    )
+    .bind(user_id)
+    .bind(server.as_str())
+    .bind(guild_id)
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[derive(sqlx::FromRow)]
+struct MembershipRow {
+    server_name: String,
+    guild_id: GuildId,
+    guild_name: String,
+    joined_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Guilds on other instances a local user belongs to.
+pub async fn list_memberships<'e, E>(
+    executor: E,
+    user_id: UserId,
+) -> Result<Vec<FederatedGuildResponse>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let rows: Vec<MembershipRow> = sqlx::query_as(
+        "SELECT server_name, guild_id, guild_name, joined_at FROM federated_memberships \
+         WHERE user_id = $1 ORDER BY joined_at",
+    )
+    .bind(user_id)
+    .fetch_all(executor)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|r| {
+            Some(FederatedGuildResponse {
+                server: r.server_name.parse().ok()?,
+                guild_id: r.guild_id,
+                guild_name: r.guild_name,
+                joined_at: r.joined_at,
+            })
+        })
+        .collect())
+}
+
+/// Queue `events` for delivery to `destination` as one transaction.
+pub async fn enqueue<'e, E>(
+    executor: E,
+    destination: &ServerName,
+    events: &[FederationEvent],
+) -> Result<uuid::Uuid, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let events = serde_json::to_value(events).expect("FederationEvent serializes");
+    sqlx::query_scalar(
+        "INSERT INTO federation_outbox (destination, events) VALUES ($1, $2) RETURNING id",
+    )
+    .bind(destination.as_str())
+    .bind(events)
+    .fetch_one(executor)
+    .await
+}
@@ -0,0 +1,585 @@
+//! Guilds, their membership and the role data needed to rank members.
+
+use openconv_shared::api::guild::{
+    GuildMemberResponse, GuildResponse, RoleSummary, VerificationLevel,
+};
+use openconv_shared::ids::{ChannelId, GuildId, RoleId, UserId};
+use openconv_shared::permissions::Permissions;
+
+/// A guild row, without soft-deletion state.
+#[derive(sqlx::FromRow)]
+pub struct Guild {
+    pub id: GuildId,
+    pub name: String,
+    pub owner_id: UserId,
+    pub icon_url: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Guild {
+    pub fn into_response(self, member_count: Option<i64>) -> GuildResponse {
+        GuildResponse {
+            id: self.id,
+            name: self.name,
+            owner_id: self.owner_id,
+            icon_url: self.icon_url,
+            created_at: self.created_at,
+            member_count,
+        }
+    }
+}
+
+/// The roles every new guild starts with.
+pub struct DefaultRoles {
+    pub owner: (RoleId, Permissions),
+    pub admin: (RoleId, Permissions),
+    pub member: (RoleId, Permissions),
+}
+
+impl DefaultRoles {
+    /// The standard owner, admin and member permissions under the given IDs.
+    pub fn new(owner: RoleId, admin: RoleId, member: RoleId) -> Self {
+        Self {
+            owner: (owner, Permissions::all()),
+            admin: (
+                admin,
+                Permissions::MANAGE_GUILD
+                    | Permissions::MANAGE_CHANNELS
+                    | Permissions::MANAGE_ROLES
+                    | Permissions::MANAGE_INVITES
+                    | Permissions::KICK_MEMBERS
+                    | Permissions::BAN_MEMBERS
+                    | Permissions::SEND_MESSAGES
+                    | Permissions::READ_MESSAGES
+                    | Permissions::ATTACH_FILES
+                    | Permissions::MENTION_EVERYONE
+                    | Permissions::MANAGE_MESSAGES
+                    | Permissions::CONNECT
+                    | Permissions::SPEAK,
+            ),
+            member: (
+                member,
+                Permissions::SEND_MESSAGES
+                    | Permissions::READ_MESSAGES
+                    | Permissions::ATTACH_FILES
+                    | Permissions::CONNECT
+                    | Permissions::SPEAK,
+            ),
+        }
+    }
+}
+
+/// Insert a guild and everything it starts with: the default roles, the
+/// owner as its first member holding the owner role, and a `#main`
+/// channel. Run inside a transaction.
+pub async fn create(
+    conn: &mut sqlx::PgConnection,
+    guild_id: GuildId,
+    name: &str,
+    owner_id: UserId,
+    roles: &DefaultRoles,
+    main_channel_id: ChannelId,
+) -> Result<Guild, sqlx::Error> {
+    let guild = sqlx::query_as!(
+        Guild,
+        r#"INSERT INTO guilds (id, name, owner_id) VALUES ($1, $2, $3)
+         RETURNING id AS "id: GuildId", name, owner_id AS "owner_id: UserId", icon_url, created_at"#,
+        guild_id as GuildId,
+        name,
+        owner_id as UserId,
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO roles (id, guild_id, name, permissions, position, role_type) VALUES \
+         ($1, $2, 'owner', $3, 100, 'owner'), \
+         ($4, $2, 'admin', $5, 50, 'admin'), \
+         ($6, $2, 'member', $7, 1, 'member')",
+        roles.owner.0 as RoleId,
+        guild_id as GuildId,
+        roles.owner.1.bits() as i64,
+        roles.admin.0 as RoleId,
+        roles.admin.1.bits() as i64,
+        roles.member.0 as RoleId,
+        roles.member.1.bits() as i64,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    add_member(&mut *conn, guild_id, owner_id).await?;
+
+    sqlx::query!(
+        "INSERT INTO guild_member_roles (user_id, guild_id, role_id) VALUES ($1, $2, $3)",
+        owner_id as UserId,
+        guild_id as GuildId,
+        roles.owner.0 as RoleId,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO channels (id, guild_id, name, position) VALUES ($1, $2, 'main', 0)",
+        main_channel_id as ChannelId,
+        guild_id as GuildId,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(guild)
+}
+
+/// Live guilds `user_id` belongs to, newest first.
+pub async fn list_for_member<'e, E>(executor: E, user_id: UserId) -> Result<Vec<Guild>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as!(
+        Guild,
+        r#"SELECT g.id AS "id: GuildId", g.name, g.owner_id AS "owner_id: UserId", g.icon_url, g.created_at
+         FROM guilds g
+         INNER JOIN guild_members gm ON gm.guild_id = g.id
+         WHERE gm.user_id = $1 AND g.deleted_at IS NULL
+         ORDER BY g.created_at DESC"#,
+        user_id as UserId,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// A live guild and its member count.
+pub async fn get_with_member_count<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+) -> Result<Option<(Guild, i64)>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let row = sqlx::query!(
+        r#"SELECT g.id AS "id: GuildId", g.name, g.owner_id AS "owner_id: UserId", g.icon_url, g.created_at,
+         (SELECT COUNT(*) FROM guild_members WHERE guild_id = g.id) AS "member_count!"
+         FROM guilds g
+         WHERE g.id = $1 AND g.deleted_at IS NULL"#,
+        guild_id as GuildId,
+    )
+    .fetch_optional(executor)
+    .await?;
+    Ok(row.map(|r| {
+        let guild = Guild {
+            id: r.id,
+            name: r.name,
+            owner_id: r.owner_id,
+            icon_url: r.icon_url,
+            created_at: r.created_at,
+        };
+        (guild, r.member_count)
+    }))
+}
+
+/// Fields to change on a guild; `None` leaves a field as it is.
+#[derive(Default)]
+pub struct GuildChanges<'a> {
+    pub name: Option<&'a str>,
+    pub icon_url: Option<&'a str>,
+    pub prune_on_ban: Option<bool>,
+    /// 0 clears the limit.
+    pub max_members: Option<i32>,
+    /// 0 keeps files forever.
+    pub file_retention_days: Option<i32>,
+    /// 0 keeps messages forever.
+    pub message_retention_days: Option<i32>,
+    pub verification_level: Option<VerificationLevel>,
+    /// 0 clears the limit.
+    pub max_joins_per_minute: Option<i32>,
+    /// Pausing keeps the original pause time when already paused.
+    pub invites_paused: Option<bool>,
+}
+
+impl GuildChanges<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.icon_url.is_none()
+            && self.prune_on_ban.is_none()
+            && self.max_members.is_none()
+            && self.file_retention_days.is_none()
+            && self.message_retention_days.is_none()
+            && self.verification_level.is_none()
+            && self.max_joins_per_minute.is_none()
+            && self.invites_paused.is_none()
+    }
+}
+
+/// Apply `changes` to a live guild. `None` when the guild does not exist
+/// or is deleted. `changes` must not be empty.
+pub async fn update<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    changes: &GuildChanges<'_>,
+) -> Result<Option<Guild>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    // The SET list depends on which fields change, so this one is built at
+    // runtime and not checked against the schema at compile time
+    let mut set_clauses = Vec::new();
+    let mut param_idx = 2u32; // $1 is guild_id
+
+    if changes.name.is_some() {
+        set_clauses.push(format!("name = ${param_idx}"));
+        param_idx += 1;
+    }
+    if changes.icon_url.is_some() {
+        set_clauses.push(format!("icon_url = ${param_idx}"));
+        param_idx += 1;
+    }
+    if changes.prune_on_ban.is_some() {
+        set_clauses.push(format!("prune_on_ban = ${param_idx}"));
+        param_idx += 1;
+    }
+    if changes.max_members.is_some() {
+        set_clauses.push(format!("max_members = NULLIF(${param_idx}, 0)"));
+        param_idx += 1;
+    }
+    if changes.file_retention_days.is_some() {
+        set_clauses.push(format!("file_retention_days = NULLIF(${param_idx}, 0)"));
+        param_idx += 1;
+    }
+    if changes.message_retention_days.is_some() {
+        set_clauses.push(format!("message_retention_days = NULLIF(${param_idx}, 0)"));
+        param_idx += 1;
+    }
+    if changes.verification_level.is_some() {
+        set_clauses.push(format!("verification_level = ${param_idx}"));
+        param_idx += 1;
+    }
+    if changes.max_joins_per_minute.is_some() {
+        set_clauses.push(format!("max_joins_per_minute = NULLIF(${param_idx}, 0)"));
+        param_idx += 1;
+    }
+    if changes.invites_paused.is_some() {
+        set_clauses.push(format!(
+            "invites_paused_at = CASE WHEN ${param_idx} \
+             THEN COALESCE(invites_paused_at, NOW()) END"
+        ));
+    }
+
+    let query_str = format!(
+        "UPDATE guilds SET {} WHERE id = $1 AND deleted_at IS NULL \
+         RETURNING id, name, owner_id, icon_url, created_at",
+        set_clauses.join(", ")
+    );
+
+    let mut query = sqlx::query_as::<_, Guild>(&query_str).bind(guild_id);
+    if let Some(name) = changes.name {
+        query = query.bind(name);
+    }
+    if let Some(icon_url) = changes.icon_url {
+        query = query.bind(icon_url);
+    }
+    if let Some(prune_on_ban) = changes.prune_on_ban {
+        query = query.bind(prune_on_ban);
+    }
+    if let Some(max_members) = changes.max_members {
+        query = query.bind(max_members);
+    }
+    if let Some(days) = changes.file_retention_days {
+        query = query.bind(days);
+    }
+    if let Some(days) = changes.message_retention_days {
+        query = query.bind(days);
+    }
+    if let Some(level) = changes.verification_level {
+        query = query.bind(level.as_str());
+    }
+    if let Some(max_joins) = changes.max_joins_per_minute {
+        query = query.bind(max_joins);
+    }
+    if let Some(paused) = changes.invites_paused {
+        query = query.bind(paused);
+    }
+
+    query.fetch_optional(executor).await
+}
+
+/// Soft-delete a guild if `owner_id` owns it. Returns false when the guild
+/// is not theirs or is already deleted.
+pub async fn soft_delete<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    owner_id: UserId,
+) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let result = sqlx::query!(
+        "UPDATE guilds SET deleted_at = NOW() \
+         WHERE id = $1 AND owner_id = $2 AND deleted_at IS NULL",
+        guild_id as GuildId,
+        owner_id as UserId,
+    )
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Undo a soft delete made in the last 7 days, if `owner_id` owns the
+/// guild. `None` when any of those conditions fails.
+pub async fn restore<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    owner_id: UserId,
+) -> Result<Option<Guild>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as!(
+        Guild,
+        r#"UPDATE guilds SET deleted_at = NULL
+         WHERE id = $1 AND owner_id = $2 AND deleted_at IS NOT NULL
+         AND deleted_at > NOW() - INTERVAL '7 days'
+         RETURNING id AS "id: GuildId", name, owner_id AS "owner_id: UserId", icon_url, created_at"#,
+        guild_id as GuildId,
+        owner_id as UserId,
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+pub async fn owner<'e, E>(executor: E, guild_id: GuildId) -> Result<Option<UserId>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar!(
+        r#"SELECT owner_id AS "owner_id: UserId" FROM guilds WHERE id = $1"#,
+        guild_id as GuildId,
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+pub async fn is_member<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM guild_members WHERE user_id = $1 AND guild_id = $2) AS "exists!""#,
+        user_id as UserId,
+        guild_id as GuildId,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+/// Whether `channel_id` is a channel of the guild.
+pub async fn has_channel<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM channels WHERE id = $1 AND guild_id = $2) AS "exists!""#,
+        channel_id as ChannelId,
+        guild_id as GuildId,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn add_member<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        "INSERT INTO guild_members (user_id, guild_id) VALUES ($1, $2)",
+        user_id as UserId,
+        guild_id as GuildId,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Remove a member. Their role assignments cascade.
+pub async fn remove_member<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        "DELETE FROM guild_members WHERE user_id = $1 AND guild_id = $2",
+        user_id as UserId,
+        guild_id as GuildId,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Position of a member's highest role, or 0 when they hold none.
+pub async fn highest_role_position<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<i32, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let position = sqlx::query_scalar!(
+        "SELECT MAX(r.position) FROM guild_member_roles gmr \
+         JOIN roles r ON r.id = gmr.role_id \
+         WHERE gmr.user_id = $1 AND gmr.guild_id = $2",
+        user_id as UserId,
+        guild_id as GuildId,
+    )
+    .fetch_one(executor)
+    .await?;
+    Ok(position.unwrap_or(0))
+}
+
+/// A member with their roles, aggregated as JSON by the query.
+pub struct Member {
+    pub user_id: UserId,
+    pub display_name: String,
+    pub joined_at: chrono::DateTime<chrono::Utc>,
+    roles: serde_json::Value,
+}
+
+impl Member {
+    pub fn into_response(self) -> GuildMemberResponse {
+        let roles: Vec<RoleSummary> = match serde_json::from_value(self.roles) {
+            Ok(v) => v,
+            Err(e) => {
+                tracing::warn!(
+                    user_id = %self.user_id,
+                    error = %e,
+                    "failed to deserialize member roles from JSON, defaulting to empty"
+                );
+                vec![]
+            }
+        };
+        GuildMemberResponse {
+            user_id: self.user_id,
+            display_name: self.display_name,
+            joined_at: self.joined_at,
+            roles,
+        }
+    }
+}
+
+/// Every member of a guild, in join order.
+pub async fn list_members<'e, E>(executor: E, guild_id: GuildId) -> Result<Vec<Member>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as!(
+        Member,
+        r#"SELECT
+             u.id AS "user_id: UserId",
+             u.display_name,
+             gm.joined_at,
+             COALESCE(
+                 json_agg(json_build_object('id', r.id, 'name', r.name, 'position', r.position))
+                 FILTER (WHERE r.id IS NOT NULL),
+                 '[]'
+             ) AS "roles!"
+         FROM guild_members gm
+         JOIN users u ON u.id = gm.user_id
+         LEFT JOIN guild_member_roles gmr ON gmr.user_id = gm.user_id AND gmr.guild_id = gm.guild_id
+         LEFT JOIN roles r ON r.id = gmr.role_id
+         WHERE gm.guild_id = $1
+         GROUP BY u.id, u.display_name, gm.joined_at
+         ORDER BY gm.joined_at ASC"#,
+        guild_id as GuildId,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// The members among `user_ids`, in join order. Non-members are skipped.
+pub async fn members_by_ids<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    user_ids: &[UserId],
+) -> Result<Vec<Member>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let user_ids: Vec<uuid::Uuid> = user_ids.iter().map(|id| id.0).collect();
+    sqlx::query_as!(
+        Member,
+        r#"SELECT
+             u.id AS "user_id: UserId",
+             u.display_name,
+             gm.joined_at,
+             COALESCE(
+                 json_agg(json_build_object('id', r.id, 'name', r.name, 'position', r.position))
+                 FILTER (WHERE r.id IS NOT NULL),
+                 '[]'
+             ) AS "roles!"
+         FROM guild_members gm
+         JOIN users u ON u.id = gm.user_id
+         LEFT JOIN guild_member_roles gmr ON gmr.user_id = gm.user_id AND gmr.guild_id = gm.guild_id
+         LEFT JOIN roles r ON r.id = gmr.role_id
+         WHERE gm.guild_id = $1 AND gm.user_id = ANY($2)
+         GROUP BY u.id, u.display_name, gm.joined_at
+         ORDER BY gm.joined_at ASC"#,
+        guild_id as GuildId,
+        &user_ids,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// One keyset page of members, ordered by join time then user ID, starting
+/// after `after` (the last member of the previous page).
+pub async fn members_page<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    after: Option<(chrono::DateTime<chrono::Utc>, UserId)>,
+    limit: i64,
+) -> Result<Vec<Member>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as!(
+        Member,
+        r#"SELECT
+             u.id AS "user_id: UserId",
+             u.display_name,
+             gm.joined_at,
+             COALESCE(
+                 json_agg(json_build_object('id', r.id, 'name', r.name, 'position', r.position))
+                 FILTER (WHERE r.id IS NOT NULL),
+                 '[]'
+             ) AS "roles!"
+         FROM guild_members gm
+         JOIN users u ON u.id = gm.user_id
+         LEFT JOIN guild_member_roles gmr ON gmr.user_id = gm.user_id AND gmr.guild_id = gm.guild_id
+         LEFT JOIN roles r ON r.id = gmr.role_id
+         WHERE gm.guild_id = $1
+           AND ($2::timestamptz IS NULL OR (gm.joined_at, u.id) > ($2, $3))
+         GROUP BY u.id, u.display_name, gm.joined_at
+         ORDER BY gm.joined_at ASC, u.id ASC
+         LIMIT $4"#,
+        guild_id as GuildId,
+        after.map(|(joined_at, _)| joined_at),
+        after.map(|(_, user_id)| user_id) as Option<UserId>,
+        limit,
+    )
+    .fetch_all(executor)
+    .await
+}
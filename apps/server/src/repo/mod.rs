@@ -0,0 +1,23 @@
+//! Typed data access, shared by HTTP handlers and anything else that needs
+//! the same reads and writes (the gateway, background jobs, the CLI).
+//!
+//! Functions here return `sqlx::Error` (or `fred` errors for Redis) and
+//! leave mapping to HTTP responses to the caller. Single-statement
+//! functions take any executor, so callers can run them on the pool or
+//! inside their own transaction.
+//!
+//! Static SQL in the auth, guild and role modules goes through the checked
+//! `query!` macros. Their metadata is committed under `.sqlx/` at the
+//! workspace root; after changing one of those queries or the schema they
+//! read, regenerate it with `just sqlx-prepare` against a migrated database.
+
+pub mod bots;
+pub mod bridges;
+pub mod devices;
+#[cfg(feature = "federation")]
+pub mod federation;
+pub mod guilds;
+pub mod oauth;
+pub mod roles;
+pub mod tokens;
+pub mod users;
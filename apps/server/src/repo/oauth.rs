@@ -0,0 +1,312 @@
+//! OAuth clients, the consents users grant them, and issued tokens.
+//!
+//! Scopes are stored as `TEXT[]` of wire names; see
+//! [`crate::oauth::scopes_to_db`]. Token values are never stored, only
+//! their hashes.
+
+use openconv_shared::api::oauth::{OAuthClientResponse, OAuthConsentResponse};
+use openconv_shared::ids::UserId;
+
+use crate::oauth::scopes_from_db;
+
+#[derive(sqlx::FromRow)]
+pub struct Client {
+    pub id: uuid::Uuid,
+    pub owner_id: UserId,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub secret_hash: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Client {
+    pub fn into_response(self) -> OAuthClientResponse {
+        OAuthClientResponse {
+            client_id: self.id,
+            name: self.name,
+            redirect_uris: self.redirect_uris,
+            confidential: self.secret_hash.is_some(),
+            owner_id: self.owner_id,
+            created_at: self.created_at,
+            client_secret: None,
+        }
+    }
+}
+
+const CLIENT_COLUMNS: &str = "id, owner_id, name, redirect_uris, secret_hash, created_at";
+
+pub async fn insert_client<'e, E>(
+    executor: E,
+    owner_id: UserId,
+    name: &str,
+    redirect_uris: &[String],
+    secret_hash: Option<&str>,
+) -> Result<Client, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as(&format!(
+        "INSERT INTO oauth_clients (owner_id, name, redirect_uris, secret_hash) \
+         VALUES ($1, $2, $3, $4) RETURNING {CLIENT_COLUMNS}"
+    ))
+    .bind(owner_id)
+    .bind(name)
+    .bind(redirect_uris)
+    .bind(secret_hash)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn get_client<'e, E>(
+    executor: E,
+    client_id: uuid::Uuid,
+) -> Result<Option<Client>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as(&format!(
+        "SELECT {CLIENT_COLUMNS} FROM oauth_clients WHERE id = $1"
+    ))
+    .bind(client_id)
+    .fetch_optional(executor)
+    .await
+}
+
+pub async fn list_clients_for_owner<'e, E>(
+    executor: E,
+    owner_id: UserId,
+) -> Result<Vec<Client>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as(&format!(
+        "SELECT {CLIENT_COLUMNS} FROM oauth_clients WHERE owner_id = $1 ORDER BY created_at"
+    ))
+    .bind(owner_id)
+    .fetch_all(executor)
+    .await
+}
+
+pub async fn count_clients_for_owner<'e, E>(
+    executor: E,
+    owner_id: UserId,
+) -> Result<i64, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar("SELECT COUNT(*) FROM oauth_clients WHERE owner_id = $1")
+        .bind(owner_id)
+        .fetch_one(executor)
+        .await
+}
+
+/// Delete a client. Its consents and tokens cascade. Returns false when
+/// the owner has no such client.
+pub async fn delete_client<'e, E>(
+    executor: E,
+    owner_id: UserId,
+    client_id: uuid::Uuid,
+) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let result = sqlx::query("DELETE FROM oauth_clients WHERE id = $1 AND owner_id = $2")
+        .bind(client_id)
+        .bind(owner_id)
+        .execute(executor)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// The scopes a user has granted a client, if any.
+pub async fn consented_scopes<'e, E>(
+    executor: E,
+    user_id: UserId,
+    client_id: uuid::Uuid,
+) -> Result<Option<Vec<String>>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar("SELECT scopes FROM oauth_consents WHERE user_id = $1 AND client_id = $2")
+        .bind(user_id)
+        .bind(client_id)
+        .fetch_optional(executor)
+        .await
+}
+
+/// Record a grant, widening any earlier consent to include `scopes`.
+pub async fn upsert_consent<'e, E>(
+    executor: E,
+    user_id: UserId,
+    client_id: uuid::Uuid,
+    scopes: &[String],
+) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query(
+        "INSERT INTO oauth_consents (user_id, client_id, scopes) VALUES ($1, $2, $3) \
+         ON CONFLICT (user_id, client_id) DO UPDATE SET \
+             scopes = ARRAY(SELECT DISTINCT unnest(oauth_consents.scopes || EXCLUDED.scopes) ORDER BY 1), \
+             granted_at = NOW()",
+    )
+    .bind(user_id)
+    .bind(client_id)
+    .bind(scopes)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+#[derive(sqlx::FromRow)]
+struct ConsentRow {
+    client_id: uuid::Uuid,
+    client_name: String,
+    scopes: Vec<String>,
+    granted_at: chrono::DateTime<chrono::Utc>,
+}
+
+pub async fn list_consents<'e, E>(
+    executor: E,
+    user_id: UserId,
+) -> Result<Vec<OAuthConsentResponse>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let rows: Vec<ConsentRow> = sqlx::query_as(
+        "SELECT c.client_id, oc.name AS client_name, c.scopes, c.granted_at \
+         FROM oauth_consents c JOIN oauth_clients oc ON oc.id = c.client_id \
+         WHERE c.user_id = $1 ORDER BY c.granted_at DESC",
+    )
+    .bind(user_id)
+    .fetch_all(executor)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|r| OAuthConsentResponse {
+            client_id: r.client_id,
+            client_name: r.client_name,
+            scopes: scopes_from_db(&r.scopes),
+            granted_at: r.granted_at,
+        })
+        .collect())
+}
+
+/// Withdraw a user's consent and every token issued under it. Returns false
+/// when there was no consent.
+pub async fn revoke_consent(
+    conn: &mut sqlx::PgConnection,
+    user_id: UserId,
+    client_id: uuid::Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM oauth_consents WHERE user_id = $1 AND client_id = $2")
+        .bind(user_id)
+        .bind(client_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM oauth_tokens WHERE user_id = $1 AND client_id = $2")
+        .bind(user_id)
+        .bind(client_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// An access and refresh token pair to record.
+pub struct NewGrant<'a> {
+    pub client_id: uuid::Uuid,
+    pub user_id: UserId,
+    pub scopes: &'a [String],
+    pub access_hash: &'a str,
+    pub refresh_hash: &'a str,
+}
+
+pub async fn insert_grant(
+    conn: &mut sqlx::PgConnection,
+    grant: &NewGrant<'_>,
+) -> Result<(), sqlx::Error> {
+    let grant_id = uuid::Uuid::new_v4();
+    for (kind, hash, ttl) in [
+        (
+            "access",
+            grant.access_hash,
+            crate::oauth::ACCESS_TOKEN_TTL_SECS,
+        ),
+        (
+            "refresh",
+            grant.refresh_hash,
+            crate::oauth::REFRESH_TOKEN_TTL_SECS,
+        ),
+    ] {
+        sqlx::query(
+            "INSERT INTO oauth_tokens (token_hash, kind, grant_id, client_id, user_id, scopes, expires_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, NOW() + make_interval(secs => $7))",
+        )
+        .bind(hash)
+        .bind(kind)
+        .bind(grant_id)
+        .bind(grant.client_id)
+        .bind(grant.user_id)
+        .bind(grant.scopes)
+        .bind(ttl as f64)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Who a live token was issued to, and for what.
+#[derive(Debug, sqlx::FromRow)]
+pub struct TokenGrant {
+    pub client_id: uuid::Uuid,
+    pub user_id: UserId,
+    pub scopes: Vec<String>,
+}
+
+/// Look up an unexpired access token.
+pub async fn find_access_token<'e, E>(
+    executor: E,
+    token_hash: &str,
+) -> Result<Option<TokenGrant>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as(
+        "SELECT client_id, user_id, scopes FROM oauth_tokens \
+         WHERE token_hash = $1 AND kind = 'access' AND expires_at > NOW()",
+    )
+    .bind(token_hash)
+    .fetch_optional(executor)
+    .await
+}
+
+/// Redeem a refresh token: delete its whole grant and return what it was
+/// for, or `None` if it is unknown, expired or already used.
+pub async fn take_refresh_token(
+    conn: &mut sqlx::PgConnection,
+    token_hash: &str,
+) -> Result<Option<TokenGrant>, sqlx::Error> {
+    let grant_id: Option<uuid::Uuid> = sqlx::query_scalar(
+        "SELECT grant_id FROM oauth_tokens \
+         WHERE token_hash = $1 AND kind = 'refresh' AND expires_at > NOW() FOR UPDATE",
+    )
+    .bind(token_hash)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(grant_id) = grant_id else {
+        return Ok(None);
+    };
+
+    let grant: Option<TokenGrant> = sqlx::query_as(
+        "DELETE FROM oauth_tokens WHERE grant_id = $1 AND kind = 'refresh' \
+         RETURNING client_id, user_id, scopes",
+    )
+    .bind(grant_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    sqlx::query("DELETE FROM oauth_tokens WHERE grant_id = $1")
+        .bind(grant_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(grant)
+}
@@ -0,0 +1,261 @@
+//! Guild roles and their assignment to members.
+//!
+//! Positions are unique per guild. The built-in owner, admin and member
+//! roles sit at fixed positions; custom roles are created at position 2 and
+//! push the existing custom roles up.
+
+use openconv_shared::api::role::RoleResponse;
+use openconv_shared::ids::{GuildId, RoleId, UserId};
+
+/// A role row.
+pub struct Role {
+    pub id: RoleId,
+    pub guild_id: GuildId,
+    pub name: String,
+    pub permissions: i64,
+    pub position: i32,
+    pub role_type: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Role {
+    pub fn into_response(self) -> RoleResponse {
+        RoleResponse {
+            id: self.id,
+            guild_id: self.guild_id,
+            name: self.name,
+            permissions: self.permissions as u64,
+            position: self.position,
+            role_type: self.role_type,
+            created_at: self.created_at,
+        }
+    }
+}
+
+/// Move every custom role at position 2 or above up by one, freeing
+/// position 2 for a new role. Run inside a transaction.
+pub async fn shift_custom_up(
+    conn: &mut sqlx::PgConnection,
+    guild_id: GuildId,
+) -> Result<(), sqlx::Error> {
+    // Two steps keep the positions unique throughout: negate them (all
+    // originals are positive), then set them to -(negated) + 1.
+    sqlx::query!(
+        "UPDATE roles SET position = -position \
+         WHERE guild_id = $1 AND position >= 2 AND role_type = 'custom'",
+        guild_id as GuildId,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE roles SET position = -position + 1 WHERE guild_id = $1 AND position < 0",
+        guild_id as GuildId,
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Insert a custom role at position 2. Fails with a unique violation when
+/// the position is taken; see [`shift_custom_up`].
+pub async fn insert_custom<'e, E>(
+    executor: E,
+    role_id: RoleId,
+    guild_id: GuildId,
+    name: &str,
+    permissions: i64,
+) -> Result<Role, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as!(
+        Role,
+        r#"INSERT INTO roles (id, guild_id, name, permissions, position, role_type)
+         VALUES ($1, $2, $3, $4, 2, 'custom')
+         RETURNING id AS "id: RoleId", guild_id AS "guild_id: GuildId", name, permissions,
+             position, role_type, created_at"#,
+        role_id as RoleId,
+        guild_id as GuildId,
+        name,
+        permissions,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+/// Every role of a guild, lowest position first.
+pub async fn list<'e, E>(executor: E, guild_id: GuildId) -> Result<Vec<Role>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as!(
+        Role,
+        r#"SELECT id AS "id: RoleId", guild_id AS "guild_id: GuildId", name, permissions,
+             position, role_type, created_at
+         FROM roles WHERE guild_id = $1 ORDER BY position ASC"#,
+        guild_id as GuildId,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+pub async fn get<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    role_id: RoleId,
+) -> Result<Option<Role>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as!(
+        Role,
+        r#"SELECT id AS "id: RoleId", guild_id AS "guild_id: GuildId", name, permissions,
+             position, role_type, created_at
+         FROM roles WHERE id = $1 AND guild_id = $2"#,
+        role_id as RoleId,
+        guild_id as GuildId,
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+/// Change a role's name, permissions and/or position; `None` leaves a
+/// field as it is. `None` when the role does not exist. Fails with a
+/// unique violation when the new position is taken.
+pub async fn update<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    role_id: RoleId,
+    name: Option<&str>,
+    permissions: Option<i64>,
+    position: Option<i32>,
+) -> Result<Option<Role>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as!(
+        Role,
+        r#"UPDATE roles SET name = COALESCE($3, name),
+             permissions = COALESCE($4, permissions),
+             position = COALESCE($5, position)
+         WHERE id = $1 AND guild_id = $2
+         RETURNING id AS "id: RoleId", guild_id AS "guild_id: GuildId", name, permissions,
+             position, role_type, created_at"#,
+        role_id as RoleId,
+        guild_id as GuildId,
+        name,
+        permissions,
+        position,
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+/// Delete a role. Its assignments cascade.
+pub async fn delete<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    role_id: RoleId,
+) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        "DELETE FROM roles WHERE id = $1 AND guild_id = $2",
+        role_id as RoleId,
+        guild_id as GuildId,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Give a member a role. Returns false when they already held it.
+pub async fn assign<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    user_id: UserId,
+    role_id: RoleId,
+) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let result = sqlx::query!(
+        "INSERT INTO guild_member_roles (user_id, guild_id, role_id) \
+         VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+        user_id as UserId,
+        guild_id as GuildId,
+        role_id as RoleId,
+    )
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Take a role from a member. Returns false when they did not hold it.
+pub async fn unassign<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    user_id: UserId,
+    role_id: RoleId,
+) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let result = sqlx::query!(
+        "DELETE FROM guild_member_roles WHERE user_id = $1 AND guild_id = $2 AND role_id = $3",
+        user_id as UserId,
+        guild_id as GuildId,
+        role_id as RoleId,
+    )
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// `role_id` together with the guild's base member role, as IDs and
+/// permission bits, lowest position first. `role_id` is missing from the
+/// result when it is not a role of the guild.
+pub async fn permissions_with_member_role<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    role_id: RoleId,
+) -> Result<Vec<(RoleId, i64)>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let rows = sqlx::query!(
+        r#"SELECT id AS "id: RoleId", permissions FROM roles
+         WHERE guild_id = $1 AND (id = $2 OR role_type = 'member')
+         ORDER BY position ASC"#,
+        guild_id as GuildId,
+        role_id as RoleId,
+    )
+    .fetch_all(executor)
+    .await?;
+    Ok(rows.into_iter().map(|r| (r.id, r.permissions)).collect())
+}
+
+/// The roles a member holds, as IDs and permission bits, lowest position
+/// first.
+pub async fn permissions_held_by<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<Vec<(RoleId, i64)>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let rows = sqlx::query!(
+        r#"SELECT r.id AS "id: RoleId", r.permissions FROM guild_member_roles gmr
+         JOIN roles r ON r.id = gmr.role_id
+         WHERE gmr.user_id = $1 AND gmr.guild_id = $2
+         ORDER BY r.position ASC"#,
+        user_id as UserId,
+        guild_id as GuildId,
+    )
+    .fetch_all(executor)
+    .await?;
+    Ok(rows.into_iter().map(|r| (r.id, r.permissions)).collect())
+}
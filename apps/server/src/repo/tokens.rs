@@ -0,0 +1,193 @@
+//! Refresh tokens and the sessions they form.
+//!
+//! Refresh tokens are rotated within a family; a family is what the API
+//! calls a session. Marking a token used rather than deleting it keeps
+//! reuse detection working, so only session revocation deletes rows.
+
+use openconv_shared::ids::{DeviceId, UserId};
+
+use crate::extractors::client_info::ClientInfo;
+
+/// A refresh token to record after issuing it.
+pub struct NewRefreshToken<'a> {
+    pub jti: uuid::Uuid,
+    pub user_id: UserId,
+    pub device_id: DeviceId,
+    pub family: uuid::Uuid,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub client: &'a ClientInfo,
+}
+
+pub async fn insert_refresh<'e, E>(
+    executor: E,
+    token: &NewRefreshToken<'_>,
+) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        "INSERT INTO refresh_tokens (jti, user_id, device_id, family, expires_at, is_used, ip_address, geo_country) \
+         VALUES ($1, $2, $3, $4, $5, false, $6, $7)",
+        token.jti,
+        token.user_id as UserId,
+        token.device_id as DeviceId,
+        token.family,
+        token.expires_at,
+        token.client.ip.as_deref(),
+        token.client.country.as_deref(),
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Lock a refresh token row for rotation and report whether it was already
+/// used. `None` when the token is unknown or was revoked.
+pub async fn lock_for_rotation<'e, E>(
+    executor: E,
+    jti: uuid::Uuid,
+) -> Result<Option<bool>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar!(
+        "SELECT is_used FROM refresh_tokens WHERE jti = $1 FOR UPDATE",
+        jti
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+pub async fn mark_used<'e, E>(executor: E, jti: uuid::Uuid) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        "UPDATE refresh_tokens SET is_used = true, used_at = NOW() WHERE jti = $1",
+        jti
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Burn every token in a family, after reuse of a rotated token.
+pub async fn mark_family_used<'e, E>(executor: E, family: uuid::Uuid) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        "UPDATE refresh_tokens SET is_used = true, used_at = NOW() WHERE family = $1",
+        family,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Burn a device's live tokens, keeping the rows as an audit trail.
+pub async fn invalidate_for_device<'e, E>(
+    executor: E,
+    user_id: UserId,
+    device_id: DeviceId,
+) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        "UPDATE refresh_tokens SET is_used = true, used_at = NOW() \
+         WHERE user_id = $1 AND device_id = $2 AND is_used = false",
+        user_id as UserId,
+        device_id as DeviceId,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Burn every live token of a user, keeping the rows as an audit trail.
+pub async fn invalidate_for_user<'e, E>(executor: E, user_id: UserId) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        "UPDATE refresh_tokens SET is_used = true, used_at = NOW() WHERE user_id = $1 AND is_used = false",
+        user_id as UserId,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_for_user<'e, E>(executor: E, user_id: UserId) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query!(
+        "DELETE FROM refresh_tokens WHERE user_id = $1",
+        user_id as UserId
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Delete a session outright, returning the device it was on. Deleting
+/// rather than marking used gives the revoked client a plain 401 on
+/// refresh instead of tripping reuse detection.
+pub async fn delete_session<'e, E>(
+    executor: E,
+    user_id: UserId,
+    family: uuid::Uuid,
+) -> Result<Option<DeviceId>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let device_ids = sqlx::query_scalar!(
+        r#"DELETE FROM refresh_tokens WHERE family = $1 AND user_id = $2 RETURNING device_id AS "device_id: DeviceId""#,
+        family,
+        user_id as UserId,
+    )
+    .fetch_all(executor)
+    .await?;
+    Ok(device_ids.into_iter().next())
+}
+
+/// An active session: the newest live token of a family.
+pub struct Session {
+    pub family: uuid::Uuid,
+    pub device_id: DeviceId,
+    pub device_name: String,
+    pub ip_address: Option<String>,
+    pub geo_country: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub last_used_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A user's unexpired sessions, most recently used first.
+pub async fn list_sessions<'e, E>(executor: E, user_id: UserId) -> Result<Vec<Session>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    // The subquery hides column origins, so nullability is spelled out
+    sqlx::query_as!(
+        Session,
+        r#"SELECT family AS "family!", device_id AS "device_id!: DeviceId", device_name AS "device_name!",
+             ip_address, geo_country, created_at AS "created_at!",
+             last_used_at AS "last_used_at!", expires_at AS "expires_at!"
+         FROM (
+             SELECT DISTINCT ON (rt.family)
+                 rt.family, rt.device_id, d.device_name, rt.ip_address, rt.geo_country,
+                 (SELECT MIN(f.created_at) FROM refresh_tokens f WHERE f.family = rt.family) AS created_at,
+                 rt.created_at AS last_used_at, rt.expires_at
+             FROM refresh_tokens rt
+             JOIN devices d ON d.id = rt.device_id
+             WHERE rt.user_id = $1 AND rt.is_used = false AND rt.expires_at > NOW()
+             ORDER BY rt.family, rt.created_at DESC
+         ) s ORDER BY last_used_at DESC"#,
+        user_id as UserId,
+    )
+    .fetch_all(executor)
+    .await
+}
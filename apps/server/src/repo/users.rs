@@ -0,0 +1,281 @@
+//! Account rows: identity keys, email and suspension state.
+
+use openconv_shared::ids::{GuildId, UserId};
+
+/// A user to insert at registration.
+pub struct NewUser<'a> {
+    pub id: UserId,
+    pub public_key: &'a str,
+    pub email: &'a str,
+    pub display_name: &'a str,
+}
+
+/// Insert a new account. Fails with a unique violation when the email or
+/// public key is already registered.
+pub async fn insert<'e, E>(executor: E, user: &NewUser<'_>) -> Result<(), sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    // Registration only creates accounts for verified addresses
+    sqlx::query!(
+        "INSERT INTO users (id, public_key, email, display_name, email_verified_at) \
+         VALUES ($1, $2, $3, $4, NOW())",
+        user.id as UserId,
+        user.public_key,
+        user.email,
+        user.display_name,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+pub async fn email_exists<'e, E>(executor: E, email: &str) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM users WHERE email = $1) AS "exists!""#,
+        email,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+/// Whether `public_key` is an account key or a device's identity key.
+pub async fn public_key_exists<'e, E>(executor: E, public_key: &str) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar!(
+        r#"SELECT (EXISTS(SELECT 1 FROM users WHERE public_key = $1)
+             OR EXISTS(SELECT 1 FROM devices WHERE identity_key = $1)) AS "exists!""#,
+        public_key,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn id_by_email<'e, E>(executor: E, email: &str) -> Result<Option<UserId>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar!(
+        r#"SELECT id AS "id: UserId" FROM users WHERE email = $1"#,
+        email
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+/// The account holding `public_key`, unless it is suspended.
+pub async fn active_id_by_public_key<'e, E>(
+    executor: E,
+    public_key: &str,
+) -> Result<Option<UserId>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar!(
+        r#"SELECT id AS "id: UserId" FROM users WHERE public_key = $1 AND suspended_at IS NULL"#,
+        public_key,
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+/// The identity key of an account, unless it is suspended.
+pub async fn active_public_key<'e, E>(
+    executor: E,
+    user_id: UserId,
+) -> Result<Option<String>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar!(
+        "SELECT public_key FROM users WHERE id = $1 AND suspended_at IS NULL",
+        user_id as UserId,
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+pub async fn is_suspended<'e, E>(executor: E, user_id: UserId) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM users WHERE id = $1 AND suspended_at IS NOT NULL) AS "exists!""#,
+        user_id as UserId,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+/// Swap in a new identity key after account recovery. Returns false when
+/// the user does not exist.
+pub async fn replace_public_key<'e, E>(
+    executor: E,
+    user_id: UserId,
+    public_key: &str,
+) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let result = sqlx::query!(
+        "UPDATE users SET public_key = $1, public_key_changed_at = NOW() WHERE id = $2",
+        public_key,
+        user_id as UserId,
+    )
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Recompute contact discovery hashes under `salt`, for every account or
+/// only `user_id`. Verified, non-bot accounts are discoverable; see
+/// [`crate::discovery::contact_hash`] for the hash. Returns the number of
+/// rows changed.
+pub async fn refresh_discovery_hashes<'e, E>(
+    executor: E,
+    salt: &str,
+    user_id: Option<UserId>,
+) -> Result<u64, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let hash_bytes = crate::discovery::HASH_BYTES as i32;
+    let result = sqlx::query!(
+        "UPDATE users \
+         SET discovery_hash = substring(sha256(convert_to($1 || email, 'UTF8')) FROM 1 FOR $2) \
+         WHERE NOT is_bot AND email_verified_at IS NOT NULL AND email NOT LIKE '%.invalid' \
+           AND ($3::uuid IS NULL OR id = $3) \
+           AND discovery_hash IS DISTINCT FROM \
+               substring(sha256(convert_to($1 || email, 'UTF8')) FROM 1 FOR $2)",
+        salt,
+        hash_bytes,
+        user_id as Option<UserId>,
+    )
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// An account found by contact discovery.
+#[derive(Debug)]
+pub struct DiscoveredUser {
+    pub discovery_hash: Vec<u8>,
+    pub id: UserId,
+    pub display_name: String,
+    pub avatar_url: Option<String>,
+    pub public_key: String,
+}
+
+/// Active accounts whose discovery hash is one of `hashes`, other than
+/// `requester`.
+pub async fn discoverable_by_hashes<'e, E>(
+    executor: E,
+    hashes: &[Vec<u8>],
+    requester: UserId,
+) -> Result<Vec<DiscoveredUser>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as!(
+        DiscoveredUser,
+        r#"SELECT discovery_hash AS "discovery_hash!", id AS "id: UserId", display_name, avatar_url, public_key
+         FROM users WHERE discovery_hash = ANY($1) AND id <> $2 AND suspended_at IS NULL"#,
+        hashes,
+        requester as UserId,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// Result of [`purge`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum PurgeOutcome {
+    NotFound,
+    /// The user still owns this many guilds, which must be transferred or
+    /// deleted first.
+    OwnsGuilds(i64),
+    /// The guilds the user was removed from, each now with a queued prune
+    /// job for their messages and files.
+    Purged {
+        guilds: Vec<GuildId>,
+    },
+}
+
+/// Remove a user's personal data for good: devices, sessions, keys, OAuth
+/// grants and guild memberships are deleted, the profile is scrubbed and the
+/// account suspended. A member prune job is queued in each guild the user
+/// was in, so their messages there are tombstoned and files deleted. As with
+/// bots and puppets, the account row stays so existing messages keep a
+/// sender. Uploaded avatar images are left for the caller to delete.
+pub async fn purge(
+    conn: &mut sqlx::PgConnection,
+    user_id: UserId,
+) -> Result<PurgeOutcome, sqlx::Error> {
+    let exists = sqlx::query_scalar!(
+        r#"SELECT id AS "id: UserId" FROM users WHERE id = $1 FOR UPDATE"#,
+        user_id as UserId,
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    if exists.is_none() {
+        return Ok(PurgeOutcome::NotFound);
+    }
+
+    let owned = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM guilds WHERE owner_id = $1 AND deleted_at IS NULL"#,
+        user_id as UserId,
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+    if owned > 0 {
+        return Ok(PurgeOutcome::OwnsGuilds(owned));
+    }
+
+    let guilds = sqlx::query_scalar!(
+        r#"DELETE FROM guild_members WHERE user_id = $1 RETURNING guild_id AS "guild_id: GuildId""#,
+        user_id as UserId,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    // Table names cannot be bound, so this stays a runtime-checked query
+    for table in [
+        "devices",
+        "pre_key_bundles",
+        "refresh_tokens",
+        "oauth_consents",
+        "oauth_tokens",
+    ] {
+        sqlx::query(&format!("DELETE FROM {table} WHERE user_id = $1"))
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    sqlx::query!(
+        "UPDATE users SET public_key = $2, email = $3, display_name = 'Deleted User', \
+             avatar_url = NULL, avatar_key = NULL, discovery_hash = NULL, \
+             suspended_at = COALESCE(suspended_at, NOW()), suspension_reason = 'purged' \
+         WHERE id = $1",
+        user_id as UserId,
+        format!("purged:{user_id}"),
+        format!("{user_id}@purged.invalid"),
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO member_prune_jobs (guild_id, user_id) \
+         SELECT guild_id, $2 FROM UNNEST($1::uuid[]) AS g(guild_id)",
+        &guilds.iter().map(|g| g.0).collect::<Vec<_>>(),
+        user_id as UserId,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(PurgeOutcome::Purged { guilds })
+}
@@ -0,0 +1,213 @@
+//! Access token denylist backed by Redis.
+//!
+//! Access tokens are stateless JWTs that stay valid until `exp`. To cut off a
+//! compromised session early, every issued access token's `jti` is tracked
+//! per device. Revoking a device moves its tracked jtis onto a denylist.
+//! Entries expire with the access token TTL, so the denylist never outgrows
+//! the set of live tokens.
+//!
+//! Suspended users additionally get a marker key, so tokens issued in a race
+//! with the suspension are rejected too. The marker lives until the user is
+//! unsuspended; `users.suspended_at` stays the source of truth for login and
+//! refresh. The `AuthUser` extractor checks both keys in one pipelined round
+//! trip; they live in different hash slots, so a multi-key `EXISTS` would be
+//! refused by a Redis Cluster.
+//!
+//! Revoking touches keys in several hash slots, so it runs as separate
+//! commands rather than one script and works against a Redis Cluster.
+
+use fred::interfaces::{KeysInterface, LuaInterface, SetsInterface};
+use fred::types::Expiration;
+use openconv_shared::error::OpenConvError;
+use openconv_shared::ids::{DeviceId, UserId};
+
+use crate::redis::RedisPool;
+use crate::state::AppState;
+
+/// Adds a jti to the device's tracking set and refreshes the set's TTL.
+const TRACK_JTI_SCRIPT: &str = r#"
+redis.call('SADD', KEYS[1], ARGV[1])
+redis.call('EXPIRE', KEYS[1], ARGV[2])
+return 1
+"#;
+
+const DENYLIST_PREFIX: &str = "revoked_jti:";
+const SUSPENDED_PREFIX: &str = "suspended_user:";
+
+fn tracking_key(user_id: UserId, device_id: DeviceId) -> String {
+    format!("access_jtis:{user_id}:{device_id}")
+}
+
+fn denylist_key(jti: &str) -> String {
+    format!("{DENYLIST_PREFIX}{jti}")
+}
+
+fn suspended_key(user_id: UserId) -> String {
+    format!("{SUSPENDED_PREFIX}{user_id}")
+}
+
+/// Issue an access token and record its jti so it can be revoked later.
+///
+/// A Redis failure is logged and the token is still returned: the session
+/// works, it just cannot be cut off before expiry.
+pub async fn issue_tracked_access_token(
+    state: &AppState,
+    user_id: &UserId,
+    device_id: &DeviceId,
+) -> Result<String, OpenConvError> {
+    let (token, jti) = state.jwt.issue_access_token_with_jti(user_id, device_id)?;
+    track_jti(state, user_id, device_id, jti).await;
+    Ok(token)
+}
+
+/// Issue a step-up (re-authenticated) access token, tracked like any other.
+pub async fn issue_tracked_elevated_access_token(
+    state: &AppState,
+    user_id: &UserId,
+    device_id: &DeviceId,
+) -> Result<String, OpenConvError> {
+    let (token, jti) = state.jwt.issue_elevated_access_token(user_id, device_id)?;
+    track_jti(state, user_id, device_id, jti).await;
+    Ok(token)
+}
+
+async fn track_jti(state: &AppState, user_id: &UserId, device_id: &DeviceId, jti: String) {
+    let ttl = state.jwt.access_ttl().as_secs();
+
+    let result: Result<i64, _> = state
+        .redis
+        .eval(
+            TRACK_JTI_SCRIPT,
+            vec![state.redis.key(tracking_key(*user_id, *device_id))],
+            vec![jti, ttl.to_string()],
+        )
+        .await;
+    if let Err(e) = result {
+        tracing::warn!(user_id = %user_id, device_id = %device_id, error = %e, "failed to track access token jti");
+    }
+}
+
+/// Denylist all outstanding access tokens for a device. Returns the count revoked.
+pub async fn revoke_device_access_tokens(
+    state: &AppState,
+    user_id: UserId,
+    device_id: DeviceId,
+) -> Result<u64, OpenConvError> {
+    let redis = &state.redis;
+    let ttl = state.jwt.access_ttl().as_secs() as i64;
+    let tracking = redis.key(tracking_key(user_id, device_id));
+    let redis_err = |e: fred::error::Error| OpenConvError::Internal(format!("redis error: {e}"));
+
+    let jtis: Vec<String> = redis.smembers(&tracking).await.map_err(redis_err)?;
+    for jti in &jtis {
+        redis
+            .set::<(), _, _>(
+                redis.key(denylist_key(jti)),
+                "1",
+                Some(Expiration::EX(ttl)),
+                None,
+                false,
+            )
+            .await
+            .map_err(redis_err)?;
+    }
+    // Only the jtis denylisted above: one tracked meanwhile stays tracked
+    let revoked = jtis.len() as u64;
+    if revoked > 0 {
+        let _: i64 = redis.srem(&tracking, jtis).await.map_err(redis_err)?;
+    }
+    Ok(revoked)
+}
+
+/// Denylist all outstanding access tokens for each of the given devices.
+pub async fn revoke_user_access_tokens(
+    state: &AppState,
+    user_id: UserId,
+    device_ids: &[DeviceId],
+) -> Result<u64, OpenConvError> {
+    let mut total = 0;
+    for device_id in device_ids {
+        total += revoke_device_access_tokens(state, user_id, *device_id).await?;
+    }
+    Ok(total)
+}
+
+/// Check whether an access token's jti has been revoked or its user carries
+/// the suspension marker, with both `EXISTS` sent in one pipeline.
+///
+/// Fails open (returns `false`) when Redis is unreachable or its breaker is
+/// open, like the rate limiter's default, so a Redis outage does not lock
+/// every user out.
+pub async fn is_access_token_rejected(redis: &RedisPool, jti: &str, user_id: UserId) -> bool {
+    let pipeline = redis.next().pipeline();
+    let checks = async {
+        let _: () = pipeline.exists(redis.key(denylist_key(jti))).await?;
+        let _: () = pipeline.exists(redis.key(suspended_key(user_id))).await?;
+        pipeline.all::<(i64, i64)>().await
+    };
+    match redis.guarded(checks).await {
+        Ok((revoked, suspended)) => revoked > 0 || suspended > 0,
+        Err(e) => {
+            tracing::warn!(error = %e, "revocation check failed, failing open");
+            false
+        }
+    }
+}
+
+/// Set or clear the suspension marker checked by the `AuthUser` extractor.
+pub async fn set_user_suspended(
+    redis: &RedisPool,
+    user_id: UserId,
+    suspended: bool,
+) -> Result<(), OpenConvError> {
+    let key = redis.key(suspended_key(user_id));
+    let result = if suspended {
+        redis.set::<(), _, _>(&key, "1", None, None, false).await
+    } else {
+        redis.del::<(), _>(&key).await
+    };
+    result.map_err(|e| OpenConvError::Internal(format!("redis error: {e}")))
+}
+
+/// Check whether a user carries the suspension marker.
+///
+/// Fails open like `is_access_token_rejected`; a suspended user's refresh
+/// tokens are already gone, so they lose access once their access token expires.
+pub async fn is_user_suspended(redis: &RedisPool, user_id: UserId) -> bool {
+    match redis
+        .guarded(redis.exists::<i64, _>(redis.key(suspended_key(user_id))))
+        .await
+    {
+        Ok(n) => n > 0,
+        Err(e) => {
+            tracing::warn!(error = %e, "suspension check failed, failing open");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracking_key_is_scoped_to_user_and_device() {
+        let user_id = UserId::new();
+        let device_id = DeviceId::new();
+        let key = tracking_key(user_id, device_id);
+        assert!(key.starts_with("access_jtis:"));
+        assert!(key.contains(&user_id.to_string()));
+        assert!(key.contains(&device_id.to_string()));
+    }
+
+    #[test]
+    fn denylist_key_is_scoped_to_jti() {
+        assert_eq!(denylist_key("abc"), "revoked_jti:abc");
+    }
+
+    #[test]
+    fn suspended_key_is_scoped_to_user() {
+        let user_id = UserId::new();
+        assert_eq!(suspended_key(user_id), format!("suspended_user:{user_id}"));
+    }
+}
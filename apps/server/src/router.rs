@@ -1,14 +1,17 @@
 use axum::extract::DefaultBodyLimit;
-use axum::http::HeaderValue;
+use axum::http::{header, Extensions, HeaderMap, HeaderValue, StatusCode, Version};
 use axum::middleware;
 use axum::routing::{delete, get, post};
+use tower_http::compression::predicate::{Predicate, SizeAbove};
+use tower_http::compression::CompressionLayer;
 use tower_http::cors::{AllowOrigin, CorsLayer};
 use tower_http::trace::TraceLayer;
 use utoipa::OpenApi;
 use utoipa_scalar::{Scalar, Servable};
 
+use crate::config::{HttpConfig, RouteClass};
 use crate::handlers;
-use crate::middleware::rate_limit::UserRateLimitLayer;
+use crate::middleware::rate_limit::RateLimitLayer;
 use crate::openapi::ApiDoc;
 use crate::state::AppState;
 
@@ -34,8 +37,28 @@ pub fn build_router(state: AppState) -> axum::Router {
         .allow_headers([
             axum::http::header::CONTENT_TYPE,
             axum::http::header::AUTHORIZATION,
+            axum::http::HeaderName::from_static(crate::idempotency::HEADER),
         ]);
 
+    // Rate limits are declared per route class in `[rate_limit]` config
+    // and follow config reloads.
+    let limit = |class: RouteClass| {
+        RateLimitLayer::for_route(
+            state.redis.clone(),
+            state.jwt.clone(),
+            state.live.clone(),
+            class,
+        )
+    };
+
+    // Retried creates with the same Idempotency-Key replay the first response
+    let idempotent = || {
+        middleware::from_fn_with_state(
+            state.clone(),
+            crate::middleware::idempotency::idempotency_middleware,
+        )
+    };
+
     let auth_routes = axum::Router::new()
         .route("/register/start", post(handlers::auth::register_start))
         .route("/register/verify", post(handlers::auth::register_verify))
@@ -43,26 +66,39 @@ pub fn build_router(state: AppState) -> axum::Router {
             "/register/complete",
             post(handlers::auth::register_complete),
         )
-        .route("/challenge", post(handlers::auth::challenge))
+        .route(
+            "/challenge",
+            post(handlers::auth::challenge).layer(limit(RouteClass::Challenge)),
+        )
         .route("/verify", post(handlers::auth::login_verify))
         .route("/refresh", post(handlers::auth::refresh))
+        .route("/reauth", post(handlers::auth::reauth))
         .route("/logout", post(handlers::auth::logout))
         .route("/logout-all", post(handlers::auth::logout_all))
-        .route("/devices", get(handlers::auth::list_devices))
+        .route(
+            "/devices",
+            get(handlers::auth::list_devices).post(handlers::auth::link_device),
+        )
         .route(
             "/devices/{device_id}",
             delete(handlers::auth::revoke_device),
         )
+        .route("/sessions", get(handlers::auth::list_sessions))
+        .route(
+            "/sessions/{session_id}",
+            delete(handlers::auth::revoke_session),
+        )
         .route("/recover/start", post(handlers::auth::recover_start))
         .route("/recover/verify", post(handlers::auth::recover_verify))
         .route("/recover/complete", post(handlers::auth::recover_complete))
-        .layer(crate::middleware::rate_limit::RateLimitLayer::new(
-            state.redis.clone(),
-            state.config.rate_limit.auth_per_ip_per_minute,
-            60,
-            "auth".to_string(),
+        .layer(limit(RouteClass::Auth))
+        .layer(DefaultBodyLimit::max(
+            state.config.http.auth_body_limit_bytes,
         ));
 
+    // Avatar and icon uploads are raw image bodies
+    let image_body_limit = DefaultBodyLimit::max(state.config.http.image_body_limit_bytes);
+
     let user_routes = axum::Router::new()
         .route(
             "/me",
@@ -70,118 +106,201 @@ pub fn build_router(state: AppState) -> axum::Router {
         )
         .route("/me/prekeys", post(handlers::users::upload_prekeys))
         .route("/search", get(handlers::users::search_users))
+        .route("/bulk", post(handlers::users::bulk_users))
         .route("/{user_id}", get(handlers::users::get_user))
         .route("/{user_id}/prekeys", get(handlers::users::get_prekeys))
-        .layer(crate::middleware::rate_limit::RateLimitLayer::new(
-            state.redis.clone(),
-            state.config.rate_limit.auth_per_ip_per_minute,
-            60,
-            "users".to_string(),
-        ));
-
-    let rl = &state.config.rate_limit;
+        .route("/{user_id}/devices", get(handlers::users::get_device_keys))
+        .route(
+            "/{user_id}/devices/{device_id}/prekeys",
+            get(handlers::users::get_device_prekeys),
+        )
+        .merge(handlers::settings::routes())
+        .merge(handlers::quotas::user_routes())
+        .merge(handlers::images::user_routes().layer(image_body_limit.clone()))
+        .layer(limit(RouteClass::Users));
 
-    let guild_routes = handlers::guilds::routes().layer(UserRateLimitLayer::new(
-        state.redis.clone(),
-        state.jwt.clone(),
-        rl.guild_per_user_per_minute,
-        60,
-        "guilds".to_string(),
-    ));
+    let guild_routes = handlers::guilds::routes()
+        .layer(idempotent())
+        .merge(handlers::quotas::guild_routes())
+        .merge(handlers::images::guild_routes().layer(image_body_limit))
+        .layer(limit(RouteClass::Guilds));
 
-    let channel_routes = handlers::channels::routes().layer(UserRateLimitLayer::new(
-        state.redis.clone(),
-        state.jwt.clone(),
-        rl.channel_per_user_per_minute,
-        60,
-        "channels".to_string(),
-    ));
+    let channel_routes = handlers::channels::routes().layer(limit(RouteClass::Channels));
 
     let channel_detail_routes = handlers::channels::detail_routes();
     let role_routes = handlers::roles::routes();
+    let permission_routes = handlers::roles::permission_routes();
     let member_routes = handlers::guilds::member_routes();
+    let ban_routes = handlers::moderation::ban_routes();
+    let prune_job_routes = handlers::moderation::prune_job_routes();
+    let inactive_prune_routes = handlers::moderation::inactive_prune_routes();
+    let webhook_routes = handlers::webhooks::routes();
+    let automod_routes = handlers::automod::routes();
+    let bot_routes = handlers::bots::routes();
+    let oauth_routes = handlers::oauth::routes().route(
+        "/token",
+        post(handlers::oauth::token).layer(limit(RouteClass::Auth)),
+    );
+    let admin_routes = handlers::admin::routes();
 
-    let invite_guild_routes = handlers::invites::guild_routes().layer(UserRateLimitLayer::new(
-        state.redis.clone(),
-        state.jwt.clone(),
-        rl.invite_per_user_per_hour,
-        3600,
-        "invites".to_string(),
-    ));
+    let invite_guild_routes = handlers::invites::guild_routes().layer(limit(RouteClass::Invites));
 
     let invite_public_routes = handlers::invites::public_routes();
     let dm_routes = handlers::dm_channels::routes();
     let message_routes = handlers::messages::guild_message_routes();
+    let history_routes = handlers::messages::history_routes().layer(limit(RouteClass::Backfill));
+
+    // File upload routes get a body limit sized to the configured maximum file
+    // (plus multipart framing) and per-user rate limiting
+    let upload_body_limit =
+        DefaultBodyLimit::max(state.config.file_storage.max_file_size_bytes as usize + 64 * 1024);
 
-    // File upload routes get a higher body limit (25MB) and per-user rate limiting
     let guild_file_routes = handlers::files::guild_file_routes()
-        .layer(DefaultBodyLimit::max(26_214_400))
-        .layer(UserRateLimitLayer::new(
-            state.redis.clone(),
-            state.jwt.clone(),
-            rl.file_per_user_per_minute,
-            60,
-            "files".to_string(),
-        ));
+        .layer(idempotent())
+        .layer(upload_body_limit.clone())
+        .layer(limit(RouteClass::Files));
 
     let dm_file_routes = handlers::files::dm_file_routes()
-        .layer(DefaultBodyLimit::max(26_214_400))
-        .layer(UserRateLimitLayer::new(
-            state.redis.clone(),
-            state.jwt.clone(),
-            rl.file_per_user_per_minute,
-            60,
-            "files".to_string(),
-        ));
+        .layer(idempotent())
+        .layer(upload_body_limit.clone())
+        .layer(limit(RouteClass::Files));
+
+    let file_routes = handlers::files::file_routes().merge(
+        handlers::files::upload_routes()
+            .layer(idempotent())
+            .layer(upload_body_limit)
+            .layer(limit(RouteClass::Files)),
+    );
+    let attachment_routes = handlers::files::attachment_routes();
+
+    // Chunk uploads are bounded by the session, so they only get a body limit;
+    // starting and finishing sessions counts against the file rate limit
+    let chunk_body_limit = DefaultBodyLimit::max(
+        state.config.file_storage.upload_chunk_size_bytes as usize + 64 * 1024,
+    );
+    let upload_session_routes = handlers::upload_sessions::routes()
+        .layer(limit(RouteClass::Files))
+        .merge(handlers::upload_sessions::chunk_routes().layer(chunk_body_limit));
 
-    let file_routes = handlers::files::file_routes();
+    let discovery_routes = handlers::discovery::routes().layer(limit(RouteClass::Discovery));
 
     let ws_ticket_routes = axum::Router::new()
         .route("/", post(handlers::ws::create_ws_ticket))
-        .layer(UserRateLimitLayer::new(
-            state.redis.clone(),
-            state.jwt.clone(),
-            10,
-            60,
-            "ws_ticket".to_string(),
-        ));
+        .layer(limit(RouteClass::WsTicket));
 
-    axum::Router::new()
+    let app = axum::Router::new()
         .merge(Scalar::with_url("/docs", ApiDoc::openapi()))
+        .route("/healthz", get(handlers::health::liveness))
+        .route("/readyz", get(handlers::health::readiness))
         .route("/health/live", get(handlers::health::liveness))
         .route("/health/ready", get(handlers::health::readiness))
+        .route("/.well-known/jwks.json", get(handlers::jwks::jwks))
         .nest("/api/auth", auth_routes)
         .nest("/api/users", user_routes)
         .nest("/api/guilds", guild_routes)
         .nest("/api/guilds/{guild_id}/channels", channel_routes)
         .nest("/api/channels/{channel_id}/messages", message_routes)
+        .nest("/api/channels/{channel_id}/history", history_routes)
         .nest("/api/channels/{channel_id}/files", guild_file_routes)
+        .nest(
+            "/api/channels/{channel_id}/messages/{message_id}/attachments",
+            attachment_routes,
+        )
         .nest("/api/channels", channel_detail_routes)
         .nest("/api/guilds/{guild_id}/roles", role_routes)
+        .nest("/api/guilds/{guild_id}/permissions", permission_routes)
         .nest("/api/guilds/{guild_id}/members", member_routes)
+        .nest("/api/guilds/{guild_id}/bans", ban_routes)
+        .nest("/api/guilds/{guild_id}/prune-jobs", prune_job_routes)
+        .nest("/api/guilds/{guild_id}/prune", inactive_prune_routes)
+        .nest("/api/guilds/{guild_id}/webhooks", webhook_routes)
+        .nest("/api/guilds/{guild_id}/automod/rules", automod_routes)
+        .nest("/api/guilds/{guild_id}/bots", bot_routes)
+        .nest(
+            "/api/guilds/{guild_id}/voice-states",
+            handlers::voice::guild_routes().layer(limit(RouteClass::Users)),
+        )
+        .nest("/api/bots", handlers::bots::self_routes())
+        .nest("/api/bridge", handlers::bridges::routes())
+        .nest("/api/oauth", oauth_routes)
         .nest("/api/guilds/{guild_id}/invites", invite_guild_routes)
         .nest("/api/invites", invite_public_routes)
         .nest("/api/dm-channels", dm_routes)
         .nest("/api/dm-channels/{dm_channel_id}/files", dm_file_routes)
+        .nest("/api/files/sessions", upload_session_routes)
         .nest("/api/files", file_routes)
         .nest("/api/ws/ticket", ws_ticket_routes)
-        .route("/ws", get(handlers::ws::ws_upgrade))
-        .layer(middleware::from_fn(request_id_middleware))
-        .layer(DefaultBodyLimit::max(2 * 1024 * 1024))
-        .layer(cors)
-        .layer(TraceLayer::new_for_http())
-        .with_state(state)
+        .nest("/api/discovery", discovery_routes)
+        .nest("/api/admin/bridges", handlers::bridges::admin_routes())
+        .nest("/api/admin", admin_routes)
+        .nest("/api/policies", handlers::policies::routes())
+        .nest(
+            "/api/voice",
+            handlers::voice::routes().layer(limit(RouteClass::Users)),
+        )
+        .route("/ws", get(handlers::ws::ws_upgrade));
+
+    #[cfg(feature = "federation")]
+    let app = if state.config.federation.is_active() {
+        app.nest(
+            openconv_shared::federation::FEDERATION_PATH_PREFIX,
+            handlers::federation::server_routes(),
+        )
+        .nest(
+            "/api/federation",
+            handlers::federation::client_routes().layer(limit(RouteClass::Users)),
+        )
+    } else {
+        app
+    };
+
+    app.layer(middleware::from_fn_with_state(
+        state.clone(),
+        crate::middleware::policy::policy_update_middleware,
+    ))
+    // Denied addresses are refused before rate limiting or any handler runs
+    .layer(middleware::from_fn_with_state(
+        state.clone(),
+        crate::ip_denylist::ip_denylist_middleware,
+    ))
+    .layer(DefaultBodyLimit::max(state.config.http.body_limit_bytes))
+    .layer(middleware::map_response(
+        crate::middleware::body_limit::typed_payload_too_large,
+    ))
+    // Outside the body limit rewrite so its 413 bodies carry the ID too
+    .layer(middleware::from_fn_with_state(
+        state.clone(),
+        crate::middleware::request_id::request_id_middleware,
+    ))
+    .layer(CompressionLayer::new().compress_when(compress_json(&state.config.http)))
+    .layer(cors)
+    .layer(TraceLayer::new_for_http().make_span_with(request_span))
+    .with_state(state)
+}
+
+/// The span each request is traced in. `request_id` is filled in by the
+/// request ID middleware.
+fn request_span<B>(request: &axum::http::Request<B>) -> tracing::Span {
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+        request_id = tracing::field::Empty,
+    )
 }
 
-async fn request_id_middleware(
-    request: axum::extract::Request,
-    next: middleware::Next,
-) -> axum::response::Response {
-    let request_id = uuid::Uuid::new_v4().to_string();
-    tracing::Span::current().record("request_id", request_id.as_str());
-    let mut response = next.run(request).await;
-    response
-        .headers_mut()
-        .insert("x-request-id", HeaderValue::from_str(&request_id).unwrap());
-    response
+/// Compress JSON bodies above the configured size. Streamed NDJSON and SSE
+/// are left alone, since compressing them would buffer the stream.
+fn compress_json(config: &HttpConfig) -> impl Predicate {
+    let enabled = config.compression;
+    SizeAbove::new(config.compression_min_bytes).and(
+        move |_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions| {
+            enabled
+                && headers
+                    .get(header::CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .is_some_and(|ct| ct.starts_with("application/json"))
+        },
+    )
 }
@@ -0,0 +1,362 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::StreamExt;
+use object_store::path::Path as StorePath;
+use object_store::ObjectStore;
+use openconv_shared::error::OpenConvError;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::config::{ScanBackend, UploadScanConfig};
+
+/// Largest reply read from a scanner before giving up on it.
+const MAX_REPLY_LEN: usize = 64 * 1024;
+
+/// Outcome of scanning one upload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanVerdict {
+    Clean,
+    /// Blocked, with the scanner's reason (e.g. a signature name).
+    Flagged(String),
+}
+
+/// Inspects uploaded blobs after they are stored.
+///
+/// Blobs are end-to-end encrypted, so a scanner only sees ciphertext; the
+/// hook exists for operators whose compliance regime requires every stored
+/// object to pass through one regardless.
+#[async_trait::async_trait]
+pub trait UploadScanner: Send + Sync {
+    /// Whether uploads wait for this scanner. When false, files are
+    /// available as soon as they are stored and `scan` is never called.
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    /// Scan the object at `location`. An error means no verdict was
+    /// reached; the file stays pending and is retried.
+    async fn scan(
+        &self,
+        store: &dyn ObjectStore,
+        location: &StorePath,
+    ) -> Result<ScanVerdict, OpenConvError>;
+}
+
+/// Build the scanner selected by the config.
+pub fn create_scanner(config: &UploadScanConfig) -> Arc<dyn UploadScanner> {
+    let timeout = Duration::from_secs(config.timeout_seconds);
+    match config.backend {
+        ScanBackend::None => Arc::new(NoopScanner),
+        ScanBackend::Clamav => Arc::new(ClamAvScanner {
+            address: config.address.clone(),
+            timeout,
+        }),
+        ScanBackend::Icap => Arc::new(IcapScanner {
+            address: config.address.clone(),
+            service: config.icap_service.clone(),
+            timeout,
+        }),
+    }
+}
+
+/// Scanner that accepts everything without looking. The default.
+#[derive(Default)]
+pub struct NoopScanner;
+
+#[async_trait::async_trait]
+impl UploadScanner for NoopScanner {
+    fn enabled(&self) -> bool {
+        false
+    }
+
+    async fn scan(
+        &self,
+        _store: &dyn ObjectStore,
+        _location: &StorePath,
+    ) -> Result<ScanVerdict, OpenConvError> {
+        Ok(ScanVerdict::Clean)
+    }
+}
+
+fn scanner_err(what: &str, e: impl std::fmt::Display) -> OpenConvError {
+    OpenConvError::ServiceUnavailable(format!("{what}: {e}"))
+}
+
+/// Streams the object to clamd with `zINSTREAM`.
+pub struct ClamAvScanner {
+    address: String,
+    timeout: Duration,
+}
+
+impl ClamAvScanner {
+    async fn scan_inner(
+        &self,
+        store: &dyn ObjectStore,
+        location: &StorePath,
+    ) -> Result<ScanVerdict, OpenConvError> {
+        let mut body = store
+            .get(location)
+            .await
+            .map_err(|e| scanner_err("object store read failed", e))?
+            .into_stream();
+        let mut conn = TcpStream::connect(&self.address)
+            .await
+            .map_err(|e| scanner_err("clamd connect failed", e))?;
+        let io_err = |e: std::io::Error| scanner_err("clamd write failed", e);
+
+        conn.write_all(b"zINSTREAM\0").await.map_err(io_err)?;
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk.map_err(|e| scanner_err("object store read failed", e))?;
+            for part in chunk.chunks(u32::MAX as usize) {
+                conn.write_all(&(part.len() as u32).to_be_bytes())
+                    .await
+                    .map_err(io_err)?;
+                conn.write_all(part).await.map_err(io_err)?;
+            }
+        }
+        conn.write_all(&0u32.to_be_bytes()).await.map_err(io_err)?;
+
+        let reply = read_reply(&mut conn, |reply| reply.contains(&0))
+            .await
+            .map_err(|e| scanner_err("clamd read failed", e))?;
+        parse_clamd_reply(&String::from_utf8_lossy(&reply))
+    }
+}
+
+#[async_trait::async_trait]
+impl UploadScanner for ClamAvScanner {
+    async fn scan(
+        &self,
+        store: &dyn ObjectStore,
+        location: &StorePath,
+    ) -> Result<ScanVerdict, OpenConvError> {
+        tokio::time::timeout(self.timeout, self.scan_inner(store, location))
+            .await
+            .map_err(|_| scanner_err("clamd scan", "timed out"))?
+    }
+}
+
+/// Interpret a clamd INSTREAM reply such as `stream: OK` or
+/// `stream: Eicar-Signature FOUND`.
+fn parse_clamd_reply(reply: &str) -> Result<ScanVerdict, OpenConvError> {
+    let reply = reply.trim_end_matches(['\0', '\n', '\r']).trim();
+    let result = reply
+        .strip_prefix("stream:")
+        .map(str::trim)
+        .unwrap_or(reply);
+    if result == "OK" {
+        return Ok(ScanVerdict::Clean);
+    }
+    if let Some(signature) = result.strip_suffix(" FOUND") {
+        return Ok(ScanVerdict::Flagged(signature.trim().to_string()));
+    }
+    Err(scanner_err("clamd error", result))
+}
+
+/// Sends the object to an ICAP server as the body of an HTTP response
+/// (RESPMOD). `204 No Content` means clean; a `200` carrying a replacement
+/// response means the server blocked it.
+pub struct IcapScanner {
+    address: String,
+    service: String,
+    timeout: Duration,
+}
+
+impl IcapScanner {
+    async fn scan_inner(
+        &self,
+        store: &dyn ObjectStore,
+        location: &StorePath,
+    ) -> Result<ScanVerdict, OpenConvError> {
+        let mut body = store
+            .get(location)
+            .await
+            .map_err(|e| scanner_err("object store read failed", e))?
+            .into_stream();
+        let mut conn = TcpStream::connect(&self.address)
+            .await
+            .map_err(|e| scanner_err("ICAP connect failed", e))?;
+        let io_err = |e: std::io::Error| scanner_err("ICAP write failed", e);
+
+        let http_headers = "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\n\r\n";
+        let request = format!(
+            "RESPMOD icap://{address}/{service} ICAP/1.0\r\n\
+             Host: {address}\r\n\
+             Allow: 204\r\n\
+             Encapsulated: res-hdr=0, res-body={body_offset}\r\n\
+             \r\n\
+             {http_headers}",
+            address = self.address,
+            service = self.service,
+            body_offset = http_headers.len(),
+        );
+        conn.write_all(request.as_bytes()).await.map_err(io_err)?;
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk.map_err(|e| scanner_err("object store read failed", e))?;
+            if chunk.is_empty() {
+                continue;
+            }
+            conn.write_all(format!("{:x}\r\n", chunk.len()).as_bytes())
+                .await
+                .map_err(io_err)?;
+            conn.write_all(&chunk).await.map_err(io_err)?;
+            conn.write_all(b"\r\n").await.map_err(io_err)?;
+        }
+        conn.write_all(b"0\r\n\r\n").await.map_err(io_err)?;
+
+        let reply = read_reply(&mut conn, |reply| {
+            reply.windows(4).any(|window| window == b"\r\n\r\n")
+        })
+        .await
+        .map_err(|e| scanner_err("ICAP read failed", e))?;
+        parse_icap_reply(&String::from_utf8_lossy(&reply))
+    }
+}
+
+#[async_trait::async_trait]
+impl UploadScanner for IcapScanner {
+    async fn scan(
+        &self,
+        store: &dyn ObjectStore,
+        location: &StorePath,
+    ) -> Result<ScanVerdict, OpenConvError> {
+        tokio::time::timeout(self.timeout, self.scan_inner(store, location))
+            .await
+            .map_err(|_| scanner_err("ICAP scan", "timed out"))?
+    }
+}
+
+/// Interpret the status line and headers of an ICAP RESPMOD reply.
+fn parse_icap_reply(reply: &str) -> Result<ScanVerdict, OpenConvError> {
+    let mut lines = reply.lines();
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .ok_or_else(|| scanner_err("ICAP error", "malformed reply"))?;
+    match status {
+        "204" => Ok(ScanVerdict::Clean),
+        "200" => {
+            let reason = lines
+                .take_while(|line| !line.is_empty())
+                .filter_map(|line| line.split_once(':'))
+                .find(|(name, _)| {
+                    ["x-infection-found", "x-violations-found", "x-virus-id"]
+                        .contains(&name.trim().to_ascii_lowercase().as_str())
+                })
+                .map(|(_, value)| value.trim().to_string())
+                .unwrap_or_else(|| "blocked by ICAP service".to_string());
+            Ok(ScanVerdict::Flagged(reason))
+        }
+        other => Err(scanner_err("ICAP error", format!("status {other}"))),
+    }
+}
+
+/// Read from `conn` until `done` says the reply is complete or the peer
+/// closes the connection.
+async fn read_reply(
+    conn: &mut TcpStream,
+    done: impl Fn(&[u8]) -> bool,
+) -> Result<Vec<u8>, std::io::Error> {
+    let mut reply = Vec::new();
+    let mut buf = [0u8; 4096];
+    while !done(&reply) && reply.len() < MAX_REPLY_LEN {
+        let n = conn.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        reply.extend_from_slice(&buf[..n]);
+    }
+    Ok(reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object_store::memory::InMemory;
+    use object_store::PutPayload;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn clamd_replies_are_parsed() {
+        assert_eq!(
+            parse_clamd_reply("stream: OK\0").unwrap(),
+            ScanVerdict::Clean
+        );
+        assert_eq!(
+            parse_clamd_reply("stream: Eicar-Signature FOUND\0").unwrap(),
+            ScanVerdict::Flagged("Eicar-Signature".into())
+        );
+        assert!(parse_clamd_reply("INSTREAM size limit exceeded. ERROR\0").is_err());
+    }
+
+    #[test]
+    fn icap_replies_are_parsed() {
+        assert_eq!(
+            parse_icap_reply("ICAP/1.0 204 No Content\r\nISTag: \"x\"\r\n\r\n").unwrap(),
+            ScanVerdict::Clean
+        );
+        assert_eq!(
+            parse_icap_reply(
+                "ICAP/1.0 200 OK\r\nX-Infection-Found: Type=0; Resolution=2; Threat=EICAR;\r\n\r\n"
+            )
+            .unwrap(),
+            ScanVerdict::Flagged("Type=0; Resolution=2; Threat=EICAR;".into())
+        );
+        assert_eq!(
+            parse_icap_reply("ICAP/1.0 200 OK\r\n\r\n").unwrap(),
+            ScanVerdict::Flagged("blocked by ICAP service".into())
+        );
+        assert!(parse_icap_reply("ICAP/1.0 500 Server Error\r\n\r\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn noop_scanner_is_disabled() {
+        let scanner = create_scanner(&UploadScanConfig::default());
+        assert!(!scanner.enabled());
+    }
+
+    #[tokio::test]
+    async fn clamav_scanner_streams_object_to_clamd() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = tokio::spawn(async move {
+            let (mut conn, _) = listener.accept().await.unwrap();
+            let mut command = [0u8; 10];
+            conn.read_exact(&mut command).await.unwrap();
+            assert_eq!(&command, b"zINSTREAM\0");
+            let mut received = Vec::new();
+            loop {
+                let mut len = [0u8; 4];
+                conn.read_exact(&mut len).await.unwrap();
+                let len = u32::from_be_bytes(len) as usize;
+                if len == 0 {
+                    break;
+                }
+                let mut chunk = vec![0u8; len];
+                conn.read_exact(&mut chunk).await.unwrap();
+                received.extend(chunk);
+            }
+            conn.write_all(b"stream: Test-Signature FOUND\0")
+                .await
+                .unwrap();
+            received
+        });
+
+        let store = InMemory::new();
+        let location = StorePath::from("blobs/abc");
+        store
+            .put(&location, PutPayload::from(b"suspicious".to_vec()))
+            .await
+            .unwrap();
+        let scanner = create_scanner(&UploadScanConfig {
+            backend: ScanBackend::Clamav,
+            address,
+            ..Default::default()
+        });
+
+        let verdict = scanner.scan(&store, &location).await.unwrap();
+        assert_eq!(verdict, ScanVerdict::Flagged("Test-Signature".into()));
+        assert_eq!(server.await.unwrap(), b"suspicious");
+    }
+}
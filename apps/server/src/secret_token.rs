@@ -0,0 +1,39 @@
+//! Random bearer secrets that the server stores only as hashes: bot tokens
+//! and OAuth tokens.
+
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+/// A new random token. Only its [`hash`] should be stored.
+pub fn generate() -> String {
+    let bytes: [u8; 32] = rand::rng().random();
+    hex::encode(bytes)
+}
+
+/// Tokens are high-entropy, so an unsalted SHA-256 is enough to keep a
+/// database leak from exposing usable tokens.
+pub fn hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_tokens_are_unique_hex() {
+        let a = generate();
+        let b = generate();
+        assert_eq!(a.len(), 64);
+        assert!(a.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn hash_is_stable_and_differs_from_token() {
+        let token = generate();
+        assert_eq!(hash(&token), hash(&token));
+        assert_ne!(hash(&token), token);
+        assert_ne!(hash(&token), hash(&generate()));
+    }
+}
@@ -1,25 +1,73 @@
+use std::future::Future;
 use std::sync::Arc;
 
 use object_store::ObjectStore;
 
 use crate::config::ServerConfig;
+use crate::db::{is_connection_error, ReadReplica};
+use crate::email::outbox::OutboxKey;
 use crate::email::EmailService;
+use crate::ip_denylist::IpDenylist;
 use crate::jwt::JwtService;
+use crate::live_config::LiveConfig;
+use crate::redis::RedisPool;
+use crate::scan::UploadScanner;
+use crate::tasks::scheduler::JobRegistry;
 use crate::ws::state::WsState;
 
 /// Shared application state passed to all handlers via Axum's State extractor.
 ///
 /// `PgPool` is internally Arc-wrapped. `ServerConfig` is wrapped in `Arc`
-/// so cloning `AppState` is cheap.
+/// so cloning `AppState` is cheap. `config` holds the startup values;
+/// settings that can be reloaded are read from `live` instead.
 #[derive(Clone)]
 pub struct AppState {
     pub db: sqlx::PgPool,
+    /// Read replica, when configured. Use through [`AppState::read`].
+    pub db_replica: Option<Arc<ReadReplica>>,
     pub config: Arc<ServerConfig>,
-    pub redis: fred::clients::Pool,
+    pub live: Arc<LiveConfig>,
+    pub redis: RedisPool,
     pub jwt: Arc<JwtService>,
     pub email: Arc<dyn EmailService>,
+    /// Seals codes queued in the email outbox; built once from `config`.
+    pub outbox_key: OutboxKey,
     pub object_store: Arc<dyn ObjectStore>,
+    pub scanner: Arc<dyn UploadScanner>,
+    pub jobs: Arc<JobRegistry>,
     pub ws: Arc<WsState>,
+    pub ip_denylist: Arc<IpDenylist>,
+}
+
+impl AppState {
+    /// Pool for a read that tolerates replication lag: the replica while it
+    /// is healthy, else the primary. For streams that page through one pool;
+    /// single queries should use [`AppState::read`].
+    pub fn read_pool(&self) -> sqlx::PgPool {
+        match self.db_replica.as_deref().filter(|r| r.is_healthy()) {
+            Some(replica) => replica.pool().clone(),
+            None => self.db.clone(),
+        }
+    }
+
+    /// Run a read that tolerates replication lag on the replica, retrying on
+    /// the primary if the replica cannot be reached.
+    pub async fn read<T, F, Fut>(&self, query: F) -> Result<T, sqlx::Error>
+    where
+        F: Fn(sqlx::PgPool) -> Fut,
+        Fut: Future<Output = Result<T, sqlx::Error>>,
+    {
+        if let Some(replica) = self.db_replica.as_deref().filter(|r| r.is_healthy()) {
+            match query(replica.pool().clone()).await {
+                Err(e) if is_connection_error(&e) => {
+                    tracing::warn!(error = %e, "read replica query failed, retrying on primary");
+                    replica.set_healthy(false);
+                }
+                result => return result,
+            }
+        }
+        query(self.db.clone()).await
+    }
 }
 
 #[cfg(test)]
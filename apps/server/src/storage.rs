@@ -1,34 +1,345 @@
+use std::fmt;
+use std::path::{Path as FsPath, PathBuf};
 use std::sync::Arc;
 
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use object_store::azure::MicrosoftAzureBuilder;
+use object_store::gcp::GoogleCloudStorageBuilder;
 use object_store::local::LocalFileSystem;
 use object_store::memory::InMemory;
-use object_store::ObjectStore;
+use object_store::path::Path;
+use object_store::{
+    GetOptions, GetResult, ListResult, MultipartUpload, ObjectMeta, ObjectStore, PutMultipartOpts,
+    PutOptions, PutPayload, PutResult, UploadPart,
+};
 
-use crate::config::FileStorageConfig;
+use crate::config::{AzureStorageConfig, FileStorageConfig, GcsStorageConfig};
+
+/// File written, renamed and removed in the local root during startup to
+/// prove the directory is usable before the server accepts uploads.
+const WRITE_PROBE_NAME: &str = ".openconv-write-probe";
 
 /// Creates an ObjectStore backend from the file storage configuration.
 ///
-/// - `"local"` backend: creates directory if needed, uses `LocalFileSystem`
+/// - `"local"` backend: creates the root directory if needed, verifies it is
+///   writable, and serves it through [`LocalStore`]
 /// - `"memory"` backend: uses `InMemory` (for testing)
+/// - `"azure"` backend: Azure Blob Storage, see [`AzureStorageConfig`]
+/// - `"gcs"` backend: Google Cloud Storage, see [`GcsStorageConfig`]
+///
+/// Cloud backends are only configured here; call [`check_object_store`]
+/// once the runtime is up to confirm the credentials actually work.
 pub fn create_object_store(
     config: &FileStorageConfig,
 ) -> Result<Arc<dyn ObjectStore>, Box<dyn std::error::Error>> {
     match config.backend.as_str() {
         "local" => {
-            std::fs::create_dir_all(&config.local_path)?;
-            let store = LocalFileSystem::new_with_prefix(&config.local_path)?;
+            let root = prepare_local_root(FsPath::new(&config.local_path))?;
+            let store = LocalStore::new(root, config.local_fsync)?;
             Ok(Arc::new(store))
         }
         "memory" => Ok(Arc::new(InMemory::new())),
+        "azure" => Ok(Arc::new(azure_store(&config.azure)?)),
+        "gcs" => Ok(Arc::new(gcs_store(&config.gcs)?)),
         other => Err(format!("unknown file storage backend: {other}").into()),
     }
 }
 
+/// Builds an Azure Blob store, starting from the `AZURE_*` environment
+/// variables and layering the explicit config on top.
+fn azure_store(
+    config: &AzureStorageConfig,
+) -> object_store::Result<object_store::azure::MicrosoftAzure> {
+    let mut builder = MicrosoftAzureBuilder::from_env()
+        .with_container_name(&config.container)
+        .with_use_emulator(config.use_emulator);
+    if let Some(account) = &config.account {
+        builder = builder.with_account(account);
+    }
+    if let Some(access_key) = &config.access_key {
+        builder = builder.with_access_key(access_key);
+    }
+    if let Some(endpoint) = &config.endpoint {
+        builder = builder.with_endpoint(endpoint.clone());
+    }
+    builder.build()
+}
+
+/// Builds a Google Cloud Storage store, starting from the `GOOGLE_*`
+/// environment variables and layering the explicit config on top.
+fn gcs_store(
+    config: &GcsStorageConfig,
+) -> object_store::Result<object_store::gcp::GoogleCloudStorage> {
+    let mut builder = GoogleCloudStorageBuilder::from_env().with_bucket_name(&config.bucket);
+    if let Some(path) = &config.service_account_path {
+        builder = builder.with_service_account_path(path);
+    }
+    builder.build()
+}
+
+/// Round-trips a small probe object through the store: write, read back,
+/// delete. Run at startup so bad credentials or a missing bucket stop the
+/// server instead of failing the first upload.
+pub async fn check_object_store(store: &dyn ObjectStore) -> object_store::Result<()> {
+    let probe = Path::from(format!("{WRITE_PROBE_NAME}-{}", uuid::Uuid::new_v4()));
+    let payload = b"openconv".to_vec();
+
+    store.put(&probe, PutPayload::from(payload.clone())).await?;
+    let read_back = store.get(&probe).await?.bytes().await;
+    let deleted = store.delete(&probe).await;
+    let read_back = read_back?;
+    deleted?;
+
+    if read_back.as_ref() != payload.as_slice() {
+        return Err(storage_err(
+            "health check object read back with different contents".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Read-only reachability check for readiness probes. A missing object is
+/// a successful answer from the backend.
+pub async fn ping_object_store(store: &dyn ObjectStore) -> object_store::Result<()> {
+    match store.head(&Path::from(WRITE_PROBE_NAME)).await {
+        Ok(_) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Creates the local storage root, resolves it to an absolute path and
+/// checks that files can be written, renamed and removed inside it.
+fn prepare_local_root(path: &FsPath) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(path).map_err(|e| {
+        format!(
+            "cannot create file storage directory {}: {e}",
+            path.display()
+        )
+    })?;
+    let root = path.canonicalize()?;
+    if !root.is_dir() {
+        return Err(format!("file storage path {} is not a directory", root.display()).into());
+    }
+    verify_writable(&root).map_err(|e| {
+        format!(
+            "file storage directory {} is not writable: {e}",
+            root.display()
+        )
+    })?;
+    Ok(root)
+}
+
+fn verify_writable(root: &FsPath) -> std::io::Result<()> {
+    let staged = root.join(format!("{WRITE_PROBE_NAME}.{}", std::process::id()));
+    let target = root.join(WRITE_PROBE_NAME);
+
+    let result = write_probe(&staged, &target);
+    if result.is_err() {
+        let _ = std::fs::remove_file(&staged);
+        let _ = std::fs::remove_file(&target);
+    }
+    result
+}
+
+/// Writes and syncs `staged`, renames it over `target`, reads it back and
+/// removes it -- the same sequence `LocalStore` relies on for every upload.
+fn write_probe(staged: &FsPath, target: &FsPath) -> std::io::Result<()> {
+    use std::io::Write;
+
+    const PAYLOAD: &[u8] = b"openconv";
+
+    let mut file = std::fs::File::create(staged)?;
+    file.write_all(PAYLOAD)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(staged, target)?;
+
+    if std::fs::read(target)? != PAYLOAD {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "probe file read back with different contents",
+        ));
+    }
+    std::fs::remove_file(target)
+}
+
+/// Local-disk object store for self-hosted deployments.
+///
+/// Writes go through `LocalFileSystem`, which stages each object in a
+/// temporary file next to its destination and renames it into place, so
+/// readers never observe a partially written object. When `fsync` is enabled
+/// the object and every directory between it and the root are flushed to
+/// disk before the write is acknowledged, so an upload that returned success
+/// survives a crash or power loss.
+#[derive(Debug)]
+pub struct LocalStore {
+    inner: LocalFileSystem,
+    root: Arc<PathBuf>,
+    fsync: bool,
+}
+
+impl LocalStore {
+    /// Serves objects under `root`, which must already exist.
+    pub fn new(root: PathBuf, fsync: bool) -> object_store::Result<Self> {
+        let inner = LocalFileSystem::new_with_prefix(&root)?;
+        Ok(Self {
+            inner,
+            root: Arc::new(root),
+            fsync,
+        })
+    }
+
+    async fn sync(&self, location: &Path) -> object_store::Result<()> {
+        if !self.fsync {
+            return Ok(());
+        }
+        let file = self.inner.path_to_filesystem(location)?;
+        sync_to_disk(file, Arc::clone(&self.root)).await
+    }
+}
+
+impl fmt::Display for LocalStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LocalStore({})", self.root.display())
+    }
+}
+
+/// Flushes `file` and then each directory from its parent up to `root`, so
+/// both the data and the rename that published it are durable.
+async fn sync_to_disk(file: PathBuf, root: Arc<PathBuf>) -> object_store::Result<()> {
+    tokio::task::spawn_blocking(move || {
+        std::fs::File::open(&file)?.sync_all()?;
+        #[cfg(unix)]
+        {
+            let mut dir = file.parent();
+            while let Some(current) = dir {
+                std::fs::File::open(current)?.sync_all()?;
+                if current == root.as_path() {
+                    break;
+                }
+                dir = current.parent();
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = root;
+        Ok::<_, std::io::Error>(())
+    })
+    .await
+    .map_err(|e| storage_err(e.into()))?
+    .map_err(|e| storage_err(e.into()))
+}
+
+fn storage_err(source: Box<dyn std::error::Error + Send + Sync>) -> object_store::Error {
+    object_store::Error::Generic {
+        store: "openconv",
+        source,
+    }
+}
+
+#[async_trait]
+impl ObjectStore for LocalStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        payload: PutPayload,
+        opts: PutOptions,
+    ) -> object_store::Result<PutResult> {
+        let result = self.inner.put_opts(location, payload, opts).await?;
+        self.sync(location).await?;
+        Ok(result)
+    }
+
+    async fn put_multipart_opts(
+        &self,
+        location: &Path,
+        opts: PutMultipartOpts,
+    ) -> object_store::Result<Box<dyn MultipartUpload>> {
+        let upload = self.inner.put_multipart_opts(location, opts).await?;
+        if !self.fsync {
+            return Ok(upload);
+        }
+        Ok(Box::new(SyncOnComplete {
+            upload,
+            file: self.inner.path_to_filesystem(location)?,
+            root: Arc::clone(&self.root),
+        }))
+    }
+
+    async fn get_opts(
+        &self,
+        location: &Path,
+        options: GetOptions,
+    ) -> object_store::Result<GetResult> {
+        self.inner.get_opts(location, options).await
+    }
+
+    async fn delete(&self, location: &Path) -> object_store::Result<()> {
+        self.inner.delete(location).await
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, object_store::Result<ObjectMeta>> {
+        self.inner.list(prefix)
+    }
+
+    fn list_with_offset(
+        &self,
+        prefix: Option<&Path>,
+        offset: &Path,
+    ) -> BoxStream<'_, object_store::Result<ObjectMeta>> {
+        self.inner.list_with_offset(prefix, offset)
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> object_store::Result<ListResult> {
+        self.inner.list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+        self.inner.copy(from, to).await?;
+        self.sync(to).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+        self.inner.rename(from, to).await?;
+        self.sync(to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+        self.inner.copy_if_not_exists(from, to).await?;
+        self.sync(to).await
+    }
+}
+
+/// Multipart upload that flushes the assembled object once it is renamed
+/// into place.
+#[derive(Debug)]
+struct SyncOnComplete {
+    upload: Box<dyn MultipartUpload>,
+    file: PathBuf,
+    root: Arc<PathBuf>,
+}
+
+#[async_trait]
+impl MultipartUpload for SyncOnComplete {
+    fn put_part(&mut self, data: PutPayload) -> UploadPart {
+        self.upload.put_part(data)
+    }
+
+    async fn complete(&mut self) -> object_store::Result<PutResult> {
+        let result = self.upload.complete().await?;
+        sync_to_disk(self.file.clone(), Arc::clone(&self.root)).await?;
+        Ok(result)
+    }
+
+    async fn abort(&mut self) -> object_store::Result<()> {
+        self.upload.abort().await
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use object_store::path::Path;
-    use object_store::PutPayload;
+    use futures::TryStreamExt;
 
     #[tokio::test]
     async fn inmemory_backend_roundtrip() {
@@ -36,6 +347,7 @@ mod tests {
             backend: "memory".into(),
             local_path: String::new(),
             max_file_size_bytes: 1024,
+            ..Default::default()
         };
         let store = create_object_store(&config).unwrap();
         let path = Path::from("test/blob.bin");
@@ -57,6 +369,7 @@ mod tests {
             backend: "local".into(),
             local_path: dir.path().to_str().unwrap().into(),
             max_file_size_bytes: 1024,
+            ..Default::default()
         };
         let store = create_object_store(&config).unwrap();
         let path = Path::from("test.bin");
@@ -77,7 +390,111 @@ mod tests {
             backend: "s3".into(),
             local_path: String::new(),
             max_file_size_bytes: 1024,
+            ..Default::default()
         };
         assert!(create_object_store(&config).is_err());
     }
+
+    #[tokio::test]
+    async fn health_check_passes_and_cleans_up() {
+        let store = InMemory::new();
+        check_object_store(&store).await.unwrap();
+        let listed: Vec<_> = store.list(None).try_collect().await.unwrap();
+        assert!(listed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn ping_treats_missing_probe_as_reachable() {
+        let store = InMemory::new();
+        ping_object_store(&store).await.unwrap();
+    }
+
+    #[test]
+    fn azure_backend_builds_against_emulator() {
+        let config = FileStorageConfig {
+            backend: "azure".into(),
+            azure: AzureStorageConfig {
+                container: "files".into(),
+                use_emulator: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let store = create_object_store(&config).unwrap();
+        assert!(store.to_string().contains("files"));
+    }
+
+    fn local_config(dir: &FsPath, fsync: bool) -> FileStorageConfig {
+        FileStorageConfig {
+            backend: "local".into(),
+            local_path: dir.to_str().unwrap().into(),
+            local_fsync: fsync,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn local_backend_creates_missing_root_without_leaving_probe() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("nested/files");
+        let store = create_object_store(&local_config(&root, true)).unwrap();
+
+        assert!(root.is_dir());
+        let listed: Vec<_> = store.list(None).try_collect().await.unwrap();
+        assert!(listed.is_empty());
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn local_backend_rejects_root_that_is_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("not-a-dir");
+        std::fs::write(&file, b"x").unwrap();
+
+        let err = create_object_store(&local_config(&file, true)).unwrap_err();
+        assert!(err.to_string().contains("not-a-dir"));
+    }
+
+    #[tokio::test]
+    async fn local_backend_fsync_multipart_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = create_object_store(&local_config(dir.path(), true)).unwrap();
+        let path = Path::from("guild/abc/blob.bin");
+
+        let mut upload = store.put_multipart(&path).await.unwrap();
+        upload
+            .put_part(PutPayload::from(b"hello ".to_vec()))
+            .await
+            .unwrap();
+        upload
+            .put_part(PutPayload::from(b"world".to_vec()))
+            .await
+            .unwrap();
+        upload.complete().await.unwrap();
+
+        let bytes = store.get(&path).await.unwrap().bytes().await.unwrap();
+        assert_eq!(bytes.as_ref(), b"hello world");
+        assert!(dir.path().join("guild/abc/blob.bin").is_file());
+    }
+
+    #[tokio::test]
+    async fn local_backend_without_fsync_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = create_object_store(&local_config(dir.path(), false)).unwrap();
+        let path = Path::from("a/b.bin");
+
+        store
+            .put(&path, PutPayload::from(b"data".to_vec()))
+            .await
+            .unwrap();
+        store.copy(&path, &Path::from("a/c.bin")).await.unwrap();
+        let bytes = store
+            .get(&Path::from("a/c.bin"))
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        assert_eq!(bytes.as_ref(), b"data");
+    }
 }
@@ -0,0 +1,127 @@
+//! Streaming response bodies for large exports.
+//!
+//! Handlers produce a `Stream` of serializable items; this module turns it
+//! into either newline-delimited JSON or Server-Sent Events depending on the
+//! client's `Accept` header. Items are pulled from the stream only as the
+//! connection drains, so a slow client applies backpressure all the way back
+//! to the database query instead of the server buffering the whole body.
+
+use std::convert::Infallible;
+
+use axum::http::{header, HeaderMap};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+const SSE_CONTENT_TYPE: &str = "text/event-stream";
+
+/// Wire format for a streamed response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamFormat {
+    Ndjson,
+    Sse,
+}
+
+impl StreamFormat {
+    /// Pick the format from the `Accept` header. NDJSON unless the client
+    /// explicitly asks for `text/event-stream`.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let wants_sse = headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .any(|v| v.trim().starts_with(SSE_CONTENT_TYPE));
+        if wants_sse {
+            StreamFormat::Sse
+        } else {
+            StreamFormat::Ndjson
+        }
+    }
+}
+
+/// Build a streaming response from a stream of serializable items.
+pub fn stream_response<S, T>(format: StreamFormat, items: S) -> Response
+where
+    S: Stream<Item = T> + Send + 'static,
+    T: Serialize + Send + 'static,
+{
+    match format {
+        StreamFormat::Ndjson => {
+            let body = items.map(|item| Ok::<_, Infallible>(ndjson_line(&item)));
+            (
+                [(header::CONTENT_TYPE, NDJSON_CONTENT_TYPE)],
+                axum::body::Body::from_stream(body),
+            )
+                .into_response()
+        }
+        StreamFormat::Sse => {
+            let events = items.map(|item| {
+                Ok::<_, Infallible>(
+                    Event::default().data(serde_json::to_string(&item).unwrap_or_default()),
+                )
+            });
+            Sse::new(events)
+                .keep_alive(KeepAlive::default())
+                .into_response()
+        }
+    }
+}
+
+fn ndjson_line<T: Serialize>(item: &T) -> String {
+    let mut line = match serde_json::to_string(item) {
+        Ok(json) => json,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to serialize streamed item");
+            String::new()
+        }
+    };
+    line.push('\n');
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn format_defaults_to_ndjson() {
+        assert_eq!(
+            StreamFormat::from_headers(&HeaderMap::new()),
+            StreamFormat::Ndjson
+        );
+    }
+
+    #[test]
+    fn format_selects_sse_from_accept_list() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("application/json, text/event-stream"),
+        );
+        assert_eq!(StreamFormat::from_headers(&headers), StreamFormat::Sse);
+    }
+
+    #[test]
+    fn ndjson_line_is_newline_terminated() {
+        let line = ndjson_line(&serde_json::json!({"a": 1}));
+        assert_eq!(line, "{\"a\":1}\n");
+    }
+
+    #[tokio::test]
+    async fn ndjson_response_streams_one_line_per_item() {
+        let items = futures::stream::iter(vec![1, 2, 3]);
+        let response = stream_response(StreamFormat::Ndjson, items);
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            NDJSON_CONTENT_TYPE
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"1\n2\n3\n");
+    }
+}
@@ -1,11 +1,45 @@
 use sqlx::PgPool;
 
-/// Delete all refresh tokens that have expired.
-/// Called periodically (once at startup, then hourly) to prevent unbounded table growth.
+use crate::tasks::scheduler::{JobResult, PeriodicJob, Schedule};
+
+/// Delete all refresh tokens that have expired, along with expired OAuth tokens
+/// and outbox emails that were sent or dead-lettered over a week ago.
+/// Run on the `schedules.refresh_token_cleanup` schedule to prevent unbounded table growth.
 pub async fn cleanup_expired_refresh_tokens(pool: &PgPool) -> Result<u64, sqlx::Error> {
     let result = sqlx::query("DELETE FROM refresh_tokens WHERE expires_at < NOW()")
         .execute(pool)
         .await?;
+    let oauth = sqlx::query("DELETE FROM oauth_tokens WHERE expires_at < NOW()")
+        .execute(pool)
+        .await?;
+
+    let emails = sqlx::query(
+        "DELETE FROM email_outbox \
+         WHERE status IN ('sent', 'dead') AND created_at < NOW() - INTERVAL '7 days'",
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() + oauth.rows_affected() + emails.rows_affected())
+}
+
+/// [`cleanup_expired_refresh_tokens`] on its configured schedule.
+pub struct RefreshTokenCleanupJob {
+    pub pool: PgPool,
+    pub schedule: Schedule,
+}
+
+#[async_trait::async_trait]
+impl PeriodicJob for RefreshTokenCleanupJob {
+    fn name(&self) -> &'static str {
+        "refresh_token_cleanup"
+    }
+
+    fn schedule(&self) -> Schedule {
+        self.schedule.clone()
+    }
 
-    Ok(result.rows_affected())
+    async fn run(&self) -> JobResult {
+        Ok(cleanup_expired_refresh_tokens(&self.pool).await?)
+    }
 }
@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use crate::live_config::LiveConfig;
+use crate::tasks::scheduler::{JobResult, PeriodicJob, Schedule};
+
+/// Check the config file every 10 seconds and reload the runtime settings
+/// when it changed. Runs on every node, since each holds its own copy.
+pub struct ConfigWatchJob {
+    pub live: Arc<LiveConfig>,
+}
+
+#[async_trait::async_trait]
+impl PeriodicJob for ConfigWatchJob {
+    fn name(&self) -> &'static str {
+        "config_watch"
+    }
+
+    fn schedule(&self) -> Schedule {
+        Schedule::every_secs(10)
+    }
+
+    fn per_node(&self) -> bool {
+        true
+    }
+
+    async fn run(&self) -> JobResult {
+        let live = self.live.clone();
+        match tokio::task::spawn_blocking(move || live.reload_if_changed()).await? {
+            Ok(Some(changed)) => {
+                tracing::info!(?changed, "config file changed, runtime settings reloaded");
+                Ok(changed.len() as u64)
+            }
+            Ok(None) => Ok(0),
+            Err(e) => Err(format!("config reload rejected: {e}").into()),
+        }
+    }
+}
@@ -0,0 +1,318 @@
+//! Development seed data.
+//!
+//! Fills a database with users, guilds (with their default roles plus a
+//! `moderator` role), channels, invites and placeholder message rows for
+//! local development and load testing. Every ID, invite code and timestamp
+//! is derived from [`SeedOptions::seed`], so the same options always produce
+//! the same rows and re-running is a no-op.
+//!
+//! Seeded users get placeholder identity keys no client can sign for, like
+//! bridge puppets; mint tokens for them with the server's JWT key instead.
+//! Message payloads are random-looking bytes, not real ciphertext.
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use openconv_shared::ids::{ChannelId, GuildId, MessageId, RoleId, UserId};
+use openconv_shared::permissions::Permissions;
+use sha2::{Digest, Sha256};
+
+use crate::repo::guilds::DefaultRoles;
+
+/// How much data to generate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeedOptions {
+    pub users: u32,
+    /// Each owned by a different user, round-robin; every user joins every guild.
+    pub guilds: u32,
+    /// Text channels per guild, including `#main`.
+    pub channels_per_guild: u32,
+    pub messages_per_channel: u32,
+    /// Varies every derived ID, so several datasets can share a database.
+    pub seed: u64,
+}
+
+impl Default for SeedOptions {
+    fn default() -> Self {
+        Self {
+            users: 20,
+            guilds: 3,
+            channels_per_guild: 4,
+            messages_per_channel: 50,
+            seed: 0,
+        }
+    }
+}
+
+/// Rows inserted by [`seed`]. Rows that already existed are not counted.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SeedReport {
+    pub users: u64,
+    pub guilds: u64,
+    pub channels: u64,
+    pub invites: u64,
+    pub messages: u64,
+}
+
+/// Messages are spread one minute apart from this instant.
+fn message_epoch() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()
+}
+
+fn digest(seed: u64, kind: &str, index: u64) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(b"openconv-seed")
+        .chain_update(seed.to_be_bytes())
+        .chain_update(kind.as_bytes())
+        .chain_update(index.to_be_bytes())
+        .finalize()
+        .into()
+}
+
+/// A stable UUID (version 8) for the `index`th row of a kind.
+fn seeded_uuid(seed: u64, kind: &str, index: u64) -> uuid::Uuid {
+    let bytes = digest(seed, kind, index);
+    uuid::Builder::from_custom_bytes(bytes[..16].try_into().expect("16 bytes")).into_uuid()
+}
+
+pub fn user_id(seed: u64, index: u32) -> UserId {
+    UserId(seeded_uuid(seed, "user", index.into()))
+}
+
+pub fn guild_id(seed: u64, index: u32) -> GuildId {
+    GuildId(seeded_uuid(seed, "guild", index.into()))
+}
+
+pub fn channel_id(seed: u64, guild: u32, index: u32) -> ChannelId {
+    ChannelId(seeded_uuid(
+        seed,
+        "channel",
+        (u64::from(guild) << 32) | u64::from(index),
+    ))
+}
+
+fn role_id(seed: u64, guild: u32, role: &str) -> RoleId {
+    RoleId(seeded_uuid(seed, role, guild.into()))
+}
+
+/// The invite code seeded for a guild. Never expires, unlimited uses.
+pub fn invite_code(seed: u64, guild: u32) -> String {
+    format!("seed{seed}g{guild}")
+}
+
+/// Insert the seed dataset. Run inside a transaction so a failure leaves
+/// nothing half-seeded.
+pub async fn seed(
+    conn: &mut sqlx::PgConnection,
+    options: &SeedOptions,
+) -> Result<SeedReport, sqlx::Error> {
+    let seed = options.seed;
+    let mut report = SeedReport::default();
+    if options.users == 0 {
+        return Ok(report);
+    }
+
+    let users: Vec<UserId> = (0..options.users).map(|i| user_id(seed, i)).collect();
+    for (i, user) in users.iter().enumerate() {
+        report.users += sqlx::query(
+            "INSERT INTO users (id, public_key, email, display_name) VALUES ($1, $2, $3, $4) \
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(user)
+        .bind(format!("seed:{user}"))
+        .bind(format!("user{i}.s{seed}@seed.invalid"))
+        .bind(format!("Seed User {i}"))
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+    }
+
+    for g in 0..options.guilds {
+        let guild = guild_id(seed, g);
+        let owner = users[g as usize % users.len()];
+        let roles = DefaultRoles::new(
+            role_id(seed, g, "role:owner"),
+            role_id(seed, g, "role:admin"),
+            role_id(seed, g, "role:member"),
+        );
+
+        let inserted = sqlx::query(
+            "INSERT INTO guilds (id, name, owner_id) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+        )
+        .bind(guild)
+        .bind(format!("Seed Guild {g}"))
+        .bind(owner)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        report.guilds += inserted;
+        if inserted == 0 {
+            // Seeded by an earlier run
+            continue;
+        }
+
+        let moderator = role_id(seed, g, "role:moderator");
+        sqlx::query(
+            "INSERT INTO roles (id, guild_id, name, permissions, position, role_type) VALUES \
+             ($1, $2, 'owner', $3, 100, 'owner'), \
+             ($4, $2, 'admin', $5, 50, 'admin'), \
+             ($6, $2, 'moderator', $7, 10, 'custom'), \
+             ($8, $2, 'member', $9, 1, 'member')",
+        )
+        .bind(roles.owner.0)
+        .bind(guild)
+        .bind(roles.owner.1.bits() as i64)
+        .bind(roles.admin.0)
+        .bind(roles.admin.1.bits() as i64)
+        .bind(moderator)
+        .bind(
+            (roles.member.1 | Permissions::MANAGE_MESSAGES | Permissions::KICK_MEMBERS).bits()
+                as i64,
+        )
+        .bind(roles.member.0)
+        .bind(roles.member.1.bits() as i64)
+        .execute(&mut *conn)
+        .await?;
+
+        // Everyone joins as a member; the owner also gets the owner role and
+        // every fifth user is a moderator
+        let member_ids: Vec<uuid::Uuid> = users.iter().map(|u| u.0).collect();
+        sqlx::query(
+            "INSERT INTO guild_members (user_id, guild_id) \
+             SELECT user_id, $2 FROM UNNEST($1::uuid[]) AS m(user_id)",
+        )
+        .bind(&member_ids)
+        .bind(guild)
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query(
+            "INSERT INTO guild_member_roles (user_id, guild_id, role_id) \
+             SELECT user_id, $2, $3 FROM UNNEST($1::uuid[]) AS m(user_id)",
+        )
+        .bind(&member_ids)
+        .bind(guild)
+        .bind(roles.member.0)
+        .execute(&mut *conn)
+        .await?;
+        let moderators: Vec<uuid::Uuid> = member_ids.iter().copied().step_by(5).collect();
+        sqlx::query(
+            "INSERT INTO guild_member_roles (user_id, guild_id, role_id) \
+             SELECT user_id, $2, $3 FROM UNNEST($1::uuid[]) AS m(user_id)",
+        )
+        .bind(&moderators)
+        .bind(guild)
+        .bind(moderator)
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query(
+            "INSERT INTO guild_member_roles (user_id, guild_id, role_id) VALUES ($1, $2, $3) \
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(owner)
+        .bind(guild)
+        .bind(roles.owner.0)
+        .execute(&mut *conn)
+        .await?;
+
+        report.invites += sqlx::query(
+            "INSERT INTO guild_invites (code, guild_id, inviter_id) VALUES ($1, $2, $3) \
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(invite_code(seed, g))
+        .bind(guild)
+        .bind(owner)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+        for c in 0..options.channels_per_guild.max(1) {
+            let channel = channel_id(seed, g, c);
+            let name = if c == 0 {
+                "main".to_string()
+            } else {
+                format!("channel-{c}")
+            };
+            sqlx::query(
+                "INSERT INTO channels (id, guild_id, name, position) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(channel)
+            .bind(guild)
+            .bind(name)
+            .bind(c as i32)
+            .execute(&mut *conn)
+            .await?;
+            report.channels += 1;
+
+            report.messages += insert_messages(
+                &mut *conn,
+                seed,
+                channel,
+                &users,
+                options.messages_per_channel,
+            )
+            .await?;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Insert a channel's placeholder messages in one statement, senders taken
+/// round-robin.
+async fn insert_messages(
+    conn: &mut sqlx::PgConnection,
+    seed: u64,
+    channel: ChannelId,
+    users: &[UserId],
+    count: u32,
+) -> Result<u64, sqlx::Error> {
+    let mut ids = Vec::with_capacity(count as usize);
+    let mut senders = Vec::with_capacity(count as usize);
+    let mut contents = Vec::with_capacity(count as usize);
+    let mut nonces = Vec::with_capacity(count as usize);
+    let mut created = Vec::with_capacity(count as usize);
+    let kind = format!("message:{channel}");
+    for i in 0..count {
+        let id = MessageId(seeded_uuid(seed, &kind, i.into()));
+        let bytes = digest(seed, "payload", id.0.as_u64_pair().0);
+        ids.push(id.0);
+        senders.push(users[i as usize % users.len()].0);
+        contents.push(bytes.repeat(2));
+        nonces.push(bytes[..12].to_vec());
+        created.push(message_epoch() + Duration::minutes(i.into()));
+    }
+
+    let result = sqlx::query(
+        "INSERT INTO messages (id, channel_id, sender_id, encrypted_content, nonce, created_at) \
+         SELECT id, $2, sender_id, content, nonce, created_at \
+         FROM UNNEST($1::uuid[], $3::uuid[], $4::bytea[], $5::bytea[], $6::timestamptz[]) \
+             AS m(id, sender_id, content, nonce, created_at)",
+    )
+    .bind(&ids)
+    .bind(channel)
+    .bind(&senders)
+    .bind(&contents)
+    .bind(&nonces)
+    .bind(&created)
+    .execute(conn)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_stable_and_distinct() {
+        assert_eq!(user_id(0, 1), user_id(0, 1));
+        assert_ne!(user_id(0, 1), user_id(0, 2));
+        assert_ne!(user_id(0, 1), user_id(1, 1));
+        assert_ne!(channel_id(0, 0, 1), channel_id(0, 1, 0));
+        assert_eq!(user_id(7, 3).0.get_version_num(), 8);
+    }
+
+    #[test]
+    fn invite_codes_differ_per_seed() {
+        assert_ne!(invite_code(0, 1), invite_code(1, 1));
+        assert_eq!(invite_code(2, 3), "seed2g3");
+    }
+}
@@ -0,0 +1,179 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use sqlx::PgPool;
+
+use crate::email::outbox::{EmailKind, OutboxKey};
+use crate::email::EmailService;
+use crate::tasks::scheduler::{JobResult, PeriodicJob, Schedule};
+
+/// Emails claimed per run.
+const DELIVERY_BATCH_SIZE: i64 = 50;
+
+/// Attempts before an email is dead-lettered.
+pub const MAX_DELIVERY_ATTEMPTS: i32 = 8;
+
+/// How long a claimed email stays invisible to other workers. A worker that
+/// dies mid-send has its claims retried once this expires.
+const CLAIM_LEASE_SECS: f64 = 60.0;
+
+const BASE_BACKOFF_SECS: u64 = 5;
+const MAX_BACKOFF_SECS: u64 = 60;
+
+/// Send every due email in the outbox, opening queued codes with `key`.
+///
+/// Due rows are claimed with `FOR UPDATE SKIP LOCKED` and leased by pushing
+/// `next_attempt_at` forward, so several server instances can run this task
+/// concurrently. Failures are retried with exponential backoff. An email is
+/// dead-lettered after `MAX_DELIVERY_ATTEMPTS`, or once the code it carries
+/// has expired, since sending it then would only confuse the recipient.
+///
+/// Returns the number of emails sent.
+pub async fn deliver_pending_emails(
+    pool: &PgPool,
+    email: &dyn EmailService,
+    key: &OutboxKey,
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let expired = sqlx::query(
+        "UPDATE email_outbox \
+         SET status = 'dead', sealed_code = NULL, \
+             last_error = COALESCE(last_error, 'code expired') \
+         WHERE status = 'pending' AND expires_at <= NOW()",
+    )
+    .execute(pool)
+    .await?;
+    if expired.rows_affected() > 0 {
+        tracing::warn!(
+            count = expired.rows_affected(),
+            "dead-lettered emails whose codes expired before sending"
+        );
+    }
+
+    let due = sqlx::query_as::<_, DueEmail>(
+        "UPDATE email_outbox \
+         SET attempts = attempts + 1, \
+             next_attempt_at = NOW() + make_interval(secs => $2) \
+         WHERE id IN ( \
+             SELECT id FROM email_outbox \
+             WHERE status = 'pending' AND next_attempt_at <= NOW() \
+             ORDER BY next_attempt_at \
+             LIMIT $1 \
+             FOR UPDATE SKIP LOCKED \
+         ) \
+         RETURNING id, kind, recipient, sealed_code, attempts",
+    )
+    .bind(DELIVERY_BATCH_SIZE)
+    .bind(CLAIM_LEASE_SECS)
+    .fetch_all(pool)
+    .await?;
+
+    let mut sent = 0u64;
+    for due_email in due {
+        match send(email, key, &due_email).await {
+            Ok(()) => {
+                sqlx::query(
+                    "UPDATE email_outbox \
+                     SET status = 'sent', sent_at = NOW(), sealed_code = NULL, last_error = NULL \
+                     WHERE id = $1",
+                )
+                .bind(due_email.id)
+                .execute(pool)
+                .await?;
+                sent += 1;
+            }
+            Err(error) => {
+                tracing::warn!(email_id = %due_email.id, attempts = due_email.attempts, %error, "email delivery failed");
+                if due_email.attempts >= MAX_DELIVERY_ATTEMPTS {
+                    sqlx::query(
+                        "UPDATE email_outbox \
+                         SET status = 'dead', sealed_code = NULL, last_error = $2 \
+                         WHERE id = $1",
+                    )
+                    .bind(due_email.id)
+                    .bind(&error)
+                    .execute(pool)
+                    .await?;
+                } else {
+                    sqlx::query(
+                        "UPDATE email_outbox \
+                         SET next_attempt_at = NOW() + make_interval(secs => $2), last_error = $3 \
+                         WHERE id = $1",
+                    )
+                    .bind(due_email.id)
+                    .bind(retry_backoff(due_email.attempts).as_secs_f64())
+                    .bind(&error)
+                    .execute(pool)
+                    .await?;
+                }
+            }
+        }
+    }
+
+    Ok(sent)
+}
+
+#[derive(sqlx::FromRow)]
+struct DueEmail {
+    id: uuid::Uuid,
+    kind: String,
+    recipient: String,
+    sealed_code: Option<Vec<u8>>,
+    attempts: i32,
+}
+
+async fn send(email: &dyn EmailService, key: &OutboxKey, due: &DueEmail) -> Result<(), String> {
+    let kind =
+        EmailKind::parse(&due.kind).ok_or_else(|| format!("unknown email kind {}", due.kind))?;
+    let code = due
+        .sealed_code
+        .as_deref()
+        .and_then(|sealed| key.open(kind, &due.recipient, sealed))
+        .ok_or("queued code could not be opened")?;
+    match kind {
+        EmailKind::Verification => email.send_verification_code(&due.recipient, &code).await,
+        EmailKind::Recovery => email.send_recovery_code(&due.recipient, &code).await,
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// Delay before retrying after `attempts` failed attempts: 5s doubling up to 60s.
+fn retry_backoff(attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    let secs = BASE_BACKOFF_SECS.saturating_mul(1 << exponent);
+    Duration::from_secs(secs.min(MAX_BACKOFF_SECS))
+}
+
+/// [`deliver_pending_emails`] every 2 seconds, so codes arrive promptly.
+pub struct EmailDeliveryJob {
+    pub pool: PgPool,
+    pub email: Arc<dyn EmailService>,
+    pub key: OutboxKey,
+}
+
+#[async_trait::async_trait]
+impl PeriodicJob for EmailDeliveryJob {
+    fn name(&self) -> &'static str {
+        "email_delivery"
+    }
+
+    fn schedule(&self) -> Schedule {
+        Schedule::every_secs(2)
+    }
+
+    async fn run(&self) -> JobResult {
+        deliver_pending_emails(&self.pool, self.email.as_ref(), &self.key).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_backoff_doubles_and_caps() {
+        assert_eq!(retry_backoff(1), Duration::from_secs(5));
+        assert_eq!(retry_backoff(2), Duration::from_secs(10));
+        assert_eq!(retry_backoff(4), Duration::from_secs(40));
+        assert_eq!(retry_backoff(50), Duration::from_secs(MAX_BACKOFF_SECS));
+    }
+}
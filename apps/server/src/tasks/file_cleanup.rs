@@ -1,17 +1,121 @@
+use std::sync::Arc;
+
+use futures::TryStreamExt;
 use object_store::path::Path as StorePath;
 use object_store::ObjectStore;
+use openconv_shared::ids::UploadSessionId;
+
+use crate::tasks::scheduler::{JobResult, PeriodicJob, Schedule};
+
+/// Blobs released per cleanup run.
+const BLOB_CLEANUP_BATCH_SIZE: i64 = 1000;
+
+/// Object store location of one chunk of an upload session.
+pub fn session_chunk_path(session_id: UploadSessionId, index: i64) -> StorePath {
+    StorePath::from(format!("uploads/{session_id}/{index}"))
+}
+
+/// Object store location of a content-addressed blob.
+pub fn blob_path(digest: &str) -> StorePath {
+    StorePath::from(format!("blobs/{digest}"))
+}
+
+/// Object store location holding a file's bytes: its shared blob, or
+/// `storage_path` for files uploaded before deduplication.
+pub fn file_object_path(storage_path: &str, blob_digest: Option<&str>) -> StorePath {
+    match blob_digest {
+        Some(digest) => blob_path(digest),
+        None => StorePath::from(storage_path),
+    }
+}
+
+/// Delete the object a file owns outright. Blob-backed files own nothing:
+/// removing their row drops the blob's reference count and
+/// [`cleanup_unreferenced_blobs`] deletes the blob once nothing points at it.
+pub async fn delete_file_object(
+    store: &dyn ObjectStore,
+    storage_path: &str,
+    blob_digest: Option<&str>,
+) -> Result<(), object_store::Error> {
+    if blob_digest.is_some() {
+        return Ok(());
+    }
+    match store.delete(&StorePath::from(storage_path)).await {
+        Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Delete every stored chunk of an upload session.
+pub async fn delete_session_chunks(
+    store: &dyn ObjectStore,
+    session_id: UploadSessionId,
+) -> Result<(), object_store::Error> {
+    let prefix = StorePath::from(format!("uploads/{session_id}"));
+    let chunks: Vec<_> = store.list(Some(&prefix)).try_collect().await?;
+    for chunk in chunks {
+        match store.delete(&chunk.location).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Remove upload sessions that expired before being completed.
+///
+/// Deletes each session's chunks from the store, then the session records
+/// (chunk records cascade). Sessions whose chunks could not be deleted are
+/// kept and retried on the next run.
+pub async fn cleanup_expired_upload_sessions(
+    pool: &sqlx::PgPool,
+    store: &dyn ObjectStore,
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let expired: Vec<UploadSessionId> =
+        sqlx::query_scalar("SELECT id FROM upload_sessions WHERE expires_at < NOW()")
+            .fetch_all(pool)
+            .await?;
+
+    let mut ids_to_delete = Vec::new();
+    for session_id in expired {
+        match delete_session_chunks(store, session_id).await {
+            Ok(()) => ids_to_delete.push(session_id),
+            Err(e) => {
+                tracing::error!(
+                    error = %e,
+                    %session_id,
+                    "failed to delete expired upload session chunks"
+                );
+            }
+        }
+    }
+
+    if ids_to_delete.is_empty() {
+        return Ok(0);
+    }
+
+    let deleted = sqlx::query("DELETE FROM upload_sessions WHERE id = ANY($1)")
+        .bind(&ids_to_delete)
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+    Ok(deleted)
+}
 
 /// Remove orphaned files that were never linked to a message.
 ///
-/// Queries for files where `message_id IS NULL` and `created_at < NOW() - 24 hours`,
-/// deletes the storage objects, then deletes the DB records.
+/// Queries for files where `message_id IS NULL`, with no `attachments` row,
+/// and `created_at < NOW() - 24 hours`, deletes the storage objects, then
+/// deletes the DB records.
 pub async fn cleanup_orphan_files(
     pool: &sqlx::PgPool,
     store: &dyn ObjectStore,
 ) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
     let orphans = sqlx::query_as::<_, OrphanRow>(
-        "SELECT id, storage_path FROM files \
-         WHERE message_id IS NULL AND created_at < NOW() - INTERVAL '24 hours'",
+        "SELECT id, storage_path, blob_digest FROM files \
+         WHERE message_id IS NULL AND created_at < NOW() - INTERVAL '24 hours' \
+           AND NOT EXISTS (SELECT 1 FROM attachments a WHERE a.file_id = files.id)",
     )
     .fetch_all(pool)
     .await?;
@@ -23,22 +127,17 @@ pub async fn cleanup_orphan_files(
     let mut ids_to_delete = Vec::new();
 
     for orphan in &orphans {
-        let store_path = StorePath::from(orphan.storage_path.as_str());
-        match store.delete(&store_path).await {
-            Ok(()) => {}
-            Err(object_store::Error::NotFound { .. }) => {
-                // Already gone, still clean up DB record
-            }
+        match delete_file_object(store, &orphan.storage_path, orphan.blob_digest.as_deref()).await {
+            Ok(()) => ids_to_delete.push(orphan.id),
             Err(e) => {
                 tracing::error!(
                     error = %e,
                     file_id = %orphan.id,
                     "failed to delete orphan file from store"
                 );
-                continue; // Skip this one, try again next run
+                // Skip this one, try again next run
             }
         }
-        ids_to_delete.push(orphan.id);
     }
 
     if ids_to_delete.is_empty() {
@@ -54,14 +153,250 @@ pub async fn cleanup_orphan_files(
     Ok(deleted)
 }
 
+/// Remove files that outlived their guild's retention policy.
+///
+/// Files uploaded to a guild's channels expire `file_retention_days` after
+/// upload. Storage objects are deleted first; their attachment rows go with
+/// the file records. Files whose object could not be deleted are retried on
+/// the next run.
+pub async fn cleanup_expired_files(
+    pool: &sqlx::PgPool,
+    store: &dyn ObjectStore,
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let expired = sqlx::query_as::<_, OrphanRow>(
+        "SELECT f.id, f.storage_path, f.blob_digest FROM files f \
+         JOIN guilds g ON g.id = f.guild_id \
+         WHERE g.file_retention_days IS NOT NULL \
+           AND f.created_at < NOW() - make_interval(days => g.file_retention_days)",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut ids_to_delete = Vec::new();
+    for file in &expired {
+        match delete_file_object(store, &file.storage_path, file.blob_digest.as_deref()).await {
+            Ok(()) => ids_to_delete.push(file.id),
+            Err(e) => {
+                tracing::error!(
+                    error = %e,
+                    file_id = %file.id,
+                    "failed to delete expired file from store"
+                );
+            }
+        }
+    }
+
+    if ids_to_delete.is_empty() {
+        return Ok(0);
+    }
+
+    let deleted = sqlx::query("DELETE FROM files WHERE id = ANY($1)")
+        .bind(&ids_to_delete)
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+    Ok(deleted)
+}
+
+/// Delete blobs no file references any more.
+///
+/// The candidate rows stay locked until their objects are gone, so an
+/// upload of the same content waits and then stores the blob afresh
+/// instead of pointing at an object that is being deleted. Blobs whose
+/// object could not be deleted are retried on the next run.
+pub async fn cleanup_unreferenced_blobs(
+    pool: &sqlx::PgPool,
+    store: &dyn ObjectStore,
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let mut tx = pool.begin().await?;
+    let unreferenced: Vec<String> = sqlx::query_scalar(
+        "SELECT digest FROM blobs WHERE ref_count = 0 \
+         ORDER BY created_at LIMIT $1 FOR UPDATE SKIP LOCKED",
+    )
+    .bind(BLOB_CLEANUP_BATCH_SIZE)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut digests_to_delete = Vec::new();
+    for digest in unreferenced {
+        match store.delete(&blob_path(&digest)).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => digests_to_delete.push(digest),
+            Err(e) => {
+                tracing::error!(
+                    error = %e,
+                    %digest,
+                    "failed to delete unreferenced blob from store"
+                );
+            }
+        }
+    }
+
+    let deleted = sqlx::query("DELETE FROM blobs WHERE digest = ANY($1) AND ref_count = 0")
+        .bind(&digests_to_delete)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    tx.commit().await?;
+
+    Ok(deleted)
+}
+
 #[derive(sqlx::FromRow)]
 struct OrphanRow {
     id: uuid::Uuid,
     storage_path: String,
+    blob_digest: Option<String>,
+}
+
+/// [`cleanup_orphan_files`] on its configured schedule.
+pub struct OrphanFileCleanupJob {
+    pub pool: sqlx::PgPool,
+    pub store: Arc<dyn ObjectStore>,
+    pub schedule: Schedule,
+}
+
+#[async_trait::async_trait]
+impl PeriodicJob for OrphanFileCleanupJob {
+    fn name(&self) -> &'static str {
+        "orphan_file_cleanup"
+    }
+
+    fn schedule(&self) -> Schedule {
+        self.schedule.clone()
+    }
+
+    async fn run(&self) -> JobResult {
+        cleanup_orphan_files(&self.pool, &*self.store).await
+    }
+}
+
+/// [`cleanup_expired_upload_sessions`] on its configured schedule.
+pub struct UploadSessionCleanupJob {
+    pub pool: sqlx::PgPool,
+    pub store: Arc<dyn ObjectStore>,
+    pub schedule: Schedule,
+}
+
+#[async_trait::async_trait]
+impl PeriodicJob for UploadSessionCleanupJob {
+    fn name(&self) -> &'static str {
+        "upload_session_cleanup"
+    }
+
+    fn schedule(&self) -> Schedule {
+        self.schedule.clone()
+    }
+
+    async fn run(&self) -> JobResult {
+        cleanup_expired_upload_sessions(&self.pool, &*self.store).await
+    }
+}
+
+/// [`cleanup_expired_files`] on its configured schedule.
+pub struct ExpiredFileCleanupJob {
+    pub pool: sqlx::PgPool,
+    pub store: Arc<dyn ObjectStore>,
+    pub schedule: Schedule,
+}
+
+#[async_trait::async_trait]
+impl PeriodicJob for ExpiredFileCleanupJob {
+    fn name(&self) -> &'static str {
+        "expired_file_cleanup"
+    }
+
+    fn schedule(&self) -> Schedule {
+        self.schedule.clone()
+    }
+
+    async fn run(&self) -> JobResult {
+        cleanup_expired_files(&self.pool, &*self.store).await
+    }
+}
+
+/// [`cleanup_unreferenced_blobs`] on its configured schedule.
+pub struct BlobCleanupJob {
+    pub pool: sqlx::PgPool,
+    pub store: Arc<dyn ObjectStore>,
+    pub schedule: Schedule,
+}
+
+#[async_trait::async_trait]
+impl PeriodicJob for BlobCleanupJob {
+    fn name(&self) -> &'static str {
+        "blob_cleanup"
+    }
+
+    fn schedule(&self) -> Schedule {
+        self.schedule.clone()
+    }
+
+    async fn run(&self) -> JobResult {
+        cleanup_unreferenced_blobs(&self.pool, &*self.store).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use object_store::memory::InMemory;
+    use object_store::PutPayload;
+
+    #[tokio::test]
+    async fn delete_session_chunks_only_touches_that_session() {
+        let store = InMemory::new();
+        let session = UploadSessionId::new();
+        let other = UploadSessionId::new();
+        for index in 0..3 {
+            store
+                .put(&session_chunk_path(session, index), PutPayload::from("x"))
+                .await
+                .unwrap();
+        }
+        store
+            .put(&session_chunk_path(other, 0), PutPayload::from("y"))
+            .await
+            .unwrap();
+
+        delete_session_chunks(&store, session).await.unwrap();
+
+        assert!(store.get(&session_chunk_path(session, 0)).await.is_err());
+        assert!(store.get(&session_chunk_path(other, 0)).await.is_ok());
+    }
+
+    #[test]
+    fn file_object_path_prefers_blob() {
+        assert_eq!(
+            file_object_path("users/u/f", Some("ab12")).as_ref(),
+            "blobs/ab12"
+        );
+        assert_eq!(file_object_path("users/u/f", None).as_ref(), "users/u/f");
+    }
+
+    #[tokio::test]
+    async fn delete_file_object_leaves_shared_blobs() {
+        let store = InMemory::new();
+        store
+            .put(&blob_path("ab12"), PutPayload::from("x"))
+            .await
+            .unwrap();
+        store
+            .put(&StorePath::from("users/u/f"), PutPayload::from("y"))
+            .await
+            .unwrap();
+
+        delete_file_object(&store, "users/u/other", Some("ab12"))
+            .await
+            .unwrap();
+        delete_file_object(&store, "users/u/f", None).await.unwrap();
+        // Already gone is not an error
+        delete_file_object(&store, "users/u/f", None).await.unwrap();
+
+        assert!(store.get(&blob_path("ab12")).await.is_ok());
+        assert!(store.get(&StorePath::from("users/u/f")).await.is_err());
+    }
+
     #[test]
     fn cleanup_interval_is_24_hours() {
         // The SQL uses '24 hours' interval - verify this is the intended value
@@ -1,8 +1,12 @@
+use std::sync::Arc;
+
 use object_store::path::Path as StorePath;
 use object_store::ObjectStore;
 use openconv_shared::ids::GuildId;
 use sqlx::PgPool;
 
+use crate::tasks::scheduler::{JobResult, PeriodicJob, Schedule};
+
 /// Permanently delete guilds that have been soft-deleted for more than 7 days.
 ///
 /// For each expired guild:
@@ -28,9 +32,11 @@ pub async fn cleanup_expired_guilds(
     let mut deleted_count = 0u64;
 
     for guild_id in &expired_guilds {
-        // Prefix-based query: catches all files regardless of message_id state
+        // Prefix-based query: catches all files regardless of message_id state.
+        // Blob-backed files are released by their ref count instead.
         let storage_paths: Vec<String> = sqlx::query_scalar(
-            "SELECT storage_path FROM files WHERE storage_path LIKE 'guilds/' || $1::text || '/%'",
+            "SELECT storage_path FROM files \
+             WHERE storage_path LIKE 'guilds/' || $1::text || '/%' AND blob_digest IS NULL",
         )
         .bind(guild_id)
         .fetch_all(pool)
@@ -78,6 +84,28 @@ pub async fn cleanup_expired_guilds(
     Ok(deleted_count)
 }
 
+/// [`cleanup_expired_guilds`] on its configured schedule.
+pub struct GuildCleanupJob {
+    pub pool: PgPool,
+    pub store: Arc<dyn ObjectStore>,
+    pub schedule: Schedule,
+}
+
+#[async_trait::async_trait]
+impl PeriodicJob for GuildCleanupJob {
+    fn name(&self) -> &'static str {
+        "guild_cleanup"
+    }
+
+    fn schedule(&self) -> Schedule {
+        self.schedule.clone()
+    }
+
+    async fn run(&self) -> JobResult {
+        cleanup_expired_guilds(&self.pool, &*self.store).await
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -0,0 +1,201 @@
+use chrono::{DateTime, Utc};
+use openconv_shared::api::gateway::{MemberEvent, MemberEventKind};
+use openconv_shared::ids::{GuildId, UserId};
+use sqlx::PgPool;
+
+use crate::redis::RedisPool;
+use crate::tasks::scheduler::{JobResult, PeriodicJob, Schedule};
+use crate::{audit, member_events, permission_cache};
+
+/// Members removed per batch. Progress is updated after each batch.
+const PRUNE_BATCH_SIZE: i64 = 200;
+
+/// Members of guild `$1` considered inactive since `$2`: they joined before
+/// the cutoff, have sent no guild message since, hold no role besides the
+/// default member role, and are not the owner.
+const INACTIVE_MEMBERS_SQL: &str = "\
+    SELECT gm.user_id FROM guild_members gm \
+    JOIN guilds g ON g.id = gm.guild_id \
+    WHERE gm.guild_id = $1 \
+      AND gm.user_id <> g.owner_id \
+      AND gm.joined_at < $2 \
+      AND NOT EXISTS ( \
+          SELECT 1 FROM guild_member_roles gmr \
+          JOIN roles r ON r.id = gmr.role_id \
+          WHERE gmr.user_id = gm.user_id AND gmr.guild_id = gm.guild_id \
+            AND r.role_type <> 'member' \
+      ) \
+      AND NOT EXISTS ( \
+          SELECT 1 FROM messages m \
+          JOIN channels c ON c.id = m.channel_id \
+          WHERE m.sender_id = gm.user_id AND c.guild_id = gm.guild_id \
+            AND m.created_at >= $2 \
+      )";
+
+/// Count the members an inactive prune with this cutoff would remove.
+pub async fn count_inactive_members<'e, E>(
+    executor: E,
+    guild_id: GuildId,
+    inactive_since: DateTime<Utc>,
+) -> Result<i64, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_scalar(&format!(
+        "SELECT COUNT(*) FROM ({INACTIVE_MEMBERS_SQL}) AS inactive"
+    ))
+    .bind(guild_id)
+    .bind(inactive_since)
+    .fetch_one(executor)
+    .await
+}
+
+/// Run every pending inactive-member prune job.
+///
+/// Jobs are claimed with `FOR UPDATE SKIP LOCKED`, so several server
+/// instances can run this task concurrently. Members are removed in batches
+/// and the criteria are re-checked for each batch, so a member who becomes
+/// active while the job runs is kept. Each removal is queued for guild
+/// webhooks as a `member.kick` by the requesting moderator, and the
+/// guild's cached permissions are dropped once the job ends.
+///
+/// Returns the number of jobs processed.
+pub async fn run_pending_inactive_prunes(
+    pool: &PgPool,
+    redis: &RedisPool,
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let mut processed = 0u64;
+
+    while let Some(job) = claim_next_job(pool).await? {
+        let result = prune_inactive_members(pool, &job).await;
+        permission_cache::invalidate_guild(redis, job.guild_id).await;
+        match result {
+            Ok(removed) => {
+                sqlx::query(
+                    "UPDATE inactive_prune_jobs SET status = 'completed', completed_at = NOW() \
+                     WHERE id = $1",
+                )
+                .bind(job.id)
+                .execute(pool)
+                .await?;
+
+                audit::record(
+                    pool,
+                    job.guild_id,
+                    job.requested_by,
+                    audit::INACTIVE_PRUNE_COMPLETED,
+                    None,
+                    serde_json::json!({ "job_id": job.id, "members_removed": removed }),
+                )
+                .await?;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, job_id = %job.id, "inactive prune job failed");
+                sqlx::query(
+                    "UPDATE inactive_prune_jobs SET status = 'failed', error = $2, completed_at = NOW() \
+                     WHERE id = $1",
+                )
+                .bind(job.id)
+                .bind(e.to_string())
+                .execute(pool)
+                .await?;
+
+                audit::record(
+                    pool,
+                    job.guild_id,
+                    job.requested_by,
+                    audit::INACTIVE_PRUNE_FAILED,
+                    None,
+                    serde_json::json!({ "job_id": job.id }),
+                )
+                .await?;
+            }
+        }
+        processed += 1;
+    }
+
+    Ok(processed)
+}
+
+async fn claim_next_job(pool: &PgPool) -> Result<Option<PruneJobRow>, sqlx::Error> {
+    sqlx::query_as::<_, PruneJobRow>(
+        "UPDATE inactive_prune_jobs SET status = 'running', started_at = NOW() \
+         WHERE id = ( \
+             SELECT id FROM inactive_prune_jobs WHERE status = 'pending' \
+             ORDER BY created_at LIMIT 1 FOR UPDATE SKIP LOCKED \
+         ) \
+         RETURNING id, guild_id, requested_by, inactive_since",
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Returns the number of members removed.
+async fn prune_inactive_members(pool: &PgPool, job: &PruneJobRow) -> Result<u64, sqlx::Error> {
+    let mut removed_total = 0u64;
+
+    loop {
+        let removed: Vec<UserId> = sqlx::query_scalar(&format!(
+            "DELETE FROM guild_members \
+             WHERE guild_id = $1 AND user_id IN ({INACTIVE_MEMBERS_SQL} LIMIT $3) \
+             RETURNING user_id"
+        ))
+        .bind(job.guild_id)
+        .bind(job.inactive_since)
+        .bind(PRUNE_BATCH_SIZE)
+        .fetch_all(pool)
+        .await?;
+
+        if removed.is_empty() {
+            break;
+        }
+        removed_total += removed.len() as u64;
+
+        sqlx::query(
+            "UPDATE inactive_prune_jobs SET members_removed = members_removed + $2 WHERE id = $1",
+        )
+        .bind(job.id)
+        .bind(removed.len() as i64)
+        .execute(pool)
+        .await?;
+
+        for user_id in removed {
+            let mut event = MemberEvent::new(job.guild_id, user_id, MemberEventKind::Kick);
+            event.actor_id = job.requested_by;
+            if let Err(e) = member_events::enqueue_webhook_deliveries(pool, &event).await {
+                tracing::warn!(error = %e, job_id = %job.id, "failed to enqueue pruned member event");
+            }
+        }
+    }
+
+    Ok(removed_total)
+}
+
+#[derive(sqlx::FromRow)]
+struct PruneJobRow {
+    id: uuid::Uuid,
+    guild_id: GuildId,
+    requested_by: Option<UserId>,
+    inactive_since: DateTime<Utc>,
+}
+
+/// [`run_pending_inactive_prunes`] every minute.
+pub struct InactivePruneJob {
+    pub pool: PgPool,
+    pub redis: RedisPool,
+}
+
+#[async_trait::async_trait]
+impl PeriodicJob for InactivePruneJob {
+    fn name(&self) -> &'static str {
+        "inactive_member_prune"
+    }
+
+    fn schedule(&self) -> Schedule {
+        Schedule::every_secs(60)
+    }
+
+    async fn run(&self) -> JobResult {
+        run_pending_inactive_prunes(&self.pool, &self.redis).await
+    }
+}
@@ -0,0 +1,231 @@
+use std::sync::Arc;
+
+use object_store::ObjectStore;
+use openconv_shared::ids::{GuildId, UserId};
+use sqlx::PgPool;
+
+use crate::audit;
+use crate::tasks::file_cleanup::delete_file_object;
+use crate::tasks::scheduler::{JobResult, PeriodicJob, Schedule};
+
+/// Rows processed per batch. Progress counters are updated after each batch
+/// so moderators can watch a large prune advance.
+const PRUNE_BATCH_SIZE: i64 = 500;
+
+/// Run every pending member prune job.
+///
+/// Each job is claimed with `FOR UPDATE SKIP LOCKED`, so several server
+/// instances can run this task concurrently. For each job:
+/// 1. Delete the user's uploaded files in the guild from the object store and DB
+/// 2. Tombstone the user's messages in the guild's channels
+/// 3. Mark the job completed (or failed) and write an audit entry
+///
+/// Returns the number of jobs processed.
+pub async fn run_pending_prune_jobs(
+    pool: &PgPool,
+    store: &dyn ObjectStore,
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let mut processed = 0u64;
+
+    while let Some(job) = claim_next_job(pool).await? {
+        match prune_member(pool, store, &job).await {
+            Ok((messages, files)) => {
+                sqlx::query(
+                    "UPDATE member_prune_jobs SET status = 'completed', completed_at = NOW() \
+                     WHERE id = $1",
+                )
+                .bind(job.id)
+                .execute(pool)
+                .await?;
+
+                audit::record(
+                    pool,
+                    job.guild_id,
+                    job.requested_by,
+                    audit::MEMBER_PRUNE_COMPLETED,
+                    Some(job.user_id),
+                    serde_json::json!({
+                        "job_id": job.id,
+                        "messages_tombstoned": messages,
+                        "files_deleted": files,
+                    }),
+                )
+                .await?;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, job_id = %job.id, "member prune job failed");
+                sqlx::query(
+                    "UPDATE member_prune_jobs SET status = 'failed', error = $2, completed_at = NOW() \
+                     WHERE id = $1",
+                )
+                .bind(job.id)
+                .bind(e.to_string())
+                .execute(pool)
+                .await?;
+
+                audit::record(
+                    pool,
+                    job.guild_id,
+                    job.requested_by,
+                    audit::MEMBER_PRUNE_FAILED,
+                    Some(job.user_id),
+                    serde_json::json!({ "job_id": job.id }),
+                )
+                .await?;
+            }
+        }
+        processed += 1;
+    }
+
+    Ok(processed)
+}
+
+async fn claim_next_job(pool: &PgPool) -> Result<Option<PruneJob>, sqlx::Error> {
+    sqlx::query_as::<_, PruneJob>(
+        "UPDATE member_prune_jobs SET status = 'running', started_at = NOW() \
+         WHERE id = ( \
+             SELECT id FROM member_prune_jobs WHERE status = 'pending' \
+             ORDER BY created_at LIMIT 1 FOR UPDATE SKIP LOCKED \
+         ) \
+         RETURNING id, guild_id, user_id, requested_by",
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Returns `(messages_tombstoned, files_deleted)` for this run.
+async fn prune_member(
+    pool: &PgPool,
+    store: &dyn ObjectStore,
+    job: &PruneJob,
+) -> Result<(u64, u64), sqlx::Error> {
+    let mut files_deleted = 0u64;
+    // Files whose store delete failed; excluded from later batches so the loop terminates
+    let mut skipped: Vec<uuid::Uuid> = Vec::new();
+
+    loop {
+        let files = sqlx::query_as::<_, FileRow>(
+            "SELECT id, storage_path, blob_digest FROM files \
+             WHERE uploader_id = $1 \
+               AND storage_path LIKE 'guilds/' || $2::text || '/%' \
+               AND NOT (id = ANY($3)) \
+             LIMIT $4",
+        )
+        .bind(job.user_id)
+        .bind(job.guild_id)
+        .bind(&skipped)
+        .bind(PRUNE_BATCH_SIZE)
+        .fetch_all(pool)
+        .await?;
+
+        if files.is_empty() {
+            break;
+        }
+
+        let mut ids_to_delete = Vec::with_capacity(files.len());
+        for file in &files {
+            match delete_file_object(store, &file.storage_path, file.blob_digest.as_deref()).await {
+                Ok(()) => ids_to_delete.push(file.id),
+                Err(e) => {
+                    tracing::warn!(
+                        error = %e,
+                        file_id = %file.id,
+                        job_id = %job.id,
+                        "failed to delete pruned file from store, skipping"
+                    );
+                    skipped.push(file.id);
+                }
+            }
+        }
+
+        let deleted = sqlx::query("DELETE FROM files WHERE id = ANY($1)")
+            .bind(&ids_to_delete)
+            .execute(pool)
+            .await?
+            .rows_affected();
+        files_deleted += deleted;
+
+        sqlx::query(
+            "UPDATE member_prune_jobs SET files_deleted = files_deleted + $2 WHERE id = $1",
+        )
+        .bind(job.id)
+        .bind(deleted as i64)
+        .execute(pool)
+        .await?;
+    }
+
+    let mut messages_tombstoned = 0u64;
+    let empty: Vec<u8> = Vec::new();
+
+    loop {
+        // Same soft-delete with cryptographic erasure as a regular message delete
+        let tombstoned = sqlx::query(
+            "UPDATE messages SET deleted = true, encrypted_content = $3, nonce = $3 \
+             WHERE id IN ( \
+                 SELECT m.id FROM messages m \
+                 JOIN channels c ON c.id = m.channel_id \
+                 WHERE m.sender_id = $1 AND c.guild_id = $2 AND m.deleted = false \
+                 LIMIT $4 \
+             )",
+        )
+        .bind(job.user_id)
+        .bind(job.guild_id)
+        .bind(&empty)
+        .bind(PRUNE_BATCH_SIZE)
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        if tombstoned == 0 {
+            break;
+        }
+        messages_tombstoned += tombstoned;
+
+        sqlx::query(
+            "UPDATE member_prune_jobs SET messages_tombstoned = messages_tombstoned + $2 \
+             WHERE id = $1",
+        )
+        .bind(job.id)
+        .bind(tombstoned as i64)
+        .execute(pool)
+        .await?;
+    }
+
+    Ok((messages_tombstoned, files_deleted))
+}
+
+#[derive(sqlx::FromRow)]
+struct PruneJob {
+    id: uuid::Uuid,
+    guild_id: GuildId,
+    user_id: UserId,
+    requested_by: Option<UserId>,
+}
+
+#[derive(sqlx::FromRow)]
+struct FileRow {
+    id: uuid::Uuid,
+    storage_path: String,
+    blob_digest: Option<String>,
+}
+
+/// [`run_pending_prune_jobs`] every minute.
+pub struct MemberPruneJob {
+    pub pool: PgPool,
+    pub store: Arc<dyn ObjectStore>,
+}
+
+#[async_trait::async_trait]
+impl PeriodicJob for MemberPruneJob {
+    fn name(&self) -> &'static str {
+        "member_prune"
+    }
+
+    fn schedule(&self) -> Schedule {
+        Schedule::every_secs(60)
+    }
+
+    async fn run(&self) -> JobResult {
+        run_pending_prune_jobs(&self.pool, &*self.store).await
+    }
+}
@@ -0,0 +1,156 @@
+//! Per-guild message retention.
+//!
+//! Guild owners can set `message_retention_days`; messages in the guild's
+//! channels older than that are deleted in batches, one transaction per
+//! batch, so a large backlog never holds long locks on `messages`. Their
+//! attachments go with them, and files left without a message are picked up
+//! by the orphan file cleanup.
+//!
+//! Once a guild's purge deleted anything, its members are sent
+//! `MessagesPurged` so clients can drop the same messages from local caches.
+
+use openconv_shared::api::ws::ServerMessage;
+use openconv_shared::ids::GuildId;
+
+use crate::state::AppState;
+use crate::tasks::scheduler::{JobResult, PeriodicJob, Schedule};
+
+/// Messages deleted per transaction.
+pub const MESSAGE_RETENTION_BATCH_SIZE: i64 = 1000;
+
+/// Messages purged from one guild in a retention run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GuildPurge {
+    pub guild_id: GuildId,
+    pub messages: u64,
+    /// Every message sent before this was deleted.
+    pub before: chrono::DateTime<chrono::Utc>,
+}
+
+/// The oldest message a guild keeping `days` of history retains at `now`.
+pub fn retention_cutoff(
+    now: chrono::DateTime<chrono::Utc>,
+    days: i32,
+) -> chrono::DateTime<chrono::Utc> {
+    now - chrono::Duration::days(i64::from(days))
+}
+
+/// Purge expired messages from every guild with a retention policy and
+/// notify each affected guild's members.
+pub async fn purge_expired_messages(
+    state: &AppState,
+) -> Result<Vec<GuildPurge>, Box<dyn std::error::Error + Send + Sync>> {
+    let guilds: Vec<(GuildId, i32)> = sqlx::query_as(
+        "SELECT id, message_retention_days FROM guilds \
+         WHERE message_retention_days IS NOT NULL AND deleted_at IS NULL",
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let now = chrono::Utc::now();
+    let mut purges = Vec::new();
+    for (guild_id, days) in guilds {
+        let before = retention_cutoff(now, days);
+        let messages = purge_guild(&state.db, guild_id, before).await?;
+        if messages == 0 {
+            continue;
+        }
+        crate::ws::journal::publish_to_guild(
+            state,
+            guild_id,
+            ServerMessage::MessagesPurged { guild_id, before },
+        )
+        .await;
+        purges.push(GuildPurge {
+            guild_id,
+            messages,
+            before,
+        });
+    }
+    Ok(purges)
+}
+
+async fn purge_guild(
+    pool: &sqlx::PgPool,
+    guild_id: GuildId,
+    before: chrono::DateTime<chrono::Utc>,
+) -> Result<u64, sqlx::Error> {
+    let mut total = 0u64;
+    for batch in 1u32.. {
+        let mut tx = pool.begin().await?;
+        let deleted = sqlx::query(
+            "DELETE FROM messages WHERE id IN ( \
+                 SELECT m.id FROM messages m \
+                 JOIN channels c ON c.id = m.channel_id \
+                 WHERE c.guild_id = $1 AND m.created_at < $2 \
+                 ORDER BY m.created_at \
+                 LIMIT $3 FOR UPDATE OF m SKIP LOCKED)",
+        )
+        .bind(guild_id)
+        .bind(before)
+        .bind(MESSAGE_RETENTION_BATCH_SIZE)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        tx.commit().await?;
+
+        total += deleted;
+        if deleted > 0 {
+            tracing::info!(
+                %guild_id,
+                batch,
+                deleted,
+                total,
+                "Purged batch of expired messages"
+            );
+        }
+        if deleted < MESSAGE_RETENTION_BATCH_SIZE as u64 {
+            break;
+        }
+    }
+    Ok(total)
+}
+
+/// [`purge_expired_messages`] on its configured schedule.
+pub struct MessageRetentionJob {
+    pub state: AppState,
+    pub schedule: Schedule,
+}
+
+#[async_trait::async_trait]
+impl PeriodicJob for MessageRetentionJob {
+    fn name(&self) -> &'static str {
+        "message_retention"
+    }
+
+    fn schedule(&self) -> Schedule {
+        self.schedule.clone()
+    }
+
+    async fn run(&self) -> JobResult {
+        let purges = purge_expired_messages(&self.state).await?;
+        for purge in &purges {
+            tracing::info!(
+                guild_id = %purge.guild_id,
+                messages = purge.messages,
+                before = %purge.before,
+                "Purged expired guild messages"
+            );
+        }
+        Ok(purges.iter().map(|purge| purge.messages).sum())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cutoff_is_whole_days_before_now() {
+        let now = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        assert_eq!(
+            retention_cutoff(now, 30),
+            chrono::DateTime::from_timestamp(1_700_000_000 - 30 * 86400, 0).unwrap()
+        );
+    }
+}
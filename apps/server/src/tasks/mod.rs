@@ -1,3 +1,13 @@
 pub mod cleanup;
+pub mod config_watch;
+pub mod dev_seed;
+pub mod email_delivery;
 pub mod file_cleanup;
 pub mod guild_cleanup;
+pub mod inactive_prune;
+pub mod log_retention;
+pub mod member_prune;
+pub mod message_retention;
+pub mod scheduler;
+pub mod upload_scan;
+pub mod webhook_delivery;
@@ -0,0 +1,429 @@
+//! Periodic background jobs.
+//!
+//! Each [`PeriodicJob`] runs on its own loop with jitter, stops when the
+//! server shuts down, and records its runs in a [`JobRegistry`]. With a
+//! Redis lock configured, a run only starts on the node that takes the
+//! job's lock, so a multi-node deployment runs each job once per period
+//! instead of once per node.
+
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use openconv_shared::api::admin::JobStatusResponse;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+use crate::error_reporting;
+use crate::redis::RedisPool;
+
+/// Result of one run: how many items it processed.
+pub type JobResult = Result<u64, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Upper bound on the random delay added to each run.
+const MAX_JITTER: Duration = Duration::from_secs(30);
+
+/// Lock keys expire this fraction of a period after being taken, so the
+/// node that ran last does not hold the next period's lock if its timer
+/// fires slightly later than another node's.
+const LOCK_TTL_FRACTION: f64 = 0.9;
+
+/// Cap on the lock TTL for jobs with very long or open-ended periods.
+const MAX_LOCK_TTL_MS: f64 = 7.0 * 24.0 * 3600.0 * 1000.0;
+
+/// A job run on a schedule for as long as the server is up.
+#[async_trait::async_trait]
+pub trait PeriodicJob: Send + Sync + 'static {
+    /// Stable name used in logs, metrics and the lock key.
+    fn name(&self) -> &'static str;
+
+    fn schedule(&self) -> Schedule;
+
+    /// Run on every node, skipping the Redis lock, for jobs that act on
+    /// this process's own state. Default: false
+    fn per_node(&self) -> bool {
+        false
+    }
+
+    async fn run(&self) -> JobResult;
+}
+
+/// When a job runs.
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// At startup, then once per interval.
+    Every(Duration),
+    /// At the times matched by a cron expression (with seconds), in UTC.
+    Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+    pub fn every_secs(secs: u64) -> Self {
+        Self::Every(Duration::from_secs(secs))
+    }
+
+    /// Parse a cron expression such as `"0 0 3 * * *"` (daily at 03:00 UTC).
+    pub fn cron(expression: &str) -> Result<Self, cron::error::Error> {
+        Ok(Self::Cron(Box::new(cron::Schedule::from_str(expression)?)))
+    }
+
+    /// Time from `now` until the next run, before jitter.
+    fn next_delay(&self, now: DateTime<Utc>) -> Duration {
+        match self {
+            Self::Every(interval) => *interval,
+            Self::Cron(schedule) => schedule
+                .after(&now)
+                .next()
+                .and_then(|next| (next - now).to_std().ok())
+                .unwrap_or(Duration::MAX),
+        }
+    }
+
+    /// Typical gap between runs, which sizes the jitter and lock TTL.
+    fn period(&self, now: DateTime<Utc>) -> Duration {
+        match self {
+            Self::Every(interval) => *interval,
+            Self::Cron(schedule) => {
+                let mut upcoming = schedule.after(&now);
+                match (upcoming.next(), upcoming.next()) {
+                    (Some(first), Some(second)) => {
+                        (second - first).to_std().unwrap_or(Duration::MAX)
+                    }
+                    _ => Duration::MAX,
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Every(interval) => write!(f, "every {}s", interval.as_secs()),
+            Self::Cron(schedule) => write!(f, "cron {schedule}"),
+        }
+    }
+}
+
+fn jitter(period: Duration) -> Duration {
+    let max = (period / 10).min(MAX_JITTER).as_millis() as u64;
+    Duration::from_millis(rand::random_range(0..=max))
+}
+
+/// Run statistics for this node's jobs, exposed to instance admins.
+#[derive(Default)]
+pub struct JobRegistry {
+    jobs: DashMap<&'static str, JobStatusResponse>,
+}
+
+impl JobRegistry {
+    fn register(&self, name: &'static str, schedule: &Schedule) {
+        self.jobs.insert(
+            name,
+            JobStatusResponse {
+                name: name.to_string(),
+                schedule: schedule.to_string(),
+                running: false,
+                runs: 0,
+                failures: 0,
+                skipped: 0,
+                last_started_at: None,
+                last_duration_ms: None,
+                last_processed: None,
+                last_error: None,
+                next_run_at: None,
+            },
+        );
+    }
+
+    fn update(&self, name: &'static str, f: impl FnOnce(&mut JobStatusResponse)) {
+        if let Some(mut status) = self.jobs.get_mut(name) {
+            f(&mut status);
+        }
+    }
+
+    /// Status of every registered job, sorted by name.
+    pub fn snapshot(&self) -> Vec<JobStatusResponse> {
+        let mut jobs: Vec<_> = self.jobs.iter().map(|entry| entry.clone()).collect();
+        jobs.sort_by(|a, b| a.name.cmp(&b.name));
+        jobs
+    }
+}
+
+/// Collects jobs and runs them until shutdown.
+pub struct Scheduler {
+    jobs: Vec<Arc<dyn PeriodicJob>>,
+    registry: Arc<JobRegistry>,
+    lock: Option<RedisPool>,
+}
+
+impl Scheduler {
+    pub fn new(registry: Arc<JobRegistry>) -> Self {
+        Self {
+            jobs: Vec::new(),
+            registry,
+            lock: None,
+        }
+    }
+
+    /// Take a Redis lock before each run so only one node runs it.
+    pub fn with_redis_lock(mut self, redis: RedisPool) -> Self {
+        self.lock = Some(redis);
+        self
+    }
+
+    pub fn add(&mut self, job: impl PeriodicJob) {
+        self.jobs.push(Arc::new(job));
+    }
+
+    /// Start every job. Each loop exits when `shutdown` changes; an
+    /// in-progress run is allowed to finish first.
+    pub fn spawn(self, shutdown: watch::Receiver<bool>) -> SchedulerHandle {
+        let mut tasks = JoinSet::new();
+        for job in self.jobs {
+            self.registry.register(job.name(), &job.schedule());
+            tasks.spawn(run_loop(
+                job,
+                self.registry.clone(),
+                self.lock.clone(),
+                shutdown.clone(),
+            ));
+        }
+        SchedulerHandle { tasks }
+    }
+}
+
+/// Running jobs, returned by [`Scheduler::spawn`].
+pub struct SchedulerHandle {
+    tasks: JoinSet<()>,
+}
+
+impl SchedulerHandle {
+    /// Wait for every job loop to exit after shutdown was signalled.
+    pub async fn join(mut self) {
+        while self.tasks.join_next().await.is_some() {}
+    }
+}
+
+async fn run_loop(
+    job: Arc<dyn PeriodicJob>,
+    registry: Arc<JobRegistry>,
+    lock: Option<RedisPool>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let name = job.name();
+    let schedule = job.schedule();
+
+    // Interval jobs run at startup; jitter spreads nodes that boot together
+    let now = Utc::now();
+    let mut delay = match schedule {
+        Schedule::Every(_) => Duration::ZERO,
+        Schedule::Cron(_) => schedule.next_delay(now),
+    }
+    .saturating_add(jitter(schedule.period(now)));
+    let next_run = record_next_run(&registry, name, now, delay);
+    tracing::info!(job = name, %schedule, next_run = ?next_run, "job scheduled");
+
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = shutdown.changed() => break,
+        }
+
+        let now = Utc::now();
+        let period = schedule.period(now);
+        let acquired = match &lock {
+            Some(redis) if !job.per_node() => acquire_lock(redis, name, period).await,
+            _ => true,
+        };
+        if acquired {
+            run_once(job.as_ref(), &registry, now).await;
+        } else {
+            tracing::debug!(job = name, "job lock held by another node, skipping run");
+            registry.update(name, |status| status.skipped += 1);
+        }
+
+        let now = Utc::now();
+        delay = schedule.next_delay(now).saturating_add(jitter(period));
+        record_next_run(&registry, name, now, delay);
+    }
+    tracing::info!(job = name, "job shutting down");
+}
+
+fn record_next_run(
+    registry: &JobRegistry,
+    name: &'static str,
+    now: DateTime<Utc>,
+    delay: Duration,
+) -> Option<DateTime<Utc>> {
+    let next_run = chrono::Duration::from_std(delay)
+        .ok()
+        .and_then(|delay| now.checked_add_signed(delay));
+    registry.update(name, |status| status.next_run_at = next_run);
+    next_run
+}
+
+async fn run_once(job: &dyn PeriodicJob, registry: &JobRegistry, now: DateTime<Utc>) {
+    let name = job.name();
+    registry.update(name, |status| {
+        status.running = true;
+        status.last_started_at = Some(now);
+    });
+
+    let started = Instant::now();
+    let result = job.run().await;
+    let duration_ms = started.elapsed().as_millis() as u64;
+
+    match &result {
+        Ok(count) => {
+            if *count > 0 {
+                tracing::info!(job = name, count, duration_ms, "job completed");
+            }
+        }
+        Err(e) => {
+            tracing::error!(job = name, error = %e, "job failed");
+            error_reporting::capture_task_failure(name, e);
+        }
+    }
+    registry.update(name, |status| {
+        status.running = false;
+        status.runs += 1;
+        status.last_duration_ms = Some(duration_ms);
+        match result {
+            Ok(count) => {
+                status.last_processed = Some(count);
+                status.last_error = None;
+            }
+            Err(e) => {
+                status.failures += 1;
+                status.last_error = Some(e.to_string());
+            }
+        }
+    });
+}
+
+/// Take the job's lock for most of a period. The lock is not released
+/// after the run; expiring is what opens the next period. Fails open when
+/// Redis is unreachable, as the rate limiter does: jobs claim their work
+/// with row locks, so an occasional concurrent run is safe.
+async fn acquire_lock(redis: &RedisPool, name: &str, period: Duration) -> bool {
+    use fred::interfaces::{ClientLike, KeysInterface};
+    use fred::types::{Expiration, SetOptions};
+
+    if !redis.is_connected() {
+        tracing::warn!(job = name, "job lock: Redis not connected, running anyway");
+        return true;
+    }
+
+    let ttl_ms =
+        (period.as_millis() as f64 * LOCK_TTL_FRACTION).clamp(1000.0, MAX_LOCK_TTL_MS) as i64;
+    let token = uuid::Uuid::new_v4().to_string();
+    match redis
+        .set::<Option<String>, _, _>(
+            redis.key(format_args!("job:lock:{name}")),
+            token.as_str(),
+            Some(Expiration::PX(ttl_ms)),
+            Some(SetOptions::NX),
+            false,
+        )
+        .await
+    {
+        Ok(reply) => reply.is_some(),
+        Err(e) => {
+            tracing::warn!(job = name, error = %e, "job lock: Redis set failed, running anyway");
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    struct CountingJob {
+        runs: Arc<AtomicU64>,
+        fail: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl PeriodicJob for CountingJob {
+        fn name(&self) -> &'static str {
+            if self.fail {
+                "failing"
+            } else {
+                "counting"
+            }
+        }
+
+        fn schedule(&self) -> Schedule {
+            Schedule::Every(Duration::from_millis(10))
+        }
+
+        async fn run(&self) -> JobResult {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            if self.fail {
+                Err("boom".into())
+            } else {
+                Ok(2)
+            }
+        }
+    }
+
+    #[test]
+    fn cron_schedule_waits_for_next_match() {
+        let schedule = Schedule::cron("0 0 3 * * *").unwrap();
+        let now = DateTime::parse_from_rfc3339("2024-01-01T02:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(schedule.next_delay(now), Duration::from_secs(3600));
+        assert_eq!(schedule.period(now), Duration::from_secs(86_400));
+        assert!(Schedule::cron("not a cron line").is_err());
+    }
+
+    #[test]
+    fn jitter_is_bounded() {
+        for _ in 0..100 {
+            assert!(jitter(Duration::from_secs(5)) <= Duration::from_millis(500));
+            assert!(jitter(Duration::from_secs(86_400)) <= MAX_JITTER);
+        }
+    }
+
+    #[tokio::test]
+    async fn jobs_run_record_metrics_and_stop_on_shutdown() {
+        let runs = Arc::new(AtomicU64::new(0));
+        let failed_runs = Arc::new(AtomicU64::new(0));
+        let registry = Arc::new(JobRegistry::default());
+        let mut scheduler = Scheduler::new(registry.clone());
+        scheduler.add(CountingJob {
+            runs: runs.clone(),
+            fail: false,
+        });
+        scheduler.add(CountingJob {
+            runs: failed_runs.clone(),
+            fail: true,
+        });
+
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let handle = scheduler.spawn(shutdown_rx);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        shutdown_tx.send(true).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), handle.join())
+            .await
+            .expect("jobs stop after shutdown");
+
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        let (counting, failing) = (&snapshot[0], &snapshot[1]);
+        assert_eq!(counting.name, "counting");
+        assert!(counting.runs >= 2);
+        assert_eq!(counting.runs, runs.load(Ordering::SeqCst));
+        assert_eq!(counting.last_processed, Some(2));
+        assert_eq!(counting.failures, 0);
+        assert!(counting.next_run_at.is_some());
+        assert_eq!(failing.failures, failed_runs.load(Ordering::SeqCst));
+        assert_eq!(failing.last_error.as_deref(), Some("boom"));
+    }
+}
@@ -0,0 +1,157 @@
+use std::sync::Arc;
+
+use object_store::ObjectStore;
+use sqlx::PgPool;
+
+use crate::scan::{ScanVerdict, UploadScanner};
+use crate::tasks::file_cleanup::file_object_path;
+use crate::tasks::scheduler::{JobResult, PeriodicJob, Schedule};
+
+/// Pending files claimed per run.
+const SCAN_BATCH_SIZE: i64 = 50;
+
+/// A claimed file is not claimed again for this long, so a scan that
+/// failed or whose worker died is retried without piling up.
+const SCAN_RETRY_SECONDS: i64 = 300;
+
+/// Scan pending uploads.
+///
+/// Files are claimed with `FOR UPDATE SKIP LOCKED` and stamped with the
+/// attempt time, so several server instances and the post-upload scan can
+/// run side by side without scanning a file twice. Returns the number of
+/// files that received a verdict.
+pub async fn scan_pending_files(
+    pool: &PgPool,
+    store: &dyn ObjectStore,
+    scanner: &dyn UploadScanner,
+) -> Result<u64, sqlx::Error> {
+    let claimed = claim(pool, None).await?;
+    let mut scanned = 0u64;
+    for file in &claimed {
+        if scan_claimed(pool, store, scanner, file).await? {
+            scanned += 1;
+        }
+    }
+    Ok(scanned)
+}
+
+/// Scan one freshly uploaded file. Called right after upload so clean
+/// files become downloadable without waiting for the periodic task.
+pub async fn scan_file(
+    pool: &PgPool,
+    store: &dyn ObjectStore,
+    scanner: &dyn UploadScanner,
+    file_id: uuid::Uuid,
+) -> Result<(), sqlx::Error> {
+    if let Some(file) = claim(pool, Some(file_id)).await?.first() {
+        scan_claimed(pool, store, scanner, file).await?;
+    }
+    Ok(())
+}
+
+async fn claim(
+    pool: &PgPool,
+    file_id: Option<uuid::Uuid>,
+) -> Result<Vec<PendingFile>, sqlx::Error> {
+    sqlx::query_as::<_, PendingFile>(
+        "UPDATE files SET scan_attempted_at = NOW() \
+         WHERE id IN ( \
+             SELECT id FROM files \
+             WHERE scan_status = 'pending' \
+               AND ($1::UUID IS NULL OR id = $1) \
+               AND (scan_attempted_at IS NULL \
+                    OR scan_attempted_at < NOW() - make_interval(secs => $2)) \
+             ORDER BY scan_attempted_at NULLS FIRST \
+             LIMIT $3 FOR UPDATE SKIP LOCKED \
+         ) \
+         RETURNING id, storage_path, blob_digest",
+    )
+    .bind(file_id)
+    .bind(SCAN_RETRY_SECONDS as f64)
+    .bind(SCAN_BATCH_SIZE)
+    .fetch_all(pool)
+    .await
+}
+
+/// Returns whether the file received a verdict. Scanner failures are
+/// logged and leave the file pending for a later retry.
+async fn scan_claimed(
+    pool: &PgPool,
+    store: &dyn ObjectStore,
+    scanner: &dyn UploadScanner,
+    file: &PendingFile,
+) -> Result<bool, sqlx::Error> {
+    // Deduplicated uploads share bytes, so an earlier verdict on the same
+    // blob applies as-is
+    let previous: Option<(String, Option<String>)> = match &file.blob_digest {
+        Some(digest) => {
+            sqlx::query_as(
+                "SELECT scan_status, scan_detail FROM files \
+                 WHERE blob_digest = $1 AND scan_status <> 'pending' LIMIT 1",
+            )
+            .bind(digest)
+            .fetch_optional(pool)
+            .await?
+        }
+        None => None,
+    };
+
+    let (status, detail) = match previous {
+        Some(previous) => previous,
+        None => {
+            let location = file_object_path(&file.storage_path, file.blob_digest.as_deref());
+            match scanner.scan(store, &location).await {
+                Ok(ScanVerdict::Clean) => ("clean".to_string(), None),
+                Ok(ScanVerdict::Flagged(reason)) => {
+                    tracing::warn!(file_id = %file.id, %reason, "upload flagged by scanner");
+                    ("flagged".to_string(), Some(reason))
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, file_id = %file.id, "upload scan failed");
+                    return Ok(false);
+                }
+            }
+        }
+    };
+
+    sqlx::query(
+        "UPDATE files SET scan_status = $2, scan_detail = $3, scanned_at = NOW() \
+         WHERE id = $1",
+    )
+    .bind(file.id)
+    .bind(&status)
+    .bind(&detail)
+    .execute(pool)
+    .await?;
+    Ok(true)
+}
+
+#[derive(sqlx::FromRow)]
+struct PendingFile {
+    id: uuid::Uuid,
+    storage_path: String,
+    blob_digest: Option<String>,
+}
+
+/// [`scan_pending_files`] every 30 seconds, catching uploads whose
+/// post-upload scan failed.
+pub struct UploadScanJob {
+    pub pool: PgPool,
+    pub store: Arc<dyn ObjectStore>,
+    pub scanner: Arc<dyn UploadScanner>,
+}
+
+#[async_trait::async_trait]
+impl PeriodicJob for UploadScanJob {
+    fn name(&self) -> &'static str {
+        "upload_scan"
+    }
+
+    fn schedule(&self) -> Schedule {
+        Schedule::every_secs(30)
+    }
+
+    async fn run(&self) -> JobResult {
+        Ok(scan_pending_files(&self.pool, &*self.store, &*self.scanner).await?)
+    }
+}
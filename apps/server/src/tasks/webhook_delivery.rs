@@ -0,0 +1,195 @@
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::PgPool;
+
+use crate::tasks::scheduler::{JobResult, PeriodicJob, Schedule};
+
+/// Deliveries claimed per run.
+const DELIVERY_BATCH_SIZE: i64 = 50;
+
+/// Attempts before a delivery is marked failed.
+pub const MAX_DELIVERY_ATTEMPTS: i32 = 8;
+
+/// How long a claimed delivery stays invisible to other workers. A worker
+/// that dies mid-delivery has its claims retried once this expires.
+const CLAIM_LEASE_SECS: f64 = 300.0;
+
+/// Per-request timeout for webhook endpoints.
+pub const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+const BASE_BACKOFF_SECS: u64 = 30;
+const MAX_BACKOFF_SECS: u64 = 6 * 60 * 60;
+
+/// Deliver every due webhook event.
+///
+/// Due rows are claimed with `FOR UPDATE SKIP LOCKED` and leased by pushing
+/// `next_attempt_at` forward, so several server instances can run this task
+/// concurrently. Each delivery is POSTed with:
+/// - `X-OpenConv-Event`: the event name, e.g. `member.join`
+/// - `X-OpenConv-Delivery`: the delivery ID, stable across retries
+/// - `X-OpenConv-Signature`: `sha256=<hex HMAC-SHA256 of the body>`
+///
+/// Non-2xx responses and transport errors are retried with exponential
+/// backoff until `MAX_DELIVERY_ATTEMPTS` is reached.
+///
+/// Returns the number of deliveries that succeeded.
+pub async fn deliver_pending_webhooks(
+    pool: &PgPool,
+    client: &reqwest::Client,
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let due = sqlx::query_as::<_, DueDelivery>(
+        "UPDATE webhook_deliveries d \
+         SET attempts = d.attempts + 1, \
+             next_attempt_at = NOW() + make_interval(secs => $2) \
+         FROM guild_webhooks w \
+         WHERE w.id = d.webhook_id AND d.id IN ( \
+             SELECT id FROM webhook_deliveries \
+             WHERE status = 'pending' AND next_attempt_at <= NOW() \
+             ORDER BY next_attempt_at \
+             LIMIT $1 \
+             FOR UPDATE SKIP LOCKED \
+         ) \
+         RETURNING d.id, d.event_type, d.payload, d.attempts, w.url, w.secret",
+    )
+    .bind(DELIVERY_BATCH_SIZE)
+    .bind(CLAIM_LEASE_SECS)
+    .fetch_all(pool)
+    .await?;
+
+    let mut delivered = 0u64;
+    for delivery in due {
+        match send(client, &delivery).await {
+            Ok(()) => {
+                sqlx::query(
+                    "UPDATE webhook_deliveries \
+                     SET status = 'delivered', delivered_at = NOW(), last_error = NULL \
+                     WHERE id = $1",
+                )
+                .bind(delivery.id)
+                .execute(pool)
+                .await?;
+                delivered += 1;
+            }
+            Err(error) => {
+                tracing::warn!(delivery_id = %delivery.id, attempts = delivery.attempts, %error, "webhook delivery failed");
+                if delivery.attempts >= MAX_DELIVERY_ATTEMPTS {
+                    sqlx::query(
+                        "UPDATE webhook_deliveries SET status = 'failed', last_error = $2 \
+                         WHERE id = $1",
+                    )
+                    .bind(delivery.id)
+                    .bind(&error)
+                    .execute(pool)
+                    .await?;
+                } else {
+                    sqlx::query(
+                        "UPDATE webhook_deliveries \
+                         SET next_attempt_at = NOW() + make_interval(secs => $2), last_error = $3 \
+                         WHERE id = $1",
+                    )
+                    .bind(delivery.id)
+                    .bind(retry_backoff(delivery.attempts).as_secs_f64())
+                    .bind(&error)
+                    .execute(pool)
+                    .await?;
+                }
+            }
+        }
+    }
+
+    Ok(delivered)
+}
+
+#[derive(sqlx::FromRow)]
+struct DueDelivery {
+    id: uuid::Uuid,
+    event_type: String,
+    payload: serde_json::Value,
+    attempts: i32,
+    url: String,
+    secret: String,
+}
+
+async fn send(client: &reqwest::Client, delivery: &DueDelivery) -> Result<(), String> {
+    let body = serde_json::to_vec(&delivery.payload).map_err(|e| e.to_string())?;
+    let signature = sign_payload(&delivery.secret, &body);
+
+    let response = client
+        .post(&delivery.url)
+        .timeout(DELIVERY_TIMEOUT)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-OpenConv-Event", &delivery.event_type)
+        .header("X-OpenConv-Delivery", delivery.id.to_string())
+        .header("X-OpenConv-Signature", signature)
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("endpoint responded with {}", response.status()))
+    }
+}
+
+/// Signature header value for `body`: `sha256=<hex HMAC-SHA256>`.
+pub fn sign_payload(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Delay before retrying after `attempts` failed attempts: 30s doubling up to 6h.
+pub(crate) fn retry_backoff(attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    let secs = BASE_BACKOFF_SECS.saturating_mul(1 << exponent);
+    Duration::from_secs(secs.min(MAX_BACKOFF_SECS))
+}
+
+/// [`deliver_pending_webhooks`] every 5 seconds.
+pub struct WebhookDeliveryJob {
+    pub pool: PgPool,
+    pub client: reqwest::Client,
+}
+
+#[async_trait::async_trait]
+impl PeriodicJob for WebhookDeliveryJob {
+    fn name(&self) -> &'static str {
+        "webhook_delivery"
+    }
+
+    fn schedule(&self) -> Schedule {
+        Schedule::every_secs(5)
+    }
+
+    async fn run(&self) -> JobResult {
+        deliver_pending_webhooks(&self.pool, &self.client).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_payload_matches_known_vector() {
+        // RFC 4231 test case 2.
+        let sig = sign_payload("Jefe", b"what do ya want for nothing?");
+        assert_eq!(
+            sig,
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn retry_backoff_doubles_and_caps() {
+        assert_eq!(retry_backoff(1), Duration::from_secs(30));
+        assert_eq!(retry_backoff(2), Duration::from_secs(60));
+        assert_eq!(retry_backoff(4), Duration::from_secs(240));
+        assert_eq!(retry_backoff(50), Duration::from_secs(MAX_BACKOFF_SECS));
+    }
+}
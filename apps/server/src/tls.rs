@@ -0,0 +1,180 @@
+//! Native TLS termination, for small deployments without a reverse proxy.
+//!
+//! Certificates come from PEM files, which are reloaded in place when they
+//! change, or from an ACME provider such as Let's Encrypt. Either way
+//! HTTP/2 and HTTP/1.1 are offered over ALPN.
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use axum_server::tls_rustls::RustlsConfig;
+use futures::StreamExt;
+use rustls_acme::acme::ACME_TLS_ALPN_NAME;
+use rustls_acme::axum::AxumAcceptor;
+use rustls_acme::caches::DirCache;
+use rustls_acme::AcmeConfig;
+
+use crate::config::{TlsConfig, TlsMode};
+use crate::error_reporting;
+use crate::live_config::{file_stamp, FileStamp};
+use crate::tasks::scheduler::{JobResult, PeriodicJob, Schedule};
+
+/// A ready TLS setup, from [`setup`].
+#[derive(Clone)]
+pub enum TlsServer {
+    Files(RustlsConfig),
+    Acme(AxumAcceptor),
+}
+
+/// Load certificates or start the ACME client. Returns `None` when TLS is
+/// off. Call once at startup.
+pub async fn setup(config: &TlsConfig) -> Result<Option<TlsServer>, String> {
+    // Both ring and aws-lc-rs may be compiled in through dependencies, so
+    // rustls cannot pick a provider on its own
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    match config.mode {
+        TlsMode::Off => Ok(None),
+        TlsMode::Files => {
+            let rustls = RustlsConfig::from_pem_file(&config.cert_path, &config.key_path)
+                .await
+                .map_err(|e| format!("cannot load TLS certificate: {e}"))?;
+            Ok(Some(TlsServer::Files(rustls)))
+        }
+        TlsMode::Acme => {
+            let mut state = AcmeConfig::new(config.acme_domains.clone())
+                .contact(
+                    config
+                        .acme_contact_email
+                        .iter()
+                        .map(|email| format!("mailto:{email}")),
+                )
+                .cache(DirCache::new(PathBuf::from(&config.acme_cache_dir)))
+                .directory_lets_encrypt(!config.acme_staging)
+                .state();
+
+            let mut rustls = rustls::ServerConfig::builder()
+                .with_no_client_auth()
+                .with_cert_resolver(state.resolver());
+            rustls.alpn_protocols = vec![
+                b"h2".to_vec(),
+                b"http/1.1".to_vec(),
+                ACME_TLS_ALPN_NAME.to_vec(),
+            ];
+            let acceptor = state.axum_acceptor(Arc::new(rustls));
+
+            // Drives issuance and renewal for as long as the server runs
+            tokio::spawn(async move {
+                while let Some(event) = state.next().await {
+                    match event {
+                        Ok(event) => tracing::info!(?event, "ACME"),
+                        Err(e) => {
+                            tracing::error!(error = %e, "ACME certificate request failed");
+                            error_reporting::capture_task_failure("acme", &e);
+                        }
+                    }
+                }
+            });
+            Ok(Some(TlsServer::Acme(acceptor)))
+        }
+    }
+}
+
+/// Serve `app` over TLS on `listener` until `shutdown` resolves, then let
+/// open connections finish.
+pub async fn serve(
+    server: TlsServer,
+    listener: tokio::net::TcpListener,
+    app: axum::Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    let handle = axum_server::Handle::new();
+    let shutdown_handle = handle.clone();
+    tokio::spawn(async move {
+        shutdown.await;
+        shutdown_handle.graceful_shutdown(None);
+    });
+
+    let listener = listener.into_std()?;
+    let service = app.into_make_service();
+    match server {
+        TlsServer::Files(rustls) => {
+            axum_server::from_tcp_rustls(listener, rustls)
+                .handle(handle)
+                .serve(service)
+                .await
+        }
+        TlsServer::Acme(acceptor) => {
+            axum_server::from_tcp(listener)
+                .acceptor(acceptor)
+                .handle(handle)
+                .serve(service)
+                .await
+        }
+    }
+}
+
+/// Reload the certificate files when either changes, so renewals apply
+/// without dropping connections. Runs on every node.
+pub struct CertReloadJob {
+    rustls: RustlsConfig,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    interval: Duration,
+    last_stamps: Mutex<(Option<FileStamp>, Option<FileStamp>)>,
+}
+
+impl CertReloadJob {
+    pub fn new(rustls: RustlsConfig, config: &TlsConfig) -> Self {
+        let cert_path = PathBuf::from(&config.cert_path);
+        let key_path = PathBuf::from(&config.key_path);
+        let stamps = (file_stamp(&cert_path), file_stamp(&key_path));
+        Self {
+            rustls,
+            cert_path,
+            key_path,
+            interval: Duration::from_secs(config.cert_reload_interval_seconds),
+            last_stamps: Mutex::new(stamps),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl PeriodicJob for CertReloadJob {
+    fn name(&self) -> &'static str {
+        "tls_cert_reload"
+    }
+
+    fn schedule(&self) -> Schedule {
+        Schedule::Every(self.interval)
+    }
+
+    fn per_node(&self) -> bool {
+        true
+    }
+
+    async fn run(&self) -> JobResult {
+        let stamps = (file_stamp(&self.cert_path), file_stamp(&self.key_path));
+        {
+            let mut last = self
+                .last_stamps
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            if *last == stamps {
+                return Ok(0);
+            }
+            // Recorded before loading so a bad pair is reported once; the
+            // next write to either file retries
+            *last = stamps;
+        }
+
+        self.rustls
+            .reload_from_pem_file(&self.cert_path, &self.key_path)
+            .await
+            .map_err(|e| format!("cannot reload TLS certificate: {e}"))?;
+        tracing::info!(cert = %self.cert_path.display(), "TLS certificate reloaded");
+        Ok(1)
+    }
+}
@@ -1,4 +1,6 @@
-use openconv_shared::error::OpenConvError;
+use openconv_shared::api::envelope::PayloadKind;
+use openconv_shared::constants::MAX_DISPLAY_NAME_LENGTH;
+use openconv_shared::error::{ErrorMessage, OpenConvError};
 
 use crate::error::ServerError;
 
@@ -9,11 +11,16 @@ use crate::error::ServerError;
 pub fn validate_display_name(name: &str) -> Result<String, ServerError> {
     let trimmed = name.trim().to_string();
     if trimmed.is_empty() {
-        return Err(OpenConvError::Validation("display name is required".into()).into());
+        return Err(OpenConvError::Validation("display name is required".into())
+            .with_message(ErrorMessage::new("display_name.required"))
+            .into());
     }
-    if trimmed.chars().count() > 64 {
-        return Err(OpenConvError::Validation(
-            "display name must be 64 characters or fewer".into(),
+    if trimmed.chars().count() > MAX_DISPLAY_NAME_LENGTH {
+        return Err(OpenConvError::Validation(format!(
+            "display name must be {MAX_DISPLAY_NAME_LENGTH} characters or fewer"
+        ))
+        .with_message(
+            ErrorMessage::new("display_name.too_long").param("max", MAX_DISPLAY_NAME_LENGTH),
         )
         .into());
     }
@@ -21,11 +28,42 @@ pub fn validate_display_name(name: &str) -> Result<String, ServerError> {
         return Err(OpenConvError::Validation(
             "display name must not contain control characters".into(),
         )
+        .with_message(ErrorMessage::new("display_name.control_characters"))
         .into());
     }
     Ok(trimmed)
 }
 
+/// Validate the size of an encrypted message against its payload kind's limit.
+///
+/// The server cannot see the envelope contents, so the limit is selected by
+/// the `payload_kind` the client declares alongside the ciphertext.
+pub fn validate_encrypted_payload_size(
+    kind: PayloadKind,
+    encrypted_content: &[u8],
+) -> Result<(), ServerError> {
+    if encrypted_content.is_empty() {
+        return Err(
+            OpenConvError::Validation("message content is required".into())
+                .with_message(ErrorMessage::new("message_content.required"))
+                .into(),
+        );
+    }
+    let max = kind.max_encrypted_size();
+    if encrypted_content.len() > max {
+        return Err(OpenConvError::PayloadTooLarge(format!(
+            "{kind:?} payload exceeds maximum size of {max} bytes"
+        ))
+        .with_message(
+            ErrorMessage::new("message_content.too_large")
+                .param("kind", format!("{kind:?}"))
+                .param("max_bytes", max),
+        )
+        .into());
+    }
+    Ok(())
+}
+
 /// Escape ILIKE metacharacters (`%` and `_`) in a search pattern.
 pub fn escape_ilike(input: &str) -> String {
     input
@@ -84,6 +122,40 @@ mod tests {
         assert!(validate_display_name(&name).is_err());
     }
 
+    #[test]
+    fn validate_display_name_errors_carry_message_keys() {
+        let err = validate_display_name(&"a".repeat(65)).unwrap_err();
+        let message = err.0.message().unwrap();
+        assert_eq!(message.key, "display_name.too_long");
+        assert_eq!(message.params["max"], "64");
+        assert_eq!(
+            err.0.to_string(),
+            "validation error: display name must be 64 characters or fewer"
+        );
+
+        let err = validate_display_name(" ").unwrap_err();
+        assert_eq!(err.0.message().unwrap().key, "display_name.required");
+    }
+
+    #[test]
+    fn validate_encrypted_payload_size_accepts_within_limit() {
+        let content = vec![0u8; PayloadKind::Reaction.max_encrypted_size()];
+        assert!(validate_encrypted_payload_size(PayloadKind::Reaction, &content).is_ok());
+    }
+
+    #[test]
+    fn validate_encrypted_payload_size_rejects_over_kind_limit() {
+        let content = vec![0u8; PayloadKind::Reaction.max_encrypted_size() + 1];
+        assert!(validate_encrypted_payload_size(PayloadKind::Reaction, &content).is_err());
+        // The same bytes fit as a text payload
+        assert!(validate_encrypted_payload_size(PayloadKind::Text, &content).is_ok());
+    }
+
+    #[test]
+    fn validate_encrypted_payload_size_rejects_empty() {
+        assert!(validate_encrypted_payload_size(PayloadKind::Text, &[]).is_err());
+    }
+
     #[test]
     fn escape_ilike_escapes_percent() {
         assert_eq!(escape_ilike("100%"), "100\\%");
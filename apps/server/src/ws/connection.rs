@@ -5,12 +5,13 @@ use std::time::Duration;
 use axum::extract::ws::{CloseFrame, Message, WebSocket};
 use futures::stream::SplitSink;
 use futures::{SinkExt, StreamExt};
+use openconv_shared::api::gateway::GatewayIntents;
 use openconv_shared::ids::{DeviceId, GuildId, UserId};
 use tokio::sync::mpsc;
 
 use crate::state::AppState;
 
-use super::types::{ClientMessage, ServerMessage};
+use super::types::{close_codes, ClientMessage, ServerMessage};
 
 const PING_INTERVAL: Duration = Duration::from_secs(30);
 const MAX_MISSED_PONGS: u8 = 2;
@@ -21,6 +22,8 @@ pub async fn handle_connection(
     state: AppState,
     user_id: UserId,
     device_id: DeviceId,
+    intents: GatewayIntents,
+    resume_from: Option<u64>,
 ) {
     let (mut ws_sender, ws_receiver) = socket.split();
 
@@ -38,25 +41,49 @@ pub async fn handle_connection(
         }
     };
 
+    let archived_dm_channel_ids = match crate::handlers::dm_channels::archived_channel_ids(
+        &state.db, user_id,
+    )
+    .await
+    {
+        Ok(ids) => ids,
+        Err(e) => {
+            tracing::warn!(user_id = %user_id, error = %e, "failed to fetch archived DM channels");
+            Vec::new()
+        }
+    };
+
     // Close any existing connection for this (user_id, device_id) before registering
     if let Some(old) = state.ws.disconnect(user_id, device_id) {
         drop(old); // drop old sender, causing old send loop to exit
     }
 
+    let seq = if state.config.gateway.journal_enabled() {
+        super::journal::head(&state.redis, user_id).await
+    } else {
+        0
+    };
+
     let (tx, rx) = mpsc::channel(256);
     // Send Ready message directly through the sender before registering
     let ready = ServerMessage::Ready {
         user_id,
         guild_ids: guild_ids.iter().copied().collect(),
+        archived_dm_channel_ids,
+        seq,
     };
     if let Err(e) = tx.try_send(ready) {
         tracing::warn!(user_id = %user_id, error = %e, "failed to enqueue Ready message");
     }
 
     // Now register the connection in WsState
+    let replay_tx = tx.clone();
     state
         .ws
         .register_with_sender(user_id, device_id, guild_ids.clone(), tx);
+    if let Some(mut conn) = state.ws.connections.get_mut(&(user_id, device_id)) {
+        conn.intents = intents;
+    }
 
     // Set up guild broadcast subscriptions and announce presence
     super::presence::setup_guild_subscriptions(&state, user_id, device_id, &guild_ids);
@@ -73,6 +100,13 @@ pub async fn handle_connection(
         pong_received,
     ));
 
+    // Replay journaled events missed since the previous connection. Live
+    // events are already being forwarded, so the client drops duplicates.
+    if let Some(from) = resume_from.filter(|_| state.config.gateway.journal_enabled()) {
+        super::journal::replay(&state.redis, user_id, intents, from, &replay_tx).await;
+    }
+    drop(replay_tx);
+
     // Wait for either task to finish, then abort the other
     tokio::select! {
         _ = &mut send_handle => {
@@ -103,6 +137,7 @@ async fn send_loop(
             msg = rx.recv() => {
                 match msg {
                     Some(server_msg) => {
+                        let reconnect = matches!(server_msg, ServerMessage::Reconnect { .. });
                         match serde_json::to_string(&server_msg) {
                             Ok(json) => {
                                 if ws_sender.send(Message::Text(json.into())).await.is_err() {
@@ -113,6 +148,14 @@ async fn send_loop(
                                 tracing::error!(error = %e, "failed to serialize ServerMessage");
                             }
                         }
+                        if reconnect {
+                            // Server is draining; the client resumes elsewhere
+                            let _ = ws_sender.send(Message::Close(Some(CloseFrame {
+                                code: close_codes::SERVICE_RESTART,
+                                reason: "reconnect".into(),
+                            }))).await;
+                            break;
+                        }
                     }
                     None => {
                         // Channel closed (shutdown or disconnect)
@@ -213,6 +256,9 @@ async fn handle_client_message(
             channel_id,
             encrypted_content,
             nonce,
+            payload_kind,
+            idempotency_key,
+            metadata,
         } => {
             super::fanout::handle_send_message(
                 state,
@@ -221,6 +267,9 @@ async fn handle_client_message(
                 channel_id,
                 encrypted_content,
                 nonce,
+                payload_kind,
+                idempotency_key,
+                metadata.unwrap_or_default(),
             )
             .await;
         }
@@ -229,6 +278,8 @@ async fn handle_client_message(
             message_id,
             encrypted_content,
             nonce,
+            payload_kind,
+            metadata,
         } => {
             super::fanout::handle_edit_message(
                 state,
@@ -238,6 +289,8 @@ async fn handle_client_message(
                 message_id,
                 encrypted_content,
                 nonce,
+                payload_kind,
+                metadata.unwrap_or_default(),
             )
             .await;
         }
@@ -254,6 +307,23 @@ async fn handle_client_message(
         ClientMessage::StopTyping { channel_id } => {
             super::presence::handle_stop_typing(state, user_id, channel_id);
         }
+        ClientMessage::JoinVoice {
+            channel_id,
+            self_mute,
+            self_deaf,
+        } => {
+            super::voice::handle_join(state, user_id, device_id, channel_id, self_mute, self_deaf)
+                .await;
+        }
+        ClientMessage::LeaveVoice => {
+            super::voice::handle_leave(state, user_id, device_id).await;
+        }
+        ClientMessage::SetVoiceState {
+            self_mute,
+            self_deaf,
+        } => {
+            super::voice::handle_set_state(state, user_id, device_id, self_mute, self_deaf).await;
+        }
     }
 }
 
@@ -291,23 +361,50 @@ pub(super) fn send_error(
 
 async fn cleanup_connection(state: &AppState, user_id: UserId, device_id: DeviceId) {
     if let Some(conn) = state.ws.disconnect(user_id, device_id) {
-        // Store last_seen timestamps for message replay on reconnect
-        super::replay::store_last_seen(&state.redis, user_id, &conn.subscribed_channels).await;
+        finish_cleanup(state, user_id, device_id, conn).await;
+    }
+}
 
-        // Clean up channel broadcast senders with zero receivers
-        for channel_id in &conn.subscribed_channels {
-            state.ws.try_cleanup_channel(channel_id);
-        }
+/// Close every gateway connection of a user, e.g. on suspension.
+/// Returns the number of connections closed.
+pub async fn disconnect_user(state: &AppState, user_id: UserId) -> usize {
+    let removed = state.ws.disconnect_user(user_id);
+    let count = removed.len();
+    for (device_id, conn) in removed {
+        // Dropping the state closes the sender, which ends the send loop
+        finish_cleanup(state, user_id, device_id, conn).await;
+    }
+    count
+}
 
-        // Broadcast offline presence to guild members
-        super::presence::broadcast_disconnect(state, user_id, &conn.guild_ids);
+async fn finish_cleanup(
+    state: &AppState,
+    user_id: UserId,
+    device_id: DeviceId,
+    conn: super::state::ConnectionState,
+) {
+    // Store last_seen timestamps for message replay on reconnect
+    super::replay::store_last_seen(&state.redis, user_id, &conn.subscribed_channels).await;
+    // Keep journaling this user's events so a reconnect can resume
+    super::journal::remember_audience(state, user_id, &conn.subscribed_channels, &conn.guild_ids)
+        .await;
 
-        tracing::info!(
-            user_id = %user_id,
-            device_id = %device_id,
-            "connection cleaned up"
-        );
+    // Clean up channel broadcast senders with zero receivers
+    for channel_id in &conn.subscribed_channels {
+        state.ws.try_cleanup_channel(channel_id);
     }
+
+    // Leave voice if this device was connected
+    super::voice::handle_disconnect(state, user_id, device_id).await;
+
+    // Broadcast offline presence to guild members
+    super::presence::broadcast_disconnect(state, user_id, &conn.guild_ids);
+
+    tracing::info!(
+        user_id = %user_id,
+        device_id = %device_id,
+        "connection cleaned up"
+    );
 }
 
 async fn fetch_user_guild_ids(
@@ -1,17 +1,21 @@
 use std::sync::Arc;
 use std::time::Duration;
 
+use openconv_shared::api::automod::MessageMetadata;
+use openconv_shared::api::envelope::PayloadKind;
+use openconv_shared::api::gateway::GatewayIntents;
 use openconv_shared::ids::{ChannelId, DeviceId, GuildId, MessageId, UserId};
 use openconv_shared::permissions::Permissions;
 use tokio::sync::broadcast;
 
-use crate::extractors::guild_member::resolve_guild_membership;
+use crate::extractors::guild_member::cached_guild_membership;
 use crate::state::AppState;
+use crate::validation::validate_encrypted_payload_size;
 
-use super::connection::send_error;
-use super::replay;
-use super::state::WsState;
+use super::connection::{send_error, send_to_connection};
+use super::state::{BroadcastEvent, WsState};
 use super::types::ServerMessage;
+use super::{journal, replay};
 
 // ─── Channel-to-guild resolution ─────────────────────────────
 
@@ -26,12 +30,12 @@ async fn resolve_channel_guild(db: &sqlx::PgPool, channel_id: ChannelId) -> Opti
 
 // ─── Permission resolution with cache ────────────────────────
 
-enum PermissionError {
+pub(super) enum PermissionError {
     Denied,
     Internal,
 }
 
-async fn check_permission(
+pub(super) async fn check_permission(
     state: &AppState,
     user_id: UserId,
     guild_id: GuildId,
@@ -46,15 +50,14 @@ async fn check_permission(
         };
     }
 
-    // Cache miss — resolve from DB
-    let perms = resolve_guild_membership(&state.db, user_id, guild_id)
+    // Local miss — fall back to the shared cache, then the DB
+    let perms = cached_guild_membership(state, user_id, guild_id)
         .await
         .map_err(|e| {
             tracing::error!(user_id = %user_id, guild_id = %guild_id, error = ?e, "permission resolution failed");
             PermissionError::Internal
         })?;
 
-    // TODO: Invalidate cache on role change events (requires integration with REST role endpoints)
     state.ws.permission_cache.insert(user_id, guild_id, perms);
 
     if perms.contains(required) {
@@ -64,7 +67,7 @@ async fn check_permission(
     }
 }
 
-fn handle_permission_error(
+pub(super) fn handle_permission_error(
     state: &AppState,
     user_id: UserId,
     device_id: DeviceId,
@@ -80,6 +83,37 @@ fn handle_permission_error(
     }
 }
 
+/// Whether the guild's automod rules let a message with `metadata`
+/// through. Reports the block to the sender otherwise.
+async fn passes_automod(
+    state: &AppState,
+    user_id: UserId,
+    device_id: DeviceId,
+    guild_id: GuildId,
+    perms: Permissions,
+    metadata: &MessageMetadata,
+) -> bool {
+    if !crate::automod::applies_to(perms) {
+        return true;
+    }
+    let rules = match crate::automod::active_rules(&state.db, guild_id).await {
+        Ok(rules) => rules,
+        Err(e) => {
+            // Automod is a spam filter; an outage should not stop chat
+            tracing::warn!(guild_id = %guild_id, error = %e, "failed to load automod rules");
+            return true;
+        }
+    };
+    match crate::automod::check_message(&rules, metadata) {
+        Some(rule) => {
+            let message = crate::automod::blocked_message(rule);
+            send_error(state, user_id, device_id, 4009, &message);
+            false
+        }
+        None => true,
+    }
+}
+
 // ─── Subscribe ───────────────────────────────────────────────
 
 pub async fn handle_subscribe(
@@ -152,7 +186,7 @@ pub async fn handle_subscribe(
 }
 
 async fn forward_channel_messages(
-    mut broadcast_rx: broadcast::Receiver<ServerMessage>,
+    mut broadcast_rx: broadcast::Receiver<BroadcastEvent>,
     mpsc_tx: tokio::sync::mpsc::Sender<ServerMessage>,
     user_id: UserId,
     device_id: DeviceId,
@@ -160,7 +194,11 @@ async fn forward_channel_messages(
 ) {
     loop {
         match broadcast_rx.recv().await {
-            Ok(msg) => {
+            Ok(event) => {
+                // Channel events are not gated by intents
+                let Some(msg) = event.for_recipient(user_id, GatewayIntents::all()) else {
+                    continue;
+                };
                 if mpsc_tx.send(msg).await.is_err() {
                     break; // connection closed
                 }
@@ -208,6 +246,7 @@ pub fn handle_unsubscribe(
 
 // ─── Send Message ────────────────────────────────────────────
 
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_send_message(
     state: &AppState,
     user_id: UserId,
@@ -215,6 +254,9 @@ pub async fn handle_send_message(
     channel_id: ChannelId,
     encrypted_content: Vec<u8>,
     nonce: Vec<u8>,
+    payload_kind: PayloadKind,
+    idempotency_key: Option<String>,
+    metadata: MessageMetadata,
 ) {
     // Rate limit check
     if !state.ws.rate_limiter.check_and_record(user_id, channel_id) {
@@ -222,6 +264,11 @@ pub async fn handle_send_message(
         return;
     }
 
+    if let Err(e) = validate_encrypted_payload_size(payload_kind, &encrypted_content) {
+        send_error(state, user_id, device_id, 4004, &e.0.to_string());
+        return;
+    }
+
     // Resolve channel → guild
     let guild_id = match resolve_channel_guild(&state.db, channel_id).await {
         Some(gid) => gid,
@@ -232,8 +279,40 @@ pub async fn handle_send_message(
     };
 
     // Re-check SEND_MESSAGES permission
-    if let Err(e) = check_permission(state, user_id, guild_id, Permissions::SEND_MESSAGES).await {
-        handle_permission_error(state, user_id, device_id, e);
+    let perms = match check_permission(state, user_id, guild_id, Permissions::SEND_MESSAGES).await {
+        Ok(p) => p,
+        Err(e) => {
+            handle_permission_error(state, user_id, device_id, e);
+            return;
+        }
+    };
+
+    // A retry of a message that was already stored: confirm it to the
+    // sender again without storing or broadcasting a duplicate
+    if let Some(key) = idempotency_key.as_deref() {
+        if let Err(e) = crate::idempotency::validate_key(key) {
+            send_error(state, user_id, device_id, 4004, &e.to_string());
+            return;
+        }
+        match crate::idempotency::sent_message(&state.redis, user_id, channel_id, key).await {
+            Ok(Some(message_id)) => {
+                send_to_connection(
+                    state,
+                    user_id,
+                    device_id,
+                    ServerMessage::MessageCreated {
+                        channel_id,
+                        message_id,
+                    },
+                );
+                return;
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!(error = %e, "idempotency key lookup failed"),
+        }
+    }
+
+    if !passes_automod(state, user_id, device_id, guild_id, perms, &metadata).await {
         return;
     }
 
@@ -248,15 +327,21 @@ pub async fn handle_send_message(
             }
         };
 
+    if let Some(key) = idempotency_key.as_deref() {
+        if let Err(e) =
+            crate::idempotency::remember_message(&state.redis, user_id, channel_id, key, message_id)
+                .await
+        {
+            tracing::warn!(error = %e, "failed to store idempotency key");
+        }
+    }
+
     // Broadcast to channel subscribers
     let event = ServerMessage::MessageCreated {
         channel_id,
         message_id,
     };
-
-    if let Some(sender) = state.ws.channels.get(&channel_id) {
-        let _ = sender.send(event);
-    }
+    journal::publish_to_channel(state, channel_id, event).await;
 }
 
 async fn persist_message(
@@ -280,6 +365,7 @@ async fn persist_message(
 
 // ─── Edit Message ────────────────────────────────────────────
 
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_edit_message(
     state: &AppState,
     user_id: UserId,
@@ -288,6 +374,8 @@ pub async fn handle_edit_message(
     message_id: MessageId,
     encrypted_content: Vec<u8>,
     nonce: Vec<u8>,
+    payload_kind: PayloadKind,
+    metadata: MessageMetadata,
 ) {
     // Rate limit check
     if !state.ws.rate_limiter.check_and_record(user_id, channel_id) {
@@ -295,6 +383,11 @@ pub async fn handle_edit_message(
         return;
     }
 
+    if let Err(e) = validate_encrypted_payload_size(payload_kind, &encrypted_content) {
+        send_error(state, user_id, device_id, 4004, &e.0.to_string());
+        return;
+    }
+
     // Resolve channel → guild and verify membership
     let guild_id = match resolve_channel_guild(&state.db, channel_id).await {
         Some(gid) => gid,
@@ -304,8 +397,15 @@ pub async fn handle_edit_message(
         }
     };
 
-    if let Err(e) = check_permission(state, user_id, guild_id, Permissions::READ_MESSAGES).await {
-        handle_permission_error(state, user_id, device_id, e);
+    let perms = match check_permission(state, user_id, guild_id, Permissions::READ_MESSAGES).await {
+        Ok(p) => p,
+        Err(e) => {
+            handle_permission_error(state, user_id, device_id, e);
+            return;
+        }
+    };
+
+    if !passes_automod(state, user_id, device_id, guild_id, perms, &metadata).await {
         return;
     }
 
@@ -325,9 +425,7 @@ pub async fn handle_edit_message(
                 channel_id,
                 message_id,
             };
-            if let Some(sender) = state.ws.channels.get(&channel_id) {
-                let _ = sender.send(event);
-            }
+            journal::publish_to_channel(state, channel_id, event).await;
         }
         Ok(false) => {
             send_error(
@@ -410,9 +508,7 @@ pub async fn handle_delete_message(
                 channel_id,
                 message_id,
             };
-            if let Some(sender) = state.ws.channels.get(&channel_id) {
-                let _ = sender.send(event);
-            }
+            journal::publish_to_channel(state, channel_id, event).await;
         }
         Ok(false) => {
             send_error(state, user_id, device_id, 4007, "message not found");
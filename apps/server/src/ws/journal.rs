@@ -0,0 +1,389 @@
+//! Per-user event journal for resuming gateway clients.
+//!
+//! Message, guild membership and DM archive events are numbered in each
+//! recipient's own sequence and appended to a Redis sorted set that lives
+//! for `gateway.journal_retention_seconds` after the last append, capped at
+//! `gateway.journal_max_events`. A client reconnecting with `resume_from`
+//! gets every journaled event after that number, then `ResumeComplete`.
+//!
+//! Recipients are the users connected to this node who should see the event,
+//! plus those who saw events from the same channel or guild before
+//! disconnecting within the retention window (the "audience").
+//!
+//! Presence, typing and voice events are not journaled: clients get the
+//! current state again on reconnect.
+//!
+//! The journal is best effort. When Redis cannot be reached, events are
+//! broadcast unsequenced, and a replay that cannot read the journal reports
+//! itself as truncated.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+
+use fred::interfaces::{KeysInterface, LuaInterface};
+use futures::future::join_all;
+use openconv_shared::api::gateway::GatewayIntents;
+use openconv_shared::ids::{ChannelId, GuildId, UserId};
+use tokio::sync::mpsc;
+
+use crate::config::GatewayConfig;
+use crate::redis::{RedisError, RedisPool};
+use crate::state::AppState;
+
+use super::state::BroadcastEvent;
+use super::types::ServerMessage;
+
+/// Sequence counters outlive the journal so numbers keep increasing across
+/// short absences; a counter that expires restarts at 1, which clients see
+/// as a `Ready` seq below their own and resync.
+const SEQ_TTL_SECS: u64 = 30 * 86400;
+
+/// Append an event and return its number. Both keys share a hash slot.
+const APPEND_SCRIPT: &str = r#"
+local seq = redis.call('INCR', KEYS[1])
+redis.call('EXPIRE', KEYS[1], ARGV[4])
+redis.call('ZADD', KEYS[2], seq, seq .. ':' .. ARGV[1])
+redis.call('ZREMRANGEBYRANK', KEYS[2], 0, -(tonumber(ARGV[2]) + 1))
+redis.call('EXPIRE', KEYS[2], ARGV[3])
+return seq
+"#;
+
+/// Members of a sorted set scored between ARGV[1] and ARGV[2].
+const RANGE_SCRIPT: &str = r#"
+return redis.call('ZRANGEBYSCORE', KEYS[1], ARGV[1], ARGV[2])
+"#;
+
+/// Add a user to an audience until ARGV[1] (unix ms), pruning expired ones.
+const REMEMBER_SCRIPT: &str = r#"
+redis.call('ZADD', KEYS[1], ARGV[1], ARGV[2])
+redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', ARGV[3])
+redis.call('PEXPIREAT', KEYS[1], ARGV[1])
+return 1
+"#;
+
+fn seq_key(user_id: UserId) -> String {
+    format!("ws:seq:{{{user_id}}}")
+}
+
+fn journal_key(user_id: UserId) -> String {
+    format!("ws:journal:{{{user_id}}}")
+}
+
+/// Where an event was published, for tracking who to journal it for.
+#[derive(Debug, Clone, Copy)]
+enum Audience {
+    Channel(ChannelId),
+    Guild(GuildId),
+}
+
+impl fmt::Display for Audience {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Channel(id) => write!(f, "ws:audience:channel:{id}"),
+            Self::Guild(id) => write!(f, "ws:audience:guild:{id}"),
+        }
+    }
+}
+
+/// Split a journal member into its number and event.
+fn parse_entry(member: &str) -> Option<(u64, ServerMessage)> {
+    let (seq, json) = member.split_once(':')?;
+    Some((seq.parse().ok()?, serde_json::from_str(json).ok()?))
+}
+
+/// Whether entries starting at `first` leave out any of `from + 1..=head`.
+fn is_truncated(from: u64, head: u64, first: Option<u64>) -> bool {
+    if from > head {
+        // The counter restarted; nothing before it can be replayed
+        return true;
+    }
+    from < head && first != Some(from + 1)
+}
+
+// ─── Publishing ──────────────────────────────────────────────
+
+/// Journal `message` for everyone who should see it in `channel_id` and
+/// broadcast it to the channel's subscribers.
+pub async fn publish_to_channel(state: &AppState, channel_id: ChannelId, message: ServerMessage) {
+    let local = state
+        .ws
+        .connections
+        .iter()
+        .filter(|c| c.subscribed_channels.contains(&channel_id))
+        .map(|c| c.key().0)
+        .collect();
+    let event = sequence(state, Audience::Channel(channel_id), local, message).await;
+    // Send only fails when nobody is subscribed.
+    if let Some(sender) = state.ws.channels.get(&channel_id) {
+        let _ = sender.send(event);
+    }
+}
+
+/// Journal `message` for the members of `guild_id` and broadcast it to
+/// their connections.
+pub async fn publish_to_guild(state: &AppState, guild_id: GuildId, message: ServerMessage) {
+    let local = state
+        .ws
+        .connections
+        .iter()
+        .filter(|c| c.guild_ids.contains(&guild_id))
+        .map(|c| c.key().0)
+        .collect();
+    let event = sequence(state, Audience::Guild(guild_id), local, message).await;
+    if let Some(sender) = state.ws.guilds.get(&guild_id) {
+        let _ = sender.send(event);
+    }
+}
+
+/// Journal `message` for `user_id` and push it to all of their connections.
+pub async fn publish_to_user(state: &AppState, user_id: UserId, message: ServerMessage) {
+    let seqs = if state.config.gateway.journal_enabled() {
+        journal(state, HashSet::from([user_id]), &message).await
+    } else {
+        HashMap::new()
+    };
+    let message = match seqs.get(&user_id) {
+        Some(&seq) => ServerMessage::Sequenced {
+            seq,
+            event: Some(Box::new(message)),
+        },
+        None => message,
+    };
+    state.ws.send_to_user(user_id, message);
+}
+
+/// Number `message` for the local recipients and the audience.
+async fn sequence(
+    state: &AppState,
+    audience: Audience,
+    mut recipients: HashSet<UserId>,
+    message: ServerMessage,
+) -> BroadcastEvent {
+    if !state.config.gateway.journal_enabled() {
+        return message.into();
+    }
+    recipients.extend(audience_members(&state.redis, audience).await);
+    let seqs = journal(state, recipients, &message).await;
+    BroadcastEvent {
+        message,
+        seqs: (!seqs.is_empty()).then(|| Arc::new(seqs)),
+    }
+}
+
+/// Append `message` to each recipient's journal. Recipients whose append
+/// failed are left out and get the event unsequenced.
+async fn journal(
+    state: &AppState,
+    recipients: HashSet<UserId>,
+    message: &ServerMessage,
+) -> HashMap<UserId, u64> {
+    let json = match serde_json::to_string(message) {
+        Ok(json) => json,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to serialize journaled event");
+            return HashMap::new();
+        }
+    };
+    let config = &state.config.gateway;
+    let appends = recipients.into_iter().map(|user_id| {
+        let json = json.as_str();
+        async move {
+            match append(&state.redis, config, user_id, json).await {
+                Ok(seq) => Some((user_id, seq)),
+                Err(e) => {
+                    tracing::warn!(user_id = %user_id, error = %e, "failed to journal event");
+                    None
+                }
+            }
+        }
+    });
+    join_all(appends).await.into_iter().flatten().collect()
+}
+
+async fn append(
+    redis: &RedisPool,
+    config: &GatewayConfig,
+    user_id: UserId,
+    json: &str,
+) -> Result<u64, RedisError> {
+    redis
+        .guarded(redis.eval(
+            APPEND_SCRIPT,
+            vec![redis.key(seq_key(user_id)), redis.key(journal_key(user_id))],
+            vec![
+                json.to_string(),
+                config.journal_max_events.to_string(),
+                config.journal_retention_seconds.to_string(),
+                SEQ_TTL_SECS.to_string(),
+            ],
+        ))
+        .await
+}
+
+// ─── Audience ────────────────────────────────────────────────
+
+/// Users who disconnected from `audience` within the retention window.
+async fn audience_members(redis: &RedisPool, audience: Audience) -> HashSet<UserId> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let members: Result<Vec<String>, RedisError> = redis
+        .guarded(redis.eval(
+            RANGE_SCRIPT,
+            vec![redis.key(audience)],
+            vec![now.to_string(), "+inf".to_string()],
+        ))
+        .await;
+    match members {
+        Ok(members) => members.iter().filter_map(|m| m.parse().ok()).collect(),
+        Err(e) => {
+            tracing::warn!(audience = %audience, error = %e, "failed to read gateway audience");
+            HashSet::new()
+        }
+    }
+}
+
+/// Keep journaling a disconnecting user's channel and guild events for the
+/// retention window, so they can resume without missing any.
+pub async fn remember_audience(
+    state: &AppState,
+    user_id: UserId,
+    channel_ids: &HashSet<ChannelId>,
+    guild_ids: &HashSet<GuildId>,
+) {
+    let config = &state.config.gateway;
+    if !config.journal_enabled() {
+        return;
+    }
+    let now = chrono::Utc::now().timestamp_millis();
+    let until = now + (config.journal_retention_seconds * 1000) as i64;
+    let audiences = channel_ids
+        .iter()
+        .map(|&id| Audience::Channel(id))
+        .chain(guild_ids.iter().map(|&id| Audience::Guild(id)));
+
+    let redis = &state.redis;
+    join_all(audiences.map(|audience| async move {
+        let result: Result<i64, RedisError> = redis
+            .guarded(redis.eval(
+                REMEMBER_SCRIPT,
+                vec![redis.key(audience)],
+                vec![until.to_string(), user_id.to_string(), now.to_string()],
+            ))
+            .await;
+        if let Err(e) = result {
+            tracing::warn!(
+                user_id = %user_id,
+                audience = %audience,
+                error = %e,
+                "failed to store gateway audience"
+            );
+        }
+    }))
+    .await;
+}
+
+// ─── Resume ──────────────────────────────────────────────────
+
+/// The user's latest sequence number, or 0 if unknown.
+pub async fn head(redis: &RedisPool, user_id: UserId) -> u64 {
+    redis
+        .guarded(redis.get::<Option<u64>, _>(redis.key(seq_key(user_id))))
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or(0)
+}
+
+/// Send every journaled event after `from`, filtered by `intents`, then
+/// `ResumeComplete`. Live events keep flowing meanwhile; clients drop
+/// duplicates by number.
+pub async fn replay(
+    redis: &RedisPool,
+    user_id: UserId,
+    intents: GatewayIntents,
+    from: u64,
+    tx: &mpsc::Sender<ServerMessage>,
+) {
+    let head = head(redis, user_id).await;
+    let entries: Result<Vec<String>, RedisError> = if from < head {
+        redis
+            .guarded(redis.eval(
+                RANGE_SCRIPT,
+                vec![redis.key(journal_key(user_id))],
+                vec![(from + 1).to_string(), head.to_string()],
+            ))
+            .await
+    } else {
+        Ok(Vec::new())
+    };
+    let entries = match entries {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::warn!(user_id = %user_id, error = %e, "failed to read gateway journal");
+            let _ = tx
+                .send(ServerMessage::ResumeComplete {
+                    seq: from,
+                    truncated: true,
+                })
+                .await;
+            return;
+        }
+    };
+
+    let entries: Vec<_> = entries.iter().filter_map(|m| parse_entry(m)).collect();
+    let truncated = is_truncated(from, head, entries.first().map(|(seq, _)| *seq));
+    for (seq, message) in entries {
+        let wanted = super::presence::wants(intents, &message);
+        let sequenced = ServerMessage::Sequenced {
+            seq,
+            event: wanted.then(|| Box::new(message)),
+        };
+        if tx.send(sequenced).await.is_err() {
+            return;
+        }
+    }
+    let _ = tx
+        .send(ServerMessage::ResumeComplete {
+            seq: head,
+            truncated,
+        })
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openconv_shared::ids::MessageId;
+
+    #[test]
+    fn journal_keys_share_a_hash_slot() {
+        let user_id = UserId::new();
+        let tag = format!("{{{user_id}}}");
+        assert!(seq_key(user_id).ends_with(&tag));
+        assert!(journal_key(user_id).ends_with(&tag));
+        assert_ne!(seq_key(user_id), journal_key(user_id));
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let message = ServerMessage::MessageCreated {
+            channel_id: ChannelId::new(),
+            message_id: MessageId::new(),
+        };
+        let member = format!("42:{}", serde_json::to_string(&message).unwrap());
+        let (seq, back) = parse_entry(&member).unwrap();
+        assert_eq!(seq, 42);
+        assert!(matches!(back, ServerMessage::MessageCreated { .. }));
+        assert!(parse_entry("garbage").is_none());
+        assert!(parse_entry("x:{}").is_none());
+    }
+
+    #[test]
+    fn truncation_detects_missing_entries() {
+        assert!(!is_truncated(5, 5, None));
+        assert!(!is_truncated(5, 8, Some(6)));
+        assert!(is_truncated(5, 8, Some(7)));
+        assert!(is_truncated(5, 8, None));
+        assert!(is_truncated(9, 3, None));
+    }
+}
@@ -1,6 +1,8 @@
 pub mod connection;
 pub mod fanout;
+pub mod journal;
 pub mod presence;
 pub mod replay;
 pub mod state;
 pub mod types;
+pub mod voice;
@@ -1,10 +1,12 @@
 use std::collections::HashSet;
 use std::time::Duration;
 
+use openconv_shared::api::gateway::GatewayIntents;
 use openconv_shared::ids::{ChannelId, DeviceId, GuildId, UserId};
 
 use crate::state::AppState;
 
+use super::state::BroadcastEvent;
 use super::types::{PresenceStatus, ServerMessage};
 
 const TYPING_TIMEOUT_SECS: u64 = 5;
@@ -13,15 +15,15 @@ const TYPING_TIMEOUT_SECS: u64 = 5;
 
 /// Set up guild broadcast forwarding tasks on connect.
 /// For each guild, subscribes to the guild broadcast and forwards events
-/// to the connection's mpsc sender.
+/// matching the connection's intents to its mpsc sender.
 pub fn setup_guild_subscriptions(
     state: &AppState,
     user_id: UserId,
     device_id: DeviceId,
     guild_ids: &HashSet<GuildId>,
 ) {
-    let mpsc_tx = match state.ws.connections.get(&(user_id, device_id)) {
-        Some(c) => c.sender.clone(),
+    let (mpsc_tx, intents) = match state.ws.connections.get(&(user_id, device_id)) {
+        Some(c) => (c.sender.clone(), c.intents),
         None => return,
     };
 
@@ -30,7 +32,7 @@ pub fn setup_guild_subscriptions(
         let broadcast_rx = broadcast_tx.subscribe();
 
         let tx = mpsc_tx.clone();
-        let handle = tokio::spawn(forward_guild_messages(broadcast_rx, tx));
+        let handle = tokio::spawn(forward_guild_messages(broadcast_rx, tx, user_id, intents));
 
         if let Some(mut conn) = state.ws.connections.get_mut(&(user_id, device_id)) {
             conn.guild_forward_tasks
@@ -40,19 +42,25 @@ pub fn setup_guild_subscriptions(
 }
 
 async fn forward_guild_messages(
-    mut broadcast_rx: tokio::sync::broadcast::Receiver<ServerMessage>,
+    mut broadcast_rx: tokio::sync::broadcast::Receiver<BroadcastEvent>,
     mpsc_tx: tokio::sync::mpsc::Sender<ServerMessage>,
+    user_id: UserId,
+    intents: GatewayIntents,
 ) {
     loop {
         match broadcast_rx.recv().await {
-            Ok(msg) => {
+            Ok(event) => {
+                let Some(msg) = event.for_recipient(user_id, intents) else {
+                    continue;
+                };
                 if mpsc_tx.send(msg).await.is_err() {
                     break;
                 }
             }
             Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
                 // Guild events (presence, member join/leave) can be missed
-                // without breaking the protocol — client will see the next update.
+                // without breaking the protocol — client will see the next update,
+                // and journaled ones show up as a sequence gap.
                 continue;
             }
             Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
@@ -60,6 +68,11 @@ async fn forward_guild_messages(
     }
 }
 
+pub(super) fn wants(intents: GatewayIntents, msg: &ServerMessage) -> bool {
+    msg.required_intent()
+        .is_none_or(|required| intents.intersects(required))
+}
+
 // ─── Presence lifecycle ──────────────────────────────────────
 
 /// Broadcast PresenceUpdate { Online } to all guilds the user belongs to.
@@ -108,7 +121,7 @@ pub fn handle_set_presence(
 fn broadcast_to_guilds(state: &AppState, guild_ids: &HashSet<GuildId>, event: ServerMessage) {
     for guild_id in guild_ids {
         if let Some(sender) = state.ws.guilds.get(guild_id) {
-            let _ = sender.send(event.clone());
+            let _ = sender.send(event.clone().into());
         }
     }
 }
@@ -123,7 +136,7 @@ pub fn handle_start_typing(state: &AppState, user_id: UserId, channel_id: Channe
         user_id,
     };
     if let Some(sender) = state.ws.channels.get(&channel_id) {
-        let _ = sender.send(event);
+        let _ = sender.send(event.into());
     }
 
     // Start (or reset) typing timeout
@@ -148,6 +161,20 @@ pub fn handle_stop_typing(state: &AppState, user_id: UserId, channel_id: Channel
 mod tests {
     use super::*;
 
+    #[test]
+    fn wants_filters_guild_events_by_intent() {
+        let presence = ServerMessage::PresenceUpdate {
+            user_id: UserId::new(),
+            status: PresenceStatus::Online,
+        };
+        assert!(wants(GatewayIntents::GUILD_PRESENCES, &presence));
+        assert!(!wants(GatewayIntents::GUILD_MEMBERS, &presence));
+        assert!(wants(
+            GatewayIntents::empty(),
+            &ServerMessage::Pong { ts: 1 }
+        ));
+    }
+
     #[test]
     fn typing_timeout_is_5_seconds() {
         assert_eq!(TYPING_TIMEOUT_SECS, 5);
@@ -5,6 +5,8 @@ use futures::future::join_all;
 use openconv_shared::ids::{ChannelId, MessageId, UserId};
 use tokio::sync::mpsc;
 
+use crate::redis::RedisPool;
+
 use super::types::ServerMessage;
 
 const LAST_SEEN_TTL_SECS: i64 = 86400; // 24 hours
@@ -18,7 +20,7 @@ fn last_seen_key(user_id: UserId, channel_id: ChannelId) -> String {
 /// Store last_seen timestamps for all subscribed channels on disconnect.
 /// Uses concurrent Redis calls for efficiency.
 pub async fn store_last_seen(
-    redis: &fred::clients::Pool,
+    redis: &RedisPool,
     user_id: UserId,
     subscribed_channels: &HashSet<ChannelId>,
 ) {
@@ -31,7 +33,7 @@ pub async fn store_last_seen(
     let futures: Vec<_> = subscribed_channels
         .iter()
         .map(|&channel_id| {
-            let key = last_seen_key(user_id, channel_id);
+            let key = redis.key(last_seen_key(user_id, channel_id));
             let ts = now.clone();
             let r = redis.clone();
             async move {
@@ -64,12 +66,12 @@ pub async fn store_last_seen(
 /// Capped at MAX_REPLAY_MESSAGES; client should use REST pagination for more.
 pub async fn replay_missed_messages(
     db: &sqlx::PgPool,
-    redis: &fred::clients::Pool,
+    redis: &RedisPool,
     user_id: UserId,
     channel_id: ChannelId,
     sender: &mpsc::Sender<ServerMessage>,
 ) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
-    let key = last_seen_key(user_id, channel_id);
+    let key = redis.key(last_seen_key(user_id, channel_id));
 
     // Get last_seen timestamp from Redis
     let ts_str: Option<String> = redis.get(&key).await?;
@@ -1,9 +1,13 @@
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use dashmap::DashMap;
+use openconv_shared::api::gateway::GatewayIntents;
 use openconv_shared::ids::{ChannelId, DeviceId, GuildId, UserId};
 use openconv_shared::permissions::Permissions;
+use rand::Rng;
 use tokio::sync::{broadcast, mpsc};
 
 use super::types::{PresenceStatus, ServerMessage};
@@ -12,6 +16,12 @@ const CHANNEL_BROADCAST_CAPACITY: usize = 1000;
 const CONNECTION_MPSC_CAPACITY: usize = 256;
 const PERMISSION_CACHE_TTL_SECS: u64 = 60;
 const RATE_LIMIT_PER_SECOND: usize = 5;
+/// Gap between batches of clients told to reconnect during a drain.
+const DRAIN_BATCH_INTERVAL: Duration = Duration::from_millis(100);
+/// How long a drain waits for told clients to close after the last batch.
+const DRAIN_CLOSE_GRACE: Duration = Duration::from_secs(2);
+/// Upper bound of the random reconnect delay hinted to each client.
+const RECONNECT_JITTER_MS: u64 = 2000;
 
 /// Shared state for all active WebSocket connections.
 pub struct WsState {
@@ -19,10 +29,10 @@ pub struct WsState {
     pub connections: DashMap<(UserId, DeviceId), ConnectionState>,
 
     /// Channel broadcast senders for message fan-out.
-    pub channels: DashMap<ChannelId, broadcast::Sender<ServerMessage>>,
+    pub channels: DashMap<ChannelId, broadcast::Sender<BroadcastEvent>>,
 
     /// Guild broadcast senders for guild-wide events.
-    pub guilds: DashMap<GuildId, broadcast::Sender<ServerMessage>>,
+    pub guilds: DashMap<GuildId, broadcast::Sender<BroadcastEvent>>,
 
     /// Cached permission resolutions to reduce DB load on message sends.
     pub permission_cache: PermissionCache,
@@ -32,6 +42,43 @@ pub struct WsState {
 
     /// Tracks active typing indicators with auto-expiry.
     pub typing: TypingManager,
+
+    /// Set once shutdown starts; new connections are refused.
+    draining: AtomicBool,
+}
+
+/// A message on a channel or guild broadcast. Journaled messages carry
+/// each recipient's sequence number (see [`super::journal`]).
+#[derive(Debug, Clone)]
+pub struct BroadcastEvent {
+    pub message: ServerMessage,
+    pub seqs: Option<Arc<HashMap<UserId, u64>>>,
+}
+
+impl From<ServerMessage> for BroadcastEvent {
+    fn from(message: ServerMessage) -> Self {
+        Self {
+            message,
+            seqs: None,
+        }
+    }
+}
+
+impl BroadcastEvent {
+    /// The message as a connection of `user_id` with `intents` receives
+    /// it, or `None` if it should not see it at all. A journaled message
+    /// the intents exclude is still sent, without its event, so the
+    /// user's sequence stays gapless.
+    pub fn for_recipient(&self, user_id: UserId, intents: GatewayIntents) -> Option<ServerMessage> {
+        let wanted = super::presence::wants(intents, &self.message);
+        match self.seqs.as_ref().and_then(|seqs| seqs.get(&user_id)) {
+            Some(&seq) => Some(ServerMessage::Sequenced {
+                seq,
+                event: wanted.then(|| Box::new(self.message.clone())),
+            }),
+            None => wanted.then(|| self.message.clone()),
+        }
+    }
 }
 
 /// Per-connection state stored in the WsState DashMap.
@@ -56,6 +103,9 @@ pub struct ConnectionState {
 
     /// Abort handles for guild broadcast forwarding tasks.
     pub guild_forward_tasks: HashMap<GuildId, tokio::task::AbortHandle>,
+
+    /// Guild event categories this connection receives.
+    pub intents: GatewayIntents,
 }
 
 impl Drop for ConnectionState {
@@ -78,6 +128,7 @@ impl WsState {
             permission_cache: PermissionCache::new(Duration::from_secs(PERMISSION_CACHE_TTL_SECS)),
             rate_limiter: WsRateLimiter::new(RATE_LIMIT_PER_SECOND),
             typing: TypingManager::new(),
+            draining: AtomicBool::new(false),
         }
     }
 
@@ -97,6 +148,7 @@ impl WsState {
             guild_ids,
             channel_forward_tasks: HashMap::new(),
             guild_forward_tasks: HashMap::new(),
+            intents: GatewayIntents::default(),
         };
         self.connections.insert((user_id, device_id), conn);
         rx
@@ -119,6 +171,7 @@ impl WsState {
             guild_ids,
             channel_forward_tasks: HashMap::new(),
             guild_forward_tasks: HashMap::new(),
+            intents: GatewayIntents::default(),
         };
         self.connections.insert((user_id, device_id), conn);
     }
@@ -130,11 +183,38 @@ impl WsState {
             .map(|(_, v)| v)
     }
 
+    /// Remove every connection of a user and return their states for cleanup.
+    pub fn disconnect_user(&self, user_id: UserId) -> Vec<(DeviceId, ConnectionState)> {
+        let device_ids: Vec<DeviceId> = self
+            .connections
+            .iter()
+            .filter(|entry| entry.key().0 == user_id)
+            .map(|entry| entry.key().1)
+            .collect();
+        device_ids
+            .into_iter()
+            .filter_map(|device_id| {
+                self.disconnect(user_id, device_id)
+                    .map(|conn| (device_id, conn))
+            })
+            .collect()
+    }
+
+    /// Push a message to every connection of a user, e.g. for account
+    /// state that must stay in sync across their devices.
+    pub fn send_to_user(&self, user_id: UserId, msg: ServerMessage) {
+        for entry in self.connections.iter() {
+            if entry.key().0 == user_id {
+                let _ = entry.sender.try_send(msg.clone());
+            }
+        }
+    }
+
     /// Get or create a broadcast sender for a channel.
     pub fn get_or_create_channel_sender(
         &self,
         channel_id: ChannelId,
-    ) -> broadcast::Sender<ServerMessage> {
+    ) -> broadcast::Sender<BroadcastEvent> {
         self.channels
             .entry(channel_id)
             .or_insert_with(|| broadcast::channel(CHANNEL_BROADCAST_CAPACITY).0)
@@ -145,7 +225,7 @@ impl WsState {
     pub fn get_or_create_guild_sender(
         &self,
         guild_id: GuildId,
-    ) -> broadcast::Sender<ServerMessage> {
+    ) -> broadcast::Sender<BroadcastEvent> {
         self.guilds
             .entry(guild_id)
             .or_insert_with(|| broadcast::channel(CHANNEL_BROADCAST_CAPACITY).0)
@@ -165,6 +245,54 @@ impl WsState {
             .remove_if(guild_id, |_, sender| sender.receiver_count() == 0);
     }
 
+    /// Whether [`WsState::drain`] has started.
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Stop accepting connections and tell every client to reconnect.
+    ///
+    /// Clients are told in batches spread over `window`, so they do not all
+    /// land on the remaining nodes at once. Each send loop closes its
+    /// socket after the `Reconnect` message and the usual cleanup stores
+    /// replay positions. Returns once every connection has closed, or
+    /// shortly after the last batch.
+    pub async fn drain(&self, window: Duration) {
+        self.draining.store(true, Ordering::SeqCst);
+        let keys: Vec<(UserId, DeviceId)> =
+            self.connections.iter().map(|entry| *entry.key()).collect();
+        if keys.is_empty() {
+            return;
+        }
+        tracing::info!(
+            connections = keys.len(),
+            window_secs = window.as_secs(),
+            "draining gateway connections"
+        );
+
+        let batches = (window.as_millis() / DRAIN_BATCH_INTERVAL.as_millis()).max(1) as usize;
+        for (i, batch) in keys.chunks(keys.len().div_ceil(batches)).enumerate() {
+            if i > 0 {
+                tokio::time::sleep(DRAIN_BATCH_INTERVAL).await;
+            }
+            for key in batch {
+                if let Some(conn) = self.connections.get(key) {
+                    let reconnect_after_ms = rand::rng().random_range(0..=RECONNECT_JITTER_MS);
+                    // A full queue leaves this client to the plain close
+                    // in `shutdown_all`
+                    let _ = conn
+                        .sender
+                        .try_send(ServerMessage::Reconnect { reconnect_after_ms });
+                }
+            }
+        }
+
+        let deadline = Instant::now() + DRAIN_CLOSE_GRACE;
+        while !self.connections.is_empty() && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
     /// Send a shutdown signal to all connections by dropping their senders.
     pub async fn shutdown_all(&self) {
         self.connections.clear();
@@ -218,6 +346,12 @@ impl PermissionCache {
     pub fn invalidate(&self, user_id: UserId, guild_id: GuildId) {
         self.cache.remove(&(user_id, guild_id));
     }
+
+    /// Invalidate every entry for a guild (e.g. on a role permission change).
+    pub fn invalidate_guild(&self, guild_id: GuildId) {
+        self.cache
+            .retain(|(_, cached_guild), _| *cached_guild != guild_id);
+    }
 }
 
 // ─── Rate Limiter ────────────────────────────────────────────
@@ -319,6 +453,33 @@ mod tests {
         assert!(ws.connections.contains_key(&(uid, did)));
     }
 
+    #[tokio::test]
+    async fn drain_tells_clients_to_reconnect_and_refuses_new_ones() {
+        let ws = std::sync::Arc::new(WsState::new());
+        let uid = UserId::new();
+        let did = DeviceId::new();
+        let mut rx = ws.register(uid, did, HashSet::new());
+
+        // Stands in for the connection task closing after the message
+        let client_ws = ws.clone();
+        let client = tokio::spawn(async move {
+            let msg = rx.recv().await;
+            client_ws.disconnect(uid, did);
+            msg
+        });
+
+        assert!(!ws.is_draining());
+        ws.drain(Duration::ZERO).await;
+        assert!(ws.is_draining());
+        assert!(ws.connections.is_empty());
+        match client.await.unwrap() {
+            Some(ServerMessage::Reconnect { reconnect_after_ms }) => {
+                assert!(reconnect_after_ms <= RECONNECT_JITTER_MS)
+            }
+            other => panic!("expected Reconnect, got {other:?}"),
+        }
+    }
+
     #[test]
     fn register_returns_working_receiver() {
         let ws = WsState::new();
@@ -369,6 +530,22 @@ mod tests {
         assert!(ws.connections.contains_key(&(uid, did2)));
     }
 
+    #[test]
+    fn disconnect_user_removes_all_devices_of_that_user() {
+        let ws = WsState::new();
+        let uid = UserId::new();
+        let other = UserId::new();
+
+        let _rx1 = ws.register(uid, DeviceId::new(), HashSet::new());
+        let _rx2 = ws.register(uid, DeviceId::new(), HashSet::new());
+        let _rx3 = ws.register(other, DeviceId::new(), HashSet::new());
+
+        let removed = ws.disconnect_user(uid);
+        assert_eq!(removed.len(), 2);
+        assert_eq!(ws.connections.len(), 1);
+        assert!(ws.connections.iter().all(|e| e.key().0 == other));
+    }
+
     #[test]
     fn disconnect_nonexistent_returns_none() {
         let ws = WsState::new();
@@ -432,6 +609,69 @@ mod tests {
         assert!(!ws.guilds.contains_key(&gid));
     }
 
+    #[test]
+    fn send_to_user_reaches_only_that_users_devices() {
+        let state = WsState::new();
+        let user = UserId::new();
+        let other = UserId::new();
+        let mut rx_a = state.register(user, DeviceId::new(), HashSet::new());
+        let mut rx_b = state.register(user, DeviceId::new(), HashSet::new());
+        let mut rx_other = state.register(other, DeviceId::new(), HashSet::new());
+
+        state.send_to_user(user, ServerMessage::Pong { ts: 7 });
+
+        assert!(matches!(rx_a.try_recv(), Ok(ServerMessage::Pong { ts: 7 })));
+        assert!(matches!(rx_b.try_recv(), Ok(ServerMessage::Pong { ts: 7 })));
+        assert!(rx_other.try_recv().is_err());
+    }
+
+    #[test]
+    fn broadcast_event_carries_each_recipients_seq() {
+        let (alice, bob) = (UserId::new(), UserId::new());
+        let event = BroadcastEvent {
+            message: ServerMessage::MessageCreated {
+                channel_id: ChannelId::new(),
+                message_id: openconv_shared::ids::MessageId::new(),
+            },
+            seqs: Some(Arc::new(HashMap::from([(alice, 4)]))),
+        };
+        assert!(matches!(
+            event.for_recipient(alice, GatewayIntents::default()),
+            Some(ServerMessage::Sequenced {
+                seq: 4,
+                event: Some(_)
+            })
+        ));
+        assert!(matches!(
+            event.for_recipient(bob, GatewayIntents::default()),
+            Some(ServerMessage::MessageCreated { .. })
+        ));
+    }
+
+    #[test]
+    fn broadcast_event_keeps_seq_when_intents_exclude_it() {
+        use openconv_shared::api::gateway::{MemberEvent, MemberEventKind};
+
+        let user = UserId::new();
+        let event = BroadcastEvent {
+            message: ServerMessage::GuildMemberEvent {
+                event: MemberEvent::new(GuildId::new(), user, MemberEventKind::Join),
+            },
+            seqs: Some(Arc::new(HashMap::from([(user, 9)]))),
+        };
+        assert!(matches!(
+            event.for_recipient(user, GatewayIntents::empty()),
+            Some(ServerMessage::Sequenced {
+                seq: 9,
+                event: None
+            })
+        ));
+        let unjournaled = BroadcastEvent::from(event.message.clone());
+        assert!(unjournaled
+            .for_recipient(user, GatewayIntents::empty())
+            .is_none());
+    }
+
     #[tokio::test]
     async fn shutdown_all_clears_everything() {
         let ws = WsState::new();
@@ -498,6 +738,20 @@ mod tests {
         assert!(cache.get(uid, gid).is_none());
     }
 
+    #[test]
+    fn permission_cache_invalidate_guild_keeps_other_guilds() {
+        let cache = PermissionCache::new(Duration::from_secs(60));
+        let (uid1, uid2) = (UserId::new(), UserId::new());
+        let (gid, other) = (GuildId::new(), GuildId::new());
+        cache.insert(uid1, gid, Permissions::SEND_MESSAGES);
+        cache.insert(uid2, gid, Permissions::SEND_MESSAGES);
+        cache.insert(uid1, other, Permissions::SEND_MESSAGES);
+        cache.invalidate_guild(gid);
+        assert!(cache.get(uid1, gid).is_none());
+        assert!(cache.get(uid2, gid).is_none());
+        assert!(cache.get(uid1, other).is_some());
+    }
+
     #[test]
     fn permission_cache_expired_entry_returns_none() {
         let cache = PermissionCache::new(Duration::from_millis(0));
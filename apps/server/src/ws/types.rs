@@ -1 +1,3 @@
-pub use openconv_shared::api::ws::{error_codes, ClientMessage, PresenceStatus, ServerMessage};
+pub use openconv_shared::api::ws::{
+    close_codes, error_codes, ClientMessage, PresenceStatus, ServerMessage,
+};
@@ -0,0 +1,353 @@
+//! Voice channel signaling.
+//!
+//! Clients join, leave and mute over the gateway. The server records each
+//! member's state in Redis and broadcasts `VoiceStateUpdate` to the guild.
+//! Media never passes through here: clients connect peer to peer or through
+//! an SFU, using credentials from `GET /api/voice/turn-credentials`.
+//!
+//! States live in one Redis hash per guild, keyed by user, plus a key per
+//! user naming the guild they are connected in, so a user is in at most one
+//! voice channel at a time. A state belongs to the device that joined; that
+//! device dropping off the gateway leaves the channel. Keys expire after
+//! [`STATE_TTL`] without a change, which bounds what a crashed node leaves
+//! behind.
+
+use std::time::Duration;
+
+use fred::interfaces::{HashesInterface, KeysInterface};
+use fred::types::Expiration;
+use openconv_shared::api::voice::{VoiceState, VOICE_CHANNEL_TYPE};
+use openconv_shared::ids::{ChannelId, DeviceId, GuildId, UserId};
+use openconv_shared::permissions::Permissions;
+use serde::{Deserialize, Serialize};
+
+use crate::redis::RedisPool;
+use crate::state::AppState;
+
+use super::connection::send_error;
+use super::fanout::{check_permission, handle_permission_error};
+use super::types::{error_codes, ServerMessage};
+
+/// How long a voice state survives without a change.
+pub const STATE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn guild_key(guild_id: GuildId) -> String {
+    format!("voice:guild:{guild_id}")
+}
+
+fn user_key(user_id: UserId) -> String {
+    format!("voice:user:{user_id}")
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredVoiceState {
+    device_id: DeviceId,
+    #[serde(flatten)]
+    state: VoiceState,
+}
+
+/// Members currently in a voice channel of a guild.
+pub async fn list(
+    redis: &RedisPool,
+    guild_id: GuildId,
+) -> Result<Vec<VoiceState>, fred::error::Error> {
+    let values: Vec<String> = redis.hvals(redis.key(guild_key(guild_id))).await?;
+    Ok(values
+        .iter()
+        .filter_map(|v| serde_json::from_str::<StoredVoiceState>(v).ok())
+        .map(|stored| stored.state)
+        .collect())
+}
+
+async fn get(
+    redis: &RedisPool,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<Option<StoredVoiceState>, fred::error::Error> {
+    let value: Option<String> = redis
+        .hget(redis.key(guild_key(guild_id)), user_id.to_string())
+        .await?;
+    Ok(value.and_then(|v| serde_json::from_str(&v).ok()))
+}
+
+/// The user's current state, wherever they are connected.
+async fn current(
+    redis: &RedisPool,
+    user_id: UserId,
+) -> Result<Option<StoredVoiceState>, fred::error::Error> {
+    let guild_id: Option<String> = redis.get(redis.key(user_key(user_id))).await?;
+    match guild_id.and_then(|g| g.parse().ok()) {
+        Some(guild_id) => get(redis, guild_id, user_id).await,
+        None => Ok(None),
+    }
+}
+
+async fn put(redis: &RedisPool, stored: &StoredVoiceState) -> Result<(), fred::error::Error> {
+    // Separate commands, not a script: the two keys may sit on different
+    // cluster nodes
+    let state = &stored.state;
+    let ttl = STATE_TTL.as_secs() as i64;
+    let guild = redis.key(guild_key(state.guild_id));
+    let value = serde_json::to_string(stored).expect("voice state serializes");
+    let _: i64 = redis
+        .hset(&guild, (state.user_id.to_string(), value))
+        .await?;
+    let _: i64 = redis.expire(&guild, ttl, None).await?;
+    redis
+        .set::<(), _, _>(
+            redis.key(user_key(state.user_id)),
+            state.guild_id.to_string(),
+            Some(Expiration::EX(ttl)),
+            None,
+            false,
+        )
+        .await
+}
+
+async fn remove(
+    redis: &RedisPool,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<(), fred::error::Error> {
+    let _: i64 = redis
+        .hdel(redis.key(guild_key(guild_id)), user_id.to_string())
+        .await?;
+    let _: i64 = redis.del(redis.key(user_key(user_id))).await?;
+    Ok(())
+}
+
+fn broadcast(state: &AppState, voice_state: VoiceState) {
+    if let Some(sender) = state.ws.guilds.get(&voice_state.guild_id) {
+        let _ = sender.send(ServerMessage::VoiceStateUpdate { state: voice_state }.into());
+    }
+}
+
+/// Remove a stored state and tell the guild the member left.
+async fn leave(state: &AppState, stored: StoredVoiceState) -> Result<(), fred::error::Error> {
+    let VoiceState {
+        guild_id, user_id, ..
+    } = stored.state;
+    remove(&state.redis, guild_id, user_id).await?;
+    broadcast(
+        state,
+        VoiceState {
+            channel_id: None,
+            ..stored.state
+        },
+    );
+    Ok(())
+}
+
+fn redis_error(state: &AppState, user_id: UserId, device_id: DeviceId, e: fred::error::Error) {
+    tracing::error!(user_id = %user_id, error = %e, "voice state update failed");
+    send_error(state, user_id, device_id, 4004, "internal error");
+}
+
+async fn resolve_voice_channel(db: &sqlx::PgPool, channel_id: ChannelId) -> Option<GuildId> {
+    sqlx::query_scalar("SELECT guild_id FROM channels WHERE id = $1 AND channel_type = $2")
+        .bind(channel_id)
+        .bind(VOICE_CHANNEL_TYPE)
+        .fetch_optional(db)
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Handle JoinVoice: requires CONNECT, plus SPEAK to join unmuted. Leaves
+/// the voice channel the user was in before, if any.
+pub async fn handle_join(
+    state: &AppState,
+    user_id: UserId,
+    device_id: DeviceId,
+    channel_id: ChannelId,
+    self_mute: bool,
+    self_deaf: bool,
+) {
+    let Some(guild_id) = resolve_voice_channel(&state.db, channel_id).await else {
+        send_error(state, user_id, device_id, 4007, "voice channel not found");
+        return;
+    };
+    let perms = match check_permission(state, user_id, guild_id, Permissions::CONNECT).await {
+        Ok(perms) => perms,
+        Err(e) => {
+            handle_permission_error(state, user_id, device_id, e);
+            return;
+        }
+    };
+    if !self_mute && !perms.contains(Permissions::SPEAK) {
+        send_error(
+            state,
+            user_id,
+            device_id,
+            error_codes::PERMISSION_DENIED,
+            "permission denied",
+        );
+        return;
+    }
+
+    let result: Result<(), fred::error::Error> = async {
+        if let Some(previous) = current(&state.redis, user_id).await? {
+            // Moving within a guild is announced by the new state alone.
+            if previous.state.guild_id != guild_id {
+                leave(state, previous).await?;
+            }
+        }
+        let stored = StoredVoiceState {
+            device_id,
+            state: VoiceState {
+                guild_id,
+                user_id,
+                channel_id: Some(channel_id),
+                self_mute,
+                self_deaf,
+            },
+        };
+        put(&state.redis, &stored).await?;
+        broadcast(state, stored.state);
+        Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        redis_error(state, user_id, device_id, e);
+    }
+}
+
+/// Handle LeaveVoice from any of the user's devices.
+pub async fn handle_leave(state: &AppState, user_id: UserId, device_id: DeviceId) {
+    let result: Result<bool, fred::error::Error> = async {
+        match current(&state.redis, user_id).await? {
+            Some(stored) => leave(state, stored).await.map(|()| true),
+            None => Ok(false),
+        }
+    }
+    .await;
+    match result {
+        Ok(true) => {}
+        Ok(false) => send_error(
+            state,
+            user_id,
+            device_id,
+            error_codes::NOT_IN_VOICE,
+            "not in a voice channel",
+        ),
+        Err(e) => redis_error(state, user_id, device_id, e),
+    }
+}
+
+/// Handle SetVoiceState: unmuting requires SPEAK.
+pub async fn handle_set_state(
+    state: &AppState,
+    user_id: UserId,
+    device_id: DeviceId,
+    self_mute: bool,
+    self_deaf: bool,
+) {
+    let mut stored = match current(&state.redis, user_id).await {
+        Ok(Some(stored)) => stored,
+        Ok(None) => {
+            send_error(
+                state,
+                user_id,
+                device_id,
+                error_codes::NOT_IN_VOICE,
+                "not in a voice channel",
+            );
+            return;
+        }
+        Err(e) => {
+            redis_error(state, user_id, device_id, e);
+            return;
+        }
+    };
+    if !self_mute {
+        if let Err(e) =
+            check_permission(state, user_id, stored.state.guild_id, Permissions::SPEAK).await
+        {
+            handle_permission_error(state, user_id, device_id, e);
+            return;
+        }
+    }
+
+    stored.device_id = device_id;
+    stored.state.self_mute = self_mute;
+    stored.state.self_deaf = self_deaf;
+    if let Err(e) = put(&state.redis, &stored).await {
+        redis_error(state, user_id, device_id, e);
+        return;
+    }
+    broadcast(state, stored.state);
+}
+
+/// Leave voice when the device that joined drops off the gateway.
+pub async fn handle_disconnect(state: &AppState, user_id: UserId, device_id: DeviceId) {
+    let result: Result<(), fred::error::Error> = async {
+        match current(&state.redis, user_id).await? {
+            Some(stored) if stored.device_id == device_id => leave(state, stored).await,
+            _ => Ok(()),
+        }
+    }
+    .await;
+    if let Err(e) = result {
+        tracing::warn!(user_id = %user_id, error = %e, "failed to clear voice state on disconnect");
+    }
+}
+
+/// Drop a member's voice state after they left or were removed from the
+/// guild.
+pub async fn clear_member(state: &AppState, guild_id: GuildId, user_id: UserId) {
+    let result: Result<(), fred::error::Error> = async {
+        match get(&state.redis, guild_id, user_id).await? {
+            Some(stored) => leave(state, stored).await,
+            None => Ok(()),
+        }
+    }
+    .await;
+    if let Err(e) = result {
+        tracing::warn!(guild_id = %guild_id, user_id = %user_id, error = %e, "failed to clear voice state");
+    }
+}
+
+/// Disconnect everyone from a voice channel that was deleted.
+pub async fn clear_channel(state: &AppState, guild_id: GuildId, channel_id: ChannelId) {
+    let result: Result<(), fred::error::Error> = async {
+        let values: Vec<String> = state
+            .redis
+            .hvals(state.redis.key(guild_key(guild_id)))
+            .await?;
+        for stored in values
+            .iter()
+            .filter_map(|v| serde_json::from_str::<StoredVoiceState>(v).ok())
+            .filter(|stored| stored.state.channel_id == Some(channel_id))
+        {
+            leave(state, stored).await?;
+        }
+        Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        tracing::warn!(channel_id = %channel_id, error = %e, "failed to clear voice channel");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stored_state_keeps_the_owning_device() {
+        let stored = StoredVoiceState {
+            device_id: DeviceId::new(),
+            state: VoiceState {
+                guild_id: GuildId::new(),
+                user_id: UserId::new(),
+                channel_id: Some(ChannelId::new()),
+                self_mute: false,
+                self_deaf: true,
+            },
+        };
+        let json = serde_json::to_string(&stored).unwrap();
+        let back: StoredVoiceState = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.device_id, stored.device_id);
+        assert_eq!(back.state, stored.state);
+    }
+}
@@ -0,0 +1,616 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use tower::ServiceExt;
+
+use openconv_server::jwt::JwtService;
+use openconv_shared::ids::{DeviceId, UserId};
+use openconv_test_support::{body_json, TestApp, TestRequest};
+
+async fn build_test_app(pool: sqlx::PgPool, admin_id: UserId) -> TestApp {
+    TestApp::builder(pool)
+        .config(|config| config.admin_user_ids = vec![admin_id])
+        .build()
+        .await
+}
+
+/// Create a user, device and stored refresh token. Returns (access_token, refresh_token).
+async fn seed_user(pool: &sqlx::PgPool, jwt: &JwtService, user_id: UserId) -> (String, String) {
+    let device_id = DeviceId::new();
+
+    sqlx::query("INSERT INTO users (id, public_key, email, display_name) VALUES ($1, $2, $3, $4)")
+        .bind(user_id.0)
+        .bind(format!("pk_{}", uuid::Uuid::new_v4()))
+        .bind(format!("{}@example.com", uuid::Uuid::new_v4()))
+        .bind("Test User")
+        .execute(pool)
+        .await
+        .unwrap();
+
+    sqlx::query(
+        "INSERT INTO devices (id, user_id, device_name, last_active, created_at) VALUES ($1, $2, $3, NOW(), NOW())",
+    )
+    .bind(device_id.0)
+    .bind(user_id.0)
+    .bind("Test Device")
+    .execute(pool)
+    .await
+    .unwrap();
+
+    let family = uuid::Uuid::now_v7().to_string();
+    let access_token = jwt.issue_access_token(&user_id, &device_id).unwrap();
+    let (refresh_token, jti_str) = jwt
+        .issue_refresh_token(&user_id, &device_id, &family)
+        .unwrap();
+
+    sqlx::query(
+        "INSERT INTO refresh_tokens (jti, user_id, device_id, family, expires_at, is_used) VALUES ($1, $2, $3, $4, $5, false)",
+    )
+    .bind(jti_str.parse::<uuid::Uuid>().unwrap())
+    .bind(user_id.0)
+    .bind(device_id.0)
+    .bind(family.parse::<uuid::Uuid>().unwrap())
+    .bind(chrono::Utc::now() + jwt.refresh_ttl())
+    .execute(pool)
+    .await
+    .unwrap();
+
+    (access_token, refresh_token)
+}
+
+fn request(method: &str, uri: &str, token: &str, body: Option<serde_json::Value>) -> Request<Body> {
+    let request = TestRequest::new(method.parse().unwrap(), uri).token(token);
+    match body {
+        Some(body) => request.json(&body).build(),
+        None => request.build(),
+    }
+}
+
+fn bridge_request(
+    method: &str,
+    uri: &str,
+    token: &str,
+    puppet: Option<&str>,
+    body: Option<serde_json::Value>,
+) -> Request<Body> {
+    let mut request = TestRequest::new(method.parse().unwrap(), uri)
+        .header("Authorization", &format!("Bridge {token}"));
+    if let Some(puppet) = puppet {
+        request = request.header("X-Bridge-Puppet", puppet);
+    }
+    match body {
+        Some(body) => request.json(&body).build(),
+        None => request.build(),
+    }
+}
+
+#[sqlx::test]
+async fn suspension_requires_instance_admin(pool: sqlx::PgPool) {
+    let admin_id = UserId::new();
+    let TestApp { app, jwt, .. } = build_test_app(pool.clone(), admin_id).await;
+    let user_a = UserId::new();
+    let user_b = UserId::new();
+    let (token_a, _) = seed_user(&pool, &jwt, user_a).await;
+    seed_user(&pool, &jwt, user_b).await;
+
+    let uri = format!("/api/admin/users/{user_b}/suspension");
+    let resp = app
+        .oneshot(request("PUT", &uri, &token_a, Some(serde_json::json!({}))))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn suspended_user_loses_access_and_cannot_refresh(pool: sqlx::PgPool) {
+    let admin_id = UserId::new();
+    let TestApp { app, jwt, .. } = build_test_app(pool.clone(), admin_id).await;
+    let (admin_token, _) = seed_user(&pool, &jwt, admin_id).await;
+    let user_id = UserId::new();
+    let (user_token, refresh_token) = seed_user(&pool, &jwt, user_id).await;
+
+    let uri = format!("/api/admin/users/{user_id}/suspension");
+    let resp = app
+        .clone()
+        .oneshot(request(
+            "PUT",
+            &uri,
+            &admin_token,
+            Some(serde_json::json!({ "reason": "spam" })),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = body_json(resp).await;
+    assert_eq!(json["suspended"], true);
+    assert_eq!(json["reason"], "spam");
+    assert_eq!(json["suspended_by"], admin_id.to_string());
+
+    // Existing access token is rejected
+    let resp = app
+        .clone()
+        .oneshot(request("GET", "/api/users/me", &user_token, None))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // Refresh tokens are gone; refresh fails with the generic error
+    let remaining: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM refresh_tokens WHERE user_id = $1")
+            .bind(user_id.0)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(remaining, 0);
+
+    let resp = app
+        .clone()
+        .oneshot(request(
+            "POST",
+            "/api/auth/refresh",
+            "",
+            Some(serde_json::json!({ "refresh_token": refresh_token })),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // Suspending again conflicts
+    let resp = app
+        .clone()
+        .oneshot(request(
+            "PUT",
+            &uri,
+            &admin_token,
+            Some(serde_json::json!({})),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+}
+
+#[sqlx::test]
+async fn unsuspend_restores_access(pool: sqlx::PgPool) {
+    let admin_id = UserId::new();
+    let TestApp { app, jwt, .. } = build_test_app(pool.clone(), admin_id).await;
+    let (admin_token, _) = seed_user(&pool, &jwt, admin_id).await;
+    let user_id = UserId::new();
+    seed_user(&pool, &jwt, user_id).await;
+
+    let uri = format!("/api/admin/users/{user_id}/suspension");
+    let resp = app
+        .clone()
+        .oneshot(request(
+            "PUT",
+            &uri,
+            &admin_token,
+            Some(serde_json::json!({})),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app
+        .clone()
+        .oneshot(request("DELETE", &uri, &admin_token, None))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let resp = app
+        .clone()
+        .oneshot(request("GET", &uri, &admin_token, None))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = body_json(resp).await;
+    assert_eq!(json["suspended"], false);
+
+    // A freshly issued token works again
+    let device_id: uuid::Uuid = sqlx::query_scalar("SELECT id FROM devices WHERE user_id = $1")
+        .bind(user_id.0)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let token = jwt
+        .issue_access_token(&user_id, &DeviceId(device_id))
+        .unwrap();
+    let resp = app
+        .clone()
+        .oneshot(request("GET", "/api/users/me", &token, None))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Unsuspending a user who is not suspended is a 404
+    let resp = app
+        .oneshot(request("DELETE", &uri, &admin_token, None))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn admin_cannot_suspend_themselves(pool: sqlx::PgPool) {
+    let admin_id = UserId::new();
+    let TestApp { app, jwt, .. } = build_test_app(pool.clone(), admin_id).await;
+    let (admin_token, _) = seed_user(&pool, &jwt, admin_id).await;
+
+    let uri = format!("/api/admin/users/{admin_id}/suspension");
+    let resp = app
+        .oneshot(request(
+            "PUT",
+            &uri,
+            &admin_token,
+            Some(serde_json::json!({})),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn detailed_health_lists_every_dependency_for_admins(pool: sqlx::PgPool) {
+    let admin_id = UserId::new();
+    let TestApp { app, jwt, .. } = build_test_app(pool.clone(), admin_id).await;
+    let (admin_token, _) = seed_user(&pool, &jwt, admin_id).await;
+    let user_id = UserId::new();
+    let (user_token, _) = seed_user(&pool, &jwt, user_id).await;
+
+    let resp = app
+        .clone()
+        .oneshot(request("GET", "/api/admin/health", &user_token, None))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = app
+        .oneshot(request("GET", "/api/admin/health", &admin_token, None))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = body_json(resp).await;
+    assert_eq!(json["status"], "ok");
+    let names: Vec<_> = json["dependencies"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| d["name"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(names, ["database", "redis", "object_store", "smtp"]);
+    assert!(json["dependencies"][0]["latency_ms"].is_u64());
+}
+
+#[sqlx::test]
+async fn job_status_requires_instance_admin(pool: sqlx::PgPool) {
+    let admin_id = UserId::new();
+    let TestApp { app, jwt, .. } = build_test_app(pool.clone(), admin_id).await;
+    let (admin_token, _) = seed_user(&pool, &jwt, admin_id).await;
+    let user_id = UserId::new();
+    let (user_token, _) = seed_user(&pool, &jwt, user_id).await;
+
+    let resp = app
+        .clone()
+        .oneshot(request("GET", "/api/admin/jobs", &user_token, None))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // No scheduler runs in tests, so nothing is registered
+    let resp = app
+        .oneshot(request("GET", "/api/admin/jobs", &admin_token, None))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(body_json(resp).await, serde_json::json!([]));
+}
+
+#[sqlx::test]
+async fn config_reload_requires_instance_admin(pool: sqlx::PgPool) {
+    let admin_id = UserId::new();
+    let TestApp { app, jwt, .. } = build_test_app(pool.clone(), admin_id).await;
+    let user_id = UserId::new();
+    let (user_token, _) = seed_user(&pool, &jwt, user_id).await;
+
+    let resp = app
+        .oneshot(request(
+            "POST",
+            "/api/admin/config/reload",
+            &user_token,
+            None,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn ip_denylist_blocks_requests_until_the_entry_is_removed(pool: sqlx::PgPool) {
+    let admin_id = UserId::new();
+    let TestApp {
+        app, jwt, state, ..
+    } = build_test_app(pool.clone(), admin_id).await;
+    let (admin_token, _) = seed_user(&pool, &jwt, admin_id).await;
+    let user_id = UserId::new();
+    let (user_token, _) = seed_user(&pool, &jwt, user_id).await;
+    let body = serde_json::json!({"cidr": "203.0.113.77/24", "reason": "credential stuffing"});
+
+    let resp = app
+        .clone()
+        .oneshot(request(
+            "POST",
+            "/api/admin/ip-denylist",
+            &user_token,
+            Some(body.clone()),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = app
+        .clone()
+        .oneshot(request(
+            "POST",
+            "/api/admin/ip-denylist",
+            &admin_token,
+            Some(serde_json::json!({"cidr": "203.0.113.0/33"})),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = app
+        .clone()
+        .oneshot(request(
+            "POST",
+            "/api/admin/ip-denylist",
+            &admin_token,
+            Some(body.clone()),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let entry = body_json(resp).await;
+    assert_eq!(entry["cidr"], "203.0.113.0/24");
+    let entry_id = entry["id"].as_str().unwrap().to_string();
+
+    let resp = app
+        .clone()
+        .oneshot(request(
+            "POST",
+            "/api/admin/ip-denylist",
+            &admin_token,
+            Some(serde_json::json!({"cidr": "203.0.113.0/24"})),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let from_denied = || {
+        TestRequest::get("/api/users/me")
+            .token(&user_token)
+            .header("x-forwarded-for", "203.0.113.5")
+            .build()
+    };
+    let resp = app.clone().oneshot(from_denied()).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = app.clone().oneshot(from_denied()).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // Other addresses are unaffected
+    let resp = app
+        .clone()
+        .oneshot(request("GET", "/api/users/me", &user_token, None))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    assert_eq!(state.ip_denylist.flush_blocked(&pool).await.unwrap(), 2);
+    let resp = app
+        .clone()
+        .oneshot(request(
+            "GET",
+            "/api/admin/ip-denylist/blocks",
+            &admin_token,
+            None,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let blocks = body_json(resp).await;
+    assert_eq!(blocks[0]["ip"], "203.0.113.5");
+    assert_eq!(blocks[0]["cidr"], "203.0.113.0/24");
+    assert_eq!(blocks[0]["attempts"], 2);
+
+    let uri = format!("/api/admin/ip-denylist/{entry_id}");
+    let resp = app
+        .clone()
+        .oneshot(request("DELETE", &uri, &admin_token, None))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let resp = app.clone().oneshot(from_denied()).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app
+        .oneshot(request("GET", "/api/admin/ip-denylist", &admin_token, None))
+        .await
+        .unwrap();
+    assert_eq!(body_json(resp).await["entries"], serde_json::json!([]));
+}
+
+#[sqlx::test]
+async fn bridge_puppets_act_through_the_regular_api(pool: sqlx::PgPool) {
+    let admin_id = UserId::new();
+    let TestApp { app, jwt, .. } = build_test_app(pool.clone(), admin_id).await;
+    let (admin_token, _) = seed_user(&pool, &jwt, admin_id).await;
+    let (user_token, _) = seed_user(&pool, &jwt, UserId::new()).await;
+
+    let body = serde_json::json!({ "name": "IRC Bridge", "namespace": "irc" });
+    let resp = app
+        .clone()
+        .oneshot(request(
+            "POST",
+            "/api/admin/bridges",
+            &user_token,
+            Some(body.clone()),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = app
+        .clone()
+        .oneshot(request(
+            "POST",
+            "/api/admin/bridges",
+            &admin_token,
+            Some(body.clone()),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let bridge = body_json(resp).await;
+    let bridge_id = bridge["id"].as_str().unwrap().to_string();
+    let bridge_token = bridge["token"].as_str().unwrap().to_string();
+
+    let resp = app
+        .clone()
+        .oneshot(request(
+            "POST",
+            "/api/admin/bridges",
+            &admin_token,
+            Some(body),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    // Creating a puppet is idempotent per remote ID
+    let uri = "/api/bridge/puppets/alice%21a%40irc.example";
+    let resp = app
+        .clone()
+        .oneshot(bridge_request(
+            "PUT",
+            uri,
+            &bridge_token,
+            None,
+            Some(serde_json::json!({ "display_name": "alice" })),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let puppet = body_json(resp).await;
+    assert_eq!(puppet["remote_id"], "alice!a@irc.example");
+    let puppet_id = puppet["user_id"].as_str().unwrap().to_string();
+
+    let resp = app
+        .clone()
+        .oneshot(bridge_request(
+            "PUT",
+            uri,
+            &bridge_token,
+            None,
+            Some(serde_json::json!({ "display_name": "alice (IRC)" })),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let renamed = body_json(resp).await;
+    assert_eq!(renamed["user_id"], puppet_id.as_str());
+    assert_eq!(renamed["display_name"], "alice (IRC)");
+
+    let resp = app
+        .clone()
+        .oneshot(bridge_request(
+            "GET",
+            &format!("/api/bridge/users/{puppet_id}"),
+            &bridge_token,
+            None,
+            None,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(body_json(resp).await["remote_id"], "alice!a@irc.example");
+
+    // The bridge acts as its puppet on regular endpoints
+    let resp = app
+        .clone()
+        .oneshot(bridge_request(
+            "POST",
+            "/api/guilds",
+            &bridge_token,
+            Some(&puppet_id),
+            Some(serde_json::json!({ "name": "Bridged" })),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(body_json(resp).await["owner_id"], puppet_id.as_str());
+
+    // ...but only for its own puppets, and only with one named
+    let resp = app
+        .clone()
+        .oneshot(bridge_request(
+            "GET",
+            "/api/users/me",
+            &bridge_token,
+            Some(&admin_id.to_string()),
+            None,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = app
+        .clone()
+        .oneshot(bridge_request(
+            "GET",
+            "/api/users/me",
+            &bridge_token,
+            None,
+            None,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // Unregistering the bridge revokes it and its puppets
+    let resp = app
+        .clone()
+        .oneshot(request(
+            "DELETE",
+            &format!("/api/admin/bridges/{bridge_id}"),
+            &admin_token,
+            None,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let resp = app
+        .clone()
+        .oneshot(bridge_request(
+            "GET",
+            "/api/bridge/me",
+            &bridge_token,
+            None,
+            None,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = app
+        .oneshot(bridge_request(
+            "GET",
+            "/api/users/me",
+            &bridge_token,
+            Some(&puppet_id),
+            None,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
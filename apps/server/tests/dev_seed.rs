@@ -0,0 +1,53 @@
+use openconv_server::tasks::dev_seed::{self, SeedOptions};
+use sqlx::PgPool;
+
+/// Seeding inserts the requested rows, and a second run with the same
+/// options inserts nothing.
+#[sqlx::test]
+async fn seed_is_deterministic_and_idempotent(pool: PgPool) {
+    let options = SeedOptions {
+        users: 6,
+        guilds: 2,
+        channels_per_guild: 3,
+        messages_per_channel: 10,
+        seed: 42,
+    };
+
+    let mut conn = pool.acquire().await.unwrap();
+    let report = dev_seed::seed(&mut conn, &options).await.unwrap();
+    assert_eq!(report.users, 6);
+    assert_eq!(report.guilds, 2);
+    assert_eq!(report.channels, 6);
+    assert_eq!(report.invites, 2);
+    assert_eq!(report.messages, 60);
+
+    let again = dev_seed::seed(&mut conn, &options).await.unwrap();
+    assert_eq!(again, dev_seed::SeedReport::default());
+
+    let guild = dev_seed::guild_id(42, 1);
+    let members: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM guild_members WHERE guild_id = $1")
+        .bind(guild)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(members, 6);
+
+    let invite_guild: uuid::Uuid =
+        sqlx::query_scalar("SELECT guild_id FROM guild_invites WHERE code = $1")
+            .bind(dev_seed::invite_code(42, 1))
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(invite_guild, guild.0);
+
+    let owner_roles: Vec<String> = sqlx::query_scalar(
+        "SELECT r.role_type FROM guild_member_roles mr JOIN roles r ON r.id = mr.role_id \
+         WHERE mr.guild_id = $1 AND mr.user_id = $2 ORDER BY r.position",
+    )
+    .bind(guild)
+    .bind(dev_seed::user_id(42, 1))
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(owner_roles, ["member", "owner"]);
+}
@@ -0,0 +1,154 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use tower::ServiceExt;
+
+use openconv_server::config::DiscoveryConfig;
+use openconv_server::discovery::contact_hash;
+use openconv_server::jwt::JwtService;
+use openconv_shared::ids::UserId;
+use openconv_test_support::{
+    self as test_support, body_json, cleanup_redis_keys, TestApp, TestRequest,
+};
+
+const SALT: &str = "discovery-test-salt";
+
+async fn build_test_app(pool: sqlx::PgPool, discovery: DiscoveryConfig) -> TestApp {
+    TestApp::builder(pool)
+        .config(|config| config.discovery = discovery)
+        .build()
+        .await
+}
+
+fn enabled(max_hashes_per_request: usize, daily_hash_budget: u32) -> DiscoveryConfig {
+    DiscoveryConfig {
+        salt: SALT.to_string(),
+        max_hashes_per_request,
+        daily_hash_budget,
+    }
+}
+
+/// Create a user with the given email. Returns its ID and access token.
+async fn seed_user(pool: &sqlx::PgPool, jwt: &JwtService, email: &str) -> (UserId, String) {
+    let (user_id, _, token) = test_support::seed_user(pool, jwt, "Test User", email).await;
+    (user_id, token)
+}
+
+fn unique_email() -> String {
+    format!("{}@example.com", uuid::Uuid::new_v4())
+}
+
+fn hash(email: &str) -> String {
+    hex::encode(contact_hash(SALT, email))
+}
+
+fn discover(token: &str, hashes: &[String]) -> Request<Body> {
+    TestRequest::post("/api/discovery")
+        .token(token)
+        .json(&serde_json::json!({ "hashes": hashes }))
+        .build()
+}
+
+#[sqlx::test]
+async fn discovery_matches_verified_accounts_only(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = build_test_app(pool.clone(), enabled(100, 0)).await;
+    let own_email = unique_email();
+    let (_, token) = seed_user(&pool, &jwt, &own_email).await;
+    let friend_email = unique_email();
+    let (friend_id, _) = seed_user(&pool, &jwt, &friend_email).await;
+    let suspended_email = unique_email();
+    let (suspended_id, _) = seed_user(&pool, &jwt, &suspended_email).await;
+    sqlx::query("UPDATE users SET suspended_at = NOW() WHERE id = $1")
+        .bind(suspended_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    openconv_server::repo::users::refresh_discovery_hashes(&pool, SALT, None)
+        .await
+        .unwrap();
+
+    let resp = app
+        .clone()
+        .oneshot(TestRequest::get("/api/discovery").token(&token).build())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = body_json(resp).await;
+    assert_eq!(body["salt"], SALT);
+    assert_eq!(body["hash_bytes"], 10);
+
+    let hashes = vec![
+        hash(&friend_email.to_uppercase()),
+        hash(&own_email),
+        hash(&suspended_email),
+        hash("nobody@example.com"),
+    ];
+    let resp = app.oneshot(discover(&token, &hashes)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = body_json(resp).await;
+    let matches = body["matches"].as_array().unwrap();
+    assert_eq!(matches.len(), 1, "{body}");
+    assert_eq!(matches[0]["hash"], hash(&friend_email));
+    assert_eq!(matches[0]["user"]["id"], friend_id.to_string());
+    assert!(matches[0]["user"].get("email").is_none());
+}
+
+#[sqlx::test]
+async fn discovery_enforces_batch_size_and_daily_budget(pool: sqlx::PgPool) {
+    let TestApp {
+        app, jwt, redis, ..
+    } = build_test_app(pool.clone(), enabled(3, 4)).await;
+    let (user_id, token) = seed_user(&pool, &jwt, &unique_email()).await;
+    let budget_key = format!("rl:discovery:{user_id}");
+    cleanup_redis_keys(&redis, &[&budget_key]).await;
+
+    let hashes: Vec<String> = (0..4).map(|i| hash(&format!("{i}@example.com"))).collect();
+    let resp = app
+        .clone()
+        .oneshot(discover(&token, &hashes))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = app
+        .clone()
+        .oneshot(discover(&token, &["abcd".to_string()]))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // Duplicates count once
+    let batch = vec![hashes[0].clone(), hashes[0].clone(), hashes[1].clone()];
+    let resp = app.clone().oneshot(discover(&token, &batch)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app
+        .clone()
+        .oneshot(discover(&token, &hashes[..3]))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let resp = app.oneshot(discover(&token, &hashes[2..3])).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    cleanup_redis_keys(&redis, &[&budget_key]).await;
+}
+
+#[sqlx::test]
+async fn discovery_is_off_without_a_salt(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = build_test_app(pool.clone(), DiscoveryConfig::default()).await;
+    let (_, token) = seed_user(&pool, &jwt, &unique_email()).await;
+
+    let resp = app
+        .clone()
+        .oneshot(discover(&token, &[hash("a@example.com")]))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = app
+        .oneshot(TestRequest::get("/api/discovery").token(&token).build())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
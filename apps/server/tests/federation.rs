@@ -0,0 +1,223 @@
+#![cfg(feature = "federation")]
+
+use axum::http::StatusCode;
+use tower::ServiceExt;
+
+use openconv_server::config::FederationConfig;
+use openconv_server::federation::{inbound, signing::FederationKeys};
+use openconv_shared::federation::{FederationEvent, RemoteUserId, ServerName, Transaction};
+use openconv_shared::ids::{GuildId, UserId};
+use openconv_test_support::{
+    authed_delete, authed_get, authed_post, body_json, seed_test_user, TestApp, TestRequest,
+    TEST_PRIVATE_KEY_PEM, TEST_PUBLIC_KEY_PEM,
+};
+
+fn federation_config(server_name: &str) -> FederationConfig {
+    FederationConfig {
+        enabled: true,
+        server_name: server_name.to_string(),
+        signing_key_pem: TEST_PRIVATE_KEY_PEM.to_string(),
+        public_key_pem: TEST_PUBLIC_KEY_PEM.to_string(),
+        trusted_servers: vec!["b.example".to_string()],
+        ..Default::default()
+    }
+}
+
+fn transaction(events: Vec<FederationEvent>) -> Transaction {
+    Transaction {
+        iss: "b.example".parse().unwrap(),
+        aud: "a.example".parse().unwrap(),
+        iat: 0,
+        exp: u64::MAX,
+        jti: uuid::Uuid::new_v4(),
+        events,
+    }
+}
+
+#[sqlx::test]
+async fn remote_users_join_and_leave_with_invites(pool: sqlx::PgPool) {
+    let TestApp { app, state, .. } = TestApp::builder(pool.clone())
+        .config(|config| config.federation = federation_config("a.example"))
+        .build()
+        .await;
+    let (_, owner_token) = seed_test_user(&pool, &state.jwt).await;
+
+    let resp = app
+        .clone()
+        .oneshot(authed_post(
+            "/api/guilds",
+            &owner_token,
+            serde_json::json!({ "name": "Hosted" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let guild_id: GuildId = body_json(resp).await["id"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    let resp = app
+        .clone()
+        .oneshot(authed_post(
+            &format!("/api/guilds/{guild_id}/invites"),
+            &owner_token,
+            serde_json::json!({}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let code = body_json(resp).await["code"].as_str().unwrap().to_string();
+
+    let origin: ServerName = "b.example".parse().unwrap();
+    let remote = RemoteUserId {
+        user_id: UserId::new(),
+        server: origin.clone(),
+    };
+    // A server may not speak for another server's users.
+    let impostor = RemoteUserId {
+        user_id: UserId::new(),
+        server: "c.example".parse().unwrap(),
+    };
+    let join = transaction(vec![
+        FederationEvent::JoinRequest {
+            user: remote.clone(),
+            display_name: "Remote".into(),
+            invite_code: code.clone(),
+        },
+        FederationEvent::JoinRequest {
+            user: impostor,
+            display_name: "Impostor".into(),
+            invite_code: code,
+        },
+    ]);
+    let replay = join.clone();
+    assert_eq!(inbound::apply(&state, &origin, join).await.unwrap(), 1);
+    assert_eq!(inbound::apply(&state, &origin, replay).await.unwrap(), 0);
+
+    let stand_in: UserId = sqlx::query_scalar(
+        "SELECT user_id FROM federated_users WHERE server_name = 'b.example' AND remote_user_id = $1",
+    )
+    .bind(remote.user_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    let is_member: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM guild_members WHERE guild_id = $1 AND user_id = $2)",
+    )
+    .bind(guild_id)
+    .bind(stand_in)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert!(is_member);
+
+    // The join is forwarded to the remote user's home instance.
+    let queued: Vec<serde_json::Value> = sqlx::query_scalar(
+        "SELECT events FROM federation_outbox WHERE destination = 'b.example' ORDER BY created_at",
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0][0]["type"], "member_joined");
+    assert_eq!(queued[0][0]["user"], remote.to_string());
+
+    let leave = transaction(vec![FederationEvent::LeaveRequest {
+        user: remote.clone(),
+        guild_id,
+    }]);
+    assert_eq!(inbound::apply(&state, &origin, leave).await.unwrap(), 1);
+    let is_member: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM guild_members WHERE guild_id = $1 AND user_id = $2)",
+    )
+    .bind(guild_id)
+    .bind(stand_in)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert!(!is_member);
+}
+
+#[sqlx::test]
+async fn host_reports_update_local_users_remote_guilds(pool: sqlx::PgPool) {
+    let TestApp { app, state, .. } = TestApp::builder(pool.clone())
+        .config(|config| config.federation = federation_config("a.example"))
+        .build()
+        .await;
+    let (user_id, token) = seed_test_user(&pool, &state.jwt).await;
+
+    let origin: ServerName = "b.example".parse().unwrap();
+    let guild_id = GuildId::new();
+    let user = RemoteUserId {
+        user_id,
+        server: "a.example".parse().unwrap(),
+    };
+    let joined = transaction(vec![FederationEvent::MemberJoined {
+        guild_id,
+        guild_name: "Elsewhere".into(),
+        user: user.clone(),
+    }]);
+    assert_eq!(inbound::apply(&state, &origin, joined).await.unwrap(), 1);
+
+    let resp = app
+        .clone()
+        .oneshot(authed_get("/api/federation/guilds", &token))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = body_json(resp).await;
+    assert_eq!(body["guilds"][0]["server"], "b.example");
+    assert_eq!(body["guilds"][0]["guild_name"], "Elsewhere");
+
+    let resp = app
+        .clone()
+        .oneshot(authed_delete(
+            &format!("/api/federation/guilds/b.example/{guild_id}"),
+            &token,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let queued: serde_json::Value =
+        sqlx::query_scalar("SELECT events FROM federation_outbox WHERE destination = 'b.example'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(queued[0]["type"], "leave_request");
+}
+
+#[sqlx::test]
+async fn transactions_from_untrusted_servers_are_refused(pool: sqlx::PgPool) {
+    let TestApp { app, .. } = TestApp::builder(pool)
+        .config(|config| config.federation = federation_config("a.example"))
+        .build()
+        .await;
+
+    let resp = app
+        .clone()
+        .oneshot(TestRequest::get("/_openconv/federation/v1/keys").build())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(body_json(resp).await["server_name"], "a.example");
+
+    let untrusted = FederationKeys::from_config(&federation_config("c.example")).unwrap();
+    let token = untrusted
+        .sign(
+            &"a.example".parse().unwrap(),
+            uuid::Uuid::new_v4(),
+            Vec::new(),
+        )
+        .unwrap();
+    let resp = app
+        .oneshot(
+            TestRequest::post("/_openconv/federation/v1/transactions")
+                .header("Content-Type", "application/jwt")
+                .body(token)
+                .build(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
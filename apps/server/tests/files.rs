@@ -0,0 +1,812 @@
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use tower::ServiceExt;
+
+use openconv_server::config::FileStorageConfig;
+use openconv_test_support::{
+    add_member, authed_get, authed_post, authed_put, body_json, create_channel_via_api,
+    create_guild_via_api, seed_user, TestApp,
+};
+
+async fn build_test_app(pool: sqlx::PgPool, file_storage: FileStorageConfig) -> TestApp {
+    TestApp::builder(pool)
+        .config(|config| config.file_storage = file_storage)
+        .build()
+        .await
+}
+
+const BOUNDARY: &str = "openconv-test-boundary";
+
+fn multipart_upload(token: &str, bytes: &[u8], mime_type: &str) -> Request<Body> {
+    multipart_upload_to("/api/files", token, bytes, mime_type)
+}
+
+fn multipart_upload_to(uri: &str, token: &str, bytes: &[u8], mime_type: &str) -> Request<Body> {
+    let mut body = Vec::new();
+    for (name, value) in [
+        ("file_name", "photo.bin"),
+        ("mime_type", mime_type),
+        ("encrypted_blob_key", "a2V5"),
+    ] {
+        body.extend_from_slice(
+            format!(
+                "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+            )
+            .as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"blob\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n"
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(bytes);
+    body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+
+    Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header(
+            "Content-Type",
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        )
+        .header("Authorization", format!("Bearer {token}"))
+        .body(Body::from(body))
+        .unwrap()
+}
+
+async fn insert_message(
+    pool: &sqlx::PgPool,
+    channel_id: &str,
+    sender_id: openconv_shared::ids::UserId,
+) -> uuid::Uuid {
+    sqlx::query_scalar(
+        "INSERT INTO messages (channel_id, sender_id, encrypted_content, nonce) \
+         VALUES ($1, $2, $3, $4) RETURNING id",
+    )
+    .bind(channel_id.parse::<uuid::Uuid>().unwrap())
+    .bind(sender_id.0)
+    .bind(b"ciphertext".to_vec())
+    .bind(b"nonce".to_vec())
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+// ─── Standalone upload ─────────────────────────────────────
+
+#[sqlx::test]
+async fn standalone_upload_is_private_until_attached(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = build_test_app(pool.clone(), FileStorageConfig::default()).await;
+    let (owner, _, token_owner) = seed_user(&pool, &jwt, "Owner", "owner@test.com").await;
+    let (member, _, token_member) = seed_user(&pool, &jwt, "Member", "member@test.com").await;
+
+    let guild = create_guild_via_api(&app, &token_owner, "Files Guild").await;
+    let guild_id = guild["id"].as_str().unwrap();
+    add_member(&pool, member, guild_id.parse().unwrap()).await;
+    let channel = create_channel_via_api(&app, &token_owner, guild_id, "files").await;
+    let channel_id = channel["id"].as_str().unwrap();
+
+    let payload = vec![7u8; 200_000];
+    let resp = app
+        .clone()
+        .oneshot(multipart_upload(&token_owner, &payload, "image/png"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let file = body_json(resp).await;
+    let file_id = file["id"].as_str().unwrap().to_string();
+    assert_eq!(file["size_bytes"], 200_000);
+
+    // Uploader can download; other members cannot yet
+    let resp = app
+        .clone()
+        .oneshot(authed_get(&format!("/api/files/{file_id}"), &token_owner))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(bytes.as_ref(), payload.as_slice());
+
+    let resp = app
+        .clone()
+        .oneshot(authed_get(&format!("/api/files/{file_id}"), &token_member))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // Attach to the owner's message
+    let message_id = insert_message(&pool, channel_id, owner).await;
+    let attachments_uri = format!("/api/channels/{channel_id}/messages/{message_id}/attachments");
+    let resp = app
+        .clone()
+        .oneshot(authed_put(
+            &attachments_uri,
+            &token_owner,
+            serde_json::json!({ "file_ids": [file_id] }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app
+        .clone()
+        .oneshot(authed_get(&attachments_uri, &token_member))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = body_json(resp).await;
+    assert_eq!(json["files"][0]["id"], file_id.as_str());
+
+    let resp = app
+        .clone()
+        .oneshot(authed_get(&format!("/api/files/{file_id}"), &token_member))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn upload_rejects_disallowed_mime_and_oversized_files(pool: sqlx::PgPool) {
+    let file_storage = FileStorageConfig {
+        max_file_size_bytes: 1024,
+        allowed_mime_types: vec!["image/*".into()],
+        ..Default::default()
+    };
+    let TestApp { app, jwt, .. } = build_test_app(pool.clone(), file_storage).await;
+    let (_, _, token) = seed_user(&pool, &jwt, "Uploader", "uploader@test.com").await;
+
+    let resp = app
+        .clone()
+        .oneshot(multipart_upload(&token, b"zip bytes", "application/zip"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = app
+        .clone()
+        .oneshot(multipart_upload(&token, &[0u8; 4096], "image/png"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM files")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+}
+
+#[sqlx::test]
+async fn only_sender_can_attach_their_own_uploads(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = build_test_app(pool.clone(), FileStorageConfig::default()).await;
+    let (owner, _, token_owner) = seed_user(&pool, &jwt, "Owner", "owner@test.com").await;
+    let (member, _, token_member) = seed_user(&pool, &jwt, "Member", "member@test.com").await;
+
+    let guild = create_guild_via_api(&app, &token_owner, "Files Guild").await;
+    let guild_id = guild["id"].as_str().unwrap();
+    add_member(&pool, member, guild_id.parse().unwrap()).await;
+    let channel = create_channel_via_api(&app, &token_owner, guild_id, "files").await;
+    let channel_id = channel["id"].as_str().unwrap();
+
+    let resp = app
+        .clone()
+        .oneshot(multipart_upload(&token_member, b"member file", "image/png"))
+        .await
+        .unwrap();
+    let member_file = body_json(resp).await["id"].as_str().unwrap().to_string();
+
+    let owner_message = insert_message(&pool, channel_id, owner).await;
+    let member_message = insert_message(&pool, channel_id, member).await;
+
+    // Attaching to someone else's message is forbidden
+    let resp = app
+        .clone()
+        .oneshot(authed_put(
+            &format!("/api/channels/{channel_id}/messages/{owner_message}/attachments"),
+            &token_member,
+            serde_json::json!({ "file_ids": [member_file] }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // Attaching a file uploaded by someone else is rejected
+    let resp = app
+        .clone()
+        .oneshot(authed_put(
+            &format!("/api/channels/{channel_id}/messages/{owner_message}/attachments"),
+            &token_owner,
+            serde_json::json!({ "file_ids": [member_file] }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = app
+        .clone()
+        .oneshot(authed_put(
+            &format!("/api/channels/{channel_id}/messages/{member_message}/attachments"),
+            &token_member,
+            serde_json::json!({ "file_ids": [member_file] }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+// ─── Upload sessions ───────────────────────────────────────
+
+fn put_chunk(uri: &str, token: &str, bytes: &[u8]) -> Request<Body> {
+    Request::builder()
+        .method("PUT")
+        .uri(uri)
+        .header("Content-Type", "application/octet-stream")
+        .header("Authorization", format!("Bearer {token}"))
+        .body(Body::from(bytes.to_vec()))
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn upload_session_resumes_and_completes(pool: sqlx::PgPool) {
+    let file_storage = FileStorageConfig {
+        upload_chunk_size_bytes: 4,
+        ..Default::default()
+    };
+    let TestApp { app, jwt, .. } = build_test_app(pool.clone(), file_storage).await;
+    let (_, _, token) = seed_user(&pool, &jwt, "Uploader", "uploader@test.com").await;
+    let (_, _, token_other) = seed_user(&pool, &jwt, "Other", "other@test.com").await;
+
+    let payload = b"0123456789";
+    let resp = app
+        .clone()
+        .oneshot(authed_post(
+            "/api/files/sessions",
+            &token,
+            serde_json::json!({
+                "file_name": "video.bin",
+                "mime_type": "video/mp4",
+                "encrypted_blob_key": "a2V5",
+                "size_bytes": payload.len(),
+            }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let session = body_json(resp).await;
+    assert_eq!(session["chunk_count"], 3);
+    let session_uri = format!("/api/files/sessions/{}", session["id"].as_str().unwrap());
+
+    // First and last chunk arrive, then the connection drops
+    for (index, chunk) in [(0, &payload[0..4]), (2, &payload[8..10])] {
+        let resp = app
+            .clone()
+            .oneshot(put_chunk(
+                &format!("{session_uri}/chunks/{index}"),
+                &token,
+                chunk,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    // Wrong-sized chunk is rejected
+    let resp = app
+        .clone()
+        .oneshot(put_chunk(&format!("{session_uri}/chunks/1"), &token, b"45"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = app
+        .clone()
+        .oneshot(authed_post(
+            &format!("{session_uri}/complete"),
+            &token,
+            serde_json::json!({}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // Sessions are private to their uploader
+    let resp = app
+        .clone()
+        .oneshot(authed_get(&session_uri, &token_other))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    // Resume: fetch progress and send only the missing chunk
+    let resp = app
+        .clone()
+        .oneshot(authed_get(&session_uri, &token))
+        .await
+        .unwrap();
+    let progress = body_json(resp).await;
+    assert_eq!(progress["received_chunks"], serde_json::json!([0, 2]));
+
+    let resp = app
+        .clone()
+        .oneshot(put_chunk(
+            &format!("{session_uri}/chunks/1"),
+            &token,
+            &payload[4..8],
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app
+        .clone()
+        .oneshot(authed_post(
+            &format!("{session_uri}/complete"),
+            &token,
+            serde_json::json!({}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let file = body_json(resp).await;
+    assert_eq!(file["size_bytes"], 10);
+
+    let resp = app
+        .clone()
+        .oneshot(authed_get(
+            &format!("/api/files/{}", file["id"].as_str().unwrap()),
+            &token,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(bytes.as_ref(), payload);
+
+    // The session is gone once completed
+    let resp = app
+        .clone()
+        .oneshot(authed_get(&session_uri, &token))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+// ─── Signed download URLs ──────────────────────────────────
+
+fn anonymous_get(uri: &str) -> Request<Body> {
+    Request::builder()
+        .method("GET")
+        .uri(uri)
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn download_url_is_signed_and_checks_access(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = build_test_app(pool.clone(), FileStorageConfig::default()).await;
+    let (_, _, token_owner) = seed_user(&pool, &jwt, "Owner", "owner@test.com").await;
+    let (_, _, token_other) = seed_user(&pool, &jwt, "Other", "other@test.com").await;
+
+    let payload = vec![3u8; 4096];
+    let resp = app
+        .clone()
+        .oneshot(multipart_upload(&token_owner, &payload, "image/png"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let file_id = body_json(resp).await["id"].as_str().unwrap().to_string();
+
+    // A user who cannot see the file gets no URL
+    let resp = app
+        .clone()
+        .oneshot(authed_get(
+            &format!("/api/files/{file_id}/download-url"),
+            &token_other,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = app
+        .clone()
+        .oneshot(authed_get(
+            &format!("/api/files/{file_id}/download-url"),
+            &token_owner,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = body_json(resp).await;
+    let url = json["url"].as_str().unwrap().to_string();
+    assert!(url.starts_with(&format!("/api/files/{file_id}/signed?")));
+
+    // The URL works without a bearer token
+    let resp = app.clone().oneshot(anonymous_get(&url)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(bytes.as_ref(), payload.as_slice());
+
+    // Tampering with the expiry invalidates the signature
+    let (path, query) = url.split_once('?').unwrap();
+    let forged: String = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some(("expires", value)) => format!("expires={}", value.parse::<i64>().unwrap() + 3600),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    let resp = app
+        .clone()
+        .oneshot(anonymous_get(&format!("{path}?{forged}")))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn user_quota_rejects_uploads_that_do_not_fit(pool: sqlx::PgPool) {
+    let file_storage = FileStorageConfig {
+        user_quota_bytes: Some(10),
+        upload_chunk_size_bytes: 4,
+        ..Default::default()
+    };
+    let TestApp { app, jwt, .. } = build_test_app(pool.clone(), file_storage).await;
+    let (_, _, token) = seed_user(&pool, &jwt, "Uploader", "uploader@test.com").await;
+
+    let resp = app
+        .clone()
+        .oneshot(multipart_upload(&token, b"012345", "image/png"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let resp = app
+        .clone()
+        .oneshot(multipart_upload(&token, b"012345", "image/png"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let json = body_json(resp).await;
+    assert!(json["error"]
+        .as_str()
+        .unwrap()
+        .contains("storage quota exceeded"));
+
+    // Sessions are refused up front when the declared size cannot fit
+    let resp = app
+        .clone()
+        .oneshot(authed_post(
+            "/api/files/sessions",
+            &token,
+            serde_json::json!({
+                "file_name": "video.bin",
+                "mime_type": "video/mp4",
+                "encrypted_blob_key": "a2V5",
+                "size_bytes": 5
+            }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let resp = app
+        .clone()
+        .oneshot(authed_get("/api/users/me/storage", &token))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = body_json(resp).await;
+    assert_eq!(json["used_bytes"], 6);
+    assert_eq!(json["file_count"], 1);
+    assert_eq!(json["quota_bytes"], 10);
+}
+
+#[sqlx::test]
+async fn guild_quota_counts_channel_uploads(pool: sqlx::PgPool) {
+    let file_storage = FileStorageConfig {
+        guild_quota_bytes: Some(10),
+        ..Default::default()
+    };
+    let TestApp { app, jwt, .. } = build_test_app(pool.clone(), file_storage).await;
+    let (_, _, token_owner) = seed_user(&pool, &jwt, "Owner", "owner@test.com").await;
+    let (member, _, token_member) = seed_user(&pool, &jwt, "Member", "member@test.com").await;
+
+    let guild = create_guild_via_api(&app, &token_owner, "Quota Guild").await;
+    let guild_id = guild["id"].as_str().unwrap();
+    add_member(&pool, member, guild_id.parse().unwrap()).await;
+    let channel = create_channel_via_api(&app, &token_owner, guild_id, "files").await;
+    let upload_uri = format!("/api/channels/{}/files", channel["id"].as_str().unwrap());
+
+    let resp = app
+        .clone()
+        .oneshot(multipart_upload_to(
+            &upload_uri,
+            &token_owner,
+            b"012345",
+            "image/png",
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    // The guild is full for every member, not only the first uploader
+    let resp = app
+        .clone()
+        .oneshot(multipart_upload_to(
+            &upload_uri,
+            &token_member,
+            b"012345",
+            "image/png",
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    // Standalone uploads are not charged to the guild
+    let resp = app
+        .clone()
+        .oneshot(multipart_upload(&token_member, b"012345", "image/png"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let resp = app
+        .clone()
+        .oneshot(authed_get(
+            &format!("/api/guilds/{guild_id}/storage"),
+            &token_member,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = body_json(resp).await;
+    assert_eq!(json["used_bytes"], 6);
+    assert_eq!(json["quota_bytes"], 10);
+}
+
+#[sqlx::test]
+async fn guild_retention_expires_channel_files(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = build_test_app(pool.clone(), FileStorageConfig::default()).await;
+    let (_, _, token) = seed_user(&pool, &jwt, "Owner", "owner@test.com").await;
+
+    let guild = create_guild_via_api(&app, &token, "Retention Guild").await;
+    let guild_id = guild["id"].as_str().unwrap();
+    let channel = create_channel_via_api(&app, &token, guild_id, "files").await;
+    let upload_uri = format!("/api/channels/{}/files", channel["id"].as_str().unwrap());
+
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("PATCH")
+                .uri(format!("/api/guilds/{guild_id}"))
+                .header("Content-Type", "application/json")
+                .header("Authorization", format!("Bearer {token}"))
+                .body(Body::from(r#"{"file_retention_days":30}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app
+        .clone()
+        .oneshot(multipart_upload_to(
+            &upload_uri,
+            &token,
+            b"old",
+            "image/png",
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let file_id = body_json(resp).await["id"].as_str().unwrap().to_string();
+
+    // Standalone uploads are not covered by guild retention
+    let resp = app
+        .clone()
+        .oneshot(multipart_upload(&token, b"mine", "image/png"))
+        .await
+        .unwrap();
+    let standalone_id = body_json(resp).await["id"].as_str().unwrap().to_string();
+
+    let resp = app
+        .clone()
+        .oneshot(authed_get(&format!("/api/files/{file_id}/meta"), &token))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = body_json(resp).await;
+    assert!(json["expires_at"].is_string());
+
+    sqlx::query("UPDATE files SET created_at = NOW() - INTERVAL '31 days'")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // Hidden as soon as it expires, before cleanup runs
+    let resp = app
+        .clone()
+        .oneshot(authed_get(&format!("/api/files/{file_id}/meta"), &token))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let store = object_store::memory::InMemory::new();
+    let deleted = openconv_server::tasks::file_cleanup::cleanup_expired_files(&pool, &store)
+        .await
+        .unwrap();
+    assert_eq!(deleted, 1);
+
+    let remaining: Vec<uuid::Uuid> = sqlx::query_scalar("SELECT id FROM files")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        remaining,
+        vec![standalone_id.parse::<uuid::Uuid>().unwrap()]
+    );
+}
+
+#[sqlx::test]
+async fn identical_uploads_share_one_refcounted_blob(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = build_test_app(pool.clone(), FileStorageConfig::default()).await;
+    let (_, _, token_a) = seed_user(&pool, &jwt, "Alice", "alice@test.com").await;
+    let (_, _, token_b) = seed_user(&pool, &jwt, "Bob", "bob@test.com").await;
+
+    let payload = vec![9u8; 50_000];
+    let mut file_ids = Vec::new();
+    for token in [&token_a, &token_b] {
+        let resp = app
+            .clone()
+            .oneshot(multipart_upload(token, &payload, "image/png"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let file = body_json(resp).await;
+        file_ids.push(file["id"].as_str().unwrap().parse::<uuid::Uuid>().unwrap());
+    }
+
+    let (blobs, ref_count): (i64, i32) =
+        sqlx::query_as("SELECT COUNT(*), MAX(ref_count) FROM blobs")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!((blobs, ref_count), (1, 2));
+
+    // Each uploader still reads the shared bytes through their own file
+    for (token, file_id) in [&token_a, &token_b].into_iter().zip(&file_ids) {
+        let resp = app
+            .clone()
+            .oneshot(authed_get(&format!("/api/files/{file_id}"), token))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(bytes.as_ref(), payload.as_slice());
+    }
+
+    let store = object_store::memory::InMemory::new();
+    sqlx::query("DELETE FROM files WHERE id = $1")
+        .bind(file_ids[0])
+        .execute(&pool)
+        .await
+        .unwrap();
+    let released = openconv_server::tasks::file_cleanup::cleanup_unreferenced_blobs(&pool, &store)
+        .await
+        .unwrap();
+    assert_eq!(released, 0);
+
+    sqlx::query("DELETE FROM files WHERE id = $1")
+        .bind(file_ids[1])
+        .execute(&pool)
+        .await
+        .unwrap();
+    let released = openconv_server::tasks::file_cleanup::cleanup_unreferenced_blobs(&pool, &store)
+        .await
+        .unwrap();
+    assert_eq!(released, 1);
+
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM blobs")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining, 0);
+}
+
+/// Flags every upload, but only once the test lets it through.
+struct GatedFlaggingScanner {
+    gate: tokio::sync::Semaphore,
+}
+
+#[async_trait::async_trait]
+impl openconv_server::scan::UploadScanner for GatedFlaggingScanner {
+    async fn scan(
+        &self,
+        _store: &dyn object_store::ObjectStore,
+        _location: &object_store::path::Path,
+    ) -> Result<openconv_server::scan::ScanVerdict, openconv_shared::error::OpenConvError> {
+        let _permit = self.gate.acquire().await.unwrap();
+        Ok(openconv_server::scan::ScanVerdict::Flagged(
+            "Test-Signature".into(),
+        ))
+    }
+}
+
+#[sqlx::test]
+async fn downloads_wait_for_scan_and_stay_blocked_when_flagged(pool: sqlx::PgPool) {
+    let scanner = Arc::new(GatedFlaggingScanner {
+        gate: tokio::sync::Semaphore::new(0),
+    });
+    let TestApp { app, jwt, .. } = TestApp::builder(pool.clone())
+        .scanner(scanner.clone())
+        .build()
+        .await;
+    let (_, _, token) = seed_user(&pool, &jwt, "Alice", "alice@test.com").await;
+
+    let resp = app
+        .clone()
+        .oneshot(multipart_upload(&token, b"payload", "application/pdf"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let file_id = body_json(resp).await["id"].as_str().unwrap().to_string();
+
+    let resp = app
+        .clone()
+        .oneshot(authed_get(&format!("/api/files/{file_id}/meta"), &token))
+        .await
+        .unwrap();
+    assert_eq!(body_json(resp).await["scan_status"], "pending");
+    let resp = app
+        .clone()
+        .oneshot(authed_get(&format!("/api/files/{file_id}"), &token))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    scanner.gate.add_permits(1);
+    let mut status = String::new();
+    for _ in 0..50 {
+        status = sqlx::query_scalar("SELECT scan_status FROM files WHERE id = $1::UUID")
+            .bind(&file_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        if status != "pending" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(status, "flagged");
+
+    let resp = app
+        .clone()
+        .oneshot(authed_get(&format!("/api/files/{file_id}"), &token))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = app
+        .clone()
+        .oneshot(authed_get(
+            &format!("/api/files/{file_id}/download-url"),
+            &token,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
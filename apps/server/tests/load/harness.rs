@@ -0,0 +1,195 @@
+//! A real server on a local port, and the clients that drive it.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use futures::{SinkExt, StreamExt};
+use openconv_server::config::{RouteClass, RouteRateLimitConfig};
+use openconv_server::extractors::client_info::ClientInfo;
+use openconv_server::jwt::JwtService;
+use openconv_server::repo;
+use openconv_server::tasks::dev_seed::{self, SeedOptions};
+use openconv_shared::api::ws::{ClientMessage, ServerMessage};
+use openconv_shared::ids::{ChannelId, DeviceId};
+use openconv_test_support::TestApp;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Seed used for every scenario's data.
+const SEED: u64 = 0x10AD;
+
+pub struct TestServer {
+    pub base_url: String,
+    pub ws_url: String,
+    pub jwt: Arc<JwtService>,
+    pub http: reqwest::Client,
+}
+
+/// Rate limits high enough that the limiter never answers for the server.
+fn unlimited() -> RouteRateLimitConfig {
+    RouteRateLimitConfig {
+        limit: 1_000_000,
+        window_seconds: 60,
+        key: None,
+        fail_mode: None,
+    }
+}
+
+/// Serve the full router on an ephemeral port until the test ends.
+pub async fn spawn_server(pool: sqlx::PgPool) -> TestServer {
+    let TestApp { app, jwt, .. } = TestApp::builder(pool)
+        .config(|config| {
+            config.rate_limit.routes = HashMap::from([
+                (RouteClass::Auth, unlimited()),
+                (RouteClass::Challenge, unlimited()),
+                (RouteClass::WsTicket, unlimited()),
+            ])
+        })
+        .build()
+        .await;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    TestServer {
+        base_url: format!("http://{addr}"),
+        ws_url: format!("ws://{addr}/ws"),
+        jwt,
+        http: reqwest::Client::builder()
+            .pool_max_idle_per_host(256)
+            .build()
+            .unwrap(),
+    }
+}
+
+/// Seed one guild with `users` members and `channels` channels, give each
+/// member a device, and return the members' access tokens and the channels
+/// (`#main` first).
+pub async fn seed_guild(
+    pool: &sqlx::PgPool,
+    server: &TestServer,
+    users: u32,
+    channels: u32,
+) -> (Vec<String>, Vec<ChannelId>) {
+    let options = SeedOptions {
+        users,
+        guilds: 1,
+        channels_per_guild: channels,
+        messages_per_channel: 0,
+        seed: SEED,
+    };
+    let mut conn = pool.acquire().await.unwrap();
+    dev_seed::seed(&mut conn, &options).await.unwrap();
+
+    let mut tokens = Vec::with_capacity(users as usize);
+    for i in 0..users {
+        let user_id = dev_seed::user_id(SEED, i);
+        let device_id = DeviceId::new();
+        repo::devices::insert(
+            &mut *conn,
+            device_id,
+            user_id,
+            "load",
+            None,
+            &ClientInfo::default(),
+        )
+        .await
+        .unwrap();
+        tokens.push(server.jwt.issue_access_token(&user_id, &device_id).unwrap());
+    }
+    let channels = (0..channels.max(1))
+        .map(|c| dev_seed::channel_id(SEED, 0, c))
+        .collect();
+    (tokens, channels)
+}
+
+/// One gateway connection.
+pub struct Gateway {
+    socket: tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >,
+}
+
+impl Gateway {
+    /// Get a ticket, connect and wait for `Ready`.
+    pub async fn connect(server: &TestServer, token: &str) -> Self {
+        let ticket: serde_json::Value = server
+            .http
+            .post(format!("{}/api/ws/ticket", server.base_url))
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let url = format!(
+            "{}?ticket={}",
+            server.ws_url,
+            ticket["ticket"].as_str().unwrap()
+        );
+        let (socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let mut gateway = Self { socket };
+        match gateway.next().await {
+            ServerMessage::Ready { .. } => gateway,
+            other => panic!("expected Ready, got {other:?}"),
+        }
+    }
+
+    pub async fn send(&mut self, message: &ClientMessage) {
+        let text = serde_json::to_string(message).unwrap();
+        self.socket.send(Message::text(text)).await.unwrap();
+    }
+
+    /// The next server message, skipping presence chatter and unwrapping
+    /// journaled events.
+    pub async fn next(&mut self) -> ServerMessage {
+        loop {
+            let frame = self.socket.next().await.expect("gateway closed").unwrap();
+            if let Message::Text(text) = frame {
+                match serde_json::from_str(&text).unwrap() {
+                    ServerMessage::PresenceUpdate { .. }
+                    | ServerMessage::TypingStarted { .. }
+                    | ServerMessage::ResumeComplete { .. }
+                    | ServerMessage::Sequenced { event: None, .. } => {}
+                    ServerMessage::Sequenced {
+                        event: Some(event), ..
+                    } => return *event,
+                    message => return message,
+                }
+            }
+        }
+    }
+
+    /// Subscribe to a channel. Messages on a connection are handled in
+    /// order, so the subscription is live once the following ping returns.
+    pub async fn subscribe(&mut self, channel_id: ChannelId) {
+        self.send(&ClientMessage::Subscribe { channel_id }).await;
+        self.send(&ClientMessage::Ping { ts: 0 }).await;
+        loop {
+            match self.next().await {
+                ServerMessage::Pong { .. } => return,
+                ServerMessage::Error { code, message } => {
+                    panic!("subscribe failed: {code} {message}")
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub async fn send_message(&mut self, channel_id: ChannelId, payload: &[u8]) {
+        self.send(&ClientMessage::SendMessage {
+            channel_id,
+            encrypted_content: payload.to_vec(),
+            nonce: vec![0; 12],
+            payload_kind: Default::default(),
+            idempotency_key: None,
+            metadata: None,
+        })
+        .await;
+    }
+}
@@ -0,0 +1,261 @@
+//! Load tests: a real server on a local port, driven by concurrent HTTP and
+//! gateway clients, with each scenario checked against `baseline.toml`.
+//!
+//! They need the same Postgres and Redis as the other integration tests and
+//! are ignored by default. Run them on a release build:
+//!
+//! ```sh
+//! cargo test -p openconv-server --release --test load -- --ignored --nocapture --test-threads 1
+//! ```
+
+mod harness;
+mod stats;
+
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use openconv_server::repo;
+use openconv_server::repo::users::NewUser;
+use openconv_shared::api::ws::ServerMessage;
+use openconv_shared::ids::{DeviceId, UserId};
+
+use harness::{seed_guild, spawn_server, Gateway, TestServer};
+use stats::{check, Samples};
+
+/// Encrypted payload size for sent messages; a typical short text message.
+const PAYLOAD: [u8; 512] = [0xA5; 512];
+
+/// Challenge + signed verify for one identity. Returns false on any failure.
+async fn login(
+    server: &TestServer,
+    public_key: &str,
+    identity: &libsignal_protocol::IdentityKeyPair,
+    device_id: DeviceId,
+) -> bool {
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let Ok(resp) = server
+        .http
+        .post(format!("{}/api/auth/challenge", server.base_url))
+        .json(&serde_json::json!({ "public_key": public_key }))
+        .send()
+        .await
+    else {
+        return false;
+    };
+    let Ok(body) = resp.json::<serde_json::Value>().await else {
+        return false;
+    };
+    let Some(challenge) = body["challenge"].as_str().and_then(|c| b64.decode(c).ok()) else {
+        return false;
+    };
+    let signature = identity
+        .private_key()
+        .calculate_signature(&challenge, &mut rand::rng())
+        .unwrap();
+
+    server
+        .http
+        .post(format!("{}/api/auth/verify", server.base_url))
+        .json(&serde_json::json!({
+            "public_key": public_key,
+            "signature": b64.encode(&signature),
+            "device_id": device_id,
+            "device_name": "load",
+        }))
+        .send()
+        .await
+        .is_ok_and(|resp| resp.status().is_success())
+}
+
+#[sqlx::test]
+#[ignore = "load test; run with --ignored on a release build"]
+async fn auth_login(pool: sqlx::PgPool) {
+    const CLIENTS: usize = 50;
+    const LOGINS_PER_CLIENT: usize = 20;
+
+    let server = spawn_server(pool.clone()).await;
+    let mut identities = Vec::with_capacity(CLIENTS);
+    for i in 0..CLIENTS {
+        let identity = libsignal_protocol::IdentityKeyPair::generate(&mut rand::rng());
+        let public_key =
+            base64::engine::general_purpose::STANDARD.encode(identity.public_key().serialize());
+        repo::users::insert(
+            &pool,
+            &NewUser {
+                id: UserId::new(),
+                public_key: &public_key,
+                email: &format!("load{i}@example.com"),
+                display_name: "Load User",
+            },
+        )
+        .await
+        .unwrap();
+        identities.push((public_key, identity));
+    }
+
+    let server = std::sync::Arc::new(server);
+    let started = Instant::now();
+    let clients: Vec<_> = identities
+        .into_iter()
+        .map(|(public_key, identity)| {
+            let server = server.clone();
+            tokio::spawn(async move {
+                // The first login binds the key to this device
+                let device_id = DeviceId::new();
+                let mut samples = Samples::default();
+                for _ in 0..LOGINS_PER_CLIENT {
+                    let t = Instant::now();
+                    if login(&server, &public_key, &identity, device_id).await {
+                        samples.record(t.elapsed());
+                    } else {
+                        samples.errors += 1;
+                    }
+                }
+                samples
+            })
+        })
+        .collect();
+
+    let mut samples = Samples::default();
+    for client in clients {
+        samples.merge(client.await.unwrap());
+    }
+    check("auth_login", &samples, started.elapsed());
+}
+
+#[sqlx::test]
+#[ignore = "load test; run with --ignored on a release build"]
+async fn message_send(pool: sqlx::PgPool) {
+    const SENDERS: u32 = 100;
+    const MESSAGES_PER_SENDER: usize = 20;
+    // Just under the gateway's 5 messages/second per user and channel
+    const SEND_INTERVAL: Duration = Duration::from_millis(220);
+
+    let server = spawn_server(pool.clone()).await;
+    // Each sender has a channel to itself, so every MessageCreated it sees
+    // is the echo of its own send
+    let (tokens, channels) = seed_guild(&pool, &server, SENDERS, SENDERS).await;
+
+    let mut gateways = Vec::with_capacity(tokens.len());
+    for (token, channel_id) in tokens.iter().zip(&channels) {
+        let mut gateway = Gateway::connect(&server, token).await;
+        gateway.subscribe(*channel_id).await;
+        gateways.push((gateway, *channel_id));
+    }
+
+    let started = Instant::now();
+    let senders: Vec<_> = gateways
+        .into_iter()
+        .map(|(mut gateway, channel_id)| {
+            tokio::spawn(async move {
+                let mut samples = Samples::default();
+                let mut ticker = tokio::time::interval(SEND_INTERVAL);
+                for _ in 0..MESSAGES_PER_SENDER {
+                    ticker.tick().await;
+                    let t = Instant::now();
+                    gateway.send_message(channel_id, &PAYLOAD).await;
+                    loop {
+                        match gateway.next().await {
+                            ServerMessage::MessageCreated { .. } => {
+                                samples.record(t.elapsed());
+                                break;
+                            }
+                            ServerMessage::Error { .. } => {
+                                samples.errors += 1;
+                                break;
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                samples
+            })
+        })
+        .collect();
+
+    let mut samples = Samples::default();
+    for sender in senders {
+        samples.merge(sender.await.unwrap());
+    }
+    let elapsed = started.elapsed();
+
+    let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(stored, (SENDERS as usize * MESSAGES_PER_SENDER) as i64);
+    check("message_send", &samples, elapsed);
+}
+
+#[sqlx::test]
+#[ignore = "load test; run with --ignored on a release build"]
+async fn gateway_fanout(pool: sqlx::PgPool) {
+    const SUBSCRIBERS: u32 = 200;
+    const MESSAGES: usize = 20;
+    const SEND_INTERVAL: Duration = Duration::from_millis(220);
+
+    let server = spawn_server(pool.clone()).await;
+    // User 0 sends; everyone, including the sender, is subscribed
+    let (tokens, channels) = seed_guild(&pool, &server, SUBSCRIBERS, 1).await;
+    let channel_id = channels[0];
+
+    let mut sender = Gateway::connect(&server, &tokens[0]).await;
+    sender.subscribe(channel_id).await;
+    let mut receivers = Vec::with_capacity(tokens.len() - 1);
+    for token in &tokens[1..] {
+        let mut gateway = Gateway::connect(&server, token).await;
+        gateway.subscribe(channel_id).await;
+        receivers.push(gateway);
+    }
+
+    // Each receiver reports when it has seen each message
+    let (seen_tx, mut seen_rx) = tokio::sync::mpsc::unbounded_channel();
+    for mut gateway in receivers {
+        let seen_tx = seen_tx.clone();
+        tokio::spawn(async move {
+            for i in 0..MESSAGES {
+                loop {
+                    if let ServerMessage::MessageCreated { .. } = gateway.next().await {
+                        let _ = seen_tx.send((i, Instant::now()));
+                        break;
+                    }
+                }
+            }
+        });
+    }
+    drop(seen_tx);
+
+    let mut sent_at = Vec::with_capacity(MESSAGES);
+    let started = Instant::now();
+    let mut ticker = tokio::time::interval(SEND_INTERVAL);
+    for _ in 0..MESSAGES {
+        ticker.tick().await;
+        sent_at.push(Instant::now());
+        sender.send_message(channel_id, &PAYLOAD).await;
+    }
+
+    // A message's fan-out latency is when its last receiver got it
+    let mut last_seen = vec![None::<Instant>; MESSAGES];
+    let mut remaining = vec![tokens.len() - 1; MESSAGES];
+    let deadline = tokio::time::sleep(Duration::from_secs(30));
+    tokio::pin!(deadline);
+    while remaining.iter().any(|r| *r > 0) {
+        tokio::select! {
+            Some((i, at)) = seen_rx.recv() => {
+                remaining[i] -= 1;
+                last_seen[i] = Some(last_seen[i].map_or(at, |prev| prev.max(at)));
+            }
+            _ = &mut deadline => break,
+        }
+    }
+    let elapsed = started.elapsed();
+
+    let mut samples = Samples::default();
+    for (i, sent) in sent_at.iter().enumerate() {
+        match (remaining[i], last_seen[i]) {
+            (0, Some(at)) => samples.record(at - *sent),
+            _ => samples.errors += 1,
+        }
+    }
+    check("gateway_fanout", &samples, elapsed);
+}
@@ -0,0 +1,118 @@
+//! Latency recording and baseline thresholds.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+/// Thresholds a scenario must stay within, from `baseline.toml`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Threshold {
+    pub p95_ms: f64,
+    pub min_per_second: f64,
+}
+
+/// Baselines keyed by scenario name.
+pub fn baselines() -> HashMap<String, Threshold> {
+    toml::from_str(include_str!("baseline.toml")).expect("baseline.toml is valid")
+}
+
+/// Factor applied to every threshold: latencies may be this many times the
+/// baseline and throughput this many times lower. Set `LOAD_TEST_SLACK` on
+/// slow or shared machines. Default: 1
+fn slack() -> f64 {
+    std::env::var("LOAD_TEST_SLACK")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v: &f64| *v >= 1.0)
+        .unwrap_or(1.0)
+}
+
+/// Latencies of one scenario's operations.
+#[derive(Debug, Default)]
+pub struct Samples {
+    latencies: Vec<Duration>,
+    pub errors: u64,
+}
+
+impl Samples {
+    pub fn record(&mut self, latency: Duration) {
+        self.latencies.push(latency);
+    }
+
+    pub fn merge(&mut self, other: Samples) {
+        self.latencies.extend(other.latencies);
+        self.errors += other.errors;
+    }
+
+    /// Latency at quantile `q` (0.0 to 1.0), nearest-rank.
+    pub fn quantile(&self, q: f64) -> Duration {
+        let mut sorted = self.latencies.clone();
+        sorted.sort_unstable();
+        if sorted.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = ((q * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+        sorted[rank - 1]
+    }
+
+    pub fn len(&self) -> usize {
+        self.latencies.len()
+    }
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+/// Print a scenario's results and fail if it errored or missed its baseline.
+pub fn check(name: &str, samples: &Samples, elapsed: Duration) {
+    let baseline = baselines();
+    let threshold = baseline
+        .get(name)
+        .unwrap_or_else(|| panic!("no baseline for scenario {name}"));
+    let slack = slack();
+    let per_second = samples.len() as f64 / elapsed.as_secs_f64();
+    let p95 = ms(samples.quantile(0.95));
+
+    println!(
+        "{name}: {} ops in {:.2}s ({per_second:.0}/s), p50 {:.1}ms, p95 {p95:.1}ms, p99 {:.1}ms, {} errors",
+        samples.len(),
+        elapsed.as_secs_f64(),
+        ms(samples.quantile(0.5)),
+        ms(samples.quantile(0.99)),
+        samples.errors,
+    );
+
+    assert_eq!(samples.errors, 0, "{name}: operations failed");
+    assert!(
+        p95 <= threshold.p95_ms * slack,
+        "{name}: p95 {p95:.1}ms exceeds baseline {}ms",
+        threshold.p95_ms * slack
+    );
+    assert!(
+        per_second >= threshold.min_per_second / slack,
+        "{name}: {per_second:.0}/s is below baseline {}/s",
+        threshold.min_per_second / slack
+    );
+}
+
+#[test]
+fn quantiles_use_nearest_rank() {
+    let mut samples = Samples::default();
+    for ms in 1..=100 {
+        samples.record(Duration::from_millis(ms));
+    }
+    assert_eq!(samples.quantile(0.5), Duration::from_millis(50));
+    assert_eq!(samples.quantile(0.95), Duration::from_millis(95));
+    assert_eq!(samples.quantile(1.0), Duration::from_millis(100));
+    assert_eq!(Samples::default().quantile(0.95), Duration::ZERO);
+}
+
+#[test]
+fn every_scenario_has_a_baseline() {
+    let baseline = baselines();
+    for name in ["auth_login", "message_send", "gateway_fanout"] {
+        assert!(baseline.contains_key(name), "missing baseline for {name}");
+    }
+}
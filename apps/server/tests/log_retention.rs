@@ -0,0 +1,120 @@
+use futures::TryStreamExt;
+use object_store::ObjectStore;
+use openconv_server::config::LogRetentionConfig;
+use openconv_server::tasks::log_retention::{purge_expired_logs, GUILD_AUDIT_LOG};
+
+/// Create a guild with `old` audit entries from 100 days ago and `recent`
+/// entries from today.
+async fn seed_audit_log(pool: &sqlx::PgPool, old: usize, recent: usize) {
+    let user_id = uuid::Uuid::new_v4();
+    sqlx::query("INSERT INTO users (id, public_key, email, display_name) VALUES ($1, $2, $3, $4)")
+        .bind(user_id)
+        .bind(format!("pk_{user_id}"))
+        .bind(format!("{user_id}@example.com"))
+        .bind("Owner")
+        .execute(pool)
+        .await
+        .unwrap();
+
+    let guild_id = uuid::Uuid::new_v4();
+    sqlx::query("INSERT INTO guilds (id, name, owner_id) VALUES ($1, $2, $3)")
+        .bind(guild_id)
+        .bind("Audited")
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .unwrap();
+
+    for (count, age) in [(old, "100 days"), (recent, "0 days")] {
+        for _ in 0..count {
+            sqlx::query(
+                "INSERT INTO guild_audit_log (guild_id, actor_id, action, created_at) \
+                 VALUES ($1, $2, 'member_ban', NOW() - $3::interval)",
+            )
+            .bind(guild_id)
+            .bind(user_id)
+            .bind(age)
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+    }
+}
+
+async fn audit_log_count(pool: &sqlx::PgPool) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM guild_audit_log")
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn expired_audit_entries_are_exported_then_deleted(pool: sqlx::PgPool) {
+    seed_audit_log(&pool, 5, 2).await;
+    let store = object_store::memory::InMemory::new();
+    let config = LogRetentionConfig {
+        audit_log_days: Some(90),
+        batch_size: 2,
+        ..Default::default()
+    };
+
+    let reports = purge_expired_logs(&pool, &store, &config).await.unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].table, GUILD_AUDIT_LOG);
+    assert_eq!(reports[0].rows, 5);
+    assert!(reports[0].exported_bytes > 0);
+    assert_eq!(audit_log_count(&pool).await, 2);
+
+    // Batches of 2, 2 and 1, each exported as its own NDJSON object
+    let exports: Vec<_> = store.list(None).try_collect().await.unwrap();
+    assert_eq!(exports.len(), 3);
+    let mut lines = 0;
+    for export in exports {
+        assert!(export
+            .location
+            .as_ref()
+            .starts_with("log-exports/guild_audit_log/"));
+        let bytes = store
+            .get(&export.location)
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        for line in std::str::from_utf8(&bytes).unwrap().lines() {
+            let row: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(row["action"], "member_ban");
+            lines += 1;
+        }
+    }
+    assert_eq!(lines, 5);
+}
+
+#[sqlx::test]
+async fn audit_log_is_kept_without_retention(pool: sqlx::PgPool) {
+    seed_audit_log(&pool, 3, 0).await;
+    let store = object_store::memory::InMemory::new();
+
+    let reports = purge_expired_logs(&pool, &store, &LogRetentionConfig::default())
+        .await
+        .unwrap();
+    assert!(reports.is_empty());
+    assert_eq!(audit_log_count(&pool).await, 3);
+}
+
+#[sqlx::test]
+async fn export_can_be_disabled(pool: sqlx::PgPool) {
+    seed_audit_log(&pool, 3, 0).await;
+    let store = object_store::memory::InMemory::new();
+    let config = LogRetentionConfig {
+        audit_log_days: Some(30),
+        export_before_delete: false,
+        ..Default::default()
+    };
+
+    let reports = purge_expired_logs(&pool, &store, &config).await.unwrap();
+    assert_eq!(reports[0].rows, 3);
+    assert_eq!(reports[0].exported_bytes, 0);
+    let exports: Vec<_> = store.list(None).try_collect().await.unwrap();
+    assert!(exports.is_empty());
+}
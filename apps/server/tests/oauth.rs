@@ -0,0 +1,311 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use tower::ServiceExt;
+
+use openconv_test_support::{
+    authed_delete, authed_get, authed_post, body_json, seed_user, TestApp,
+};
+
+// RFC 7636 appendix B
+const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+const CHALLENGE: &str = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+const REDIRECT_URI: &str = "https://app.example/callback";
+
+fn form_post(uri: &str, fields: &[(&str, &str)]) -> Request<Body> {
+    let body = fields
+        .iter()
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect::<Vec<_>>()
+        .join("&");
+    Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+async fn register_client(app: &axum::Router, token: &str) -> String {
+    let req = authed_post(
+        "/api/oauth/clients",
+        token,
+        serde_json::json!({ "name": "Stats App", "redirect_uris": [REDIRECT_URI] }),
+    );
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let json = body_json(resp).await;
+    assert_eq!(json["confidential"], false);
+    assert!(json.get("client_secret").is_none());
+    json["client_id"].as_str().unwrap().to_string()
+}
+
+/// Approve a request for `scope` and return the authorization code.
+async fn authorize(app: &axum::Router, token: &str, client_id: &str, scope: &str) -> String {
+    let req = authed_post(
+        "/api/oauth/authorize",
+        token,
+        serde_json::json!({
+            "response_type": "code",
+            "client_id": client_id,
+            "redirect_uri": REDIRECT_URI,
+            "scope": scope,
+            "state": "xyz",
+            "code_challenge": CHALLENGE,
+            "code_challenge_method": "S256",
+        }),
+    );
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let redirect = body_json(resp).await["redirect_to"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let url = reqwest::Url::parse(&redirect).unwrap();
+    assert!(redirect.starts_with(REDIRECT_URI));
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.into_owned())
+    };
+    assert_eq!(param("state").as_deref(), Some("xyz"));
+    param("code").unwrap()
+}
+
+fn redeem(client_id: &str, code: &str) -> Request<Body> {
+    form_post(
+        "/api/oauth/token",
+        &[
+            ("grant_type", "authorization_code"),
+            ("client_id", client_id),
+            ("code", code),
+            ("redirect_uri", REDIRECT_URI),
+            ("code_verifier", VERIFIER),
+        ],
+    )
+}
+
+#[sqlx::test]
+async fn authorization_code_flow_issues_scoped_tokens(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = TestApp::new(pool.clone()).await;
+    let (_, _, dev_token) = seed_user(&pool, &jwt, "Dev", "dev@test.com").await;
+    let (user_id, _, user_token) = seed_user(&pool, &jwt, "Alice", "alice@test.com").await;
+    let client_id = register_client(&app, &dev_token).await;
+
+    // Consent screen
+    let req = authed_get(
+        &format!(
+            "/api/oauth/authorize?response_type=code&client_id={client_id}\
+             &redirect_uri={REDIRECT_URI}&scope=identify&code_challenge={CHALLENGE}\
+             &code_challenge_method=S256"
+        ),
+        &user_token,
+    );
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let info = body_json(resp).await;
+    assert_eq!(info["client_name"], "Stats App");
+    assert_eq!(info["scopes"], serde_json::json!(["identify"]));
+    assert_eq!(info["already_granted"], false);
+
+    let code = authorize(&app, &user_token, &client_id, "identify").await;
+    let resp = app
+        .clone()
+        .oneshot(redeem(&client_id, &code))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["cache-control"], "no-store");
+    let tokens = body_json(resp).await;
+    assert_eq!(tokens["token_type"], "Bearer");
+    assert_eq!(tokens["scope"], "identify");
+    let access = tokens["access_token"].as_str().unwrap().to_string();
+    let refresh = tokens["refresh_token"].as_str().unwrap().to_string();
+
+    // Codes are single use
+    let resp = app
+        .clone()
+        .oneshot(redeem(&client_id, &code))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(body_json(resp).await["error"], "invalid_grant");
+
+    let resp = app
+        .clone()
+        .oneshot(authed_get("/api/oauth/@me", &access))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let me = body_json(resp).await;
+    assert_eq!(me["id"], user_id.0.to_string());
+    assert_eq!(me["display_name"], "Alice");
+    assert!(me.get("email").is_none());
+
+    // Not granted guilds.read, and not usable on the regular API
+    let resp = app
+        .clone()
+        .oneshot(authed_get("/api/oauth/@me/guilds", &access))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = app
+        .clone()
+        .oneshot(authed_get("/api/users/me", &access))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // Refreshing rotates the pair
+    let req = form_post(
+        "/api/oauth/token",
+        &[
+            ("grant_type", "refresh_token"),
+            ("client_id", &client_id),
+            ("refresh_token", &refresh),
+        ],
+    );
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let rotated = body_json(resp).await;
+    let new_access = rotated["access_token"].as_str().unwrap().to_string();
+    let resp = app
+        .clone()
+        .oneshot(authed_get("/api/oauth/@me", &access))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // Consent is remembered, and revoking it kills the tokens
+    let resp = app
+        .clone()
+        .oneshot(authed_get("/api/oauth/consents", &user_token))
+        .await
+        .unwrap();
+    let consents = body_json(resp).await;
+    assert_eq!(consents["consents"][0]["client_id"], client_id.as_str());
+
+    let resp = app
+        .clone()
+        .oneshot(authed_delete(
+            &format!("/api/oauth/consents/{client_id}"),
+            &user_token,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let resp = app
+        .clone()
+        .oneshot(authed_get("/api/oauth/@me", &new_access))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn token_endpoint_rejects_wrong_verifier_and_redirect(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = TestApp::new(pool.clone()).await;
+    let (_, _, token) = seed_user(&pool, &jwt, "Alice", "alice@test.com").await;
+    let client_id = register_client(&app, &token).await;
+
+    let code = authorize(&app, &token, &client_id, "identify guilds.read").await;
+    let req = form_post(
+        "/api/oauth/token",
+        &[
+            ("grant_type", "authorization_code"),
+            ("client_id", &client_id),
+            ("code", &code),
+            ("redirect_uri", REDIRECT_URI),
+            (
+                "code_verifier",
+                "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXx",
+            ),
+        ],
+    );
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(body_json(resp).await["error"], "invalid_grant");
+
+    // Unregistered redirect URIs never get a code
+    let req = authed_post(
+        "/api/oauth/authorize",
+        &token,
+        serde_json::json!({
+            "response_type": "code",
+            "client_id": client_id,
+            "redirect_uri": "https://evil.example/callback",
+            "scope": "identify",
+            "code_challenge": CHALLENGE,
+            "code_challenge_method": "S256",
+        }),
+    );
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = form_post(
+        "/api/oauth/token",
+        &[("grant_type", "password"), ("client_id", &client_id)],
+    );
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(body_json(resp).await["error"], "unsupported_grant_type");
+}
+
+#[sqlx::test]
+async fn confidential_clients_must_present_secret(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = TestApp::new(pool.clone()).await;
+    let (_, _, token) = seed_user(&pool, &jwt, "Alice", "alice@test.com").await;
+
+    let req = authed_post(
+        "/api/oauth/clients",
+        &token,
+        serde_json::json!({
+            "name": "Server App",
+            "redirect_uris": [REDIRECT_URI],
+            "confidential": true,
+        }),
+    );
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let client = body_json(resp).await;
+    let client_id = client["client_id"].as_str().unwrap().to_string();
+    let secret = client["client_secret"].as_str().unwrap().to_string();
+
+    let code = authorize(&app, &token, &client_id, "guilds.read").await;
+    let resp = app
+        .clone()
+        .oneshot(redeem(&client_id, &code))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(body_json(resp).await["error"], "invalid_client");
+
+    let code = authorize(&app, &token, &client_id, "guilds.read").await;
+    let req = form_post(
+        "/api/oauth/token",
+        &[
+            ("grant_type", "authorization_code"),
+            ("client_id", &client_id),
+            ("client_secret", &secret),
+            ("code", &code),
+            ("redirect_uri", REDIRECT_URI),
+            ("code_verifier", VERIFIER),
+        ],
+    );
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let access = body_json(resp).await["access_token"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let resp = app
+        .clone()
+        .oneshot(authed_get("/api/oauth/@me/guilds", &access))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(body_json(resp).await["guilds"]
+        .as_array()
+        .unwrap()
+        .is_empty());
+}
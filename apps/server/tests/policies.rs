@@ -0,0 +1,174 @@
+use axum::http::StatusCode;
+use tower::ServiceExt;
+
+use openconv_server::config::PolicyConfig;
+use openconv_test_support::{
+    authed_get, authed_post, body_json, json_request, seed_test_user, TestApp,
+};
+
+const POLICY_HEADER: &str = "x-policy-update-required";
+
+fn published(terms: &str, privacy: &str) -> PolicyConfig {
+    PolicyConfig {
+        terms_version: Some(terms.to_string()),
+        privacy_version: Some(privacy.to_string()),
+    }
+}
+
+#[sqlx::test]
+async fn no_header_when_no_policy_is_published(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = TestApp::builder(pool.clone())
+        .config(|config| config.policy = PolicyConfig::default())
+        .build()
+        .await;
+    let (_, token) = seed_test_user(&pool, &jwt).await;
+
+    let resp = app
+        .oneshot(authed_get("/api/users/me", &token))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().get(POLICY_HEADER).is_none());
+}
+
+#[sqlx::test]
+async fn accepting_published_versions_clears_update_flag(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = TestApp::builder(pool.clone())
+        .config(|config| config.policy = published("2024-06", "3"))
+        .build()
+        .await;
+    let (_, token) = seed_test_user(&pool, &jwt).await;
+
+    let resp = app
+        .clone()
+        .oneshot(authed_get("/api/users/me", &token))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get(POLICY_HEADER).unwrap(), "true");
+
+    let resp = app
+        .clone()
+        .oneshot(authed_get("/api/policies", &token))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = body_json(resp).await;
+    assert_eq!(json["terms_version"], "2024-06");
+    assert_eq!(json["policy_update_required"], true);
+    assert!(json["accepted_terms_version"].is_null());
+
+    // Accepting only the terms still leaves the privacy policy pending
+    let resp = app
+        .clone()
+        .oneshot(authed_post(
+            "/api/policies/accept",
+            &token,
+            serde_json::json!({ "terms_version": "2024-06" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = body_json(resp).await;
+    assert_eq!(json["accepted_terms_version"], "2024-06");
+    assert_eq!(json["policy_update_required"], true);
+
+    let resp = app
+        .clone()
+        .oneshot(authed_post(
+            "/api/policies/accept",
+            &token,
+            serde_json::json!({ "privacy_version": "3" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(body_json(resp).await["policy_update_required"], false);
+
+    let resp = app
+        .oneshot(authed_get("/api/users/me", &token))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().get(POLICY_HEADER).is_none());
+}
+
+#[sqlx::test]
+async fn bumped_version_requires_acceptance_again(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = TestApp::builder(pool.clone())
+        .config(|config| config.policy = published("1", "1"))
+        .build()
+        .await;
+    let (user_id, token) = seed_test_user(&pool, &jwt).await;
+
+    let resp = app
+        .oneshot(authed_post(
+            "/api/policies/accept",
+            &token,
+            serde_json::json!({ "terms_version": "1", "privacy_version": "1" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // The instance publishes new terms
+    let TestApp { app, .. } = TestApp::builder(pool.clone())
+        .config(|config| config.policy = published("2", "1"))
+        .build()
+        .await;
+    let resp = app
+        .clone()
+        .oneshot(authed_get("/api/users/me", &token))
+        .await
+        .unwrap();
+    assert_eq!(resp.headers().get(POLICY_HEADER).unwrap(), "true");
+
+    // The old version can no longer be accepted
+    let resp = app
+        .oneshot(authed_post(
+            "/api/policies/accept",
+            &token,
+            serde_json::json!({ "terms_version": "1" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let versions: Vec<String> = sqlx::query_scalar(
+        "SELECT version FROM policy_acceptances WHERE user_id = $1 AND document = 'terms'",
+    )
+    .bind(user_id.0)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(versions, vec!["1".to_string()]);
+}
+
+#[sqlx::test]
+async fn accept_requires_authentication_and_a_version(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = TestApp::builder(pool.clone())
+        .config(|config| config.policy = published("1", "1"))
+        .build()
+        .await;
+    let (_, token) = seed_test_user(&pool, &jwt).await;
+
+    let resp = app
+        .clone()
+        .oneshot(json_request(
+            "/api/policies/accept",
+            serde_json::json!({ "terms_version": "1" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = app
+        .oneshot(authed_post(
+            "/api/policies/accept",
+            &token,
+            serde_json::json!({}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
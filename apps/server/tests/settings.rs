@@ -0,0 +1,194 @@
+use axum::http::StatusCode;
+use tower::ServiceExt;
+
+use openconv_shared::api::settings::RenderSettings;
+use openconv_test_support::{
+    authed_get, authed_patch, authed_put, body_json, seed_test_user, TestApp,
+};
+
+fn compact_defaults() -> RenderSettings {
+    RenderSettings {
+        compact_mode: true,
+        ..Default::default()
+    }
+}
+
+#[sqlx::test]
+async fn settings_default_to_instance_branding(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = TestApp::builder(pool.clone())
+        .config(|config| config.branding.render_defaults = compact_defaults())
+        .build()
+        .await;
+    let (_, token) = seed_test_user(&pool, &jwt).await;
+
+    let resp = app
+        .oneshot(authed_get("/api/users/me/settings", &token))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = body_json(resp).await;
+    assert_eq!(json["render"]["compact_mode"], true);
+    assert_eq!(json["render"]["message_font_size"], 16);
+    assert_eq!(json["render_overrides"], serde_json::json!({}));
+    assert_eq!(json["render_defaults"]["compact_mode"], true);
+    assert_eq!(json["version"], 0);
+}
+
+#[sqlx::test]
+async fn overrides_are_stored_and_resolved_against_defaults(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = TestApp::builder(pool.clone())
+        .config(|config| config.branding.render_defaults = compact_defaults())
+        .build()
+        .await;
+    let (_, token) = seed_test_user(&pool, &jwt).await;
+
+    let body = serde_json::json!({
+        "render": { "message_font_size": 18, "link_previews": false, "emoji_size": "large" },
+        "expected_version": 0
+    });
+    let resp = app
+        .clone()
+        .oneshot(authed_put("/api/users/me/settings", &token, body))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = body_json(resp).await;
+    assert_eq!(json["version"], 1);
+
+    // Another device sees the same resolved settings
+    let resp = app
+        .oneshot(authed_get("/api/users/me/settings", &token))
+        .await
+        .unwrap();
+    let json = body_json(resp).await;
+    assert_eq!(json["render"]["message_font_size"], 18);
+    assert_eq!(json["render"]["link_previews"], false);
+    assert_eq!(json["render"]["emoji_size"], "large");
+    assert_eq!(json["render"]["compact_mode"], true);
+    assert_eq!(json["render"]["inline_media"], true);
+    assert!(json["render_overrides"].get("compact_mode").is_none());
+    assert_eq!(json["version"], 1);
+}
+
+#[sqlx::test]
+async fn stale_expected_version_is_rejected(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = TestApp::builder(pool.clone())
+        .config(|config| config.branding.render_defaults = RenderSettings::default())
+        .build()
+        .await;
+    let (_, token) = seed_test_user(&pool, &jwt).await;
+
+    let save = |version: i64, compact: bool| {
+        authed_put(
+            "/api/users/me/settings",
+            &token,
+            serde_json::json!({
+                "render": { "compact_mode": compact },
+                "expected_version": version
+            }),
+        )
+    };
+
+    let resp = app.clone().oneshot(save(0, true)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // A second device still holding version 0 must refetch first
+    let resp = app.clone().oneshot(save(0, false)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let resp = app.clone().oneshot(save(1, false)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = body_json(resp).await;
+    assert_eq!(json["render"]["compact_mode"], false);
+    assert_eq!(json["version"], 2);
+}
+
+#[sqlx::test]
+async fn out_of_range_font_size_is_rejected(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = TestApp::builder(pool.clone())
+        .config(|config| config.branding.render_defaults = RenderSettings::default())
+        .build()
+        .await;
+    let (_, token) = seed_test_user(&pool, &jwt).await;
+
+    let body = serde_json::json!({ "render": { "message_font_size": 100 } });
+    let resp = app
+        .oneshot(authed_put("/api/users/me/settings", &token, body))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn emoji_preferences_merge_across_devices(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = TestApp::builder(pool.clone())
+        .config(|config| config.branding.render_defaults = RenderSettings::default())
+        .build()
+        .await;
+    let (_, token) = seed_test_user(&pool, &jwt).await;
+
+    let patch =
+        |body: serde_json::Value| authed_patch("/api/users/me/settings/emoji", &token, body);
+
+    let resp = app
+        .clone()
+        .oneshot(patch(serde_json::json!({
+            "skin_tone": "medium",
+            "used": ["tada", "tada", "wave"]
+        })))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = body_json(resp).await;
+    assert_eq!(json["emoji"]["skin_tone"], "medium");
+    assert_eq!(json["emoji"]["frequent"][0]["shortcode"], "tada");
+    assert_eq!(json["emoji"]["frequent"][0]["count"], 2);
+    assert_eq!(json["version"], 0);
+
+    // A second device syncs its offline ranking without resetting the tone
+    let resp = app
+        .clone()
+        .oneshot(patch(serde_json::json!({
+            "frequent": [
+                { "shortcode": "wave", "count": 5, "last_used_at": "2026-01-01T00:00:00Z" }
+            ]
+        })))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app
+        .clone()
+        .oneshot(authed_get("/api/users/me/settings", &token))
+        .await
+        .unwrap();
+    let json = body_json(resp).await;
+    assert_eq!(json["emoji"]["skin_tone"], "medium");
+    assert_eq!(json["emoji"]["frequent"][0]["shortcode"], "wave");
+    assert_eq!(json["emoji"]["frequent"][0]["count"], 5);
+    assert_eq!(json["emoji"]["frequent"][1]["shortcode"], "tada");
+
+    // Emoji merges never make a render save stale
+    let body = serde_json::json!({ "render": { "compact_mode": true }, "expected_version": 0 });
+    let resp = app
+        .oneshot(authed_put("/api/users/me/settings", &token, body))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn invalid_emoji_shortcode_is_rejected(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = TestApp::builder(pool.clone())
+        .config(|config| config.branding.render_defaults = RenderSettings::default())
+        .build()
+        .await;
+    let (_, token) = seed_test_user(&pool, &jwt).await;
+
+    let body = serde_json::json!({ "used": [":smile:"] });
+    let resp = app
+        .oneshot(authed_patch("/api/users/me/settings/emoji", &token, body))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,104 @@
+use axum::http::StatusCode;
+use tower::ServiceExt;
+
+use openconv_server::config::VoiceConfig;
+use openconv_test_support::{
+    authed_get, authed_post, body_json, seed_test_user, TestApp, TestRequest,
+};
+
+const TURN_CREDENTIALS: &str = "/api/voice/turn-credentials";
+
+#[sqlx::test]
+async fn turn_credentials_are_issued_per_user(pool: sqlx::PgPool) {
+    let voice = VoiceConfig {
+        turn_uris: vec!["turn:turn.example.org:3478".to_string()],
+        turn_shared_secret: "s3cret".to_string(),
+        turn_credential_ttl_secs: 600,
+    };
+    let TestApp { app, jwt, .. } = TestApp::builder(pool.clone())
+        .config(|config| config.voice = voice)
+        .build()
+        .await;
+    let (user_id, token) = seed_test_user(&pool, &jwt).await;
+
+    let before = chrono::Utc::now().timestamp();
+    let resp = app
+        .oneshot(authed_get(TURN_CREDENTIALS, &token))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = body_json(resp).await;
+    assert_eq!(body["urls"][0], "turn:turn.example.org:3478");
+    assert_eq!(body["ttl"], 600);
+
+    let username = body["username"].as_str().unwrap();
+    let (expiry, user) = username.split_once(':').unwrap();
+    assert_eq!(user, user_id.to_string());
+    let expiry: i64 = expiry.parse().unwrap();
+    assert!((before + 600..=before + 602).contains(&expiry));
+    assert!(!body["credential"].as_str().unwrap().is_empty());
+}
+
+#[sqlx::test]
+async fn turn_credentials_need_configuration_and_auth(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = TestApp::builder(pool.clone())
+        .config(|config| config.voice = VoiceConfig::default())
+        .build()
+        .await;
+    let (_, token) = seed_test_user(&pool, &jwt).await;
+
+    let resp = app
+        .clone()
+        .oneshot(authed_get(TURN_CREDENTIALS, &token))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = app
+        .oneshot(TestRequest::get(TURN_CREDENTIALS).build())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn voice_channels_start_empty_and_are_member_only(pool: sqlx::PgPool) {
+    let TestApp { app, jwt, .. } = TestApp::builder(pool.clone())
+        .config(|config| config.voice = VoiceConfig::default())
+        .build()
+        .await;
+    let (_, token) = seed_test_user(&pool, &jwt).await;
+    let (_, outsider) = seed_test_user(&pool, &jwt).await;
+
+    let resp = app
+        .clone()
+        .oneshot(authed_post(
+            "/api/guilds",
+            &token,
+            serde_json::json!({ "name": "Voice Guild" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let guild_id = body_json(resp).await["id"].as_str().unwrap().to_string();
+
+    let resp = app
+        .clone()
+        .oneshot(authed_post(
+            &format!("/api/guilds/{guild_id}/channels"),
+            &token,
+            serde_json::json!({ "name": "lounge", "channel_type": "voice" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(body_json(resp).await["channel_type"], "voice");
+
+    let uri = format!("/api/guilds/{guild_id}/voice-states");
+    let resp = app.clone().oneshot(authed_get(&uri, &token)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(body_json(resp).await["voice_states"], serde_json::json!([]));
+
+    let resp = app.oneshot(authed_get(&uri, &outsider)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,229 @@
+//! Async counterparts of the synchronous crypto API.
+//!
+//! Every function in this crate blocks on SQLite and libsignal, so async
+//! callers must move it onto a blocking thread. `AsyncCryptoDb` does that
+//! once: each method checks a connection out of a `CryptoDb` pool inside
+//! `tokio::task::spawn_blocking` and calls the matching synchronous
+//! function. Arguments are owned because the work outlives the caller's
+//! borrow. Anything not covered by a method can go through `run`.
+
+use libsignal_protocol::{IdentityKeyPair, ProtocolAddress};
+use openconv_shared::api::envelope::MessageEnvelope;
+use rusqlite::Connection;
+
+use crate::db::CryptoDb;
+use crate::error::CryptoError;
+use crate::message::{EncryptedMessage, MessageType};
+use crate::prekeys::{MaintenancePolicy, SerializedPreKey, SerializedPreKeyBundle, UploadManifest};
+use crate::session::SessionInfo;
+use crate::{identity, message, prekeys, session};
+
+/// Async adapter over a `CryptoDb` pool. Cheap to clone.
+#[derive(Debug, Clone)]
+pub struct AsyncCryptoDb {
+    db: CryptoDb,
+}
+
+impl AsyncCryptoDb {
+    pub fn new(db: CryptoDb) -> Self {
+        Self { db }
+    }
+
+    /// The underlying pool, for synchronous use.
+    pub fn db(&self) -> &CryptoDb {
+        &self.db
+    }
+
+    /// Run `f` with a pooled connection on the blocking thread pool.
+    ///
+    /// A panic inside `f` is returned as `CryptoError::StorageError`.
+    pub async fn run<T, F>(&self, f: F) -> Result<T, CryptoError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> Result<T, CryptoError> + Send + 'static,
+    {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || db.with_conn(f))
+            .await
+            .map_err(|e| CryptoError::StorageError(format!("blocking task failed: {e}")))?
+    }
+
+    // --- identity ---
+
+    pub async fn generate_identity(&self) -> Result<IdentityKeyPair, CryptoError> {
+        self.run(identity::generate_identity).await
+    }
+
+    pub async fn get_identity(&self) -> Result<IdentityKeyPair, CryptoError> {
+        self.run(identity::get_identity).await
+    }
+
+    pub async fn get_public_key_string(&self) -> Result<String, CryptoError> {
+        self.run(identity::get_public_key_string).await
+    }
+
+    pub async fn sign_challenge(&self, challenge: Vec<u8>) -> Result<Vec<u8>, CryptoError> {
+        self.run(move |conn| identity::sign_challenge(conn, &challenge))
+            .await
+    }
+
+    // --- prekeys ---
+
+    pub async fn generate_pre_key_bundle(
+        &self,
+        user_id: String,
+    ) -> Result<SerializedPreKeyBundle, CryptoError> {
+        self.run(move |conn| prekeys::generate_pre_key_bundle(conn, &user_id))
+            .await
+    }
+
+    pub async fn generate_one_time_pre_keys(
+        &self,
+        count: u32,
+    ) -> Result<Vec<SerializedPreKey>, CryptoError> {
+        self.run(move |conn| prekeys::generate_one_time_pre_keys(conn, count))
+            .await
+    }
+
+    pub async fn mark_pre_keys_uploaded(&self, key_ids: Vec<u32>) -> Result<(), CryptoError> {
+        self.run(move |conn| prekeys::mark_pre_keys_uploaded(conn, &key_ids))
+            .await
+    }
+
+    pub async fn needs_pre_key_replenishment(&self, threshold: u32) -> Result<bool, CryptoError> {
+        self.run(move |conn| prekeys::needs_pre_key_replenishment(conn, threshold))
+            .await
+    }
+
+    pub async fn pre_key_maintenance(
+        &self,
+        user_id: String,
+        policy: MaintenancePolicy,
+    ) -> Result<UploadManifest, CryptoError> {
+        self.run(move |conn| prekeys::maintenance(conn, &user_id, &policy))
+            .await
+    }
+
+    pub async fn mark_manifest_uploaded(
+        &self,
+        manifest: UploadManifest,
+    ) -> Result<(), CryptoError> {
+        self.run(move |conn| prekeys::mark_manifest_uploaded(conn, &manifest))
+            .await
+    }
+
+    // --- session ---
+
+    pub async fn create_outgoing_session(
+        &self,
+        remote_bundle: Vec<u8>,
+    ) -> Result<ProtocolAddress, CryptoError> {
+        self.run(move |conn| session::create_outgoing_session(conn, &remote_bundle))
+            .await
+    }
+
+    pub async fn list_sessions(&self) -> Result<Vec<SessionInfo>, CryptoError> {
+        self.run(session::list_sessions).await
+    }
+
+    pub async fn reset_session(&self, address: ProtocolAddress) -> Result<(), CryptoError> {
+        self.run(move |conn| session::reset(conn, &address)).await
+    }
+
+    // --- message ---
+
+    pub async fn encrypt_message(
+        &self,
+        recipient: ProtocolAddress,
+        plaintext: Vec<u8>,
+    ) -> Result<EncryptedMessage, CryptoError> {
+        self.run(move |conn| message::encrypt_message(conn, &recipient, &plaintext))
+            .await
+    }
+
+    pub async fn decrypt_message(
+        &self,
+        sender: ProtocolAddress,
+        ciphertext: Vec<u8>,
+        message_type: MessageType,
+    ) -> Result<Vec<u8>, CryptoError> {
+        self.run(move |conn| message::decrypt_message(conn, &sender, &ciphertext, message_type))
+            .await
+    }
+
+    pub async fn encrypt_envelope(
+        &self,
+        recipient: ProtocolAddress,
+        envelope: MessageEnvelope,
+    ) -> Result<EncryptedMessage, CryptoError> {
+        self.run(move |conn| message::encrypt_envelope(conn, &recipient, &envelope))
+            .await
+    }
+
+    pub async fn decrypt_envelope(
+        &self,
+        sender: ProtocolAddress,
+        ciphertext: Vec<u8>,
+        message_type: MessageType,
+    ) -> Result<MessageEnvelope, CryptoError> {
+        self.run(move |conn| message::decrypt_envelope(conn, &sender, &ciphertext, message_type))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::master_key::{derive_db_encryption_key, init_master_key_from_passphrase};
+
+    fn open(dir: &tempfile::TempDir, name: &str) -> AsyncCryptoDb {
+        let master_key = init_master_key_from_passphrase(name, &[21u8; 16]).unwrap();
+        let db_key = derive_db_encryption_key(&master_key).unwrap();
+        AsyncCryptoDb::new(CryptoDb::open(dir.path().join(name), db_key).unwrap())
+    }
+
+    #[tokio::test]
+    async fn async_round_trip_between_two_devices() {
+        let dir = tempfile::tempdir().unwrap();
+        let alice = open(&dir, "alice.db");
+        let bob = open(&dir, "bob.db");
+        alice.generate_identity().await.unwrap();
+        bob.generate_identity().await.unwrap();
+
+        let bundle = bob
+            .generate_pre_key_bundle("bob-user-id".into())
+            .await
+            .unwrap();
+        let bob_address = alice
+            .create_outgoing_session(serde_json::to_vec(&bundle).unwrap())
+            .await
+            .unwrap();
+
+        let encrypted = alice
+            .encrypt_envelope(bob_address, MessageEnvelope::text("hello"))
+            .await
+            .unwrap();
+        let alice_address = ProtocolAddress::new(
+            "alice-user-id".into(),
+            libsignal_protocol::DeviceId::new(1).unwrap(),
+        );
+        let envelope = bob
+            .decrypt_envelope(alice_address, encrypted.ciphertext, encrypted.message_type)
+            .await
+            .unwrap();
+        assert_eq!(envelope, MessageEnvelope::text("hello"));
+    }
+
+    #[tokio::test]
+    async fn errors_and_panics_are_returned() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = open(&dir, "errors.db");
+
+        assert!(matches!(
+            db.get_public_key_string().await,
+            Err(CryptoError::IdentityNotInitialized)
+        ));
+        let result: Result<(), _> = db.run(|_| panic!("boom")).await;
+        assert!(matches!(result, Err(CryptoError::StorageError(_))));
+    }
+}
@@ -0,0 +1,711 @@
+//! Passphrase-encrypted backups of the local crypto state.
+//!
+//! `export` archives the identity keypair (and any rotated-out ones),
+//! registration ID, trusted identities, pre-keys, sessions and sender keys;
+//! `import` restores them into another database, so a user can move to a
+//! new device or reinstall without losing their identity or breaking
+//! existing conversations.
+//!
+//! Archive layout: `MAGIC (4) || version (1) || Argon2 params (12) ||
+//! salt (16) || nonce (12) || AES-256-GCM(JSON contents) || auth tag (16)`.
+//! The key is derived from the passphrase with Argon2id (as for the master
+//! key) and then HKDF-SHA256, and the header bytes are authenticated as AAD.
+//! Version 1 archives have no params field and use the defaults.
+//!
+//! Restoring an old backup rolls sessions back to an earlier ratchet state,
+//! so messages sent to this device after the export may not decrypt.
+
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use hkdf::Hkdf;
+use rand::RngCore;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::error::CryptoError;
+use crate::master_key::{generate_salt, init_master_key_from_passphrase_with_params, Argon2Params};
+use crate::storage::with_transaction;
+
+const MAGIC: &[u8; 4] = b"OCKB";
+const VERSION: u8 = 2;
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+const V1_HEADER_SIZE: usize = MAGIC.len() + 1 + SALT_SIZE + NONCE_SIZE;
+const HEADER_SIZE: usize = V1_HEADER_SIZE + Argon2Params::ENCODED_LEN;
+const BACKUP_KEY_INFO: &[u8] = b"openconv-backup-v1";
+
+/// Everything a backup restores. Skipped message keys are left out; they
+/// only matter for messages already in flight.
+#[derive(Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+struct BackupContents {
+    identity: IdentityRow,
+    #[serde(default)]
+    archived_identities: Vec<ArchivedIdentityRow>,
+    config: Vec<ConfigRow>,
+    trusted_identities: Vec<TrustedIdentityRow>,
+    pre_keys: Vec<PreKeyRow>,
+    signed_pre_keys: Vec<KeyRow>,
+    kyber_pre_keys: Vec<KyberKeyRow>,
+    sessions: Vec<SessionRow>,
+    sender_keys: Vec<SenderKeyRow>,
+}
+
+#[derive(Serialize, Deserialize, Zeroize)]
+struct IdentityRow {
+    public_key: Vec<u8>,
+    private_key: Vec<u8>,
+    created_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Zeroize)]
+struct ArchivedIdentityRow {
+    public_key: Vec<u8>,
+    private_key: Vec<u8>,
+    created_at: i64,
+    archived_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Zeroize)]
+struct ConfigRow {
+    key: String,
+    value: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Zeroize)]
+struct TrustedIdentityRow {
+    address: String,
+    device_id: u32,
+    identity_key: Vec<u8>,
+    first_seen_at: i64,
+    verified_at: Option<i64>,
+    verified_identity_key: Option<Vec<u8>>,
+}
+
+#[derive(Serialize, Deserialize, Zeroize)]
+struct PreKeyRow {
+    key_id: u32,
+    record: Vec<u8>,
+    uploaded: bool,
+    created_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Zeroize)]
+struct KeyRow {
+    key_id: u32,
+    record: Vec<u8>,
+    created_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Zeroize)]
+struct KyberKeyRow {
+    key_id: u32,
+    record: Vec<u8>,
+    #[serde(default = "default_last_resort")]
+    last_resort: bool,
+    created_at: i64,
+}
+
+/// Backups from before one-time Kyber pre-keys hold only last-resort keys.
+fn default_last_resort() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Zeroize)]
+struct SessionRow {
+    address: String,
+    device_id: u32,
+    session_data: Vec<u8>,
+    created_at: i64,
+    last_used_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Zeroize)]
+struct SenderKeyRow {
+    address: String,
+    device_id: u32,
+    distribution_id: String,
+    record: Vec<u8>,
+    created_at: i64,
+    updated_at: i64,
+}
+
+/// Export the local crypto state as an archive encrypted under `passphrase`.
+///
+/// Returns `CryptoError::IdentityNotInitialized` if there is no identity to
+/// back up. Uses the default `Argon2Params`.
+pub fn export(conn: &Connection, passphrase: &str) -> Result<Vec<u8>, CryptoError> {
+    export_with_params(conn, passphrase, &Argon2Params::default())
+}
+
+/// Like `export`, deriving the archive key with `params`. They are stored
+/// in the archive header, so `import` needs nothing extra.
+pub fn export_with_params(
+    conn: &Connection,
+    passphrase: &str,
+    params: &Argon2Params,
+) -> Result<Vec<u8>, CryptoError> {
+    if passphrase.is_empty() {
+        return Err(CryptoError::InvalidKey(
+            "passphrase must not be empty".into(),
+        ));
+    }
+
+    // Read inside one transaction so the archive is a consistent snapshot
+    let contents = with_transaction(conn, |_| read_contents(conn))?;
+    let plaintext = Zeroizing::new(serde_json::to_vec(&contents)?);
+
+    let salt = generate_salt();
+    let mut nonce = [0u8; NONCE_SIZE];
+    rand::rng().fill_bytes(&mut nonce);
+
+    let mut archive = Vec::with_capacity(HEADER_SIZE + plaintext.len() + 16);
+    archive.extend_from_slice(MAGIC);
+    archive.push(VERSION);
+    archive.extend_from_slice(&params.to_bytes());
+    archive.extend_from_slice(&salt);
+    archive.extend_from_slice(&nonce);
+
+    let cipher = backup_cipher(passphrase, &salt, params)?;
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &plaintext,
+                aad: &archive,
+            },
+        )
+        .map_err(|e| CryptoError::StorageError(format!("backup encryption failed: {e}")))?;
+    archive.extend_from_slice(&ciphertext);
+    Ok(archive)
+}
+
+/// Restore an archive produced by `export`, replacing all crypto state in
+/// `conn`. Nothing is changed unless the whole archive is restored.
+///
+/// Returns `CryptoError::DecryptionFailed` for a wrong passphrase or a
+/// tampered archive, and `CryptoError::SerializationError` for data that is
+/// not a backup.
+pub fn import(conn: &Connection, archive: &[u8], passphrase: &str) -> Result<(), CryptoError> {
+    if archive.len() < V1_HEADER_SIZE || &archive[..MAGIC.len()] != MAGIC {
+        return Err(CryptoError::SerializationError(
+            "not an openconv key backup".into(),
+        ));
+    }
+    let (header_size, params) = match archive[MAGIC.len()] {
+        1 => (V1_HEADER_SIZE, Argon2Params::default()),
+        VERSION if archive.len() >= HEADER_SIZE => {
+            let params = &archive[MAGIC.len() + 1..MAGIC.len() + 1 + Argon2Params::ENCODED_LEN];
+            let params = Argon2Params::from_bytes(params)
+                .map_err(|e| CryptoError::SerializationError(e.to_string()))?;
+            (HEADER_SIZE, params)
+        }
+        VERSION => {
+            return Err(CryptoError::SerializationError(
+                "truncated key backup".into(),
+            ))
+        }
+        version => {
+            return Err(CryptoError::SerializationError(format!(
+                "unsupported key backup version {version}"
+            )))
+        }
+    };
+
+    let (header, ciphertext) = archive.split_at(header_size);
+    let (salt, nonce) = header[header_size - SALT_SIZE - NONCE_SIZE..].split_at(SALT_SIZE);
+
+    let cipher = backup_cipher(passphrase, salt, &params)?;
+    let plaintext = Zeroizing::new(
+        cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map_err(|_| {
+                CryptoError::DecryptionFailed("wrong passphrase or corrupted backup".into())
+            })?,
+    );
+    let contents: BackupContents = serde_json::from_slice(&plaintext)?;
+
+    with_transaction(conn, |_| write_contents(conn, &contents))
+}
+
+/// AES-256-GCM keyed from `passphrase` via Argon2id and HKDF-SHA256.
+fn backup_cipher(
+    passphrase: &str,
+    salt: &[u8],
+    params: &Argon2Params,
+) -> Result<Aes256Gcm, CryptoError> {
+    let master_key = init_master_key_from_passphrase_with_params(passphrase, salt, params)?;
+    let hk = Hkdf::<Sha256>::new(None, master_key.as_bytes());
+    let mut key = Zeroizing::new([0u8; 32]);
+    hk.expand(BACKUP_KEY_INFO, key.as_mut())
+        .map_err(|e| CryptoError::InvalidKey(e.to_string()))?;
+    Aes256Gcm::new_from_slice(key.as_ref()).map_err(|e| CryptoError::InvalidKey(e.to_string()))
+}
+
+fn read_contents(conn: &Connection) -> Result<BackupContents, CryptoError> {
+    let identity = conn
+        .query_row(
+            "SELECT public_key, private_key, created_at FROM crypto_identity_keys WHERE id = 1",
+            [],
+            |row| {
+                Ok(IdentityRow {
+                    public_key: row.get(0)?,
+                    private_key: row.get(1)?,
+                    created_at: row.get(2)?,
+                })
+            },
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => CryptoError::IdentityNotInitialized,
+            other => other.into(),
+        })?;
+
+    Ok(BackupContents {
+        identity,
+        archived_identities: query_rows(
+            conn,
+            "SELECT public_key, private_key, created_at, archived_at
+             FROM crypto_archived_identity_keys",
+            |row| {
+                Ok(ArchivedIdentityRow {
+                    public_key: row.get(0)?,
+                    private_key: row.get(1)?,
+                    created_at: row.get(2)?,
+                    archived_at: row.get(3)?,
+                })
+            },
+        )?,
+        config: query_rows(conn, "SELECT key, value FROM crypto_config", |row| {
+            Ok(ConfigRow {
+                key: row.get(0)?,
+                value: row.get(1)?,
+            })
+        })?,
+        trusted_identities: query_rows(
+            conn,
+            "SELECT address, device_id, identity_key, first_seen_at, verified_at,
+                    verified_identity_key
+             FROM crypto_trusted_identities",
+            |row| {
+                Ok(TrustedIdentityRow {
+                    address: row.get(0)?,
+                    device_id: row.get(1)?,
+                    identity_key: row.get(2)?,
+                    first_seen_at: row.get(3)?,
+                    verified_at: row.get(4)?,
+                    verified_identity_key: row.get(5)?,
+                })
+            },
+        )?,
+        pre_keys: query_rows(
+            conn,
+            "SELECT key_id, record, uploaded, created_at FROM crypto_pre_keys",
+            |row| {
+                Ok(PreKeyRow {
+                    key_id: row.get(0)?,
+                    record: row.get(1)?,
+                    uploaded: row.get(2)?,
+                    created_at: row.get(3)?,
+                })
+            },
+        )?,
+        signed_pre_keys: query_rows(
+            conn,
+            "SELECT key_id, record, created_at FROM crypto_signed_pre_keys",
+            key_row,
+        )?,
+        kyber_pre_keys: query_rows(
+            conn,
+            "SELECT key_id, record, last_resort, created_at FROM crypto_kyber_pre_keys",
+            |row| {
+                Ok(KyberKeyRow {
+                    key_id: row.get(0)?,
+                    record: row.get(1)?,
+                    last_resort: row.get(2)?,
+                    created_at: row.get(3)?,
+                })
+            },
+        )?,
+        sessions: query_rows(
+            conn,
+            "SELECT address, device_id, session_data, created_at, last_used_at
+             FROM crypto_sessions",
+            |row| {
+                Ok(SessionRow {
+                    address: row.get(0)?,
+                    device_id: row.get(1)?,
+                    session_data: row.get(2)?,
+                    created_at: row.get(3)?,
+                    last_used_at: row.get(4)?,
+                })
+            },
+        )?,
+        sender_keys: query_rows(
+            conn,
+            "SELECT address, device_id, distribution_id, record, created_at, updated_at
+             FROM crypto_sender_keys",
+            |row| {
+                Ok(SenderKeyRow {
+                    address: row.get(0)?,
+                    device_id: row.get(1)?,
+                    distribution_id: row.get(2)?,
+                    record: row.get(3)?,
+                    created_at: row.get(4)?,
+                    updated_at: row.get(5)?,
+                })
+            },
+        )?,
+    })
+}
+
+fn key_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<KeyRow> {
+    Ok(KeyRow {
+        key_id: row.get(0)?,
+        record: row.get(1)?,
+        created_at: row.get(2)?,
+    })
+}
+
+fn query_rows<T>(
+    conn: &Connection,
+    sql: &str,
+    map: impl FnMut(&rusqlite::Row<'_>) -> rusqlite::Result<T>,
+) -> Result<Vec<T>, CryptoError> {
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map([], map)?.collect::<Result<_, _>>()?;
+    Ok(rows)
+}
+
+fn write_contents(conn: &Connection, contents: &BackupContents) -> Result<(), CryptoError> {
+    for table in [
+        "crypto_identity_keys",
+        "crypto_archived_identity_keys",
+        "crypto_config",
+        "crypto_trusted_identities",
+        "crypto_pre_keys",
+        "crypto_signed_pre_keys",
+        "crypto_kyber_pre_keys",
+        "crypto_sessions",
+        "crypto_sender_keys",
+    ] {
+        conn.execute(&format!("DELETE FROM {table}"), [])?;
+    }
+
+    let identity = &contents.identity;
+    conn.execute(
+        "INSERT INTO crypto_identity_keys (id, public_key, private_key, created_at)
+         VALUES (1, ?1, ?2, ?3)",
+        rusqlite::params![
+            identity.public_key,
+            identity.private_key,
+            identity.created_at
+        ],
+    )?;
+    for row in &contents.archived_identities {
+        conn.execute(
+            "INSERT INTO crypto_archived_identity_keys
+                 (public_key, private_key, created_at, archived_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![
+                row.public_key,
+                row.private_key,
+                row.created_at,
+                row.archived_at
+            ],
+        )?;
+    }
+    for row in &contents.config {
+        conn.execute(
+            "INSERT INTO crypto_config (key, value) VALUES (?1, ?2)",
+            rusqlite::params![row.key, row.value],
+        )?;
+    }
+    for row in &contents.trusted_identities {
+        conn.execute(
+            "INSERT INTO crypto_trusted_identities
+                 (address, device_id, identity_key, first_seen_at, verified_at,
+                  verified_identity_key)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![
+                row.address,
+                row.device_id,
+                row.identity_key,
+                row.first_seen_at,
+                row.verified_at,
+                row.verified_identity_key
+            ],
+        )?;
+    }
+    for row in &contents.pre_keys {
+        conn.execute(
+            "INSERT INTO crypto_pre_keys (key_id, record, uploaded, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![row.key_id, row.record, row.uploaded, row.created_at],
+        )?;
+    }
+    for row in &contents.signed_pre_keys {
+        conn.execute(
+            "INSERT INTO crypto_signed_pre_keys (key_id, record, created_at) VALUES (?1, ?2, ?3)",
+            rusqlite::params![row.key_id, row.record, row.created_at],
+        )?;
+    }
+    for row in &contents.kyber_pre_keys {
+        conn.execute(
+            "INSERT INTO crypto_kyber_pre_keys (key_id, record, last_resort, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![row.key_id, row.record, row.last_resort, row.created_at],
+        )?;
+    }
+    for row in &contents.sessions {
+        conn.execute(
+            "INSERT INTO crypto_sessions
+                 (address, device_id, session_data, created_at, last_used_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![
+                row.address,
+                row.device_id,
+                row.session_data,
+                row.created_at,
+                row.last_used_at
+            ],
+        )?;
+    }
+    for row in &contents.sender_keys {
+        conn.execute(
+            "INSERT INTO crypto_sender_keys
+                 (address, device_id, distribution_id, record, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![
+                row.address,
+                row.device_id,
+                row.distribution_id,
+                row.record,
+                row.created_at,
+                row.updated_at
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group;
+    use crate::identity::{generate_identity, get_public_key_string};
+    use crate::message::{decrypt_message, encrypt_message};
+    use crate::prekeys::generate_pre_key_bundle;
+    use crate::session::create_outgoing_session;
+    use crate::storage::{init_test_db, CryptoStore};
+    use libsignal_protocol::{DeviceId, ProtocolAddress};
+
+    fn count(conn: &Connection, table: &str) -> u32 {
+        conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn export_then_import_restores_identity_and_keys() {
+        let conn = init_test_db();
+        generate_identity(&conn).unwrap();
+        crate::identity::rotate(&conn).unwrap();
+        generate_pre_key_bundle(&conn, "alice-user-id").unwrap();
+        let public_key = get_public_key_string(&conn).unwrap();
+
+        let archive = export(&conn, "correct horse battery staple").unwrap();
+        assert_eq!(&archive[..4], MAGIC);
+
+        let restored = init_test_db();
+        import(&restored, &archive, "correct horse battery staple").unwrap();
+        assert_eq!(get_public_key_string(&restored).unwrap(), public_key);
+        for table in [
+            "crypto_archived_identity_keys",
+            "crypto_config",
+            "crypto_pre_keys",
+            "crypto_signed_pre_keys",
+            "crypto_kyber_pre_keys",
+        ] {
+            assert_eq!(count(&restored, table), count(&conn, table), "{table}");
+        }
+        assert_eq!(
+            CryptoStore::new(&restored)
+                .get_config("registration_id")
+                .unwrap(),
+            CryptoStore::new(&conn)
+                .get_config("registration_id")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn restored_device_continues_existing_sessions() {
+        let alice_conn = init_test_db();
+        let bob_conn = init_test_db();
+        generate_identity(&alice_conn).unwrap();
+        generate_identity(&bob_conn).unwrap();
+        let bob_bundle = generate_pre_key_bundle(&bob_conn, "bob-user-id").unwrap();
+        let bob = create_outgoing_session(&alice_conn, &serde_json::to_vec(&bob_bundle).unwrap())
+            .unwrap();
+        let alice = ProtocolAddress::new("alice-user-id".into(), DeviceId::new(1).unwrap());
+
+        let first = encrypt_message(&alice_conn, &bob, b"before backup").unwrap();
+        decrypt_message(&bob_conn, &alice, &first.ciphertext, first.message_type).unwrap();
+
+        // Bob moves to a new device
+        let archive = export(&bob_conn, "passphrase").unwrap();
+        let new_bob_conn = init_test_db();
+        import(&new_bob_conn, &archive, "passphrase").unwrap();
+
+        let second = encrypt_message(&alice_conn, &bob, b"after restore").unwrap();
+        let plaintext = decrypt_message(
+            &new_bob_conn,
+            &alice,
+            &second.ciphertext,
+            second.message_type,
+        )
+        .unwrap();
+        assert_eq!(plaintext, b"after restore");
+    }
+
+    #[test]
+    fn restored_device_keeps_group_sender_keys() {
+        let alice_conn = init_test_db();
+        let bob_conn = init_test_db();
+        generate_identity(&bob_conn).unwrap();
+        let alice = ProtocolAddress::new("alice-user-id".into(), DeviceId::new(1).unwrap());
+        let distribution_id = uuid::Uuid::new_v4();
+        let distribution =
+            group::create_distribution_message(&alice_conn, &alice, distribution_id).unwrap();
+        group::process_distribution_message(&bob_conn, &alice, &distribution).unwrap();
+
+        let archive = export(&bob_conn, "passphrase").unwrap();
+        let new_bob_conn = init_test_db();
+        import(&new_bob_conn, &archive, "passphrase").unwrap();
+
+        let ciphertext = group::encrypt(&alice_conn, &alice, distribution_id, b"hi all").unwrap();
+        assert_eq!(
+            group::decrypt(&new_bob_conn, &alice, &ciphertext).unwrap(),
+            b"hi all"
+        );
+    }
+
+    #[test]
+    fn import_replaces_existing_state() {
+        let old = init_test_db();
+        generate_identity(&old).unwrap();
+        let archive = export(&old, "passphrase").unwrap();
+
+        let target = init_test_db();
+        generate_identity(&target).unwrap();
+        generate_pre_key_bundle(&target, "someone-else").unwrap();
+        import(&target, &archive, "passphrase").unwrap();
+
+        assert_eq!(
+            get_public_key_string(&target).unwrap(),
+            get_public_key_string(&old).unwrap()
+        );
+        assert_eq!(count(&target, "crypto_pre_keys"), 0);
+    }
+
+    #[test]
+    fn export_with_params_records_them_for_import() {
+        let conn = init_test_db();
+        generate_identity(&conn).unwrap();
+        let params = Argon2Params {
+            memory_kib: 8192,
+            iterations: 2,
+            parallelism: 1,
+        };
+        let archive = export_with_params(&conn, "passphrase", &params).unwrap();
+        assert_eq!(
+            &archive[MAGIC.len() + 1..MAGIC.len() + 1 + Argon2Params::ENCODED_LEN],
+            &params.to_bytes()
+        );
+
+        let target = init_test_db();
+        import(&target, &archive, "passphrase").unwrap();
+        assert_eq!(
+            get_public_key_string(&target).unwrap(),
+            get_public_key_string(&conn).unwrap()
+        );
+    }
+
+    #[test]
+    fn wrong_passphrase_fails_and_changes_nothing() {
+        let conn = init_test_db();
+        generate_identity(&conn).unwrap();
+        let archive = export(&conn, "right").unwrap();
+
+        let target = init_test_db();
+        let result = import(&target, &archive, "wrong");
+        assert!(matches!(result, Err(CryptoError::DecryptionFailed(_))));
+        assert_eq!(count(&target, "crypto_identity_keys"), 0);
+    }
+
+    #[test]
+    fn tampered_archive_fails() {
+        let conn = init_test_db();
+        generate_identity(&conn).unwrap();
+        let archive = export(&conn, "passphrase").unwrap();
+
+        // The header is authenticated too
+        for index in [
+            MAGIC.len() + Argon2Params::ENCODED_LEN,
+            HEADER_SIZE - 1,
+            archive.len() - 1,
+        ] {
+            let mut tampered = archive.clone();
+            tampered[index] ^= 0x01;
+            let result = import(&init_test_db(), &tampered, "passphrase");
+            assert!(matches!(result, Err(CryptoError::DecryptionFailed(_))));
+        }
+    }
+
+    #[test]
+    fn import_rejects_data_that_is_not_a_backup() {
+        let conn = init_test_db();
+        for data in [&b""[..], b"OCKB", b"not a backup at all, just some bytes"] {
+            let result = import(&conn, data, "passphrase");
+            assert!(matches!(result, Err(CryptoError::SerializationError(_))));
+        }
+
+        generate_identity(&conn).unwrap();
+        let mut archive = export(&conn, "passphrase").unwrap();
+        archive[MAGIC.len()] = VERSION + 1;
+        let result = import(&init_test_db(), &archive, "passphrase");
+        assert!(matches!(result, Err(CryptoError::SerializationError(_))));
+    }
+
+    #[test]
+    fn export_requires_identity_and_passphrase() {
+        let conn = init_test_db();
+        assert!(matches!(
+            export(&conn, "passphrase"),
+            Err(CryptoError::IdentityNotInitialized)
+        ));
+
+        generate_identity(&conn).unwrap();
+        assert!(matches!(export(&conn, ""), Err(CryptoError::InvalidKey(_))));
+    }
+
+    #[test]
+    fn archive_does_not_contain_private_key_in_the_clear() {
+        let conn = init_test_db();
+        generate_identity(&conn).unwrap();
+        let (_, private_key) = CryptoStore::new(&conn).get_identity_keypair().unwrap();
+
+        let archive = export(&conn, "passphrase").unwrap();
+        assert!(!archive
+            .windows(private_key.len())
+            .any(|window| window == private_key.as_slice()));
+    }
+}
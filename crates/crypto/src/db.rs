@@ -0,0 +1,251 @@
+//! Pooled access to the encrypted crypto database.
+//!
+//! The rest of the crate borrows a caller-provided `&Connection`, which
+//! forces a caller with one connection to serialize every crypto operation
+//! behind a single lock. `CryptoDb` instead owns an r2d2 pool of SQLCipher
+//! connections and lends one per operation. Each connection is keyed, in
+//! WAL mode and has a busy timeout, and the crate opens its write
+//! transactions IMMEDIATE, so readers proceed in parallel and writers wait
+//! for each other instead of failing with `SQLITE_BUSY`.
+//!
+//! The borrowed-connection API is unchanged; `CryptoDb::with_conn` simply
+//! hands a pooled connection to the same functions:
+//!
+//! ```ignore
+//! let db = CryptoDb::open(path, db_key)?;
+//! let bundle = db.with_conn(|conn| prekeys::generate_pre_key_bundle(conn, user_id))?;
+//! ```
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use r2d2::{Pool, PooledConnection};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::Connection;
+
+use crate::error::CryptoError;
+use crate::master_key::{apply_encryption_key, DbEncryptionKey};
+use crate::storage::CryptoStore;
+
+/// Tuning for `CryptoDb::open_with_options`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolOptions {
+    /// Maximum number of open connections.
+    pub max_size: u32,
+    /// How long a connection waits on a lock held by another before
+    /// returning `SQLITE_BUSY`.
+    pub busy_timeout: Duration,
+    /// How long `with_conn` waits for a free connection.
+    pub checkout_timeout: Duration,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            max_size: 4,
+            busy_timeout: Duration::from_secs(5),
+            checkout_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A pool of keyed connections to the crypto database. Cheap to clone and
+/// safe to share between threads.
+#[derive(Clone)]
+pub struct CryptoDb {
+    pool: Pool<SqliteConnectionManager>,
+}
+
+impl std::fmt::Debug for CryptoDb {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CryptoDb")
+            .field("state", &self.pool.state())
+            .finish()
+    }
+}
+
+impl CryptoDb {
+    /// Open the database at `path` with the default `PoolOptions`.
+    pub fn open(path: impl AsRef<Path>, db_key: DbEncryptionKey) -> Result<Self, CryptoError> {
+        Self::open_with_options(path, db_key, &PoolOptions::default())
+    }
+
+    /// Open the database at `path`, keying every pooled connection with
+    /// `db_key`, and run the crypto migrations.
+    ///
+    /// The key is checked on a single connection before the pool is built,
+    /// so a wrong key fails immediately rather than after the checkout
+    /// timeout.
+    pub fn open_with_options(
+        path: impl AsRef<Path>,
+        db_key: DbEncryptionKey,
+        options: &PoolOptions,
+    ) -> Result<Self, CryptoError> {
+        let path = path.as_ref();
+        let db_key = Arc::new(db_key);
+        let busy_timeout = options.busy_timeout;
+
+        let conn = Connection::open(path)?;
+        configure(&conn, &db_key, busy_timeout)?;
+        CryptoStore::new(&conn).run_migrations()?;
+        drop(conn);
+
+        let manager = SqliteConnectionManager::file(path).with_init(move |conn| {
+            configure(conn, &db_key, busy_timeout).map_err(|e| {
+                rusqlite::Error::SqliteFailure(
+                    rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_NOTADB),
+                    Some(e.to_string()),
+                )
+            })
+        });
+        let pool = Pool::builder()
+            .max_size(options.max_size)
+            .connection_timeout(options.checkout_timeout)
+            .build(manager)
+            .map_err(pool_error)?;
+        Ok(Self { pool })
+    }
+
+    /// Check out a connection. It returns to the pool when dropped.
+    pub fn get(&self) -> Result<PooledConnection<SqliteConnectionManager>, CryptoError> {
+        self.pool.get().map_err(pool_error)
+    }
+
+    /// Run `f` with a pooled connection, e.g. any of the crate's
+    /// `&Connection` functions.
+    pub fn with_conn<T>(
+        &self,
+        f: impl FnOnce(&Connection) -> Result<T, CryptoError>,
+    ) -> Result<T, CryptoError> {
+        let conn = self.get()?;
+        f(&*conn)
+    }
+}
+
+fn configure(
+    conn: &Connection,
+    db_key: &DbEncryptionKey,
+    busy_timeout: Duration,
+) -> Result<(), CryptoError> {
+    conn.busy_timeout(busy_timeout)?;
+    apply_encryption_key(conn, db_key)
+}
+
+fn pool_error(err: r2d2::Error) -> CryptoError {
+    CryptoError::StorageError(format!("connection pool: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::{generate_identity, get_public_key_string};
+    use crate::master_key::{derive_db_encryption_key, init_master_key_from_passphrase};
+    use crate::message::{decrypt_message, encrypt_message, EncryptedMessage};
+    use crate::prekeys::generate_pre_key_bundle;
+    use crate::session::create_outgoing_session;
+    use libsignal_protocol::{DeviceId, ProtocolAddress};
+
+    fn db_key(passphrase: &str) -> DbEncryptionKey {
+        let master_key = init_master_key_from_passphrase(passphrase, &[20u8; 16]).unwrap();
+        derive_db_encryption_key(&master_key).unwrap()
+    }
+
+    #[test]
+    fn pooled_connections_share_the_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = CryptoDb::open(dir.path().join("pool.db"), db_key("pool")).unwrap();
+        db.with_conn(|conn| generate_identity(conn).map(|_| ()))
+            .unwrap();
+        let expected = db.with_conn(get_public_key_string).unwrap();
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let db = db.clone();
+                std::thread::spawn(move || db.with_conn(get_public_key_string).unwrap())
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn concurrent_encrypts_and_decrypts_wait_instead_of_failing() {
+        let dir = tempfile::tempdir().unwrap();
+        let alice = CryptoDb::open(dir.path().join("alice.db"), db_key("alice")).unwrap();
+        let bob = CryptoDb::open(dir.path().join("bob.db"), db_key("bob")).unwrap();
+        alice
+            .with_conn(|conn| generate_identity(conn).map(|_| ()))
+            .unwrap();
+        bob.with_conn(|conn| generate_identity(conn).map(|_| ()))
+            .unwrap();
+        let bundle = bob
+            .with_conn(|conn| generate_pre_key_bundle(conn, "bob-user-id"))
+            .unwrap();
+        let bundle_json = serde_json::to_vec(&bundle).unwrap();
+        let bob_address = alice
+            .with_conn(|conn| create_outgoing_session(conn, &bundle_json))
+            .unwrap();
+        let alice_address = ProtocolAddress::new(
+            "alice-user-id".to_string(),
+            DeviceId::new(1).expect("valid"),
+        );
+
+        // Each encrypt and decrypt reads the session before advancing it,
+        // so the threads contend for the write lock throughout
+        let sent: Vec<Vec<(String, EncryptedMessage)>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|thread| {
+                    let (alice, bob_address) = (&alice, &bob_address);
+                    scope.spawn(move || {
+                        (0..10)
+                            .map(|i| {
+                                let plaintext = format!("message {thread}-{i}");
+                                let encrypted = alice
+                                    .with_conn(|conn| {
+                                        encrypt_message(conn, bob_address, plaintext.as_bytes())
+                                    })
+                                    .unwrap();
+                                (plaintext, encrypted)
+                            })
+                            .collect()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        std::thread::scope(|scope| {
+            for messages in &sent {
+                let (bob, alice_address) = (&bob, &alice_address);
+                scope.spawn(move || {
+                    for (plaintext, encrypted) in messages {
+                        let decrypted = bob
+                            .with_conn(|conn| {
+                                decrypt_message(
+                                    conn,
+                                    alice_address,
+                                    &encrypted.ciphertext,
+                                    encrypted.message_type,
+                                )
+                            })
+                            .unwrap();
+                        assert_eq!(decrypted, plaintext.as_bytes());
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn open_with_wrong_key_fails_fast() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keyed.db");
+        drop(CryptoDb::open(&path, db_key("right")).unwrap());
+
+        let started = std::time::Instant::now();
+        assert!(CryptoDb::open(&path, db_key("wrong")).is_err());
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
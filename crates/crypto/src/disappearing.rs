@@ -0,0 +1,161 @@
+//! Disappearing-message timer negotiation.
+//!
+//! A timer change is an ordinary encrypted `MessageEnvelope` carrying an
+//! `ExpirationTimerPayload`. Its clear-text payload kind is `System`, so the
+//! server relays it like any other control message without learning that the
+//! timer changed or what it was set to. Both clients apply the change they
+//! sent or received, which keeps their settings in agreement.
+//!
+//! Pairwise conversations use `encrypt_timer_change` and
+//! `decrypt_timer_change`. Group channels encrypt `timer_change_envelope`
+//! with `group::encrypt` and read it back with `parse_timer_change`.
+
+use libsignal_protocol::ProtocolAddress;
+use openconv_shared::api::envelope::{MessageEnvelope, MessagePayload};
+use rusqlite::Connection;
+
+use crate::error::CryptoError;
+use crate::message::{
+    decrypt_envelope_with_options, encrypt_envelope_with_options, DecryptOptions, EncryptOptions,
+    EncryptedMessage, MessageType,
+};
+
+/// A change to a conversation's disappearing-message timer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerChange {
+    /// Messages disappear this many seconds after they are read.
+    Set { expires_in_seconds: u32 },
+    /// Disappearing messages are turned off.
+    Clear,
+}
+
+/// The envelope announcing `change`.
+pub fn timer_change_envelope(change: TimerChange) -> MessageEnvelope {
+    MessageEnvelope::expiration_timer(match change {
+        TimerChange::Set { expires_in_seconds } => Some(expires_in_seconds),
+        TimerChange::Clear => None,
+    })
+}
+
+/// The timer change carried by `envelope`, or `None` for any other payload.
+///
+/// A zero-second timer is treated as `Clear`.
+pub fn parse_timer_change(envelope: &MessageEnvelope) -> Option<TimerChange> {
+    match &envelope.payload {
+        MessagePayload::ExpirationTimer(timer) => Some(match timer.expires_in_seconds {
+            Some(0) | None => TimerChange::Clear,
+            Some(expires_in_seconds) => TimerChange::Set { expires_in_seconds },
+        }),
+        _ => None,
+    }
+}
+
+/// Encrypt a timer change to `recipient` over the pairwise session.
+pub fn encrypt_timer_change(
+    conn: &Connection,
+    recipient: &ProtocolAddress,
+    change: TimerChange,
+    options: &EncryptOptions,
+) -> Result<EncryptedMessage, CryptoError> {
+    encrypt_envelope_with_options(conn, recipient, &timer_change_envelope(change), options)
+}
+
+/// Decrypt a timer change sent with `encrypt_timer_change`.
+///
+/// Returns `CryptoError::SerializationError` if the message decrypts to a
+/// different payload. As with `decrypt_envelope`, the ratchet has advanced by
+/// then.
+pub fn decrypt_timer_change(
+    conn: &Connection,
+    sender: &ProtocolAddress,
+    ciphertext: &[u8],
+    message_type: MessageType,
+    options: &DecryptOptions,
+) -> Result<TimerChange, CryptoError> {
+    let envelope = decrypt_envelope_with_options(conn, sender, ciphertext, message_type, options)?;
+    parse_timer_change(&envelope).ok_or_else(|| {
+        CryptoError::SerializationError(format!(
+            "expected an expiration timer payload, got {:?}",
+            envelope.kind()
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::generate_identity;
+    use crate::message::encrypt_envelope;
+    use crate::prekeys::generate_pre_key_bundle;
+    use crate::session::create_outgoing_session;
+    use crate::storage::init_test_db;
+    use libsignal_protocol::DeviceId;
+    use openconv_shared::api::envelope::PayloadKind;
+
+    fn setup() -> (Connection, Connection, ProtocolAddress, ProtocolAddress) {
+        let alice_conn = init_test_db();
+        let bob_conn = init_test_db();
+        generate_identity(&alice_conn).unwrap();
+        generate_identity(&bob_conn).unwrap();
+        let bundle = generate_pre_key_bundle(&bob_conn, "bob-user-id").unwrap();
+        let bob =
+            create_outgoing_session(&alice_conn, &serde_json::to_vec(&bundle).unwrap()).unwrap();
+        let alice = ProtocolAddress::new("alice-user-id".into(), DeviceId::new(1).unwrap());
+        (alice_conn, bob_conn, bob, alice)
+    }
+
+    #[test]
+    fn timer_changes_round_trip() {
+        let (alice_conn, bob_conn, bob, alice) = setup();
+
+        for change in [
+            TimerChange::Set {
+                expires_in_seconds: 86_400,
+            },
+            TimerChange::Clear,
+        ] {
+            let encrypted =
+                encrypt_timer_change(&alice_conn, &bob, change, &EncryptOptions::default())
+                    .unwrap();
+            let received = decrypt_timer_change(
+                &bob_conn,
+                &alice,
+                &encrypted.ciphertext,
+                encrypted.message_type,
+                &DecryptOptions::default(),
+            )
+            .unwrap();
+            assert_eq!(received, change);
+        }
+    }
+
+    #[test]
+    fn timer_change_is_labelled_as_system_message() {
+        let envelope = timer_change_envelope(TimerChange::Set {
+            expires_in_seconds: 60,
+        });
+        assert_eq!(envelope.kind(), PayloadKind::System);
+    }
+
+    #[test]
+    fn zero_second_timer_parses_as_clear() {
+        let envelope = MessageEnvelope::expiration_timer(Some(0));
+        assert_eq!(parse_timer_change(&envelope), Some(TimerChange::Clear));
+        assert_eq!(parse_timer_change(&MessageEnvelope::text("hi")), None);
+    }
+
+    #[test]
+    fn decrypt_timer_change_rejects_other_payloads() {
+        let (alice_conn, bob_conn, bob, alice) = setup();
+        let encrypted = encrypt_envelope(&alice_conn, &bob, &MessageEnvelope::text("hi")).unwrap();
+
+        let result = decrypt_timer_change(
+            &bob_conn,
+            &alice,
+            &encrypted.ciphertext,
+            encrypted.message_type,
+            &DecryptOptions::default(),
+        );
+        assert!(matches!(result, Err(CryptoError::SerializationError(_))));
+    }
+}
@@ -1,18 +1,55 @@
 //! Symmetric file encryption using AES-256-GCM.
 //!
 //! Provides `encrypt_file` and `decrypt_file` for standalone file encryption
-//! with a random per-file key. Independent of the Signal protocol — the caller
-//! distributes `FileKey` to recipients via their Signal sessions.
+//! with a random per-file key. The ciphertext is independent of the Signal
+//! protocol: it is uploaded once, and `wrap_key_for_recipient` encrypts the
+//! `FileKey` to each recipient over their pairwise session.
+//!
+//! For files too large to hold in memory, `EncryptingWriter` and
+//! `DecryptingReader` encrypt and decrypt a stream in fixed-size chunks.
+//! Stream layout:
+//!
+//! `version (1) || chunk size (4) || base nonce (12) || chunk* || manifest (16)`
+//!
+//! Each chunk is AES-256-GCM with its own nonce (the base nonce with the
+//! chunk index XORed into its last 8 bytes), so chunks cannot be reordered.
+//! The manifest is a GCM tag over the header, AAD, every chunk tag, the
+//! chunk count and the plaintext length, so truncation and appended data
+//! are detected at the end of the stream. Chunks and manifest use separate
+//! keys derived from the `FileKey` with HKDF-SHA256.
+
+use std::io::{self, Read, Write};
 
 use aes_gcm::aead::{Aead, Payload};
 use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use hkdf::Hkdf;
+use libsignal_protocol::ProtocolAddress;
 use rand::RngCore;
+use rusqlite::Connection;
+use sha2::{Digest, Sha256};
 use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 use crate::error::CryptoError;
+use crate::message::{decrypt_message, encrypt_message, EncryptedMessage, MessageType};
 
 const NONCE_SIZE: usize = 12; // 96-bit nonce for AES-256-GCM
 const KEY_SIZE: usize = 32; // 256-bit key
+const TAG_SIZE: usize = 16; // GCM authentication tag
+
+const STREAM_VERSION: u8 = 1;
+const STREAM_HEADER_SIZE: usize = 1 + 4 + NONCE_SIZE;
+const STREAM_CHUNK_KEY_INFO: &[u8] = b"openconv-file-stream-chunk-v1";
+const STREAM_MANIFEST_KEY_INFO: &[u8] = b"openconv-file-stream-manifest-v1";
+
+/// Prefix of a wrapped key's plaintext, so other messages sent over the same
+/// session are never mistaken for a key.
+const WRAPPED_KEY_PREFIX: &[u8; 4] = b"ocfk";
+const WRAPPED_KEY_VERSION: u8 = 1;
+
+/// Plaintext bytes per chunk used by `EncryptingWriter::new`.
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+/// Largest chunk size a stream may declare, bounding reader memory use.
+pub const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
 
 /// A 32-byte AES-256 key that is securely zeroed on drop.
 #[derive(Zeroize, ZeroizeOnDrop)]
@@ -20,6 +57,13 @@ pub struct FileKey {
     pub(crate) key: [u8; 32],
 }
 
+impl FileKey {
+    /// Rebuild a key received from a sender (e.g. inside a decrypted message).
+    pub fn from_bytes(key: [u8; KEY_SIZE]) -> Self {
+        Self { key }
+    }
+}
+
 /// Container for encrypted output: `nonce (12 bytes) || ciphertext || auth tag (16 bytes)`.
 pub struct EncryptedBlob {
     pub data: Vec<u8>,
@@ -113,6 +157,424 @@ pub fn decrypt_file(
     Ok(plaintext)
 }
 
+/// Encrypt `file_key` to `recipient` over their Double Ratchet session.
+///
+/// The result is a small message (the key plus a few bytes of framing) to
+/// send alongside the attachment reference; repeat per recipient to share
+/// one uploaded ciphertext with several people. Returns
+/// `CryptoError::SessionNotFound` if there is no session with `recipient`.
+pub fn wrap_key_for_recipient(
+    conn: &Connection,
+    recipient: &ProtocolAddress,
+    file_key: &FileKey,
+) -> Result<EncryptedMessage, CryptoError> {
+    let mut plaintext = Zeroizing::new(Vec::with_capacity(WRAPPED_KEY_PREFIX.len() + 1 + KEY_SIZE));
+    plaintext.extend_from_slice(WRAPPED_KEY_PREFIX);
+    plaintext.push(WRAPPED_KEY_VERSION);
+    plaintext.extend_from_slice(&file_key.key);
+    encrypt_message(conn, recipient, &plaintext)
+}
+
+/// Decrypt a key wrapped by `wrap_key_for_recipient` from `sender`.
+///
+/// Returns `CryptoError::FileEncryptionError` if the message decrypts but
+/// does not hold a wrapped key. The ratchet has still advanced at that
+/// point, as with `message::decrypt_envelope`.
+pub fn unwrap_key_from_sender(
+    conn: &Connection,
+    sender: &ProtocolAddress,
+    ciphertext: &[u8],
+    message_type: MessageType,
+) -> Result<FileKey, CryptoError> {
+    let plaintext = Zeroizing::new(decrypt_message(conn, sender, ciphertext, message_type)?);
+    let key = plaintext
+        .strip_prefix(WRAPPED_KEY_PREFIX.as_slice())
+        .and_then(|rest| rest.strip_prefix(&[WRAPPED_KEY_VERSION]))
+        .filter(|key| key.len() == KEY_SIZE)
+        .ok_or_else(|| {
+            CryptoError::FileEncryptionError("message is not a wrapped file key".into())
+        })?;
+
+    let mut file_key = FileKey {
+        key: [0u8; KEY_SIZE],
+    };
+    file_key.key.copy_from_slice(key);
+    Ok(file_key)
+}
+
+/// Encrypts everything written to it as a chunked stream into `inner`.
+///
+/// Plaintext is buffered one chunk at a time. `finish` must be called to
+/// write the final chunk and the manifest; a stream that is dropped without
+/// it fails to decrypt.
+pub struct EncryptingWriter<W: Write> {
+    inner: W,
+    chunk_cipher: Aes256Gcm,
+    manifest_cipher: Aes256Gcm,
+    base_nonce: [u8; NONCE_SIZE],
+    aad: Vec<u8>,
+    chunk_size: usize,
+    buffer: Zeroizing<Vec<u8>>,
+    chunk_index: u64,
+    plaintext_len: u64,
+    digest: Sha256,
+}
+
+impl<W: Write> EncryptingWriter<W> {
+    /// Start a stream with a random key and `DEFAULT_CHUNK_SIZE` chunks.
+    ///
+    /// Returns the writer and the key, which the caller distributes like the
+    /// key from `encrypt_file`. `aad` is bound to every chunk and the
+    /// manifest.
+    pub fn new(inner: W, aad: Option<&[u8]>) -> Result<(Self, FileKey), CryptoError> {
+        Self::with_chunk_size(inner, aad, DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Like `new`, with `chunk_size` plaintext bytes per chunk (at most
+    /// `MAX_CHUNK_SIZE`).
+    pub fn with_chunk_size(
+        mut inner: W,
+        aad: Option<&[u8]>,
+        chunk_size: usize,
+    ) -> Result<(Self, FileKey), CryptoError> {
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(CryptoError::FileEncryptionError(format!(
+                "chunk size must be between 1 and {MAX_CHUNK_SIZE} bytes"
+            )));
+        }
+
+        let mut key = FileKey {
+            key: [0u8; KEY_SIZE],
+        };
+        rand::rng().fill_bytes(&mut key.key);
+        let mut base_nonce = [0u8; NONCE_SIZE];
+        rand::rng().fill_bytes(&mut base_nonce);
+
+        let header = stream_header(chunk_size as u32, &base_nonce);
+        inner.write_all(&header).map_err(stream_io_err)?;
+
+        let aad = aad.unwrap_or_default().to_vec();
+        let (chunk_cipher, manifest_cipher) = stream_ciphers(&key)?;
+        let writer = Self {
+            inner,
+            chunk_cipher,
+            manifest_cipher,
+            base_nonce,
+            digest: stream_digest(&header, &aad),
+            aad,
+            chunk_size,
+            buffer: Zeroizing::new(Vec::with_capacity(chunk_size)),
+            chunk_index: 0,
+            plaintext_len: 0,
+        };
+        Ok((writer, key))
+    }
+
+    /// Write any buffered plaintext and the manifest, returning the inner
+    /// writer.
+    pub fn finish(mut self) -> Result<W, CryptoError> {
+        if !self.buffer.is_empty() {
+            self.write_chunk().map_err(stream_io_err)?;
+        }
+        let aad = manifest_aad(self.digest.clone(), self.chunk_index, self.plaintext_len);
+        let manifest = self
+            .manifest_cipher
+            .encrypt(
+                Nonce::from_slice(&self.base_nonce),
+                Payload {
+                    msg: &[],
+                    aad: &aad,
+                },
+            )
+            .map_err(|e| CryptoError::FileEncryptionError(format!("encryption failed: {e}")))?;
+        self.inner.write_all(&manifest).map_err(stream_io_err)?;
+        self.inner.flush().map_err(stream_io_err)?;
+        Ok(self.inner)
+    }
+
+    fn write_chunk(&mut self) -> io::Result<()> {
+        let nonce = chunk_nonce(&self.base_nonce, self.chunk_index);
+        let ciphertext = self
+            .chunk_cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &self.buffer,
+                    aad: &self.aad,
+                },
+            )
+            .map_err(|e| {
+                stream_crypto_err(CryptoError::FileEncryptionError(format!(
+                    "encryption failed: {e}"
+                )))
+            })?;
+        self.inner.write_all(&ciphertext)?;
+
+        self.digest
+            .update(&ciphertext[ciphertext.len() - TAG_SIZE..]);
+        self.chunk_index += 1;
+        self.plaintext_len += self.buffer.len() as u64;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for EncryptingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let take = buf.len().min(self.chunk_size - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..take]);
+        if self.buffer.len() == self.chunk_size {
+            self.write_chunk()?;
+        }
+        Ok(take)
+    }
+
+    /// Flushes the inner writer. Buffered plaintext stays buffered until a
+    /// chunk fills up or `finish` is called.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decrypts a stream written by `EncryptingWriter`.
+///
+/// Each chunk is authenticated before its plaintext is returned, but
+/// truncation is only detected at the end: treat the output as complete
+/// only once `read` returns `Ok(0)`. Authentication failures are returned
+/// as `io::ErrorKind::InvalidData` wrapping a `CryptoError`.
+pub struct DecryptingReader<R: Read> {
+    inner: R,
+    chunk_cipher: Aes256Gcm,
+    manifest_cipher: Aes256Gcm,
+    base_nonce: [u8; NONCE_SIZE],
+    aad: Vec<u8>,
+    record_size: usize,
+    pending: Vec<u8>,
+    plaintext: Zeroizing<Vec<u8>>,
+    position: usize,
+    chunk_index: u64,
+    plaintext_len: u64,
+    digest: Sha256,
+    finished: bool,
+    failed: bool,
+}
+
+impl<R: Read> DecryptingReader<R> {
+    /// Read the stream header from `inner`. `aad` must match what was given
+    /// to the writer.
+    pub fn new(mut inner: R, key: &FileKey, aad: Option<&[u8]>) -> Result<Self, CryptoError> {
+        let mut header = [0u8; STREAM_HEADER_SIZE];
+        inner.read_exact(&mut header).map_err(stream_io_err)?;
+        if header[0] != STREAM_VERSION {
+            return Err(CryptoError::FileEncryptionError(format!(
+                "unsupported stream version {}",
+                header[0]
+            )));
+        }
+        let chunk_size = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(CryptoError::FileEncryptionError(format!(
+                "invalid stream chunk size {chunk_size}"
+            )));
+        }
+        let mut base_nonce = [0u8; NONCE_SIZE];
+        base_nonce.copy_from_slice(&header[5..]);
+
+        let aad = aad.unwrap_or_default().to_vec();
+        let (chunk_cipher, manifest_cipher) = stream_ciphers(key)?;
+        Ok(Self {
+            inner,
+            chunk_cipher,
+            manifest_cipher,
+            base_nonce,
+            digest: stream_digest(&header, &aad),
+            aad,
+            record_size: chunk_size + TAG_SIZE,
+            pending: Vec::new(),
+            plaintext: Zeroizing::new(Vec::new()),
+            position: 0,
+            chunk_index: 0,
+            plaintext_len: 0,
+            finished: false,
+            failed: false,
+        })
+    }
+
+    /// Fill `pending` with a full chunk plus the manifest's worth of
+    /// lookahead, so the final chunk can be told apart from the manifest.
+    /// Returns whether the inner reader hit EOF first.
+    fn fill(&mut self) -> io::Result<bool> {
+        let target = self.record_size + TAG_SIZE;
+        while self.pending.len() < target {
+            let start = self.pending.len();
+            self.pending.resize(target, 0);
+            match self.inner.read(&mut self.pending[start..]) {
+                Ok(0) => {
+                    self.pending.truncate(start);
+                    return Ok(true);
+                }
+                Ok(n) => self.pending.truncate(start + n),
+                Err(e) => {
+                    self.pending.truncate(start);
+                    if e.kind() != io::ErrorKind::Interrupted {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    fn next_chunk(&mut self) -> io::Result<()> {
+        let eof = self.fill()?;
+        let available = self.pending.len();
+        if eof && available < TAG_SIZE {
+            return Err(stream_crypto_err(CryptoError::FileEncryptionError(
+                "stream truncated".into(),
+            )));
+        }
+        let record_len = if eof {
+            available - TAG_SIZE
+        } else {
+            self.record_size
+        };
+
+        if record_len == 0 {
+            let aad = manifest_aad(self.digest.clone(), self.chunk_index, self.plaintext_len);
+            self.manifest_cipher
+                .decrypt(
+                    Nonce::from_slice(&self.base_nonce),
+                    Payload {
+                        msg: &self.pending,
+                        aad: &aad,
+                    },
+                )
+                .map_err(|_| {
+                    stream_crypto_err(CryptoError::FileEncryptionError(
+                        "stream manifest does not match".into(),
+                    ))
+                })?;
+            self.finished = true;
+            return Ok(());
+        }
+        if record_len <= TAG_SIZE {
+            return Err(stream_crypto_err(CryptoError::FileEncryptionError(
+                "stream truncated".into(),
+            )));
+        }
+
+        let record = &self.pending[..record_len];
+        let nonce = chunk_nonce(&self.base_nonce, self.chunk_index);
+        let plaintext = self
+            .chunk_cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: record,
+                    aad: &self.aad,
+                },
+            )
+            .map_err(|e| {
+                stream_crypto_err(CryptoError::FileEncryptionError(format!(
+                    "decryption failed: {e}"
+                )))
+            })?;
+
+        self.digest.update(&record[record_len - TAG_SIZE..]);
+        self.pending.drain(..record_len);
+        self.chunk_index += 1;
+        self.plaintext_len += plaintext.len() as u64;
+        self.plaintext = Zeroizing::new(plaintext);
+        self.position = 0;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.plaintext.len() {
+            if self.failed {
+                return Err(stream_crypto_err(CryptoError::FileEncryptionError(
+                    "stream already failed to decrypt".into(),
+                )));
+            }
+            if self.finished {
+                return Ok(0);
+            }
+            if let Err(e) = self.next_chunk() {
+                if e.kind() != io::ErrorKind::Interrupted {
+                    self.failed = true;
+                }
+                return Err(e);
+            }
+        }
+
+        let available = &self.plaintext[self.position..];
+        let n = buf.len().min(available.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+fn stream_header(chunk_size: u32, base_nonce: &[u8; NONCE_SIZE]) -> [u8; STREAM_HEADER_SIZE] {
+    let mut header = [0u8; STREAM_HEADER_SIZE];
+    header[0] = STREAM_VERSION;
+    header[1..5].copy_from_slice(&chunk_size.to_be_bytes());
+    header[5..].copy_from_slice(base_nonce);
+    header
+}
+
+/// Running hash for the manifest, seeded with the header and AAD.
+fn stream_digest(header: &[u8], aad: &[u8]) -> Sha256 {
+    let mut digest = Sha256::new();
+    digest.update(header);
+    digest.update((aad.len() as u64).to_be_bytes());
+    digest.update(aad);
+    digest
+}
+
+/// Derive the chunk and manifest ciphers from the file key.
+fn stream_ciphers(key: &FileKey) -> Result<(Aes256Gcm, Aes256Gcm), CryptoError> {
+    let hk = Hkdf::<Sha256>::new(None, &key.key);
+    let cipher = |info: &[u8]| {
+        let mut subkey = Zeroizing::new([0u8; KEY_SIZE]);
+        hk.expand(info, subkey.as_mut())
+            .map_err(|e| CryptoError::FileEncryptionError(e.to_string()))?;
+        Aes256Gcm::new_from_slice(subkey.as_ref())
+            .map_err(|e| CryptoError::FileEncryptionError(e.to_string()))
+    };
+    Ok((
+        cipher(STREAM_CHUNK_KEY_INFO)?,
+        cipher(STREAM_MANIFEST_KEY_INFO)?,
+    ))
+}
+
+fn chunk_nonce(base_nonce: &[u8; NONCE_SIZE], index: u64) -> [u8; NONCE_SIZE] {
+    let mut nonce = *base_nonce;
+    for (byte, counter) in nonce[NONCE_SIZE - 8..].iter_mut().zip(index.to_be_bytes()) {
+        *byte ^= counter;
+    }
+    nonce
+}
+
+/// What the manifest tag authenticates: the running hash of header, AAD
+/// and chunk tags, then the chunk count and plaintext length.
+fn manifest_aad(digest: Sha256, chunk_count: u64, plaintext_len: u64) -> Vec<u8> {
+    let mut aad = digest.finalize().to_vec();
+    aad.extend_from_slice(&chunk_count.to_be_bytes());
+    aad.extend_from_slice(&plaintext_len.to_be_bytes());
+    aad
+}
+
+fn stream_io_err(e: io::Error) -> CryptoError {
+    CryptoError::FileEncryptionError(format!("stream i/o failed: {e}"))
+}
+
+fn stream_crypto_err(e: CryptoError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -178,10 +640,19 @@ mod tests {
 
     #[test]
     fn filekey_implements_zeroize() {
-        // Verify that FileKey can be constructed and dropped without issues.
-        // The zeroize derive ensures memory is wiped on drop.
-        let key = FileKey { key: [0xFF; 32] };
-        drop(key);
+        // The zeroize derive wipes the key on drop; zeroize() does the same
+        // eagerly.
+        let mut key = FileKey { key: [0xFF; 32] };
+        key.zeroize();
+        assert_eq!(key.key, [0u8; 32]);
+    }
+
+    #[test]
+    fn filekey_from_bytes_decrypts() {
+        let data = b"shared attachment";
+        let (blob, key) = encrypt_file(data, None).unwrap();
+        let received = FileKey::from_bytes(key.key);
+        assert_eq!(decrypt_file(&received, &blob, None).unwrap(), data);
     }
 
     #[test]
@@ -209,4 +680,258 @@ mod tests {
         let result = decrypt_file(&key, &blob, None);
         assert!(matches!(result, Err(CryptoError::FileEncryptionError(_))));
     }
+
+    /// Alice has an outgoing session to Bob. Returns both connections and
+    /// both addresses.
+    fn setup_session() -> (Connection, Connection, ProtocolAddress, ProtocolAddress) {
+        let alice_conn = crate::storage::init_test_db();
+        let bob_conn = crate::storage::init_test_db();
+        crate::identity::generate_identity(&alice_conn).unwrap();
+        crate::identity::generate_identity(&bob_conn).unwrap();
+        let bundle = crate::prekeys::generate_pre_key_bundle(&bob_conn, "bob-user-id").unwrap();
+        let bob = crate::session::create_outgoing_session(
+            &alice_conn,
+            &serde_json::to_vec(&bundle).unwrap(),
+        )
+        .unwrap();
+        let alice = ProtocolAddress::new(
+            "alice-user-id".to_string(),
+            libsignal_protocol::DeviceId::new(1).expect("valid"),
+        );
+        (alice_conn, bob_conn, bob, alice)
+    }
+
+    #[test]
+    fn wrapped_key_unwraps_and_decrypts_shared_blob() {
+        let (alice_conn, bob_conn, bob, alice) = setup_session();
+        let (blob, key) = encrypt_file(b"shared attachment", Some(b"file-id")).unwrap();
+
+        let wrapped = wrap_key_for_recipient(&alice_conn, &bob, &key).unwrap();
+        let unwrapped =
+            unwrap_key_from_sender(&bob_conn, &alice, &wrapped.ciphertext, wrapped.message_type)
+                .unwrap();
+        assert_eq!(
+            decrypt_file(&unwrapped, &blob, Some(b"file-id")).unwrap(),
+            b"shared attachment"
+        );
+    }
+
+    #[test]
+    fn one_key_wraps_for_several_recipients() {
+        let (alice_conn, bob_conn, bob, alice) = setup_session();
+        let carol_conn = crate::storage::init_test_db();
+        crate::identity::generate_identity(&carol_conn).unwrap();
+        let bundle = crate::prekeys::generate_pre_key_bundle(&carol_conn, "carol-user-id").unwrap();
+        let carol = crate::session::create_outgoing_session(
+            &alice_conn,
+            &serde_json::to_vec(&bundle).unwrap(),
+        )
+        .unwrap();
+        let (_blob, key) = encrypt_file(b"data", None).unwrap();
+
+        for (conn, recipient) in [(&bob_conn, &bob), (&carol_conn, &carol)] {
+            let wrapped = wrap_key_for_recipient(&alice_conn, recipient, &key).unwrap();
+            let unwrapped =
+                unwrap_key_from_sender(conn, &alice, &wrapped.ciphertext, wrapped.message_type)
+                    .unwrap();
+            assert_eq!(unwrapped.key, key.key);
+        }
+    }
+
+    #[test]
+    fn wrap_without_session_returns_session_not_found() {
+        let conn = crate::storage::init_test_db();
+        crate::identity::generate_identity(&conn).unwrap();
+        let nobody = ProtocolAddress::new(
+            "nobody".to_string(),
+            libsignal_protocol::DeviceId::new(1).expect("valid"),
+        );
+        let (_blob, key) = encrypt_file(b"data", None).unwrap();
+        let result = wrap_key_for_recipient(&conn, &nobody, &key);
+        assert!(matches!(result, Err(CryptoError::SessionNotFound { .. })));
+    }
+
+    #[test]
+    fn unwrap_rejects_other_message_payloads() {
+        let (alice_conn, bob_conn, bob, alice) = setup_session();
+        let message = encrypt_message(&alice_conn, &bob, b"just a chat message").unwrap();
+        let result =
+            unwrap_key_from_sender(&bob_conn, &alice, &message.ciphertext, message.message_type);
+        assert!(matches!(result, Err(CryptoError::FileEncryptionError(_))));
+    }
+
+    fn stream_encrypt(data: &[u8], aad: Option<&[u8]>, chunk_size: usize) -> (Vec<u8>, FileKey) {
+        let (mut writer, key) =
+            EncryptingWriter::with_chunk_size(Vec::new(), aad, chunk_size).unwrap();
+        writer.write_all(data).unwrap();
+        (writer.finish().unwrap(), key)
+    }
+
+    fn stream_decrypt(stream: &[u8], key: &FileKey, aad: Option<&[u8]>) -> io::Result<Vec<u8>> {
+        let mut reader = DecryptingReader::new(stream, key, aad)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn stream_roundtrip_around_chunk_boundaries() {
+        for len in [0, 1, 63, 64, 65, 128, 200] {
+            let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let (stream, key) = stream_encrypt(&data, None, 64);
+            let chunks = len.div_ceil(64);
+            assert_eq!(
+                stream.len(),
+                STREAM_HEADER_SIZE + len + chunks * TAG_SIZE + TAG_SIZE,
+                "len {len}"
+            );
+            assert_eq!(
+                stream_decrypt(&stream, &key, None).unwrap(),
+                data,
+                "len {len}"
+            );
+        }
+    }
+
+    #[test]
+    fn stream_roundtrip_with_default_chunks_and_io_copy() {
+        let data: Vec<u8> = (0..1_000_000u32).map(|i| (i % 251) as u8).collect();
+        let (mut writer, key) = EncryptingWriter::new(Vec::new(), Some(b"file-id")).unwrap();
+        io::copy(&mut data.as_slice(), &mut writer).unwrap();
+        let stream = writer.finish().unwrap();
+
+        let mut reader = DecryptingReader::new(stream.as_slice(), &key, Some(b"file-id")).unwrap();
+        let mut out = Vec::new();
+        io::copy(&mut reader, &mut out).unwrap();
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn stream_reader_handles_short_reads() {
+        /// Hands out one byte per read call.
+        struct Trickle<'a>(&'a [u8]);
+        impl Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.0.is_empty() || buf.is_empty() {
+                    return Ok(0);
+                }
+                buf[0] = self.0[0];
+                self.0 = &self.0[1..];
+                Ok(1)
+            }
+        }
+
+        let data = vec![0x5Au8; 300];
+        let (stream, key) = stream_encrypt(&data, None, 64);
+        let mut reader = DecryptingReader::new(Trickle(&stream), &key, None).unwrap();
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn stream_wrong_key_or_aad_fails() {
+        let (stream, key) = stream_encrypt(b"attachment", Some(b"aad"), 64);
+        let wrong_key = FileKey { key: [0xAB; 32] };
+        assert!(stream_decrypt(&stream, &wrong_key, Some(b"aad")).is_err());
+        assert!(stream_decrypt(&stream, &key, Some(b"other")).is_err());
+        assert!(stream_decrypt(&stream, &key, None).is_err());
+    }
+
+    #[test]
+    fn stream_empty_file_aad_is_still_checked() {
+        let (stream, key) = stream_encrypt(b"", Some(b"aad"), 64);
+        assert!(stream_decrypt(&stream, &key, Some(b"aad"))
+            .unwrap()
+            .is_empty());
+        assert!(stream_decrypt(&stream, &key, None).is_err());
+    }
+
+    #[test]
+    fn stream_truncation_is_detected() {
+        let data = vec![7u8; 200];
+        let (stream, key) = stream_encrypt(&data, None, 64);
+        let record = 64 + TAG_SIZE;
+
+        // Manifest missing
+        assert!(stream_decrypt(&stream[..stream.len() - TAG_SIZE], &key, None).is_err());
+        // Cut at a chunk boundary
+        assert!(stream_decrypt(&stream[..STREAM_HEADER_SIZE + 2 * record], &key, None).is_err());
+        // Last chunk dropped, manifest kept
+        let mut dropped = stream[..STREAM_HEADER_SIZE + 2 * record].to_vec();
+        dropped.extend_from_slice(&stream[stream.len() - TAG_SIZE..]);
+        assert!(stream_decrypt(&dropped, &key, None).is_err());
+        // Header only
+        assert!(stream_decrypt(&stream[..STREAM_HEADER_SIZE], &key, None).is_err());
+    }
+
+    #[test]
+    fn stream_reordered_tampered_or_extended_fails() {
+        let data: Vec<u8> = (0..200).map(|i| i as u8).collect();
+        let (stream, key) = stream_encrypt(&data, None, 64);
+        let record = 64 + TAG_SIZE;
+
+        let mut swapped = stream.clone();
+        let first = STREAM_HEADER_SIZE..STREAM_HEADER_SIZE + record;
+        let second = STREAM_HEADER_SIZE + record..STREAM_HEADER_SIZE + 2 * record;
+        let chunk = stream[first.clone()].to_vec();
+        swapped.copy_within(second, first.start);
+        swapped[first.start + record..first.start + 2 * record].copy_from_slice(&chunk);
+        assert!(stream_decrypt(&swapped, &key, None).is_err());
+
+        for index in [
+            0,
+            1,
+            STREAM_HEADER_SIZE - 1,
+            STREAM_HEADER_SIZE,
+            stream.len() - 1,
+        ] {
+            let mut tampered = stream.clone();
+            tampered[index] ^= 0x01;
+            assert!(
+                stream_decrypt(&tampered, &key, None).is_err(),
+                "byte {index}"
+            );
+        }
+
+        let mut extended = stream.clone();
+        extended.extend_from_slice(&[0u8; 40]);
+        assert!(stream_decrypt(&extended, &key, None).is_err());
+    }
+
+    #[test]
+    fn stream_reader_stays_failed_after_error() {
+        let (mut stream, key) = stream_encrypt(&[1u8; 100], None, 64);
+        let last = stream.len() - 1;
+        stream[last] ^= 0xFF;
+
+        let mut reader = DecryptingReader::new(stream.as_slice(), &key, None).unwrap();
+        let mut out = Vec::new();
+        let err = reader.read_to_end(&mut out).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(reader.read(&mut [0u8; 8]).is_err());
+    }
+
+    #[test]
+    fn stream_writer_dropped_without_finish_does_not_decrypt() {
+        let mut stream = Vec::new();
+        let (mut writer, key) = EncryptingWriter::with_chunk_size(&mut stream, None, 64).unwrap();
+        writer.write_all(&[3u8; 150]).unwrap();
+        drop(writer);
+        assert!(stream_decrypt(&stream, &key, None).is_err());
+    }
+
+    #[test]
+    fn stream_chunk_size_is_validated() {
+        for size in [0, MAX_CHUNK_SIZE + 1] {
+            let result = EncryptingWriter::with_chunk_size(Vec::new(), None, size);
+            assert!(matches!(result, Err(CryptoError::FileEncryptionError(_))));
+        }
+
+        let (mut stream, key) = stream_encrypt(b"data", None, 64);
+        stream[1..5].copy_from_slice(&u32::MAX.to_be_bytes());
+        let result = DecryptingReader::new(stream.as_slice(), &key, None);
+        assert!(matches!(result, Err(CryptoError::FileEncryptionError(_))));
+    }
 }
@@ -1,10 +1,14 @@
 //! Safety number (fingerprint) generation for out-of-band identity verification.
 //!
-//! Users compare numeric fingerprints visually or scan QR codes to verify they
-//! are communicating with the intended party and not a man-in-the-middle.
+//! Users compare numeric fingerprints visually, read out the word form, or
+//! scan QR codes to verify they are communicating with the intended party and
+//! not a man-in-the-middle. With the `qrcode` feature, the compact scannable
+//! form can be rendered to a PNG directly.
 
 use crate::error::CryptoError;
 use libsignal_protocol::{Fingerprint as LibsignalFingerprint, IdentityKey, ScannableFingerprint};
+use openconv_shared::wordcode;
+use sha2::{Digest, Sha256};
 
 /// Fingerprint version used by Signal protocol.
 const FINGERPRINT_VERSION: u32 = 2;
@@ -12,6 +16,18 @@ const FINGERPRINT_VERSION: u32 = 2;
 /// Number of hash iterations for fingerprint generation (Signal standard).
 const FINGERPRINT_ITERATIONS: u32 = 5200;
 
+/// Number of words in the spoken form of a safety number (96 bits).
+pub const SAFETY_WORDS_LEN: usize = 12;
+
+/// Leading byte of the compact scannable encoding.
+const COMPACT_VERSION: u8 = 1;
+
+/// Per-party digest length in the compact scannable encoding.
+const COMPACT_DIGEST_LEN: usize = 32;
+
+/// Length of the compact scannable encoding: version, local digest, remote digest.
+pub const COMPACT_SCANNABLE_LEN: usize = 1 + 2 * COMPACT_DIGEST_LEN;
+
 /// A safety number fingerprint containing both human-readable and machine-scannable
 /// representations for out-of-band identity verification.
 #[derive(Debug, Clone)]
@@ -20,9 +36,52 @@ pub struct Fingerprint {
     /// separated by spaces (e.g., "12345 67890 12345 67890 ...").
     pub display: String,
 
+    /// The same safety number as [`SAFETY_WORDS_LEN`] dash-separated words,
+    /// for confirming over a call where reading 60 digits is error-prone.
+    /// Derived only from `display`, so it is symmetric as well.
+    pub words: String,
+
     /// Protobuf-serialized scannable fingerprint bytes, suitable for encoding
     /// as a QR code. The other party scans this and calls `compare_fingerprints`.
     pub scannable: Vec<u8>,
+
+    /// Compact scannable encoding ([`COMPACT_SCANNABLE_LEN`] bytes): a
+    /// version byte, then a digest of our identity and one of theirs. Fits
+    /// a small QR code; the other party scans it and calls `verify_scanned`.
+    pub compact: Vec<u8>,
+}
+
+impl Fingerprint {
+    /// Check a compact payload scanned from the other party's screen.
+    ///
+    /// Returns `true` if it holds the same two identities with the sides
+    /// swapped, `false` if either differs (including scanning our own
+    /// code), and `CryptoError::FingerprintError` if the bytes are not a
+    /// compact fingerprint at all.
+    pub fn verify_scanned(&self, scanned: &[u8]) -> Result<bool, CryptoError> {
+        if scanned.len() != COMPACT_SCANNABLE_LEN || scanned[0] != COMPACT_VERSION {
+            return Err(CryptoError::FingerprintError(
+                "scanned data is not a compact fingerprint".into(),
+            ));
+        }
+        let (ours_local, ours_remote) = self.compact[1..].split_at(COMPACT_DIGEST_LEN);
+        let (theirs_local, theirs_remote) = scanned[1..].split_at(COMPACT_DIGEST_LEN);
+        Ok(theirs_local == ours_remote && theirs_remote == ours_local)
+    }
+
+    /// Render `compact` as a QR code PNG.
+    #[cfg(feature = "qrcode")]
+    pub fn qr_png(&self) -> Result<Vec<u8>, CryptoError> {
+        let code = qrcode::QrCode::new(&self.compact)
+            .map_err(|e| CryptoError::FingerprintError(e.to_string()))?;
+        let image = code.render::<image::Luma<u8>>().build();
+
+        let mut png = Vec::new();
+        image
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .map_err(|e| CryptoError::FingerprintError(e.to_string()))?;
+        Ok(png)
+    }
 }
 
 /// Generate a safety number fingerprint for verifying identity between two parties.
@@ -69,12 +128,40 @@ pub fn generate_fingerprint(
         .serialize()
         .map_err(|e| CryptoError::FingerprintError(e.to_string()))?;
 
+    let mut compact = Vec::with_capacity(COMPACT_SCANNABLE_LEN);
+    compact.push(COMPACT_VERSION);
+    compact.extend_from_slice(&compact_digest(local_address, &local_key.serialize()));
+    compact.extend_from_slice(&compact_digest(remote_address, &remote_key.serialize()));
+
     Ok(Fingerprint {
+        words: safety_words(&raw),
         display: formatted,
         scannable,
+        compact,
     })
 }
 
+/// One party's half of the compact encoding. Unlike the displayed digits,
+/// this is the full SHA-256 output, so no key stretching is needed.
+fn compact_digest(address: &str, identity: &[u8]) -> [u8; COMPACT_DIGEST_LEN] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"openconv-scannable-fingerprint:");
+    hasher.update((address.len() as u32).to_be_bytes());
+    hasher.update(address.as_bytes());
+    hasher.update(identity);
+    hasher.finalize().into()
+}
+
+/// Word form of a safety number: a truncated hash of its digits, encoded
+/// with the shared wordlist.
+fn safety_words(digits: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"openconv-safety-words:");
+    hasher.update(digits.as_bytes());
+    let hash = hasher.finalize();
+    wordcode::encode(&hash[..SAFETY_WORDS_LEN])
+}
+
 /// Compare a locally-generated fingerprint against scanned QR code data from
 /// the other party.
 ///
@@ -208,6 +295,25 @@ mod tests {
         let fp_bob = generate_fingerprint(&bob_bytes, "bob", &alice_bytes, "alice").unwrap();
 
         assert_eq!(fp_alice.display, fp_bob.display);
+        assert_eq!(fp_alice.words, fp_bob.words);
+    }
+
+    #[test]
+    fn safety_words_decode_to_fixed_length() {
+        let alice = generate_test_identity();
+        let bob = generate_test_identity();
+
+        let fp = generate_fingerprint(
+            &alice.identity_key().serialize(),
+            "alice",
+            &bob.identity_key().serialize(),
+            "bob",
+        )
+        .unwrap();
+
+        let bytes = wordcode::decode(&fp.words).unwrap();
+        assert_eq!(bytes.len(), SAFETY_WORDS_LEN);
+        assert_ne!(safety_words("1".repeat(60).as_str()), fp.words);
     }
 
     #[test]
@@ -242,4 +348,84 @@ mod tests {
         let result = compare_fingerprints(&fp_alice_bob, &fp_alice_charlie.scannable).unwrap();
         assert!(!result);
     }
+
+    #[test]
+    fn compact_encoding_has_fixed_length() {
+        let alice = generate_test_identity();
+        let bob = generate_test_identity();
+        let fp = generate_fingerprint(
+            &alice.identity_key().serialize(),
+            "alice",
+            &bob.identity_key().serialize(),
+            "bob",
+        )
+        .unwrap();
+
+        assert_eq!(fp.compact.len(), COMPACT_SCANNABLE_LEN);
+        assert_eq!(fp.compact[0], COMPACT_VERSION);
+        assert!(fp.compact.len() < fp.scannable.len());
+    }
+
+    #[test]
+    fn verify_scanned_accepts_the_other_partys_code() {
+        let alice_bytes = generate_test_identity().identity_key().serialize();
+        let bob_bytes = generate_test_identity().identity_key().serialize();
+
+        let fp_alice = generate_fingerprint(&alice_bytes, "alice", &bob_bytes, "bob").unwrap();
+        let fp_bob = generate_fingerprint(&bob_bytes, "bob", &alice_bytes, "alice").unwrap();
+
+        assert!(fp_alice.verify_scanned(&fp_bob.compact).unwrap());
+        assert!(fp_bob.verify_scanned(&fp_alice.compact).unwrap());
+        // Scanning your own screen proves nothing
+        assert!(!fp_alice.verify_scanned(&fp_alice.compact).unwrap());
+    }
+
+    #[test]
+    fn verify_scanned_rejects_a_different_identity() {
+        let alice_bytes = generate_test_identity().identity_key().serialize();
+        let bob_bytes = generate_test_identity().identity_key().serialize();
+        let mallory_bytes = generate_test_identity().identity_key().serialize();
+
+        let fp_alice = generate_fingerprint(&alice_bytes, "alice", &bob_bytes, "bob").unwrap();
+        let fp_mallory =
+            generate_fingerprint(&mallory_bytes, "bob", &alice_bytes, "alice").unwrap();
+        assert!(!fp_alice.verify_scanned(&fp_mallory.compact).unwrap());
+
+        // Same keys, different account
+        let fp_other = generate_fingerprint(&bob_bytes, "carol", &alice_bytes, "alice").unwrap();
+        assert!(!fp_alice.verify_scanned(&fp_other.compact).unwrap());
+    }
+
+    #[test]
+    fn verify_scanned_rejects_malformed_data() {
+        let alice_bytes = generate_test_identity().identity_key().serialize();
+        let bob_bytes = generate_test_identity().identity_key().serialize();
+        let fp_alice = generate_fingerprint(&alice_bytes, "alice", &bob_bytes, "bob").unwrap();
+        let fp_bob = generate_fingerprint(&bob_bytes, "bob", &alice_bytes, "alice").unwrap();
+
+        let mut wrong_version = fp_bob.compact.clone();
+        wrong_version[0] = COMPACT_VERSION + 1;
+        for data in [
+            &b""[..],
+            &fp_bob.compact[1..],
+            wrong_version.as_slice(),
+            fp_bob.scannable.as_slice(),
+        ] {
+            assert!(matches!(
+                fp_alice.verify_scanned(data),
+                Err(CryptoError::FingerprintError(_))
+            ));
+        }
+    }
+
+    #[cfg(feature = "qrcode")]
+    #[test]
+    fn qr_png_renders_a_png() {
+        let alice_bytes = generate_test_identity().identity_key().serialize();
+        let bob_bytes = generate_test_identity().identity_key().serialize();
+        let fp = generate_fingerprint(&alice_bytes, "alice", &bob_bytes, "bob").unwrap();
+
+        let png = fp.qr_png().unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    }
 }
@@ -0,0 +1,275 @@
+//! Group messaging with Sender Keys, for end-to-end encrypted guild channels.
+//!
+//! Each member keeps a sender key chain per group, named by a distribution
+//! ID (e.g. the channel ID). `create_distribution_message` returns the
+//! chain's current state for the other members; it must be sent to each of
+//! them over their pairwise session (`message::encrypt_message`), never in
+//! the clear. Once a member has run `process_distribution_message`, every
+//! message the sender encrypts with `encrypt` is a single ciphertext that
+//! all members can `decrypt`.
+//!
+//! Sender keys do not heal after compromise the way the Double Ratchet
+//! does. When a member leaves, the remaining members should switch to a new
+//! distribution ID and distribute fresh chains.
+
+use libsignal_protocol::{
+    ProtocolAddress, SenderKeyDistributionMessage, SenderKeyStore, SignalProtocolError,
+};
+use rusqlite::Connection;
+use uuid::Uuid;
+
+use crate::error::CryptoError;
+use crate::storage::{write_transaction, CryptoStore};
+
+/// Create (or continue) our sender key chain for `distribution_id` and
+/// return a serialized distribution message for the other members.
+///
+/// `sender` is our own address. Calling this again for the same
+/// distribution ID returns the existing chain at its current iteration.
+pub fn create_distribution_message(
+    conn: &Connection,
+    sender: &ProtocolAddress,
+    distribution_id: Uuid,
+) -> Result<Vec<u8>, CryptoError> {
+    let tx = write_transaction(conn)?;
+    let mut store = CryptoStore::new(conn);
+
+    let message =
+        futures::executor::block_on(libsignal_protocol::create_sender_key_distribution_message(
+            sender,
+            distribution_id,
+            &mut store,
+            &mut rand::rng(),
+        ))?;
+
+    tx.commit()?;
+    Ok(message.serialized().to_vec())
+}
+
+/// Store the sender key chain distributed by `sender`, so their group
+/// messages can be decrypted. Returns the distribution ID it belongs to.
+///
+/// `sender` must be the address the distribution message was received from
+/// over a pairwise session; the message itself does not authenticate it.
+pub fn process_distribution_message(
+    conn: &Connection,
+    sender: &ProtocolAddress,
+    distribution_message: &[u8],
+) -> Result<Uuid, CryptoError> {
+    let message = SenderKeyDistributionMessage::try_from(distribution_message)
+        .map_err(|e| CryptoError::DecryptionFailed(e.to_string()))?;
+    let distribution_id = message.distribution_id()?;
+
+    let tx = write_transaction(conn)?;
+    let mut store = CryptoStore::new(conn);
+    futures::executor::block_on(libsignal_protocol::process_sender_key_distribution_message(
+        sender, &message, &mut store,
+    ))?;
+
+    tx.commit()?;
+    Ok(distribution_id)
+}
+
+/// Encrypt `plaintext` to every member holding our chain for
+/// `distribution_id`.
+///
+/// Returns `CryptoError::SessionNotFound` if `create_distribution_message`
+/// has not been called for this group yet. The chain advance and ciphertext
+/// creation are atomic.
+pub fn encrypt(
+    conn: &Connection,
+    sender: &ProtocolAddress,
+    distribution_id: Uuid,
+    plaintext: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    let tx = write_transaction(conn)?;
+    let mut store = CryptoStore::new(conn);
+
+    let existing = futures::executor::block_on(store.load_sender_key(sender, distribution_id))?;
+    if existing.is_none() {
+        return Err(CryptoError::SessionNotFound {
+            address: sender.name().to_string(),
+        });
+    }
+
+    let message = futures::executor::block_on(libsignal_protocol::group_encrypt(
+        &mut store,
+        sender,
+        distribution_id,
+        plaintext,
+        &mut rand::rng(),
+    ))
+    .map_err(|e| group_error(sender, e))?;
+
+    tx.commit()?;
+    Ok(message.serialized().to_vec())
+}
+
+/// Decrypt a group message from `sender`.
+///
+/// Returns `CryptoError::SessionNotFound` when no chain from `sender` has
+/// been processed for the message's distribution ID, so the caller can ask
+/// the sender to distribute it again. The chain advance is atomic.
+pub fn decrypt(
+    conn: &Connection,
+    sender: &ProtocolAddress,
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    let tx = write_transaction(conn)?;
+    let mut store = CryptoStore::new(conn);
+
+    let plaintext = futures::executor::block_on(libsignal_protocol::group_decrypt(
+        ciphertext, &mut store, sender,
+    ))
+    .map_err(|e| group_error(sender, e))?;
+
+    tx.commit()?;
+    Ok(plaintext)
+}
+
+/// Map libsignal's group cipher errors. A missing chain is reported like a
+/// missing pairwise session; everything else means the message cannot be
+/// decrypted and there is no session to recover.
+fn group_error(sender: &ProtocolAddress, err: SignalProtocolError) -> CryptoError {
+    match err {
+        SignalProtocolError::NoSenderKeyState { .. } => CryptoError::SessionNotFound {
+            address: sender.name().to_string(),
+        },
+        other => CryptoError::DecryptionFailed(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::init_test_db;
+    use libsignal_protocol::DeviceId;
+
+    fn address(name: &str) -> ProtocolAddress {
+        ProtocolAddress::new(name.to_string(), DeviceId::new(1).expect("valid"))
+    }
+
+    /// Alice creates a chain for a new group and Bob processes it.
+    fn setup_group() -> (Connection, Connection, ProtocolAddress, Uuid) {
+        let alice_conn = init_test_db();
+        let bob_conn = init_test_db();
+        let alice = address("alice-user-id");
+        let distribution_id = Uuid::new_v4();
+
+        let distribution =
+            create_distribution_message(&alice_conn, &alice, distribution_id).unwrap();
+        let processed = process_distribution_message(&bob_conn, &alice, &distribution).unwrap();
+        assert_eq!(processed, distribution_id);
+
+        (alice_conn, bob_conn, alice, distribution_id)
+    }
+
+    #[test]
+    fn encrypt_then_decrypt_round_trips_for_each_member() {
+        let (alice_conn, bob_conn, alice, distribution_id) = setup_group();
+        let carol_conn = init_test_db();
+        let distribution =
+            create_distribution_message(&alice_conn, &alice, distribution_id).unwrap();
+        process_distribution_message(&carol_conn, &alice, &distribution).unwrap();
+
+        let ciphertext = encrypt(&alice_conn, &alice, distribution_id, b"hello group").unwrap();
+        assert_eq!(
+            decrypt(&bob_conn, &alice, &ciphertext).unwrap(),
+            b"hello group"
+        );
+        assert_eq!(
+            decrypt(&carol_conn, &alice, &ciphertext).unwrap(),
+            b"hello group"
+        );
+    }
+
+    #[test]
+    fn messages_decrypt_in_sequence_and_out_of_order() {
+        let (alice_conn, bob_conn, alice, distribution_id) = setup_group();
+
+        let first = encrypt(&alice_conn, &alice, distribution_id, b"one").unwrap();
+        let second = encrypt(&alice_conn, &alice, distribution_id, b"two").unwrap();
+        let third = encrypt(&alice_conn, &alice, distribution_id, b"three").unwrap();
+        assert_ne!(first, second);
+
+        assert_eq!(decrypt(&bob_conn, &alice, &first).unwrap(), b"one");
+        assert_eq!(decrypt(&bob_conn, &alice, &third).unwrap(), b"three");
+        assert_eq!(decrypt(&bob_conn, &alice, &second).unwrap(), b"two");
+    }
+
+    #[test]
+    fn replayed_message_is_rejected() {
+        let (alice_conn, bob_conn, alice, distribution_id) = setup_group();
+
+        let ciphertext = encrypt(&alice_conn, &alice, distribution_id, b"once").unwrap();
+        decrypt(&bob_conn, &alice, &ciphertext).unwrap();
+        let result = decrypt(&bob_conn, &alice, &ciphertext);
+        assert!(matches!(result, Err(CryptoError::DecryptionFailed(_))));
+    }
+
+    #[test]
+    fn encrypt_without_distribution_returns_session_not_found() {
+        let conn = init_test_db();
+        let result = encrypt(&conn, &address("alice-user-id"), Uuid::new_v4(), b"hello");
+        assert!(matches!(result, Err(CryptoError::SessionNotFound { .. })));
+    }
+
+    #[test]
+    fn decrypt_without_distribution_returns_session_not_found() {
+        let alice_conn = init_test_db();
+        let bob_conn = init_test_db();
+        let alice = address("alice-user-id");
+        let distribution_id = Uuid::new_v4();
+        create_distribution_message(&alice_conn, &alice, distribution_id).unwrap();
+
+        let ciphertext = encrypt(&alice_conn, &alice, distribution_id, b"hello").unwrap();
+        let result = decrypt(&bob_conn, &alice, &ciphertext);
+        assert!(matches!(result, Err(CryptoError::SessionNotFound { .. })));
+    }
+
+    #[test]
+    fn message_from_another_sender_address_fails() {
+        let (alice_conn, bob_conn, alice, distribution_id) = setup_group();
+
+        let ciphertext = encrypt(&alice_conn, &alice, distribution_id, b"hello").unwrap();
+        let result = decrypt(&bob_conn, &address("mallory-user-id"), &ciphertext);
+        assert!(matches!(result, Err(CryptoError::SessionNotFound { .. })));
+    }
+
+    #[test]
+    fn tampered_ciphertext_fails_and_leaves_chain_usable() {
+        let (alice_conn, bob_conn, alice, distribution_id) = setup_group();
+
+        let mut tampered = encrypt(&alice_conn, &alice, distribution_id, b"hello").unwrap();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 0xFF;
+        assert!(matches!(
+            decrypt(&bob_conn, &alice, &tampered),
+            Err(CryptoError::DecryptionFailed(_))
+        ));
+
+        let ciphertext = encrypt(&alice_conn, &alice, distribution_id, b"again").unwrap();
+        assert_eq!(decrypt(&bob_conn, &alice, &ciphertext).unwrap(), b"again");
+    }
+
+    #[test]
+    fn malformed_distribution_message_is_rejected() {
+        let conn = init_test_db();
+        let result = process_distribution_message(&conn, &address("alice-user-id"), b"garbage");
+        assert!(matches!(result, Err(CryptoError::DecryptionFailed(_))));
+    }
+
+    #[test]
+    fn chains_persist_in_storage() {
+        let (_alice_conn, bob_conn, alice, distribution_id) = setup_group();
+
+        let count: u32 = bob_conn
+            .query_row(
+                "SELECT COUNT(*) FROM crypto_sender_keys WHERE address = ?1 AND distribution_id = ?2",
+                rusqlite::params![alice.name(), distribution_id.to_string()],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+}
@@ -2,12 +2,14 @@
 //!
 //! Generates Curve25519 identity keypairs via libsignal, persists them in
 //! encrypted SQLite, and provides public key export and challenge signing
-//! for the registration/login flows.
+//! for the registration/login flows. `rotate` replaces the keypair with a
+//! signed transition statement, archiving the old one.
 
 use base64::Engine;
-use libsignal_protocol::IdentityKeyPair;
+use libsignal_protocol::{IdentityKey, IdentityKeyPair};
 use rand::Rng;
 use rusqlite::Connection;
+use zeroize::Zeroizing;
 
 use crate::error::CryptoError;
 use crate::storage::{with_transaction, CryptoStore};
@@ -33,7 +35,7 @@ pub fn generate_identity(conn: &Connection) -> Result<IdentityKeyPair, CryptoErr
 
         store.store_identity_keypair(
             keypair.public_key().serialize().as_ref(),
-            &keypair.private_key().serialize(),
+            &Zeroizing::new(keypair.private_key().serialize()),
         )?;
 
         store.store_config("registration_id", &reg_id.to_be_bytes())?;
@@ -81,6 +83,99 @@ pub fn sign_challenge(conn: &Connection, challenge: &[u8]) -> Result<Vec<u8>, Cr
     Ok(Vec::from(signature.as_ref()))
 }
 
+/// A statement that `old_identity_key` has been replaced by
+/// `new_identity_key`, produced by `rotate` for upload to the server and
+/// checked by contacts with `verify_transition`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct IdentityTransition {
+    /// The replaced identity public key, serialized via libsignal
+    pub old_identity_key: Vec<u8>,
+    /// The new identity public key, serialized via libsignal
+    pub new_identity_key: Vec<u8>,
+    /// Signature over the new key, created with the old identity private key
+    pub old_key_signature: Vec<u8>,
+    /// Signature over the old key, created with the new identity private key,
+    /// proving the new key is held by the same device
+    pub new_key_signature: Vec<u8>,
+}
+
+/// Replace the local identity keypair with a freshly generated one.
+///
+/// The old keypair is archived (see `archived_identities`) and the
+/// registration ID is kept. Existing sessions carry their own copy of the
+/// identity keys they were established with, so they keep working; new
+/// sessions need a fresh pre-key bundle, so the caller should upload the
+/// returned statement together with a new bundle from
+/// `prekeys::generate_pre_key_bundle`.
+///
+/// Returns `CryptoError::IdentityNotInitialized` if there is no identity to
+/// rotate.
+pub fn rotate(conn: &Connection) -> Result<IdentityTransition, CryptoError> {
+    let new_keypair = IdentityKeyPair::generate(&mut rand::rng());
+
+    with_transaction(conn, |store| {
+        let old_keypair = get_identity(conn)?;
+        let old_key_signature = old_keypair
+            .sign_alternate_identity(new_keypair.identity_key(), &mut rand::rng())
+            .map_err(|e| CryptoError::SignalProtocolError(e.to_string()))?;
+        let new_key_signature = new_keypair
+            .sign_alternate_identity(old_keypair.identity_key(), &mut rand::rng())
+            .map_err(|e| CryptoError::SignalProtocolError(e.to_string()))?;
+
+        store.archive_identity_keypair()?;
+        store.store_identity_keypair(
+            new_keypair.public_key().serialize().as_ref(),
+            &Zeroizing::new(new_keypair.private_key().serialize()),
+        )?;
+
+        Ok(IdentityTransition {
+            old_identity_key: old_keypair.identity_key().serialize().to_vec(),
+            new_identity_key: new_keypair.identity_key().serialize().to_vec(),
+            old_key_signature: old_key_signature.to_vec(),
+            new_key_signature: new_key_signature.to_vec(),
+        })
+    })
+}
+
+/// Check both signatures on a transition statement.
+///
+/// Returns `CryptoError::InvalidKey` if either key is malformed or either
+/// signature does not verify. The caller must separately check that
+/// `old_identity_key` is the key it currently trusts for the sender.
+pub fn verify_transition(transition: &IdentityTransition) -> Result<(), CryptoError> {
+    let old_key = IdentityKey::decode(&transition.old_identity_key)
+        .map_err(|e| CryptoError::InvalidKey(format!("old identity key: {e}")))?;
+    let new_key = IdentityKey::decode(&transition.new_identity_key)
+        .map_err(|e| CryptoError::InvalidKey(format!("new identity key: {e}")))?;
+
+    let verified = old_key
+        .verify_alternate_identity(&new_key, &transition.old_key_signature)
+        .unwrap_or(false)
+        && new_key
+            .verify_alternate_identity(&old_key, &transition.new_key_signature)
+            .unwrap_or(false);
+    if !verified {
+        return Err(CryptoError::InvalidKey(
+            "identity transition signature is invalid".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Identity keypairs replaced by `rotate`, most recent first.
+pub fn archived_identities(conn: &Connection) -> Result<Vec<IdentityKeyPair>, CryptoError> {
+    CryptoStore::new(conn)
+        .get_archived_identity_keypairs()?
+        .into_iter()
+        .map(|(pub_bytes, priv_bytes)| {
+            let public = IdentityKey::decode(&pub_bytes)?;
+            let private = libsignal_protocol::PrivateKey::deserialize(&priv_bytes)
+                .map_err(|e| CryptoError::SignalProtocolError(e.to_string()))?;
+            Ok(IdentityKeyPair::new(public, private))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -194,4 +289,148 @@ mod tests {
         let sig2 = sign_challenge(&conn, b"challenge two").unwrap();
         assert_ne!(sig1, sig2);
     }
+
+    #[test]
+    fn rotate_replaces_identity_and_archives_old_key() {
+        let conn = init_test_db();
+        let old = generate_identity(&conn).unwrap();
+        let store = CryptoStore::new(&conn);
+        let reg_id = store.get_config("registration_id").unwrap();
+
+        let transition = rotate(&conn).unwrap();
+        let new = get_identity(&conn).unwrap();
+        assert_ne!(new.public_key(), old.public_key());
+        assert_eq!(
+            transition.old_identity_key,
+            old.identity_key().serialize().to_vec()
+        );
+        assert_eq!(
+            transition.new_identity_key,
+            new.identity_key().serialize().to_vec()
+        );
+        assert_eq!(store.get_config("registration_id").unwrap(), reg_id);
+
+        let archived = archived_identities(&conn).unwrap();
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].public_key(), old.public_key());
+        assert_eq!(
+            archived[0].private_key().serialize(),
+            old.private_key().serialize()
+        );
+    }
+
+    #[test]
+    fn rotate_twice_archives_both_keys_newest_first() {
+        let conn = init_test_db();
+        generate_identity(&conn).unwrap();
+        let first = rotate(&conn).unwrap();
+        let second = rotate(&conn).unwrap();
+
+        assert_eq!(second.old_identity_key, first.new_identity_key);
+        let archived: Vec<Vec<u8>> = archived_identities(&conn)
+            .unwrap()
+            .iter()
+            .map(|pair| pair.identity_key().serialize().to_vec())
+            .collect();
+        assert_eq!(
+            archived,
+            vec![second.old_identity_key, first.old_identity_key]
+        );
+    }
+
+    #[test]
+    fn rotate_without_identity_returns_not_initialized() {
+        let conn = init_test_db();
+        assert!(matches!(
+            rotate(&conn),
+            Err(CryptoError::IdentityNotInitialized)
+        ));
+    }
+
+    #[test]
+    fn transition_statement_verifies() {
+        let conn = init_test_db();
+        generate_identity(&conn).unwrap();
+        let transition = rotate(&conn).unwrap();
+        verify_transition(&transition).unwrap();
+
+        let json = serde_json::to_vec(&transition).unwrap();
+        let parsed: IdentityTransition = serde_json::from_slice(&json).unwrap();
+        verify_transition(&parsed).unwrap();
+    }
+
+    #[test]
+    fn forged_or_altered_transition_is_rejected() {
+        let conn = init_test_db();
+        generate_identity(&conn).unwrap();
+        let transition = rotate(&conn).unwrap();
+
+        // Someone else's key substituted as the new one
+        let mut substituted = transition.clone();
+        substituted.new_identity_key = IdentityKeyPair::generate(&mut rand::rng())
+            .identity_key()
+            .serialize()
+            .to_vec();
+        assert!(matches!(
+            verify_transition(&substituted),
+            Err(CryptoError::InvalidKey(_))
+        ));
+
+        let mut swapped = transition.clone();
+        std::mem::swap(&mut swapped.old_identity_key, &mut swapped.new_identity_key);
+        assert!(verify_transition(&swapped).is_err());
+
+        let mut tampered = transition.clone();
+        tampered.old_key_signature[0] ^= 0x01;
+        assert!(verify_transition(&tampered).is_err());
+
+        let mut malformed = transition;
+        malformed.old_identity_key = vec![1, 2, 3];
+        assert!(matches!(
+            verify_transition(&malformed),
+            Err(CryptoError::InvalidKey(_))
+        ));
+    }
+
+    #[test]
+    fn existing_sessions_survive_rotation_and_new_bundles_use_new_key() {
+        use crate::message::{decrypt_message, encrypt_message};
+        use crate::prekeys::generate_pre_key_bundle;
+        use crate::session::create_outgoing_session;
+        use libsignal_protocol::{DeviceId, ProtocolAddress};
+
+        let bob_conn = init_test_db();
+        generate_identity(&bob_conn).unwrap();
+        let alice_conn = init_test_db();
+        generate_identity(&alice_conn).unwrap();
+        let alice = ProtocolAddress::new("alice-user-id".into(), DeviceId::new(1).unwrap());
+
+        let bundle = generate_pre_key_bundle(&bob_conn, "bob-user-id").unwrap();
+        let bob =
+            create_outgoing_session(&alice_conn, &serde_json::to_vec(&bundle).unwrap()).unwrap();
+        let first = encrypt_message(&alice_conn, &bob, b"before").unwrap();
+        decrypt_message(&bob_conn, &alice, &first.ciphertext, first.message_type).unwrap();
+
+        let transition = rotate(&bob_conn).unwrap();
+
+        let second = encrypt_message(&alice_conn, &bob, b"after").unwrap();
+        assert_eq!(
+            decrypt_message(&bob_conn, &alice, &second.ciphertext, second.message_type).unwrap(),
+            b"after"
+        );
+
+        // A new contact gets a bundle under the new identity
+        let carol_conn = init_test_db();
+        generate_identity(&carol_conn).unwrap();
+        let carol = ProtocolAddress::new("carol-user-id".into(), DeviceId::new(1).unwrap());
+        let bundle = generate_pre_key_bundle(&bob_conn, "bob-user-id").unwrap();
+        assert_eq!(bundle.identity_key, transition.new_identity_key);
+        let bob =
+            create_outgoing_session(&carol_conn, &serde_json::to_vec(&bundle).unwrap()).unwrap();
+        let message = encrypt_message(&carol_conn, &bob, b"hello").unwrap();
+        assert_eq!(
+            decrypt_message(&bob_conn, &carol, &message.ciphertext, message.message_type).unwrap(),
+            b"hello"
+        );
+    }
 }
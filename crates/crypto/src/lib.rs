@@ -1,8 +1,9 @@
 //! openconv-crypto -- Signal protocol implementation for OpenConv.
 //!
 //! Provides identity keypair generation (Curve25519), X3DH key agreement,
-//! Double Ratchet message encryption, AES-256-GCM file encryption, and
-//! secure key storage backed by encrypted SQLite (SQLCipher).
+//! Double Ratchet message encryption, Sender Key group messaging,
+//! AES-256-GCM file encryption, and secure key storage backed by encrypted
+//! SQLite (SQLCipher).
 //!
 //! ## Architecture
 //!
@@ -10,6 +11,11 @@
 //!   contexts (e.g., Tauri commands) should use `spawn_blocking`.
 //! - **Caller-provided connection**: Functions accept `&rusqlite::Connection`.
 //!   The desktop app manages the connection lifecycle and passes it in.
+//!   With the `pool` feature, `db::CryptoDb` can manage a pool of connections
+//!   instead and lend one per operation.
+//! - **Async variant**: With the `async` feature, `async_api::AsyncCryptoDb`
+//!   wraps the common operations in `spawn_blocking`, so async callers need
+//!   no bridging of their own.
 //! - **libsignal internally**: Uses the reference Signal protocol implementation
 //!   for X3DH and Double Ratchet. Async trait implementations bridge to sync
 //!   SQLite via `futures::executor::block_on`.
@@ -19,22 +25,36 @@
 //! - [`error`] -- `CryptoError` enum
 //! - [`master_key`] -- OS keychain and passphrase-based key management
 //! - [`storage`] -- SQLite storage layer and libsignal store trait implementations
-//! - [`identity`] -- Identity keypair generation and management
+//! - [`identity`] -- Identity keypair generation, management and rotation
 //! - [`prekeys`] -- Pre-key bundle and one-time pre-key management
-//! - [`session`] -- Signal session creation and recovery
+//! - [`session`] -- Signal session creation, listing, archiving and reset
 //! - [`message`] -- Message encryption and decryption
-//! - [`file_encryption`] -- AES-256-GCM symmetric file encryption
+//! - [`group`] -- Sender Key group messaging for E2E guild channels
+//! - [`file_encryption`] -- AES-256-GCM file encryption and per-recipient key wrapping
 //! - [`fingerprint`] -- Safety number generation and verification
+//! - [`verification`] -- Per-contact verified identity state
+//! - [`disappearing`] -- Encrypted disappearing-message timer changes
+//! - [`backup`] -- Passphrase-encrypted export and import of key material
+//! - `db` -- Pooled `CryptoDb` handle for concurrent access (`pool` feature)
+//! - `async_api` -- Async adapter over `CryptoDb` (`async` feature)
 
+#[cfg(feature = "async")]
+pub mod async_api;
+pub mod backup;
+#[cfg(feature = "pool")]
+pub mod db;
+pub mod disappearing;
 pub mod error;
 pub mod file_encryption;
 pub mod fingerprint;
+pub mod group;
 pub mod identity;
 pub mod master_key;
 pub mod message;
 pub mod prekeys;
 pub mod session;
 pub mod storage;
+pub mod verification;
 
 #[cfg(test)]
 mod tests {
@@ -60,11 +80,14 @@ mod tests {
             prekeys::generate_pre_key_bundle as fn(&_, &_) -> _,
         );
         let _ = session::create_outgoing_session as fn(&_, &_) -> _;
+        let _ = crate::group::encrypt as fn(&_, &_, _, &_) -> _;
+        let _ = crate::backup::export as fn(&_, &_) -> _;
         let _ = std::mem::size_of::<EncryptedMessage>();
         let _ = std::mem::size_of::<MessageType>();
         let _ = std::mem::size_of::<FileKey>();
         let _ = std::mem::size_of::<EncryptedBlob>();
         let _ = std::mem::size_of::<Fingerprint>();
+        let _ = std::mem::size_of::<crate::verification::VerificationStatus>();
 
         // Verify CryptoStore is accessible
         let _ = std::mem::size_of::<crate::storage::CryptoStore>();
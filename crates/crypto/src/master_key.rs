@@ -3,15 +3,34 @@
 //! Provides two-tier key management: a 32-byte master key (from OS keychain or
 //! user passphrase via Argon2id), derived into a database encryption key via
 //! HKDF-SHA256 for SQLCipher.
+//!
+//! Passphrase mode: `unlock_with_passphrase` keeps a random database key in
+//! the keychain, wrapped under a key derived from the passphrase, and
+//! `change_passphrase` rekeys the database to a fresh key wrapped under the
+//! new passphrase. The change goes through a pending keychain slot so a crash
+//! at any step leaves the database openable; the next unlock finishes or
+//! discards the interrupted change.
 
 use crate::error::CryptoError;
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
 use hkdf::Hkdf;
 use sha2::Sha256;
-use zeroize::{Zeroize, ZeroizeOnDrop};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 const KEYCHAIN_SERVICE: &str = "com.openconv.crypto";
 const KEYCHAIN_ACCOUNT: &str = "master_key";
+const KEYCHAIN_WRAPPED_DB_KEY: &str = "wrapped_db_key";
+const KEYCHAIN_PENDING_DB_KEY: &str = "wrapped_db_key_pending";
 const DB_KEY_INFO: &[u8] = b"openconv-db-encryption-v1";
+const DB_KEY_WRAP_INFO: &[u8] = b"openconv-db-key-wrap-v1";
+const DB_KEY_WRAP_VERSION: u8 = 2;
+// Version 1 had no Argon2 parameters and always used the defaults
+const DB_KEY_WRAP_V1_HEADER_LEN: usize = 1 + 16 + 12;
+const DB_KEY_WRAP_HEADER_LEN: usize = 1 + Argon2Params::ENCODED_LEN + 16 + 12;
+
+/// Lowest iteration count `calibrate` will choose, however slow the host.
+const MIN_CALIBRATED_ITERATIONS: u32 = 2;
 
 /// A 32-byte master key, securely wiped from memory on drop.
 #[derive(Zeroize, ZeroizeOnDrop)]
@@ -99,25 +118,128 @@ pub fn init_master_key_from_keychain() -> Result<MasterKey, CryptoError> {
     }
 }
 
-/// Derive a master key from a user passphrase and salt via Argon2id.
+/// Argon2id cost parameters for passphrase-derived keys.
+///
+/// Whatever derives a key with non-default parameters must store them with
+/// the salt (see `to_bytes`), since the same parameters are needed to derive
+/// the key again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Argon2Params {
+    /// Memory cost in KiB.
+    pub memory_kib: u32,
+    /// Number of passes over memory.
+    pub iterations: u32,
+    /// Degree of parallelism (lanes).
+    pub parallelism: u32,
+}
+
+impl Default for Argon2Params {
+    fn default() -> Self {
+        Self {
+            memory_kib: 65536,
+            iterations: 3,
+            parallelism: 4,
+        }
+    }
+}
+
+impl Argon2Params {
+    /// Length of the `to_bytes` encoding.
+    pub const ENCODED_LEN: usize = 12;
+    /// Largest memory cost `from_bytes` accepts (1 GiB).
+    pub const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+
+    /// Encode as `memory_kib || iterations || parallelism`, big-endian.
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut bytes = [0u8; Self::ENCODED_LEN];
+        bytes[..4].copy_from_slice(&self.memory_kib.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.iterations.to_be_bytes());
+        bytes[8..].copy_from_slice(&self.parallelism.to_be_bytes());
+        bytes
+    }
+
+    /// Decode parameters written by `to_bytes`, rejecting values Argon2id
+    /// does not accept and memory costs above `MAX_MEMORY_KIB`, so stored
+    /// parameters cannot make a derivation exhaust memory.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CryptoError> {
+        if bytes.len() != Self::ENCODED_LEN {
+            return Err(CryptoError::InvalidKey(
+                "malformed Argon2 parameters".into(),
+            ));
+        }
+        let word = |i: usize| u32::from_be_bytes(bytes[i..i + 4].try_into().unwrap());
+        let params = Self {
+            memory_kib: word(0),
+            iterations: word(4),
+            parallelism: word(8),
+        };
+        if params.memory_kib > Self::MAX_MEMORY_KIB {
+            return Err(CryptoError::InvalidKey(
+                "Argon2 memory cost too large".into(),
+            ));
+        }
+        params.to_argon2()?;
+        Ok(params)
+    }
+
+    fn to_argon2(self) -> Result<argon2::Params, CryptoError> {
+        argon2::Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| CryptoError::InvalidKey(e.to_string()))
+    }
+}
+
+/// Derive a master key from a user passphrase and salt via Argon2id, using
+/// the default `Argon2Params`.
 pub fn init_master_key_from_passphrase(
     passphrase: &str,
     salt: &[u8],
+) -> Result<MasterKey, CryptoError> {
+    init_master_key_from_passphrase_with_params(passphrase, salt, &Argon2Params::default())
+}
+
+/// Derive a master key from a user passphrase and salt via Argon2id with the
+/// given cost parameters.
+pub fn init_master_key_from_passphrase_with_params(
+    passphrase: &str,
+    salt: &[u8],
+    params: &Argon2Params,
 ) -> Result<MasterKey, CryptoError> {
     if salt.len() < 16 {
         return Err(CryptoError::InvalidKey("salt too short".into()));
     }
 
-    let params = argon2::Params::new(65536, 3, 4, Some(32))
-        .map_err(|e| CryptoError::InvalidKey(e.to_string()))?;
+    let params = params.to_argon2()?;
     let argon2 = argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
 
-    let mut output = [0u8; 32];
+    let mut master_key = MasterKey { key: [0u8; 32] };
     argon2
-        .hash_password_into(passphrase.as_bytes(), salt, &mut output)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut master_key.key)
         .map_err(|e| CryptoError::InvalidKey(e.to_string()))?;
 
-    Ok(MasterKey { key: output })
+    Ok(master_key)
+}
+
+/// Choose Argon2id parameters that take roughly `target_ms` on this host.
+///
+/// Keeps the default memory cost and parallelism and scales the iteration
+/// count from a single timed pass. The count never drops below
+/// `MIN_CALIBRATED_ITERATIONS`, so a slow host gets slower unlocks rather
+/// than weaker keys.
+pub fn calibrate(target_ms: u64) -> Result<Argon2Params, CryptoError> {
+    let probe = Argon2Params {
+        iterations: 1,
+        ..Argon2Params::default()
+    };
+    let started = std::time::Instant::now();
+    init_master_key_from_passphrase_with_params("calibration", &generate_salt(), &probe)?;
+    let per_pass_ms = started.elapsed().as_millis().max(1) as u64;
+
+    let iterations =
+        (target_ms / per_pass_ms).clamp(MIN_CALIBRATED_ITERATIONS as u64, u32::MAX as u64);
+    Ok(Argon2Params {
+        iterations: iterations as u32,
+        ..Argon2Params::default()
+    })
 }
 
 /// Generate a random 16-byte salt for passphrase derivation.
@@ -130,19 +252,20 @@ pub fn generate_salt() -> [u8; 16] {
 /// Derive a database encryption key from a master key via HKDF-SHA256.
 pub fn derive_db_encryption_key(master_key: &MasterKey) -> Result<DbEncryptionKey, CryptoError> {
     let hk = Hkdf::<Sha256>::new(None, master_key.as_bytes());
-    let mut okm = [0u8; 32];
-    hk.expand(DB_KEY_INFO, &mut okm)
+    let mut okm = Zeroizing::new([0u8; 32]);
+    hk.expand(DB_KEY_INFO, okm.as_mut())
         .map_err(|e| CryptoError::InvalidKey(e.to_string()))?;
 
-    let mut hex_str = hex_encode(&okm);
-    okm.zeroize();
+    Ok(db_key_from_bytes(&okm))
+}
 
+fn db_key_from_bytes(bytes: &[u8; 32]) -> DbEncryptionKey {
+    let mut hex_str = hex_encode(bytes);
     let result = DbEncryptionKey {
         hex: format!("x'{hex_str}'"),
     };
     hex_str.zeroize();
-
-    Ok(result)
+    result
 }
 
 /// Apply a SQLCipher encryption key to a database connection.
@@ -157,6 +280,24 @@ pub fn apply_encryption_key(
     // x'...' hex key syntax is a SQL literal. If passed as a bound parameter,
     // SQLCipher treats it as a passphrase and applies PBKDF2, producing a
     // different key than intended.
+    let pragma_val = checked_pragma_value(db_key)?;
+    conn.execute_batch(&format!("PRAGMA key = \"{pragma_val}\";"))?;
+
+    let cipher_version: String = conn
+        .pragma_query_value(None, "cipher_version", |row| row.get(0))
+        .map_err(|_| CryptoError::StorageError("SQLCipher not available".into()))?;
+
+    if cipher_version.is_empty() {
+        return Err(CryptoError::StorageError("SQLCipher not available".into()));
+    }
+
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.pragma_update(None, "foreign_keys", "ON")?;
+
+    Ok(())
+}
+
+fn checked_pragma_value(db_key: &DbEncryptionKey) -> Result<&str, CryptoError> {
     let pragma_val = db_key.as_pragma_value();
     if pragma_val.len() != 67
         || !pragma_val.starts_with("x'")
@@ -167,20 +308,295 @@ pub fn apply_encryption_key(
             "malformed database encryption key".into(),
         ));
     }
-    conn.execute_batch(&format!("PRAGMA key = \"{pragma_val}\";"))?;
+    Ok(pragma_val)
+}
 
-    let cipher_version: String = conn
-        .pragma_query_value(None, "cipher_version", |row| row.get(0))
-        .map_err(|_| CryptoError::StorageError("SQLCipher not available".into()))?;
+/// Open a passphrase-protected database on `conn`.
+///
+/// On first use, generates a random database key and stores it in the OS
+/// keychain wrapped under `passphrase`. Returns `CryptoError::InvalidKey` if
+/// `passphrase` does not unlock the database.
+///
+/// If a `change_passphrase` was interrupted, whichever of the old and new
+/// passphrases matches the database's current key unlocks it, and the
+/// keychain is brought back in line with that key.
+pub fn unlock_with_passphrase(
+    conn: &rusqlite::Connection,
+    passphrase: &str,
+) -> Result<(), CryptoError> {
+    unlock_with(&OsKeychain, conn, passphrase)
+}
 
-    if cipher_version.is_empty() {
-        return Err(CryptoError::StorageError("SQLCipher not available".into()));
+/// Change the passphrase protecting the database open on `conn`.
+///
+/// Rekeys the database to a new random key with `PRAGMA rekey` and stores
+/// that key in the OS keychain wrapped under `new_passphrase`. Returns
+/// `CryptoError::InvalidKey` without changing anything if `old_passphrase`
+/// is wrong.
+///
+/// The new wrapped key is written to a pending keychain slot before the
+/// rekey and promoted only after it succeeds, so a crash at any point leaves
+/// a keychain entry that opens the database; see `unlock_with_passphrase`.
+///
+/// Keeps the Argon2 parameters the current key was wrapped with.
+pub fn change_passphrase(
+    conn: &rusqlite::Connection,
+    old_passphrase: &str,
+    new_passphrase: &str,
+) -> Result<(), CryptoError> {
+    change_passphrase_with(&OsKeychain, conn, old_passphrase, new_passphrase, None)
+}
+
+/// Like `change_passphrase`, but wraps the new key with `params`, e.g. ones
+/// chosen by `calibrate`. Passing the same passphrase twice only changes the
+/// parameters (and the database key).
+pub fn change_passphrase_with_params(
+    conn: &rusqlite::Connection,
+    old_passphrase: &str,
+    new_passphrase: &str,
+    params: &Argon2Params,
+) -> Result<(), CryptoError> {
+    change_passphrase_with(
+        &OsKeychain,
+        conn,
+        old_passphrase,
+        new_passphrase,
+        Some(params),
+    )
+}
+
+/// Named storage for wrapped database keys. The OS keychain in production;
+/// tests substitute an in-memory map.
+trait KeySlots {
+    fn get(&self, slot: &str) -> Result<Option<Vec<u8>>, CryptoError>;
+    fn set(&self, slot: &str, value: &[u8]) -> Result<(), CryptoError>;
+    fn delete(&self, slot: &str) -> Result<(), CryptoError>;
+}
+
+struct OsKeychain;
+
+impl OsKeychain {
+    fn entry(slot: &str) -> Result<keyring::Entry, CryptoError> {
+        keyring::Entry::new(KEYCHAIN_SERVICE, slot).map_err(|_| CryptoError::KeychainUnavailable)
+    }
+}
+
+impl KeySlots for OsKeychain {
+    fn get(&self, slot: &str) -> Result<Option<Vec<u8>>, CryptoError> {
+        match Self::entry(slot)?.get_password() {
+            Ok(hex_string) => hex_decode(&hex_string)
+                .map(Some)
+                .ok_or_else(|| CryptoError::KeychainError(format!("malformed {slot} in keychain"))),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(CryptoError::from(e)),
+        }
     }
 
+    fn set(&self, slot: &str, value: &[u8]) -> Result<(), CryptoError> {
+        Self::entry(slot)?
+            .set_password(&hex_encode(value))
+            .map_err(CryptoError::from)
+    }
+
+    fn delete(&self, slot: &str) -> Result<(), CryptoError> {
+        match Self::entry(slot)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(CryptoError::from(e)),
+        }
+    }
+}
+
+fn unlock_with(
+    slots: &dyn KeySlots,
+    conn: &rusqlite::Connection,
+    passphrase: &str,
+) -> Result<(), CryptoError> {
+    let Some(wrapped) = slots.get(KEYCHAIN_WRAPPED_DB_KEY)? else {
+        let mut key = Zeroizing::new([0u8; 32]);
+        rand::RngCore::fill_bytes(&mut rand::rng(), key.as_mut());
+        let wrapped = wrap_db_key(passphrase, &key, &Argon2Params::default())?;
+        slots.set(KEYCHAIN_WRAPPED_DB_KEY, &wrapped)?;
+        return apply_encryption_key(conn, &db_key_from_bytes(&key));
+    };
+    let pending = slots.get(KEYCHAIN_PENDING_DB_KEY)?;
+
+    // In-memory databases cannot be probed from a second connection
+    let path = conn.path().filter(|path| !path.is_empty());
+    let Some(path) = path else {
+        let key = unwrap_db_key(passphrase, &wrapped)?.ok_or_else(incorrect_passphrase)?;
+        return apply_encryption_key(conn, &db_key_from_bytes(&key));
+    };
+
+    if let Some(key) = unwrap_db_key(passphrase, &wrapped)? {
+        let db_key = db_key_from_bytes(&key);
+        if opens_with(path, &db_key)? {
+            // Any pending key belongs to a change that never rekeyed
+            slots.delete(KEYCHAIN_PENDING_DB_KEY)?;
+            return apply_encryption_key(conn, &db_key);
+        }
+    }
+    if let Some(pending) = pending {
+        if let Some(key) = unwrap_db_key(passphrase, &pending)? {
+            let db_key = db_key_from_bytes(&key);
+            if opens_with(path, &db_key)? {
+                // The rekey completed but the new key was never promoted
+                slots.set(KEYCHAIN_WRAPPED_DB_KEY, &pending)?;
+                slots.delete(KEYCHAIN_PENDING_DB_KEY)?;
+                return apply_encryption_key(conn, &db_key);
+            }
+        }
+    }
+    Err(incorrect_passphrase())
+}
+
+fn change_passphrase_with(
+    slots: &dyn KeySlots,
+    conn: &rusqlite::Connection,
+    old_passphrase: &str,
+    new_passphrase: &str,
+    params: Option<&Argon2Params>,
+) -> Result<(), CryptoError> {
+    let wrapped = slots
+        .get(KEYCHAIN_WRAPPED_DB_KEY)?
+        .ok_or(CryptoError::KeychainEntryNotFound)?;
+    unwrap_db_key(old_passphrase, &wrapped)?.ok_or_else(incorrect_passphrase)?;
+    let params = match params {
+        Some(params) => *params,
+        None => parse_wrapped_db_key(&wrapped)?.params,
+    };
+
+    let mut key = Zeroizing::new([0u8; 32]);
+    rand::RngCore::fill_bytes(&mut rand::rng(), key.as_mut());
+    let new_wrapped = wrap_db_key(new_passphrase, &key, &params)?;
+    let db_key = db_key_from_bytes(&key);
+    let pragma_val = checked_pragma_value(&db_key)?;
+
+    slots.set(KEYCHAIN_PENDING_DB_KEY, &new_wrapped)?;
+
+    // Leave WAL first so no frames written under the old key outlive the rekey
+    conn.pragma_update(None, "journal_mode", "DELETE")?;
+    let rekeyed = conn.execute_batch(&format!("PRAGMA rekey = \"{pragma_val}\";"));
     conn.pragma_update(None, "journal_mode", "WAL")?;
-    conn.pragma_update(None, "foreign_keys", "ON")?;
+    if let Err(e) = rekeyed {
+        slots.delete(KEYCHAIN_PENDING_DB_KEY)?;
+        return Err(e.into());
+    }
 
-    Ok(())
+    slots.set(KEYCHAIN_WRAPPED_DB_KEY, &new_wrapped)?;
+    slots.delete(KEYCHAIN_PENDING_DB_KEY)
+}
+
+fn incorrect_passphrase() -> CryptoError {
+    CryptoError::InvalidKey("incorrect passphrase".into())
+}
+
+/// Whether `db_key` opens the database at `path`, checked on a separate
+/// connection so a wrong guess does not poison the caller's.
+fn opens_with(path: &str, db_key: &DbEncryptionKey) -> Result<bool, CryptoError> {
+    let probe = rusqlite::Connection::open(path)?;
+    probe.execute_batch(&format!(
+        "PRAGMA key = \"{}\";",
+        checked_pragma_value(db_key)?
+    ))?;
+    Ok(detect_encryption_status(&probe)? == EncryptionStatus::Unencrypted)
+}
+
+/// Wrapped layout: `version || Argon2 params (12) || salt (16) || nonce (12)
+/// || AES-256-GCM(key)`, with the header as associated data.
+fn wrap_db_key(
+    passphrase: &str,
+    key: &[u8; 32],
+    params: &Argon2Params,
+) -> Result<Vec<u8>, CryptoError> {
+    let salt = generate_salt();
+    let mut nonce = [0u8; 12];
+    rand::RngCore::fill_bytes(&mut rand::rng(), &mut nonce);
+
+    let mut wrapped = Vec::with_capacity(DB_KEY_WRAP_HEADER_LEN + 48);
+    wrapped.push(DB_KEY_WRAP_VERSION);
+    wrapped.extend_from_slice(&params.to_bytes());
+    wrapped.extend_from_slice(&salt);
+    wrapped.extend_from_slice(&nonce);
+
+    let ciphertext = wrapping_cipher(passphrase, &salt, params)?
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: key,
+                aad: &wrapped,
+            },
+        )
+        .map_err(|_| CryptoError::InvalidKey("failed to wrap database key".into()))?;
+    wrapped.extend_from_slice(&ciphertext);
+    Ok(wrapped)
+}
+
+/// Unwrap a key produced by `wrap_db_key`. Returns `None` if `passphrase` is
+/// not the one it was wrapped under.
+fn unwrap_db_key(
+    passphrase: &str,
+    wrapped: &[u8],
+) -> Result<Option<Zeroizing<[u8; 32]>>, CryptoError> {
+    let parsed = parse_wrapped_db_key(wrapped)?;
+    let Ok(plaintext) = wrapping_cipher(passphrase, parsed.salt, &parsed.params)?.decrypt(
+        Nonce::from_slice(parsed.nonce),
+        Payload {
+            msg: parsed.ciphertext,
+            aad: parsed.header,
+        },
+    ) else {
+        return Ok(None);
+    };
+    let plaintext = Zeroizing::new(plaintext);
+    let mut key = Zeroizing::new([0u8; 32]);
+    key.copy_from_slice(&plaintext);
+    Ok(Some(key))
+}
+
+struct WrappedDbKey<'a> {
+    header: &'a [u8],
+    params: Argon2Params,
+    salt: &'a [u8],
+    nonce: &'a [u8],
+    ciphertext: &'a [u8],
+}
+
+fn parse_wrapped_db_key(wrapped: &[u8]) -> Result<WrappedDbKey<'_>, CryptoError> {
+    let malformed = || CryptoError::KeychainError("malformed wrapped database key".into());
+    let (header_len, params) = match wrapped.first() {
+        Some(&1) => (DB_KEY_WRAP_V1_HEADER_LEN, Argon2Params::default()),
+        Some(&DB_KEY_WRAP_VERSION) if wrapped.len() > DB_KEY_WRAP_HEADER_LEN => (
+            DB_KEY_WRAP_HEADER_LEN,
+            Argon2Params::from_bytes(&wrapped[1..1 + Argon2Params::ENCODED_LEN])
+                .map_err(|_| malformed())?,
+        ),
+        _ => return Err(malformed()),
+    };
+    if wrapped.len() != header_len + 48 {
+        return Err(malformed());
+    }
+    let (header, ciphertext) = wrapped.split_at(header_len);
+    let (salt, nonce) = header[header_len - 28..].split_at(16);
+    Ok(WrappedDbKey {
+        header,
+        params,
+        salt,
+        nonce,
+        ciphertext,
+    })
+}
+
+fn wrapping_cipher(
+    passphrase: &str,
+    salt: &[u8],
+    params: &Argon2Params,
+) -> Result<Aes256Gcm, CryptoError> {
+    let master_key = init_master_key_from_passphrase_with_params(passphrase, salt, params)?;
+    let mut okm = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(None, master_key.as_bytes())
+        .expand(DB_KEY_WRAP_INFO, okm.as_mut())
+        .map_err(|e| CryptoError::InvalidKey(e.to_string()))?;
+    Aes256Gcm::new_from_slice(okm.as_ref()).map_err(|e| CryptoError::InvalidKey(e.to_string()))
 }
 
 /// Detect whether a database is encrypted or unencrypted.
@@ -403,6 +819,14 @@ mod tests {
         assert!(!debug.contains(&format!("{:02x}", mk.as_bytes()[0])));
     }
 
+    #[test]
+    fn test_master_key_zeroize_wipes_bytes() {
+        let mut mk = passphrase_key("wipe-test", &[19u8; 16]);
+        assert_ne!(mk.as_bytes(), &[0u8; 32]);
+        mk.zeroize();
+        assert_eq!(mk.as_bytes(), &[0u8; 32]);
+    }
+
     #[test]
     fn test_db_encryption_key_debug_is_redacted() {
         let mk = passphrase_key("debug-test", &[15u8; 16]);
@@ -411,6 +835,259 @@ mod tests {
         assert!(debug.contains("REDACTED"));
     }
 
+    // --- Passphrase Change ---
+
+    #[derive(Default)]
+    struct MemorySlots(std::cell::RefCell<std::collections::HashMap<String, Vec<u8>>>);
+
+    impl KeySlots for MemorySlots {
+        fn get(&self, slot: &str) -> Result<Option<Vec<u8>>, CryptoError> {
+            Ok(self.0.borrow().get(slot).cloned())
+        }
+
+        fn set(&self, slot: &str, value: &[u8]) -> Result<(), CryptoError> {
+            self.0.borrow_mut().insert(slot.into(), value.to_vec());
+            Ok(())
+        }
+
+        fn delete(&self, slot: &str) -> Result<(), CryptoError> {
+            self.0.borrow_mut().remove(slot);
+            Ok(())
+        }
+    }
+
+    fn create_db(
+        slots: &MemorySlots,
+        path: &std::path::Path,
+        passphrase: &str,
+    ) -> rusqlite::Connection {
+        let conn = rusqlite::Connection::open(path).unwrap();
+        unlock_with(slots, &conn, passphrase).unwrap();
+        conn.execute_batch("CREATE TABLE test (val TEXT); INSERT INTO test VALUES ('kept');")
+            .unwrap();
+        conn
+    }
+
+    fn read_with(
+        slots: &MemorySlots,
+        path: &std::path::Path,
+        passphrase: &str,
+    ) -> Result<String, CryptoError> {
+        let conn = rusqlite::Connection::open(path).unwrap();
+        unlock_with(slots, &conn, passphrase)?;
+        Ok(conn.query_row("SELECT val FROM test", [], |row| row.get(0))?)
+    }
+
+    #[test]
+    fn test_change_passphrase_rekeys_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rekey.db");
+        let slots = MemorySlots::default();
+        let conn = create_db(&slots, &path, "old-pass");
+        let old_wrapped = slots.get(KEYCHAIN_WRAPPED_DB_KEY).unwrap();
+
+        change_passphrase_with(&slots, &conn, "old-pass", "new-pass", None).unwrap();
+        drop(conn);
+
+        assert_ne!(slots.get(KEYCHAIN_WRAPPED_DB_KEY).unwrap(), old_wrapped);
+        assert!(slots.get(KEYCHAIN_PENDING_DB_KEY).unwrap().is_none());
+        assert_eq!(read_with(&slots, &path, "new-pass").unwrap(), "kept");
+        assert!(matches!(
+            read_with(&slots, &path, "old-pass"),
+            Err(CryptoError::InvalidKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_change_passphrase_rejects_wrong_old_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wrong.db");
+        let slots = MemorySlots::default();
+        let conn = create_db(&slots, &path, "old-pass");
+
+        let result = change_passphrase_with(&slots, &conn, "not-it", "new-pass", None);
+        assert!(matches!(result, Err(CryptoError::InvalidKey(_))));
+        drop(conn);
+
+        assert!(slots.get(KEYCHAIN_PENDING_DB_KEY).unwrap().is_none());
+        assert_eq!(read_with(&slots, &path, "old-pass").unwrap(), "kept");
+    }
+
+    #[test]
+    fn test_interrupted_before_rekey_keeps_old_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("before.db");
+        let slots = MemorySlots::default();
+        drop(create_db(&slots, &path, "old-pass"));
+
+        // Crash after the pending key was stored, before PRAGMA rekey
+        let pending = wrap_db_key("new-pass", &[7u8; 32], &Argon2Params::default()).unwrap();
+        slots.set(KEYCHAIN_PENDING_DB_KEY, &pending).unwrap();
+
+        assert!(read_with(&slots, &path, "new-pass").is_err());
+        assert_eq!(read_with(&slots, &path, "old-pass").unwrap(), "kept");
+        assert!(slots.get(KEYCHAIN_PENDING_DB_KEY).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_interrupted_after_rekey_promotes_pending_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("after.db");
+        let slots = MemorySlots::default();
+        let conn = create_db(&slots, &path, "old-pass");
+        let old_wrapped = slots.get(KEYCHAIN_WRAPPED_DB_KEY).unwrap().unwrap();
+        change_passphrase_with(&slots, &conn, "old-pass", "new-pass", None).unwrap();
+        drop(conn);
+
+        // Crash after PRAGMA rekey, before the new key was promoted
+        let new_wrapped = slots.get(KEYCHAIN_WRAPPED_DB_KEY).unwrap().unwrap();
+        slots.set(KEYCHAIN_WRAPPED_DB_KEY, &old_wrapped).unwrap();
+        slots.set(KEYCHAIN_PENDING_DB_KEY, &new_wrapped).unwrap();
+
+        assert!(read_with(&slots, &path, "old-pass").is_err());
+        assert_eq!(read_with(&slots, &path, "new-pass").unwrap(), "kept");
+        assert_eq!(
+            slots.get(KEYCHAIN_WRAPPED_DB_KEY).unwrap(),
+            Some(new_wrapped)
+        );
+        assert!(slots.get(KEYCHAIN_PENDING_DB_KEY).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_interrupted_before_cleanup_removes_pending_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cleanup.db");
+        let slots = MemorySlots::default();
+        let conn = create_db(&slots, &path, "old-pass");
+        change_passphrase_with(&slots, &conn, "old-pass", "new-pass", None).unwrap();
+        drop(conn);
+
+        // Crash after promotion, before the pending slot was deleted
+        let new_wrapped = slots.get(KEYCHAIN_WRAPPED_DB_KEY).unwrap().unwrap();
+        slots.set(KEYCHAIN_PENDING_DB_KEY, &new_wrapped).unwrap();
+
+        assert_eq!(read_with(&slots, &path, "new-pass").unwrap(), "kept");
+        assert!(slots.get(KEYCHAIN_PENDING_DB_KEY).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_unwrap_db_key_with_wrong_passphrase_returns_none() {
+        let wrapped = wrap_db_key("right", &[3u8; 32], &Argon2Params::default()).unwrap();
+        assert_eq!(
+            *unwrap_db_key("right", &wrapped).unwrap().unwrap(),
+            [3u8; 32]
+        );
+        assert!(unwrap_db_key("wrong", &wrapped).unwrap().is_none());
+        assert!(unwrap_db_key("right", &wrapped[1..]).is_err());
+    }
+
+    #[test]
+    fn test_change_passphrase_with_params_stores_params() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("params.db");
+        let slots = MemorySlots::default();
+        let conn = create_db(&slots, &path, "pass");
+        let params = Argon2Params {
+            memory_kib: 8192,
+            iterations: 2,
+            parallelism: 1,
+        };
+
+        change_passphrase_with(&slots, &conn, "pass", "pass", Some(&params)).unwrap();
+        let wrapped = slots.get(KEYCHAIN_WRAPPED_DB_KEY).unwrap().unwrap();
+        assert_eq!(parse_wrapped_db_key(&wrapped).unwrap().params, params);
+
+        // A later change without params keeps them
+        change_passphrase_with(&slots, &conn, "pass", "next", None).unwrap();
+        drop(conn);
+        let wrapped = slots.get(KEYCHAIN_WRAPPED_DB_KEY).unwrap().unwrap();
+        assert_eq!(parse_wrapped_db_key(&wrapped).unwrap().params, params);
+        assert_eq!(read_with(&slots, &path, "next").unwrap(), "kept");
+    }
+
+    // --- Argon2 Parameters ---
+
+    #[test]
+    fn test_default_params_match_plain_derivation() {
+        let salt = [16u8; 16];
+        let mk1 = passphrase_key("params", &salt);
+        let mk2 =
+            init_master_key_from_passphrase_with_params("params", &salt, &Argon2Params::default())
+                .unwrap();
+        assert_eq!(mk1.as_bytes(), mk2.as_bytes());
+    }
+
+    #[test]
+    fn test_different_params_produce_different_keys() {
+        let salt = [17u8; 16];
+        let light = Argon2Params {
+            memory_kib: 8192,
+            iterations: 1,
+            parallelism: 1,
+        };
+        let mk1 = init_master_key_from_passphrase_with_params("params", &salt, &light).unwrap();
+        let mk2 = passphrase_key("params", &salt);
+        assert_ne!(mk1.as_bytes(), mk2.as_bytes());
+    }
+
+    #[test]
+    fn test_params_bytes_round_trip_and_reject_invalid() {
+        let params = Argon2Params {
+            memory_kib: 131072,
+            iterations: 5,
+            parallelism: 2,
+        };
+        assert_eq!(
+            Argon2Params::from_bytes(&params.to_bytes()).unwrap(),
+            params
+        );
+
+        let zero_iterations = Argon2Params {
+            iterations: 0,
+            ..params
+        };
+        assert!(Argon2Params::from_bytes(&zero_iterations.to_bytes()).is_err());
+        assert!(Argon2Params::from_bytes(&[0u8; 4]).is_err());
+        let huge = Argon2Params {
+            memory_kib: Argon2Params::MAX_MEMORY_KIB + 1,
+            ..params
+        };
+        assert!(Argon2Params::from_bytes(&huge.to_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_calibrate_scales_iterations_only() {
+        let params = calibrate(1).unwrap();
+        assert_eq!(params.iterations, MIN_CALIBRATED_ITERATIONS);
+
+        let params = calibrate(60_000).unwrap();
+        assert!(params.iterations > MIN_CALIBRATED_ITERATIONS);
+        assert_eq!(params.memory_kib, Argon2Params::default().memory_kib);
+        assert_eq!(params.parallelism, Argon2Params::default().parallelism);
+    }
+
+    #[test]
+    fn test_version_1_wrapped_key_uses_default_params() {
+        let salt = [18u8; 16];
+        let nonce = [0u8; 12];
+        let mut wrapped = vec![1u8];
+        wrapped.extend_from_slice(&salt);
+        wrapped.extend_from_slice(&nonce);
+        let ciphertext = wrapping_cipher("v1", &salt, &Argon2Params::default())
+            .unwrap()
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &[5u8; 32],
+                    aad: &wrapped,
+                },
+            )
+            .unwrap();
+        wrapped.extend_from_slice(&ciphertext);
+
+        assert_eq!(*unwrap_db_key("v1", &wrapped).unwrap().unwrap(), [5u8; 32]);
+    }
+
     // --- Salt Generation ---
 
     #[test]
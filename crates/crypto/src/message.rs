@@ -7,15 +7,42 @@
 //! Auto-recovery: when decryption detects a corrupted session, it deletes the
 //! session via `recover_session` and returns `CryptoError::SessionCorrupted`
 //! so the caller can request a fresh pre-key bundle and re-establish.
+//!
+//! Typed payloads: `encrypt_envelope` and `decrypt_envelope` wrap the same
+//! primitives around a shared `MessageEnvelope`, so reactions, stickers, and
+//! system notices travel over the E2EE channel like text.
+//!
+//! Skipped message keys: libsignal stashes them in the session record, and
+//! every successful decrypt enforces `SkippedKeyLimits` on what is stored.
+//! `decrypt_message` uses the defaults; `decrypt_message_with_limits` lets the
+//! caller configure the caps and max age.
+//!
+//! Framing: when the recipient's envelopes carry `FRAMED_ENVELOPE_VERSION`
+//! or newer, the plaintext is framed with an associated-data header and
+//! padded, and `EncryptedMessage::envelope_version` says so. Older peers get
+//! the bare plaintext. The recipient unframes according to the version the
+//! sender declared, never by inspecting the plaintext.
+//!
+//! Padding: framed plaintexts are padded so the ciphertext length leaks less
+//! about the content. `EncryptOptions::padding` chooses the `PaddingPolicy`;
+//! decryption strips the padding whatever policy the sender used.
+//!
+//! Associated data: `EncryptOptions::associated_data` binds caller context
+//! (see `message_aad`) into a framed ciphertext. `decrypt_message_with_options`
+//! rejects a message whose bound context differs from the one the recipient
+//! expects, so a ciphertext cannot be replayed into another conversation.
 
 use libsignal_protocol::{
     CiphertextMessageType, PreKeySignalMessage, ProtocolAddress, SignalMessage, SignalProtocolError,
 };
+use openconv_shared::api::envelope::{MessageEnvelope, ENVELOPE_VERSION, FRAMED_ENVELOPE_VERSION};
 use rusqlite::Connection;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
 
 use crate::error::CryptoError;
 use crate::session::recover_session;
-use crate::storage::CryptoStore;
+use crate::storage::{write_transaction, CryptoStore, SkippedKeyLimits};
 
 /// The type of Signal protocol message, indicating how it should be decrypted.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -55,21 +82,139 @@ pub struct EncryptedMessage {
     pub ciphertext: Vec<u8>,
     /// Whether this is a PreKey message (first in session) or Signal message (subsequent).
     pub message_type: MessageType,
+    /// Envelope version the plaintext was framed for. Sent in the clear
+    /// alongside the ciphertext; the recipient passes it back as
+    /// `DecryptOptions::envelope_version`.
+    pub envelope_version: u16,
+}
+
+/// Envelope version declared for bare plaintexts sent to peers that predate
+/// framing.
+const BARE_ENVELOPE_VERSION: u16 = 1;
+
+/// Marks the end of the plaintext; only zero bytes may follow it.
+const PADDING_MARKER: u8 = 0x80;
+
+/// Prefix of a plaintext carrying an associated-data digest.
+const AAD_MAGIC: &[u8; 4] = b"ocad";
+const AAD_VERSION: u8 = 1;
+const AAD_HEADER_LEN: usize = 4 + 1 + 32;
+
+/// How a plaintext is padded before Double Ratchet encryption.
+///
+/// Every policy appends a `0x80` marker followed by zero bytes up to the
+/// padded length, so the recipient can strip it without knowing the policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PaddingPolicy {
+    /// Only the end marker. The ciphertext length reveals the exact
+    /// plaintext length.
+    None,
+    /// Padmé: rounds the length up so at most O(log log n) bits of it leak,
+    /// with no more than ~12% overhead.
+    #[default]
+    Padme,
+    /// Rounds the length up to the next multiple of the given bucket size.
+    /// A size of 0 is treated as 1.
+    Bucket(usize),
+}
+
+impl PaddingPolicy {
+    /// Padded length for an unpadded length of `len` bytes (marker included).
+    fn padded_len(&self, len: usize) -> usize {
+        match *self {
+            PaddingPolicy::None => len,
+            PaddingPolicy::Padme => padme(len),
+            PaddingPolicy::Bucket(size) => len.div_ceil(size.max(1)) * size.max(1),
+        }
+    }
+}
+
+/// Options for `encrypt_message_with_options`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EncryptOptions<'a> {
+    /// Padding applied to the plaintext before encryption.
+    pub padding: PaddingPolicy,
+    /// Context bound into the ciphertext. The recipient must pass the same
+    /// bytes to `decrypt_message_with_options`.
+    pub associated_data: &'a [u8],
+    /// Highest envelope version the recipient supports, as learned from the
+    /// envelopes it sends. Below `FRAMED_ENVELOPE_VERSION` the plaintext is
+    /// sent bare: `padding` is ignored and `associated_data` must be empty.
+    pub peer_envelope_version: u16,
+}
+
+/// Options for `decrypt_message_with_options`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DecryptOptions<'a> {
+    /// Skipped-message-key limits enforced on the sender's session.
+    pub limits: SkippedKeyLimits,
+    /// Context the message must have been bound to by the sender.
+    pub associated_data: &'a [u8],
+    /// Envelope version the sender declared in `EncryptedMessage`.
+    pub envelope_version: u16,
+}
+
+/// Associated data identifying where a message belongs: the channel, the
+/// message, and the sending device.
+///
+/// Fields are length-prefixed so distinct contexts never encode equally.
+pub fn message_aad(channel_id: &str, message_id: &str, sender_device_id: u32) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(16 + channel_id.len() + message_id.len());
+    for field in [channel_id.as_bytes(), message_id.as_bytes()] {
+        aad.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        aad.extend_from_slice(field);
+    }
+    aad.extend_from_slice(&sender_device_id.to_be_bytes());
+    aad
 }
 
 /// Encrypt a plaintext message to a remote recipient.
 ///
 /// Requires an established session (created via `create_outgoing_session`).
-/// Returns `CryptoError::SessionNotFound` if no session exists.
+/// Returns `CryptoError::SessionNotFound` if no session exists or it has been
+/// archived with `session::archive`.
 ///
 /// The session ratchet advance and ciphertext creation are atomic — wrapped
 /// in a transaction so a partial failure cannot desync ratchet state.
+///
+/// Sends the plaintext bare, as a recipient that predates framing expects.
 pub fn encrypt_message(
     conn: &Connection,
     recipient: &ProtocolAddress,
     plaintext: &[u8],
 ) -> Result<EncryptedMessage, CryptoError> {
-    let tx = conn.unchecked_transaction()?;
+    encrypt_message_with_options(conn, recipient, plaintext, &EncryptOptions::default())
+}
+
+/// Encrypt a plaintext message, padding it according to `options.padding`
+/// and binding `options.associated_data` into the ciphertext when the
+/// recipient supports framing.
+///
+/// Returns `CryptoError::SerializationError` if associated data is given for
+/// a recipient that predates framing. Behaves like `encrypt_message` otherwise.
+pub fn encrypt_message_with_options(
+    conn: &Connection,
+    recipient: &ProtocolAddress,
+    plaintext: &[u8],
+    options: &EncryptOptions,
+) -> Result<EncryptedMessage, CryptoError> {
+    let framed = options.peer_envelope_version >= FRAMED_ENVELOPE_VERSION;
+    let padded = if framed {
+        let mut bound = Zeroizing::new(Vec::with_capacity(AAD_HEADER_LEN + plaintext.len()));
+        bound.extend_from_slice(AAD_MAGIC);
+        bound.push(AAD_VERSION);
+        bound.extend_from_slice(&Sha256::digest(options.associated_data));
+        bound.extend_from_slice(plaintext);
+        Zeroizing::new(pad(&bound, options.padding))
+    } else if options.associated_data.is_empty() {
+        Zeroizing::new(plaintext.to_vec())
+    } else {
+        return Err(CryptoError::SerializationError(
+            "recipient does not support associated data".into(),
+        ));
+    };
+
+    let tx = write_transaction(conn)?;
 
     let mut session_store = CryptoStore::new(conn);
     let mut identity_store = CryptoStore::new(conn);
@@ -81,7 +226,8 @@ pub fn encrypt_message(
     ))
     .map_err(|e| CryptoError::SignalProtocolError(e.to_string()))?;
 
-    if session.is_none() {
+    // An archived session has no current state and cannot send
+    if !session.is_some_and(|record| record.session_version().is_ok()) {
         return Err(CryptoError::SessionNotFound {
             address: recipient.name().to_string(),
         });
@@ -89,7 +235,7 @@ pub fn encrypt_message(
 
     let now = std::time::SystemTime::now();
     let ciphertext_message = futures::executor::block_on(libsignal_protocol::message_encrypt(
-        plaintext,
+        &padded,
         recipient,
         &mut session_store,
         &mut identity_store,
@@ -111,6 +257,11 @@ pub fn encrypt_message(
     let result = EncryptedMessage {
         ciphertext: ciphertext_message.serialize().to_vec(),
         message_type,
+        envelope_version: if framed {
+            ENVELOPE_VERSION
+        } else {
+            BARE_ENVELOPE_VERSION
+        },
     };
 
     tx.commit()?;
@@ -124,18 +275,78 @@ pub fn encrypt_message(
 ///
 /// On session corruption, attempts auto-recovery (deletes the session) and
 /// returns `CryptoError::SessionCorrupted` so the caller can re-establish.
+///
+/// Applies the default `SkippedKeyLimits` to the sender's session. Expects a
+/// bare plaintext from a sender that predates framing.
 pub fn decrypt_message(
     conn: &Connection,
     sender: &ProtocolAddress,
     ciphertext: &[u8],
     message_type: MessageType,
 ) -> Result<Vec<u8>, CryptoError> {
-    let tx = conn.unchecked_transaction()?;
+    decrypt_message_with_options(
+        conn,
+        sender,
+        ciphertext,
+        message_type,
+        &DecryptOptions::default(),
+    )
+}
+
+/// Decrypt a ciphertext message, enforcing the given skipped-message-key limits.
+///
+/// Behaves like `decrypt_message`. After a successful decrypt, sessions unused
+/// for longer than `limits.max_age_seconds` lose their skipped keys, the
+/// sender's session is trimmed to `limits.max_per_session`, and all stored
+/// keys are trimmed to `limits.max_total`, inside the same transaction.
+pub fn decrypt_message_with_limits(
+    conn: &Connection,
+    sender: &ProtocolAddress,
+    ciphertext: &[u8],
+    message_type: MessageType,
+    limits: &SkippedKeyLimits,
+) -> Result<Vec<u8>, CryptoError> {
+    decrypt_message_with_options(
+        conn,
+        sender,
+        ciphertext,
+        message_type,
+        &DecryptOptions {
+            limits: *limits,
+            ..Default::default()
+        },
+    )
+}
+
+/// Decrypt a ciphertext message, enforcing `options.limits` and checking that
+/// it was bound to `options.associated_data`.
+///
+/// `options.envelope_version` decides whether the plaintext is framed. Returns
+/// `CryptoError::DecryptionFailed` if the message was bound to other
+/// associated data or declares an unsupported version. The session is left
+/// untouched in that case, so the genuine message can still be decrypted in
+/// its own context. Bare messages are accepted only when
+/// `options.associated_data` is empty.
+pub fn decrypt_message_with_options(
+    conn: &Connection,
+    sender: &ProtocolAddress,
+    ciphertext: &[u8],
+    message_type: MessageType,
+    options: &DecryptOptions,
+) -> Result<Vec<u8>, CryptoError> {
+    let tx = write_transaction(conn)?;
 
-    let result = decrypt_inner(conn, sender, ciphertext, message_type);
+    let result = decrypt_inner(conn, sender, ciphertext, message_type)
+        .and_then(|body| unframe(body, options.envelope_version, options.associated_data));
 
     match result {
         Ok(plaintext) => {
+            let device_id: u32 = sender.device_id().into();
+            CryptoStore::new(conn).enforce_skipped_message_key_limits(
+                sender.name(),
+                device_id,
+                &options.limits,
+            )?;
             tx.commit()?;
             Ok(plaintext)
         }
@@ -162,6 +373,140 @@ pub fn decrypt_message(
     }
 }
 
+/// Encrypt a typed `MessageEnvelope` to a remote recipient.
+///
+/// The envelope is serialized to JSON and encrypted with `encrypt_message`.
+/// The caller sends `envelope.kind()` in the clear so the server can apply
+/// per-type size limits.
+pub fn encrypt_envelope(
+    conn: &Connection,
+    recipient: &ProtocolAddress,
+    envelope: &MessageEnvelope,
+) -> Result<EncryptedMessage, CryptoError> {
+    encrypt_envelope_with_options(conn, recipient, envelope, &EncryptOptions::default())
+}
+
+/// Encrypt a typed `MessageEnvelope` with `encrypt_message_with_options`.
+pub fn encrypt_envelope_with_options(
+    conn: &Connection,
+    recipient: &ProtocolAddress,
+    envelope: &MessageEnvelope,
+    options: &EncryptOptions,
+) -> Result<EncryptedMessage, CryptoError> {
+    let plaintext = serde_json::to_vec(envelope)?;
+    encrypt_message_with_options(conn, recipient, &plaintext, options)
+}
+
+/// Decrypt a ciphertext produced by `encrypt_envelope`.
+///
+/// Returns `CryptoError::SerializationError` if the plaintext is not a valid
+/// envelope. The ratchet has still advanced at that point, so the message
+/// cannot be retried.
+pub fn decrypt_envelope(
+    conn: &Connection,
+    sender: &ProtocolAddress,
+    ciphertext: &[u8],
+    message_type: MessageType,
+) -> Result<MessageEnvelope, CryptoError> {
+    decrypt_envelope_with_options(
+        conn,
+        sender,
+        ciphertext,
+        message_type,
+        &DecryptOptions::default(),
+    )
+}
+
+/// Decrypt a ciphertext produced by `encrypt_envelope_with_options`, using
+/// `decrypt_message_with_options`.
+pub fn decrypt_envelope_with_options(
+    conn: &Connection,
+    sender: &ProtocolAddress,
+    ciphertext: &[u8],
+    message_type: MessageType,
+    options: &DecryptOptions,
+) -> Result<MessageEnvelope, CryptoError> {
+    let plaintext = decrypt_message_with_options(conn, sender, ciphertext, message_type, options)?;
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+/// Append the end marker and zero-fill up to the length `policy` requires.
+fn pad(plaintext: &[u8], policy: PaddingPolicy) -> Vec<u8> {
+    let len = policy.padded_len(plaintext.len() + 1);
+    let mut padded = Vec::with_capacity(len);
+    padded.extend_from_slice(plaintext);
+    padded.push(PADDING_MARKER);
+    padded.resize(len, 0);
+    padded
+}
+
+/// Recover the plaintext from a decrypted message body sent with
+/// `envelope_version`.
+///
+/// A framed body must carry its padding, which is stripped, and an
+/// associated-data header matching `aad`. A bare body is returned untouched.
+fn unframe(body: Vec<u8>, envelope_version: u16, aad: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let body = Zeroizing::new(body);
+    if envelope_version < FRAMED_ENVELOPE_VERSION {
+        if aad.is_empty() {
+            return Ok(body.to_vec());
+        }
+        return Err(CryptoError::DecryptionFailed(
+            "message is not bound to associated data".into(),
+        ));
+    }
+    if envelope_version > ENVELOPE_VERSION {
+        return Err(CryptoError::DecryptionFailed(format!(
+            "unsupported envelope version {envelope_version}"
+        )));
+    }
+    let bound = unpad(&body)?;
+    Ok(check_associated_data(bound, aad)?.to_vec())
+}
+
+/// Strip padding added by `pad`, failing if the end marker is missing.
+fn unpad(padded: &[u8]) -> Result<&[u8], CryptoError> {
+    match padded.iter().rposition(|&b| b != 0) {
+        Some(end) if padded[end] == PADDING_MARKER => Ok(&padded[..end]),
+        _ => Err(CryptoError::DecryptionFailed(
+            "message padding has no end marker".into(),
+        )),
+    }
+}
+
+/// Strip the associated-data header, failing if it does not match `aad`.
+fn check_associated_data<'a>(bound: &'a [u8], aad: &[u8]) -> Result<&'a [u8], CryptoError> {
+    if bound.len() < AAD_HEADER_LEN || !bound.starts_with(AAD_MAGIC) {
+        return Err(CryptoError::DecryptionFailed(
+            "message has no associated data header".into(),
+        ));
+    }
+    if bound[AAD_MAGIC.len()] != AAD_VERSION {
+        return Err(CryptoError::DecryptionFailed(format!(
+            "unsupported associated data version {}",
+            bound[AAD_MAGIC.len()]
+        )));
+    }
+    if bound[AAD_MAGIC.len() + 1..AAD_HEADER_LEN] != Sha256::digest(aad)[..] {
+        return Err(CryptoError::DecryptionFailed(
+            "associated data mismatch".into(),
+        ));
+    }
+    Ok(&bound[AAD_HEADER_LEN..])
+}
+
+/// Padmé length for `len`: keeps the top bits of the length and rounds the
+/// remaining low bits up, so only O(log log len) bits of it are revealed.
+fn padme(len: usize) -> usize {
+    if len < 2 {
+        return len;
+    }
+    let exponent = len.ilog2();
+    let exponent_bits = exponent.ilog2() + 1;
+    let mask = (1usize << (exponent - exponent_bits)) - 1;
+    (len + mask) & !mask
+}
+
 /// Inner decrypt logic, separated so the caller can handle transaction + recovery.
 fn decrypt_inner(
     conn: &Connection,
@@ -314,6 +659,221 @@ mod tests {
         assert_eq!(decrypted, b"hello world");
     }
 
+    #[test]
+    fn padded_messages_round_trip_under_every_policy() {
+        let (alice_conn, bob_conn, bob_address, alice_address) = setup_alice_bob_session();
+
+        // Trailing marker and zero bytes in the content must survive
+        let plaintext = b"ends like padding\x80\x00\x00";
+        for padding in [
+            PaddingPolicy::None,
+            PaddingPolicy::Padme,
+            PaddingPolicy::Bucket(160),
+            PaddingPolicy::Bucket(0),
+        ] {
+            let options = EncryptOptions {
+                padding,
+                peer_envelope_version: FRAMED_ENVELOPE_VERSION,
+                ..Default::default()
+            };
+            let encrypted =
+                encrypt_message_with_options(&alice_conn, &bob_address, plaintext, &options)
+                    .unwrap();
+            assert_eq!(encrypted.envelope_version, ENVELOPE_VERSION);
+            let decrypted = decrypt_message_with_options(
+                &bob_conn,
+                &alice_address,
+                &encrypted.ciphertext,
+                encrypted.message_type,
+                &DecryptOptions {
+                    envelope_version: encrypted.envelope_version,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            assert_eq!(decrypted, plaintext, "policy {padding:?}");
+        }
+    }
+
+    #[test]
+    fn bucket_padding_hides_length_differences() {
+        let (alice_conn, _bob_conn, bob_address, _) = setup_alice_bob_session();
+        let options = EncryptOptions {
+            padding: PaddingPolicy::Bucket(160),
+            peer_envelope_version: FRAMED_ENVELOPE_VERSION,
+            ..Default::default()
+        };
+
+        let short =
+            encrypt_message_with_options(&alice_conn, &bob_address, b"hi", &options).unwrap();
+        let long = encrypt_message_with_options(&alice_conn, &bob_address, &[b'a'; 150], &options)
+            .unwrap();
+        assert_eq!(short.ciphertext.len(), long.ciphertext.len());
+    }
+
+    #[test]
+    fn associated_data_must_match_to_decrypt() {
+        let (alice_conn, bob_conn, bob_address, alice_address) = setup_alice_bob_session();
+        let general = message_aad("channel-general", "msg-1", 1);
+        let random = message_aad("channel-random", "msg-1", 1);
+
+        let encrypted = encrypt_message_with_options(
+            &alice_conn,
+            &bob_address,
+            b"for general only",
+            &EncryptOptions {
+                associated_data: &general,
+                peer_envelope_version: FRAMED_ENVELOPE_VERSION,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        // Replayed into another channel, or with no context at all
+        for aad in [random.as_slice(), b""] {
+            let result = decrypt_message_with_options(
+                &bob_conn,
+                &alice_address,
+                &encrypted.ciphertext,
+                encrypted.message_type,
+                &DecryptOptions {
+                    associated_data: aad,
+                    envelope_version: encrypted.envelope_version,
+                    ..Default::default()
+                },
+            );
+            assert!(matches!(result, Err(CryptoError::DecryptionFailed(_))));
+        }
+
+        // The rejected attempts did not consume the message or the session
+        let decrypted = decrypt_message_with_options(
+            &bob_conn,
+            &alice_address,
+            &encrypted.ciphertext,
+            encrypted.message_type,
+            &DecryptOptions {
+                associated_data: &general,
+                envelope_version: encrypted.envelope_version,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(decrypted, b"for general only");
+    }
+
+    #[test]
+    fn unbound_plaintext_is_accepted_only_without_associated_data() {
+        let bare = BARE_ENVELOPE_VERSION;
+        assert_eq!(unframe(b"{}".to_vec(), bare, b"").unwrap(), b"{}");
+        assert!(matches!(
+            unframe(b"{}".to_vec(), bare, b"ctx"),
+            Err(CryptoError::DecryptionFailed(_))
+        ));
+    }
+
+    #[test]
+    fn legacy_plaintext_starting_like_a_header_is_untouched() {
+        let (alice_conn, bob_conn, bob_address, alice_address) = setup_alice_bob_session();
+
+        let mut plaintext = b"ocad".to_vec();
+        plaintext.extend_from_slice(&[AAD_VERSION; AAD_HEADER_LEN]);
+        plaintext.push(PADDING_MARKER);
+        let encrypted = encrypt_message(&alice_conn, &bob_address, &plaintext).unwrap();
+        assert_eq!(encrypted.envelope_version, BARE_ENVELOPE_VERSION);
+        let decrypted = decrypt_message(
+            &bob_conn,
+            &alice_address,
+            &encrypted.ciphertext,
+            encrypted.message_type,
+        )
+        .unwrap();
+        assert_eq!(decrypted, plaintext);
+    }
+
+    #[test]
+    fn associated_data_requires_a_framing_peer() {
+        let (alice_conn, _bob_conn, bob_address, _) = setup_alice_bob_session();
+
+        let result = encrypt_message_with_options(
+            &alice_conn,
+            &bob_address,
+            b"hello",
+            &EncryptOptions {
+                associated_data: b"ctx",
+                ..Default::default()
+            },
+        );
+        assert!(matches!(result, Err(CryptoError::SerializationError(_))));
+    }
+
+    #[test]
+    fn unsupported_envelope_version_is_rejected() {
+        let framed = pad(b"ocad", PaddingPolicy::None);
+        assert!(matches!(
+            unframe(framed, ENVELOPE_VERSION + 1, b""),
+            Err(CryptoError::DecryptionFailed(_))
+        ));
+    }
+
+    #[test]
+    fn legacy_plaintext_ending_like_padding_is_untouched() {
+        let (alice_conn, bob_conn, bob_address, alice_address) = setup_alice_bob_session();
+
+        // "む" is E3 82 80: its last byte is the padding marker
+        let encrypted = encrypt_message(&alice_conn, &bob_address, "む".as_bytes()).unwrap();
+        let decrypted = decrypt_message(
+            &bob_conn,
+            &alice_address,
+            &encrypted.ciphertext,
+            encrypted.message_type,
+        )
+        .unwrap();
+        assert_eq!(decrypted, "む".as_bytes());
+    }
+
+    #[test]
+    fn framed_plaintext_without_padding_marker_is_rejected() {
+        let mut framed = AAD_MAGIC.to_vec();
+        framed.push(AAD_VERSION);
+        framed.extend_from_slice(&Sha256::digest(b""));
+        framed.extend_from_slice(b"no marker\0\0");
+        assert!(matches!(
+            unframe(framed, FRAMED_ENVELOPE_VERSION, b""),
+            Err(CryptoError::DecryptionFailed(_))
+        ));
+    }
+
+    #[test]
+    fn message_aad_fields_do_not_run_together() {
+        assert_ne!(message_aad("ab", "c", 1), message_aad("a", "bc", 1));
+        assert_ne!(message_aad("a", "b", 1), message_aad("a", "b", 2));
+    }
+
+    #[test]
+    fn padding_lengths_follow_policy() {
+        assert_eq!(pad(b"abc", PaddingPolicy::None), b"abc\x80");
+        assert_eq!(pad(b"abc", PaddingPolicy::Bucket(16)).len(), 16);
+        assert_eq!(pad(&[1; 16], PaddingPolicy::Bucket(16)).len(), 32);
+
+        // Padmé examples: lengths keep only their top bits
+        assert_eq!(padme(9), 10);
+        assert_eq!(padme(100), 104);
+        assert_eq!(padme(1000), 1024);
+        for len in 1..5000 {
+            let padded = padme(len);
+            assert!(padded >= len && padded <= len + len / 8 + 1, "len {len}");
+        }
+    }
+
+    #[test]
+    fn unpad_requires_end_marker() {
+        assert!(unpad(b"{\"legacy\":true}").is_err());
+        assert!(unpad(b"").is_err());
+        assert!(unpad(b"\x80\x01").is_err());
+        assert_eq!(unpad(&pad(b"", PaddingPolicy::Padme)).unwrap(), b"");
+        assert_eq!(unpad(b"ab\x80\x80\0").unwrap(), b"ab\x80");
+    }
+
     #[test]
     fn decrypt_prekey_message_establishes_session_on_recipient_side() {
         let (alice_conn, bob_conn, bob_address, alice_address) = setup_alice_bob_session();
@@ -405,6 +965,163 @@ mod tests {
         assert_eq!(d2, b"m2");
     }
 
+    #[test]
+    fn decrypt_message_with_limits_trims_sender_skipped_keys() {
+        let (alice_conn, bob_conn, bob_address, alice_address) = setup_alice_bob_session();
+
+        let messages: Vec<_> = (0..6)
+            .map(|i| {
+                encrypt_message(&alice_conn, &bob_address, format!("m{i}").as_bytes()).unwrap()
+            })
+            .collect();
+
+        // The last message arrives first, so the ratchet stashes keys for the other five
+        let limits = SkippedKeyLimits {
+            max_per_session: 2,
+            ..Default::default()
+        };
+        let last = &messages[5];
+        let decrypted = decrypt_message_with_limits(
+            &bob_conn,
+            &alice_address,
+            &last.ciphertext,
+            last.message_type,
+            &limits,
+        )
+        .unwrap();
+        assert_eq!(decrypted, b"m5");
+
+        let metrics = CryptoStore::new(&bob_conn)
+            .skipped_message_key_metrics()
+            .unwrap();
+        assert_eq!(metrics.stored, 2);
+        assert_eq!(metrics.evicted_total, 3);
+
+        // The oldest keys were evicted; the newest two still decrypt
+        for (i, message) in messages[..5].iter().enumerate() {
+            let result = decrypt_message_with_limits(
+                &bob_conn,
+                &alice_address,
+                &message.ciphertext,
+                message.message_type,
+                &limits,
+            );
+            if i < 3 {
+                assert!(matches!(result, Err(CryptoError::DecryptionFailed(_))));
+            } else {
+                assert_eq!(result.unwrap(), format!("m{i}").as_bytes());
+            }
+        }
+        assert_eq!(
+            CryptoStore::new(&bob_conn)
+                .skipped_message_key_metrics()
+                .unwrap()
+                .stored,
+            0
+        );
+    }
+
+    #[test]
+    fn out_of_order_storm_stays_within_skipped_key_limits() {
+        let (alice_conn, bob_conn, bob_address, alice_address) = setup_alice_bob_session();
+
+        // A second sender, so the global cap has to act across sessions
+        let carol_conn = init_test_db();
+        generate_identity(&carol_conn).unwrap();
+        let bob_bundle = generate_pre_key_bundle(&bob_conn, "bob-user-id").unwrap();
+        let bob_for_carol =
+            create_outgoing_session(&carol_conn, &serde_json::to_vec(&bob_bundle).unwrap())
+                .unwrap();
+        let carol_address = ProtocolAddress::new(
+            "carol-user-id".to_string(),
+            DeviceId::new(1).expect("valid"),
+        );
+
+        let from_alice: Vec<_> = (0..50u8)
+            .map(|i| encrypt_message(&alice_conn, &bob_address, &[i]).unwrap())
+            .collect();
+        let from_carol: Vec<_> = (0..50u8)
+            .map(|i| encrypt_message(&carol_conn, &bob_for_carol, &[i]).unwrap())
+            .collect();
+
+        // Both senders' messages arrive newest first, interleaved
+        let limits = SkippedKeyLimits {
+            max_per_session: 20,
+            max_total: 30,
+            ..Default::default()
+        };
+        let (mut alice_delivered, mut carol_delivered) = (Vec::new(), Vec::new());
+        for (a, c) in from_alice.iter().zip(&from_carol).rev() {
+            for (sender, message, delivered) in [
+                (&alice_address, a, &mut alice_delivered),
+                (&carol_address, c, &mut carol_delivered),
+            ] {
+                if let Ok(plaintext) = decrypt_message_with_limits(
+                    &bob_conn,
+                    sender,
+                    &message.ciphertext,
+                    message.message_type,
+                    &limits,
+                ) {
+                    delivered.push(plaintext[0]);
+                }
+
+                let metrics = CryptoStore::new(&bob_conn)
+                    .skipped_message_key_metrics()
+                    .unwrap();
+                assert!(metrics.largest_session <= limits.max_per_session);
+                assert!(metrics.stored <= limits.max_total);
+            }
+        }
+
+        // Each session was trimmed to its newest 20 keys. Alice's session was
+        // then used least recently when Carol's pushed the total over 30, so it
+        // lost 10 more.
+        assert_eq!(alice_delivered, (39..50).rev().collect::<Vec<u8>>());
+        assert_eq!(carol_delivered, (29..50).rev().collect::<Vec<u8>>());
+
+        let metrics = CryptoStore::new(&bob_conn)
+            .skipped_message_key_metrics()
+            .unwrap();
+        assert_eq!(metrics.stored, 0);
+        assert_eq!(metrics.evicted_total, 29 + 29 + 10);
+    }
+
+    #[test]
+    fn encrypt_then_decrypt_envelope_round_trips_reaction() {
+        use openconv_shared::api::envelope::PayloadKind;
+        use openconv_shared::ids::MessageId;
+
+        let (alice_conn, bob_conn, bob_address, alice_address) = setup_alice_bob_session();
+
+        let envelope = MessageEnvelope::reaction(MessageId::new(), "\u{1f44d}", false);
+        let encrypted = encrypt_envelope(&alice_conn, &bob_address, &envelope).unwrap();
+        let decrypted = decrypt_envelope(
+            &bob_conn,
+            &alice_address,
+            &encrypted.ciphertext,
+            encrypted.message_type,
+        )
+        .unwrap();
+
+        assert_eq!(decrypted, envelope);
+        assert_eq!(decrypted.kind(), PayloadKind::Reaction);
+    }
+
+    #[test]
+    fn decrypt_envelope_rejects_non_envelope_plaintext() {
+        let (alice_conn, bob_conn, bob_address, alice_address) = setup_alice_bob_session();
+
+        let encrypted = encrypt_message(&alice_conn, &bob_address, b"raw bytes").unwrap();
+        let result = decrypt_envelope(
+            &bob_conn,
+            &alice_address,
+            &encrypted.ciphertext,
+            encrypted.message_type,
+        );
+        assert!(matches!(result, Err(CryptoError::SerializationError(_))));
+    }
+
     #[test]
     fn message_from_unknown_sender_fails_with_decryption_error() {
         let bob_conn = init_test_db();
@@ -3,17 +3,24 @@
 //! Generates signed pre-key bundles and one-time pre-keys for X3DH
 //! asynchronous key exchange. Handles upload tracking, replenishment
 //! thresholds, and signed pre-key rotation.
+//!
+//! `maintenance` combines those checks under a `MaintenancePolicy` and
+//! returns an `UploadManifest` of everything the server is missing; after
+//! uploading it, the caller confirms with `mark_manifest_uploaded`.
 
 use libsignal_protocol::{
-    kem, GenericSignedPreKey, KeyPair, KyberPreKeyId, KyberPreKeyRecord, KyberPreKeyStore,
-    PreKeyId, PreKeyRecord, PreKeyStore, SignedPreKeyId, SignedPreKeyRecord, SignedPreKeyStore,
-    Timestamp,
+    kem, GenericSignedPreKey, IdentityKeyPair, KeyPair, KyberPreKeyId, KyberPreKeyRecord,
+    KyberPreKeyStore, PreKeyId, PreKeyRecord, PreKeyStore, SignedPreKeyId, SignedPreKeyRecord,
+    SignedPreKeyStore, Timestamp,
 };
 use rusqlite::Connection;
 
 use crate::error::CryptoError;
 use crate::identity::get_identity;
-use crate::storage::CryptoStore;
+use crate::storage::{write_transaction, CryptoStore};
+
+/// Config key holding the ID of the signed pre-key last confirmed uploaded.
+const UPLOADED_SIGNED_PRE_KEY_ID: &str = "uploaded_signed_pre_key_id";
 
 /// A serialized pre-key bundle containing the identity key, signed pre-key,
 /// and Kyber (PQXDH) pre-key, ready for upload to the server.
@@ -33,12 +40,32 @@ pub struct SerializedPreKeyBundle {
     pub signed_pre_key_signature: Vec<u8>,
     /// The local registration ID (14-bit range: 1..=16380)
     pub registration_id: u32,
-    /// The ID of the Kyber (post-quantum) last-resort pre-key
+    /// The ID of the Kyber (post-quantum) last-resort pre-key. Bundles from
+    /// before PQXDH lack the Kyber fields; they still parse, but
+    /// `create_outgoing_session` rejects them.
+    #[serde(default)]
     pub kyber_pre_key_id: u32,
     /// The Kyber pre-key public key bytes
+    #[serde(default)]
     pub kyber_pre_key: Vec<u8>,
     /// Signature over the Kyber pre-key, created with the identity private key
+    #[serde(default)]
     pub kyber_pre_key_signature: Vec<u8>,
+    /// A one-time Kyber pre-key handed out by the server, used instead of the
+    /// last-resort key when present
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub one_time_kyber_pre_key: Option<SerializedKyberPreKey>,
+}
+
+/// A serialized signed Kyber pre-key for upload to the server.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SerializedKyberPreKey {
+    /// Unique ID for this Kyber pre-key
+    pub key_id: u32,
+    /// The Kyber public key bytes
+    pub public_key: Vec<u8>,
+    /// Signature over the public key, created with the identity private key
+    pub signature: Vec<u8>,
 }
 
 /// A serialized one-time pre-key for upload to the server.
@@ -65,13 +92,7 @@ pub fn generate_pre_key_bundle(
     let identity = get_identity(conn)?;
 
     let mut store = CryptoStore::new(conn);
-    let reg_bytes = store
-        .get_config("registration_id")?
-        .ok_or(CryptoError::IdentityNotInitialized)?;
-    let arr: [u8; 4] = reg_bytes
-        .try_into()
-        .map_err(|_| CryptoError::StorageError("invalid registration_id length".into()))?;
-    let registration_id = u32::from_be_bytes(arr);
+    let registration_id = registration_id(&store)?;
 
     // Determine next signed pre-key ID
     let next_spk_id: u32 = conn.query_row(
@@ -106,30 +127,7 @@ pub fn generate_pre_key_bundle(
     .map_err(|e| CryptoError::SignalProtocolError(e.to_string()))?;
 
     // Generate Kyber (PQXDH) last-resort pre-key
-    let next_kyber_id: u32 = conn.query_row(
-        "SELECT COALESCE(MAX(key_id), 0) + 1 FROM crypto_kyber_pre_keys",
-        [],
-        |row| row.get(0),
-    )?;
-
-    let kyber_record = KyberPreKeyRecord::generate(
-        kem::KeyType::Kyber1024,
-        KyberPreKeyId::from(next_kyber_id),
-        identity.private_key(),
-    )
-    .map_err(|e| CryptoError::SignalProtocolError(e.to_string()))?;
-
-    let kyber_pub = kyber_record
-        .public_key()
-        .map_err(|e| CryptoError::SignalProtocolError(e.to_string()))?;
-    let kyber_sig = kyber_record
-        .signature()
-        .map_err(|e| CryptoError::SignalProtocolError(e.to_string()))?;
-
-    futures::executor::block_on(
-        store.save_kyber_pre_key(KyberPreKeyId::from(next_kyber_id), &kyber_record),
-    )
-    .map_err(|e| CryptoError::SignalProtocolError(e.to_string()))?;
+    let kyber = generate_kyber_pre_key(conn, &mut store, &identity, true)?;
 
     Ok(SerializedPreKeyBundle {
         user_id: user_id.to_string(),
@@ -138,9 +136,10 @@ pub fn generate_pre_key_bundle(
         signed_pre_key: spk_pair.public_key.serialize().to_vec(),
         signed_pre_key_signature: Vec::from(signature.as_ref()),
         registration_id,
-        kyber_pre_key_id: next_kyber_id,
-        kyber_pre_key: kyber_pub.serialize().to_vec(),
-        kyber_pre_key_signature: kyber_sig.to_vec(),
+        kyber_pre_key_id: kyber.key_id,
+        kyber_pre_key: kyber.public_key,
+        kyber_pre_key_signature: kyber.signature,
+        one_time_kyber_pre_key: None,
     })
 }
 
@@ -152,7 +151,7 @@ pub fn generate_one_time_pre_keys(
     conn: &Connection,
     count: u32,
 ) -> Result<Vec<SerializedPreKey>, CryptoError> {
-    let tx = conn.unchecked_transaction()?;
+    let tx = write_transaction(conn)?;
 
     let start_id: u32 = conn.query_row(
         "SELECT COALESCE(MAX(key_id), 0) + 1 FROM crypto_pre_keys",
@@ -181,11 +180,73 @@ pub fn generate_one_time_pre_keys(
     Ok(keys)
 }
 
+/// Generate a batch of one-time Kyber pre-keys for upload to the server.
+///
+/// Unlike the last-resort Kyber key in the bundle, each of these is deleted
+/// once a session has been established with it.
+pub fn generate_one_time_kyber_pre_keys(
+    conn: &Connection,
+    count: u32,
+) -> Result<Vec<SerializedKyberPreKey>, CryptoError> {
+    let identity = get_identity(conn)?;
+    let tx = write_transaction(conn)?;
+    let mut store = CryptoStore::new(conn);
+
+    let keys = (0..count)
+        .map(|_| generate_kyber_pre_key(conn, &mut store, &identity, false))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    tx.commit()?;
+    Ok(keys)
+}
+
+/// Generate, sign and store a Kyber pre-key with the next free ID.
+fn generate_kyber_pre_key(
+    conn: &Connection,
+    store: &mut CryptoStore,
+    identity: &IdentityKeyPair,
+    last_resort: bool,
+) -> Result<SerializedKyberPreKey, CryptoError> {
+    let key_id: u32 = conn.query_row(
+        "SELECT COALESCE(MAX(key_id), 0) + 1 FROM crypto_kyber_pre_keys",
+        [],
+        |row| row.get(0),
+    )?;
+
+    let record = KyberPreKeyRecord::generate(
+        kem::KeyType::Kyber1024,
+        KyberPreKeyId::from(key_id),
+        identity.private_key(),
+    )
+    .map_err(|e| CryptoError::SignalProtocolError(e.to_string()))?;
+
+    let public_key = record
+        .public_key()
+        .map_err(|e| CryptoError::SignalProtocolError(e.to_string()))?;
+    let signature = record
+        .signature()
+        .map_err(|e| CryptoError::SignalProtocolError(e.to_string()))?;
+
+    futures::executor::block_on(store.save_kyber_pre_key(KyberPreKeyId::from(key_id), &record))
+        .map_err(|e| CryptoError::SignalProtocolError(e.to_string()))?;
+    // Last-resort keys travel in the bundle; one-time keys await upload
+    conn.execute(
+        "UPDATE crypto_kyber_pre_keys SET last_resort = ?2, uploaded = ?2 WHERE key_id = ?1",
+        rusqlite::params![key_id, last_resort],
+    )?;
+
+    Ok(SerializedKyberPreKey {
+        key_id,
+        public_key: public_key.serialize().to_vec(),
+        signature: signature.to_vec(),
+    })
+}
+
 /// Mark one-time pre-keys as uploaded to the server.
 ///
 /// Sets the `uploaded` flag to 1 for each key ID in the provided slice.
 pub fn mark_pre_keys_uploaded(conn: &Connection, key_ids: &[u32]) -> Result<(), CryptoError> {
-    let tx = conn.unchecked_transaction()?;
+    let tx = write_transaction(conn)?;
     {
         let mut stmt = conn.prepare("UPDATE crypto_pre_keys SET uploaded = 1 WHERE key_id = ?1")?;
         for &id in key_ids {
@@ -196,6 +257,20 @@ pub fn mark_pre_keys_uploaded(conn: &Connection, key_ids: &[u32]) -> Result<(),
     Ok(())
 }
 
+/// Mark one-time Kyber pre-keys as uploaded to the server.
+pub fn mark_kyber_pre_keys_uploaded(conn: &Connection, key_ids: &[u32]) -> Result<(), CryptoError> {
+    let tx = write_transaction(conn)?;
+    {
+        let mut stmt =
+            conn.prepare("UPDATE crypto_kyber_pre_keys SET uploaded = 1 WHERE key_id = ?1")?;
+        for &id in key_ids {
+            stmt.execute([id])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
 /// Check if the number of uploaded pre-keys is below the replenishment threshold.
 ///
 /// Returns `true` if fewer than `threshold` pre-keys have been uploaded,
@@ -248,6 +323,219 @@ pub fn is_signed_pre_key_stale(conn: &Connection, max_age_days: u32) -> Result<b
     }
 }
 
+/// Thresholds and batch sizes applied by `maintenance`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaintenancePolicy {
+    /// Refill when fewer one-time pre-keys than this are stored, whether
+    /// uploaded or still awaiting upload.
+    pub min_pre_keys: u32,
+    /// One-time pre-keys generated per refill.
+    pub pre_key_batch: u32,
+    /// Refill threshold for one-time Kyber pre-keys.
+    pub min_kyber_pre_keys: u32,
+    /// One-time Kyber pre-keys generated per refill.
+    pub kyber_pre_key_batch: u32,
+    /// Rotate the signed pre-key once it is older than this.
+    pub signed_pre_key_max_age_days: u32,
+}
+
+impl Default for MaintenancePolicy {
+    fn default() -> Self {
+        Self {
+            min_pre_keys: 20,
+            pre_key_batch: 100,
+            min_kyber_pre_keys: 10,
+            kyber_pre_key_batch: 40,
+            signed_pre_key_max_age_days: 7,
+        }
+    }
+}
+
+/// Key material produced by `maintenance` that the server does not have yet.
+#[derive(Debug, Clone, Default)]
+pub struct UploadManifest {
+    /// The current bundle, if its signed pre-key has not been confirmed
+    /// uploaded (e.g. after a rotation).
+    pub bundle: Option<SerializedPreKeyBundle>,
+    /// One-time pre-keys awaiting upload.
+    pub pre_keys: Vec<SerializedPreKey>,
+    /// One-time Kyber pre-keys awaiting upload.
+    pub kyber_pre_keys: Vec<SerializedKyberPreKey>,
+}
+
+impl UploadManifest {
+    /// Whether there is nothing to upload.
+    pub fn is_empty(&self) -> bool {
+        self.bundle.is_none() && self.pre_keys.is_empty() && self.kyber_pre_keys.is_empty()
+    }
+}
+
+/// Bring local pre-keys in line with `policy` and list what needs uploading.
+///
+/// Rotates a stale signed pre-key and generates a refill batch for each kind
+/// of one-time pre-key that has fallen below its threshold. The manifest
+/// also repeats anything generated earlier whose upload was never
+/// confirmed, so a failed upload is simply retried on the next run. A bundle
+/// uploaded without `mark_manifest_uploaded` (e.g. at registration) is
+/// included once more.
+pub fn maintenance(
+    conn: &Connection,
+    user_id: &str,
+    policy: &MaintenancePolicy,
+) -> Result<UploadManifest, CryptoError> {
+    if is_signed_pre_key_stale(conn, policy.signed_pre_key_max_age_days)? {
+        rotate_signed_pre_key(conn, user_id)?;
+    }
+
+    let pre_keys: u32 =
+        conn.query_row("SELECT COUNT(*) FROM crypto_pre_keys", [], |row| row.get(0))?;
+    if pre_keys < policy.min_pre_keys {
+        generate_one_time_pre_keys(conn, policy.pre_key_batch)?;
+    }
+
+    let kyber_pre_keys: u32 = conn.query_row(
+        "SELECT COUNT(*) FROM crypto_kyber_pre_keys WHERE last_resort = 0",
+        [],
+        |row| row.get(0),
+    )?;
+    if kyber_pre_keys < policy.min_kyber_pre_keys {
+        generate_one_time_kyber_pre_keys(conn, policy.kyber_pre_key_batch)?;
+    }
+
+    let store = CryptoStore::new(conn);
+    let latest_signed_pre_key_id: u32 = conn.query_row(
+        "SELECT MAX(key_id) FROM crypto_signed_pre_keys",
+        [],
+        |row| row.get(0),
+    )?;
+    let uploaded_signed_pre_key_id = store
+        .get_config(UPLOADED_SIGNED_PRE_KEY_ID)?
+        .and_then(|bytes| <[u8; 4]>::try_from(bytes).ok())
+        .map(u32::from_be_bytes);
+    let bundle = if uploaded_signed_pre_key_id == Some(latest_signed_pre_key_id) {
+        None
+    } else {
+        Some(current_bundle(conn, user_id, latest_signed_pre_key_id)?)
+    };
+
+    Ok(UploadManifest {
+        bundle,
+        pre_keys: pending_pre_keys(conn)?,
+        kyber_pre_keys: pending_kyber_pre_keys(conn)?,
+    })
+}
+
+/// Record that everything in `manifest` reached the server.
+pub fn mark_manifest_uploaded(
+    conn: &Connection,
+    manifest: &UploadManifest,
+) -> Result<(), CryptoError> {
+    let pre_key_ids: Vec<u32> = manifest.pre_keys.iter().map(|key| key.key_id).collect();
+    let kyber_pre_key_ids: Vec<u32> = manifest
+        .kyber_pre_keys
+        .iter()
+        .map(|key| key.key_id)
+        .collect();
+
+    let tx = write_transaction(conn)?;
+    mark_pre_keys_uploaded(conn, &pre_key_ids)?;
+    mark_kyber_pre_keys_uploaded(conn, &kyber_pre_key_ids)?;
+    if let Some(bundle) = &manifest.bundle {
+        CryptoStore::new(conn).store_config(
+            UPLOADED_SIGNED_PRE_KEY_ID,
+            &bundle.signed_pre_key_id.to_be_bytes(),
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+fn registration_id(store: &CryptoStore) -> Result<u32, CryptoError> {
+    let reg_bytes = store
+        .get_config("registration_id")?
+        .ok_or(CryptoError::IdentityNotInitialized)?;
+    let arr: [u8; 4] = reg_bytes
+        .try_into()
+        .map_err(|_| CryptoError::StorageError("invalid registration_id length".into()))?;
+    Ok(u32::from_be_bytes(arr))
+}
+
+/// Rebuild the bundle for an already stored signed pre-key and the newest
+/// last-resort Kyber pre-key.
+fn current_bundle(
+    conn: &Connection,
+    user_id: &str,
+    signed_pre_key_id: u32,
+) -> Result<SerializedPreKeyBundle, CryptoError> {
+    let identity = get_identity(conn)?;
+    let store = CryptoStore::new(conn);
+    let signed = futures::executor::block_on(
+        store.get_signed_pre_key(SignedPreKeyId::from(signed_pre_key_id)),
+    )?;
+    let kyber_pre_key_id: u32 = conn.query_row(
+        "SELECT MAX(key_id) FROM crypto_kyber_pre_keys WHERE last_resort = 1",
+        [],
+        |row| row.get(0),
+    )?;
+    let kyber = futures::executor::block_on(
+        store.get_kyber_pre_key(KyberPreKeyId::from(kyber_pre_key_id)),
+    )?;
+
+    Ok(SerializedPreKeyBundle {
+        user_id: user_id.to_string(),
+        identity_key: identity.public_key().serialize().to_vec(),
+        signed_pre_key_id,
+        signed_pre_key: signed.public_key()?.serialize().to_vec(),
+        signed_pre_key_signature: signed.signature()?.to_vec(),
+        registration_id: registration_id(&store)?,
+        kyber_pre_key_id,
+        kyber_pre_key: kyber.public_key()?.serialize().to_vec(),
+        kyber_pre_key_signature: kyber.signature()?.to_vec(),
+        one_time_kyber_pre_key: None,
+    })
+}
+
+fn pending_pre_keys(conn: &Connection) -> Result<Vec<SerializedPreKey>, CryptoError> {
+    let mut stmt = conn
+        .prepare("SELECT key_id, record FROM crypto_pre_keys WHERE uploaded = 0 ORDER BY key_id")?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, u32>(0)?, row.get::<_, Vec<u8>>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    rows.into_iter()
+        .map(|(key_id, record)| {
+            let record = PreKeyRecord::deserialize(&record)?;
+            Ok(SerializedPreKey {
+                key_id,
+                public_key: record.public_key()?.serialize().to_vec(),
+            })
+        })
+        .collect()
+}
+
+fn pending_kyber_pre_keys(conn: &Connection) -> Result<Vec<SerializedKyberPreKey>, CryptoError> {
+    let mut stmt = conn.prepare(
+        "SELECT key_id, record FROM crypto_kyber_pre_keys
+         WHERE last_resort = 0 AND uploaded = 0 ORDER BY key_id",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, u32>(0)?, row.get::<_, Vec<u8>>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    rows.into_iter()
+        .map(|(key_id, record)| {
+            let record = KyberPreKeyRecord::deserialize(&record)?;
+            Ok(SerializedKyberPreKey {
+                key_id,
+                public_key: record.public_key()?.serialize().to_vec(),
+                signature: record.signature()?.to_vec(),
+            })
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -466,4 +754,150 @@ mod tests {
 
         assert!(is_signed_pre_key_stale(&conn, 7).unwrap());
     }
+
+    #[test]
+    fn generate_one_time_kyber_pre_keys_are_signed_and_not_last_resort() {
+        let conn = init_test_db();
+        let identity = generate_identity(&conn).unwrap();
+        let bundle = generate_pre_key_bundle(&conn, "test-user-id").unwrap();
+
+        let keys = generate_one_time_kyber_pre_keys(&conn, 3).unwrap();
+        assert_eq!(keys.len(), 3);
+        for key in &keys {
+            assert_ne!(key.key_id, bundle.kyber_pre_key_id);
+            assert!(identity
+                .identity_key()
+                .public_key()
+                .verify_signature(&key.public_key, &key.signature));
+        }
+
+        let last_resort: Vec<(u32, bool)> = {
+            let mut stmt = conn
+                .prepare("SELECT key_id, last_resort FROM crypto_kyber_pre_keys ORDER BY key_id")
+                .unwrap();
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap()
+        };
+        assert_eq!(last_resort.len(), 4);
+        for (key_id, is_last_resort) in last_resort {
+            assert_eq!(is_last_resort, key_id == bundle.kyber_pre_key_id);
+        }
+    }
+
+    #[test]
+    fn bundle_without_one_time_kyber_key_omits_the_field() {
+        let conn = init_test_db();
+        generate_identity(&conn).unwrap();
+        let bundle = generate_pre_key_bundle(&conn, "test-user-id").unwrap();
+
+        let json = serde_json::to_value(&bundle).unwrap();
+        assert!(json.get("one_time_kyber_pre_key").is_none());
+        let parsed: SerializedPreKeyBundle = serde_json::from_value(json).unwrap();
+        assert!(parsed.one_time_kyber_pre_key.is_none());
+    }
+
+    #[test]
+    fn maintenance_on_fresh_identity_produces_full_manifest() {
+        let conn = init_test_db();
+        generate_identity(&conn).unwrap();
+        let policy = MaintenancePolicy::default();
+
+        let manifest = maintenance(&conn, "test-user-id", &policy).unwrap();
+        assert!(manifest.bundle.is_some());
+        assert_eq!(manifest.pre_keys.len(), policy.pre_key_batch as usize);
+        assert_eq!(
+            manifest.kyber_pre_keys.len(),
+            policy.kyber_pre_key_batch as usize
+        );
+
+        mark_manifest_uploaded(&conn, &manifest).unwrap();
+        assert!(maintenance(&conn, "test-user-id", &policy)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn maintenance_repeats_unconfirmed_uploads_without_new_batches() {
+        let conn = init_test_db();
+        generate_identity(&conn).unwrap();
+        let policy = MaintenancePolicy::default();
+
+        let first = maintenance(&conn, "test-user-id", &policy).unwrap();
+        let second = maintenance(&conn, "test-user-id", &policy).unwrap();
+        let ids = |m: &UploadManifest| m.pre_keys.iter().map(|k| k.key_id).collect::<Vec<_>>();
+        assert_eq!(ids(&first), ids(&second));
+        assert_eq!(
+            first.bundle.unwrap().signed_pre_key_id,
+            second.bundle.unwrap().signed_pre_key_id
+        );
+        assert_eq!(first.kyber_pre_keys.len(), second.kyber_pre_keys.len());
+    }
+
+    #[test]
+    fn maintenance_refills_below_threshold() {
+        let conn = init_test_db();
+        generate_identity(&conn).unwrap();
+        let policy = MaintenancePolicy {
+            min_pre_keys: 5,
+            pre_key_batch: 10,
+            min_kyber_pre_keys: 5,
+            kyber_pre_key_batch: 10,
+            ..MaintenancePolicy::default()
+        };
+        let manifest = maintenance(&conn, "test-user-id", &policy).unwrap();
+        mark_manifest_uploaded(&conn, &manifest).unwrap();
+
+        // Simulate peers consuming most of the uploaded keys
+        conn.execute(
+            "DELETE FROM crypto_pre_keys WHERE key_id IN (SELECT key_id FROM crypto_pre_keys LIMIT 7)",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "DELETE FROM crypto_kyber_pre_keys WHERE key_id IN
+             (SELECT key_id FROM crypto_kyber_pre_keys WHERE last_resort = 0 LIMIT 4)",
+            [],
+        )
+        .unwrap();
+
+        let refill = maintenance(&conn, "test-user-id", &policy).unwrap();
+        assert!(refill.bundle.is_none());
+        assert_eq!(refill.pre_keys.len(), 10);
+        assert!(refill.kyber_pre_keys.is_empty());
+    }
+
+    #[test]
+    fn maintenance_rotates_stale_signed_pre_key() {
+        let conn = init_test_db();
+        generate_identity(&conn).unwrap();
+        let policy = MaintenancePolicy::default();
+        let manifest = maintenance(&conn, "test-user-id", &policy).unwrap();
+        let old_id = manifest.bundle.as_ref().unwrap().signed_pre_key_id;
+        mark_manifest_uploaded(&conn, &manifest).unwrap();
+
+        conn.execute("UPDATE crypto_signed_pre_keys SET created_at = 0", [])
+            .unwrap();
+
+        let rotated = maintenance(&conn, "test-user-id", &policy).unwrap();
+        let bundle = rotated.bundle.expect("rotation should produce a bundle");
+        assert_ne!(bundle.signed_pre_key_id, old_id);
+        assert!(rotated.pre_keys.is_empty());
+    }
+
+    #[test]
+    fn manifest_bundle_establishes_a_session() {
+        let alice_conn = init_test_db();
+        let bob_conn = init_test_db();
+        generate_identity(&alice_conn).unwrap();
+        generate_identity(&bob_conn).unwrap();
+        let manifest =
+            maintenance(&bob_conn, "bob-user-id", &MaintenancePolicy::default()).unwrap();
+
+        // The manifest bundle is rebuilt from stored records
+        let bundle = manifest.bundle.unwrap();
+        crate::session::create_outgoing_session(&alice_conn, &serde_json::to_vec(&bundle).unwrap())
+            .unwrap();
+    }
 }
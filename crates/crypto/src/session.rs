@@ -1,17 +1,18 @@
 //! Signal protocol session management.
 //!
-//! Provides X3DH/PQXDH-based outgoing session creation, session recovery
-//! on corruption, and skipped message key pruning.
+//! Provides X3DH/PQXDH-based outgoing session creation, session listing,
+//! archiving and reset, recovery on corruption, and skipped message key
+//! pruning.
 
 use libsignal_protocol::{
     kem, DeviceId, IdentityKey, KyberPreKeyId, PreKeyBundle, ProtocolAddress, PublicKey,
-    SignedPreKeyId,
+    SessionRecord, SessionStore, SignedPreKeyId,
 };
 use rusqlite::Connection;
 
 use crate::error::CryptoError;
 use crate::prekeys::SerializedPreKeyBundle;
-use crate::storage::CryptoStore;
+use crate::storage::{write_transaction, CryptoStore};
 
 /// Describes the result of a session recovery attempt.
 #[derive(Debug, PartialEq)]
@@ -27,6 +28,8 @@ pub enum RecoveryAction {
 ///
 /// The `remote_bundle` is JSON-serialized `SerializedPreKeyBundle` data from the server.
 /// The bundle's `user_id` field (server-assigned UUID) is used as the address name.
+/// The session is established with PQXDH, using the bundle's one-time Kyber
+/// pre-key if it has one and its last-resort Kyber pre-key otherwise.
 ///
 /// Returns the `ProtocolAddress` for subsequent message encryption calls.
 pub fn create_outgoing_session(
@@ -41,7 +44,23 @@ pub fn create_outgoing_session(
     let signed_pre_key_public = PublicKey::deserialize(&bundle.signed_pre_key)
         .map_err(|e| CryptoError::InvalidKey(e.to_string()))?;
 
-    let kyber_pre_key_public = kem::PublicKey::deserialize(&bundle.kyber_pre_key)
+    // Prefer a one-time Kyber pre-key over the last-resort one. libsignal
+    // only supports PQXDH, so a bundle without any Kyber key is rejected.
+    let (kyber_pre_key_id, kyber_pre_key, kyber_pre_key_signature) =
+        match &bundle.one_time_kyber_pre_key {
+            Some(key) => (key.key_id, &key.public_key, &key.signature),
+            None => (
+                bundle.kyber_pre_key_id,
+                &bundle.kyber_pre_key,
+                &bundle.kyber_pre_key_signature,
+            ),
+        };
+    if kyber_pre_key.is_empty() {
+        return Err(CryptoError::InvalidKey(
+            "pre-key bundle has no kyber pre-key; PQXDH is required".into(),
+        ));
+    }
+    let kyber_pre_key_public = kem::PublicKey::deserialize(kyber_pre_key)
         .map_err(|e| CryptoError::InvalidKey(format!("invalid kyber key: {e}")))?;
 
     let pre_key_bundle = PreKeyBundle::new(
@@ -51,9 +70,9 @@ pub fn create_outgoing_session(
         SignedPreKeyId::from(bundle.signed_pre_key_id),
         signed_pre_key_public,
         bundle.signed_pre_key_signature.clone(),
-        KyberPreKeyId::from(bundle.kyber_pre_key_id),
+        KyberPreKeyId::from(kyber_pre_key_id),
         kyber_pre_key_public,
-        bundle.kyber_pre_key_signature.clone(),
+        kyber_pre_key_signature.clone(),
         identity_key,
     )
     .map_err(|e| CryptoError::SignalProtocolError(e.to_string()))?;
@@ -61,7 +80,7 @@ pub fn create_outgoing_session(
     let remote_address = ProtocolAddress::new(bundle.user_id, DeviceId::new(1).expect("valid"));
 
     // Wrap in transaction so identity save + session save are atomic
-    let tx = conn.unchecked_transaction()?;
+    let tx = write_transaction(conn)?;
 
     let mut session_store = CryptoStore::new(conn);
     let mut identity_store = CryptoStore::new(conn);
@@ -90,29 +109,90 @@ pub fn recover_session(
     conn: &Connection,
     address: &ProtocolAddress,
 ) -> Result<RecoveryAction, CryptoError> {
-    let tx = conn.unchecked_transaction()?;
+    reset(conn, address)?;
+    Ok(RecoveryAction::SessionReset)
+}
 
-    let addr_name = address.name();
-    let device_id: u32 = address.device_id().into();
+/// Summary of a stored session, for session management UIs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionInfo {
+    /// Address name of the remote party (their user ID)
+    pub address: String,
+    /// Remote device ID
+    pub device_id: u32,
+    /// Whether the session can send. `false` once archived; archived
+    /// sessions can still decrypt messages that were already in flight.
+    pub active: bool,
+    /// Unix timestamp (seconds) when the session was created
+    pub created_at: i64,
+    /// Unix timestamp (seconds) when the session was last stored
+    pub last_used_at: i64,
+}
 
-    conn.execute(
-        "DELETE FROM crypto_sessions WHERE address = ?1 AND device_id = ?2",
-        rusqlite::params![addr_name, device_id],
-    )?;
+/// List all stored sessions, most recently used first.
+pub fn list_sessions(conn: &Connection) -> Result<Vec<SessionInfo>, CryptoError> {
+    CryptoStore::new(conn)
+        .list_sessions()?
+        .into_iter()
+        .map(
+            |(address, device_id, session_data, created_at, last_used_at)| {
+                let record = SessionRecord::deserialize(&session_data)?;
+                Ok(SessionInfo {
+                    address,
+                    device_id,
+                    active: record.session_version().is_ok(),
+                    created_at,
+                    last_used_at,
+                })
+            },
+        )
+        .collect()
+}
 
-    conn.execute(
-        "DELETE FROM crypto_skipped_message_keys WHERE session_address = ?1 AND session_device_id = ?2",
-        rusqlite::params![addr_name, device_id],
-    )?;
+/// Archive the current session with `address`.
+///
+/// Its ratchet state is kept so messages already in flight still decrypt,
+/// but nothing more can be sent on it: `message::encrypt_message` returns
+/// `CryptoError::SessionNotFound` until a new session is created, either by
+/// `create_outgoing_session` or by an incoming pre-key message.
+///
+/// Returns `CryptoError::SessionNotFound` if there is no session.
+pub fn archive(conn: &Connection, address: &ProtocolAddress) -> Result<(), CryptoError> {
+    let tx = write_transaction(conn)?;
+    let mut store = CryptoStore::new(conn);
+
+    let mut record =
+        futures::executor::block_on(store.load_session(address))?.ok_or_else(|| {
+            CryptoError::SessionNotFound {
+                address: address.name().to_string(),
+            }
+        })?;
+    record.archive_current_state()?;
+    futures::executor::block_on(store.store_session(address, &record))?;
 
     tx.commit()?;
-    Ok(RecoveryAction::SessionReset)
+    Ok(())
 }
 
-/// Delete skipped message keys older than `max_age_seconds`.
+/// Delete the session with `address`, including its skipped message keys.
 ///
-/// Returns the number of entries deleted. Recommended to call on app startup
-/// with `max_age_seconds = 604800` (7 days).
+/// Use after a "bad MAC" desync: the next send needs a fresh pre-key bundle
+/// and `create_outgoing_session`. Messages encrypted under the deleted
+/// session can no longer be decrypted. Succeeds if there was no session.
+pub fn reset(conn: &Connection, address: &ProtocolAddress) -> Result<(), CryptoError> {
+    let tx = write_transaction(conn)?;
+    CryptoStore::new(conn).delete_session(address.name(), address.device_id().into())?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Delete the skipped message keys of sessions unused for longer than
+/// `max_age_seconds`.
+///
+/// Returns the number of keys deleted. Recommended to call on app startup
+/// with `SkippedKeyLimits::default().max_age_seconds` (7 days). Per-session
+/// and global caps are enforced on every decrypt; see
+/// `decrypt_message_with_limits`.
 pub fn prune_old_skipped_keys(conn: &Connection, max_age_seconds: u64) -> Result<u32, CryptoError> {
     let store = CryptoStore::new(conn);
     store.prune_skipped_message_keys(max_age_seconds)
@@ -210,40 +290,12 @@ mod tests {
 
     #[test]
     fn recover_session_deletes_associated_skipped_message_keys() {
-        let alice_conn = init_test_db();
-        let bob_conn = init_test_db();
+        let (alice_conn, bob_conn, bob, alice) = setup_conversation();
+        stash_skipped_keys(&bob_conn, &alice_conn, &alice, &bob, 2);
 
-        generate_identity(&alice_conn).unwrap();
-        generate_identity(&bob_conn).unwrap();
+        recover_session(&alice_conn, &bob).unwrap();
 
-        let bob_bundle = generate_pre_key_bundle(&bob_conn, "bob-user-id").unwrap();
-        let bundle_json = serde_json::to_vec(&bob_bundle).unwrap();
-
-        let address = create_outgoing_session(&alice_conn, &bundle_json).unwrap();
-
-        // Insert dummy skipped message keys
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
-        alice_conn
-            .execute(
-                "INSERT INTO crypto_skipped_message_keys (session_address, session_device_id, ratchet_key, message_number, message_key, created_at)
-                 VALUES (?1, ?2, X'AA', 1, X'BB', ?3)",
-                rusqlite::params![address.name(), u32::from(address.device_id()), now],
-            )
-            .unwrap();
-
-        recover_session(&alice_conn, &address).unwrap();
-
-        let skipped_count: u32 = alice_conn
-            .query_row(
-                "SELECT COUNT(*) FROM crypto_skipped_message_keys",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap();
-        assert_eq!(skipped_count, 0);
+        assert_eq!(skipped_keys_stored(&alice_conn), 0);
     }
 
     #[test]
@@ -264,97 +316,44 @@ mod tests {
 
     #[test]
     fn prune_old_skipped_keys_deletes_old_entries() {
-        let conn = init_test_db();
-
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
-
-        let thirty_days_ago = now - (30 * 86400);
-
-        conn.execute(
-            "INSERT INTO crypto_skipped_message_keys (session_address, session_device_id, ratchet_key, message_number, message_key, created_at)
-             VALUES ('addr', 1, X'AA', 1, X'BB', ?1)",
-            [thirty_days_ago],
-        )
-        .unwrap();
+        let (alice_conn, bob_conn, bob, alice) = setup_conversation();
+        stash_skipped_keys(&bob_conn, &alice_conn, &alice, &bob, 1);
+        age_session(&alice_conn, &bob, 30 * 86400);
 
-        let deleted = prune_old_skipped_keys(&conn, 7 * 86400).unwrap();
+        let deleted = prune_old_skipped_keys(&alice_conn, 7 * 86400).unwrap();
         assert_eq!(deleted, 1);
     }
 
     #[test]
     fn prune_old_skipped_keys_keeps_recent_entries() {
-        let conn = init_test_db();
-
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
-
-        conn.execute(
-            "INSERT INTO crypto_skipped_message_keys (session_address, session_device_id, ratchet_key, message_number, message_key, created_at)
-             VALUES ('addr', 1, X'AA', 1, X'BB', ?1)",
-            [now],
-        )
-        .unwrap();
+        let (alice_conn, bob_conn, bob, alice) = setup_conversation();
+        stash_skipped_keys(&bob_conn, &alice_conn, &alice, &bob, 1);
 
-        let deleted = prune_old_skipped_keys(&conn, 7 * 86400).unwrap();
+        let deleted = prune_old_skipped_keys(&alice_conn, 7 * 86400).unwrap();
         assert_eq!(deleted, 0);
 
-        let remaining: u32 = conn
-            .query_row(
-                "SELECT COUNT(*) FROM crypto_skipped_message_keys",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap();
-        assert_eq!(remaining, 1);
+        assert_eq!(skipped_keys_stored(&alice_conn), 1);
     }
 
     #[test]
     fn prune_old_skipped_keys_returns_correct_count() {
-        let conn = init_test_db();
-
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
-
-        let old = now - (30 * 86400);
-
-        // 3 old entries
-        for i in 1..=3 {
-            conn.execute(
-                "INSERT INTO crypto_skipped_message_keys (session_address, session_device_id, ratchet_key, message_number, message_key, created_at)
-                 VALUES ('addr', 1, X'AA', ?1, X'BB', ?2)",
-                rusqlite::params![i, old],
-            )
-            .unwrap();
-        }
-
-        // 2 recent entries
-        for i in 4..=5 {
-            conn.execute(
-                "INSERT INTO crypto_skipped_message_keys (session_address, session_device_id, ratchet_key, message_number, message_key, created_at)
-                 VALUES ('addr', 1, X'AA', ?1, X'BB', ?2)",
-                rusqlite::params![i, now],
-            )
-            .unwrap();
-        }
-
-        let deleted = prune_old_skipped_keys(&conn, 7 * 86400).unwrap();
+        let (alice_conn, bob_conn, bob, alice) = setup_conversation();
+
+        // 3 keys in a session unused for 30 days, 2 in one used just now
+        stash_skipped_keys(&alice_conn, &bob_conn, &bob, &alice, 3);
+        let carol_conn = init_test_db();
+        generate_identity(&carol_conn).unwrap();
+        let bundle = generate_pre_key_bundle(&bob_conn, "bob-user-id").unwrap();
+        let bob_for_carol =
+            create_outgoing_session(&carol_conn, &serde_json::to_vec(&bundle).unwrap()).unwrap();
+        let carol = ProtocolAddress::new("carol-user-id".into(), DeviceId::new(1).unwrap());
+        stash_skipped_keys(&carol_conn, &bob_conn, &bob_for_carol, &carol, 2);
+        age_session(&bob_conn, &alice, 30 * 86400);
+
+        let deleted = prune_old_skipped_keys(&bob_conn, 7 * 86400).unwrap();
         assert_eq!(deleted, 3);
 
-        let remaining: u32 = conn
-            .query_row(
-                "SELECT COUNT(*) FROM crypto_skipped_message_keys",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap();
-        assert_eq!(remaining, 2);
+        assert_eq!(skipped_keys_stored(&bob_conn), 2);
     }
 
     #[test]
@@ -423,4 +422,199 @@ mod tests {
         let action = recover_session(&conn, &address).unwrap();
         assert_eq!(action, RecoveryAction::SessionReset);
     }
+
+    #[test]
+    fn one_time_kyber_pre_key_is_used_and_consumed() {
+        use crate::message::{decrypt_message, encrypt_message};
+        use crate::prekeys::generate_one_time_kyber_pre_keys;
+
+        let alice_conn = init_test_db();
+        let bob_conn = init_test_db();
+        generate_identity(&alice_conn).unwrap();
+        generate_identity(&bob_conn).unwrap();
+
+        let mut bundle = generate_pre_key_bundle(&bob_conn, "bob-user-id").unwrap();
+        let one_time = generate_one_time_kyber_pre_keys(&bob_conn, 1)
+            .unwrap()
+            .remove(0);
+        let one_time_id = one_time.key_id;
+        bundle.one_time_kyber_pre_key = Some(one_time);
+
+        let bob =
+            create_outgoing_session(&alice_conn, &serde_json::to_vec(&bundle).unwrap()).unwrap();
+        let message = encrypt_message(&alice_conn, &bob, b"post-quantum").unwrap();
+        let alice = ProtocolAddress::new("alice-user-id".into(), DeviceId::new(1).unwrap());
+        assert_eq!(
+            decrypt_message(&bob_conn, &alice, &message.ciphertext, message.message_type).unwrap(),
+            b"post-quantum"
+        );
+
+        let remaining: Vec<u32> = {
+            let mut stmt = bob_conn
+                .prepare("SELECT key_id FROM crypto_kyber_pre_keys")
+                .unwrap();
+            stmt.query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap()
+        };
+        assert_eq!(remaining, vec![bundle.kyber_pre_key_id]);
+        assert_ne!(one_time_id, bundle.kyber_pre_key_id);
+    }
+
+    #[test]
+    fn bundle_without_kyber_pre_key_is_rejected() {
+        let alice_conn = init_test_db();
+        let bob_conn = init_test_db();
+        generate_identity(&alice_conn).unwrap();
+        generate_identity(&bob_conn).unwrap();
+
+        // A bundle from before PQXDH: no Kyber fields at all
+        let bundle = generate_pre_key_bundle(&bob_conn, "bob-user-id").unwrap();
+        let mut json = serde_json::to_value(&bundle).unwrap();
+        let object = json.as_object_mut().unwrap();
+        for field in [
+            "kyber_pre_key_id",
+            "kyber_pre_key",
+            "kyber_pre_key_signature",
+        ] {
+            object.remove(field);
+        }
+
+        let result = create_outgoing_session(&alice_conn, &serde_json::to_vec(&json).unwrap());
+        assert!(matches!(result, Err(CryptoError::InvalidKey(_))));
+    }
+
+    /// Alice and Bob with a session in both directions. Returns
+    /// `(alice_conn, bob_conn, bob_address, alice_address)`.
+    fn setup_conversation() -> (Connection, Connection, ProtocolAddress, ProtocolAddress) {
+        use crate::message::{decrypt_message, encrypt_message};
+
+        let alice_conn = init_test_db();
+        let bob_conn = init_test_db();
+        generate_identity(&alice_conn).unwrap();
+        generate_identity(&bob_conn).unwrap();
+        let bundle = generate_pre_key_bundle(&bob_conn, "bob-user-id").unwrap();
+        let bob =
+            create_outgoing_session(&alice_conn, &serde_json::to_vec(&bundle).unwrap()).unwrap();
+        let alice = ProtocolAddress::new("alice-user-id".into(), DeviceId::new(1).unwrap());
+
+        let hello = encrypt_message(&alice_conn, &bob, b"hello").unwrap();
+        decrypt_message(&bob_conn, &alice, &hello.ciphertext, hello.message_type).unwrap();
+        let reply = encrypt_message(&bob_conn, &alice, b"hi").unwrap();
+        decrypt_message(&alice_conn, &bob, &reply.ciphertext, reply.message_type).unwrap();
+
+        (alice_conn, bob_conn, bob, alice)
+    }
+
+    #[test]
+    fn list_sessions_reports_active_and_archived_sessions() {
+        let (alice_conn, _bob_conn, bob, _alice) = setup_conversation();
+        assert!(list_sessions(&init_test_db()).unwrap().is_empty());
+
+        let sessions = list_sessions(&alice_conn).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].address, "bob-user-id");
+        assert_eq!(sessions[0].device_id, 1);
+        assert!(sessions[0].active);
+        assert!(sessions[0].last_used_at >= sessions[0].created_at);
+
+        archive(&alice_conn, &bob).unwrap();
+        let sessions = list_sessions(&alice_conn).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert!(!sessions[0].active);
+
+        reset(&alice_conn, &bob).unwrap();
+        assert!(list_sessions(&alice_conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn archived_session_stops_sending_but_decrypts_in_flight_messages() {
+        use crate::message::{decrypt_message, encrypt_message};
+
+        let (alice_conn, bob_conn, bob, alice) = setup_conversation();
+        let in_flight = encrypt_message(&alice_conn, &bob, b"in flight").unwrap();
+
+        archive(&bob_conn, &alice).unwrap();
+        assert_eq!(
+            decrypt_message(
+                &bob_conn,
+                &alice,
+                &in_flight.ciphertext,
+                in_flight.message_type
+            )
+            .unwrap(),
+            b"in flight"
+        );
+        let result = encrypt_message(&bob_conn, &alice, b"reply");
+        assert!(matches!(result, Err(CryptoError::SessionNotFound { .. })));
+    }
+
+    #[test]
+    fn archive_without_session_returns_session_not_found() {
+        let conn = init_test_db();
+        let address = ProtocolAddress::new("nobody".into(), DeviceId::new(1).unwrap());
+        let result = archive(&conn, &address);
+        assert!(matches!(result, Err(CryptoError::SessionNotFound { .. })));
+    }
+
+    #[test]
+    fn reset_then_new_session_recovers_conversation() {
+        use crate::message::{decrypt_message, encrypt_message};
+
+        let (alice_conn, bob_conn, bob, alice) = setup_conversation();
+        stash_skipped_keys(&bob_conn, &alice_conn, &alice, &bob, 1);
+
+        reset(&alice_conn, &bob).unwrap();
+        assert_eq!(skipped_keys_stored(&alice_conn), 0);
+        let result = encrypt_message(&alice_conn, &bob, b"lost");
+        assert!(matches!(result, Err(CryptoError::SessionNotFound { .. })));
+
+        let bundle = generate_pre_key_bundle(&bob_conn, "bob-user-id").unwrap();
+        let bob =
+            create_outgoing_session(&alice_conn, &serde_json::to_vec(&bundle).unwrap()).unwrap();
+        let message = encrypt_message(&alice_conn, &bob, b"fresh start").unwrap();
+        assert_eq!(
+            decrypt_message(&bob_conn, &alice, &message.ciphertext, message.message_type).unwrap(),
+            b"fresh start"
+        );
+
+        // Resetting again with nothing stored is fine
+        reset(&alice_conn, &bob).unwrap();
+        reset(&alice_conn, &bob).unwrap();
+    }
+
+    /// Send `count + 1` messages and deliver only the last, so the recipient
+    /// stashes `count` skipped message keys.
+    fn stash_skipped_keys(
+        sender_conn: &Connection,
+        recipient_conn: &Connection,
+        recipient: &ProtocolAddress,
+        sender: &ProtocolAddress,
+        count: usize,
+    ) {
+        use crate::message::{decrypt_message, encrypt_message};
+
+        let mut last = None;
+        for _ in 0..=count {
+            last = Some(encrypt_message(sender_conn, recipient, b"gap").unwrap());
+        }
+        let last = last.unwrap();
+        decrypt_message(recipient_conn, sender, &last.ciphertext, last.message_type).unwrap();
+    }
+
+    fn skipped_keys_stored(conn: &Connection) -> u32 {
+        CryptoStore::new(conn)
+            .skipped_message_key_metrics()
+            .unwrap()
+            .stored
+    }
+
+    fn age_session(conn: &Connection, address: &ProtocolAddress, seconds: i64) {
+        conn.execute(
+            "UPDATE crypto_sessions SET last_used_at = last_used_at - ?2 WHERE address = ?1",
+            rusqlite::params![address.name(), seconds],
+        )
+        .unwrap();
+    }
 }
@@ -6,17 +6,19 @@ use libsignal_protocol::{
     SignalProtocolError,
 };
 
+use zeroize::Zeroizing;
+
 use crate::storage::CryptoStore;
 
 #[async_trait(?Send)]
 impl IdentityKeyStore for CryptoStore<'_> {
     async fn get_identity_key_pair(&self) -> Result<IdentityKeyPair, SignalProtocolError> {
-        let (pub_bytes, priv_bytes): (Vec<u8>, Vec<u8>) = self
+        let (pub_bytes, priv_bytes): (Vec<u8>, Zeroizing<Vec<u8>>) = self
             .conn
             .query_row(
                 "SELECT public_key, private_key FROM crypto_identity_keys WHERE id = 1",
                 [],
-                |row| Ok((row.get(0)?, row.get(1)?)),
+                |row| Ok((row.get(0)?, Zeroizing::new(row.get(1)?))),
             )
             .map_err(|_| {
                 SignalProtocolError::InvalidState(
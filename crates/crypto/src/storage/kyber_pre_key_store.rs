@@ -62,10 +62,16 @@ impl KyberPreKeyStore for CryptoStore<'_> {
         _ec_prekey_id: SignedPreKeyId,
         _base_key: &PublicKey,
     ) -> Result<(), SignalProtocolError> {
-        // For last-resort Kyber pre-keys, we don't delete on use.
-        // For one-time Kyber pre-keys (future), delete after use.
-        // For V1, all Kyber pre-keys are last-resort, so this is a no-op.
-        let _id: u32 = kyber_prekey_id.into();
+        // One-time Kyber pre-keys are deleted on use; last-resort ones are kept
+        let id: u32 = kyber_prekey_id.into();
+        self.conn
+            .execute(
+                "DELETE FROM crypto_kyber_pre_keys WHERE key_id = ?1 AND last_resort = 0",
+                [id],
+            )
+            .map_err(|e| {
+                SignalProtocolError::InvalidState("mark_kyber_pre_key_used", e.to_string())
+            })?;
         Ok(())
     }
 }
@@ -127,4 +133,36 @@ mod tests {
         ));
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn mark_kyber_pre_key_used_deletes_only_one_time_keys() {
+        let conn = init_test_db();
+        let mut store = CryptoStore::new(&conn);
+        let dummy_key = libsignal_protocol::KeyPair::generate(&mut rand::rng());
+        for id in [1, 2] {
+            let record = create_kyber_pre_key_record(id);
+            futures::executor::block_on(store.save_kyber_pre_key(KyberPreKeyId::from(id), &record))
+                .unwrap();
+        }
+        conn.execute(
+            "UPDATE crypto_kyber_pre_keys SET last_resort = 0 WHERE key_id = 2",
+            [],
+        )
+        .unwrap();
+
+        for id in [1, 2] {
+            futures::executor::block_on(store.mark_kyber_pre_key_used(
+                KyberPreKeyId::from(id),
+                SignedPreKeyId::from(1),
+                &dummy_key.public_key,
+            ))
+            .unwrap();
+        }
+
+        let last_resort =
+            futures::executor::block_on(store.get_kyber_pre_key(KyberPreKeyId::from(1)));
+        assert!(last_resort.is_ok());
+        let one_time = futures::executor::block_on(store.get_kyber_pre_key(KyberPreKeyId::from(2)));
+        assert!(one_time.is_err());
+    }
 }
@@ -3,7 +3,18 @@
 use crate::error::CryptoError;
 use rusqlite::Connection;
 
-const MIGRATIONS: &[(i32, &str)] = &[(1, MIGRATION_001), (2, MIGRATION_002)];
+const MIGRATIONS: &[(i32, &str)] = &[
+    (1, MIGRATION_001),
+    (2, MIGRATION_002),
+    (3, MIGRATION_003),
+    (4, MIGRATION_004),
+    (5, MIGRATION_005),
+    (6, MIGRATION_006),
+    (7, MIGRATION_007),
+    (8, MIGRATION_008),
+    (9, MIGRATION_009),
+    (10, MIGRATION_010),
+];
 
 const MIGRATION_001: &str = "
 CREATE TABLE IF NOT EXISTS crypto_identity_keys (
@@ -69,6 +80,57 @@ CREATE TABLE IF NOT EXISTS crypto_kyber_pre_keys (
 );
 ";
 
+const MIGRATION_003: &str = "
+CREATE INDEX IF NOT EXISTS idx_crypto_skipped_message_keys_session
+    ON crypto_skipped_message_keys (session_address, session_device_id, created_at);
+";
+
+const MIGRATION_004: &str = "
+CREATE TABLE IF NOT EXISTS crypto_sender_keys (
+    address         TEXT NOT NULL,
+    device_id       INTEGER NOT NULL DEFAULT 1,
+    distribution_id TEXT NOT NULL,
+    record          BLOB NOT NULL,
+    created_at      INTEGER NOT NULL,
+    updated_at      INTEGER NOT NULL,
+    PRIMARY KEY (address, device_id, distribution_id)
+);
+";
+
+const MIGRATION_005: &str = "
+ALTER TABLE crypto_trusted_identities ADD COLUMN verified_identity_key BLOB;
+";
+
+const MIGRATION_006: &str = "
+CREATE TABLE IF NOT EXISTS crypto_archived_identity_keys (
+    public_key  BLOB PRIMARY KEY,
+    private_key BLOB NOT NULL,
+    created_at  INTEGER NOT NULL,
+    archived_at INTEGER NOT NULL
+);
+";
+
+// Kyber pre-keys created before one-time Kyber keys were all last-resort
+const MIGRATION_007: &str = "
+ALTER TABLE crypto_kyber_pre_keys ADD COLUMN last_resort INTEGER NOT NULL DEFAULT 1;
+";
+
+// Existing Kyber pre-keys are assumed to have been uploaded already
+const MIGRATION_008: &str = "
+ALTER TABLE crypto_kyber_pre_keys ADD COLUMN uploaded INTEGER NOT NULL DEFAULT 1;
+";
+
+// Skipped message keys held in session_data; NULL until the session is counted
+const MIGRATION_009: &str = "
+ALTER TABLE crypto_sessions ADD COLUMN skipped_key_count INTEGER;
+";
+
+// libsignal keeps skipped message keys inside session_data; nothing wrote this table
+const MIGRATION_010: &str = "
+DROP INDEX IF EXISTS idx_crypto_skipped_message_keys_session;
+DROP TABLE IF EXISTS crypto_skipped_message_keys;
+";
+
 pub fn run_crypto_migrations(conn: &Connection) -> Result<(), CryptoError> {
     conn.execute_batch(
         "CREATE TABLE IF NOT EXISTS _crypto_migrations (
@@ -85,7 +147,7 @@ pub fn run_crypto_migrations(conn: &Connection) -> Result<(), CryptoError> {
 
     for &(version, sql) in MIGRATIONS {
         if version > current_version {
-            let tx = conn.unchecked_transaction()?;
+            let tx = super::write_transaction(conn)?;
             tx.execute_batch(sql)?;
             tx.execute(
                 "INSERT INTO _crypto_migrations (version) VALUES (?1)",
@@ -112,9 +174,10 @@ mod tests {
             "crypto_pre_keys",
             "crypto_signed_pre_keys",
             "crypto_sessions",
-            "crypto_skipped_message_keys",
             "crypto_config",
             "crypto_kyber_pre_keys",
+            "crypto_sender_keys",
+            "crypto_archived_identity_keys",
         ];
         for table in &expected {
             let exists: bool = conn
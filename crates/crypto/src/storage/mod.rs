@@ -5,11 +5,74 @@ pub mod kyber_pre_key_store;
 pub mod migrations;
 pub mod pre_key_store;
 pub mod sender_key_store;
+mod session_record;
 pub mod session_store;
 pub mod signed_pre_key_store;
 
 use crate::error::CryptoError;
-use rusqlite::Connection;
+use rusqlite::{Connection, Transaction, TransactionBehavior};
+use zeroize::Zeroizing;
+
+/// Config key holding the cumulative count of skipped message keys dropped for age.
+const SKIPPED_KEYS_EXPIRED_TOTAL: &str = "skipped_keys_expired_total";
+/// Config key holding the cumulative count of skipped message keys evicted by either cap.
+const SKIPPED_KEYS_EVICTED_TOTAL: &str = "skipped_keys_evicted_total";
+
+/// Bounds on how many skipped message keys are retained.
+///
+/// Out-of-order delivery forces the ratchet to stash message keys for messages
+/// that have not arrived yet. libsignal keeps them inside the session record
+/// and only bounds them per receiver chain, so a burst of gaps (or a malicious
+/// sender skipping ahead) can still grow a session to thousands of keys
+/// across its chains and archived states. The global cap also stops many
+/// sessions that each stay under their own cap from doing so together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SkippedKeyLimits {
+    /// Hard cap on stored keys per session. The oldest keys are evicted first.
+    pub max_per_session: u32,
+    /// Hard cap on stored keys across all sessions. Keys of the least
+    /// recently used sessions are evicted first, oldest first within each.
+    pub max_total: u32,
+    /// Sessions left unused for longer than this lose all their keys,
+    /// regardless of the cap. libsignal does not timestamp individual keys,
+    /// so a session's last use bounds the age of the keys it holds.
+    pub max_age_seconds: u64,
+}
+
+impl Default for SkippedKeyLimits {
+    fn default() -> Self {
+        Self {
+            max_per_session: 2000,
+            max_total: 20_000,
+            max_age_seconds: 7 * 24 * 3600,
+        }
+    }
+}
+
+/// Outcome of a single limit enforcement pass for one session.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SkippedKeyPruneStats {
+    /// Keys removed from sessions unused for longer than `max_age_seconds`.
+    pub expired: u32,
+    /// Keys removed because the session exceeded `max_per_session`.
+    pub evicted: u32,
+    /// Keys removed from any session because the total exceeded `max_total`.
+    pub evicted_global: u32,
+}
+
+/// Snapshot of skipped-message-key usage, for diagnostics.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SkippedKeyMetrics {
+    /// Keys currently stored across all sessions.
+    pub stored: u32,
+    /// Keys stored by the session holding the most.
+    pub largest_session: u32,
+    /// Keys dropped for age since the database was created.
+    pub expired_total: u64,
+    /// Keys evicted by the per-session or global cap since the database was
+    /// created.
+    pub evicted_total: u64,
+}
 
 /// Central storage coordinator for all crypto state.
 /// Wraps a borrowed SQLite connection and exposes libsignal store
@@ -45,16 +108,85 @@ impl<'a> CryptoStore<'a> {
         Ok(())
     }
 
-    pub fn get_identity_keypair(&self) -> Result<(Vec<u8>, Vec<u8>), CryptoError> {
+    /// The identity `(public_key, private_key)`. The private key is wiped
+    /// from memory when dropped.
+    pub fn get_identity_keypair(&self) -> Result<(Vec<u8>, Zeroizing<Vec<u8>>), CryptoError> {
         self.conn
             .query_row(
                 "SELECT public_key, private_key FROM crypto_identity_keys WHERE id = 1",
                 [],
-                |row| Ok((row.get(0)?, row.get(1)?)),
+                |row| Ok((row.get(0)?, Zeroizing::new(row.get(1)?))),
             )
             .map_err(|_| CryptoError::IdentityNotInitialized)
     }
 
+    /// Copy the current identity keypair into the archive, so it outlives
+    /// a rotation. Archiving the same keypair twice is a no-op.
+    pub fn archive_identity_keypair(&self) -> Result<(), CryptoError> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|_| CryptoError::StorageError("system clock before epoch".into()))?
+            .as_secs() as i64;
+
+        let archived = self.conn.execute(
+            "INSERT OR IGNORE INTO crypto_archived_identity_keys
+                 (public_key, private_key, created_at, archived_at)
+             SELECT public_key, private_key, created_at, ?1 FROM crypto_identity_keys WHERE id = 1",
+            [now],
+        )?;
+        if archived == 0 && self.get_identity_keypair().is_err() {
+            return Err(CryptoError::IdentityNotInitialized);
+        }
+        Ok(())
+    }
+
+    /// Archived identity keypairs, most recently archived first.
+    #[allow(clippy::type_complexity)]
+    pub fn get_archived_identity_keypairs(
+        &self,
+    ) -> Result<Vec<(Vec<u8>, Zeroizing<Vec<u8>>)>, CryptoError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT public_key, private_key FROM crypto_archived_identity_keys
+             ORDER BY archived_at DESC, rowid DESC",
+        )?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, Zeroizing::new(row.get(1)?))))?
+            .collect::<Result<_, _>>()?;
+        Ok(rows)
+    }
+
+    /// Every stored session as `(address, device_id, session_data,
+    /// created_at, last_used_at)`, most recently used first.
+    #[allow(clippy::type_complexity)]
+    pub fn list_sessions(&self) -> Result<Vec<(String, u32, Vec<u8>, i64, i64)>, CryptoError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT address, device_id, session_data, created_at, last_used_at
+             FROM crypto_sessions ORDER BY last_used_at DESC, address, device_id",
+        )?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(rows)
+    }
+
+    /// Delete a session, and with it the skipped message keys held in its
+    /// record. Returns whether a session existed.
+    pub fn delete_session(&self, address: &str, device_id: u32) -> Result<bool, CryptoError> {
+        let deleted = self.conn.execute(
+            "DELETE FROM crypto_sessions WHERE address = ?1 AND device_id = ?2",
+            rusqlite::params![address, device_id],
+        )?;
+        Ok(deleted > 0)
+    }
+
     pub fn count_available_pre_keys(&self) -> Result<u32, CryptoError> {
         let count: u32 =
             self.conn
@@ -62,6 +194,8 @@ impl<'a> CryptoStore<'a> {
         Ok(count)
     }
 
+    /// Drop every skipped message key of sessions unused for longer than
+    /// `max_age_seconds`. Returns the number of keys dropped.
     pub fn prune_skipped_message_keys(&self, max_age_seconds: u64) -> Result<u32, CryptoError> {
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -69,11 +203,206 @@ impl<'a> CryptoStore<'a> {
             .as_secs();
         let cutoff = now.saturating_sub(max_age_seconds) as i64;
 
-        let deleted = self.conn.execute(
-            "DELETE FROM crypto_skipped_message_keys WHERE created_at < ?1",
-            [cutoff],
+        self.count_untracked_skipped_message_keys()?;
+        let idle: Vec<(String, u32)> = self
+            .conn
+            .prepare(
+                "SELECT address, device_id FROM crypto_sessions
+                 WHERE last_used_at < ?1 AND skipped_key_count > 0",
+            )?
+            .query_map([cutoff], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+
+        let mut expired = 0;
+        for (address, device_id) in idle {
+            expired += self.trim_skipped_message_keys(&address, device_id, 0)?;
+        }
+        self.increment_counter(SKIPPED_KEYS_EXPIRED_TOTAL, u64::from(expired))?;
+        Ok(expired)
+    }
+
+    /// Apply `limits` after a message from `address` was decrypted.
+    ///
+    /// Sessions left unused for longer than `max_age_seconds` lose their keys
+    /// first, then the sender's session is trimmed to `max_per_session` by
+    /// evicting its oldest keys, and finally keys are evicted from the least
+    /// recently used sessions until at most `max_total` remain.
+    pub fn enforce_skipped_message_key_limits(
+        &self,
+        address: &str,
+        device_id: u32,
+        limits: &SkippedKeyLimits,
+    ) -> Result<SkippedKeyPruneStats, CryptoError> {
+        let expired = self.prune_skipped_message_keys(limits.max_age_seconds)?;
+        let evicted = self.trim_skipped_message_keys(address, device_id, limits.max_per_session)?;
+        let evicted_global =
+            self.trim_all_skipped_message_keys(address, device_id, limits.max_total)?;
+
+        self.increment_counter(
+            SKIPPED_KEYS_EVICTED_TOTAL,
+            u64::from(evicted + evicted_global),
         )?;
-        Ok(deleted as u32)
+
+        if evicted > 0 {
+            tracing::warn!(
+                address,
+                device_id,
+                evicted,
+                cap = limits.max_per_session,
+                "skipped message key cap reached, evicted oldest keys"
+            );
+        }
+        if evicted_global > 0 {
+            tracing::warn!(
+                evicted = evicted_global,
+                cap = limits.max_total,
+                "global skipped message key cap reached, evicted oldest keys"
+            );
+        }
+
+        Ok(SkippedKeyPruneStats {
+            expired,
+            evicted,
+            evicted_global,
+        })
+    }
+
+    /// Report current and cumulative skipped-message-key usage.
+    pub fn skipped_message_key_metrics(&self) -> Result<SkippedKeyMetrics, CryptoError> {
+        self.count_untracked_skipped_message_keys()?;
+        let (stored, largest_session): (u32, u32) = self.conn.query_row(
+            "SELECT COALESCE(SUM(skipped_key_count), 0), COALESCE(MAX(skipped_key_count), 0)
+             FROM crypto_sessions",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        Ok(SkippedKeyMetrics {
+            stored,
+            largest_session,
+            expired_total: self.read_counter(SKIPPED_KEYS_EXPIRED_TOTAL)?,
+            evicted_total: self.read_counter(SKIPPED_KEYS_EVICTED_TOTAL)?,
+        })
+    }
+
+    /// Evict skipped message keys until at most `max_total` remain across all
+    /// sessions. Least recently used sessions go first; the sender's session,
+    /// which was just used, goes last. Returns the number of keys evicted.
+    fn trim_all_skipped_message_keys(
+        &self,
+        address: &str,
+        device_id: u32,
+        max_total: u32,
+    ) -> Result<u32, CryptoError> {
+        let stored: u64 = self.conn.query_row(
+            "SELECT COALESCE(SUM(skipped_key_count), 0) FROM crypto_sessions",
+            [],
+            |row| row.get(0),
+        )?;
+        let mut excess = stored.saturating_sub(u64::from(max_total));
+        if excess == 0 {
+            return Ok(0);
+        }
+
+        let sessions: Vec<(String, u32, u32)> = self
+            .conn
+            .prepare(
+                "SELECT address, device_id, skipped_key_count FROM crypto_sessions
+                 WHERE skipped_key_count > 0
+                 ORDER BY address = ?1 AND device_id = ?2, last_used_at, address, device_id",
+            )?
+            .query_map(rusqlite::params![address, device_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect::<Result<_, _>>()?;
+
+        let mut evicted = 0;
+        for (session_address, session_device_id, count) in sessions {
+            if excess == 0 {
+                break;
+            }
+            let keep = u64::from(count).saturating_sub(excess) as u32;
+            let trimmed =
+                self.trim_skipped_message_keys(&session_address, session_device_id, keep)?;
+            excess = excess.saturating_sub(u64::from(trimmed));
+            evicted += trimmed;
+        }
+        Ok(evicted)
+    }
+
+    /// Evict the oldest skipped message keys of a session until at most
+    /// `keep` remain. Returns the number of keys evicted.
+    fn trim_skipped_message_keys(
+        &self,
+        address: &str,
+        device_id: u32,
+        keep: u32,
+    ) -> Result<u32, CryptoError> {
+        let session_data: Vec<u8> = match self.conn.query_row(
+            "SELECT session_data FROM crypto_sessions WHERE address = ?1 AND device_id = ?2",
+            rusqlite::params![address, device_id],
+            |row| row.get(0),
+        ) {
+            Ok(data) => data,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+
+        let (session_data, evicted) = session_record::trim_message_keys(&session_data, keep)?;
+        if evicted > 0 {
+            // Leaves last_used_at alone: trimming is not a use of the session
+            self.conn.execute(
+                "UPDATE crypto_sessions SET session_data = ?3, skipped_key_count = ?4
+                 WHERE address = ?1 AND device_id = ?2",
+                rusqlite::params![address, device_id, session_data, keep],
+            )?;
+        }
+        Ok(evicted)
+    }
+
+    /// Fill in `skipped_key_count` for sessions stored before it was tracked.
+    fn count_untracked_skipped_message_keys(&self) -> Result<(), CryptoError> {
+        let untracked: Vec<(String, u32, Vec<u8>)> = self
+            .conn
+            .prepare(
+                "SELECT address, device_id, session_data FROM crypto_sessions
+                 WHERE skipped_key_count IS NULL",
+            )?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<Result<_, _>>()?;
+
+        for (address, device_id, session_data) in untracked {
+            self.conn.execute(
+                "UPDATE crypto_sessions SET skipped_key_count = ?3
+                 WHERE address = ?1 AND device_id = ?2",
+                rusqlite::params![
+                    address,
+                    device_id,
+                    session_record::count_message_keys(&session_data)?
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn read_counter(&self, key: &str) -> Result<u64, CryptoError> {
+        match self.get_config(key)? {
+            Some(bytes) => {
+                let arr: [u8; 8] = bytes.as_slice().try_into().map_err(|_| {
+                    CryptoError::StorageError(format!("counter {key} has invalid length"))
+                })?;
+                Ok(u64::from_be_bytes(arr))
+            }
+            None => Ok(0),
+        }
+    }
+
+    fn increment_counter(&self, key: &str, by: u64) -> Result<(), CryptoError> {
+        if by == 0 {
+            return Ok(());
+        }
+        let value = self.read_counter(key)?.saturating_add(by);
+        self.store_config(key, &value.to_be_bytes())
     }
 
     pub fn store_config(&self, key: &str, value: &[u8]) -> Result<(), CryptoError> {
@@ -98,13 +427,26 @@ impl<'a> CryptoStore<'a> {
     }
 }
 
-/// Execute a closure within a SQLite transaction.
-/// Commits on Ok, rolls back on Err.
+/// Begin a write transaction on a borrowed connection.
+///
+/// The transaction is IMMEDIATE, taking the write lock up front. A DEFERRED
+/// transaction that reads and then writes cannot wait for the lock under
+/// WAL: if another connection wrote in between, SQLite fails it with
+/// `SQLITE_BUSY_SNAPSHOT` without honouring the busy timeout.
+pub(crate) fn write_transaction(conn: &Connection) -> Result<Transaction<'_>, CryptoError> {
+    Ok(Transaction::new_unchecked(
+        conn,
+        TransactionBehavior::Immediate,
+    )?)
+}
+
+/// Execute a closure within a SQLite write transaction (see
+/// `write_transaction`). Commits on Ok, rolls back on Err.
 pub fn with_transaction<F, T>(conn: &Connection, f: F) -> Result<T, CryptoError>
 where
     F: FnOnce(&CryptoStore) -> Result<T, CryptoError>,
 {
-    let tx = conn.unchecked_transaction()?;
+    let tx = write_transaction(conn)?;
     let store = CryptoStore::new(conn);
     match f(&store) {
         Ok(value) => {
@@ -187,6 +529,8 @@ mod tests {
 
     #[test]
     fn prune_skipped_message_keys_deletes_old_keeps_recent() {
+        use session_record::test_support::{chain, record, session};
+
         let conn = init_test_db();
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -195,34 +539,224 @@ mod tests {
 
         let two_weeks_ago = now - (14 * 24 * 3600);
 
-        // Old entry
+        // Session last used two weeks ago
+        insert_session(
+            &conn,
+            "old",
+            &record(&session(&[chain(&[2, 1])]), &[]),
+            two_weeks_ago,
+        );
+
+        // Recently used session
+        insert_session(&conn, "recent", &record(&session(&[chain(&[1])]), &[]), now);
+
+        let store = CryptoStore::new(&conn);
+        let deleted = store.prune_skipped_message_keys(7 * 24 * 3600).unwrap();
+        assert_eq!(deleted, 2);
+
+        let metrics = store.skipped_message_key_metrics().unwrap();
+        assert_eq!(metrics.stored, 1);
+        assert_eq!(metrics.expired_total, 2);
+    }
+
+    /// Store a hand-built session record, leaving its key count untracked as
+    /// for sessions stored before the count existed.
+    fn insert_session(conn: &Connection, address: &str, session_data: &[u8], last_used_at: i64) {
         conn.execute(
-            "INSERT INTO crypto_skipped_message_keys (session_address, session_device_id, ratchet_key, message_number, message_key, created_at)
-             VALUES ('addr', 1, X'AA', 1, X'BB', ?1)",
-            [two_weeks_ago],
+            "INSERT INTO crypto_sessions (address, device_id, session_data, created_at, last_used_at)
+             VALUES (?1, 1, ?2, ?3, ?3)",
+            rusqlite::params![address, session_data, last_used_at],
         )
         .unwrap();
+    }
 
-        // Recent entry
-        conn.execute(
-            "INSERT INTO crypto_skipped_message_keys (session_address, session_device_id, ratchet_key, message_number, message_key, created_at)
-             VALUES ('addr', 1, X'CC', 2, X'DD', ?1)",
-            [now],
+    fn stored_record(conn: &Connection, address: &str) -> Vec<u8> {
+        conn.query_row(
+            "SELECT session_data FROM crypto_sessions WHERE address = ?1",
+            [address],
+            |row| row.get(0),
         )
-        .unwrap();
+        .unwrap()
+    }
+
+    #[test]
+    fn enforce_skipped_message_key_limits_evicts_oldest_over_cap() {
+        use session_record::test_support::{chain, record, remaining, session};
+
+        let conn = init_test_db();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        insert_session(
+            &conn,
+            "addr",
+            &record(&session(&[chain(&[4, 3, 2, 1, 0])]), &[]),
+            now,
+        );
+        let other = record(&session(&[chain(&[0])]), &[]);
+        insert_session(&conn, "other", &other, now);
 
         let store = CryptoStore::new(&conn);
-        let deleted = store.prune_skipped_message_keys(7 * 24 * 3600).unwrap();
-        assert_eq!(deleted, 1);
+        let limits = SkippedKeyLimits {
+            max_per_session: 3,
+            ..Default::default()
+        };
+        let stats = store
+            .enforce_skipped_message_key_limits("addr", 1, &limits)
+            .unwrap();
+        assert_eq!(
+            stats,
+            SkippedKeyPruneStats {
+                expired: 0,
+                evicted: 2,
+                evicted_global: 0,
+            }
+        );
+        assert_eq!(
+            remaining(&stored_record(&conn, "addr")),
+            vec![(0, 0, 4), (0, 0, 3), (0, 0, 2)]
+        );
+
+        // Other sessions are untouched
+        assert_eq!(stored_record(&conn, "other"), other);
+    }
+
+    #[test]
+    fn enforce_skipped_message_key_limits_expires_idle_sessions() {
+        use session_record::test_support::{chain, record, remaining, session};
+
+        let conn = init_test_db();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        insert_session(
+            &conn,
+            "idle",
+            &record(&session(&[chain(&[2, 1])]), &[]),
+            now - 3600,
+        );
+        insert_session(&conn, "addr", &record(&session(&[chain(&[1])]), &[]), now);
 
-        let remaining: i32 = conn
+        let store = CryptoStore::new(&conn);
+        let limits = SkippedKeyLimits {
+            max_per_session: 10,
+            max_age_seconds: 60,
+            ..Default::default()
+        };
+        let stats = store
+            .enforce_skipped_message_key_limits("addr", 1, &limits)
+            .unwrap();
+        assert_eq!(stats.expired, 2);
+        assert_eq!(stats.evicted, 0);
+        assert!(remaining(&stored_record(&conn, "idle")).is_empty());
+        assert_eq!(remaining(&stored_record(&conn, "addr")).len(), 1);
+
+        // Trimming does not count as using the session
+        let last_used_at: i64 = conn
             .query_row(
-                "SELECT COUNT(*) FROM crypto_skipped_message_keys",
+                "SELECT last_used_at FROM crypto_sessions WHERE address = 'idle'",
                 [],
                 |row| row.get(0),
             )
             .unwrap();
-        assert_eq!(remaining, 1);
+        assert_eq!(last_used_at, now - 3600);
+    }
+
+    #[test]
+    fn enforce_skipped_message_key_limits_applies_global_cap_across_sessions() {
+        use session_record::test_support::{chain, record, remaining, session};
+
+        let conn = init_test_db();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        // Each session stays under its own cap, but together they exceed the total
+        insert_session(
+            &conn,
+            "first",
+            &record(&session(&[chain(&[2, 1, 0])]), &[]),
+            now - 20,
+        );
+        insert_session(
+            &conn,
+            "second",
+            &record(&session(&[chain(&[2, 1, 0])]), &[]),
+            now - 10,
+        );
+        insert_session(
+            &conn,
+            "third",
+            &record(&session(&[chain(&[2, 1, 0])]), &[]),
+            now,
+        );
+
+        // The sender's session goes last even though "third" was used more recently
+        let store = CryptoStore::new(&conn);
+        let limits = SkippedKeyLimits {
+            max_per_session: 3,
+            max_total: 4,
+            ..Default::default()
+        };
+        let stats = store
+            .enforce_skipped_message_key_limits("first", 1, &limits)
+            .unwrap();
+        assert_eq!(stats.evicted, 0);
+        assert_eq!(stats.evicted_global, 5);
+
+        assert_eq!(
+            remaining(&stored_record(&conn, "first")),
+            vec![(0, 0, 2), (0, 0, 1), (0, 0, 0)]
+        );
+        assert!(remaining(&stored_record(&conn, "second")).is_empty());
+        assert_eq!(remaining(&stored_record(&conn, "third")), vec![(0, 0, 2)]);
+        assert_eq!(
+            store.skipped_message_key_metrics().unwrap().evicted_total,
+            5
+        );
+    }
+
+    #[test]
+    fn skipped_message_key_metrics_track_cumulative_pruning() {
+        use session_record::test_support::{chain, record, session};
+
+        let conn = init_test_db();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        insert_session(
+            &conn,
+            "addr",
+            &record(&session(&[chain(&[3, 2])]), &[session(&[chain(&[1, 0])])]),
+            now,
+        );
+        insert_session(&conn, "other", &record(&session(&[chain(&[0])]), &[]), now);
+
+        let store = CryptoStore::new(&conn);
+        let metrics = store.skipped_message_key_metrics().unwrap();
+        assert_eq!(metrics.stored, 5);
+        assert_eq!(metrics.largest_session, 4);
+        assert_eq!(metrics.evicted_total, 0);
+
+        let limits = SkippedKeyLimits {
+            max_per_session: 1,
+            ..Default::default()
+        };
+        store
+            .enforce_skipped_message_key_limits("addr", 1, &limits)
+            .unwrap();
+
+        let metrics = store.skipped_message_key_metrics().unwrap();
+        assert_eq!(metrics.stored, 2);
+        assert_eq!(metrics.largest_session, 1);
+        assert_eq!(metrics.evicted_total, 3);
     }
 
     #[test]
@@ -1,7 +1,8 @@
-//! SenderKeyStore no-op implementation for CryptoStore.
+//! SenderKeyStore trait implementation for CryptoStore.
 //!
-//! Group messaging (Sender Keys) is out of scope for V1, but libsignal
-//! requires this trait to be present for some operations.
+//! Holds one sender key record per (sender address, device, distribution ID):
+//! our own chains for groups we send to, and the chains other members
+//! distributed to us. See [`crate::group`].
 
 use async_trait::async_trait;
 use libsignal_protocol::{ProtocolAddress, SenderKeyRecord, SenderKeyStore, SignalProtocolError};
@@ -13,19 +14,64 @@ use crate::storage::CryptoStore;
 impl SenderKeyStore for CryptoStore<'_> {
     async fn store_sender_key(
         &mut self,
-        _sender: &ProtocolAddress,
-        _distribution_id: Uuid,
-        _record: &SenderKeyRecord,
+        sender: &ProtocolAddress,
+        distribution_id: Uuid,
+        record: &SenderKeyRecord,
     ) -> Result<(), SignalProtocolError> {
+        let addr_name = sender.name();
+        let device_id: u32 = sender.device_id().into();
+        let record_bytes = record.serialize()?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|_| {
+                SignalProtocolError::InvalidState(
+                    "store_sender_key",
+                    "system clock before epoch".into(),
+                )
+            })?
+            .as_secs() as i64;
+
+        self.conn
+            .execute(
+                "INSERT INTO crypto_sender_keys (address, device_id, distribution_id, record, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+                 ON CONFLICT(address, device_id, distribution_id) DO UPDATE SET
+                     record = excluded.record,
+                     updated_at = excluded.updated_at",
+                rusqlite::params![
+                    addr_name,
+                    device_id,
+                    distribution_id.to_string(),
+                    record_bytes,
+                    now
+                ],
+            )
+            .map_err(|e| SignalProtocolError::InvalidState("store_sender_key", e.to_string()))?;
+
         Ok(())
     }
 
     async fn load_sender_key(
         &mut self,
-        _sender: &ProtocolAddress,
-        _distribution_id: Uuid,
+        sender: &ProtocolAddress,
+        distribution_id: Uuid,
     ) -> Result<Option<SenderKeyRecord>, SignalProtocolError> {
-        Ok(None)
+        let addr_name = sender.name();
+        let device_id: u32 = sender.device_id().into();
+
+        match self.conn.query_row(
+            "SELECT record FROM crypto_sender_keys
+             WHERE address = ?1 AND device_id = ?2 AND distribution_id = ?3",
+            rusqlite::params![addr_name, device_id, distribution_id.to_string()],
+            |row| row.get::<_, Vec<u8>>(0),
+        ) {
+            Ok(bytes) => Ok(Some(SenderKeyRecord::deserialize(&bytes)?)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(SignalProtocolError::InvalidState(
+                "load_sender_key",
+                e.to_string(),
+            )),
+        }
     }
 }
 
@@ -36,27 +82,97 @@ mod tests {
     use crate::storage::CryptoStore;
     use libsignal_protocol::{DeviceId, ProtocolAddress};
 
+    fn address(name: &str, device_id: u8) -> ProtocolAddress {
+        ProtocolAddress::new(name.to_string(), DeviceId::new(device_id).unwrap())
+    }
+
+    /// A record with one sender key state, as created for our own chain.
+    fn populated_record(conn: &rusqlite::Connection, sender: &ProtocolAddress) -> SenderKeyRecord {
+        let mut store = CryptoStore::new(conn);
+        let dist_id = Uuid::new_v4();
+        futures::executor::block_on(libsignal_protocol::create_sender_key_distribution_message(
+            sender,
+            dist_id,
+            &mut store,
+            &mut rand::rng(),
+        ))
+        .unwrap();
+        futures::executor::block_on(store.load_sender_key(sender, dist_id))
+            .unwrap()
+            .unwrap()
+    }
+
     #[test]
-    fn store_sender_key_returns_ok() {
+    fn load_sender_key_returns_none() {
         let conn = init_test_db();
         let mut store = CryptoStore::new(&conn);
-        let addr = ProtocolAddress::new("user1".to_string(), DeviceId::new(1).unwrap());
+        let addr = address("user1", 1);
+        let dist_id = Uuid::new_v4();
+
+        let result = futures::executor::block_on(store.load_sender_key(&addr, dist_id)).unwrap();
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn store_then_load_sender_key_round_trips() {
+        let conn = init_test_db();
+        let addr = address("user1", 1);
+        let record = populated_record(&conn, &addr);
+        let mut store = CryptoStore::new(&conn);
+        let dist_id = Uuid::new_v4();
+
+        futures::executor::block_on(store.store_sender_key(&addr, dist_id, &record)).unwrap();
+        let loaded = futures::executor::block_on(store.load_sender_key(&addr, dist_id))
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.serialize().unwrap(), record.serialize().unwrap());
+    }
+
+    #[test]
+    fn store_sender_key_replaces_existing_record() {
+        let conn = init_test_db();
+        let addr = address("user1", 1);
+        let mut store = CryptoStore::new(&conn);
         let dist_id = Uuid::new_v4();
         // SenderKeyRecord::new_empty() is pub(crate), so deserialize from empty protobuf
-        let record = SenderKeyRecord::deserialize(&[]).unwrap();
+        let empty = SenderKeyRecord::deserialize(&[]).unwrap();
+        futures::executor::block_on(store.store_sender_key(&addr, dist_id, &empty)).unwrap();
+
+        let record = populated_record(&conn, &addr);
+        futures::executor::block_on(store.store_sender_key(&addr, dist_id, &record)).unwrap();
 
-        let result = futures::executor::block_on(store.store_sender_key(&addr, dist_id, &record));
-        assert!(result.is_ok());
+        let count: u32 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM crypto_sender_keys WHERE distribution_id = ?1",
+                [dist_id.to_string()],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 1);
+        let loaded = futures::executor::block_on(store.load_sender_key(&addr, dist_id))
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.serialize().unwrap(), record.serialize().unwrap());
     }
 
     #[test]
-    fn load_sender_key_returns_none() {
+    fn sender_keys_are_scoped_by_address_device_and_distribution() {
         let conn = init_test_db();
+        let addr = address("user1", 1);
+        let record = populated_record(&conn, &addr);
         let mut store = CryptoStore::new(&conn);
-        let addr = ProtocolAddress::new("user1".to_string(), DeviceId::new(1).unwrap());
         let dist_id = Uuid::new_v4();
+        futures::executor::block_on(store.store_sender_key(&addr, dist_id, &record)).unwrap();
 
-        let result = futures::executor::block_on(store.load_sender_key(&addr, dist_id)).unwrap();
-        assert!(result.is_none());
+        for (other_addr, other_dist) in [
+            (address("user2", 1), dist_id),
+            (address("user1", 2), dist_id),
+            (address("user1", 1), Uuid::new_v4()),
+        ] {
+            let loaded =
+                futures::executor::block_on(store.load_sender_key(&other_addr, other_dist))
+                    .unwrap();
+            assert!(loaded.is_none());
+        }
     }
 }
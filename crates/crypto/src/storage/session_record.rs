@@ -0,0 +1,346 @@
+//! Skipped message keys inside serialized libsignal session records.
+//!
+//! libsignal keeps the keys of messages that have not arrived yet in the
+//! receiver chains of each session state of a `SessionRecord`, and its API
+//! offers no way to count or drop them. These helpers walk the record's
+//! protobuf encoding directly. Fields they do not rewrite are copied byte for
+//! byte, so data added by newer libsignal versions survives a trim.
+//!
+//! Age order, oldest first: archived states before the current one (the most
+//! recently archived state comes first in the record), receiver chains in the
+//! order they were added, and message keys by counter.
+
+use std::cmp::Reverse;
+use std::collections::HashSet;
+
+use crate::error::CryptoError;
+
+/// `RecordStructure.current_session`
+const RECORD_CURRENT_SESSION: u32 = 1;
+/// `RecordStructure.previous_sessions`
+const RECORD_PREVIOUS_SESSIONS: u32 = 2;
+/// `SessionStructure.receiver_chains`
+const SESSION_RECEIVER_CHAINS: u32 = 7;
+/// `SessionStructure.Chain.message_keys`
+const CHAIN_MESSAGE_KEYS: u32 = 4;
+/// `SessionStructure.Chain.MessageKey.index`
+const MESSAGE_KEY_INDEX: u32 = 1;
+
+const WIRE_VARINT: u64 = 0;
+const WIRE_FIXED64: u64 = 1;
+const WIRE_LEN: u64 = 2;
+const WIRE_FIXED32: u64 = 5;
+
+/// Number of skipped message keys held by an encoded `SessionRecord`.
+pub(crate) fn count_message_keys(record: &[u8]) -> Result<u32, CryptoError> {
+    Ok(message_keys(record)?.len() as u32)
+}
+
+/// Drop the oldest skipped message keys of an encoded `SessionRecord` until
+/// at most `keep` remain. Returns the new encoding and the number of keys
+/// dropped.
+pub(crate) fn trim_message_keys(record: &[u8], keep: u32) -> Result<(Vec<u8>, u32), CryptoError> {
+    let mut keys = message_keys(record)?;
+    let excess = keys.len().saturating_sub(keep as usize);
+    if excess == 0 {
+        return Ok((record.to_vec(), 0));
+    }
+
+    keys.sort_by_key(|k| (Reverse(k.state), k.chain, k.index));
+    let dropped: HashSet<(usize, usize, usize)> = keys[..excess]
+        .iter()
+        .map(|k| (k.state, k.chain, k.key))
+        .collect();
+
+    let rewrite_state = |state: usize, session: &[u8]| {
+        rewrite_nested(session, SESSION_RECEIVER_CHAINS, |chain, chain_bytes| {
+            rewrite_nested(chain_bytes, CHAIN_MESSAGE_KEYS, |key, key_bytes| {
+                Ok((!dropped.contains(&(state, chain, key))).then(|| key_bytes.to_vec()))
+            })
+            .map(Some)
+        })
+    };
+    let record = rewrite_nested(record, RECORD_CURRENT_SESSION, |_, session| {
+        rewrite_state(0, session).map(Some)
+    })?;
+    let record = rewrite_nested(&record, RECORD_PREVIOUS_SESSIONS, |n, session| {
+        rewrite_state(n + 1, session).map(Some)
+    })?;
+    Ok((record, excess as u32))
+}
+
+/// Where a skipped message key sits in the record.
+struct KeyPosition {
+    /// 0 for the current session state, n for the nth archived one.
+    state: usize,
+    /// Position among the state's receiver chains.
+    chain: usize,
+    /// Position among the chain's message keys.
+    key: usize,
+    /// The message counter the key decrypts.
+    index: u64,
+}
+
+fn message_keys(record: &[u8]) -> Result<Vec<KeyPosition>, CryptoError> {
+    let mut keys = Vec::new();
+    let mut archived = 0;
+    for field in fields(record)? {
+        let (state, session) = match (field.number, field.payload) {
+            (RECORD_CURRENT_SESSION, Some(session)) => (0, session),
+            (RECORD_PREVIOUS_SESSIONS, Some(session)) => {
+                archived += 1;
+                (archived, session)
+            }
+            _ => continue,
+        };
+        for (chain, chain_bytes) in nested(session, SESSION_RECEIVER_CHAINS)?
+            .into_iter()
+            .enumerate()
+        {
+            for (key, key_bytes) in nested(chain_bytes, CHAIN_MESSAGE_KEYS)?
+                .into_iter()
+                .enumerate()
+            {
+                let index = fields(key_bytes)?
+                    .into_iter()
+                    .find(|f| f.number == MESSAGE_KEY_INDEX && f.payload.is_none())
+                    .map_or(0, |f| f.value);
+                keys.push(KeyPosition {
+                    state,
+                    chain,
+                    key,
+                    index,
+                });
+            }
+        }
+    }
+    Ok(keys)
+}
+
+/// One field of an encoded message.
+struct Field<'a> {
+    number: u32,
+    /// The whole field, tag included.
+    raw: &'a [u8],
+    /// The contents of a length-delimited field.
+    payload: Option<&'a [u8]>,
+    /// The value of a varint field.
+    value: u64,
+}
+
+fn malformed() -> CryptoError {
+    CryptoError::SerializationError("malformed session record".into())
+}
+
+fn read_varint(buf: &[u8], pos: &mut usize) -> Result<u64, CryptoError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *buf.get(*pos).ok_or_else(malformed)?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(malformed())
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn fields(buf: &[u8]) -> Result<Vec<Field<'_>>, CryptoError> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while pos < buf.len() {
+        let start = pos;
+        let tag = read_varint(buf, &mut pos)?;
+        let number = u32::try_from(tag >> 3).map_err(|_| malformed())?;
+        let mut payload = None;
+        let mut value = 0;
+        match tag & 7 {
+            WIRE_VARINT => value = read_varint(buf, &mut pos)?,
+            WIRE_FIXED64 => pos += 8,
+            WIRE_FIXED32 => pos += 4,
+            WIRE_LEN => {
+                let len = usize::try_from(read_varint(buf, &mut pos)?).map_err(|_| malformed())?;
+                let end = pos.checked_add(len).ok_or_else(malformed)?;
+                payload = Some(buf.get(pos..end).ok_or_else(malformed)?);
+                pos = end;
+            }
+            _ => return Err(malformed()),
+        }
+        fields.push(Field {
+            number,
+            raw: buf.get(start..pos).ok_or_else(malformed)?,
+            payload,
+            value,
+        });
+    }
+    Ok(fields)
+}
+
+/// The contents of every length-delimited `number` field of `buf`.
+fn nested(buf: &[u8], number: u32) -> Result<Vec<&[u8]>, CryptoError> {
+    Ok(fields(buf)?
+        .into_iter()
+        .filter(|f| f.number == number)
+        .filter_map(|f| f.payload)
+        .collect())
+}
+
+/// Copy `buf`, passing the contents of the nth length-delimited `number`
+/// field through `rewrite(n, contents)`. `None` drops the field.
+fn rewrite_nested<F>(buf: &[u8], number: u32, mut rewrite: F) -> Result<Vec<u8>, CryptoError>
+where
+    F: FnMut(usize, &[u8]) -> Result<Option<Vec<u8>>, CryptoError>,
+{
+    let mut out = Vec::with_capacity(buf.len());
+    let mut nth = 0;
+    for field in fields(buf)? {
+        match field.payload {
+            Some(payload) if field.number == number => {
+                if let Some(contents) = rewrite(nth, payload)? {
+                    write_varint(&mut out, (u64::from(number) << 3) | WIRE_LEN);
+                    write_varint(&mut out, contents.len() as u64);
+                    out.extend_from_slice(&contents);
+                }
+                nth += 1;
+            }
+            _ => out.extend_from_slice(field.raw),
+        }
+    }
+    Ok(out)
+}
+
+/// Hand-built `SessionRecord` encodings, for tests that need skipped keys
+/// without running the ratchet.
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
+
+    pub(crate) fn varint_field(number: u32, value: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_varint(&mut out, u64::from(number) << 3 | WIRE_VARINT);
+        write_varint(&mut out, value);
+        out
+    }
+
+    pub(crate) fn len_field(number: u32, contents: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_varint(&mut out, u64::from(number) << 3 | WIRE_LEN);
+        write_varint(&mut out, contents.len() as u64);
+        out.extend_from_slice(contents);
+        out
+    }
+
+    /// A receiver chain holding keys for `indices`.
+    pub(crate) fn chain(indices: &[u64]) -> Vec<u8> {
+        let mut chain = len_field(1, &[0x05; 33]);
+        for &index in indices {
+            let mut key = varint_field(MESSAGE_KEY_INDEX, index);
+            key.extend(len_field(2, &[0xAB; 32]));
+            chain.extend(len_field(CHAIN_MESSAGE_KEYS, &key));
+        }
+        chain
+    }
+
+    /// A session state with the given receiver chains, a sender chain and a
+    /// field this module does not know about.
+    pub(crate) fn session(chains: &[Vec<u8>]) -> Vec<u8> {
+        let mut session = varint_field(1, 4);
+        session.extend(len_field(6, &chain(&[])));
+        for c in chains {
+            session.extend(len_field(SESSION_RECEIVER_CHAINS, c));
+        }
+        session.extend(len_field(99, b"future"));
+        session
+    }
+
+    pub(crate) fn record(current: &[u8], archived: &[Vec<u8>]) -> Vec<u8> {
+        let mut record = len_field(RECORD_CURRENT_SESSION, current);
+        for state in archived {
+            record.extend(len_field(RECORD_PREVIOUS_SESSIONS, state));
+        }
+        record
+    }
+
+    /// `(state, chain, counter)` of every key left in `record`.
+    pub(crate) fn remaining(record: &[u8]) -> Vec<(usize, usize, u64)> {
+        message_keys(record)
+            .unwrap()
+            .into_iter()
+            .map(|k| (k.state, k.chain, k.index))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::*;
+    use super::*;
+
+    #[test]
+    fn count_message_keys_covers_all_chains_and_archived_states() {
+        let record = record(
+            &session(&[chain(&[1, 2]), chain(&[7])]),
+            &[session(&[chain(&[3, 4, 5])])],
+        );
+        assert_eq!(count_message_keys(&record).unwrap(), 6);
+    }
+
+    #[test]
+    fn count_message_keys_of_empty_record_is_zero() {
+        assert_eq!(count_message_keys(&[]).unwrap(), 0);
+        assert_eq!(count_message_keys(&record(&session(&[]), &[])).unwrap(), 0);
+    }
+
+    #[test]
+    fn trim_message_keys_drops_oldest_first() {
+        // Newest key first within a chain, as libsignal stores them
+        let record = record(
+            &session(&[chain(&[3, 2, 1]), chain(&[11, 10])]),
+            &[session(&[chain(&[21, 20])]), session(&[chain(&[31, 30])])],
+        );
+
+        let (trimmed, dropped) = trim_message_keys(&record, 4).unwrap();
+        assert_eq!(dropped, 5);
+        assert_eq!(
+            remaining(&trimmed),
+            vec![(0, 0, 3), (0, 0, 2), (0, 1, 11), (0, 1, 10)]
+        );
+
+        let (trimmed, dropped) = trim_message_keys(&trimmed, 1).unwrap();
+        assert_eq!(dropped, 3);
+        assert_eq!(remaining(&trimmed), vec![(0, 1, 11)]);
+    }
+
+    #[test]
+    fn trim_message_keys_keeps_everything_else() {
+        let current = session(&[chain(&[2, 1])]);
+        let record = record(&current, &[]);
+
+        let (untouched, dropped) = trim_message_keys(&record, 2).unwrap();
+        assert_eq!(dropped, 0);
+        assert_eq!(untouched, record);
+
+        let (trimmed, _) = trim_message_keys(&record, 0).unwrap();
+        let expected = self::record(&session(&[chain(&[])]), &[]);
+        assert_eq!(trimmed, expected);
+    }
+
+    #[test]
+    fn malformed_record_is_rejected() {
+        let record = record(&session(&[chain(&[1])]), &[]);
+        let truncated = &record[..record.len() - 1];
+        assert!(matches!(
+            count_message_keys(truncated),
+            Err(CryptoError::SerializationError(_))
+        ));
+        assert!(trim_message_keys(truncated, 0).is_err());
+    }
+}
@@ -3,7 +3,7 @@
 use async_trait::async_trait;
 use libsignal_protocol::{ProtocolAddress, SessionRecord, SessionStore, SignalProtocolError};
 
-use crate::storage::CryptoStore;
+use crate::storage::{session_record, CryptoStore};
 
 #[async_trait(?Send)]
 impl SessionStore for CryptoStore<'_> {
@@ -36,6 +36,8 @@ impl SessionStore for CryptoStore<'_> {
         let addr_name = address.name();
         let device_id: u32 = address.device_id().into();
         let session_bytes = record.serialize()?;
+        let skipped_key_count = session_record::count_message_keys(&session_bytes)
+            .map_err(|e| SignalProtocolError::InvalidState("store_session", e.to_string()))?;
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .map_err(|_| {
@@ -48,12 +50,14 @@ impl SessionStore for CryptoStore<'_> {
 
         self.conn
             .execute(
-                "INSERT INTO crypto_sessions (address, device_id, session_data, created_at, last_used_at)
-                 VALUES (?1, ?2, ?3, ?4, ?4)
+                "INSERT INTO crypto_sessions
+                     (address, device_id, session_data, skipped_key_count, created_at, last_used_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?5)
                  ON CONFLICT(address, device_id) DO UPDATE SET
                      session_data = excluded.session_data,
+                     skipped_key_count = excluded.skipped_key_count,
                      last_used_at = excluded.last_used_at",
-                rusqlite::params![addr_name, device_id, session_bytes, now],
+                rusqlite::params![addr_name, device_id, session_bytes, skipped_key_count, now],
             )
             .map_err(|e| SignalProtocolError::InvalidState("store_session", e.to_string()))?;
 
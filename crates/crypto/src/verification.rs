@@ -0,0 +1,350 @@
+//! Per-contact identity verification state.
+//!
+//! Once two users have compared safety numbers (see [`crate::fingerprint`]),
+//! `mark_verified` records the identity key they verified. The status of a
+//! contact is then derived by comparing that key with the one currently
+//! trusted for their device: if the device later presents a new key (a
+//! reinstall, or a man-in-the-middle), the status becomes `Changed` until
+//! the users verify again.
+
+use libsignal_protocol::{IdentityKey, PreKeySignalMessage, ProtocolAddress};
+use rusqlite::Connection;
+
+use crate::error::CryptoError;
+use crate::message::MessageType;
+use crate::storage::write_transaction;
+
+/// Verification status of a contact's device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerificationStatus {
+    /// Never verified, or verification was cleared.
+    Unverified,
+    /// The current identity key is the one that was verified.
+    Verified,
+    /// The contact was verified, but against a different identity key.
+    Changed,
+}
+
+/// Record that the user verified `identity_key` for `address`.
+///
+/// `identity_key` must be the key the compared fingerprint was generated
+/// from. Returns `CryptoError::InvalidKey` if it is not a valid identity key
+/// or differs from the key already trusted for `address`, since the user
+/// would then have verified a stale fingerprint. A contact with no trusted
+/// key yet is pinned to `identity_key`.
+pub fn mark_verified(
+    conn: &Connection,
+    address: &ProtocolAddress,
+    identity_key: &[u8],
+) -> Result<(), CryptoError> {
+    let key_bytes = decode(identity_key)?;
+    let device_id: u32 = address.device_id().into();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|_| CryptoError::StorageError("system clock before epoch".into()))?
+        .as_secs() as i64;
+
+    let tx = write_transaction(conn)?;
+    if let Some((trusted, _)) = load(conn, address)? {
+        if trusted.as_slice() != key_bytes.as_ref() {
+            return Err(CryptoError::InvalidKey(
+                "identity key does not match the contact's current key".into(),
+            ));
+        }
+    }
+    conn.execute(
+        "INSERT INTO crypto_trusted_identities
+             (address, device_id, identity_key, first_seen_at, verified_at, verified_identity_key)
+         VALUES (?1, ?2, ?3, ?4, ?4, ?3)
+         ON CONFLICT(address, device_id) DO UPDATE SET
+             verified_at = excluded.verified_at,
+             verified_identity_key = excluded.verified_identity_key",
+        rusqlite::params![address.name(), device_id, key_bytes.as_ref(), now],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Forget the verification for `address`, returning it to `Unverified`.
+pub fn clear_verification(conn: &Connection, address: &ProtocolAddress) -> Result<(), CryptoError> {
+    let device_id: u32 = address.device_id().into();
+    conn.execute(
+        "UPDATE crypto_trusted_identities
+         SET verified_at = NULL, verified_identity_key = NULL
+         WHERE address = ?1 AND device_id = ?2",
+        rusqlite::params![address.name(), device_id],
+    )?;
+    Ok(())
+}
+
+/// Verification status of `address` against its currently trusted key.
+pub fn get_status(
+    conn: &Connection,
+    address: &ProtocolAddress,
+) -> Result<VerificationStatus, CryptoError> {
+    Ok(match load(conn, address)? {
+        Some((trusted, verified)) => status(verified.as_deref(), &trusted),
+        None => VerificationStatus::Unverified,
+    })
+}
+
+/// Verification status `address` would have if it presented
+/// `identity_key`, e.g. one taken from a pre-key bundle before starting a
+/// session.
+pub fn check_identity(
+    conn: &Connection,
+    address: &ProtocolAddress,
+    identity_key: &[u8],
+) -> Result<VerificationStatus, CryptoError> {
+    let key_bytes = decode(identity_key)?;
+    let verified = load(conn, address)?.and_then(|(_, verified)| verified);
+    Ok(status(verified.as_deref(), &key_bytes))
+}
+
+/// Verification status of the identity an incoming message was sent with.
+///
+/// Call before `message::decrypt_message`: a `PreKey` message can start a
+/// new session under a different identity key, and this returns `Changed`
+/// for one that does not match the verified key. `Signal` messages use the
+/// existing session, so they get the contact's current status.
+pub fn check_incoming_message(
+    conn: &Connection,
+    sender: &ProtocolAddress,
+    ciphertext: &[u8],
+    message_type: MessageType,
+) -> Result<VerificationStatus, CryptoError> {
+    match message_type {
+        MessageType::PreKey => {
+            let message = PreKeySignalMessage::try_from(ciphertext)?;
+            check_identity(conn, sender, &message.identity_key().serialize())
+        }
+        MessageType::Signal => get_status(conn, sender),
+    }
+}
+
+fn decode(identity_key: &[u8]) -> Result<Box<[u8]>, CryptoError> {
+    IdentityKey::decode(identity_key)
+        .map(|key| key.serialize())
+        .map_err(|e| CryptoError::InvalidKey(e.to_string()))
+}
+
+fn status(verified: Option<&[u8]>, identity_key: &[u8]) -> VerificationStatus {
+    match verified {
+        None => VerificationStatus::Unverified,
+        Some(verified) if verified == identity_key => VerificationStatus::Verified,
+        Some(_) => VerificationStatus::Changed,
+    }
+}
+
+/// The trusted key and verified key (if any) stored for `address`.
+fn load(
+    conn: &Connection,
+    address: &ProtocolAddress,
+) -> Result<Option<(Vec<u8>, Option<Vec<u8>>)>, CryptoError> {
+    let device_id: u32 = address.device_id().into();
+    match conn.query_row(
+        "SELECT identity_key, verified_identity_key FROM crypto_trusted_identities
+         WHERE address = ?1 AND device_id = ?2",
+        rusqlite::params![address.name(), device_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ) {
+        Ok(row) => Ok(Some(row)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::generate_identity;
+    use crate::message::encrypt_message;
+    use crate::prekeys::generate_pre_key_bundle;
+    use crate::session::create_outgoing_session;
+    use crate::storage::{init_test_db, CryptoStore};
+    use libsignal_protocol::{DeviceId, IdentityKeyPair, IdentityKeyStore};
+
+    fn address(name: &str, device_id: u8) -> ProtocolAddress {
+        ProtocolAddress::new(name.to_string(), DeviceId::new(device_id).expect("valid"))
+    }
+
+    fn new_key() -> Vec<u8> {
+        IdentityKeyPair::generate(&mut rand::rng())
+            .identity_key()
+            .serialize()
+            .to_vec()
+    }
+
+    fn save_identity(conn: &Connection, address: &ProtocolAddress, key: &[u8]) {
+        let mut store = CryptoStore::new(conn);
+        futures::executor::block_on(
+            store.save_identity(address, &IdentityKey::decode(key).unwrap()),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn contacts_start_unverified() {
+        let conn = init_test_db();
+        let bob = address("bob", 1);
+        assert_eq!(
+            get_status(&conn, &bob).unwrap(),
+            VerificationStatus::Unverified
+        );
+
+        save_identity(&conn, &bob, &new_key());
+        assert_eq!(
+            get_status(&conn, &bob).unwrap(),
+            VerificationStatus::Unverified
+        );
+    }
+
+    #[test]
+    fn mark_verified_then_key_change_is_flagged() {
+        let conn = init_test_db();
+        let bob = address("bob", 1);
+        let key = new_key();
+        save_identity(&conn, &bob, &key);
+
+        mark_verified(&conn, &bob, &key).unwrap();
+        assert_eq!(
+            get_status(&conn, &bob).unwrap(),
+            VerificationStatus::Verified
+        );
+
+        let new = new_key();
+        save_identity(&conn, &bob, &new);
+        assert_eq!(
+            get_status(&conn, &bob).unwrap(),
+            VerificationStatus::Changed
+        );
+
+        // Verifying the new key clears the flag
+        mark_verified(&conn, &bob, &new).unwrap();
+        assert_eq!(
+            get_status(&conn, &bob).unwrap(),
+            VerificationStatus::Verified
+        );
+    }
+
+    #[test]
+    fn mark_verified_pins_a_contact_without_a_trusted_key() {
+        let conn = init_test_db();
+        let bob = address("bob", 1);
+        let key = new_key();
+
+        mark_verified(&conn, &bob, &key).unwrap();
+        assert_eq!(
+            get_status(&conn, &bob).unwrap(),
+            VerificationStatus::Verified
+        );
+        let trusted = futures::executor::block_on(CryptoStore::new(&conn).get_identity(&bob))
+            .unwrap()
+            .unwrap();
+        assert_eq!(trusted.serialize().as_ref(), key.as_slice());
+    }
+
+    #[test]
+    fn mark_verified_rejects_stale_or_invalid_keys() {
+        let conn = init_test_db();
+        let bob = address("bob", 1);
+        save_identity(&conn, &bob, &new_key());
+
+        let result = mark_verified(&conn, &bob, &new_key());
+        assert!(matches!(result, Err(CryptoError::InvalidKey(_))));
+        let result = mark_verified(&conn, &bob, b"not a key");
+        assert!(matches!(result, Err(CryptoError::InvalidKey(_))));
+        assert_eq!(
+            get_status(&conn, &bob).unwrap(),
+            VerificationStatus::Unverified
+        );
+    }
+
+    #[test]
+    fn clear_verification_returns_to_unverified() {
+        let conn = init_test_db();
+        let bob = address("bob", 1);
+        let key = new_key();
+        mark_verified(&conn, &bob, &key).unwrap();
+
+        clear_verification(&conn, &bob).unwrap();
+        assert_eq!(
+            get_status(&conn, &bob).unwrap(),
+            VerificationStatus::Unverified
+        );
+        assert_eq!(
+            check_identity(&conn, &bob, &new_key()).unwrap(),
+            VerificationStatus::Unverified
+        );
+    }
+
+    #[test]
+    fn verification_is_per_device() {
+        let conn = init_test_db();
+        let key = new_key();
+        mark_verified(&conn, &address("bob", 1), &key).unwrap();
+
+        assert_eq!(
+            get_status(&conn, &address("bob", 2)).unwrap(),
+            VerificationStatus::Unverified
+        );
+    }
+
+    #[test]
+    fn check_identity_compares_against_verified_key() {
+        let conn = init_test_db();
+        let bob = address("bob", 1);
+        let key = new_key();
+        mark_verified(&conn, &bob, &key).unwrap();
+
+        assert_eq!(
+            check_identity(&conn, &bob, &key).unwrap(),
+            VerificationStatus::Verified
+        );
+        assert_eq!(
+            check_identity(&conn, &bob, &new_key()).unwrap(),
+            VerificationStatus::Changed
+        );
+    }
+
+    #[test]
+    fn incoming_pre_key_message_from_new_identity_is_flagged() {
+        let bob_conn = init_test_db();
+        generate_identity(&bob_conn).unwrap();
+        let alice = address("alice-user-id", 1);
+
+        // Alice's first install, verified by Bob
+        let alice_conn = init_test_db();
+        let alice_identity = generate_identity(&alice_conn).unwrap();
+        let bundle = generate_pre_key_bundle(&bob_conn, "bob-user-id").unwrap();
+        let bob =
+            create_outgoing_session(&alice_conn, &serde_json::to_vec(&bundle).unwrap()).unwrap();
+        mark_verified(
+            &bob_conn,
+            &alice,
+            &alice_identity.identity_key().serialize(),
+        )
+        .unwrap();
+
+        let message = encrypt_message(&alice_conn, &bob, b"hi").unwrap();
+        assert_eq!(
+            check_incoming_message(&bob_conn, &alice, &message.ciphertext, message.message_type)
+                .unwrap(),
+            VerificationStatus::Verified
+        );
+
+        // Someone else claiming to be Alice
+        let impostor_conn = init_test_db();
+        generate_identity(&impostor_conn).unwrap();
+        let bundle = generate_pre_key_bundle(&bob_conn, "bob-user-id").unwrap();
+        let bob =
+            create_outgoing_session(&impostor_conn, &serde_json::to_vec(&bundle).unwrap()).unwrap();
+        let message = encrypt_message(&impostor_conn, &bob, b"hi").unwrap();
+        assert_eq!(message.message_type, MessageType::PreKey);
+        assert_eq!(
+            check_incoming_message(&bob_conn, &alice, &message.ciphertext, message.message_type)
+                .unwrap(),
+            VerificationStatus::Changed
+        );
+    }
+}
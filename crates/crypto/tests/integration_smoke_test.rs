@@ -163,6 +163,10 @@ fn fingerprint_generation_and_comparison() {
     // QR comparison succeeds
     assert!(fingerprint::compare_fingerprints(&fp_alice, &fp_bob.scannable).unwrap());
     assert!(fingerprint::compare_fingerprints(&fp_bob, &fp_alice.scannable).unwrap());
+
+    // Compact QR payload verifies the same way
+    assert!(fp_alice.verify_scanned(&fp_bob.compact).unwrap());
+    assert!(fp_bob.verify_scanned(&fp_alice.compact).unwrap());
 }
 
 #[test]
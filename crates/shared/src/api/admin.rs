@@ -0,0 +1,205 @@
+use crate::ids::UserId;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Request body for PUT /api/admin/users/:user_id/suspension.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct SuspendUserRequest {
+    /// Internal note for other administrators. Never shown to the user.
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Suspension state of a user account.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct UserSuspensionResponse {
+    pub user_id: UserId,
+    pub suspended: bool,
+    pub suspended_at: Option<DateTime<Utc>>,
+    pub reason: Option<String>,
+    pub suspended_by: Option<UserId>,
+}
+
+/// Result of checking one backing service.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct DependencyHealth {
+    /// "database", "redis", "object_store" or "smtp".
+    pub name: String,
+    /// "ok" or "unavailable".
+    pub status: String,
+    pub latency_ms: u64,
+    /// Why the check failed. Only shown to instance admins.
+    pub error: Option<String>,
+}
+
+/// Response body for GET /api/admin/health.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct HealthReportResponse {
+    /// "ok" when every dependency is, else "unavailable".
+    pub status: String,
+    pub version: String,
+    pub dependencies: Vec<DependencyHealth>,
+}
+
+/// Run statistics for one background job on the answering node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct JobStatusResponse {
+    pub name: String,
+    /// Human-readable schedule, e.g. "every 3600s".
+    pub schedule: String,
+    pub running: bool,
+    pub runs: u64,
+    pub failures: u64,
+    /// Runs skipped because another node held the job's lock.
+    pub skipped: u64,
+    pub last_started_at: Option<DateTime<Utc>>,
+    pub last_duration_ms: Option<u64>,
+    /// Items the last successful run processed.
+    pub last_processed: Option<u64>,
+    pub last_error: Option<String>,
+    pub next_run_at: Option<DateTime<Utc>>,
+}
+
+/// Result of reloading the runtime configuration on the answering node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct ConfigReloadResponse {
+    /// Config file read, or `None` when running on defaults and env vars.
+    pub source: Option<String>,
+    /// Settings whose values changed, e.g. "rate_limit".
+    pub changed: Vec<String>,
+}
+
+/// A queued email that was given up on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EmailDeadLetterResponse {
+    pub id: uuid::Uuid,
+    /// "verification" or "recovery".
+    pub kind: String,
+    pub recipient: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Response for GET /api/admin/email-queue.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EmailQueueStatsResponse {
+    /// Emails waiting for a first or further attempt.
+    pub pending: i64,
+    pub sent: i64,
+    /// Emails given up on after repeated failures or once their code expired.
+    pub dead: i64,
+    pub oldest_pending_at: Option<DateTime<Utc>>,
+    /// The most recent dead letters, newest first.
+    pub dead_letters: Vec<EmailDeadLetterResponse>,
+}
+
+/// Request body for POST /api/admin/ip-denylist.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct CreateIpDenylistEntryRequest {
+    /// An IPv4 or IPv6 address or CIDR range, e.g. "203.0.113.0/24".
+    pub cidr: String,
+    /// Internal note for other administrators.
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// When the entry stops applying. Permanent when omitted.
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// A denylist entry managed at runtime.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct IpDenylistEntryResponse {
+    pub id: uuid::Uuid,
+    /// Normalized range, e.g. "203.0.113.0/24" or "192.0.2.7/32".
+    pub cidr: String,
+    pub reason: Option<String>,
+    pub created_by: Option<UserId>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Response for GET /api/admin/ip-denylist.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct IpDenylistResponse {
+    /// Ranges from the config file. These cannot be removed at runtime.
+    pub configured: Vec<String>,
+    /// Ranges managed through this API, including expired ones.
+    pub entries: Vec<IpDenylistEntryResponse>,
+}
+
+/// Requests refused from one address against one entry, as tallied by a
+/// node between flushes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct IpBlockResponse {
+    pub ip: String,
+    /// The denylist entry the address matched.
+    pub cidr: String,
+    pub attempts: i64,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suspend_user_request_reason_is_optional() {
+        let req: SuspendUserRequest = serde_json::from_str("{}").unwrap();
+        assert!(req.reason.is_none());
+    }
+
+    #[test]
+    fn user_suspension_response_serde() {
+        let resp = UserSuspensionResponse {
+            user_id: UserId::new(),
+            suspended: true,
+            suspended_at: Some(Utc::now()),
+            reason: Some("spam".into()),
+            suspended_by: Some(UserId::new()),
+        };
+        let json = serde_json::to_string(&resp).unwrap();
+        let back: UserSuspensionResponse = serde_json::from_str(&json).unwrap();
+        assert!(back.suspended);
+        assert_eq!(back.reason.as_deref(), Some("spam"));
+    }
+
+    #[test]
+    fn health_report_response_serde() {
+        let resp = HealthReportResponse {
+            status: "unavailable".into(),
+            version: "0.1.0".into(),
+            dependencies: vec![DependencyHealth {
+                name: "smtp".into(),
+                status: "unavailable".into(),
+                latency_ms: 2000,
+                error: Some("timed out".into()),
+            }],
+        };
+        let json = serde_json::to_value(&resp).unwrap();
+        assert_eq!(json["dependencies"][0]["name"], "smtp");
+        assert_eq!(json["dependencies"][0]["latency_ms"], 2000);
+    }
+
+    #[test]
+    fn create_ip_denylist_entry_request_optional_fields() {
+        let req: CreateIpDenylistEntryRequest =
+            serde_json::from_str(r#"{"cidr":"203.0.113.0/24"}"#).unwrap();
+        assert_eq!(req.cidr, "203.0.113.0/24");
+        assert!(req.reason.is_none());
+        assert!(req.expires_at.is_none());
+    }
+}
@@ -54,7 +54,7 @@ pub struct RegisterVerifyResponse {
 pub struct RegisterCompleteRequest {
     pub registration_token: String,
     pub public_key: String,
-    /// Base64-encoded pre-key bundle bytes.
+    /// Base64-encoded JSON pre-key bundle, signed by `public_key`.
     pub pre_key_bundle: String,
     pub device_id: DeviceId,
     pub device_name: String,
@@ -127,6 +127,29 @@ pub struct RefreshResponse {
     pub refresh_token: String,
 }
 
+// ---------------------------------------------------------------------------
+// Step-up re-authentication
+// ---------------------------------------------------------------------------
+
+/// POST /api/auth/reauth
+///
+/// `signature` signs a challenge obtained from `/api/auth/challenge` for the
+/// caller's own public key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct ReauthRequest {
+    pub signature: String,
+}
+
+/// Elevated access token that unlocks sensitive operations for a short window.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct ReauthResponse {
+    pub access_token: String,
+    /// Seconds until the token no longer counts as recently re-authenticated.
+    pub reauth_expires_in: u64,
+}
+
 // ---------------------------------------------------------------------------
 // Account recovery (three-phase: start → verify → complete)
 // ---------------------------------------------------------------------------
@@ -166,7 +189,7 @@ pub struct RecoverVerifyResponse {
 pub struct RecoverCompleteRequest {
     pub recovery_token: String,
     pub new_public_key: String,
-    /// Base64-encoded new pre-key bundle bytes.
+    /// Base64-encoded JSON pre-key bundle, signed by `new_public_key`.
     pub new_pre_key_bundle: String,
     pub device_id: DeviceId,
     pub device_name: String,
@@ -194,6 +217,10 @@ pub struct DeviceInfo {
     pub device_name: String,
     pub last_active: Option<chrono::DateTime<chrono::Utc>>,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_ip: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_geo_country: Option<String>,
 }
 
 /// Response body for GET /api/auth/devices.
@@ -203,6 +230,45 @@ pub struct DevicesListResponse {
     pub devices: Vec<DeviceInfo>,
 }
 
+/// POST /api/auth/devices — link another device to the signed-in account.
+/// The new device then signs in with its own identity key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct LinkDeviceRequest {
+    pub device_id: DeviceId,
+    pub device_name: String,
+    /// Base64-encoded identity public key generated on the new device.
+    pub identity_key: String,
+    /// Base64-encoded JSON pre-key bundle, signed by `identity_key`.
+    pub pre_key_bundle: String,
+}
+
+/// An active session (refresh token family) returned in session listing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct SessionInfo {
+    /// Refresh token family; identifies the session across token rotations.
+    pub id: uuid::Uuid,
+    pub device_id: DeviceId,
+    pub device_name: String,
+    /// IP address the session last refreshed from.
+    pub ip_address: Option<String>,
+    /// Two-letter country code the session last refreshed from.
+    pub geo_country: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub last_used_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    /// True for sessions on the device making the request.
+    pub current: bool,
+}
+
+/// Response body for GET /api/auth/sessions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct SessionsListResponse {
+    pub sessions: Vec<SessionInfo>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -407,9 +473,27 @@ mod tests {
             device_name: "iPhone".into(),
             last_active: Some(now),
             created_at: now,
+            last_ip: None,
+            last_geo_country: Some("DE".into()),
         };
         let json = serde_json::to_string(&info).unwrap();
         let back: DeviceInfo = serde_json::from_str(&json).unwrap();
         assert_eq!(back.device_name, "iPhone");
+        assert_eq!(back.last_geo_country.as_deref(), Some("DE"));
+        assert!(!json.contains("last_ip"));
+    }
+
+    #[test]
+    fn link_device_request_serde_roundtrip() {
+        let req = LinkDeviceRequest {
+            device_id: DeviceId::new(),
+            device_name: "Laptop".into(),
+            identity_key: "BQab".into(),
+            pre_key_bundle: "e30=".into(),
+        };
+        let json = serde_json::to_string(&req).unwrap();
+        let back: LinkDeviceRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.device_id, req.device_id);
+        assert_eq!(back.identity_key, "BQab");
     }
 }
@@ -0,0 +1,200 @@
+use crate::ids::{GuildId, UserId};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Most automod rules a guild can have.
+pub const MAX_AUTOMOD_RULES_PER_GUILD: usize = 25;
+/// Most keywords in a single keyword rule.
+pub const MAX_AUTOMOD_KEYWORDS: usize = 100;
+/// Longest keyword, in characters.
+pub const MAX_AUTOMOD_KEYWORD_LEN: usize = 60;
+/// Longest attachment rate window, in seconds.
+pub const MAX_AUTOMOD_WINDOW_SECONDS: u32 = 86400;
+
+/// What an automod rule checks.
+///
+/// Message content is end-to-end encrypted, so the server only enforces what
+/// it can see: the attachment count, and the [`MessageMetadata`] the sending
+/// client declares alongside the ciphertext. Keyword rules are enforced by
+/// clients before encrypting; the server only stores and distributes them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AutomodRule {
+    /// Block messages mentioning more than `max_mentions` users.
+    MentionLimit { max_mentions: u32 },
+    /// Block messages that contain guild invite links.
+    BlockInviteLinks,
+    /// Block attaching more than `max_attachments` files within
+    /// `window_seconds`.
+    AttachmentRate {
+        max_attachments: u32,
+        window_seconds: u32,
+    },
+    /// Block messages containing any of `keywords`, matched
+    /// case-insensitively by the sending client.
+    Keyword { keywords: Vec<String> },
+}
+
+impl AutomodRule {
+    /// Whether the server can enforce this rule itself.
+    pub fn is_server_enforced(&self) -> bool {
+        !matches!(self, AutomodRule::Keyword { .. })
+    }
+
+    /// Check the rule's parameters.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            AutomodRule::MentionLimit { .. } | AutomodRule::BlockInviteLinks => Ok(()),
+            AutomodRule::AttachmentRate {
+                max_attachments,
+                window_seconds,
+            } => {
+                if *max_attachments == 0 {
+                    return Err("max_attachments must be at least 1".into());
+                }
+                if *window_seconds == 0 || *window_seconds > MAX_AUTOMOD_WINDOW_SECONDS {
+                    return Err(format!(
+                        "window_seconds must be between 1 and {MAX_AUTOMOD_WINDOW_SECONDS}"
+                    ));
+                }
+                Ok(())
+            }
+            AutomodRule::Keyword { keywords } => {
+                if keywords.is_empty() || keywords.len() > MAX_AUTOMOD_KEYWORDS {
+                    return Err(format!(
+                        "keywords must contain between 1 and {MAX_AUTOMOD_KEYWORDS} entries"
+                    ));
+                }
+                if keywords.iter().any(|k| {
+                    let k = k.trim();
+                    k.is_empty() || k.chars().count() > MAX_AUTOMOD_KEYWORD_LEN
+                }) {
+                    return Err(format!(
+                        "keywords must be between 1 and {MAX_AUTOMOD_KEYWORD_LEN} characters"
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Plaintext facts about an encrypted message, declared by the sending
+/// client so automod can enforce rules on content the server cannot read.
+///
+/// The server trusts these as declared; they exist to stop accidents and
+/// spam from unmodified clients, not a determined sender.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(default)]
+pub struct MessageMetadata {
+    /// Number of distinct users mentioned.
+    pub mention_count: u32,
+    /// Whether the message contains a guild invite link.
+    pub contains_invite: bool,
+}
+
+/// Request body for POST /api/guilds/:guild_id/automod/rules.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct CreateAutomodRuleRequest {
+    pub name: String,
+    pub rule: AutomodRule,
+    /// Defaults to true.
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+/// Request body for PATCH /api/guilds/:guild_id/automod/rules/:rule_id.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct UpdateAutomodRuleRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<AutomodRule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+}
+
+/// A guild automod rule.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct AutomodRuleResponse {
+    pub id: uuid::Uuid,
+    pub guild_id: GuildId,
+    pub name: String,
+    pub rule: AutomodRule,
+    pub enabled: bool,
+    /// Whether the server enforces the rule; otherwise clients must.
+    pub server_enforced: bool,
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Response for GET /api/guilds/:guild_id/automod/rules.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct AutomodRuleListResponse {
+    pub rules: Vec<AutomodRuleResponse>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_are_tagged_by_type() {
+        let rule = AutomodRule::AttachmentRate {
+            max_attachments: 5,
+            window_seconds: 60,
+        };
+        let json = serde_json::to_value(&rule).unwrap();
+        assert_eq!(json["type"], "attachment_rate");
+        assert_eq!(json["max_attachments"], 5);
+
+        let back: AutomodRule = serde_json::from_str(r#"{"type":"block_invite_links"}"#).unwrap();
+        assert_eq!(back, AutomodRule::BlockInviteLinks);
+    }
+
+    #[test]
+    fn keyword_rules_are_client_enforced() {
+        let keyword = AutomodRule::Keyword {
+            keywords: vec!["spam".into()],
+        };
+        assert!(!keyword.is_server_enforced());
+        assert!(AutomodRule::MentionLimit { max_mentions: 3 }.is_server_enforced());
+    }
+
+    #[test]
+    fn validate_rejects_degenerate_rules() {
+        let zero = AutomodRule::AttachmentRate {
+            max_attachments: 0,
+            window_seconds: 60,
+        };
+        assert!(zero.validate().is_err());
+        let forever = AutomodRule::AttachmentRate {
+            max_attachments: 1,
+            window_seconds: MAX_AUTOMOD_WINDOW_SECONDS + 1,
+        };
+        assert!(forever.validate().is_err());
+        let blank = AutomodRule::Keyword {
+            keywords: vec!["  ".into()],
+        };
+        assert!(blank.validate().is_err());
+        assert!(AutomodRule::Keyword { keywords: vec![] }
+            .validate()
+            .is_err());
+        assert!(AutomodRule::MentionLimit { max_mentions: 0 }
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn message_metadata_defaults_when_absent() {
+        let metadata: MessageMetadata = serde_json::from_str("{}").unwrap();
+        assert_eq!(metadata, MessageMetadata::default());
+    }
+}
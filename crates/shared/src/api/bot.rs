@@ -0,0 +1,66 @@
+use crate::ids::{GuildId, UserId};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Request body for POST /api/guilds/:guild_id/bots.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct CreateBotRequest {
+    pub display_name: String,
+    /// `Permissions` bits capping what the bot's roles grant it in the
+    /// guild. Defaults to `READ_MESSAGES | SEND_MESSAGES`; may not exceed
+    /// the creator's own permissions.
+    #[serde(default)]
+    pub permissions: Option<u64>,
+}
+
+/// A guild bot account.
+///
+/// Bots authenticate with `Authorization: Bot <token>` instead of a bearer
+/// token. The token is only returned when the bot is created or its token
+/// is regenerated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct BotResponse {
+    pub id: UserId,
+    pub guild_id: GuildId,
+    pub display_name: String,
+    pub permissions: u64,
+    pub created_by: Option<UserId>,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+/// Response for GET /api/guilds/:guild_id/bots.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct BotListResponse {
+    pub bots: Vec<BotResponse>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_bot_request_permissions_default_to_none() {
+        let req: CreateBotRequest = serde_json::from_str(r#"{"display_name":"Mod"}"#).unwrap();
+        assert!(req.permissions.is_none());
+    }
+
+    #[test]
+    fn bot_response_omits_token_when_unset() {
+        let resp = BotResponse {
+            id: UserId::new(),
+            guild_id: GuildId::new(),
+            display_name: "Mod".into(),
+            permissions: 0,
+            created_by: None,
+            created_at: Utc::now(),
+            token: None,
+        };
+        let json = serde_json::to_value(&resp).unwrap();
+        assert!(json.get("token").is_none());
+    }
+}
@@ -0,0 +1,80 @@
+use crate::ids::UserId;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Request body for POST /api/admin/bridges.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct CreateBridgeRequest {
+    pub name: String,
+    /// Short lowercase identifier for the remote network, e.g. `irc` or
+    /// `matrix`. Unique per instance.
+    pub namespace: String,
+}
+
+/// A registered bridge application.
+///
+/// Bridges authenticate with `Authorization: Bridge <token>`. The token is
+/// only returned when the bridge is registered or its token is regenerated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct BridgeResponse {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub namespace: String,
+    pub created_by: Option<UserId>,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+/// Response for GET /api/admin/bridges.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct BridgeListResponse {
+    pub bridges: Vec<BridgeResponse>,
+}
+
+/// Request body for PUT /api/bridge/puppets/:remote_id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct UpsertPuppetRequest {
+    pub display_name: String,
+}
+
+/// A puppet user and the remote account it mirrors.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct PuppetResponse {
+    pub user_id: UserId,
+    pub remote_id: String,
+    pub display_name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Response for GET /api/bridge/puppets.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct PuppetListResponse {
+    pub puppets: Vec<PuppetResponse>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bridge_response_omits_token_when_unset() {
+        let resp = BridgeResponse {
+            id: uuid::Uuid::new_v4(),
+            name: "IRC".into(),
+            namespace: "irc".into(),
+            created_by: None,
+            created_at: Utc::now(),
+            token: None,
+        };
+        let json = serde_json::to_value(&resp).unwrap();
+        assert!(json.get("token").is_none());
+        assert_eq!(json["namespace"], "irc");
+    }
+}
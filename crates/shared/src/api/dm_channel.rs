@@ -2,9 +2,13 @@ use crate::ids::{DmChannelId, UserId};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+/// Display name clients show for a user's personal notes channel.
+pub const NOTES_CHANNEL_NAME: &str = "Notes";
+
 /// Request to create a DM channel.
 /// - For 1:1 DMs: provide a single user_id in `user_ids`
 /// - For group DMs: provide 2+ user_ids (up to 24, since the creator is added automatically)
+/// - For the caller's notes channel: provide only the caller's own user_id
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct CreateDmChannelRequest {
@@ -20,10 +24,33 @@ pub struct DmChannelResponse {
     pub name: Option<String>,
     pub creator_id: Option<UserId>,
     pub is_group: bool,
+    /// The creator's personal notes channel ("message yourself"). Its only
+    /// member is the creator; messages sync across their devices.
+    #[serde(default)]
+    pub is_notes: bool,
+    /// Hidden from the caller's default DM list. Per member: archiving does
+    /// not affect the other members.
+    #[serde(default)]
+    pub archived: bool,
     pub members: Vec<UserId>,
     pub created_at: DateTime<Utc>,
 }
 
+/// Request body for PUT /api/dm-channels/{id}/archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct SetDmArchiveRequest {
+    pub archived: bool,
+    /// Unarchive automatically when another member sends a new message.
+    /// Defaults to true; set false to keep the conversation archived.
+    #[serde(default = "default_unarchive_on_message")]
+    pub unarchive_on_message: bool,
+}
+
+fn default_unarchive_on_message() -> bool {
+    true
+}
+
 /// Request to add a member to a group DM.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
@@ -54,6 +81,8 @@ mod tests {
             name: Some("Group Chat".into()),
             creator_id: Some(UserId::new()),
             is_group: true,
+            is_notes: false,
+            archived: false,
             members: vec![UserId::new(), UserId::new()],
             created_at: Utc::now(),
         };
@@ -63,6 +92,27 @@ mod tests {
         assert_eq!(back.members.len(), 2);
     }
 
+    #[test]
+    fn dm_channel_response_is_notes_defaults_to_false() {
+        let json = serde_json::json!({
+            "id": DmChannelId::new(),
+            "name": null,
+            "creator_id": null,
+            "is_group": false,
+            "members": [],
+            "created_at": Utc::now(),
+        });
+        let resp: DmChannelResponse = serde_json::from_value(json).unwrap();
+        assert!(!resp.is_notes);
+    }
+
+    #[test]
+    fn set_dm_archive_request_unarchives_on_message_by_default() {
+        let req: SetDmArchiveRequest = serde_json::from_str(r#"{"archived":true}"#).unwrap();
+        assert!(req.archived);
+        assert!(req.unarchive_on_message);
+    }
+
     #[test]
     fn add_dm_member_request_serde() {
         let req = AddDmMemberRequest {
@@ -0,0 +1,262 @@
+use crate::constants::{
+    MAX_MESSAGE_SIZE_BYTES, MAX_REACTION_SIZE_BYTES, MAX_STICKER_SIZE_BYTES,
+    MAX_SYSTEM_MESSAGE_SIZE_BYTES,
+};
+use crate::ids::MessageId;
+use serde::{Deserialize, Serialize};
+
+/// Current version of the outer envelope framing.
+pub const ENVELOPE_VERSION: u16 = 2;
+/// First envelope version whose encrypted plaintext is framed with an
+/// associated-data header and padding. A peer whose envelopes carry this
+/// version or newer can receive framed messages; older peers get the bare
+/// envelope.
+pub const FRAMED_ENVELOPE_VERSION: u16 = 2;
+/// Current version of the text sub-payload.
+pub const TEXT_PAYLOAD_VERSION: u16 = 1;
+/// Current version of the reaction sub-payload.
+pub const REACTION_PAYLOAD_VERSION: u16 = 1;
+/// Current version of the sticker sub-payload.
+pub const STICKER_PAYLOAD_VERSION: u16 = 1;
+/// Current version of the system sub-payload.
+pub const SYSTEM_PAYLOAD_VERSION: u16 = 1;
+/// Current version of the expiration timer sub-payload.
+pub const EXPIRATION_TIMER_PAYLOAD_VERSION: u16 = 1;
+
+/// Kind of payload carried inside an encrypted message.
+///
+/// Sent in the clear alongside the ciphertext so the server can apply
+/// per-type size limits without seeing the content.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadKind {
+    #[default]
+    Text,
+    Reaction,
+    Sticker,
+    System,
+}
+
+impl PayloadKind {
+    /// Maximum encrypted size accepted by the server for this payload kind.
+    pub fn max_encrypted_size(&self) -> usize {
+        match self {
+            PayloadKind::Text => MAX_MESSAGE_SIZE_BYTES,
+            PayloadKind::Reaction => MAX_REACTION_SIZE_BYTES,
+            PayloadKind::Sticker => MAX_STICKER_SIZE_BYTES,
+            PayloadKind::System => MAX_SYSTEM_MESSAGE_SIZE_BYTES,
+        }
+    }
+}
+
+/// Plaintext message body.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextPayload {
+    pub version: u16,
+    pub body: String,
+}
+
+/// Emoji reaction to another message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReactionPayload {
+    pub version: u16,
+    pub target_message_id: MessageId,
+    pub emoji: String,
+    /// `true` retracts a previously sent reaction.
+    #[serde(default)]
+    pub remove: bool,
+}
+
+/// Sticker reference. The sticker image itself is fetched separately.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StickerPayload {
+    pub version: u16,
+    pub pack_id: String,
+    pub sticker_id: String,
+}
+
+/// Client-generated system notice (e.g. "changed the group name").
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SystemPayload {
+    pub version: u16,
+    pub event: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<serde_json::Value>,
+}
+
+/// Disappearing-message timer change for the conversation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExpirationTimerPayload {
+    pub version: u16,
+    /// New message lifetime in seconds. `None` turns disappearing messages off.
+    #[serde(default)]
+    pub expires_in_seconds: Option<u32>,
+}
+
+/// Typed sub-payload carried inside a `MessageEnvelope`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MessagePayload {
+    Text(TextPayload),
+    Reaction(ReactionPayload),
+    Sticker(StickerPayload),
+    System(SystemPayload),
+    ExpirationTimer(ExpirationTimerPayload),
+}
+
+impl MessagePayload {
+    pub fn kind(&self) -> PayloadKind {
+        match self {
+            MessagePayload::Text(_) => PayloadKind::Text,
+            MessagePayload::Reaction(_) => PayloadKind::Reaction,
+            MessagePayload::Sticker(_) => PayloadKind::Sticker,
+            // Reported as a system notice so the server cannot tell timer
+            // changes apart from other control messages
+            MessagePayload::System(_) | MessagePayload::ExpirationTimer(_) => PayloadKind::System,
+        }
+    }
+
+    /// Version of the sub-payload, independent of the envelope version.
+    pub fn version(&self) -> u16 {
+        match self {
+            MessagePayload::Text(p) => p.version,
+            MessagePayload::Reaction(p) => p.version,
+            MessagePayload::Sticker(p) => p.version,
+            MessagePayload::System(p) => p.version,
+            MessagePayload::ExpirationTimer(p) => p.version,
+        }
+    }
+}
+
+/// Plaintext structure that is encrypted end-to-end as a message body.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageEnvelope {
+    pub version: u16,
+    pub payload: MessagePayload,
+}
+
+impl MessageEnvelope {
+    pub fn new(payload: MessagePayload) -> Self {
+        Self {
+            version: ENVELOPE_VERSION,
+            payload,
+        }
+    }
+
+    pub fn text(body: impl Into<String>) -> Self {
+        Self::new(MessagePayload::Text(TextPayload {
+            version: TEXT_PAYLOAD_VERSION,
+            body: body.into(),
+        }))
+    }
+
+    pub fn reaction(target_message_id: MessageId, emoji: impl Into<String>, remove: bool) -> Self {
+        Self::new(MessagePayload::Reaction(ReactionPayload {
+            version: REACTION_PAYLOAD_VERSION,
+            target_message_id,
+            emoji: emoji.into(),
+            remove,
+        }))
+    }
+
+    pub fn sticker(pack_id: impl Into<String>, sticker_id: impl Into<String>) -> Self {
+        Self::new(MessagePayload::Sticker(StickerPayload {
+            version: STICKER_PAYLOAD_VERSION,
+            pack_id: pack_id.into(),
+            sticker_id: sticker_id.into(),
+        }))
+    }
+
+    pub fn system(event: impl Into<String>, detail: Option<serde_json::Value>) -> Self {
+        Self::new(MessagePayload::System(SystemPayload {
+            version: SYSTEM_PAYLOAD_VERSION,
+            event: event.into(),
+            detail,
+        }))
+    }
+
+    pub fn expiration_timer(expires_in_seconds: Option<u32>) -> Self {
+        Self::new(MessagePayload::ExpirationTimer(ExpirationTimerPayload {
+            version: EXPIRATION_TIMER_PAYLOAD_VERSION,
+            expires_in_seconds,
+        }))
+    }
+
+    pub fn kind(&self) -> PayloadKind {
+        self.payload.kind()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_envelope_round_trip() {
+        let env = MessageEnvelope::text("hello");
+        let json = serde_json::to_string(&env).unwrap();
+        let back: MessageEnvelope = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, env);
+        assert_eq!(back.kind(), PayloadKind::Text);
+    }
+
+    #[test]
+    fn payload_is_tagged_by_kind() {
+        let env = MessageEnvelope::reaction(MessageId::new(), "\u{1f44d}", false);
+        let json = serde_json::to_value(&env).unwrap();
+        assert_eq!(json["version"], ENVELOPE_VERSION);
+        assert_eq!(json["payload"]["kind"], "reaction");
+        assert_eq!(json["payload"]["version"], REACTION_PAYLOAD_VERSION);
+    }
+
+    #[test]
+    fn sub_payload_versions_are_independent_of_envelope() {
+        let json = r#"{"version":1,"payload":{"kind":"sticker","version":7,"pack_id":"p","sticker_id":"s"}}"#;
+        let env: MessageEnvelope = serde_json::from_str(json).unwrap();
+        assert_eq!(env.version, 1);
+        assert_eq!(env.payload.version(), 7);
+        assert_eq!(env.kind(), PayloadKind::Sticker);
+    }
+
+    #[test]
+    fn reaction_remove_defaults_to_false() {
+        let id = MessageId::new();
+        let json = format!(
+            r#"{{"version":1,"payload":{{"kind":"reaction","version":1,"target_message_id":"{id}","emoji":"x"}}}}"#
+        );
+        let env: MessageEnvelope = serde_json::from_str(&json).unwrap();
+        match env.payload {
+            MessagePayload::Reaction(r) => assert!(!r.remove),
+            other => panic!("expected reaction, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn expiration_timer_is_sent_as_system_kind() {
+        let env = MessageEnvelope::expiration_timer(Some(3600));
+        assert_eq!(env.kind(), PayloadKind::System);
+        let json = serde_json::to_value(&env).unwrap();
+        assert_eq!(json["payload"]["kind"], "expiration_timer");
+        assert_eq!(json["payload"]["expires_in_seconds"], 3600);
+    }
+
+    #[test]
+    fn unknown_payload_kind_fails_deserialization() {
+        let json = r#"{"version":1,"payload":{"kind":"poll","version":1}}"#;
+        assert!(serde_json::from_str::<MessageEnvelope>(json).is_err());
+    }
+
+    #[test]
+    fn payload_kind_defaults_to_text() {
+        assert_eq!(PayloadKind::default(), PayloadKind::Text);
+    }
+
+    #[test]
+    fn small_payload_kinds_have_tighter_limits_than_text() {
+        assert!(
+            PayloadKind::Reaction.max_encrypted_size() < PayloadKind::Text.max_encrypted_size()
+        );
+        assert!(PayloadKind::Sticker.max_encrypted_size() < PayloadKind::Text.max_encrypted_size());
+    }
+}
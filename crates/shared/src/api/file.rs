@@ -1,4 +1,4 @@
-use crate::ids::{FileId, UserId};
+use crate::ids::{FileId, MessageId, UploadSessionId, UserId};
 use serde::{Deserialize, Serialize};
 
 /// Response returned after a successful file upload.
@@ -22,6 +22,86 @@ pub struct FileMetaResponse {
     pub size_bytes: i64,
     pub uploader_id: UserId,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    /// When the file is deleted under its guild's retention policy, or
+    /// `None` if it is kept indefinitely.
+    #[serde(default)]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Upload scan result: "pending", "clean" or "flagged". Only clean
+    /// files can be downloaded.
+    pub scan_status: String,
+}
+
+/// Request body for PUT /api/channels/{channel_id}/messages/{message_id}/attachments.
+///
+/// Replaces the message's attachments with these files, in order. Files must
+/// have been uploaded by the message sender.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct SetAttachmentsRequest {
+    pub file_ids: Vec<FileId>,
+}
+
+/// Files attached to a message, in display order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct MessageAttachmentsResponse {
+    pub message_id: MessageId,
+    pub files: Vec<FileResponse>,
+}
+
+/// Request body for POST /api/files/sessions.
+///
+/// Starts a resumable upload of an encrypted file that is sent in
+/// fixed-size chunks. `size_bytes` is the total encrypted size.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct CreateUploadSessionRequest {
+    pub file_name: String,
+    pub mime_type: String,
+    pub encrypted_blob_key: String,
+    pub size_bytes: i64,
+}
+
+/// State of a resumable upload session.
+///
+/// Every chunk except the last must be exactly `chunk_size` bytes. After a
+/// dropped connection, clients fetch the session and send only the chunks
+/// missing from `received_chunks`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct UploadSessionResponse {
+    pub id: UploadSessionId,
+    pub size_bytes: i64,
+    pub chunk_size: i64,
+    pub chunk_count: i32,
+    /// Indexes of chunks stored so far, ascending.
+    pub received_chunks: Vec<i32>,
+    /// Unfinished sessions and their chunks are deleted after this time.
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Response for GET /api/files/{file_id}/download-url.
+///
+/// `url` is a path relative to the API base URL. It can be fetched without
+/// a bearer token until `expires_at`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct DownloadUrlResponse {
+    pub url: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Response for GET /api/users/me/storage and GET /api/guilds/{guild_id}/storage.
+///
+/// `used_bytes` counts every stored file; pending upload sessions are not
+/// included until they complete.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct StorageUsageResponse {
+    pub used_bytes: i64,
+    pub file_count: i64,
+    /// Configured quota, or `None` when storage is unlimited.
+    pub quota_bytes: Option<i64>,
 }
 
 #[cfg(test)]
@@ -52,6 +132,8 @@ mod tests {
             size_bytes: 2048,
             uploader_id: UserId::new(),
             created_at: chrono::Utc::now(),
+            expires_at: None,
+            scan_status: "clean".into(),
         };
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json.get("uploader_id").is_some());
@@ -72,4 +154,42 @@ mod tests {
         assert_eq!(back.file_name, "test.bin");
         assert_eq!(back.size_bytes, 1024);
     }
+
+    #[test]
+    fn set_attachments_request_roundtrip() {
+        let req = SetAttachmentsRequest {
+            file_ids: vec![FileId::new(), FileId::new()],
+        };
+        let json = serde_json::to_string(&req).unwrap();
+        let back: SetAttachmentsRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.file_ids, req.file_ids);
+    }
+
+    #[test]
+    fn upload_session_response_roundtrip() {
+        let resp = UploadSessionResponse {
+            id: UploadSessionId::new(),
+            size_bytes: 20_000_000,
+            chunk_size: 8_388_608,
+            chunk_count: 3,
+            received_chunks: vec![0, 2],
+            expires_at: chrono::Utc::now(),
+        };
+        let json = serde_json::to_string(&resp).unwrap();
+        let back: UploadSessionResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.id, resp.id);
+        assert_eq!(back.received_chunks, vec![0, 2]);
+    }
+
+    #[test]
+    fn unlimited_storage_serializes_null_quota() {
+        let resp = StorageUsageResponse {
+            used_bytes: 4096,
+            file_count: 2,
+            quota_bytes: None,
+        };
+        let json = serde_json::to_value(&resp).unwrap();
+        assert_eq!(json["used_bytes"], 4096);
+        assert!(json["quota_bytes"].is_null());
+    }
 }
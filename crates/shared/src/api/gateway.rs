@@ -0,0 +1,150 @@
+//! Gateway event schema shared by WebSocket delivery and outgoing webhooks.
+//!
+//! A WebSocket connection or webhook only receives events whose intent it
+//! subscribed to. Events carry the same JSON body on both transports.
+
+use bitflags::bitflags;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::ids::{GuildId, RoleId, UserId};
+
+bitflags! {
+    /// Event categories a gateway consumer can subscribe to.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct GatewayIntents: u64 {
+        /// Presence updates of guild members.
+        const GUILD_PRESENCES = 1 << 0;
+        /// Member joins, leaves, kicks, bans and role changes.
+        const GUILD_MEMBERS   = 1 << 1;
+        /// Members joining, leaving and muting in voice channels. Gateway
+        /// only; never delivered to webhooks.
+        const GUILD_VOICE_STATES = 1 << 2;
+    }
+}
+
+impl Default for GatewayIntents {
+    /// Connections that do not ask for specific intents receive everything.
+    fn default() -> Self {
+        GatewayIntents::all()
+    }
+}
+
+impl serde::Serialize for GatewayIntents {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.bits().serialize(serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for GatewayIntents {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bits = u64::deserialize(deserializer)?;
+        Ok(GatewayIntents::from_bits_truncate(bits))
+    }
+}
+
+/// What happened to a guild membership.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub enum MemberEventKind {
+    Join,
+    Leave,
+    Kick,
+    Ban,
+    RoleAdded,
+    RoleRemoved,
+}
+
+impl MemberEventKind {
+    /// Dotted event name, sent as the webhook `X-OpenConv-Event` header.
+    pub fn event_name(self) -> &'static str {
+        match self {
+            MemberEventKind::Join => "member.join",
+            MemberEventKind::Leave => "member.leave",
+            MemberEventKind::Kick => "member.kick",
+            MemberEventKind::Ban => "member.ban",
+            MemberEventKind::RoleAdded => "member.role_added",
+            MemberEventKind::RoleRemoved => "member.role_removed",
+        }
+    }
+}
+
+/// A guild membership change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct MemberEvent {
+    pub guild_id: GuildId,
+    pub user_id: UserId,
+    pub kind: MemberEventKind,
+    /// Who caused the change; `None` when the member acted on themselves.
+    pub actor_id: Option<UserId>,
+    /// Set for `role_added` and `role_removed`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role_id: Option<RoleId>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl MemberEvent {
+    pub fn new(guild_id: GuildId, user_id: UserId, kind: MemberEventKind) -> Self {
+        Self {
+            guild_id,
+            user_id,
+            kind,
+            actor_id: None,
+            role_id: None,
+            occurred_at: Utc::now(),
+        }
+    }
+
+    pub fn with_actor(mut self, actor_id: UserId) -> Self {
+        self.actor_id = Some(actor_id);
+        self
+    }
+
+    pub fn with_role(mut self, role_id: RoleId) -> Self {
+        self.role_id = Some(role_id);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intents_default_to_all() {
+        assert_eq!(GatewayIntents::default(), GatewayIntents::all());
+    }
+
+    #[test]
+    fn intents_deserialize_truncates_unknown_bits() {
+        let intents: GatewayIntents = serde_json::from_value(serde_json::json!(u64::MAX)).unwrap();
+        assert_eq!(intents, GatewayIntents::all());
+        let json = serde_json::to_string(&GatewayIntents::GUILD_MEMBERS).unwrap();
+        assert_eq!(json, "2");
+    }
+
+    #[test]
+    fn member_event_serializes_kind_as_snake_case() {
+        let event = MemberEvent::new(GuildId::new(), UserId::new(), MemberEventKind::RoleAdded)
+            .with_role(RoleId::new());
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["kind"], "role_added");
+        assert!(json["role_id"].is_string());
+        assert!(json["actor_id"].is_null());
+    }
+
+    #[test]
+    fn member_event_omits_role_id_when_unset() {
+        let event = MemberEvent::new(GuildId::new(), UserId::new(), MemberEventKind::Join);
+        let json = serde_json::to_value(&event).unwrap();
+        assert!(json.get("role_id").is_none());
+    }
+
+    #[test]
+    fn event_names_are_dotted() {
+        assert_eq!(MemberEventKind::Join.event_name(), "member.join");
+        assert_eq!(MemberEventKind::Ban.event_name(), "member.ban");
+    }
+}
@@ -8,12 +8,103 @@ pub struct CreateGuildRequest {
     pub name: String,
 }
 
+/// Longest file retention a guild can configure, in days.
+pub const MAX_FILE_RETENTION_DAYS: i32 = 3650;
+
+/// Longest message retention a guild can configure, in days.
+pub const MAX_MESSAGE_RETENTION_DAYS: i32 = 3650;
+
+/// How established an account must be to join a guild through an invite.
+///
+/// Each level includes the requirements of the ones below it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationLevel {
+    /// Anyone with an invite can join.
+    #[default]
+    None,
+    /// The account has a verified email address.
+    Low,
+    /// The account is at least 5 minutes old.
+    Medium,
+    /// The account is at least a day old and its email address was
+    /// verified at least an hour ago.
+    High,
+}
+
+impl VerificationLevel {
+    /// Name used in the database and the API.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+        }
+    }
+
+    /// Inverse of [`VerificationLevel::as_str`].
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "none" => Some(Self::None),
+            "low" => Some(Self::Low),
+            "medium" => Some(Self::Medium),
+            "high" => Some(Self::High),
+            _ => None,
+        }
+    }
+
+    /// Whether an account created at `created_at`, whose email was verified
+    /// at `email_verified_at`, meets this level at `now`.
+    pub fn is_met_by(
+        self,
+        created_at: chrono::DateTime<chrono::Utc>,
+        email_verified_at: Option<chrono::DateTime<chrono::Utc>>,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> bool {
+        let (account_age, email_age) = match self {
+            Self::None => return true,
+            Self::Low => (chrono::Duration::zero(), chrono::Duration::zero()),
+            Self::Medium => (chrono::Duration::minutes(5), chrono::Duration::zero()),
+            Self::High => (chrono::Duration::days(1), chrono::Duration::hours(1)),
+        };
+        let Some(email_verified_at) = email_verified_at else {
+            return false;
+        };
+        now - created_at >= account_age && now - email_verified_at >= email_age
+    }
+}
+
 /// Request to update guild properties.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct UpdateGuildRequest {
     pub name: Option<String>,
     pub icon_url: Option<String>,
+    /// Queue a background prune of a member's messages and files when they are banned.
+    #[serde(default)]
+    pub prune_on_ban: Option<bool>,
+    /// Maximum number of members; invites stop working once reached. 0 removes the limit.
+    #[serde(default)]
+    pub max_members: Option<i32>,
+    /// Delete files uploaded to the guild's channels this many days after
+    /// upload. 0 keeps files forever.
+    #[serde(default)]
+    pub file_retention_days: Option<i32>,
+    /// Delete messages in the guild's channels this many days after they
+    /// were sent. Owner only. 0 keeps messages forever.
+    #[serde(default)]
+    pub message_retention_days: Option<i32>,
+    /// Requirements an account must meet to join through an invite.
+    #[serde(default)]
+    pub verification_level: Option<VerificationLevel>,
+    /// Refuse invite joins beyond this many per minute. 0 removes the limit.
+    #[serde(default)]
+    pub max_joins_per_minute: Option<i32>,
+    /// Lockdown: while true, no invite to the guild can be accepted.
+    #[serde(default)]
+    pub invites_paused: Option<bool>,
 }
 
 /// Guild details response.
@@ -46,6 +137,17 @@ pub struct GuildMemberResponse {
     pub roles: Vec<RoleSummary>,
 }
 
+/// Most user IDs accepted by one bulk member lookup.
+pub const MAX_BULK_MEMBER_IDS: usize = 100;
+
+/// Request body for POST /api/guilds/:guild_id/members/bulk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct BulkMembersRequest {
+    /// At most [`MAX_BULK_MEMBER_IDS`]. IDs of non-members are skipped.
+    pub user_ids: Vec<UserId>,
+}
+
 /// Minimal role info included in member listings.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
@@ -55,6 +157,96 @@ pub struct RoleSummary {
     pub position: i32,
 }
 
+/// Request to ban a guild member.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct BanMemberRequest {
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Response after banning a member. `prune_job_id` is set when the guild's
+/// `prune_on_ban` setting queued a cleanup of the member's content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct BanMemberResponse {
+    pub user_id: UserId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prune_job_id: Option<uuid::Uuid>,
+}
+
+/// Progress of a background member prune job.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct PruneJobResponse {
+    pub id: uuid::Uuid,
+    pub user_id: UserId,
+    pub requested_by: Option<UserId>,
+    /// One of `pending`, `running`, `completed`, `failed`.
+    pub status: String,
+    pub messages_tombstoned: i64,
+    pub files_deleted: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// List of prune jobs for a guild, newest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct PruneJobListResponse {
+    pub jobs: Vec<PruneJobResponse>,
+}
+
+/// Request body for POST /api/guilds/:guild_id/prune.
+///
+/// Members are pruned when they joined more than `days` days ago, have not
+/// sent a message in the guild in that time, and hold no role besides the
+/// default member role.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct InactivePruneRequest {
+    pub days: u32,
+    /// Only count matching members; nothing is removed.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Response for POST /api/guilds/:guild_id/prune.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct InactivePruneResponse {
+    /// Members matching the prune criteria at request time.
+    pub member_count: i64,
+    /// Set when the prune was queued (not a dry run).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<uuid::Uuid>,
+}
+
+/// Progress of a background inactive-member prune.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct InactivePruneJobResponse {
+    pub id: uuid::Uuid,
+    pub requested_by: Option<UserId>,
+    pub inactive_days: i32,
+    /// One of `pending`, `running`, `completed`, `failed`.
+    pub status: String,
+    pub members_removed: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// List of inactive-member prune jobs for a guild, newest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct InactivePruneJobListResponse {
+    pub jobs: Vec<InactivePruneJobResponse>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -117,12 +309,53 @@ mod tests {
         let req = UpdateGuildRequest {
             name: Some("New Name".into()),
             icon_url: None,
+            prune_on_ban: None,
+            max_members: None,
+            file_retention_days: None,
+            message_retention_days: None,
+            verification_level: None,
+            max_joins_per_minute: None,
+            invites_paused: None,
         };
         let json = serde_json::to_string(&req).unwrap();
         let back: UpdateGuildRequest = serde_json::from_str(&json).unwrap();
         assert_eq!(back.name, Some("New Name".into()));
     }
 
+    #[test]
+    fn verification_level_names_round_trip() {
+        for level in [
+            VerificationLevel::None,
+            VerificationLevel::Low,
+            VerificationLevel::Medium,
+            VerificationLevel::High,
+        ] {
+            assert_eq!(VerificationLevel::parse(level.as_str()), Some(level));
+            assert_eq!(
+                serde_json::to_value(level).unwrap(),
+                serde_json::Value::from(level.as_str())
+            );
+        }
+        assert_eq!(VerificationLevel::parse("extreme"), None);
+    }
+
+    #[test]
+    fn verification_levels_check_account_and_email_age() {
+        let now = chrono::Utc::now();
+        let minutes_ago = |m| now - chrono::Duration::minutes(m);
+
+        assert!(VerificationLevel::None.is_met_by(now, None, now));
+        assert!(!VerificationLevel::Low.is_met_by(now, None, now));
+        assert!(VerificationLevel::Low.is_met_by(now, Some(now), now));
+
+        assert!(!VerificationLevel::Medium.is_met_by(minutes_ago(4), Some(minutes_ago(4)), now));
+        assert!(VerificationLevel::Medium.is_met_by(minutes_ago(5), Some(minutes_ago(5)), now));
+
+        let day_old = minutes_ago(24 * 60);
+        assert!(VerificationLevel::High.is_met_by(day_old, Some(day_old), now));
+        assert!(!VerificationLevel::High.is_met_by(day_old, Some(minutes_ago(30)), now));
+    }
+
     #[test]
     fn guild_member_response_roundtrip() {
         let resp = GuildMemberResponse {
@@ -140,4 +373,38 @@ mod tests {
         assert_eq!(back.display_name, "Alice");
         assert_eq!(back.roles.len(), 1);
     }
+
+    #[test]
+    fn ban_member_response_omits_missing_prune_job() {
+        let resp = BanMemberResponse {
+            user_id: UserId::new(),
+            prune_job_id: None,
+        };
+        let json = serde_json::to_value(&resp).unwrap();
+        assert!(json.get("prune_job_id").is_none());
+    }
+
+    #[test]
+    fn update_guild_request_prune_on_ban_defaults_to_none() {
+        let req: UpdateGuildRequest = serde_json::from_str(r#"{"name":"x"}"#).unwrap();
+        assert!(req.prune_on_ban.is_none());
+    }
+
+    #[test]
+    fn inactive_prune_request_defaults_to_real_run() {
+        let req: InactivePruneRequest = serde_json::from_str(r#"{"days":30}"#).unwrap();
+        assert_eq!(req.days, 30);
+        assert!(!req.dry_run);
+    }
+
+    #[test]
+    fn inactive_prune_response_omits_job_for_dry_run() {
+        let resp = InactivePruneResponse {
+            member_count: 4,
+            job_id: None,
+        };
+        let json = serde_json::to_value(&resp).unwrap();
+        assert_eq!(json["member_count"], 4);
+        assert!(json.get("job_id").is_none());
+    }
 }
@@ -37,6 +37,38 @@ pub struct InviteInfoResponse {
     pub inviter_display_name: Option<String>,
 }
 
+/// Stable reason codes explaining why the caller cannot accept an invite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub enum InviteBlocker {
+    InviteExpired,
+    InviteExhausted,
+    /// The guild is in lockdown and accepts no invites.
+    InvitesPaused,
+    Banned,
+    /// The account does not meet the guild's verification level.
+    VerificationRequired,
+    AlreadyMember,
+    GuildFull,
+}
+
+/// Response for GET /api/invites/:code/preflight.
+///
+/// `can_join` is true exactly when `blockers` is empty. The result is
+/// advisory: accept re-checks everything atomically.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct InvitePreflightResponse {
+    pub code: String,
+    pub guild_id: GuildId,
+    pub guild_name: String,
+    pub member_count: i64,
+    pub max_members: Option<i32>,
+    pub can_join: bool,
+    pub blockers: Vec<InviteBlocker>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -83,4 +115,12 @@ mod tests {
         assert_eq!(back.guild_name, "Test Guild");
         assert_eq!(back.member_count, 42);
     }
+
+    #[test]
+    fn invite_blocker_serializes_as_snake_case() {
+        let json = serde_json::to_string(&InviteBlocker::AlreadyMember).unwrap();
+        assert_eq!(json, r#""already_member""#);
+        let back: InviteBlocker = serde_json::from_str(r#""guild_full""#).unwrap();
+        assert_eq!(back, InviteBlocker::GuildFull);
+    }
 }
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+/// Square edge lengths, in pixels, rendered for every avatar and guild icon.
+/// Request one with `?size=`; other values round up to the next of these.
+pub const IMAGE_SIZES: [u32; 3] = [64, 128, 512];
+
+/// Largest accepted avatar or guild icon upload.
+pub const MAX_IMAGE_UPLOAD_BYTES: usize = 8 * 1024 * 1024;
+
+/// Largest accepted width or height of an uploaded image.
+pub const MAX_IMAGE_DIMENSION: u32 = 4096;
+
+/// Response after uploading an avatar or guild icon.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct ImageAssetResponse {
+    /// URL of the image, versioned so it changes on every upload.
+    pub url: String,
+    /// Sizes available through `?size=`, besides the original.
+    pub sizes: Vec<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_sizes_are_ascending() {
+        assert!(IMAGE_SIZES.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(IMAGE_SIZES.iter().all(|size| *size <= MAX_IMAGE_DIMENSION));
+    }
+
+    #[test]
+    fn image_asset_response_roundtrip() {
+        let resp = ImageAssetResponse {
+            url: "/api/users/x/avatar?v=1".into(),
+            sizes: IMAGE_SIZES.to_vec(),
+        };
+        let json = serde_json::to_string(&resp).unwrap();
+        let back: ImageAssetResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.sizes, vec![64, 128, 512]);
+    }
+}
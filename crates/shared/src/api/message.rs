@@ -1,3 +1,4 @@
+use crate::api::envelope::PayloadKind;
 use crate::ids::{ChannelId, DmChannelId, MessageId, UserId};
 use serde::{Deserialize, Serialize};
 
@@ -29,6 +30,9 @@ pub struct SendMessageRequest {
     #[serde(with = "base64_serde")]
     #[cfg_attr(feature = "utoipa", schema(value_type = String))]
     pub nonce: Vec<u8>,
+    /// Kind of the encrypted envelope payload; selects the size limit.
+    #[serde(default)]
+    pub payload_kind: PayloadKind,
 }
 
 /// Message details response with encrypted content.
@@ -67,6 +71,40 @@ pub struct MessageHistoryResponse {
     pub has_more: bool,
 }
 
+/// Request body for a bulk history backfill stream.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct HistoryBackfillRequest {
+    /// Checkpoint from a previous stream. Resumes after the last delivered batch.
+    #[serde(default)]
+    pub checkpoint: Option<String>,
+    /// Oldest message timestamp to include. Omit to backfill the full history.
+    #[serde(default)]
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// One line of an NDJSON history backfill stream.
+///
+/// Batches run newest to oldest. Every stream ends with a `complete` or
+/// `error` frame; a `checkpoint` on either can be sent back to resume.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HistoryBackfillFrame {
+    Batch {
+        messages: Vec<MessageResponse>,
+        checkpoint: String,
+    },
+    Complete {
+        has_more: bool,
+        checkpoint: Option<String>,
+    },
+    Error {
+        error: String,
+        checkpoint: Option<String>,
+    },
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -178,6 +216,7 @@ mod tests {
         let req = SendMessageRequest {
             encrypted_content: b"message payload".to_vec(),
             nonce: b"msg_nonce".to_vec(),
+            payload_kind: PayloadKind::Reaction,
         };
 
         let json_str = serde_json::to_string(&req).unwrap();
@@ -185,6 +224,14 @@ mod tests {
 
         assert_eq!(deserialized.encrypted_content, b"message payload");
         assert_eq!(deserialized.nonce, b"msg_nonce");
+        assert_eq!(deserialized.payload_kind, PayloadKind::Reaction);
+    }
+
+    #[test]
+    fn send_message_request_payload_kind_defaults_to_text() {
+        let json = r#"{"encrypted_content": "AA==", "nonce": "AA=="}"#;
+        let req: SendMessageRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(req.payload_kind, PayloadKind::Text);
     }
 
     #[test]
@@ -252,4 +299,30 @@ mod tests {
         let back: MessageResponse = serde_json::from_str(&json_str).unwrap();
         assert_eq!(back.dm_channel_id, Some(dm_id));
     }
+
+    #[test]
+    fn history_backfill_request_defaults_to_full_history() {
+        let req: HistoryBackfillRequest = serde_json::from_str("{}").unwrap();
+        assert!(req.checkpoint.is_none());
+        assert!(req.since.is_none());
+    }
+
+    #[test]
+    fn history_backfill_frames_are_tagged_by_type() {
+        let batch = HistoryBackfillFrame::Batch {
+            messages: vec![],
+            checkpoint: "abc".into(),
+        };
+        let json = serde_json::to_value(&batch).unwrap();
+        assert_eq!(json["type"], "batch");
+        assert_eq!(json["checkpoint"], "abc");
+
+        let complete = HistoryBackfillFrame::Complete {
+            has_more: true,
+            checkpoint: Some("abc".into()),
+        };
+        let json = serde_json::to_value(&complete).unwrap();
+        assert_eq!(json["type"], "complete");
+        assert_eq!(json["has_more"], true);
+    }
 }
@@ -1,10 +1,22 @@
+pub mod admin;
 pub mod auth;
+pub mod automod;
+pub mod bot;
+pub mod bridge;
 pub mod channel;
 pub mod dm_channel;
+pub mod envelope;
 pub mod file;
+pub mod gateway;
 pub mod guild;
 pub mod invite;
+pub mod media;
 pub mod message;
+pub mod oauth;
+pub mod policy;
 pub mod role;
+pub mod settings;
 pub mod user;
+pub mod voice;
+pub mod webhook;
 pub mod ws;
@@ -0,0 +1,139 @@
+use crate::ids::UserId;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// What a third-party app may do with a user's OAuth token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub enum OAuthScope {
+    /// Read the user's ID, display name and avatar.
+    #[serde(rename = "identify")]
+    Identify,
+    /// List the guilds the user is a member of.
+    #[serde(rename = "guilds.read")]
+    GuildsRead,
+}
+
+impl OAuthScope {
+    pub const ALL: [OAuthScope; 2] = [OAuthScope::Identify, OAuthScope::GuildsRead];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OAuthScope::Identify => "identify",
+            OAuthScope::GuildsRead => "guilds.read",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.as_str() == s)
+    }
+}
+
+/// Request body for POST /api/oauth/clients.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct CreateOAuthClientRequest {
+    pub name: String,
+    /// Exact redirect URIs the app may send users back to.
+    pub redirect_uris: Vec<String>,
+    /// Confidential clients (server-side apps) get a secret they must send
+    /// when redeeming codes. Public clients rely on PKCE alone.
+    #[serde(default)]
+    pub confidential: bool,
+}
+
+/// A registered third-party app. The secret is only returned when the
+/// client is created.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct OAuthClientResponse {
+    pub client_id: uuid::Uuid,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub confidential: bool,
+    pub owner_id: UserId,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+}
+
+/// Response for GET /api/oauth/clients.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct OAuthClientListResponse {
+    pub clients: Vec<OAuthClientResponse>,
+}
+
+/// What the consent screen shows for an authorization request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct AuthorizeInfoResponse {
+    pub client_id: uuid::Uuid,
+    pub client_name: String,
+    pub scopes: Vec<OAuthScope>,
+    /// The user already granted every requested scope to this app, so the
+    /// client may approve without asking again.
+    pub already_granted: bool,
+}
+
+/// Response for an approved authorization request: where to send the
+/// user's browser, with the code and state attached.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct AuthorizeResponse {
+    pub redirect_to: String,
+}
+
+/// RFC 6749 token response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct OAuthTokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: u64,
+    pub refresh_token: String,
+    /// Space-separated granted scopes.
+    pub scope: String,
+}
+
+/// An app the user has authorized.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct OAuthConsentResponse {
+    pub client_id: uuid::Uuid,
+    pub client_name: String,
+    pub scopes: Vec<OAuthScope>,
+    pub granted_at: DateTime<Utc>,
+}
+
+/// Response for GET /api/oauth/consents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct OAuthConsentListResponse {
+    pub consents: Vec<OAuthConsentResponse>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes_use_dotted_wire_names() {
+        assert_eq!(
+            serde_json::to_string(&OAuthScope::GuildsRead).unwrap(),
+            r#""guilds.read""#
+        );
+        for scope in OAuthScope::ALL {
+            assert_eq!(OAuthScope::parse(scope.as_str()), Some(scope));
+        }
+        assert_eq!(OAuthScope::parse("admin"), None);
+    }
+
+    #[test]
+    fn create_client_request_defaults_to_public() {
+        let req: CreateOAuthClientRequest =
+            serde_json::from_str(r#"{"name":"App","redirect_uris":["https://app.example/cb"]}"#)
+                .unwrap();
+        assert!(!req.confidential);
+    }
+}
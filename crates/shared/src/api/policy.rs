@@ -0,0 +1,63 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Request body for POST /api/policies/accept.
+///
+/// Each version must equal the currently published one. Omitted documents
+/// are left unchanged.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct AcceptPolicyRequest {
+    #[serde(default)]
+    pub terms_version: Option<String>,
+    #[serde(default)]
+    pub privacy_version: Option<String>,
+}
+
+/// Published policy versions and what the calling user has accepted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct PolicyStatusResponse {
+    /// Current terms-of-service version, if the instance publishes one.
+    pub terms_version: Option<String>,
+    /// Current privacy-policy version, if the instance publishes one.
+    pub privacy_version: Option<String>,
+    /// Most recent terms-of-service version the user accepted.
+    pub accepted_terms_version: Option<String>,
+    pub accepted_terms_at: Option<DateTime<Utc>>,
+    /// Most recent privacy-policy version the user accepted.
+    pub accepted_privacy_version: Option<String>,
+    pub accepted_privacy_at: Option<DateTime<Utc>>,
+    /// True while a published version has not been accepted.
+    pub policy_update_required: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_policy_request_fields_are_optional() {
+        let req: AcceptPolicyRequest =
+            serde_json::from_str(r#"{"terms_version":"2024-06"}"#).unwrap();
+        assert_eq!(req.terms_version.as_deref(), Some("2024-06"));
+        assert!(req.privacy_version.is_none());
+    }
+
+    #[test]
+    fn policy_status_response_serde() {
+        let resp = PolicyStatusResponse {
+            terms_version: Some("2".into()),
+            privacy_version: None,
+            accepted_terms_version: Some("1".into()),
+            accepted_terms_at: Some(Utc::now()),
+            accepted_privacy_version: None,
+            accepted_privacy_at: None,
+            policy_update_required: true,
+        };
+        let json = serde_json::to_string(&resp).unwrap();
+        assert!(json.contains("\"policy_update_required\":true"));
+        let back: PolicyStatusResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.accepted_terms_version.as_deref(), Some("1"));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::ids::{GuildId, RoleId};
+use crate::ids::{ChannelId, GuildId, RoleId, UserId};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
@@ -32,6 +32,33 @@ pub struct RoleResponse {
     pub created_at: DateTime<Utc>,
 }
 
+/// Query parameters for previewing effective permissions.
+///
+/// Exactly one of `role_id` or `user_id` must be given.
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema, utoipa::IntoParams))]
+pub struct PermissionPreviewQuery {
+    /// Preview a role as held by a plain member (the role plus the base member role).
+    pub role_id: Option<RoleId>,
+    /// Preview an existing member's actual role set.
+    pub user_id: Option<UserId>,
+    /// Evaluate in the context of this channel.
+    pub channel_id: Option<ChannelId>,
+}
+
+/// Effective permission set computed by the preview endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct PermissionPreviewResponse {
+    pub permissions: u64,
+    /// Names of the set flags, e.g. `"SEND_MESSAGES"`.
+    pub permission_names: Vec<String>,
+    pub is_owner: bool,
+    /// Roles whose permissions were combined, ordered by position ascending.
+    pub role_ids: Vec<RoleId>,
+    pub channel_id: Option<ChannelId>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -63,4 +90,13 @@ mod tests {
         let back: RoleResponse = serde_json::from_str(&json).unwrap();
         assert_eq!(back.name, "admin");
     }
+
+    #[test]
+    fn permission_preview_query_accepts_partial_params() {
+        let q: PermissionPreviewQuery =
+            serde_json::from_value(serde_json::json!({ "role_id": RoleId::new() })).unwrap();
+        assert!(q.role_id.is_some());
+        assert!(q.user_id.is_none());
+        assert!(q.channel_id.is_none());
+    }
 }
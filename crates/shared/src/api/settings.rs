@@ -0,0 +1,317 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Smallest allowed message font size, in pixels.
+pub const MIN_MESSAGE_FONT_SIZE: u16 = 12;
+/// Largest allowed message font size, in pixels.
+pub const MAX_MESSAGE_FONT_SIZE: u16 = 24;
+
+/// Size at which emoji are rendered inline in messages.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum EmojiSize {
+    Small,
+    #[default]
+    Medium,
+    Large,
+}
+
+/// Settings that change how messages are rendered.
+///
+/// Instance defaults come from the server's branding config; each user can
+/// override individual fields, and the resolved values are the same on all
+/// of that user's devices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(default)]
+pub struct RenderSettings {
+    /// Message text size in pixels.
+    pub message_font_size: u16,
+    /// Show image and video attachments inline instead of as links.
+    pub inline_media: bool,
+    /// Fetch and show previews for links in messages.
+    pub link_previews: bool,
+    pub emoji_size: EmojiSize,
+    /// Dense message list without avatars.
+    pub compact_mode: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            message_font_size: 16,
+            inline_media: true,
+            link_previews: true,
+            emoji_size: EmojiSize::Medium,
+            compact_mode: false,
+        }
+    }
+}
+
+/// A user's overrides of the instance render defaults. `None` fields follow
+/// the instance default, so later changes to the default still apply.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct RenderSettingsOverrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_font_size: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inline_media: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_previews: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emoji_size: Option<EmojiSize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compact_mode: Option<bool>,
+}
+
+impl RenderSettingsOverrides {
+    /// Resolve the effective settings on top of `defaults`.
+    pub fn apply_to(&self, defaults: &RenderSettings) -> RenderSettings {
+        RenderSettings {
+            message_font_size: self.message_font_size.unwrap_or(defaults.message_font_size),
+            inline_media: self.inline_media.unwrap_or(defaults.inline_media),
+            link_previews: self.link_previews.unwrap_or(defaults.link_previews),
+            emoji_size: self.emoji_size.unwrap_or(defaults.emoji_size),
+            compact_mode: self.compact_mode.unwrap_or(defaults.compact_mode),
+        }
+    }
+}
+
+/// Entries kept in a user's frequently-used emoji list.
+pub const MAX_FREQUENT_EMOJI: usize = 36;
+/// Longest accepted emoji shortcode, without the surrounding colons.
+pub const MAX_EMOJI_SHORTCODE_LEN: usize = 64;
+/// Most shortcode uses accepted in one emoji preferences update.
+pub const MAX_EMOJI_USES_PER_UPDATE: usize = 256;
+
+/// Default skin tone applied to emoji that support modifiers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum SkinTone {
+    #[default]
+    Default,
+    Light,
+    MediumLight,
+    Medium,
+    MediumDark,
+    Dark,
+}
+
+/// An entry in the frequently-used emoji list.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct FrequentEmoji {
+    /// Shortcode without colons, e.g. "thumbsup".
+    pub shortcode: String,
+    pub count: u32,
+    pub last_used_at: DateTime<Utc>,
+}
+
+/// A user's emoji picker preferences, synced across devices.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(default)]
+pub struct EmojiPreferences {
+    pub skin_tone: SkinTone,
+    /// Ranked most used first, at most [`MAX_FREQUENT_EMOJI`] entries.
+    pub frequent: Vec<FrequentEmoji>,
+}
+
+impl EmojiPreferences {
+    /// Merge a ranking from another device. Each shortcode keeps the higher
+    /// count and the later use, so merging the same list twice is a no-op.
+    pub fn merge_frequent(&mut self, other: &[FrequentEmoji]) {
+        for entry in other {
+            match self
+                .frequent
+                .iter_mut()
+                .find(|e| e.shortcode == entry.shortcode)
+            {
+                Some(existing) => {
+                    existing.count = existing.count.max(entry.count);
+                    existing.last_used_at = existing.last_used_at.max(entry.last_used_at);
+                }
+                None => self.frequent.push(entry.clone()),
+            }
+        }
+        self.rank();
+    }
+
+    /// Count one use of each shortcode.
+    pub fn record_use(&mut self, shortcodes: &[String], now: DateTime<Utc>) {
+        for shortcode in shortcodes {
+            match self.frequent.iter_mut().find(|e| &e.shortcode == shortcode) {
+                Some(existing) => {
+                    existing.count = existing.count.saturating_add(1);
+                    existing.last_used_at = now;
+                }
+                None => self.frequent.push(FrequentEmoji {
+                    shortcode: shortcode.clone(),
+                    count: 1,
+                    last_used_at: now,
+                }),
+            }
+        }
+        self.rank();
+    }
+
+    fn rank(&mut self) {
+        self.frequent.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then(b.last_used_at.cmp(&a.last_used_at))
+                .then(a.shortcode.cmp(&b.shortcode))
+        });
+        self.frequent.truncate(MAX_FREQUENT_EMOJI);
+    }
+}
+
+/// Request body for PATCH /api/users/me/settings/emoji.
+///
+/// Unlike the render overrides, emoji preferences are merged rather than
+/// replaced, so devices never need to refetch before saving.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct UpdateEmojiPreferencesRequest {
+    /// New default skin tone; unchanged when absent.
+    #[serde(default)]
+    pub skin_tone: Option<SkinTone>,
+    /// A ranking from another device (e.g. one kept while offline).
+    #[serde(default)]
+    pub frequent: Vec<FrequentEmoji>,
+    /// Shortcodes used since the last sync; each counts one use.
+    #[serde(default)]
+    pub used: Vec<String>,
+}
+
+/// Request body for PUT /api/users/me/settings.
+///
+/// Replaces the stored overrides. When `expected_version` is set and the
+/// stored version differs (another device saved first), the update is
+/// rejected with 409 so the client can refetch and merge.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct UpdateUserSettingsRequest {
+    #[serde(default)]
+    pub render: RenderSettingsOverrides,
+    #[serde(default)]
+    pub expected_version: Option<i64>,
+}
+
+/// A user's synced settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct UserSettingsResponse {
+    /// Effective render settings: the user's overrides on top of the
+    /// instance defaults.
+    pub render: RenderSettings,
+    /// Fields the user has set explicitly.
+    pub render_overrides: RenderSettingsOverrides,
+    /// Instance defaults from branding config.
+    pub render_defaults: RenderSettings,
+    #[serde(default)]
+    pub emoji: EmojiPreferences,
+    /// Incremented on every render update; emoji merges never conflict and
+    /// leave it unchanged. 0 if the user never saved settings.
+    pub version: i64,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_apply_on_top_of_defaults() {
+        let defaults = RenderSettings {
+            compact_mode: true,
+            ..Default::default()
+        };
+        let overrides = RenderSettingsOverrides {
+            message_font_size: Some(20),
+            link_previews: Some(false),
+            ..Default::default()
+        };
+        let effective = overrides.apply_to(&defaults);
+        assert_eq!(effective.message_font_size, 20);
+        assert!(!effective.link_previews);
+        assert!(effective.inline_media);
+        assert!(effective.compact_mode);
+        assert_eq!(effective.emoji_size, EmojiSize::Medium);
+    }
+
+    #[test]
+    fn overrides_serialize_only_set_fields() {
+        let overrides = RenderSettingsOverrides {
+            emoji_size: Some(EmojiSize::Large),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&overrides).unwrap();
+        assert_eq!(json, r#"{"emoji_size":"large"}"#);
+        let back: RenderSettingsOverrides = serde_json::from_str("{}").unwrap();
+        assert_eq!(back, RenderSettingsOverrides::default());
+    }
+
+    fn frequent(shortcode: &str, count: u32, minute: u32) -> FrequentEmoji {
+        FrequentEmoji {
+            shortcode: shortcode.into(),
+            count,
+            last_used_at: DateTime::from_timestamp(i64::from(minute) * 60, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn merging_frequent_emoji_keeps_highest_counts() {
+        let mut prefs = EmojiPreferences {
+            frequent: vec![frequent("smile", 5, 1), frequent("tada", 1, 2)],
+            ..Default::default()
+        };
+        let other = [frequent("tada", 7, 3), frequent("wave", 2, 4)];
+        prefs.merge_frequent(&other);
+        prefs.merge_frequent(&other);
+
+        let ranked: Vec<_> = prefs
+            .frequent
+            .iter()
+            .map(|e| (e.shortcode.as_str(), e.count))
+            .collect();
+        assert_eq!(ranked, [("tada", 7), ("smile", 5), ("wave", 2)]);
+        assert_eq!(
+            prefs.frequent[0].last_used_at,
+            frequent("x", 0, 3).last_used_at
+        );
+    }
+
+    #[test]
+    fn recording_use_bumps_and_caps_the_list() {
+        let mut prefs = EmojiPreferences::default();
+        let now = Utc::now();
+        let shortcodes: Vec<String> = (0..MAX_FREQUENT_EMOJI + 5)
+            .map(|i| format!("e{i}"))
+            .collect();
+        prefs.record_use(&shortcodes, now);
+        prefs.record_use(&["e7".to_string()], now);
+        assert_eq!(prefs.frequent.len(), MAX_FREQUENT_EMOJI);
+        assert_eq!(prefs.frequent[0].shortcode, "e7");
+        assert_eq!(prefs.frequent[0].count, 2);
+    }
+
+    #[test]
+    fn emoji_preferences_default_when_missing() {
+        let prefs: EmojiPreferences = serde_json::from_str("{}").unwrap();
+        assert_eq!(prefs.skin_tone, SkinTone::Default);
+        assert!(prefs.frequent.is_empty());
+        let tone: SkinTone = serde_json::from_str(r#""medium_dark""#).unwrap();
+        assert_eq!(tone, SkinTone::MediumDark);
+    }
+
+    #[test]
+    fn render_settings_fill_missing_fields_with_defaults() {
+        let settings: RenderSettings = serde_json::from_str(r#"{"compact_mode":true}"#).unwrap();
+        assert!(settings.compact_mode);
+        assert_eq!(settings.message_font_size, 16);
+    }
+}
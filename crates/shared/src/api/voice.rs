@@ -0,0 +1,80 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::ids::{ChannelId, GuildId, UserId};
+
+/// Channel type of voice channels.
+pub const VOICE_CHANNEL_TYPE: &str = "voice";
+
+/// A member's voice connection state in a guild. Media flows peer to peer
+/// or through an SFU; the server only coordinates who is where.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct VoiceState {
+    pub guild_id: GuildId,
+    pub user_id: UserId,
+    /// The voice channel the member is in; `None` once they have left.
+    pub channel_id: Option<ChannelId>,
+    pub self_mute: bool,
+    pub self_deaf: bool,
+}
+
+/// Response for GET /api/guilds/:guild_id/voice-states.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct VoiceStateListResponse {
+    pub voice_states: Vec<VoiceState>,
+}
+
+/// Response for GET /api/voice/turn-credentials.
+///
+/// `urls`, `username` and `credential` can be passed to WebRTC as an
+/// `RTCIceServer` unchanged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct TurnCredentialsResponse {
+    /// STUN and TURN server URIs, e.g. `turn:turn.example.org:3478`.
+    pub urls: Vec<String>,
+    /// `<expiry unix timestamp>:<user ID>`.
+    pub username: String,
+    /// Base64 HMAC-SHA1 of `username` under the TURN server's shared secret.
+    pub credential: String,
+    /// Seconds the credentials stay valid.
+    pub ttl: u64,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turn_credentials_serialize_as_ice_server_fields() {
+        let response = TurnCredentialsResponse {
+            urls: vec!["turn:turn.example.org:3478".into()],
+            username: "1700000000:user".into(),
+            credential: "c2VjcmV0".into(),
+            ttl: 3600,
+            expires_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        };
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["urls"][0], "turn:turn.example.org:3478");
+        assert_eq!(json["username"], "1700000000:user");
+        assert_eq!(json["credential"], "c2VjcmV0");
+        assert_eq!(json["ttl"], 3600);
+    }
+
+    #[test]
+    fn voice_state_serializes_leave_as_null_channel() {
+        let state = VoiceState {
+            guild_id: GuildId::new(),
+            user_id: UserId::new(),
+            channel_id: None,
+            self_mute: true,
+            self_deaf: false,
+        };
+        let json = serde_json::to_value(&state).unwrap();
+        assert!(json["channel_id"].is_null());
+        assert_eq!(serde_json::from_value::<VoiceState>(json).unwrap(), state);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::ids::{GuildId, UserId};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Request body for POST /api/guilds/:guild_id/webhooks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    /// `GatewayIntents` bits to deliver. Defaults to `GUILD_MEMBERS`.
+    #[serde(default)]
+    pub intents: Option<u64>,
+}
+
+/// An outgoing guild webhook.
+///
+/// Deliveries are signed with HMAC-SHA256 over the raw body using `secret`,
+/// sent as `X-OpenConv-Signature: sha256=<hex>`. The secret is only returned
+/// when the webhook is created.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct WebhookResponse {
+    pub id: uuid::Uuid,
+    pub guild_id: GuildId,
+    pub url: String,
+    pub intents: u64,
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+/// Response for GET /api/guilds/:guild_id/webhooks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct WebhookListResponse {
+    pub webhooks: Vec<WebhookResponse>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_webhook_request_intents_default_to_none() {
+        let req: CreateWebhookRequest =
+            serde_json::from_str(r#"{"url":"https://example.com/hook"}"#).unwrap();
+        assert!(req.intents.is_none());
+    }
+
+    #[test]
+    fn webhook_response_omits_secret_when_unset() {
+        let resp = WebhookResponse {
+            id: uuid::Uuid::new_v4(),
+            guild_id: GuildId::new(),
+            url: "https://example.com/hook".into(),
+            intents: 2,
+            created_by: UserId::new(),
+            created_at: Utc::now(),
+            secret: None,
+        };
+        let json = serde_json::to_value(&resp).unwrap();
+        assert!(json.get("secret").is_none());
+    }
+}
@@ -1,6 +1,12 @@
+use crate::api::automod::MessageMetadata;
+use crate::api::envelope::PayloadKind;
+use crate::api::gateway::{GatewayIntents, MemberEvent};
 use crate::api::message::base64_serde;
-use crate::ids::{ChannelId, GuildId, MessageId, UserId};
+use crate::api::voice::VoiceState;
+use crate::ids::{ChannelId, DmChannelId, GuildId, MessageId, UserId};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::ops::RangeInclusive;
 
 /// Presence status for a user connection.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -29,6 +35,15 @@ pub enum ClientMessage {
         encrypted_content: Vec<u8>,
         #[serde(with = "base64_serde")]
         nonce: Vec<u8>,
+        #[serde(default)]
+        payload_kind: PayloadKind,
+        /// Retries with the same key within 24 hours return the message
+        /// already sent instead of sending it again.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        idempotency_key: Option<String>,
+        /// Checked against the guild's automod rules.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        metadata: Option<MessageMetadata>,
     },
     EditMessage {
         channel_id: ChannelId,
@@ -37,6 +52,11 @@ pub enum ClientMessage {
         encrypted_content: Vec<u8>,
         #[serde(with = "base64_serde")]
         nonce: Vec<u8>,
+        #[serde(default)]
+        payload_kind: PayloadKind,
+        /// Checked against the guild's automod rules.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        metadata: Option<MessageMetadata>,
     },
     DeleteMessage {
         channel_id: ChannelId,
@@ -51,6 +71,21 @@ pub enum ClientMessage {
     SetPresence {
         status: PresenceStatus,
     },
+    /// Join a voice channel, leaving any other one the user is in.
+    JoinVoice {
+        channel_id: ChannelId,
+        #[serde(default)]
+        self_mute: bool,
+        #[serde(default)]
+        self_deaf: bool,
+    },
+    /// Leave the voice channel the user is in, if any.
+    LeaveVoice,
+    /// Change mute and deafen state in the current voice channel.
+    SetVoiceState {
+        self_mute: bool,
+        self_deaf: bool,
+    },
     Ping {
         ts: u64,
     },
@@ -64,6 +99,14 @@ pub enum ServerMessage {
     Ready {
         user_id: UserId,
         guild_ids: Vec<GuildId>,
+        /// DM channels the user has archived, so every device starts with
+        /// the same list.
+        #[serde(default)]
+        archived_dm_channel_ids: Vec<DmChannelId>,
+        /// Latest journal sequence number for this user; see
+        /// [`ServerMessage::Sequenced`]. 0 when nothing has been journaled.
+        #[serde(default)]
+        seq: u64,
     },
     MessageCreated {
         channel_id: ChannelId,
@@ -103,6 +146,70 @@ pub enum ServerMessage {
     ReplayComplete {
         channel_id: ChannelId,
     },
+    GuildMemberEvent {
+        event: MemberEvent,
+    },
+    /// The user archived or unarchived a DM channel on one of their devices.
+    /// Sent only to that user's connections.
+    DmArchiveUpdated {
+        dm_channel_id: DmChannelId,
+        archived: bool,
+    },
+    /// A guild member joined, left or changed state in a voice channel.
+    VoiceStateUpdate {
+        state: VoiceState,
+    },
+    /// The server is shutting down. It closes the connection with
+    /// [`close_codes::SERVICE_RESTART`] right after; the client should
+    /// reconnect after `reconnect_after_ms`, re-subscribe and resume from
+    /// replay, most likely landing on another node.
+    Reconnect {
+        reconnect_after_ms: u64,
+    },
+    /// The guild's message retention job deleted every message in its
+    /// channels sent before `before`. Clients should drop those messages
+    /// from local caches too.
+    MessagesPurged {
+        guild_id: GuildId,
+        before: chrono::DateTime<chrono::Utc>,
+    },
+    /// A journaled event, numbered in the user's own gapless sequence.
+    ///
+    /// Message, guild membership and DM archive events are journaled for a
+    /// few minutes, so a client that reconnects with `resume_from` set to
+    /// the last number it handled receives what it missed. Delivery is
+    /// at-least-once: drop numbers already seen, e.g. with a
+    /// [`SequenceTracker`]. `event` is omitted when this connection's
+    /// intents exclude it; the number still counts.
+    Sequenced {
+        seq: u64,
+        /// A `ServerMessage`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(feature = "utoipa", schema(value_type = Option<Object>))]
+        event: Option<Box<ServerMessage>>,
+    },
+    /// Ends the replay of journaled events requested with `resume_from`.
+    /// `seq` is the latest number replayed. When `truncated`, some events
+    /// after `resume_from` had already left the journal; the client should
+    /// refetch its state over REST.
+    ResumeComplete {
+        seq: u64,
+        truncated: bool,
+    },
+}
+
+impl ServerMessage {
+    /// Intent a connection must hold to receive this message, if any.
+    pub fn required_intent(&self) -> Option<GatewayIntents> {
+        match self {
+            ServerMessage::PresenceUpdate { .. } => Some(GatewayIntents::GUILD_PRESENCES),
+            ServerMessage::MemberJoined { .. }
+            | ServerMessage::MemberLeft { .. }
+            | ServerMessage::GuildMemberEvent { .. } => Some(GatewayIntents::GUILD_MEMBERS),
+            ServerMessage::VoiceStateUpdate { .. } => Some(GatewayIntents::GUILD_VOICE_STATES),
+            _ => None,
+        }
+    }
 }
 
 /// WebSocket error codes.
@@ -113,6 +220,75 @@ pub mod error_codes {
     pub const INVALID_MESSAGE_FORMAT: u32 = 4004;
     pub const CHANNEL_NOT_SUBSCRIBED: u32 = 4005;
     pub const LAGGED: u32 = 4006;
+    pub const NOT_IN_VOICE: u32 = 4008;
+    pub const AUTOMOD_BLOCKED: u32 = 4009;
+}
+
+/// WebSocket close frame codes.
+pub mod close_codes {
+    /// Standard "service restart" code, sent after
+    /// [`ServerMessage::Reconnect`](super::ServerMessage::Reconnect).
+    pub const SERVICE_RESTART: u16 = 1012;
+}
+
+/// Journal sequence numbers a client has received, to drop duplicates and
+/// find gaps.
+///
+/// Replay after a resume runs while live events already flow, so numbers can
+/// arrive twice or out of order. A gap still open after
+/// [`ServerMessage::ResumeComplete`] means events were lost; reconnect with
+/// [`SequenceTracker::resume_from`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SequenceTracker {
+    /// Every number up to here has been received.
+    contiguous: u64,
+    /// Numbers received beyond `contiguous`.
+    ahead: BTreeSet<u64>,
+}
+
+impl SequenceTracker {
+    /// Track numbers after `seq`, e.g. the `seq` of a fresh `Ready`.
+    pub fn starting_at(seq: u64) -> Self {
+        Self {
+            contiguous: seq,
+            ahead: BTreeSet::new(),
+        }
+    }
+
+    /// Record `seq`. Returns false if it was already received.
+    pub fn observe(&mut self, seq: u64) -> bool {
+        if seq <= self.contiguous || !self.ahead.insert(seq) {
+            return false;
+        }
+        self.advance();
+        true
+    }
+
+    /// The number to resume from: everything up to it has been received.
+    pub fn resume_from(&self) -> u64 {
+        self.contiguous
+    }
+
+    /// The first run of missing numbers, if any.
+    pub fn gap(&self) -> Option<RangeInclusive<u64>> {
+        let &next = self.ahead.first()?;
+        Some(self.contiguous + 1..=next - 1)
+    }
+
+    /// Give up on everything up to `seq`, e.g. after a truncated replay.
+    pub fn skip_to(&mut self, seq: u64) {
+        if seq > self.contiguous {
+            self.contiguous = seq;
+            self.ahead.retain(|&s| s > seq);
+            self.advance();
+        }
+    }
+
+    fn advance(&mut self) {
+        while self.ahead.remove(&(self.contiguous + 1)) {
+            self.contiguous += 1;
+        }
+    }
 }
 
 #[cfg(test)]
@@ -152,6 +328,12 @@ mod tests {
             channel_id: ChannelId::new(),
             encrypted_content: content.clone(),
             nonce: nonce_bytes.clone(),
+            payload_kind: PayloadKind::Text,
+            idempotency_key: None,
+            metadata: Some(MessageMetadata {
+                mention_count: 2,
+                contains_invite: false,
+            }),
         };
         let json = serde_json::to_string(&msg).unwrap();
         // Verify base64 encoding in JSON
@@ -164,10 +346,34 @@ mod tests {
             ClientMessage::SendMessage {
                 encrypted_content,
                 nonce,
+                metadata,
                 ..
             } => {
                 assert_eq!(encrypted_content, content);
                 assert_eq!(nonce, nonce_bytes);
+                assert_eq!(metadata.unwrap().mention_count, 2);
+            }
+            _ => panic!("wrong variant"),
+        }
+    }
+
+    #[test]
+    fn client_message_send_message_payload_kind_defaults_to_text() {
+        let json = format!(
+            r#"{{"type":"SendMessage","channel_id":"{}","encrypted_content":"AA==","nonce":"AA=="}}"#,
+            ChannelId::new()
+        );
+        let msg: ClientMessage = serde_json::from_str(&json).unwrap();
+        match msg {
+            ClientMessage::SendMessage {
+                payload_kind,
+                idempotency_key,
+                metadata,
+                ..
+            } => {
+                assert_eq!(payload_kind, PayloadKind::Text);
+                assert_eq!(idempotency_key, None);
+                assert_eq!(metadata, None);
             }
             _ => panic!("wrong variant"),
         }
@@ -201,17 +407,57 @@ mod tests {
         }
     }
 
+    #[test]
+    fn server_message_reconnect_round_trip() {
+        let msg = ServerMessage::Reconnect {
+            reconnect_after_ms: 1500,
+        };
+        let json = serde_json::to_string(&msg).unwrap();
+        assert_eq!(json, r#"{"type":"Reconnect","reconnect_after_ms":1500}"#);
+        let back: ServerMessage = serde_json::from_str(&json).unwrap();
+        match back {
+            ServerMessage::Reconnect { reconnect_after_ms } => assert_eq!(reconnect_after_ms, 1500),
+            _ => panic!("wrong variant"),
+        }
+    }
+
+    #[test]
+    fn server_message_messages_purged_round_trip() {
+        let guild_id = GuildId::new();
+        let before = chrono::Utc::now();
+        let msg = ServerMessage::MessagesPurged { guild_id, before };
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["type"], "MessagesPurged");
+        let back: ServerMessage = serde_json::from_value(json).unwrap();
+        match back {
+            ServerMessage::MessagesPurged {
+                guild_id: g,
+                before: b,
+            } => {
+                assert_eq!(g, guild_id);
+                assert_eq!(b, before);
+            }
+            _ => panic!("wrong variant"),
+        }
+        assert_eq!(msg.required_intent(), None);
+    }
+
     #[test]
     fn server_message_ready_round_trip() {
         let msg = ServerMessage::Ready {
             user_id: UserId::new(),
             guild_ids: vec![GuildId::new(), GuildId::new()],
+            archived_dm_channel_ids: vec![DmChannelId::new()],
+            seq: 12,
         };
         let json = serde_json::to_string(&msg).unwrap();
         assert!(json.contains(r#""type":"Ready""#));
         let back: ServerMessage = serde_json::from_str(&json).unwrap();
         match back {
-            ServerMessage::Ready { guild_ids, .. } => assert_eq!(guild_ids.len(), 2),
+            ServerMessage::Ready { guild_ids, seq, .. } => {
+                assert_eq!(guild_ids.len(), 2);
+                assert_eq!(seq, 12);
+            }
             _ => panic!("wrong variant"),
         }
     }
@@ -277,4 +523,85 @@ mod tests {
             _ => panic!("wrong variant"),
         }
     }
+
+    #[test]
+    fn guild_member_event_requires_members_intent() {
+        use crate::api::gateway::MemberEventKind;
+        let msg = ServerMessage::GuildMemberEvent {
+            event: MemberEvent::new(GuildId::new(), UserId::new(), MemberEventKind::Kick),
+        };
+        assert_eq!(msg.required_intent(), Some(GatewayIntents::GUILD_MEMBERS));
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["type"], "GuildMemberEvent");
+        assert_eq!(json["event"]["kind"], "kick");
+        assert!(ServerMessage::Pong { ts: 1 }.required_intent().is_none());
+    }
+
+    #[test]
+    fn sequenced_wraps_event_and_omits_filtered_ones() {
+        let msg = ServerMessage::Sequenced {
+            seq: 7,
+            event: Some(Box::new(ServerMessage::MessageDeleted {
+                channel_id: ChannelId::new(),
+                message_id: MessageId::new(),
+            })),
+        };
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["type"], "Sequenced");
+        assert_eq!(json["event"]["type"], "MessageDeleted");
+        let back: ServerMessage = serde_json::from_value(json).unwrap();
+        assert!(matches!(
+            back,
+            ServerMessage::Sequenced { seq: 7, event: Some(e) }
+                if matches!(*e, ServerMessage::MessageDeleted { .. })
+        ));
+
+        let skipped = ServerMessage::Sequenced {
+            seq: 8,
+            event: None,
+        };
+        let json = serde_json::to_string(&skipped).unwrap();
+        assert_eq!(json, r#"{"type":"Sequenced","seq":8}"#);
+    }
+
+    #[test]
+    fn ready_without_seq_defaults_to_zero() {
+        let json = format!(
+            r#"{{"type":"Ready","user_id":"{}","guild_ids":[]}}"#,
+            UserId::new()
+        );
+        match serde_json::from_str(&json).unwrap() {
+            ServerMessage::Ready { seq, .. } => assert_eq!(seq, 0),
+            _ => panic!("wrong variant"),
+        }
+    }
+
+    #[test]
+    fn sequence_tracker_drops_duplicates_and_finds_gaps() {
+        let mut tracker = SequenceTracker::starting_at(10);
+        assert!(!tracker.observe(10));
+        assert!(tracker.observe(11));
+        assert!(tracker.observe(14));
+        assert!(!tracker.observe(14));
+        assert_eq!(tracker.resume_from(), 11);
+        assert_eq!(tracker.gap(), Some(12..=13));
+
+        // Replay fills the gap out of order
+        assert!(tracker.observe(13));
+        assert!(tracker.observe(12));
+        assert_eq!(tracker.resume_from(), 14);
+        assert_eq!(tracker.gap(), None);
+    }
+
+    #[test]
+    fn sequence_tracker_skips_truncated_ranges() {
+        let mut tracker = SequenceTracker::starting_at(3);
+        tracker.observe(9);
+        tracker.observe(11);
+        tracker.skip_to(8);
+        assert_eq!(tracker.resume_from(), 9);
+        assert_eq!(tracker.gap(), Some(10..=10));
+        tracker.skip_to(2);
+        assert_eq!(tracker.resume_from(), 9);
+    }
 }
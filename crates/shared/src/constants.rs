@@ -8,6 +8,12 @@ pub const MAX_CHANNEL_NAME_LENGTH: usize = 100;
 pub const MAX_GUILD_NAME_LENGTH: usize = 100;
 /// Maximum size for a single message in bytes.
 pub const MAX_MESSAGE_SIZE_BYTES: usize = 8 * 1024;
+/// Maximum encrypted size for a reaction payload.
+pub const MAX_REACTION_SIZE_BYTES: usize = 1024;
+/// Maximum encrypted size for a sticker payload.
+pub const MAX_STICKER_SIZE_BYTES: usize = 1024;
+/// Maximum encrypted size for a client-generated system payload.
+pub const MAX_SYSTEM_MESSAGE_SIZE_BYTES: usize = 4 * 1024;
 
 #[cfg(test)]
 mod tests {
@@ -25,5 +31,8 @@ mod tests {
         assert!(MAX_CHANNEL_NAME_LENGTH > 0);
         assert!(MAX_GUILD_NAME_LENGTH > 0);
         assert!(MAX_MESSAGE_SIZE_BYTES > 0);
+        assert!(MAX_REACTION_SIZE_BYTES > 0);
+        assert!(MAX_STICKER_SIZE_BYTES > 0);
+        assert!(MAX_SYSTEM_MESSAGE_SIZE_BYTES > 0);
     }
 }
@@ -1,3 +1,123 @@
+use std::collections::BTreeMap;
+
+/// Limiter state reported with a rate limit rejection so clients can back off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitInfo {
+    /// Requests allowed per window.
+    pub limit: u32,
+    /// Requests left in the current window.
+    pub remaining: u32,
+    /// Seconds until the full quota is available again.
+    pub reset_seconds: u64,
+    /// Seconds until the next request may be accepted.
+    pub retry_after_seconds: u64,
+}
+
+/// Stable, machine-readable identifier sent as `code` in every error
+/// response. Clients branch on this rather than the message, which may be
+/// reworded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub enum ErrorCode {
+    NotFound,
+    Unauthorized,
+    Forbidden,
+    ReauthRequired,
+    ValidationFailed,
+    InternalError,
+    CryptoError,
+    RateLimited,
+    SessionCompromised,
+    Conflict,
+    ServiceUnavailable,
+    TemporarilyUnavailable,
+    PayloadTooLarge,
+    /// The email or recovery code does not match.
+    AuthCodeInvalid,
+    /// The email or recovery code expired or was used up; request a new one.
+    AuthCodeExpired,
+    /// An account already exists for the email address or public key.
+    AccountExists,
+    InviteExpired,
+    InviteMaxUses,
+    /// The guild is in lockdown and accepts no invites.
+    InvitesPaused,
+    GuildFull,
+    AlreadyMember,
+    BannedFromGuild,
+    /// The account does not meet the guild's verification level.
+    VerificationLevelNotMet,
+}
+
+impl ErrorCode {
+    /// The code as sent on the wire, e.g. "INVITE_MAX_USES".
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NotFound => "NOT_FOUND",
+            Self::Unauthorized => "UNAUTHORIZED",
+            Self::Forbidden => "FORBIDDEN",
+            Self::ReauthRequired => "REAUTH_REQUIRED",
+            Self::ValidationFailed => "VALIDATION_FAILED",
+            Self::InternalError => "INTERNAL_ERROR",
+            Self::CryptoError => "CRYPTO_ERROR",
+            Self::RateLimited => "RATE_LIMITED",
+            Self::SessionCompromised => "SESSION_COMPROMISED",
+            Self::Conflict => "CONFLICT",
+            Self::ServiceUnavailable => "SERVICE_UNAVAILABLE",
+            Self::TemporarilyUnavailable => "TEMPORARILY_UNAVAILABLE",
+            Self::PayloadTooLarge => "PAYLOAD_TOO_LARGE",
+            Self::AuthCodeInvalid => "AUTH_CODE_INVALID",
+            Self::AuthCodeExpired => "AUTH_CODE_EXPIRED",
+            Self::AccountExists => "ACCOUNT_EXISTS",
+            Self::InviteExpired => "INVITE_EXPIRED",
+            Self::InviteMaxUses => "INVITE_MAX_USES",
+            Self::InvitesPaused => "INVITES_PAUSED",
+            Self::GuildFull => "GUILD_FULL",
+            Self::AlreadyMember => "ALREADY_MEMBER",
+            Self::BannedFromGuild => "BANNED_FROM_GUILD",
+            Self::VerificationLevelNotMet => "VERIFICATION_LEVEL_NOT_MET",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A localizable error message: a stable key naming the message and the
+/// values to fill into it, sent as `localized` alongside the English
+/// `error` text. Clients translate known keys and show `error` for the
+/// rest. Unlike [`ErrorCode`], which says what went wrong, the key says
+/// how to phrase it, e.g. `display_name.too_long` with `{ "max": "64" }`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ErrorMessage {
+    /// Dotted, subject first: "guild_name.length", "channel_topic.too_long".
+    pub key: String,
+    #[serde(default)]
+    pub params: BTreeMap<String, String>,
+}
+
+impl ErrorMessage {
+    pub fn new(key: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            params: BTreeMap::new(),
+        }
+    }
+
+    /// Add a value for the message's `{name}` placeholder.
+    pub fn param(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.params.insert(name.into(), value.to_string());
+        self
+    }
+}
+
 /// Shared error type used across server and client.
 #[derive(Debug, thiserror::Error)]
 pub enum OpenConvError {
@@ -10,6 +130,9 @@ pub enum OpenConvError {
     #[error("forbidden")]
     Forbidden,
 
+    #[error("reauthentication required")]
+    ReauthRequired,
+
     #[error("validation error: {0}")]
     Validation(String),
 
@@ -20,7 +143,7 @@ pub enum OpenConvError {
     Crypto(String),
 
     #[error("rate limited")]
-    RateLimited,
+    RateLimited(RateLimitInfo),
 
     #[error("session compromised")]
     SessionCompromised,
@@ -31,14 +154,110 @@ pub enum OpenConvError {
     #[error("service unavailable: {0}")]
     ServiceUnavailable(String),
 
+    /// A dependency is down but expected back; retry after the given delay.
+    #[error("{service} temporarily unavailable")]
+    TemporarilyUnavailable {
+        service: String,
+        retry_after_seconds: u64,
+    },
+
     #[error("payload too large: {0}")]
     PayloadTooLarge(String),
+
+    /// An error with a more specific code or a localizable message
+    /// attached. Built with [`OpenConvError::with_code`] and
+    /// [`OpenConvError::with_message`]; responds like `error`.
+    #[error("{error}")]
+    Detailed {
+        code: Option<ErrorCode>,
+        message: Option<ErrorMessage>,
+        error: Box<OpenConvError>,
+    },
+}
+
+impl OpenConvError {
+    /// Attach a specific code, replacing any attached before.
+    pub fn with_code(self, code: ErrorCode) -> Self {
+        let (_, message, error) = self.into_parts();
+        Self::Detailed {
+            code: Some(code),
+            message,
+            error: Box::new(error),
+        }
+    }
+
+    /// Attach a localizable message, replacing any attached before.
+    pub fn with_message(self, message: ErrorMessage) -> Self {
+        let (code, _, error) = self.into_parts();
+        Self::Detailed {
+            code,
+            message: Some(message),
+            error: Box::new(error),
+        }
+    }
+
+    /// The error without any code or message attached.
+    pub fn base(&self) -> &OpenConvError {
+        match self {
+            Self::Detailed { error, .. } => error.base(),
+            other => other,
+        }
+    }
+
+    fn into_parts(self) -> (Option<ErrorCode>, Option<ErrorMessage>, OpenConvError) {
+        match self {
+            Self::Detailed {
+                code,
+                message,
+                error,
+            } => (code, message, error.into_parts().2),
+            other => (None, None, other),
+        }
+    }
+
+    /// The attached code, else the variant's default.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotFound => ErrorCode::NotFound,
+            Self::Unauthorized => ErrorCode::Unauthorized,
+            Self::Forbidden => ErrorCode::Forbidden,
+            Self::ReauthRequired => ErrorCode::ReauthRequired,
+            Self::Validation(_) => ErrorCode::ValidationFailed,
+            Self::Internal(_) => ErrorCode::InternalError,
+            Self::Crypto(_) => ErrorCode::CryptoError,
+            Self::RateLimited(_) => ErrorCode::RateLimited,
+            Self::SessionCompromised => ErrorCode::SessionCompromised,
+            Self::Conflict(_) => ErrorCode::Conflict,
+            Self::ServiceUnavailable(_) => ErrorCode::ServiceUnavailable,
+            Self::TemporarilyUnavailable { .. } => ErrorCode::TemporarilyUnavailable,
+            Self::PayloadTooLarge(_) => ErrorCode::PayloadTooLarge,
+            Self::Detailed {
+                code: Some(code), ..
+            } => *code,
+            Self::Detailed { error, .. } => error.code(),
+        }
+    }
+
+    /// The attached localizable message, if any.
+    pub fn message(&self) -> Option<&ErrorMessage> {
+        match self {
+            Self::Detailed { message, .. } => message.as_ref(),
+            _ => None,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const RATE_LIMIT_INFO: RateLimitInfo = RateLimitInfo {
+        limit: 10,
+        remaining: 0,
+        reset_seconds: 60,
+        retry_after_seconds: 6,
+    };
+
     #[test]
     fn not_found_display() {
         let err = OpenConvError::NotFound;
@@ -63,13 +282,18 @@ mod tests {
             Box::new(OpenConvError::NotFound),
             Box::new(OpenConvError::Unauthorized),
             Box::new(OpenConvError::Forbidden),
+            Box::new(OpenConvError::ReauthRequired),
             Box::new(OpenConvError::Validation("x".into())),
             Box::new(OpenConvError::Internal("y".into())),
             Box::new(OpenConvError::Crypto("z".into())),
             Box::new(OpenConvError::Conflict("duplicate".into())),
-            Box::new(OpenConvError::RateLimited),
+            Box::new(OpenConvError::RateLimited(RATE_LIMIT_INFO)),
             Box::new(OpenConvError::SessionCompromised),
             Box::new(OpenConvError::ServiceUnavailable("redis down".into())),
+            Box::new(OpenConvError::TemporarilyUnavailable {
+                service: "redis".into(),
+                retry_after_seconds: 5,
+            }),
             Box::new(OpenConvError::PayloadTooLarge("too big".into())),
         ];
         for e in &errors {
@@ -77,9 +301,15 @@ mod tests {
         }
     }
 
+    #[test]
+    fn reauth_required_display() {
+        let err = OpenConvError::ReauthRequired;
+        assert_eq!(err.to_string(), "reauthentication required");
+    }
+
     #[test]
     fn rate_limited_display() {
-        let err = OpenConvError::RateLimited;
+        let err = OpenConvError::RateLimited(RATE_LIMIT_INFO);
         assert_eq!(err.to_string(), "rate limited");
     }
 
@@ -94,4 +324,83 @@ mod tests {
         let err = OpenConvError::ServiceUnavailable("redis down".into());
         assert_eq!(err.to_string(), "service unavailable: redis down");
     }
+
+    #[test]
+    fn codes_default_by_variant() {
+        assert_eq!(OpenConvError::NotFound.code(), ErrorCode::NotFound);
+        assert_eq!(
+            OpenConvError::Validation("x".into()).code(),
+            ErrorCode::ValidationFailed
+        );
+        assert_eq!(
+            OpenConvError::RateLimited(RATE_LIMIT_INFO).code(),
+            ErrorCode::RateLimited
+        );
+    }
+
+    #[test]
+    fn with_code_keeps_the_message_and_replaces_the_code() {
+        let err = OpenConvError::Validation("invite is at max uses".into())
+            .with_code(ErrorCode::InviteExpired)
+            .with_code(ErrorCode::InviteMaxUses);
+        assert_eq!(err.code(), ErrorCode::InviteMaxUses);
+        assert_eq!(err.to_string(), "validation error: invite is at max uses");
+        assert!(matches!(err.base(), OpenConvError::Validation(_)));
+    }
+
+    #[test]
+    fn code_and_message_attach_independently() {
+        let message = ErrorMessage::new("display_name.too_long").param("max", 64);
+        let err = OpenConvError::Validation("display name too long".into())
+            .with_message(message.clone())
+            .with_code(ErrorCode::ValidationFailed);
+        assert_eq!(err.message(), Some(&message));
+        assert_eq!(err.code(), ErrorCode::ValidationFailed);
+
+        let err = OpenConvError::NotFound.with_message(ErrorMessage::new("invite.not_found"));
+        assert_eq!(err.code(), ErrorCode::NotFound);
+        assert_eq!(err.to_string(), "not found");
+    }
+
+    #[test]
+    fn error_message_serde() {
+        let message = ErrorMessage::new("guild_name.length")
+            .param("min", 1)
+            .param("max", 100);
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "key": "guild_name.length",
+                "params": { "max": "100", "min": "1" }
+            })
+        );
+        let back: ErrorMessage = serde_json::from_str(r#"{"key":"x.y"}"#).unwrap();
+        assert!(back.params.is_empty());
+    }
+
+    #[test]
+    fn error_code_serializes_as_its_str() {
+        for code in [
+            ErrorCode::NotFound,
+            ErrorCode::AuthCodeExpired,
+            ErrorCode::VerificationLevelNotMet,
+        ] {
+            assert_eq!(
+                serde_json::to_value(code).unwrap(),
+                serde_json::json!(code.as_str())
+            );
+        }
+        let code: ErrorCode = serde_json::from_str("\"INVITE_MAX_USES\"").unwrap();
+        assert_eq!(code, ErrorCode::InviteMaxUses);
+    }
+
+    #[test]
+    fn temporarily_unavailable_display() {
+        let err = OpenConvError::TemporarilyUnavailable {
+            service: "redis".into(),
+            retry_after_seconds: 5,
+        };
+        assert_eq!(err.to_string(), "redis temporarily unavailable");
+    }
 }
@@ -0,0 +1,293 @@
+//! Server-to-server federation protocol types (experimental).
+//!
+//! Instances identify themselves by a [`ServerName`] and sign everything
+//! they send with an Ed25519 key published at
+//! `/_openconv/federation/v1/keys`. Events travel in batches called
+//! transactions: a compact EdDSA JWS whose claims are a [`Transaction`],
+//! POSTed to the destination's `/_openconv/federation/v1/transactions`.
+//!
+//! Users on another instance are referred to by [`RemoteUserId`]: their
+//! home instance's ID for them, qualified by the server name.
+//!
+//! Only compiled with the `federation` feature; the wire format may change
+//! without notice while it is experimental.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ids::{GuildId, UserId};
+
+/// Path prefix of the federation API.
+pub const FEDERATION_PATH_PREFIX: &str = "/_openconv/federation/v1";
+
+/// Longest a server name may be, port included.
+pub const MAX_SERVER_NAME_LEN: usize = 255;
+
+/// Events in one transaction, at most.
+pub const MAX_TRANSACTION_EVENTS: usize = 50;
+
+/// A federating instance's public name: a lowercase DNS hostname with an
+/// optional port, e.g. `chat.example.org` or `chat.example.org:8448`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct ServerName(String);
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FederationIdError {
+    #[error("invalid server name: {0}")]
+    ServerName(String),
+    #[error("invalid remote user ID: {0}")]
+    RemoteUserId(String),
+}
+
+impl ServerName {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+fn is_valid_hostname(host: &str) -> bool {
+    !host.is_empty()
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+        })
+}
+
+impl FromStr for ServerName {
+    type Err = FederationIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || FederationIdError::ServerName(s.to_string());
+        if s.len() > MAX_SERVER_NAME_LEN {
+            return Err(invalid());
+        }
+        let host = match s.rsplit_once(':') {
+            Some((host, port)) => {
+                port.parse::<u16>()
+                    .ok()
+                    .filter(|p| *p != 0)
+                    .ok_or_else(invalid)?;
+                host
+            }
+            None => s,
+        };
+        if !is_valid_hostname(host) {
+            return Err(invalid());
+        }
+        Ok(Self(s.to_string()))
+    }
+}
+
+impl TryFrom<String> for ServerName {
+    type Error = FederationIdError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<ServerName> for String {
+    fn from(name: ServerName) -> Self {
+        name.0
+    }
+}
+
+impl fmt::Display for ServerName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A user homed on another instance, written `<user_id>@<server_name>`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RemoteUserId {
+    /// The user's ID on their home instance.
+    pub user_id: UserId,
+    /// Their home instance.
+    pub server: ServerName,
+}
+
+impl FromStr for RemoteUserId {
+    type Err = FederationIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || FederationIdError::RemoteUserId(s.to_string());
+        let (user_id, server) = s.split_once('@').ok_or_else(invalid)?;
+        Ok(Self {
+            user_id: user_id.parse().map_err(|_| invalid())?,
+            server: server.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl TryFrom<String> for RemoteUserId {
+    type Error = FederationIdError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<RemoteUserId> for String {
+    fn from(id: RemoteUserId) -> Self {
+        id.to_string()
+    }
+}
+
+impl fmt::Display for RemoteUserId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.user_id, self.server)
+    }
+}
+
+/// An event sent between instances.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FederationEvent {
+    /// Sent by a user's home instance to the instance hosting a guild, to
+    /// join it with an invite. The host answers by forwarding
+    /// [`FederationEvent::MemberJoined`] once the user is a member.
+    JoinRequest {
+        user: RemoteUserId,
+        display_name: String,
+        invite_code: String,
+    },
+    /// Sent by a user's home instance when the user leaves a remote guild.
+    LeaveRequest {
+        user: RemoteUserId,
+        guild_id: GuildId,
+    },
+    /// Forwarded by a guild's host when anyone joins it.
+    MemberJoined {
+        guild_id: GuildId,
+        guild_name: String,
+        user: RemoteUserId,
+    },
+    /// Forwarded by a guild's host when anyone leaves or is removed.
+    MemberLeft {
+        guild_id: GuildId,
+        user: RemoteUserId,
+    },
+}
+
+/// Claims of a signed transaction. `iss` and `aud` are the sending and
+/// receiving server names; `jti` is unique per transaction so the receiver
+/// can drop replays.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transaction {
+    pub iss: ServerName,
+    pub aud: ServerName,
+    pub iat: u64,
+    pub exp: u64,
+    pub jti: uuid::Uuid,
+    pub events: Vec<FederationEvent>,
+}
+
+/// A server's federation signing key, as an RFC 8037 OKP public key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct ServerKey {
+    pub kid: String,
+    /// Base64url-encoded raw Ed25519 public key.
+    pub x: String,
+}
+
+/// Response for GET /_openconv/federation/v1/keys.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct ServerKeysResponse {
+    pub server_name: ServerName,
+    pub keys: Vec<ServerKey>,
+}
+
+/// Response for POST /_openconv/federation/v1/transactions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct TransactionResponse {
+    /// Events applied. Events the receiver does not act on are skipped
+    /// without failing the transaction.
+    pub accepted: usize,
+}
+
+/// Request body for POST /api/federation/joins.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct RemoteJoinRequest {
+    pub server: ServerName,
+    pub invite_code: String,
+}
+
+/// A guild on another instance that the caller has joined.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct FederatedGuildResponse {
+    pub server: ServerName,
+    pub guild_id: GuildId,
+    pub guild_name: String,
+    pub joined_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Response for GET /api/federation/guilds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct FederatedGuildListResponse {
+    pub guilds: Vec<FederatedGuildResponse>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn server_name_accepts_hosts_and_ports() {
+        assert!("chat.example.org".parse::<ServerName>().is_ok());
+        assert!("localhost:8448".parse::<ServerName>().is_ok());
+        assert!("Chat.example.org".parse::<ServerName>().is_err());
+        assert!("chat..example".parse::<ServerName>().is_err());
+        assert!("chat.example:0".parse::<ServerName>().is_err());
+        assert!("-chat.example".parse::<ServerName>().is_err());
+        assert!("".parse::<ServerName>().is_err());
+    }
+
+    #[test]
+    fn remote_user_id_round_trips() {
+        let id = RemoteUserId {
+            user_id: UserId::new(),
+            server: "b.example:8448".parse().unwrap(),
+        };
+        let s = id.to_string();
+        assert_eq!(s, format!("{}@b.example:8448", id.user_id));
+        assert_eq!(s.parse::<RemoteUserId>().unwrap(), id);
+
+        let json = serde_json::to_value(&id).unwrap();
+        assert_eq!(json, serde_json::Value::String(s));
+        assert!(serde_json::from_str::<RemoteUserId>("\"nobody@b.example\"").is_err());
+    }
+
+    #[test]
+    fn events_are_tagged_by_type() {
+        let event = FederationEvent::MemberLeft {
+            guild_id: GuildId::new(),
+            user: RemoteUserId {
+                user_id: UserId::new(),
+                server: "b.example".parse().unwrap(),
+            },
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "member_left");
+        assert_eq!(
+            serde_json::from_value::<FederationEvent>(json).unwrap(),
+            event
+        );
+    }
+}
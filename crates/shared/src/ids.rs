@@ -39,6 +39,23 @@ define_id!(RoleId);
 define_id!(FileId);
 define_id!(DmChannelId);
 define_id!(DeviceId);
+define_id!(UploadSessionId);
+
+/// Words in a [`DeviceId::pairing_code`].
+pub const DEVICE_PAIRING_CODE_WORDS: usize = 4;
+
+impl DeviceId {
+    /// Short word code identifying this device, shown in place of the raw
+    /// UUID when linking or verifying devices.
+    ///
+    /// Encodes the last bytes of the ID, which are random in UUID v7; the
+    /// leading bytes are a timestamp and would collide for devices added
+    /// around the same time.
+    pub fn pairing_code(&self) -> String {
+        let bytes = self.0.as_bytes();
+        crate::wordcode::encode(&bytes[bytes.len() - DEVICE_PAIRING_CODE_WORDS..])
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -183,4 +200,16 @@ mod tests {
         let parsed = DeviceId::from_str(&s).unwrap();
         assert_eq!(id, parsed);
     }
+
+    #[test]
+    fn device_pairing_code_is_short_and_stable() {
+        let id = DeviceId::from_str("0190a5b2-7c1e-7a3b-8f00-0123456789ab").unwrap();
+        let code = id.pairing_code();
+        assert_eq!(code.split('-').count(), DEVICE_PAIRING_CODE_WORDS);
+        assert_eq!(code, id.pairing_code());
+        assert_eq!(
+            crate::wordcode::decode(&code).unwrap(),
+            [0x45, 0x67, 0x89, 0xab]
+        );
+    }
 }
@@ -3,5 +3,8 @@
 pub mod api;
 pub mod constants;
 pub mod error;
+#[cfg(feature = "federation")]
+pub mod federation;
 pub mod ids;
 pub mod permissions;
+pub mod wordcode;
@@ -14,6 +14,11 @@ bitflags! {
         const ATTACH_FILES     = 1 << 8;
         const MENTION_EVERYONE = 1 << 9;
         const MANAGE_MESSAGES  = 1 << 10;
+        const BAN_MEMBERS      = 1 << 11;
+        /// Join voice channels.
+        const CONNECT          = 1 << 12;
+        /// Transmit audio in voice channels.
+        const SPEAK            = 1 << 13;
     }
 }
 
@@ -83,6 +88,14 @@ pub fn resolve(role_permissions: &[Permissions]) -> Permissions {
     result
 }
 
+/// Flag names set in `perms`, in bit order (e.g. `["SEND_MESSAGES", "READ_MESSAGES"]`).
+pub fn names(perms: Permissions) -> Vec<String> {
+    perms
+        .iter_names()
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -121,6 +134,13 @@ mod tests {
         assert!(perms.is_empty());
     }
 
+    #[test]
+    fn test_names_lists_set_flags_in_bit_order() {
+        let perms = Permissions::READ_MESSAGES | Permissions::SEND_MESSAGES;
+        assert_eq!(names(perms), vec!["SEND_MESSAGES", "READ_MESSAGES"]);
+        assert!(names(Permissions::empty()).is_empty());
+    }
+
     #[test]
     fn test_manage_messages_bit_position() {
         assert_eq!(Permissions::MANAGE_MESSAGES.bits(), 1 << 10);
@@ -140,6 +160,9 @@ mod tests {
             Permissions::ATTACH_FILES,
             Permissions::MENTION_EVERYONE,
             Permissions::MANAGE_MESSAGES,
+            Permissions::BAN_MEMBERS,
+            Permissions::CONNECT,
+            Permissions::SPEAK,
         ];
         for (i, a) in flags.iter().enumerate() {
             for (j, b) in flags.iter().enumerate() {
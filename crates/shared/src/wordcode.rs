@@ -0,0 +1,126 @@
+//! Human-readable word codes for short byte strings.
+//!
+//! Each byte maps to one word from a fixed 256-word list, so a code is a
+//! lossless, deterministic encoding that people can read aloud and compare
+//! at a glance. Used where users confirm a value out of band, such as
+//! device pairing and safety number checks.
+
+/// Separator placed between words in an encoded code.
+pub const SEPARATOR: char = '-';
+
+/// Word for each byte value, sorted so the index is the byte.
+///
+/// Words are lowercase ASCII, at most seven letters, and unique within
+/// their first four letters.
+pub const WORDLIST: [&str; 256] = [
+    "acid", "acorn", "actor", "agent", "alarm", "album", "alien", "alpha", "amber", "angel",
+    "anvil", "apple", "apron", "arena", "arrow", "atlas", "audio", "autumn", "bacon", "badge",
+    "bagel", "baker", "bamboo", "banjo", "barn", "basil", "beach", "beard", "beetle", "bench",
+    "berry", "bison", "blade", "blanket", "boat", "bonus", "boot", "bottle", "brave", "bread",
+    "brick", "bridge", "broom", "brush", "bucket", "buffalo", "bugle", "cabin", "cactus", "camel",
+    "candle", "canoe", "canyon", "carbon", "cargo", "carpet", "castle", "cedar", "cello", "chalk",
+    "cherry", "chess", "cider", "cinema", "circus", "citrus", "clay", "cliff", "clock", "cloud",
+    "clover", "cobra", "cocoa", "comet", "copper", "coral", "cotton", "crane", "crater", "crystal",
+    "curtain", "cycle", "dagger", "daisy", "dancer", "delta", "denim", "desert", "diamond",
+    "dingo", "dolphin", "domino", "donkey", "dragon", "drum", "eagle", "echo", "eclipse", "elbow",
+    "ember", "engine", "falcon", "feather", "fern", "fiddle", "fig", "flame", "flute", "forest",
+    "fossil", "fox", "galaxy", "garden", "garlic", "gecko", "geyser", "ginger", "glacier", "globe",
+    "goblet", "gold", "gopher", "granite", "grape", "gravel", "guitar", "hammer", "harbor", "harp",
+    "hazel", "helmet", "hermit", "honey", "hornet", "husky", "igloo", "iguana", "indigo", "iris",
+    "island", "ivory", "jacket", "jaguar", "jelly", "jester", "jigsaw", "jungle", "kayak",
+    "kernel", "kettle", "kiwi", "koala", "ladder", "lagoon", "lantern", "laser", "lemon", "lilac",
+    "linen", "lizard", "llama", "lobster", "locket", "lotus", "lunar", "magnet", "mango", "maple",
+    "marble", "meadow", "melon", "meteor", "mint", "mirror", "mitten", "mosaic", "moss", "muffin",
+    "nectar", "needle", "nickel", "noodle", "nugget", "oasis", "ocean", "olive", "onion", "orbit",
+    "orchid", "otter", "oyster", "paddle", "panda", "papaya", "parrot", "peach", "pebble",
+    "pepper", "piano", "pickle", "pilot", "pine", "pirate", "planet", "plum", "pocket", "polar",
+    "pony", "poppy", "prism", "pumpkin", "puzzle", "quartz", "quill", "rabbit", "radar", "raven",
+    "reef", "ribbon", "river", "robot", "rocket", "ruby", "saddle", "salmon", "satin", "scarf",
+    "seal", "shadow", "sierra", "silver", "socket", "spice", "sponge", "spruce", "squid", "summit",
+    "sunset", "swan", "tango", "temple", "thunder", "tiger", "timber", "toast", "tomato", "topaz",
+    "tulip", "tundra", "turtle", "ultra", "valley", "velvet", "violet", "walnut", "willow",
+];
+
+/// Error decoding a word code.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum WordCodeError {
+    #[error("word code is empty")]
+    Empty,
+
+    #[error("unknown word: {0}")]
+    UnknownWord(String),
+}
+
+/// Encode `bytes` as words joined by [`SEPARATOR`].
+pub fn encode(bytes: &[u8]) -> String {
+    let mut code = String::with_capacity(bytes.len() * 7);
+    for (i, byte) in bytes.iter().enumerate() {
+        if i > 0 {
+            code.push(SEPARATOR);
+        }
+        code.push_str(WORDLIST[usize::from(*byte)]);
+    }
+    code
+}
+
+/// Decode a code produced by [`encode`].
+///
+/// Case-insensitive, and words may be separated by dashes or whitespace so
+/// codes typed by hand are accepted.
+pub fn decode(code: &str) -> Result<Vec<u8>, WordCodeError> {
+    let bytes = code
+        .split(|c: char| c == SEPARATOR || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let word = word.to_ascii_lowercase();
+            WORDLIST
+                .binary_search(&word.as_str())
+                .map(|index| index as u8)
+                .map_err(|_| WordCodeError::UnknownWord(word))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if bytes.is_empty() {
+        return Err(WordCodeError::Empty);
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wordlist_is_sorted_and_unique() {
+        assert!(WORDLIST.windows(2).all(|pair| pair[0] < pair[1]));
+        let mut prefixes: Vec<_> = WORDLIST.iter().map(|w| &w[..4.min(w.len())]).collect();
+        prefixes.sort_unstable();
+        prefixes.dedup();
+        assert_eq!(prefixes.len(), WORDLIST.len());
+    }
+
+    #[test]
+    fn every_byte_round_trips() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode(&encode(&bytes)).unwrap(), bytes);
+    }
+
+    #[test]
+    fn encoding_is_deterministic() {
+        assert_eq!(encode(&[0, 1, 255]), "acid-acorn-willow");
+        assert_eq!(encode(&[0, 1, 255]), encode(&[0, 1, 255]));
+    }
+
+    #[test]
+    fn decode_accepts_typed_input() {
+        assert_eq!(decode("  Acid acorn-WILLOW ").unwrap(), [0, 1, 255]);
+    }
+
+    #[test]
+    fn decode_rejects_unknown_and_empty_codes() {
+        assert_eq!(
+            decode("acid-banana"),
+            Err(WordCodeError::UnknownWord("banana".into()))
+        );
+        assert_eq!(decode(" - "), Err(WordCodeError::Empty));
+    }
+}
@@ -0,0 +1,116 @@
+//! Seeding users, guilds and channels.
+
+use axum::http::StatusCode;
+use openconv_server::jwt::JwtService;
+use openconv_server::redis::RedisPool;
+use openconv_shared::ids::{DeviceId, UserId};
+use tower::ServiceExt;
+
+use crate::request::{authed_post, body_json};
+
+/// Insert a user with one device and return an access token for it.
+pub async fn seed_user(
+    pool: &sqlx::PgPool,
+    jwt: &JwtService,
+    display_name: &str,
+    email: &str,
+) -> (UserId, DeviceId, String) {
+    let user_id = UserId::new();
+    let device_id = DeviceId::new();
+
+    sqlx::query(
+        "INSERT INTO users (id, public_key, email, display_name, email_verified_at) \
+         VALUES ($1, $2, $3, $4, NOW())",
+    )
+    .bind(user_id.0)
+    .bind(format!("pk_{}", uuid::Uuid::new_v4()))
+    .bind(email)
+    .bind(display_name)
+    .execute(pool)
+    .await
+    .unwrap();
+
+    sqlx::query(
+        "INSERT INTO devices (id, user_id, device_name, last_active, created_at) VALUES ($1, $2, $3, NOW(), NOW())",
+    )
+    .bind(device_id.0)
+    .bind(user_id.0)
+    .bind("Test Device")
+    .execute(pool)
+    .await
+    .unwrap();
+
+    let access_token = jwt.issue_access_token(&user_id, &device_id).unwrap();
+    (user_id, device_id, access_token)
+}
+
+/// Insert a user with a random email and one device. Returns the user and
+/// an access token.
+pub async fn seed_test_user(pool: &sqlx::PgPool, jwt: &JwtService) -> (UserId, String) {
+    let email = format!("{}@example.com", uuid::Uuid::new_v4());
+    let (user_id, _, token) = seed_user(pool, jwt, "Test User", &email).await;
+    (user_id, token)
+}
+
+/// Create a guild via the API and return the response JSON.
+pub async fn create_guild_via_api(
+    app: &axum::Router,
+    token: &str,
+    name: &str,
+) -> serde_json::Value {
+    let req = authed_post("/api/guilds", token, serde_json::json!({ "name": name }));
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    body_json(resp).await
+}
+
+/// Add a user as a guild member with the "member" role.
+pub async fn add_member(pool: &sqlx::PgPool, user_id: UserId, guild_uuid: uuid::Uuid) {
+    sqlx::query("INSERT INTO guild_members (user_id, guild_id) VALUES ($1, $2)")
+        .bind(user_id.0)
+        .bind(guild_uuid)
+        .execute(pool)
+        .await
+        .unwrap();
+
+    let member_role_id: uuid::Uuid =
+        sqlx::query_scalar("SELECT id FROM roles WHERE guild_id = $1 AND role_type = 'member'")
+            .bind(guild_uuid)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+
+    sqlx::query("INSERT INTO guild_member_roles (user_id, guild_id, role_id) VALUES ($1, $2, $3)")
+        .bind(user_id.0)
+        .bind(guild_uuid)
+        .bind(member_role_id)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+/// Create a text channel via the API and return the response JSON.
+pub async fn create_channel_via_api(
+    app: &axum::Router,
+    token: &str,
+    guild_id: &str,
+    name: &str,
+) -> serde_json::Value {
+    let req = authed_post(
+        &format!("/api/guilds/{guild_id}/channels"),
+        token,
+        serde_json::json!({ "name": name, "channel_type": "text" }),
+    );
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    body_json(resp).await
+}
+
+/// Delete keys a test left in Redis, such as challenges or per-email
+/// counters. `keys` are given without the app's prefix.
+pub async fn cleanup_redis_keys(redis: &RedisPool, keys: &[&str]) {
+    use fred::interfaces::KeysInterface;
+    for key in keys {
+        let _: i64 = redis.del(redis.key(key)).await.unwrap_or_default();
+    }
+}
@@ -0,0 +1,110 @@
+//! Request builders and response helpers.
+//!
+//! Requests carry no client address; [`crate::TestApp`] fills one in.
+
+use axum::body::Body;
+use axum::http::{header, Method, Request, Response};
+use serde::Serialize;
+
+/// A request to the app under test.
+pub struct TestRequest {
+    builder: axum::http::request::Builder,
+    body: Body,
+}
+
+impl TestRequest {
+    pub fn new(method: Method, uri: &str) -> Self {
+        Self {
+            builder: Request::builder().method(method).uri(uri),
+            body: Body::empty(),
+        }
+    }
+
+    pub fn get(uri: &str) -> Self {
+        Self::new(Method::GET, uri)
+    }
+
+    pub fn post(uri: &str) -> Self {
+        Self::new(Method::POST, uri)
+    }
+
+    pub fn put(uri: &str) -> Self {
+        Self::new(Method::PUT, uri)
+    }
+
+    pub fn patch(uri: &str) -> Self {
+        Self::new(Method::PATCH, uri)
+    }
+
+    pub fn delete(uri: &str) -> Self {
+        Self::new(Method::DELETE, uri)
+    }
+
+    /// Authenticate with an access token.
+    pub fn token(self, token: &str) -> Self {
+        self.header(header::AUTHORIZATION.as_str(), &format!("Bearer {token}"))
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.builder = self.builder.header(name, value);
+        self
+    }
+
+    /// Send `body` as JSON.
+    pub fn json(mut self, body: &impl Serialize) -> Self {
+        self.builder = self
+            .builder
+            .header(header::CONTENT_TYPE, "application/json");
+        self.body = Body::from(serde_json::to_vec(body).unwrap());
+        self
+    }
+
+    /// Send `body` as is; set its content type with [`Self::header`].
+    pub fn body(mut self, body: impl Into<Body>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn build(self) -> Request<Body> {
+        self.builder.body(self.body).unwrap()
+    }
+}
+
+impl From<TestRequest> for Request<Body> {
+    fn from(request: TestRequest) -> Self {
+        request.build()
+    }
+}
+
+/// Unauthenticated JSON POST.
+pub fn json_request(uri: &str, body: serde_json::Value) -> Request<Body> {
+    TestRequest::post(uri).json(&body).build()
+}
+
+pub fn authed_get(uri: &str, token: &str) -> Request<Body> {
+    TestRequest::get(uri).token(token).build()
+}
+
+pub fn authed_post(uri: &str, token: &str, body: serde_json::Value) -> Request<Body> {
+    TestRequest::post(uri).token(token).json(&body).build()
+}
+
+pub fn authed_put(uri: &str, token: &str, body: serde_json::Value) -> Request<Body> {
+    TestRequest::put(uri).token(token).json(&body).build()
+}
+
+pub fn authed_patch(uri: &str, token: &str, body: serde_json::Value) -> Request<Body> {
+    TestRequest::patch(uri).token(token).json(&body).build()
+}
+
+pub fn authed_delete(uri: &str, token: &str) -> Request<Body> {
+    TestRequest::delete(uri).token(token).build()
+}
+
+/// Read a response body as JSON.
+pub async fn body_json(response: Response<Body>) -> serde_json::Value {
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&body).unwrap()
+}